@@ -11,7 +11,7 @@ _alacritty()
     cur="${COMP_WORDS[COMP_CWORD]}"
     prev="${COMP_WORDS[COMP_CWORD-1]}"
     prevprev="${COMP_WORDS[COMP_CWORD-2]}"
-    opts="-h --help -V --version --live-config-reload --no-live-config-reload --print-events -q -qq -v -vv -vvv --ref-test -e --command --config-file -d --dimensions -t --title --working-directory"
+    opts="-h --help -V --version --live-config-reload --no-live-config-reload --print-config --strict-config --print-events --persistent-logging -q -qq -v -vv -vvv --ref-test --benchmark -e --command --config-file -d --dimensions -t --title --working-directory"
 
     # If `--command` or `-e` is used, stop completing
     for i in "${!COMP_WORDS[@]}"; do
@@ -34,7 +34,7 @@ _alacritty()
             # Complete all commands in $PATH
             COMPREPLY=( $(compgen -c -- "${cur}") )
             return 0;;
-        --config-file)
+        --config-file | --benchmark)
             # Path based completion
             local IFS=$'\n'
             compopt -o filenames
@@ -29,10 +29,22 @@ complete -c alacritty \
   -d "Start shell in specified directory"
 
 # Output
+complete \
+  -c alacritty \
+  -l "print-config" \
+  -d "Print the used configuration and exit"
+complete \
+  -c alacritty \
+  -l "strict-config" \
+  -d "Refuse to load a configuration file with invalid or unknown fields"
 complete \
   -c alacritty \
   -l "print-events" \
   -d "Print all events to stdout"
+complete \
+  -c alacritty \
+  -l "persistent-logging" \
+  -d "Keep the log file after quitting Alacritty"
 complete \
   -c alacritty \
   -s "q" \
@@ -58,12 +70,16 @@ complete \
   -c alacritty \
   -l "ref-test" \
   -d "Generates ref test"
+complete -c alacritty \
+  -f \
+  -l "benchmark" \
+  -d "Replay a recording and print performance statistics"
 
 complete \
   -c alacritty \
   -s "d" \
   -l "dimensions" \
-  -d "Window dimensions <columns> <lines>"
+  -d "Window dimensions <columns>x<lines> or <width>x<height>px"
 
 complete \
   -c alacritty \
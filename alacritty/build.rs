@@ -24,13 +24,17 @@ extern crate zip;
 use tempdir::TempDir;
 
 extern crate gl_generator;
+extern crate image;
 
 use gl_generator::{Api, Fallbacks, GlobalGenerator, Profile, Registry};
 
 use std::env;
 use std::fs::File;
 use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use std::io::Write;
 #[cfg(windows)]
 use std::io;
 #[cfg(windows)]
@@ -52,6 +56,13 @@ fn main() {
     ).write_bindings(GlobalGenerator, &mut file)
         .unwrap();
 
+    emit_build_info();
+
+    // The window icon is only used by the window
+    if env::var_os("CARGO_FEATURE_GUI").is_some() {
+        emit_window_icon(Path::new(&dest));
+    }
+
     #[cfg(windows)]
     {
         embed_resource::compile("assets/windows/windows.rc");
@@ -64,6 +75,79 @@ fn main() {
     }
 }
 
+/// Pass information about the build to the compiler as environment variables
+///
+/// Builds from a tarball don't have a git repository, so the git information
+/// falls back to "unknown".
+fn emit_build_info() {
+    const FEATURE_PREFIX: &str = "CARGO_FEATURE_";
+
+    let describe = git(&["describe", "--tags", "--always", "--dirty"]);
+    let commit = git(&["rev-parse", "HEAD"]);
+
+    let mut features = env::vars()
+        .filter(|&(ref key, _)| key.starts_with(FEATURE_PREFIX))
+        .map(|(key, _)| key[FEATURE_PREFIX.len()..].to_lowercase().replace('_', "-"))
+        .collect::<Vec<_>>();
+    features.sort();
+
+    println!("cargo:rustc-env=ALACRITTY_GIT_DESCRIBE={}", describe);
+    println!("cargo:rustc-env=ALACRITTY_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=ALACRITTY_BUILD_DATE={}", build_date());
+    println!("cargo:rustc-env=ALACRITTY_BUILD_PROFILE={}", env::var("PROFILE").unwrap());
+    println!("cargo:rustc-env=ALACRITTY_FEATURES={}", features.join(","));
+}
+
+/// Sizes of the window icon in pixels, for the X11 `_NET_WM_ICON`
+const WINDOW_ICON_SIZES: [u32; 3] = [16, 32, 64];
+
+/// Scale the logo to the sizes of the window icon
+///
+/// Each size is written to `window_icon_SIZE.rgba` in the output directory,
+/// as RGBA pixels without a header.
+fn emit_window_icon(dest: &Path) {
+    let logo = image::open("assets/alacritty.png").expect("load assets/alacritty.png");
+
+    for &size in &WINDOW_ICON_SIZES {
+        let icon = logo.resize_exact(size, size, image::FilterType::Lanczos3).to_rgba();
+        let path = dest.join(format!("window_icon_{}.rgba", size));
+        File::create(&path)
+            .and_then(|mut file| file.write_all(&icon.into_raw()))
+            .expect("write window icon");
+    }
+}
+
+fn git(args: &[&str]) -> String {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|output| output.trim().to_owned())
+        .filter(|output| !output.is_empty())
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+/// Current UTC date in the `YYYY-MM-DD` format
+fn build_date() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+
+    // Convert days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = secs / 86_400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
 #[cfg(windows)]
 fn aquire_winpty_agent(out_path: &Path) {
     let tmp_dir = TempDir::new("alacritty_build").unwrap();
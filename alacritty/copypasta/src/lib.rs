@@ -9,6 +9,11 @@ extern crate objc;
 
 #[cfg(windows)]
 extern crate clipboard;
+#[cfg(windows)]
+extern crate winapi;
+
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+extern crate x11_dl;
 
 /// An enumeration describing available clipboard buffers
 pub enum Buffer {
@@ -60,6 +65,15 @@ pub trait Store: Load {
     where
         S: Into<String>;
 
+    /// Sets the primary clipboard contents to HTML
+    ///
+    /// Clipboards which can hold multiple types offer the `plain` text as an
+    /// alternative. By default, only the plain text is stored.
+    fn store_primary_html(&mut self, html: String, plain: String) -> Result<(), Self::Err> {
+        let _ = html;
+        self.store_primary(plain)
+    }
+
     /// Store into the specified `buffer`.
     fn store<S>(&mut self, contents: S, buffer: Buffer) -> Result<(), Self::Err>
     where
@@ -72,10 +86,12 @@ pub trait Store: Load {
     }
 }
 
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+mod unix;
 #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
 mod x11;
 #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
-pub use x11::{Clipboard, Error};
+pub use unix::{Clipboard, Error};
 
 #[cfg(target_os = "macos")]
 mod macos;
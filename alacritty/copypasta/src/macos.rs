@@ -216,6 +216,29 @@ mod ns {
             Ok(Pasteboard(id))
         }
 
+        /// Replace the contents with HTML and its plain text alternative
+        pub fn write_html(&mut self, html: &str, plain: &str) -> Result<(), WriteStringError> {
+            let html_type = NSString::from_str("public.html");
+            let string_type = NSString::from_str("public.utf8-plain-text");
+            let html = NSString::from_str(html);
+            let plain = NSString::from_str(plain);
+
+            self.clear_contents();
+
+            // The setString:forType: method returns true in case of success
+            let ok: bool = unsafe {
+                let html_ok: bool = msg_send![self.0, setString:&*html forType:&*html_type];
+                let plain_ok: bool = msg_send![self.0, setString:&*plain forType:&*string_type];
+                html_ok && plain_ok
+            };
+
+            if ok {
+                Ok(())
+            } else {
+                Err(WriteStringError)
+            }
+        }
+
         /// Clears the existing contents of the pasteboard, preparing it for new
         /// contents.
         ///
@@ -322,6 +345,11 @@ impl super::Store for Clipboard {
         // No such thing on macOS
         Ok(())
     }
+
+    fn store_primary_html(&mut self, html: String, plain: String) -> Result<(), Self::Err> {
+        self.0.write_html(&html, &plain)
+            .map_err(::std::convert::From::from)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,261 @@
+//! X11 and Wayland Clipboard implementation
+//!
+//! Note that this implementation is really crap right now - we just depend
+//! on xclip or wl-clipboard being on the user's path. If pasting doesn't work,
+//! it's probably because the tool for the current display server is
+//! unavailable. There's currently no non-GPL x11 clipboard library for Rust.
+//! Until then, we have this hack.
+//!
+//! The display server is picked the same way winit picks the backend of the
+//! window, so the clipboard of the window's display server is used.
+//!
+//! Since both programs serve a single type, HTML is served by the `x11` module
+//! on X11. wl-copy can't offer a plain text alternative, so only the plain text
+//! is stored on Wayland.
+//!
+//! FIXME: Implement actual X11 clipboard API using the ICCCM reference
+//!        https://tronche.com/gui/x/icccm/
+use std::env;
+use std::io;
+use std::process::{Output, Command, Stdio};
+use std::string::FromUtf8Error;
+use std::ffi::OsStr;
+
+use super::{Load, Store};
+use x11;
+
+/// Display server the clipboard belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Backend {
+    X11,
+    Wayland,
+}
+
+impl Backend {
+    /// Pick the backend winit uses for creating windows
+    fn detect() -> Backend {
+        match env::var("WINIT_UNIX_BACKEND").as_ref().map(|backend| backend.as_str()) {
+            Ok("x11") => Backend::X11,
+            Ok("wayland") => Backend::Wayland,
+            _ if env::var_os("WAYLAND_DISPLAY").is_some() => Backend::Wayland,
+            _ => Backend::X11,
+        }
+    }
+
+    /// Program used for accessing the clipboard
+    fn program(self, store: bool) -> &'static str {
+        match (self, store) {
+            (Backend::X11, _) => "xclip",
+            (Backend::Wayland, false) => "wl-paste",
+            (Backend::Wayland, true) => "wl-copy",
+        }
+    }
+}
+
+/// The x11 and wayland clipboard
+pub struct Clipboard {
+    backend: Backend,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(&'static str, io::Error),
+    Xclip(String),
+    Utf8(FromUtf8Error),
+}
+
+impl ::std::error::Error for Error {
+    fn cause(&self) -> Option<&::std::error::Error> {
+        match *self {
+            Error::Io(_, ref err) => Some(err),
+            Error::Utf8(ref err) => Some(err),
+            _ => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(..) => "error calling clipboard program",
+            Error::Xclip(..) => "error reported by clipboard program",
+            Error::Utf8(..) => "clipboard contents not utf8",
+        }
+    }
+}
+
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            Error::Io(program, ref err) => {
+                match err.kind() {
+                    io::ErrorKind::NotFound => {
+                        let package = if program == "xclip" { program } else { "wl-clipboard" };
+                        write!(f, "Please install `{}` to enable clipboard support", package)
+                    },
+                    _ => write!(f, "error calling {}: {}", program, err),
+                }
+            },
+            Error::Xclip(ref s) => write!(f, "error from clipboard program: {}", s),
+            Error::Utf8(ref err) => write!(f, "error parsing clipboard contents: {}", err),
+        }
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(val: FromUtf8Error) -> Error {
+        Error::Utf8(val)
+    }
+}
+
+impl Load for Clipboard {
+    type Err = Error;
+
+    fn new() -> Result<Self, Error> {
+        Ok(Clipboard { backend: Backend::detect() })
+    }
+
+    fn load_primary(&self) -> Result<String, Self::Err> {
+        match self.backend {
+            Backend::X11 => self.load(&["-o", "-selection", "clipboard"]),
+            Backend::Wayland => self.load(&["--no-newline"]),
+        }
+    }
+
+    fn load_selection(&self) -> Result<String, Self::Err> {
+        match self.backend {
+            Backend::X11 => self.load(&["-o"]),
+            Backend::Wayland => self.load(&["--no-newline", "--primary"]),
+        }
+    }
+}
+
+impl Store for Clipboard {
+    /// Sets the primary clipboard contents
+    #[inline]
+    fn store_primary<S>(&mut self, contents: S) -> Result<(), Self::Err>
+        where S: Into<String>
+    {
+        match self.backend {
+            Backend::X11 => self.store(contents, &["-i", "-selection", "clipboard"]),
+            Backend::Wayland => self.store::<_, &str>(contents, &[]),
+        }
+    }
+
+    /// Sets the secondary clipboard contents
+    #[inline]
+    fn store_selection<S>(&mut self, contents: S) -> Result<(), Self::Err>
+        where S: Into<String>
+    {
+        match self.backend {
+            Backend::X11 => self.store(contents, &["-i"]),
+            Backend::Wayland => self.store(contents, &["--primary"]),
+        }
+    }
+
+    /// Sets the primary clipboard contents to HTML
+    ///
+    /// Applications which don't accept HTML get the plain text instead.
+    fn store_primary_html(&mut self, html: String, plain: String) -> Result<(), Self::Err> {
+        match self.backend {
+            Backend::X11 => x11::serve_html(html, plain).map_err(Error::Xclip),
+            Backend::Wayland => self.store_primary(plain),
+        }
+    }
+}
+
+/// Check if wl-paste failed because nothing has been copied yet
+///
+/// It reports the same error when the selection has no text, there's nothing
+/// to paste in both cases.
+fn is_empty_selection(message: &str) -> bool {
+    message.starts_with("Nothing is copied") || message.starts_with("No suitable type of content")
+}
+
+impl Clipboard {
+    fn load<S: AsRef<OsStr>>(&self, args: &[S]) -> Result<String, Error> {
+        let program = self.backend.program(false);
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| Error::Io(program, err))?;
+
+        self.process_output(output)
+    }
+
+    fn process_output(&self, output: Output) -> Result<String, Error> {
+        if output.status.success() {
+            String::from_utf8(output.stdout)
+                .map_err(::std::convert::From::from)
+        } else if self.backend == Backend::Wayland {
+            let message = String::from_utf8(output.stderr)?;
+            if is_empty_selection(&message) {
+                Ok(String::new())
+            } else {
+                Err(Error::Xclip(message.trim().to_owned()))
+            }
+        } else {
+            String::from_utf8(output.stderr)
+                .map_err(::std::convert::From::from)
+        }
+    }
+
+    fn store<C, S>(&mut self, contents: C, args: &[S]) -> Result<(), Error>
+        where C: Into<String>,
+              S: AsRef<OsStr>,
+    {
+        use std::io::Write;
+
+        let program = self.backend.program(true);
+        let contents = contents.into();
+
+        // wl-copy keeps serving the selection from the background until it's
+        // replaced, so its output must not be tied to ours
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| Error::Io(program, err))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(contents.as_bytes()).map_err(|err| Error::Io(program, err))?;
+        }
+
+        // Return error if didn't exit cleanly
+        let exit_status = child.wait().map_err(|err| Error::Io(program, err))?;
+        if exit_status.success() {
+            Ok(())
+        } else {
+            Err(Error::Xclip(format!("{} returned non-zero exit code", program)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_empty_selection, Clipboard};
+    use ::{Load, Store};
+
+    #[test]
+    fn empty_wayland_selection() {
+        assert!(is_empty_selection("Nothing is copied\n"));
+        assert!(is_empty_selection("No suitable type of content copied\n"));
+        assert!(!is_empty_selection("Failed to connect to a Wayland server\n"));
+    }
+
+    #[test]
+    fn clipboard_works() {
+        let mut clipboard = Clipboard::new().expect("create clipboard");
+        let arst = "arst";
+        let oien = "oien";
+        clipboard.store_primary(arst).expect("store selection");
+        clipboard.store_selection(oien).expect("store selection");
+
+        let selection = clipboard.load_selection().expect("load selection");
+        let primary = clipboard.load_primary().expect("load selection");
+
+        assert_eq!(arst, primary);
+        assert_eq!(oien, selection);
+    }
+}
@@ -1,5 +1,12 @@
+use std::{io, ptr};
+
 use clipboard::ClipboardContext;
 use clipboard::ClipboardProvider;
+use winapi::um::winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+use winapi::um::winuser::{
+    CloseClipboard, EmptyClipboard, OpenClipboard, RegisterClipboardFormatW, SetClipboardData,
+    CF_UNICODETEXT,
+};
 
 use super::{Load, Store};
 
@@ -57,7 +64,7 @@ impl Store for Clipboard {
         S: Into<String>,
     {
         self.0
-            .set_contents(contents.into())
+            .set_contents(crlf(contents.into()))
             .map_err(Error::Clipboard)
     }
 
@@ -68,7 +75,117 @@ impl Store for Clipboard {
         S: Into<String>,
     {
         self.0
-            .set_contents(contents.into())
+            .set_contents(crlf(contents.into()))
             .map_err(Error::Clipboard)
     }
+
+    /// Sets the primary clipboard contents to HTML
+    ///
+    /// The HTML is stored in the `HTML Format` next to the plain text.
+    fn store_primary_html(&mut self, html: String, plain: String) -> Result<(), Self::Err> {
+        store_html(&cf_html(&html), &crlf(plain)).map_err(|err| Error::Clipboard(Box::new(err)))
+    }
+}
+
+/// Replace the clipboard contents with CF_HTML and its plain text alternative
+fn store_html(html: &str, plain: &str) -> io::Result<()> {
+    let name: Vec<u16> = "HTML Format\0".encode_utf16().collect();
+    let mut html = html.as_bytes().to_vec();
+    html.push(0);
+    let plain: Vec<u8> = plain.encode_utf16()
+        .chain(Some(0))
+        .flat_map(|unit| vec![unit as u8, (unit >> 8) as u8])
+        .collect();
+
+    unsafe {
+        let format = RegisterClipboardFormatW(name.as_ptr());
+        if format == 0 || OpenClipboard(ptr::null_mut()) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let result = if EmptyClipboard() == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            set_clipboard_data(format, &html)
+                .and_then(|_| set_clipboard_data(CF_UNICODETEXT, &plain))
+        };
+        CloseClipboard();
+
+        result
+    }
+}
+
+/// Hand a copy of `data` over to the opened clipboard
+unsafe fn set_clipboard_data(format: u32, data: &[u8]) -> io::Result<()> {
+    let handle = GlobalAlloc(GMEM_MOVEABLE, data.len());
+    if handle.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let locked = GlobalLock(handle) as *mut u8;
+    if locked.is_null() {
+        GlobalFree(handle);
+        return Err(io::Error::last_os_error());
+    }
+    ptr::copy_nonoverlapping(data.as_ptr(), locked, data.len());
+    GlobalUnlock(handle);
+
+    // The clipboard owns the memory once it's set
+    if SetClipboardData(format, handle).is_null() {
+        GlobalFree(handle);
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Wrap an HTML fragment into a CF_HTML document
+///
+/// Its header holds the byte offsets of the document and of the fragment.
+fn cf_html(fragment: &str) -> String {
+    const PREFIX: &str = "<html><body><!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment--></body></html>";
+
+    let header = |start_html: usize, end_html: usize, start_fragment: usize, end_fragment: usize| {
+        format!(
+            "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\n\
+             StartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+            start_html, end_html, start_fragment, end_fragment,
+        )
+    };
+
+    let start_html = header(0, 0, 0, 0).len();
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + SUFFIX.len();
+
+    let mut document = header(start_html, end_html, start_fragment, end_fragment);
+    document.push_str(PREFIX);
+    document.push_str(fragment);
+    document.push_str(SUFFIX);
+    document
+}
+
+/// Convert line breaks to the `\r\n` expected by other Windows applications
+fn crlf(contents: String) -> String {
+    contents.replace("\r\n", "\n").replace('\n', "\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cf_html;
+
+    #[test]
+    fn cf_html_offsets() {
+        let document = cf_html("<pre>a</pre>");
+
+        let offset = |name: &str| -> usize {
+            let start = document.find(name).unwrap() + name.len() + 1;
+            document[start..start + 10].parse().unwrap()
+        };
+
+        assert!(document[offset("StartHTML")..].starts_with("<html>"));
+        assert_eq!(offset("EndHTML"), document.len());
+        assert_eq!(&document[offset("StartFragment")..offset("EndFragment")], "<pre>a</pre>");
+    }
 }
@@ -1,173 +1,177 @@
-//! X11 Clipboard implementation
+//! Owner of the X11 clipboard serving multiple targets
 //!
-//! Note that the x11 implementation is really crap right now - we just depend
-//! on xclip being on the user's path. If x11 pasting doesn't work, it's
-//! probably because xclip is unavailable. There's currently no non-GPL x11
-//! clipboard library for Rust. Until then, we have this hack.
+//! xclip only serves a single target, so text which is offered both as HTML
+//! and as plain text is served by a thread of our own instead. It owns the
+//! clipboard until another client takes it over.
 //!
-//! FIXME: Implement actual X11 clipboard API using the ICCCM reference
-//!        https://tronche.com/gui/x/icccm/
-use std::io;
-use std::process::{Output, Command};
-use std::string::FromUtf8Error;
-use std::ffi::OsStr;
-
-use super::{Load, Store};
-
-/// The x11 clipboard
-pub struct Clipboard;
-
-#[derive(Debug)]
-pub enum Error {
-    Io(io::Error),
-    Xclip(String),
-    Utf8(FromUtf8Error),
+//! The contents are sent in a single property, so they're limited to the
+//! maximum request size of the server; the incremental transfer protocol isn't
+//! implemented.
+use std::ffi::CString;
+use std::os::raw::{c_int, c_uchar};
+use std::{mem, ptr, thread};
+
+use x11_dl::xlib::{self, Atom, Display, Xlib};
+
+/// Bytes of a request which aren't available for the property data
+const REQUEST_OVERHEAD: usize = 1024;
+
+/// Clipboard contents and the connection they're served over
+struct Owner {
+    xlib: Xlib,
+    display: *mut Display,
+    window: xlib::Window,
+
+    targets: Atom,
+    utf8_string: Atom,
+    text_plain: Atom,
+    text_html: Atom,
+
+    html: String,
+    plain: String,
 }
 
-impl ::std::error::Error for Error {
-    fn cause(&self) -> Option<&::std::error::Error> {
-        match *self {
-            Error::Io(ref err) => Some(err),
-            Error::Utf8(ref err) => Some(err),
-            _ => None,
-        }
-    }
+// The display connection is only used by the serving thread once it's handed over
+unsafe impl Send for Owner {}
 
-    fn description(&self) -> &str {
-        match *self {
-            Error::Io(..) => "error calling xclip",
-            Error::Xclip(..) => "error reported by xclip",
-            Error::Utf8(..) => "clipboard contents not utf8",
-        }
-    }
-}
+/// Take over the clipboard and serve `html` next to its `plain` text alternative
+pub fn serve_html(html: String, plain: String) -> Result<(), String> {
+    let xlib = Xlib::open().map_err(|err| err.to_string())?;
 
-impl ::std::fmt::Display for Error {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-        match *self {
-            Error::Io(ref err) => {
-                match err.kind() {
-                    io::ErrorKind::NotFound => {
-                        write!(f, "Please install `xclip` to enable clipboard support")
-                    },
-                    _ => write!(f, "error calling xclip: {}", err),
-                }
-            },
-            Error::Xclip(ref s) => write!(f, "error from xclip: {}", s),
-            Error::Utf8(ref err) => write!(f, "error parsing xclip output: {}", err),
+    unsafe {
+        let display = (xlib.XOpenDisplay)(ptr::null());
+        if display.is_null() {
+            return Err("unable to connect to the X server".into());
         }
-    }
-}
-
-impl From<io::Error> for Error {
-    fn from(val: io::Error) -> Error {
-        Error::Io(val)
-    }
-}
-
-impl From<FromUtf8Error> for Error {
-    fn from(val: FromUtf8Error) -> Error {
-        Error::Utf8(val)
-    }
-}
-
-impl Load for Clipboard {
-    type Err = Error;
-
-    fn new() -> Result<Self, Error> {
-        Ok(Clipboard)
-    }
 
-    fn load_primary(&self) -> Result<String, Self::Err> {
-        let output = Command::new("xclip")
-            .args(&["-o", "-selection", "clipboard"])
-            .output()?;
-
-        Clipboard::process_xclip_output(output)
-    }
+        let max_size = (xlib.XExtendedMaxRequestSize)(display)
+            .max((xlib.XMaxRequestSize)(display));
+        let max_len = (max_size as usize * 4).saturating_sub(REQUEST_OVERHEAD);
+        if html.len() > max_len || plain.len() > max_len {
+            (xlib.XCloseDisplay)(display);
+            return Err("selection is too large for the clipboard".into());
+        }
 
-    fn load_selection(&self) -> Result<String, Self::Err> {
-        let output = Command::new("xclip")
-            .args(&["-o"])
-            .output()?;
+        let (clipboard, targets, utf8_string, text_plain, text_html) = {
+            let intern = |name: &str| {
+                let name = CString::new(name).unwrap();
+                (xlib.XInternAtom)(display, name.as_ptr(), xlib::False)
+            };
+            (
+                intern("CLIPBOARD"),
+                intern("TARGETS"),
+                intern("UTF8_STRING"),
+                intern("text/plain;charset=utf-8"),
+                intern("text/html"),
+            )
+        };
+
+        let root = (xlib.XDefaultRootWindow)(display);
+        let window = (xlib.XCreateSimpleWindow)(display, root, 0, 0, 1, 1, 0, 0, 0);
+        (xlib.XSetSelectionOwner)(display, clipboard, window, xlib::CurrentTime);
+        if (xlib.XGetSelectionOwner)(display, clipboard) != window {
+            (xlib.XDestroyWindow)(display, window);
+            (xlib.XCloseDisplay)(display);
+            return Err("unable to take over the clipboard".into());
+        }
 
-        Clipboard::process_xclip_output(output)
+        let owner = Owner {
+            xlib,
+            display,
+            window,
+            targets,
+            utf8_string,
+            text_plain,
+            text_html,
+            html,
+            plain,
+        };
+
+        thread::Builder::new()
+            .name("clipboard owner".into())
+            .spawn(move || owner.run())
+            .map(|_| ())
+            .map_err(|err| err.to_string())
     }
 }
 
-impl Store for Clipboard {
-    /// Sets the primary clipboard contents
-    #[inline]
-    fn store_primary<S>(&mut self, contents: S) -> Result<(), Self::Err>
-        where S: Into<String>
-    {
-        self.store(contents, &["-i", "-selection", "clipboard"])
-    }
-
-    /// Sets the secondary clipboard contents
-    #[inline]
-    fn store_selection<S>(&mut self, contents: S) -> Result<(), Self::Err>
-        where S: Into<String>
-    {
-        self.store(contents, &["-i"])
-    }
-}
+impl Owner {
+    /// Answer requests until another client owns the clipboard
+    fn run(self) {
+        unsafe {
+            loop {
+                let mut event: xlib::XEvent = mem::zeroed();
+                (self.xlib.XNextEvent)(self.display, &mut event);
+
+                match event.get_type() {
+                    xlib::SelectionClear => break,
+                    xlib::SelectionRequest => self.reply(&event.selection_request),
+                    _ => (),
+                }
+            }
 
-impl Clipboard {
-    fn process_xclip_output(output: Output) -> Result<String, Error> {
-        if output.status.success() {
-            String::from_utf8(output.stdout)
-                .map_err(::std::convert::From::from)
-        } else {
-            String::from_utf8(output.stderr)
-                .map_err(::std::convert::From::from)
+            (self.xlib.XDestroyWindow)(self.display, self.window);
+            (self.xlib.XCloseDisplay)(self.display);
         }
     }
 
-    fn store<C, S>(&mut self, contents: C, args: &[S]) -> Result<(), Error>
-        where C: Into<String>,
-              S: AsRef<OsStr>,
-    {
-        use std::io::Write;
-        use std::process::{Command, Stdio};
-
-        let contents = contents.into();
-        let mut child = Command::new("xclip")
-            .args(args)
-            .stdin(Stdio::piped())
-            .spawn()?;
-
-        if let Some(stdin) = child.stdin.as_mut() {
-            stdin.write_all(contents.as_bytes())?;
-        }
-
-        // Return error if didn't exit cleanly
-        let exit_status = child.wait()?;
-        if exit_status.success() {
-            Ok(())
+    /// Contents which are served for `target`
+    fn contents(&self, target: Atom) -> Option<&str> {
+        if target == self.text_html {
+            Some(&self.html)
+        } else if target == self.utf8_string || target == self.text_plain {
+            Some(&self.plain)
         } else {
-            Err(Error::Xclip("xclip returned non-zero exit code".into()))
+            None
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::Clipboard;
-    use ::{Load, Store};
-
-    #[test]
-    fn clipboard_works() {
-        let mut clipboard = Clipboard::new().expect("create clipboard");
-        let arst = "arst";
-        let oien = "oien";
-        clipboard.store_primary(arst).expect("store selection");
-        clipboard.store_selection(oien).expect("store selection");
-
-        let selection = clipboard.load_selection().expect("load selection");
-        let primary = clipboard.load_primary().expect("load selection");
-
-        assert_eq!(arst, primary);
-        assert_eq!(oien, selection);
+    unsafe fn reply(&self, request: &xlib::XSelectionRequestEvent) {
+        // Obsolete clients don't name a property, the target is used instead
+        let property = if request.property == 0 { request.target } else { request.property };
+
+        let served = if request.target == self.targets {
+            let targets = [self.targets, self.utf8_string, self.text_plain, self.text_html];
+            (self.xlib.XChangeProperty)(
+                self.display,
+                request.requestor,
+                property,
+                xlib::XA_ATOM,
+                32,
+                xlib::PropModeReplace,
+                targets.as_ptr() as *const c_uchar,
+                targets.len() as c_int,
+            );
+            true
+        } else if let Some(contents) = self.contents(request.target) {
+            (self.xlib.XChangeProperty)(
+                self.display,
+                request.requestor,
+                property,
+                request.target,
+                8,
+                xlib::PropModeReplace,
+                contents.as_ptr(),
+                contents.len() as c_int,
+            );
+            true
+        } else {
+            false
+        };
+
+        // A property of `None` refuses the request
+        let mut notify = xlib::XEvent::from(xlib::XSelectionEvent {
+            type_: xlib::SelectionNotify,
+            serial: 0,
+            send_event: xlib::True,
+            display: self.display,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if served { property } else { 0 },
+            time: request.time,
+        });
+        (self.xlib.XSendEvent)(self.display, request.requestor, xlib::False, 0, &mut notify);
+        (self.xlib.XFlush)(self.display);
     }
 }
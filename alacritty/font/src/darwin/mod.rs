@@ -40,7 +40,8 @@ use core_text::font_descriptor::SymbolicTraitAccessors;
 
 use euclid::{Point2D, Rect, Size2D};
 
-use super::{FontDesc, RasterizedGlyph, Metrics, FontKey, GlyphKey};
+use super::{FontDesc, RasterizedGlyph, Metrics, FontKey, GlyphKey, Feature, Rendering, Subpixel};
+use gsub::{self, Substitutions};
 
 pub mod byte_order;
 use self::byte_order::kCGBitmapByteOrder32Host;
@@ -83,6 +84,7 @@ pub struct Rasterizer {
     keys: HashMap<(FontDesc, Size), FontKey>,
     device_pixel_ratio: f32,
     use_thin_strokes: bool,
+    rendering: Rendering,
 }
 
 /// Errors occurring when using the core text rasterizer
@@ -128,13 +130,19 @@ impl ::std::fmt::Display for Error {
 impl ::Rasterize for Rasterizer {
     type Err = Error;
 
-    fn new(device_pixel_ratio: f32, use_thin_strokes: bool) -> Result<Rasterizer, Error> {
+    fn new(
+        device_pixel_ratio: f32,
+        use_thin_strokes: bool,
+        _: bool,
+        rendering: Rendering,
+    ) -> Result<Rasterizer, Error> {
         info!("device_pixel_ratio: {}", device_pixel_ratio);
         Ok(Rasterizer {
             fonts: HashMap::new(),
             keys: HashMap::new(),
             device_pixel_ratio,
             use_thin_strokes,
+            rendering,
         })
     }
 
@@ -188,6 +196,10 @@ impl ::Rasterize for Rasterizer {
     fn set_device_pixel_ratio(&mut self, dpr: f32) {
         self.device_pixel_ratio = dpr;
     }
+
+    fn set_rendering(&mut self, rendering: Rendering) {
+        self.rendering = rendering;
+    }
 }
 
 impl Rasterizer {
@@ -197,13 +209,14 @@ impl Rasterizer {
         style: &str,
         size: Size
     ) -> Result<Font, Error> {
-        let descriptors = descriptors_for_family(&desc.name[..]);
+        let descriptors = descriptors_for_family(::resolve_generic_family(&desc.name));
         for descriptor in descriptors {
             if descriptor.style_name == style {
                 // Found the font we want
+                descriptor.log_loading();
                 let scaled_size = f64::from(size.as_f32_pts()) * f64::from(self.device_pixel_ratio);
                 let font = descriptor.to_font(scaled_size, true);
-                return Ok(font);
+                return Ok(font.with_features(&desc.features));
             }
         }
 
@@ -227,12 +240,13 @@ impl Rasterizer {
         };
         let scaled_size = f64::from(size.as_f32_pts()) * f64::from(self.device_pixel_ratio);
 
-        let descriptors = descriptors_for_family(&desc.name[..]);
+        let descriptors = descriptors_for_family(::resolve_generic_family(&desc.name));
         for descriptor in descriptors {
             let font = descriptor.to_font(scaled_size, true);
             if font.is_bold() == bold && font.is_italic() == italic {
                 // Found the font we want
-                return Ok(font);
+                descriptor.log_loading();
+                return Ok(font.with_features(&desc.features));
             }
         }
 
@@ -255,7 +269,7 @@ impl Rasterizer {
         font: &Font,
     ) -> Option<Result<RasterizedGlyph, Error>> {
         let scaled_size = self.device_pixel_ratio * glyph.size.as_f32_pts();
-        font.get_glyph(glyph.c, f64::from(scaled_size), self.use_thin_strokes)
+        font.get_glyph(glyph.c, f64::from(scaled_size), self.use_thin_strokes, self.rendering)
             .map(|r| Some(Ok(r)))
             .unwrap_or_else(|e| match e {
                 Error::MissingGlyph(_) => None,
@@ -285,6 +299,7 @@ pub struct Font {
     ct_font: CTFont,
     cg_font: CGFont,
     fallbacks: Vec<Font>,
+    substitutions: Substitutions,
 }
 
 unsafe impl Send for Font {}
@@ -349,6 +364,10 @@ pub fn descriptors_for_family(family: &str) -> Vec<Descriptor> {
 }
 
 impl Descriptor {
+    fn log_loading(&self) {
+        info!("Loading font {} from {}", self.family_name, self.font_path.display());
+    }
+
     /// Create a Font from this descriptor
     pub fn to_font(&self, size: f64, load_fallbacks:bool) -> Font {
         let ct_font = ct_new_from_descriptor(&self.ct_descriptor, size);
@@ -385,7 +404,8 @@ impl Descriptor {
                     fallbacks.insert(0, Font {
                         cg_font: menlo.copy_to_CGFont(),
                         ct_font: menlo,
-                        fallbacks: Vec::new()
+                        fallbacks: Vec::new(),
+                        substitutions: Substitutions::default(),
                     });
 
                     fallbacks
@@ -399,11 +419,30 @@ impl Descriptor {
             ct_font,
             cg_font,
             fallbacks,
+            substitutions: Substitutions::default(),
         }
     }
 }
 
 impl Font {
+    /// Apply the glyph substitutions of OpenType features
+    fn with_features(mut self, features: &[Feature]) -> Font {
+        if features.is_empty() {
+            return self;
+        }
+
+        let tag = gsub::GSUB.iter().fold(0, |tag, &byte| tag << 8 | u32::from(byte));
+        self.substitutions = match self.ct_font.get_font_table(tag) {
+            Some(table) => Substitutions::new(table.bytes(), features),
+            None => {
+                warn!("Font has no GSUB table, ignoring font features");
+                Substitutions::default()
+            },
+        };
+
+        self
+    }
+
     /// The the bounding rect of a glyph
     pub fn bounding_rect_for_glyph(
         &self,
@@ -459,38 +498,18 @@ impl Font {
         }
     }
 
-    pub fn get_glyph(&self, character: char, _size: f64, use_thin_strokes: bool) -> Result<RasterizedGlyph, Error> {
-        // Render custom symbols for underline and beam cursor
-        match character {
-            super::UNDERLINE_CURSOR_CHAR => {
-                // Get the bottom of the bounding box
-                let descent = -(self.ct_font.descent() as i32);
-                // Get the width of the cell
-                let width = self.glyph_advance('0') as i32;
-                // Return the new custom glyph
-                return super::get_underline_cursor_glyph(descent, width);
-            }
-            super::BEAM_CURSOR_CHAR | super::BOX_CURSOR_CHAR => {
-                // Get the top of the bounding box
-                let metrics = self.metrics();
-                let height = metrics.line_height;
-                let ascent = (height - self.ct_font.descent()).ceil();
-
-                // Get the width of the cell
-                let width = self.glyph_advance('0') as i32;
-
-                // Return the new custom glyph
-                if character == super::BEAM_CURSOR_CHAR {
-                    return super::get_beam_cursor_glyph(ascent as i32, height as i32, width);
-                } else {
-                    return super::get_box_cursor_glyph(ascent as i32, height as i32, width);
-                }
-            }
-            _ => ()
-        }
-
+    /// CoreText doesn't hint glyphs, so only the antialiasing of `rendering`
+    /// is used
+    pub fn get_glyph(
+        &self,
+        character: char,
+        _size: f64,
+        use_thin_strokes: bool,
+        rendering: Rendering,
+    ) -> Result<RasterizedGlyph, Error> {
         let glyph_index = self.glyph_index(character)
             .ok_or_else(|| Error::MissingGlyph(character))?;
+        let glyph_index = self.substitutions.apply(glyph_index);
 
         let bounds = self.bounding_rect_for_glyph(Default::default(), glyph_index);
 
@@ -538,14 +557,18 @@ impl Font {
             cg_context.set_font_smoothing_style(16);
         }
 
+        // Font smoothing is the subpixel antialiasing of CoreText
+        let antialias = rendering.antialias.unwrap_or(true);
+        let subpixel = antialias && rendering.subpixel != Subpixel::None;
+
         cg_context.set_allows_font_smoothing(true);
-        cg_context.set_should_smooth_fonts(true);
+        cg_context.set_should_smooth_fonts(subpixel);
         cg_context.set_allows_font_subpixel_quantization(true);
         cg_context.set_should_subpixel_quantize_fonts(true);
         cg_context.set_allows_font_subpixel_positioning(true);
         cg_context.set_should_subpixel_position_fonts(true);
         cg_context.set_allows_antialiasing(true);
-        cg_context.set_should_antialias(true);
+        cg_context.set_should_antialias(antialias);
 
         // Set fill color to white for drawing the glyph
         cg_context.set_rgb_fill_color(1.0, 1.0, 1.0, 1.0);
@@ -560,7 +583,10 @@ impl Font {
 
         let rasterized_pixels = cg_context.data().to_vec();
 
-        let buf = extract_rgb(&rasterized_pixels);
+        let mut buf = extract_rgb(&rasterized_pixels);
+        if subpixel && rendering.subpixel == Subpixel::Bgr {
+            super::swap_red_and_blue(&mut buf);
+        }
 
         Ok(RasterizedGlyph {
             c: character,
@@ -625,7 +651,7 @@ mod tests {
         for font in fonts {
             // Get a glyph
             for c in &['a', 'b', 'c', 'd'] {
-                let glyph = font.get_glyph(*c, 72., false).unwrap();
+                let glyph = font.get_glyph(*c, 72., false, Default::default()).unwrap();
 
                 // Debug the glyph.. sigh
                 for row in 0..glyph.height {
@@ -215,6 +215,7 @@ impl From<isize> for Width {
 }
 
 /// Subpixel geometry
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Rgba {
     Unknown,
     Rgb,
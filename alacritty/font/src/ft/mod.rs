@@ -14,9 +14,9 @@
 //
 //! Rasterization powered by FreeType and FontConfig
 use std::collections::HashMap;
-use std::cmp::min;
-use std::path::PathBuf;
-use std::fmt;
+use std::cmp::{min, Ordering};
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, slice};
 
 use freetype::{self, Library};
 use libc::c_uint;
@@ -25,8 +25,18 @@ use libc::c_uint;
 pub mod fc;
 
 use super::{FontDesc, RasterizedGlyph, Metrics, Size, FontKey, GlyphKey, Weight, Slant, Style};
+use super::{Feature, Hinting, Rendering, Subpixel};
+use gsub::Substitutions;
 
-struct FixedSize {
+/// Largest difference in pixels between the requested size and an embedded
+/// bitmap strike of a scalable face, for the strike to be used
+const STRIKE_TOLERANCE: f64 = 1.;
+
+/// Embedded bitmap strike of a face
+#[derive(Debug)]
+struct Strike {
+    /// Index in the available sizes of the face
+    index: i32,
     pixelsize: f64,
 }
 
@@ -36,7 +46,54 @@ struct Face {
     load_flags: freetype::face::LoadFlag,
     render_mode: freetype::RenderMode,
     lcd_filter: c_uint,
-    non_scalable: Option<FixedSize>
+    /// Subpixels are in BGR order, FreeType renders them as RGB
+    bgr: bool,
+    strikes: Vec<Strike>,
+    /// Faces without outlines can only be rendered from their strikes
+    scalable: bool,
+    substitutions: Substitutions,
+}
+
+/// Strike which should be used instead of the outlines at `pixelsize`
+fn select_strike(
+    strikes: &[Strike],
+    scalable: bool,
+    pixelsize: f64,
+    use_embedded_bitmaps: bool,
+) -> Option<&Strike> {
+    let distance = |strike: &Strike| (strike.pixelsize - pixelsize).abs();
+    let closest = strikes.iter()
+        .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap_or(Ordering::Equal));
+
+    match closest {
+        Some(strike) if !scalable => Some(strike),
+        Some(strike) if use_embedded_bitmaps && distance(strike) <= STRIKE_TOLERANCE => {
+            Some(strike)
+        },
+        _ => None,
+    }
+}
+
+impl Face {
+    /// Select the strike or scale the outlines for `pixelsize`
+    ///
+    /// The size metrics of the face only describe the last selected size, so
+    /// this has to happen before reading metrics as well as before rendering.
+    fn set_size(&self, pixelsize: f64, use_embedded_bitmaps: bool) -> Result<(), Error> {
+        match select_strike(&self.strikes, self.scalable, pixelsize, use_embedded_bitmaps) {
+            Some(strike) => {
+                // `FT_Face` is a handle, selecting a size does not move the face
+                let ft_face = self.ft_face.raw() as *const _ as freetype::ffi::FT_Face;
+                let err = unsafe { freetype::ffi::FT_Select_Size(ft_face, strike.index) };
+                if err != freetype::ffi::FT_Err_Ok {
+                    return Err(freetype::Error::from(err).into());
+                }
+            },
+            None => self.ft_face.set_char_size(to_freetype_26_6(pixelsize as f32), 0, 0, 0)?,
+        }
+
+        Ok(())
+    }
 }
 
 impl fmt::Debug for Face {
@@ -54,6 +111,10 @@ impl fmt::Debug for Face {
                 freetype::RenderMode::Max => "Max",
             })
             .field("lcd_filter", &self.lcd_filter)
+            .field("bgr", &self.bgr)
+            .field("strikes", &self.strikes)
+            .field("scalable", &self.scalable)
+            .field("substitutions", &self.substitutions)
             .finish()
     }
 }
@@ -62,8 +123,11 @@ impl fmt::Debug for Face {
 pub struct FreeTypeRasterizer {
     faces: HashMap<FontKey, Face>,
     library: Library,
-    keys: HashMap<PathBuf, FontKey>,
+    /// Faces are loaded once for every set of features
+    keys: HashMap<(PathBuf, Vec<Feature>), FontKey>,
     device_pixel_ratio: f32,
+    use_embedded_bitmaps: bool,
+    rendering: Rendering,
 }
 
 #[inline]
@@ -74,7 +138,12 @@ fn to_freetype_26_6(f: f32) -> isize {
 impl ::Rasterize for FreeTypeRasterizer {
     type Err = Error;
 
-    fn new(device_pixel_ratio: f32, _: bool) -> Result<FreeTypeRasterizer, Error> {
+    fn new(
+        device_pixel_ratio: f32,
+        _: bool,
+        use_embedded_bitmaps: bool,
+        rendering: Rendering,
+    ) -> Result<FreeTypeRasterizer, Error> {
         let library = Library::init()?;
 
         Ok(FreeTypeRasterizer {
@@ -82,11 +151,13 @@ impl ::Rasterize for FreeTypeRasterizer {
             keys: HashMap::new(),
             library,
             device_pixel_ratio,
+            use_embedded_bitmaps,
+            rendering,
         })
     }
 
-    fn metrics(&self, key: FontKey, _size: Size) -> Result<Metrics, Error> {
-        let full = self.full_metrics(key)?;
+    fn metrics(&self, key: FontKey, size: Size) -> Result<Metrics, Error> {
+        let full = self.full_metrics(key, size)?;
 
         let height = (full.size_metrics.height / 64) as f64;
         let descent = (full.size_metrics.descender / 64) as f32;
@@ -109,6 +180,15 @@ impl ::Rasterize for FreeTypeRasterizer {
     fn set_device_pixel_ratio(&mut self, dpr: f32) {
         self.device_pixel_ratio = dpr;
     }
+
+    /// Faces are loaded again, since their load flags depend on the rendering
+    fn set_rendering(&mut self, rendering: Rendering) {
+        if rendering != self.rendering {
+            self.rendering = rendering;
+            self.faces.clear();
+            self.keys.clear();
+        }
+    }
 }
 
 pub trait IntoFontconfigType {
@@ -161,11 +241,18 @@ impl FreeTypeRasterizer {
         }
     }
 
-    fn full_metrics(&self, key: FontKey) -> Result<FullMetrics, Error> {
+    /// Size in pixels of a font size in points
+    fn pixelsize(&self, size: Size) -> f64 {
+        f64::from(size.as_f32_pts() * self.device_pixel_ratio * 96. / 72.)
+    }
+
+    fn full_metrics(&self, key: FontKey, size: Size) -> Result<FullMetrics, Error> {
         let face = self.faces
             .get(&key)
             .ok_or(Error::FontNotLoaded)?;
 
+        face.set_size(self.pixelsize(size), self.use_embedded_bitmaps)?;
+
         let size_metrics = face.ft_face.size_metrics()
             .ok_or(Error::MissingSizeMetrics)?;
 
@@ -193,15 +280,7 @@ impl FreeTypeRasterizer {
         pattern.set_slant(slant.into_fontconfig_type());
         pattern.add_pixelsize(f64::from(size.as_f32_pts()));
 
-        let font = fc::font_match(fc::Config::get_current(), &mut pattern)
-            .ok_or_else(|| Error::MissingFont(desc.to_owned()))?;
-
-        self.face_from_pattern(&font)
-            .and_then(|pattern| {
-                pattern
-                    .map(Ok)
-                    .unwrap_or_else(|| Err(Error::MissingFont(desc.to_owned())))
-            })
+        self.face_from_family(desc, pattern)
     }
 
     fn get_specific_face(
@@ -215,9 +294,27 @@ impl FreeTypeRasterizer {
         pattern.add_style(style);
         pattern.add_pixelsize(f64::from(size.as_f32_pts()));
 
+        self.face_from_family(desc, pattern)
+    }
+
+    /// Load the face fontconfig matches for the family of `desc`
+    ///
+    /// Fontconfig always matches some font, so the match is rejected when it
+    /// belongs to an unrelated family. Aliases like `monospace` are replaced
+    /// by the families they prefer during substitution, while unknown families
+    /// are followed by the default families, which tells the two apart.
+    fn face_from_family(&mut self, desc: &FontDesc, mut pattern: fc::Pattern) -> Result<FontKey, Error> {
         let font = fc::font_match(fc::Config::get_current(), &mut pattern)
             .ok_or_else(|| Error::MissingFont(desc.to_owned()))?;
-        self.face_from_pattern(&font)
+
+        let is_alias = pattern.family().next()
+            .map_or(false, |family| !family.eq_ignore_ascii_case(&desc.name));
+        let is_family = font.family().any(|family| family.eq_ignore_ascii_case(&desc.name));
+        if !is_alias && !is_family {
+            return Err(Error::MissingFont(desc.to_owned()));
+        }
+
+        self.face_from_pattern(&font, &desc.features)
             .and_then(|pattern| {
                 pattern
                     .map(Ok)
@@ -225,41 +322,52 @@ impl FreeTypeRasterizer {
             })
     }
 
-    fn face_from_pattern(&mut self, pattern: &fc::Pattern) -> Result<Option<FontKey>, Error> {
+    fn face_from_pattern(
+        &mut self,
+        pattern: &fc::Pattern,
+        features: &[Feature],
+    ) -> Result<Option<FontKey>, Error> {
         if let (Some(path), Some(index)) = (pattern.file(0), pattern.index().nth(0)) {
-            if let Some(key) = self.keys.get(&path) {
+            let path_key = (path, features.to_vec());
+            if let Some(key) = self.keys.get(&path_key) {
                 return Ok(Some(*key));
             }
+            let path = &path_key.0;
+
+            info!(
+                "Loading font {} from {}",
+                pattern.family().next().unwrap_or("unknown"),
+                path.display()
+            );
+            let ft_face = self.library.new_face(path, index)?;
+
+            let scalable = pattern.scalable().next().unwrap_or(true);
+            let strikes = Self::strikes(&ft_face);
+            debug!("strikes: {:?}", strikes);
+
+            let mut load_flags = Self::ft_load_flags(pattern, self.rendering);
+            if scalable && !self.use_embedded_bitmaps {
+                load_flags |= freetype::face::LoadFlag::NO_BITMAP;
+            }
 
-            trace!("got font path={:?}", path);
-            let ft_face = self.library.new_face(&path, index)?;
-
-            // Get available pixel sizes if font isn't scalable.
-            let non_scalable = if pattern.scalable().next().unwrap_or(true) {
-                None
-            } else {
-                let mut pixelsize = pattern.pixelsize();
-                debug!("pixelsizes: {:?}", pixelsize);
-
-                Some(FixedSize {
-                    pixelsize: pixelsize.next().expect("has 1+ pixelsize"),
-                })
-            };
-
+            let (_, _, rgba) = Self::ft_settings(pattern, self.rendering);
             let face = Face {
                 ft_face,
                 key: FontKey::next(),
-                load_flags: Self::ft_load_flags(pattern),
-                render_mode: Self::ft_render_mode(pattern),
+                load_flags,
+                render_mode: Self::ft_render_mode(pattern, self.rendering),
                 lcd_filter: Self::ft_lcd_filter(pattern),
-                non_scalable,
+                bgr: rgba == fc::Rgba::Bgr || rgba == fc::Rgba::Vbgr,
+                strikes,
+                scalable,
+                substitutions: Self::substitutions(path, index, features),
             };
 
             debug!("Loaded Face {:?}", face);
 
             let key = face.key;
             self.faces.insert(key, face);
-            self.keys.insert(path, key);
+            self.keys.insert(path_key, key);
 
             Ok(Some(key))
         } else {
@@ -290,68 +398,30 @@ impl FreeTypeRasterizer {
 
     fn get_rendered_glyph(&mut self, glyph_key: GlyphKey)
                           -> Result<RasterizedGlyph, Error> {
-        // Render a custom symbol for the underline and beam cursor
-        match glyph_key.c {
-            super::UNDERLINE_CURSOR_CHAR => {
-                // Get the primary face metrics
-                // This always loads the default face
-                let full = self.full_metrics(glyph_key.font_key)?;
-
-                // Get the bottom of the bounding box
-                let descent = (full.size_metrics.descender / 64) as i32;
-
-                // Get the width of the cell
-                let width = full.cell_width as i32;
-
-                // Return the new custom glyph
-                return super::get_underline_cursor_glyph(descent, width);
-            },
-            super::BEAM_CURSOR_CHAR | super::BOX_CURSOR_CHAR => {
-                // Get the primary face metrics
-                // This always loads the default face
-                let full = self.full_metrics(glyph_key.font_key)?;
-
-                // Get the height of the cell
-                let height = (full.size_metrics.height / 64) as i32;
-
-                // Get the top of the bounding box
-                let descent = (full.size_metrics.descender / 64) as i32;
-                let ascent = height + descent;
-
-                // Get the width of the cell
-                let width = full.cell_width as i32;
-
-                // Return the new custom glyph
-                return if glyph_key.c == super::BEAM_CURSOR_CHAR {
-                    super::get_beam_cursor_glyph(ascent, height, width)
-                } else {
-                    super::get_box_cursor_glyph(ascent, height, width)
-                };
-            },
-            _ => (),
-        }
-
-        // Render a normal character if it's not a cursor
         let font_key = self.face_for_glyph(glyph_key, false)?;
-        let face = &self.faces[&font_key];
+        let pixelsize = self.pixelsize(glyph_key.size);
+        let use_embedded_bitmaps = self.use_embedded_bitmaps;
+        let face = self.faces.get_mut(&font_key).ok_or(Error::FontNotLoaded)?;
         let index = face.ft_face.get_char_index(glyph_key.c as usize);
+        let index = face.substitutions.apply(index);
 
-        let size = face.non_scalable.as_ref()
-            .map(|v| v.pixelsize as f32)
-            .unwrap_or_else(|| glyph_key.size.as_f32_pts() * self.device_pixel_ratio * 96. / 72.);
-
-        face.ft_face.set_char_size(to_freetype_26_6(size), 0, 0, 0)?;
+        face.set_size(pixelsize, use_embedded_bitmaps)?;
 
         unsafe {
             let ft_lib = self.library.raw();
             freetype::ffi::FT_Library_SetLcdFilter(ft_lib, face.lcd_filter);
         }
 
+        // Glyphs of a strike are bitmaps already, rendering keeps their pixel
+        // mode and only applies to outlines which are missing in the strike
         face.ft_face.load_glyph(index as u32, face.load_flags)?;
         let glyph = face.ft_face.glyph();
         glyph.render_glyph(face.render_mode)?;
 
-        let (pixel_height, pixel_width, buf) = Self::normalize_buffer(&glyph.bitmap())?;
+        let (pixel_height, pixel_width, mut buf) = Self::normalize_buffer(&glyph.bitmap())?;
+        if face.bgr {
+            super::swap_red_and_blue(&mut buf);
+        }
 
         Ok(RasterizedGlyph {
             c: glyph_key.c,
@@ -363,10 +433,66 @@ impl FreeTypeRasterizer {
         })
     }
 
-    fn ft_load_flags(pat: &fc::Pattern) -> freetype::face::LoadFlag {
-        let antialias = pat.antialias().next().unwrap_or(true);
-        let hinting = pat.hintstyle().next().unwrap_or(fc::HintStyle::Slight);
-        let rgba = pat.rgba().next().unwrap_or(fc::Rgba::Unknown);
+    /// Embedded bitmap strikes of a face
+    fn strikes(ft_face: &freetype::Face) -> Vec<Strike> {
+        let raw = ft_face.raw();
+        if raw.available_sizes.is_null() {
+            return Vec::new();
+        }
+
+        let sizes = unsafe {
+            slice::from_raw_parts(raw.available_sizes, raw.num_fixed_sizes as usize)
+        };
+
+        sizes.iter()
+            .enumerate()
+            .map(|(index, size)| Strike {
+                index: index as i32,
+                // The size is stored in 26.6 fractional pixels
+                pixelsize: size.y_ppem as f64 / 64.,
+            })
+            .collect()
+    }
+
+    /// Glyph substitutions for the OpenType features of a face
+    fn substitutions(path: &Path, index: isize, features: &[Feature]) -> Substitutions {
+        if features.is_empty() {
+            return Substitutions::default();
+        }
+
+        match fs::read(path) {
+            Ok(data) => Substitutions::from_font(&data, index as usize, features),
+            Err(err) => {
+                warn!("Unable to read {} for font features: {}", path.display(), err);
+                Substitutions::default()
+            },
+        }
+    }
+
+    /// Antialiasing, hinting and subpixel order of a face
+    ///
+    /// The settings of the pattern are used unless they're overridden.
+    fn ft_settings(pat: &fc::Pattern, rendering: Rendering) -> (bool, fc::HintStyle, fc::Rgba) {
+        let antialias = rendering.antialias
+            .unwrap_or_else(|| pat.antialias().next().unwrap_or(true));
+        let hinting = match rendering.hinting {
+            Some(Hinting::None) => fc::HintStyle::None,
+            Some(Hinting::Slight) => fc::HintStyle::Slight,
+            Some(Hinting::Full) => fc::HintStyle::Full,
+            None => pat.hintstyle().next().unwrap_or(fc::HintStyle::Slight),
+        };
+        let rgba = match rendering.subpixel {
+            Subpixel::Auto => pat.rgba().next().unwrap_or(fc::Rgba::Unknown),
+            Subpixel::Rgb => fc::Rgba::Rgb,
+            Subpixel::Bgr => fc::Rgba::Bgr,
+            Subpixel::None => fc::Rgba::None,
+        };
+
+        (antialias, hinting, rgba)
+    }
+
+    fn ft_load_flags(pat: &fc::Pattern, rendering: Rendering) -> freetype::face::LoadFlag {
+        let (antialias, hinting, rgba) = Self::ft_settings(pat, rendering);
 
         use freetype::face::LoadFlag;
         match (antialias, hinting, rgba) {
@@ -401,9 +527,8 @@ impl FreeTypeRasterizer {
         }
     }
 
-    fn ft_render_mode(pat: &fc::Pattern) -> freetype::RenderMode {
-        let antialias = pat.antialias().next().unwrap_or(true);
-        let rgba = pat.rgba().next().unwrap_or(fc::Rgba::Unknown);
+    fn ft_render_mode(pat: &fc::Pattern, rendering: Rendering) -> freetype::RenderMode {
+        let (antialias, _, rgba) = Self::ft_settings(pat, rendering);
 
         match (antialias, rgba) {
             (false, _) => freetype::RenderMode::Mono,
@@ -512,7 +637,8 @@ impl FreeTypeRasterizer {
         match fc::font_match(config, &mut pattern) {
             Some(pattern) => {
                 if let (Some(path), Some(_)) = (pattern.file(0), pattern.index().nth(0)) {
-                    match self.keys.get(&path) {
+                    // Fallback fonts are loaded without features
+                    match self.keys.get(&(path.clone(), Vec::new())) {
                         // We've previously loaded this font, so don't
                         // load it again.
                         Some(&key) => {
@@ -524,7 +650,7 @@ impl FreeTypeRasterizer {
                             debug!("Miss for font {:?}; loading now.", path);
                             // Safe to unwrap the option since we've already checked for the path
                             // and index above.
-                            let key = self.face_from_pattern(&pattern)?.unwrap();
+                            let key = self.face_from_pattern(&pattern, &[])?.unwrap();
                             Ok(key)
                         }
                     }
@@ -604,3 +730,122 @@ impl From<freetype::Error> for Error {
 }
 
 unsafe impl Send for FreeTypeRasterizer {}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use freetype;
+
+    use super::{select_strike, Face, FreeTypeRasterizer, Strike};
+    use {FontKey, Rasterize, Rendering, Size};
+    use gsub::Substitutions;
+
+    /// Bitmap-only font with a single 16 pixel strike and 8 pixel wide digits
+    const BDF: &str = "\
+STARTFONT 2.1
+FONT -misc-strike-medium-r-normal--16-120-96-96-c-80-iso10646-1
+SIZE 12 96 96
+FONTBOUNDINGBOX 8 16 0 -4
+STARTPROPERTIES 6
+FAMILY_NAME \"Strike\"
+PIXEL_SIZE 16
+FONT_ASCENT 12
+FONT_DESCENT 4
+CHARSET_REGISTRY \"ISO10646\"
+CHARSET_ENCODING \"1\"
+ENDPROPERTIES
+CHARS 1
+STARTCHAR zero
+ENCODING 48
+SWIDTH 500 0
+DWIDTH 8 0
+BBX 8 16 0 -4
+BITMAP
+00
+00
+3C
+66
+66
+6E
+76
+66
+66
+66
+3C
+00
+00
+00
+00
+00
+ENDCHAR
+ENDFONT
+";
+
+    fn strikes(pixelsizes: &[f64]) -> Vec<Strike> {
+        pixelsizes.iter()
+            .enumerate()
+            .map(|(index, &pixelsize)| Strike { index: index as i32, pixelsize })
+            .collect()
+    }
+
+    /// Rasterizer with the bitmap-only test font loaded
+    fn bitmap_rasterizer() -> (FreeTypeRasterizer, FontKey) {
+        let path = ::std::env::temp_dir()
+            .join(format!("alacritty-font-test-{}.bdf", ::std::process::id()));
+        fs::write(&path, BDF).unwrap();
+
+        let mut rasterizer = FreeTypeRasterizer::new(1., false, false, Rendering::default())
+            .unwrap();
+        let ft_face = rasterizer.library.new_face(&path, 0).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let face = Face {
+            strikes: FreeTypeRasterizer::strikes(&ft_face),
+            ft_face,
+            key: FontKey::next(),
+            load_flags: freetype::face::LoadFlag::DEFAULT,
+            render_mode: freetype::RenderMode::Normal,
+            lcd_filter: 0,
+            bgr: false,
+            scalable: false,
+            substitutions: Substitutions::default(),
+        };
+        let key = face.key;
+        rasterizer.faces.insert(key, face);
+
+        (rasterizer, key)
+    }
+
+    #[test]
+    fn bitmap_only_face_uses_closest_strike() {
+        let strikes = strikes(&[10., 16., 24.]);
+
+        let strike = select_strike(&strikes, false, 17., false).unwrap();
+        assert_eq!(strike.index, 1);
+        let strike = select_strike(&strikes, false, 100., false).unwrap();
+        assert_eq!(strike.index, 2);
+        assert!(select_strike(&[], false, 16., true).is_none());
+    }
+
+    #[test]
+    fn scalable_face_uses_strike_within_tolerance() {
+        let strikes = strikes(&[13., 16.]);
+
+        let strike = select_strike(&strikes, true, 15.5, true).unwrap();
+        assert_eq!(strike.index, 1);
+        assert!(select_strike(&strikes, true, 14.5, true).is_none());
+        assert!(select_strike(&strikes, true, 16., false).is_none());
+    }
+
+    #[test]
+    fn bitmap_only_face_metrics_come_from_strike() {
+        let (rasterizer, key) = bitmap_rasterizer();
+
+        // 12pt and 15pt at 96 DPI are 16 and 20 pixels, both use the strike
+        for &size in &[12., 15.] {
+            let metrics = rasterizer.metrics(key, Size::new(size)).unwrap();
+            assert_eq!((metrics.average_advance, metrics.line_height, metrics.descent), (8., 16., -4.));
+        }
+    }
+}
@@ -0,0 +1,315 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Glyph substitution for OpenType features
+//!
+//! Without a shaping pass, only the lookups of the `GSUB` table replacing a
+//! single glyph are applied. This covers features like stylistic sets, `zero`
+//! or `onum`, while ligatures and contextual alternates are never applied, so
+//! disabling them has no effect.
+//!
+//! The script and language system of the lookups are ignored, every lookup of
+//! a feature is applied.
+use std::collections::HashMap;
+
+use super::Feature;
+
+/// Tag of the glyph substitution table
+pub const GSUB: [u8; 4] = *b"GSUB";
+
+const SINGLE: u16 = 1;
+const ALTERNATE: u16 = 3;
+const EXTENSION: u16 = 7;
+
+/// Glyph replacements for a set of features
+#[derive(Debug, Default, Clone)]
+pub struct Substitutions {
+    glyphs: HashMap<u16, u16>,
+}
+
+impl Substitutions {
+    /// Collect the substitutions of the enabled `features` from a `GSUB` table
+    pub fn new(gsub: &[u8], features: &[Feature]) -> Substitutions {
+        let mut substitutions = Substitutions::default();
+
+        let lookups = match lookup_indices(gsub, features) {
+            Some(lookups) => lookups,
+            None => {
+                warn!("Unable to parse GSUB table, ignoring font features");
+                return substitutions;
+            },
+        };
+
+        for (lookup, value) in lookups {
+            match lookup_substitutions(gsub, lookup, value) {
+                Some(glyphs) => substitutions.chain(&glyphs),
+                None => warn!("Unable to parse GSUB lookup {}", lookup),
+            }
+        }
+
+        substitutions
+    }
+
+    /// Collect the substitutions of the enabled `features` from an sfnt font file
+    #[cfg(not(target_os = "macos"))]
+    pub fn from_font(data: &[u8], index: usize, features: &[Feature]) -> Substitutions {
+        match sfnt_table(data, index, GSUB) {
+            Some(gsub) => Substitutions::new(gsub, features),
+            None => {
+                warn!("Font has no GSUB table, ignoring font features");
+                Substitutions::default()
+            },
+        }
+    }
+
+    /// Glyph which replaces `glyph`
+    #[inline]
+    pub fn apply(&self, glyph: u32) -> u32 {
+        if glyph > u32::from(u16::max_value()) {
+            return glyph;
+        }
+
+        self.glyphs.get(&(glyph as u16)).map_or(glyph, |&glyph| u32::from(glyph))
+    }
+
+    /// Apply a lookup after the substitutions collected so far
+    fn chain(&mut self, lookup: &HashMap<u16, u16>) {
+        for replacement in self.glyphs.values_mut() {
+            if let Some(&glyph) = lookup.get(replacement) {
+                *replacement = glyph;
+            }
+        }
+
+        for (&glyph, &replacement) in lookup {
+            self.glyphs.entry(glyph).or_insert(replacement);
+        }
+    }
+}
+
+/// Locate a table in the table directory of an sfnt font file
+///
+/// The `index` selects the font of a font collection.
+#[cfg(not(target_os = "macos"))]
+pub fn sfnt_table(data: &[u8], index: usize, tag: [u8; 4]) -> Option<&[u8]> {
+    let directory = if data.get(..4)? == b"ttcf" {
+        u32_at(data, 12 + index * 4)? as usize
+    } else {
+        0
+    };
+
+    let num_tables = u16_at(data, directory + 4)? as usize;
+    for index in 0..num_tables {
+        let record = directory + 12 + index * 16;
+        if data.get(record..record + 4)? == tag {
+            let offset = u32_at(data, record + 8)? as usize;
+            let len = u32_at(data, record + 12)? as usize;
+            return data.get(offset..offset + len);
+        }
+    }
+
+    None
+}
+
+/// Indices of the lookups of all enabled features in the order they're applied
+fn lookup_indices(gsub: &[u8], features: &[Feature]) -> Option<Vec<(u16, u32)>> {
+    let feature_list = u16_at(gsub, 6)? as usize;
+    let mut lookups = Vec::new();
+
+    let feature_count = u16_at(gsub, feature_list)? as usize;
+    for index in 0..feature_count {
+        let record = feature_list + 2 + index * 6;
+        let tag = gsub.get(record..record + 4)?;
+        let feature = match features.iter().rev().find(|feature| feature.tag == tag) {
+            Some(feature) if feature.is_enabled() => feature,
+            _ => continue,
+        };
+
+        let table = feature_list + u16_at(gsub, record + 4)? as usize;
+        let lookup_count = u16_at(gsub, table + 2)? as usize;
+        for lookup in 0..lookup_count {
+            lookups.push((u16_at(gsub, table + 4 + lookup * 2)?, feature.value));
+        }
+    }
+
+    // Lookups are applied in the order of the lookup list
+    lookups.sort();
+    lookups.dedup_by_key(|&mut (lookup, _)| lookup);
+
+    Some(lookups)
+}
+
+/// Single glyph substitutions of a lookup
+///
+/// The `value` of the feature selects the glyph of alternate substitutions.
+fn lookup_substitutions(gsub: &[u8], lookup: u16, value: u32) -> Option<HashMap<u16, u16>> {
+    let lookup_list = u16_at(gsub, 8)? as usize;
+    let table = lookup_list + u16_at(gsub, lookup_list + 2 + lookup as usize * 2)? as usize;
+
+    let lookup_type = u16_at(gsub, table)?;
+    let subtable_count = u16_at(gsub, table + 4)? as usize;
+
+    let mut glyphs = HashMap::new();
+    for index in 0..subtable_count {
+        let mut subtable = table + u16_at(gsub, table + 6 + index * 2)? as usize;
+        let mut subtable_type = lookup_type;
+
+        // Extension subtables point to a subtable of another type
+        if subtable_type == EXTENSION {
+            subtable_type = u16_at(gsub, subtable + 2)?;
+            subtable += u32_at(gsub, subtable + 4)? as usize;
+        }
+
+        match subtable_type {
+            SINGLE => single_substitutions(gsub, subtable, &mut glyphs)?,
+            ALTERNATE => alternate_substitutions(gsub, subtable, value, &mut glyphs)?,
+            // Substitutions of other types depend on the surrounding glyphs
+            _ => (),
+        }
+    }
+
+    Some(glyphs)
+}
+
+fn single_substitutions(gsub: &[u8], subtable: usize, glyphs: &mut HashMap<u16, u16>) -> Option<()> {
+    let format = u16_at(gsub, subtable)?;
+    let coverage = coverage(gsub, subtable + u16_at(gsub, subtable + 2)? as usize)?;
+
+    for (index, glyph) in coverage.into_iter().enumerate() {
+        let replacement = match format {
+            1 => glyph.wrapping_add(u16_at(gsub, subtable + 4)?),
+            2 => u16_at(gsub, subtable + 6 + index * 2)?,
+            _ => return None,
+        };
+
+        // The first subtable covering a glyph takes precedence
+        glyphs.entry(glyph).or_insert(replacement);
+    }
+
+    Some(())
+}
+
+fn alternate_substitutions(
+    gsub: &[u8],
+    subtable: usize,
+    value: u32,
+    glyphs: &mut HashMap<u16, u16>,
+) -> Option<()> {
+    let coverage = coverage(gsub, subtable + u16_at(gsub, subtable + 2)? as usize)?;
+
+    for (index, glyph) in coverage.into_iter().enumerate() {
+        let set = subtable + u16_at(gsub, subtable + 6 + index * 2)? as usize;
+        let count = u16_at(gsub, set)?;
+
+        // Values beyond the available alternates leave the glyph alone
+        if value <= u32::from(count) {
+            let replacement = u16_at(gsub, set + 2 * value as usize)?;
+            glyphs.entry(glyph).or_insert(replacement);
+        }
+    }
+
+    Some(())
+}
+
+/// Glyphs of a coverage table, ordered by their coverage index
+fn coverage(gsub: &[u8], table: usize) -> Option<Vec<u16>> {
+    let count = u16_at(gsub, table + 2)? as usize;
+
+    match u16_at(gsub, table)? {
+        1 => (0..count).map(|index| u16_at(gsub, table + 4 + index * 2)).collect(),
+        2 => {
+            let mut glyphs = Vec::new();
+            for index in 0..count {
+                let record = table + 4 + index * 6;
+                let start = u16_at(gsub, record)?;
+                let end = u16_at(gsub, record + 2)?;
+                if end >= start {
+                    glyphs.extend(start..=end);
+                }
+            }
+            Some(glyphs)
+        },
+        _ => None,
+    }
+}
+
+#[inline]
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+}
+
+#[inline]
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from(u16_at(data, offset)?) << 16 | u32::from(u16_at(data, offset + 2)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Substitutions;
+    use Feature;
+
+    /// GSUB table with a `zero` feature replacing glyph 16 by 100, and an
+    /// `salt` feature with two alternates for glyphs 20 and 21
+    fn gsub() -> Vec<u8> {
+        vec![
+            // Header: version 1.0, script list, feature list, lookup list
+            0, 1, 0, 0, 0, 0, 0, 10, 0, 36,
+            // Feature list with `salt` and `zero`
+            0, 2, b's', b'a', b'l', b't', 0, 14, b'z', b'e', b'r', b'o', 0, 20,
+            // Feature `salt` using lookup 1
+            0, 0, 0, 1, 0, 1,
+            // Feature `zero` using lookup 0
+            0, 0, 0, 1, 0, 0,
+            // Lookup list with two lookups
+            0, 2, 0, 6, 0, 28,
+            // Lookup 0: single substitution with one subtable
+            0, 1, 0, 0, 0, 1, 0, 8,
+            // Single substitution format 2 with coverage at offset 8
+            0, 2, 0, 8, 0, 1, 0, 100,
+            // Coverage format 1 for glyph 16
+            0, 1, 0, 1, 0, 16,
+            // Lookup 1: alternate substitution with one subtable
+            0, 3, 0, 0, 0, 1, 0, 8,
+            // Alternate substitution with coverage and two alternate sets
+            0, 1, 0, 10, 0, 2, 0, 20, 0, 26,
+            // Coverage format 2 for glyphs 20 to 21
+            0, 2, 0, 1, 0, 20, 0, 21, 0, 0,
+            // Alternate sets
+            0, 2, 0, 200, 0, 201,
+            0, 2, 0, 210, 0, 211,
+        ]
+    }
+
+    #[test]
+    fn single_substitution() {
+        let substitutions = Substitutions::new(&gsub(), &["zero".parse::<Feature>().unwrap()]);
+        assert_eq!(substitutions.apply(16), 100);
+        assert_eq!(substitutions.apply(20), 20);
+    }
+
+    #[test]
+    fn alternate_substitution() {
+        let substitutions = Substitutions::new(&gsub(), &["salt=2".parse::<Feature>().unwrap()]);
+        assert_eq!(substitutions.apply(20), 201);
+        assert_eq!(substitutions.apply(21), 211);
+        assert_eq!(substitutions.apply(16), 16);
+    }
+
+    #[test]
+    fn disabled_feature() {
+        let features = ["zero".parse::<Feature>().unwrap(), "zero off".parse().unwrap()];
+        let substitutions = Substitutions::new(&gsub(), &features);
+        assert_eq!(substitutions.apply(16), 16);
+    }
+}
@@ -20,71 +20,157 @@
 
 #![cfg_attr(feature = "cargo-clippy", deny(clippy, if_not_else, enum_glob_use, wrong_pub_self_convention))]
 
-#[cfg(not(any(target_os = "macos", windows)))]
+#[cfg(all(feature = "rasterizer", not(any(target_os = "macos", windows))))]
 extern crate fontconfig;
-#[cfg(not(any(target_os = "macos", windows)))]
+#[cfg(all(feature = "rasterizer", not(any(target_os = "macos", windows))))]
 extern crate freetype;
 
-#[cfg(target_os = "macos")]
+#[cfg(all(feature = "rasterizer", target_os = "macos"))]
 extern crate core_foundation;
-#[cfg(target_os = "macos")]
+#[cfg(all(feature = "rasterizer", target_os = "macos"))]
 extern crate core_foundation_sys;
-#[cfg(target_os = "macos")]
+#[cfg(all(feature = "rasterizer", target_os = "macos"))]
 extern crate core_graphics;
-#[cfg(target_os = "macos")]
+#[cfg(all(feature = "rasterizer", target_os = "macos"))]
 extern crate core_text;
-#[cfg(target_os = "macos")]
+#[cfg(all(feature = "rasterizer", target_os = "macos"))]
 extern crate euclid;
 
 extern crate libc;
 
-#[cfg(not(any(target_os = "macos", windows)))]
+#[cfg(all(feature = "rasterizer", not(any(target_os = "macos", windows))))]
 #[macro_use]
 extern crate foreign_types;
 
-#[cfg_attr(not(windows), macro_use)]
+#[macro_use]
 extern crate log;
 
 use std::hash::{Hash, Hasher};
-use std::{fmt, cmp};
+use std::fmt;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
 
+// Without the `rasterizer` feature only the types describing fonts and glyphs
+// are available, which doesn't require any of the platform's font libraries.
+
 // If target isn't macos or windows, reexport everything from ft
-#[cfg(not(any(target_os = "macos", windows)))]
+#[cfg(all(feature = "rasterizer", not(any(target_os = "macos", windows))))]
 pub mod ft;
-#[cfg(not(any(target_os = "macos", windows)))]
+#[cfg(all(feature = "rasterizer", not(any(target_os = "macos", windows))))]
 pub use ft::{Error, FreeTypeRasterizer as Rasterizer};
 
-#[cfg(windows)]
+#[cfg(all(feature = "rasterizer", windows))]
 pub mod rusttype;
-#[cfg(windows)]
+#[cfg(all(feature = "rasterizer", windows))]
 pub use rusttype::{Error, RustTypeRasterizer as Rasterizer};
 
+#[cfg(feature = "rasterizer")]
+mod gsub;
+
+pub mod synthesis;
+
 // If target is macos, reexport everything from darwin
-#[cfg(target_os = "macos")]
+#[cfg(all(feature = "rasterizer", target_os = "macos"))]
 mod darwin;
-#[cfg(target_os = "macos")]
+#[cfg(all(feature = "rasterizer", target_os = "macos"))]
 pub use darwin::*;
 
-/// Width/Height of the cursor relative to the font width
-pub const CURSOR_WIDTH_PERCENTAGE: i32 = 15;
-
-/// Character used for the underline cursor
-// This is part of the private use area and should not conflict with any font
-pub const UNDERLINE_CURSOR_CHAR: char = '\u{10a3e2}';
-
-/// Character used for the beam cursor
-// This is part of the private use area and should not conflict with any font
-pub const BEAM_CURSOR_CHAR: char = '\u{10a3e3}';
-
-/// Character used for the empty box cursor
-// This is part of the private use area and should not conflict with any font
-pub const BOX_CURSOR_CHAR: char = '\u{10a3e4}';
+/// Family loaded when the configured family can't be found
+///
+/// Fontconfig resolves the generic name to the preferred monospace family.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub const DEFAULT_FAMILY: &str = "monospace";
+#[cfg(target_os = "macos")]
+pub const DEFAULT_FAMILY: &str = "Menlo";
+#[cfg(windows)]
+pub const DEFAULT_FAMILY: &str = "Consolas";
+
+/// Resolve the generic family names `monospace`, `sans-serif` and `serif`
+///
+/// Only required for font engines which have no aliases of their own.
+#[cfg(all(feature = "rasterizer", any(target_os = "macos", windows)))]
+fn resolve_generic_family(family: &str) -> &str {
+    match &family.to_lowercase()[..] {
+        "monospace" | "mono" => DEFAULT_FAMILY,
+        #[cfg(target_os = "macos")]
+        "sans-serif" | "sans" => "Helvetica",
+        #[cfg(target_os = "macos")]
+        "serif" => "Times",
+        #[cfg(windows)]
+        "sans-serif" | "sans" => "Segoe UI",
+        #[cfg(windows)]
+        "serif" => "Times New Roman",
+        _ => family,
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FontDesc {
     name: String,
     style: Style,
+    features: Vec<Feature>,
+}
+
+/// OpenType feature, like the stylistic set `ss01`
+///
+/// Parsed from `tag`, `tag on`, `tag off` or `tag=value`, where the value
+/// selects one of multiple alternates. Later features override earlier ones
+/// with the same tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Feature {
+    pub tag: [u8; 4],
+    pub value: u32,
+}
+
+impl Feature {
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.value != 0
+    }
+}
+
+impl FromStr for Feature {
+    type Err = String;
+
+    fn from_str(feature: &str) -> Result<Feature, String> {
+        let feature = feature.trim();
+        let (name, value) = match feature.find(|c: char| c == '=' || c.is_whitespace()) {
+            Some(index) => {
+                let value = feature[index..].trim();
+                let value = if value.starts_with('=') { value[1..].trim() } else { value };
+                (&feature[..index], value)
+            },
+            None => (feature, "on"),
+        };
+
+        // Shorter tags are padded with spaces
+        if name.is_empty() || name.len() > 4 || !name.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(format!("invalid font feature tag `{}`", name));
+        }
+        let mut tag = *b"    ";
+        tag[..name.len()].copy_from_slice(name.as_bytes());
+
+        let value = match value {
+            "on" => 1,
+            "off" => 0,
+            value => value.parse().map_err(|_| {
+                format!("invalid value `{}` of font feature `{}`", value, name)
+            })?,
+        };
+
+        Ok(Feature { tag, value })
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tag = String::from_utf8_lossy(&self.tag);
+        match self.value {
+            0 => write!(f, "{} off", tag.trim()),
+            1 => write!(f, "{}", tag.trim()),
+            value => write!(f, "{}={}", tag.trim(), value),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -126,8 +212,27 @@ impl FontDesc {
         FontDesc {
             name: name.into(),
             style,
+            features: Vec::new(),
         }
     }
+
+    /// Same style and features with a different family
+    pub fn with_family<S>(&self, name: S) -> FontDesc
+    where
+        S: Into<String>,
+    {
+        FontDesc {
+            name: name.into(),
+            style: self.style.clone(),
+            features: self.features.clone(),
+        }
+    }
+
+    /// Enable OpenType features of the font
+    pub fn with_features(mut self, features: Vec<Feature>) -> FontDesc {
+        self.features = features;
+        self
+    }
 }
 
 impl fmt::Display for FontDesc {
@@ -239,75 +344,6 @@ impl Default for RasterizedGlyph {
     }
 }
 
-// Returns a custom underline cursor character
-pub fn get_underline_cursor_glyph(descent: i32, width: i32) -> Result<RasterizedGlyph, Error> {
-    // Create a new rectangle, the height is relative to the font width
-    let height = cmp::max(width * CURSOR_WIDTH_PERCENTAGE / 100, 1);
-    let buf = vec![255u8; (width * height * 3) as usize];
-
-    // Create a custom glyph with the rectangle data attached to it
-    Ok(RasterizedGlyph {
-        c: UNDERLINE_CURSOR_CHAR,
-        top: descent + height,
-        left: 0,
-        height,
-        width,
-        buf,
-    })
-}
-
-// Returns a custom beam cursor character
-pub fn get_beam_cursor_glyph(
-    ascent: i32,
-    height: i32,
-    width: i32,
-) -> Result<RasterizedGlyph, Error> {
-    // Create a new rectangle that is at least one pixel wide
-    let beam_width = cmp::max(width * CURSOR_WIDTH_PERCENTAGE / 100, 1);
-    let buf = vec![255u8; (beam_width * height * 3) as usize];
-
-    // Create a custom glyph with the rectangle data attached to it
-    Ok(RasterizedGlyph {
-        c: BEAM_CURSOR_CHAR,
-        top: ascent,
-        left: 0,
-        height,
-        width: beam_width,
-        buf,
-    })
-}
-
-// Returns a custom box cursor character
-pub fn get_box_cursor_glyph(
-    ascent: i32,
-    height: i32,
-    width: i32,
-) -> Result<RasterizedGlyph, Error> {
-    // Create a new box outline rectangle
-    let border_width = cmp::max(width * CURSOR_WIDTH_PERCENTAGE / 100, 1);
-    let mut buf = Vec::with_capacity((width * height * 3) as usize);
-    for y in 0..height {
-        for x in 0..width {
-            if y < border_width || y >= height - border_width ||
-               x < border_width || x >= width - border_width {
-                buf.append(&mut vec![255u8; 3]);
-            } else {
-                buf.append(&mut vec![0u8; 3]);
-            }
-        }
-    }
-
-    // Create a custom glyph with the rectangle data attached to it
-    Ok(RasterizedGlyph {
-        c: BOX_CURSOR_CHAR,
-        top: ascent,
-        left: 0,
-        height,
-        width,
-        buf,
-    })
-}
-
 struct BufDebugger<'a>(&'a [u8]);
 
 impl<'a> fmt::Debug for BufDebugger<'a> {
@@ -332,6 +368,55 @@ impl fmt::Debug for RasterizedGlyph {
     }
 }
 
+/// How glyphs are rasterized
+///
+/// Settings which are `None` are left to the system, like the fontconfig
+/// settings of the font.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rendering {
+    pub antialias: Option<bool>,
+    pub subpixel: Subpixel,
+    /// Only supported by FreeType
+    pub hinting: Option<Hinting>,
+}
+
+/// Order of the color components of the screen's pixels
+///
+/// Subpixel antialiasing is only blended correctly when the renderer can blend
+/// each color component separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subpixel {
+    /// Subpixel order of the system, if it uses subpixel antialiasing
+    Auto,
+    Rgb,
+    Bgr,
+    /// Grayscale antialiasing
+    None,
+}
+
+impl Default for Subpixel {
+    fn default() -> Subpixel {
+        Subpixel::Auto
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hinting {
+    None,
+    Slight,
+    Full,
+}
+
+/// Swap the red and blue component of every pixel of a glyph
+///
+/// Rasterizers only produce subpixel antialiased glyphs in RGB order.
+#[cfg(all(feature = "rasterizer", not(windows)))]
+fn swap_red_and_blue(buf: &mut [u8]) {
+    for pixel in buf.chunks_mut(3) {
+        pixel.swap(0, 2);
+    }
+}
+
 pub struct Metrics {
     pub average_advance: f64,
     pub line_height: f64,
@@ -343,7 +428,15 @@ pub trait Rasterize {
     type Err: ::std::error::Error + Send + Sync + 'static;
 
     /// Create a new Rasterizer
-    fn new(device_pixel_ratio: f32, use_thin_strokes: bool) -> Result<Self, Self::Err>
+    ///
+    /// Embedded bitmap strikes are used for sizes they're available for unless
+    /// `use_embedded_bitmaps` is disabled.
+    fn new(
+        device_pixel_ratio: f32,
+        use_thin_strokes: bool,
+        use_embedded_bitmaps: bool,
+        rendering: Rendering,
+    ) -> Result<Self, Self::Err>
     where
         Self: Sized;
 
@@ -358,4 +451,36 @@ pub trait Rasterize {
 
     /// Update stored device pixel ratio
     fn set_device_pixel_ratio(&mut self, f32);
+
+    /// Change how glyphs are rasterized
+    ///
+    /// Fonts may have to be loaded again, which makes the keys of loaded fonts
+    /// invalid.
+    fn set_rendering(&mut self, Rendering);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Feature;
+
+    #[test]
+    fn parse_features() {
+        let feature = |s: &str| s.parse::<Feature>().map(|feature| (feature.tag, feature.value));
+
+        assert_eq!(feature("ss01"), Ok((*b"ss01", 1)));
+        assert_eq!(feature("liga off"), Ok((*b"liga", 0)));
+        assert_eq!(feature("cv1=3"), Ok((*b"cv1 ", 3)));
+        assert_eq!(feature(" zero on "), Ok((*b"zero", 1)));
+        assert!(feature("toolong").is_err());
+        assert!(feature("ss01 maybe").is_err());
+        assert!(feature("").is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "rasterizer", not(windows)))]
+    fn swap_subpixel_order() {
+        let mut buf = vec![1, 2, 3, 4, 5, 6];
+        super::swap_red_and_blue(&mut buf);
+        assert_eq!(buf, vec![3, 2, 1, 6, 5, 4]);
+    }
 }
@@ -1,30 +1,46 @@
+use std::collections::HashMap;
+
 extern crate font_loader;
 use self::font_loader::system_fonts;
 
 extern crate rusttype;
-use self::rusttype::{point, Codepoint, FontCollection, Scale};
+use self::rusttype::{point, Codepoint, FontCollection, GlyphId, Scale};
 
-use super::{FontDesc, FontKey, GlyphKey, Metrics, RasterizedGlyph, Size, Slant, Style, Weight};
+use super::{FontDesc, FontKey, GlyphKey, Metrics, RasterizedGlyph, Rendering, Size, Slant, Style};
+use super::Weight;
+use gsub::Substitutions;
 
 pub struct RustTypeRasterizer {
-    fonts: Vec<rusttype::Font<'static>>,
+    fonts: HashMap<FontKey, rusttype::Font<'static>>,
+    /// Glyph substitutions for the features of the font with the same key
+    substitutions: HashMap<FontKey, Substitutions>,
     dpi_ratio: f32,
+    /// Rusttype only supports grayscale antialiasing without hinting
+    antialias: bool,
 }
 
 impl ::Rasterize for RustTypeRasterizer {
     type Err = Error;
 
-    fn new(device_pixel_ratio: f32, _: bool) -> Result<RustTypeRasterizer, Error> {
+    fn new(
+        device_pixel_ratio: f32,
+        _: bool,
+        _: bool,
+        rendering: Rendering,
+    ) -> Result<RustTypeRasterizer, Error> {
         Ok(RustTypeRasterizer {
-            fonts: Vec::new(),
+            fonts: HashMap::new(),
+            substitutions: HashMap::new(),
             dpi_ratio: device_pixel_ratio,
+            antialias: rendering.antialias.unwrap_or(true),
         })
     }
 
     fn metrics(&self, key: FontKey, size: Size) -> Result<Metrics, Error> {
         let scale = Scale::uniform(size.as_f32_pts() * self.dpi_ratio * 96. / 72.);
-        let vmetrics = self.fonts[key.token as usize].v_metrics(scale);
-        let hmetrics = self.fonts[key.token as usize]
+        let font = self.fonts.get(&key).ok_or(Error::FontNotLoaded)?;
+        let vmetrics = font.v_metrics(scale);
+        let hmetrics = font
             .glyph(
                 // If the font is monospaced all glyphs *should* have the same width
                 // 33 '!' is the first displaying character
@@ -40,66 +56,60 @@ impl ::Rasterize for RustTypeRasterizer {
         })
     }
 
+    fn set_rendering(&mut self, rendering: Rendering) {
+        self.antialias = rendering.antialias.unwrap_or(true);
+    }
+
     fn load_font(&mut self, desc: &FontDesc, _size: Size) -> Result<FontKey, Error> {
+        let family = ::resolve_generic_family(&desc.name);
         let fp = system_fonts::FontPropertyBuilder::new()
-            .family(&desc.name)
+            .family(family)
             .monospace();
 
-        let fp = match desc.style {
-            Style::Specific(_) => unimplemented!(""),
-            Style::Description { slant, weight } => {
-                let fp = match slant {
-                    Slant::Normal => fp,
-                    Slant::Italic => fp.italic(),
-                    // This style is not supported by rust-font-loader
-                    Slant::Oblique => return Err(Error::UnsupportedStyle),
-                };
-                match weight {
-                    Weight::Bold => fp.bold(),
-                    Weight::Normal => fp,
-                }
-            }
+        let (slant, weight) = match desc.style {
+            // Only the slant and weight of a style name can be matched
+            Style::Specific(ref style) => {
+                let style = style.to_lowercase();
+                let slant = if style.contains("italic") { Slant::Italic } else { Slant::Normal };
+                let weight = if style.contains("bold") { Weight::Bold } else { Weight::Normal };
+                (slant, weight)
+            },
+            Style::Description { slant, weight } => (slant, weight),
         };
-        self.fonts.push(FontCollection::from_bytes(
-            system_fonts::get(&fp.build())
-                .ok_or_else(|| Error::MissingFont(desc.clone()))?
-                .0,
-        ).into_font()
-            .ok_or(Error::UnsupportedFont)?);
-        Ok(FontKey {
-            token: (self.fonts.len() - 1) as u16,
-        })
+
+        let fp = match slant {
+            Slant::Normal => fp,
+            Slant::Italic => fp.italic(),
+            // This style is not supported by rust-font-loader
+            Slant::Oblique => return Err(Error::UnsupportedStyle),
+        };
+        let fp = match weight {
+            Weight::Bold => fp.bold(),
+            Weight::Normal => fp,
+        };
+
+        info!("Loading font {} with style {}", family, desc.style);
+        let (data, index) = system_fonts::get(&fp.build())
+            .ok_or_else(|| Error::MissingFont(desc.clone()))?;
+        let substitutions = if desc.features.is_empty() {
+            Substitutions::default()
+        } else {
+            Substitutions::from_font(&data, index as usize, &desc.features)
+        };
+        let font = FontCollection::from_bytes(data).into_font().ok_or(Error::UnsupportedFont)?;
+        let key = FontKey::next();
+        self.fonts.insert(key, font);
+        self.substitutions.insert(key, substitutions);
+        Ok(key)
     }
 
     fn get_glyph(&mut self, glyph_key: GlyphKey) -> Result<RasterizedGlyph, Error> {
-        match glyph_key.c {
-            super::UNDERLINE_CURSOR_CHAR => {
-                let metrics = self.metrics(glyph_key.font_key, glyph_key.size)?;
-                return super::get_underline_cursor_glyph(metrics.descent as i32, metrics.average_advance as i32);
-            }
-            super::BEAM_CURSOR_CHAR => {
-                let metrics = self.metrics(glyph_key.font_key, glyph_key.size)?;
-
-                return super::get_beam_cursor_glyph(
-                    (metrics.line_height + f64::from(metrics.descent)).round() as i32,
-                    metrics.line_height.round() as i32,
-                    metrics.average_advance.round() as i32
-                );
-            }
-            super::BOX_CURSOR_CHAR => {
-                let metrics = self.metrics(glyph_key.font_key, glyph_key.size)?;
-
-                return super::get_box_cursor_glyph(
-                    (metrics.line_height + f64::from(metrics.descent)).round() as i32,
-                    metrics.line_height.round() as i32,
-                    metrics.average_advance.round() as i32
-                );
-            }
-            _ => ()
-        }
+        let font = self.fonts.get(&glyph_key.font_key).ok_or(Error::FontNotLoaded)?;
+        let id = font.glyph(glyph_key.c).ok_or(Error::MissingGlyph)?.id();
+        let id = self.substitutions[&glyph_key.font_key].apply(id.0);
 
-        let scaled_glyph = self.fonts[glyph_key.font_key.token as usize]
-            .glyph(glyph_key.c)
+        let scaled_glyph = font
+            .glyph(GlyphId(id))
             .ok_or(Error::MissingGlyph)?
             .scaled(Scale::uniform(
                 glyph_key.size.as_f32_pts() * self.dpi_ratio * 96. / 72.,
@@ -119,7 +129,9 @@ impl ::Rasterize for RustTypeRasterizer {
 
         let mut buf = Vec::with_capacity((bb.width() * bb.height()) as usize);
 
+        let antialias = self.antialias;
         glyph.draw(|_x, _y, v| {
+            let v = if antialias { v } else { v.round() };
             buf.push((v * 255.0) as u8);
             buf.push((v * 255.0) as u8);
             buf.push((v * 255.0) as u8);
@@ -142,6 +154,8 @@ pub enum Error {
     UnsupportedStyle,
     // NOTE: This error is different from how the FreeType code handles it
     MissingGlyph,
+    /// Requested an operation with a FontKey that isn't known to the rasterizer
+    FontNotLoaded,
 }
 
 impl ::std::error::Error for Error {
@@ -151,6 +165,7 @@ impl ::std::error::Error for Error {
             Error::UnsupportedFont => "only TrueType fonts are supported",
             Error::UnsupportedStyle => "the selected style is not supported by rusttype",
             Error::MissingGlyph => "the selected font did not have the requested glyph",
+            Error::FontNotLoaded => "tried to operate on font that hasn't been loaded",
         }
     }
 }
@@ -172,6 +187,7 @@ impl ::std::fmt::Display for Error {
                 write!(f, "The selected font style is not supported by rusttype.")
             }
             Error::MissingGlyph => write!(f, "The selected font did not have the requested glyph."),
+            Error::FontNotLoaded => write!(f, "Tried to use a font that hasn't been loaded"),
         }
     }
 }
@@ -0,0 +1,182 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Bold and italic glyphs for fonts which only have a regular face
+//!
+//! The rasterized glyphs of the regular face are changed, which works the same
+//! with every rasterizer. Bold glyphs are struck several times next to each
+//! other, italic glyphs are sheared. The metrics of the font stay the same, so
+//! glyphs only grow until they fill their cell and are clipped past it.
+use std::cmp::{max, min};
+
+use RasterizedGlyph;
+
+/// Horizontal shift of italic glyphs per pixel above the baseline
+///
+/// This is a slant of about 12 degrees, like most oblique faces have.
+const SLANT: f32 = 0.2;
+
+/// Columns of the cell which a synthesized glyph may cover
+///
+/// Glyphs which are wider than their cell already keep their size.
+fn clip(glyph: &RasterizedGlyph, cell_width: i32) -> (i32, i32) {
+    (min(0, glyph.left), max(cell_width, glyph.left + glyph.width))
+}
+
+/// Widen the strokes of a glyph by `strength` pixels to the right
+pub fn embolden(glyph: &RasterizedGlyph, strength: u8, cell_width: i32) -> RasterizedGlyph {
+    let strength = i32::from(strength);
+    let (_, right) = clip(glyph, cell_width);
+    let width = max(glyph.width, min(glyph.width + strength, right - glyph.left));
+
+    let mut buf = vec![0; (width * glyph.height * 3) as usize];
+    for y in 0..glyph.height {
+        for x in 0..width {
+            let first = max(0, x - strength);
+            let last = min(glyph.width - 1, x);
+            for channel in 0..3 {
+                let value = (first..=last)
+                    .map(|src| glyph.buf[((y * glyph.width + src) * 3 + channel) as usize])
+                    .max()
+                    .unwrap_or(0);
+                buf[((y * width + x) * 3 + channel) as usize] = value;
+            }
+        }
+    }
+
+    RasterizedGlyph {
+        c: glyph.c,
+        width,
+        height: glyph.height,
+        top: glyph.top,
+        left: glyph.left,
+        buf,
+    }
+}
+
+/// Slant a glyph to the right, around its baseline
+pub fn oblique(glyph: &RasterizedGlyph, cell_width: i32) -> RasterizedGlyph {
+    if glyph.width == 0 || glyph.height == 0 {
+        return RasterizedGlyph { buf: glyph.buf.clone(), ..*glyph };
+    }
+
+    // Rows are shifted by the height of their center above the baseline
+    let shift = |y: i32| (glyph.top - y) as f32 - 0.5;
+    let (clip_left, clip_right) = clip(glyph, cell_width);
+    let left = max(clip_left, glyph.left + (shift(glyph.height - 1) * SLANT).floor() as i32);
+    let right = min(clip_right, glyph.left + glyph.width + (shift(0) * SLANT).ceil() as i32);
+    let width = right - left;
+
+    let source = |x: i32, y: i32, channel: i32| {
+        if x < 0 || x >= glyph.width {
+            0.
+        } else {
+            f32::from(glyph.buf[((y * glyph.width + x) * 3 + channel) as usize])
+        }
+    };
+
+    let mut buf = vec![0; (width * glyph.height * 3) as usize];
+    for y in 0..glyph.height {
+        let offset = shift(y) * SLANT;
+        for x in 0..width {
+            // Interpolate between the two source pixels the sheared pixel covers
+            let position = (left + x - glyph.left) as f32 - offset;
+            let src = position.floor();
+            let fraction = position - src;
+            let src = src as i32;
+            for channel in 0..3 {
+                let value = source(src, y, channel) * (1. - fraction)
+                    + source(src + 1, y, channel) * fraction;
+                buf[((y * width + x) * 3 + channel) as usize] = value.round() as u8;
+            }
+        }
+    }
+
+    RasterizedGlyph {
+        c: glyph.c,
+        width,
+        height: glyph.height,
+        top: glyph.top,
+        left,
+        buf,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use RasterizedGlyph;
+
+    use super::{embolden, oblique};
+
+    /// Glyph with the same value in every channel of a pixel
+    fn glyph(left: i32, top: i32, rows: &[&[u8]]) -> RasterizedGlyph {
+        RasterizedGlyph {
+            c: 'x',
+            width: rows[0].len() as i32,
+            height: rows.len() as i32,
+            top,
+            left,
+            buf: rows.iter().flat_map(|row| row.iter()).flat_map(|&v| vec![v, v, v]).collect(),
+        }
+    }
+
+    fn rows(glyph: &RasterizedGlyph) -> Vec<Vec<u8>> {
+        glyph.buf
+            .chunks(glyph.width as usize * 3)
+            .map(|row| row.chunks(3).map(|pixel| pixel[0]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn bold_glyphs_are_clipped_to_the_cell() {
+        let stem = glyph(1, 1, &[&[0, 255, 0]]);
+
+        let bold = embolden(&stem, 1, 5);
+        assert_eq!(rows(&bold), vec![vec![0, 255, 255, 0]]);
+        assert_eq!(bold.left, 1);
+
+        let bold = embolden(&stem, 2, 4);
+        assert_eq!(rows(&bold), vec![vec![0, 255, 255]]);
+
+        // The glyph doesn't shrink if it's wider than the cell already
+        assert_eq!(embolden(&glyph(0, 1, &[&[255, 0, 255]]), 1, 2).width, 3);
+    }
+
+    #[test]
+    fn italic_glyphs_lean_right() {
+        let stem: Vec<&[u8]> = vec![&[255]; 10];
+        let italic = oblique(&glyph(4, 10, &stem), 20);
+
+        // Center of the ink in every row, relative to the cell
+        let centers: Vec<f32> = rows(&italic)
+            .iter()
+            .map(|row| {
+                let ink: f32 = row.iter().map(|&v| f32::from(v)).sum();
+                let moment: f32 = row.iter()
+                    .enumerate()
+                    .map(|(x, &v)| (x as f32 + 0.5) * f32::from(v))
+                    .sum();
+                italic.left as f32 + moment / ink
+            })
+            .collect();
+        assert!(centers.windows(2).all(|pair| pair[0] > pair[1]));
+        assert!((centers[0] - 6.4).abs() < 0.01);
+        assert!((centers[9] - 4.6).abs() < 0.01);
+
+        // Glyphs below the baseline lean left and the cell clips them
+        let italic = oblique(&glyph(0, 0, &stem), 20);
+        assert_eq!(italic.left, 0);
+        assert_eq!(italic.width, 1);
+    }
+}
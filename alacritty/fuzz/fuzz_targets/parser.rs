@@ -0,0 +1,47 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Feed arbitrary output of the shell through the parser into a terminal
+//!
+//! Run with `cargo fuzz run parser` from the `alacritty` directory.
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate alacritty;
+
+use std::io;
+
+use alacritty::ansi::Processor;
+use alacritty::config::Config;
+use alacritty::term::{SizeInfo, Term};
+
+fuzz_target!(|data: &[u8]| {
+    let size = SizeInfo {
+        width: 800.0,
+        height: 240.0,
+        cell_width: 10.0,
+        cell_height: 10.0,
+        padding_x: 0.0,
+        padding_y: 0.0,
+    };
+    let config = Config::default();
+    let mut term = Term::new(&config, size);
+
+    // A short limit, so the fuzzer reaches the handling of long strings
+    let mut parser = Processor::new();
+    parser.set_max_string_len(256);
+
+    for byte in data {
+        parser.advance(&mut term, *byte, &mut io::sink());
+    }
+});
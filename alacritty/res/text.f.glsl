@@ -22,18 +22,43 @@ layout(location = 0, index = 0) out vec4 color;
 layout(location = 0, index = 1) out vec4 alphaMask;
 
 uniform float bgOpacity;
+uniform int gammaCorrect;
 uniform sampler2D mask;
 
+// Conversion between sRGB and linear light
+vec3 toLinear(vec3 c)
+{
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+vec3 toSrgb(vec3 c)
+{
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
 void main()
 {
     if (background != 0) {
         if (bg.a == 0.0)
             discard;
 
+        // Colors are premultiplied like the window background
         alphaMask = vec4(1.0);
-        color = vec4(bg.rgb + vb, 1.0);
+        color = vec4(min(bg.rgb + vb, vec3(1.0)) * bg.a, bg.a);
     } else {
         vec3 textColor = texture(mask, TexCoords).rgb;
+
+        if (gammaCorrect != 0) {
+            // Blend with the background of the cell in linear light, then
+            // adjust the mask so the sRGB blend has the same result
+            vec3 cellBg = min(bg.rgb + vb, vec3(1.0));
+            vec3 target = toSrgb(mix(toLinear(cellBg), toLinear(fg), textColor));
+            vec3 delta = fg - cellBg;
+            vec3 distinct = step(1.0 / 255.0, abs(delta));
+            vec3 divisor = mix(vec3(1.0), delta, distinct);
+            textColor = mix(textColor, (target - cellBg) / divisor, distinct);
+        }
+
         alphaMask = vec4(textColor, textColor.r);
         color = vec4(fg, 1.0);
     }
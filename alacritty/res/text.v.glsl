@@ -57,8 +57,13 @@ void main()
     // Invert Y since framebuffer origin is bottom-left
     cellPosition.y = termDim.y - cellPosition.y - cellDim.y;
 
-    if (backgroundPass != 0) {
-        cellPosition.y = cellPosition.y;
+    if (backgroundPass == 2) {
+        // Rectangles are offset from the top-left of their cell
+        vec2 rectOffset = vec2(glyphOffset.x, cellDim.y - glyphOffset.y - glyphSize.y);
+        vec2 finalPosition = glyphSize * position + cellPosition + rectOffset;
+        gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
+        TexCoords = vec2(0, 0);
+    } else if (backgroundPass != 0) {
         vec2 finalPosition = cellDim * position + cellPosition;
         gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
         TexCoords = vec2(0, 0);
@@ -0,0 +1,60 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#version 100
+precision mediump float;
+
+varying vec2 TexCoords;
+varying vec3 fg;
+varying vec4 bg;
+varying float vb;
+varying float background;
+
+uniform int gammaCorrect;
+uniform sampler2D mask;
+
+// Conversion between sRGB and linear light
+vec3 toLinear(vec3 c)
+{
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+vec3 toSrgb(vec3 c)
+{
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+void main()
+{
+    if (background > 0.5) {
+        if (bg.a == 0.0)
+            discard;
+
+        // Colors are premultiplied like the window background
+        gl_FragColor = vec4(min(bg.rgb + vb, vec3(1.0)) * bg.a, bg.a);
+    } else {
+        // Without dual-source blending the mask can only be a single alpha value
+        vec3 textColor = texture2D(mask, TexCoords).rgb;
+        float alpha = max(max(textColor.r, textColor.g), textColor.b);
+
+        if (gammaCorrect != 0) {
+            // Blend with the background of the cell in linear light, the
+            // premultiplied color gives the same result over that background
+            vec3 cellBg = min(bg.rgb + vb, vec3(1.0));
+            vec3 target = toSrgb(mix(toLinear(cellBg), toLinear(fg), vec3(alpha)));
+            gl_FragColor = vec4(target - cellBg * (1.0 - alpha), alpha);
+        } else {
+            gl_FragColor = vec4(fg * alpha, alpha);
+        }
+    }
+}
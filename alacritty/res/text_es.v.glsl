@@ -0,0 +1,85 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#version 100
+attribute vec2 position;
+
+// Cell properties
+attribute vec2 gridCoords;
+
+// glyph properties
+attribute vec4 glyph;
+
+// uv mapping
+attribute vec4 uv;
+
+// text fg color
+attribute vec3 textColor;
+// Background color
+attribute vec4 backgroundColor;
+
+varying vec2 TexCoords;
+varying vec3 fg;
+varying vec4 bg;
+varying float vb;
+varying float background;
+
+// Terminal properties
+uniform vec2 termDim;
+uniform vec2 cellDim;
+
+uniform float visualBell;
+uniform int backgroundPass;
+
+// Orthographic projection
+uniform mat4 projection;
+
+void main()
+{
+    vec2 glyphOffset = glyph.xy;
+    vec2 glyphSize = glyph.zw;
+    vec2 uvOffset = uv.xy;
+    vec2 uvSize = uv.zw;
+
+    // Position of cell from top-left
+    vec2 cellPosition = cellDim * gridCoords;
+
+    // Invert Y since framebuffer origin is bottom-left
+    cellPosition.y = termDim.y - cellPosition.y - cellDim.y;
+
+    if (backgroundPass == 2) {
+        // Rectangles are offset from the top-left of their cell
+        vec2 rectOffset = vec2(glyphOffset.x, cellDim.y - glyphOffset.y - glyphSize.y);
+        vec2 finalPosition = glyphSize * position + cellPosition + rectOffset;
+        gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
+        TexCoords = vec2(0.0, 0.0);
+    } else if (backgroundPass != 0) {
+        vec2 finalPosition = cellDim * position + cellPosition;
+        gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
+        TexCoords = vec2(0.0, 0.0);
+    } else {
+        // Glyphs are offset within their cell; account for y-flip
+        vec2 cellOffset = vec2(glyphOffset.x, glyphOffset.y - glyphSize.y);
+
+        // position coordinates are normalized on [0, 1]
+        vec2 finalPosition = glyphSize * position + cellPosition + cellOffset;
+
+        gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
+        TexCoords = uvOffset + vec2(position.x, 1.0 - position.y) * uvSize;
+    }
+
+    vb = visualBell;
+    background = float(backgroundPass);
+    bg = vec4(backgroundColor.rgb / 255.0, backgroundColor.a);
+    fg = textColor / vec3(255.0, 255.0, 255.0);
+}
@@ -0,0 +1,30 @@
+#!/usr/bin/env bash
+
+# Replay recorded workloads through Alacritty's benchmark mode
+
+if [ ! -e target/release/alacritty ]
+then
+    echo "Must build alacritty first: cargo build --release"
+    exit 1
+fi
+
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+# Scrolling through a large file in vim
+echo "vim_large_window_scroll:"
+target/release/alacritty --benchmark tests/ref/vim_large_window_scroll/alacritty.recording
+
+# Listing a large directory tree with `ls -laR --color=always`
+echo "ls_lar:"
+target/release/alacritty --benchmark scripts/workloads/ls_lar.recording
+
+# Output of `yes`, read with the buffer size used before `debug.read_buffer_size`
+# was added and with its default
+yes | head -n 2000000 | sed 's/$/\r/' > "$tmp/yes.recording"
+for size in 4096 65536
+do
+    printf 'debug:\n  read_buffer_size: %s\n' "$size" > "$tmp/read_buffer_$size.yml"
+    echo "yes (read_buffer_size: $size):"
+    target/release/alacritty --config-file "$tmp/read_buffer_$size.yml" --benchmark "$tmp/yes.recording"
+done
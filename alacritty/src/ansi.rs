@@ -14,11 +14,17 @@
 //
 //! ANSI Terminal Stream Parsing
 use std::io;
+use std::mem;
 use std::ops::Range;
+use std::path::PathBuf;
+use std::char::REPLACEMENT_CHARACTER as REPLACEMENT_CHAR;
 use std::str;
+use std::time::{Duration, Instant};
 
 use vte;
 use base64;
+use url::Url;
+use url::percent_encoding::percent_decode;
 use index::{Column, Line, Contains};
 
 use ::{MouseCursor, Rgb};
@@ -97,15 +103,259 @@ fn parse_number(input: &[u8]) -> Option<u8> {
     Some(num)
 }
 
+// Parse the path of a `file://` URL, as sent by shells to report their
+// working directory
+fn parse_file_url(url: &[u8]) -> Option<PathBuf> {
+    let url = Url::parse(str::from_utf8(url).ok()?).ok()?;
+    if url.scheme() != "file" {
+        return None;
+    }
+
+    let path = percent_decode(url.path().as_bytes()).decode_utf8().ok()?;
+    Some(PathBuf::from(path.into_owned()))
+}
+
 /// The processor wraps a `vte::Parser` to ultimately call methods on a Handler
 pub struct Processor {
     state: ProcessorState,
     parser: vte::Parser,
+
+    /// Control string which is currently being received
+    string: Option<ControlString>,
+
+    /// The last byte was an escape, which might start a control string
+    after_escape: bool,
+
+    /// Longest control string which is passed on to the parser
+    max_string_len: usize,
+
+    /// Decodes the output before it's parsed
+    decoder: Utf8Decoder,
+}
+
+/// Incremental UTF-8 decoder in front of the parser
+///
+/// Ill-formed input is replaced with one U+FFFD per maximal subpart, as the
+/// Unicode standard recommends and `String::from_utf8_lossy` does. The byte
+/// which ends an incomplete sequence is decoded on its own, so it can't be
+/// swallowed. Stray bytes of binary output also can't reach the parser as C1
+/// controls, which would start escape sequences out of nowhere.
+///
+/// The decoder keeps its state between calls of `Processor::advance`, so a
+/// character split across two reads from the pty is decoded as usual.
+#[derive(Debug, Copy, Clone)]
+struct Utf8Decoder {
+    code_point: u32,
+
+    /// Continuation bytes of the current sequence
+    needed: u8,
+    seen: u8,
+
+    /// Range of the next continuation byte, which excludes overlong
+    /// encodings, surrogates and code points beyond U+10FFFF
+    lower: u8,
+    upper: u8,
+}
+
+impl Utf8Decoder {
+    fn new() -> Utf8Decoder {
+        Utf8Decoder {
+            code_point: 0,
+            needed: 0,
+            seen: 0,
+            lower: 0x80,
+            upper: 0xbf,
+        }
+    }
+
+    #[inline]
+    fn is_idle(&self) -> bool {
+        self.needed == 0
+    }
+
+    /// Decode the next byte, passing the completed characters to `emit`
+    fn advance<F: FnMut(char)>(&mut self, byte: u8, emit: &mut F) {
+        if !self.is_idle() {
+            if (self.lower..=self.upper).contains(&byte) {
+                self.code_point = (self.code_point << 6) | u32::from(byte & 0x3f);
+                self.seen += 1;
+                self.lower = 0x80;
+                self.upper = 0xbf;
+
+                if self.seen == self.needed {
+                    let c = char::from_u32(self.code_point).unwrap_or(REPLACEMENT_CHAR);
+                    *self = Utf8Decoder::new();
+                    emit(c);
+                }
+                return;
+            }
+
+            // The sequence is incomplete, the byte starts the next one
+            *self = Utf8Decoder::new();
+            emit(REPLACEMENT_CHAR);
+        }
+
+        match byte {
+            0x00..=0x7f => emit(byte as char),
+            0xc2..=0xdf => self.start(1, byte & 0x1f),
+            0xe0..=0xef => {
+                match byte {
+                    0xe0 => self.lower = 0xa0,
+                    0xed => self.upper = 0x9f,
+                    _ => (),
+                }
+                self.start(2, byte & 0x0f);
+            },
+            0xf0..=0xf4 => {
+                match byte {
+                    0xf0 => self.lower = 0x90,
+                    0xf4 => self.upper = 0x8f,
+                    _ => (),
+                }
+                self.start(3, byte & 0x07);
+            },
+            _ => emit(REPLACEMENT_CHAR),
+        }
+    }
+
+    #[inline]
+    fn start(&mut self, needed: u8, bits: u8) {
+        self.needed = needed;
+        self.code_point = u32::from(bits);
+    }
 }
 
 /// Internal state for VTE processor
 struct ProcessorState {
-    preceding_char: Option<char>
+    preceding_char: Option<char>,
+
+    /// Selector of a DECRQSS request which is being received
+    status_request: Option<Vec<u8>>,
+
+    /// Output diverted to the printer in printer controller mode
+    printer: Option<PrinterController>,
+
+    /// Parameter bytes of the CSI sequence which is being received
+    ///
+    /// The parser passes omitted parameters as zero, they're told apart from
+    /// an explicit zero with these.
+    csi_params: Option<Vec<u8>>,
+}
+
+/// Longest DECRQSS selector which is accepted
+const MAX_STATUS_REQUEST_LEN: usize = 8;
+
+/// Parameter bytes of a CSI sequence which are kept
+const MAX_CSI_PARAMS_LEN: usize = 32;
+
+/// Longest OSC or DCS payload which is accepted by default
+///
+/// The limit ends strings which would swallow the following output. The
+/// parser itself keeps only the first 1024 bytes of an OSC and drops the rest,
+/// so the payload of a long OSC never reaches the handler.
+pub const DEFAULT_MAX_STRING_LEN: usize = 100 * 1024;
+
+/// Kind of a control string, which decides how it ends
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum StringKind {
+    /// Operating system command, ended by BEL or ST
+    Osc,
+
+    /// Device control string, or a string ignored by the parser (SOS, PM, APC)
+    Dcs,
+}
+
+/// Control string started by the latest escape sequence
+///
+/// Nothing reaches the grid until the string is ended, so output which never
+/// ends it would be swallowed. Once a string is longer than the limit, the
+/// parser is reset and the rest of the string is discarded.
+struct ControlString {
+    kind: StringKind,
+    len: usize,
+
+    /// The string was too long, its remaining bytes are discarded
+    aborted: bool,
+}
+
+impl ControlString {
+    fn new(kind: StringKind) -> ControlString {
+        ControlString {
+            kind,
+            len: 0,
+            aborted: false,
+        }
+    }
+
+    /// Whether the byte ends the string
+    ///
+    /// An escape ends every string, either as the start of ST or by starting
+    /// another sequence.
+    fn ends_with(&self, byte: u8) -> bool {
+        match byte {
+            0x18 | 0x1a | 0x1b => true,
+            0x07 => self.kind == StringKind::Osc,
+            0x9c => self.kind == StringKind::Dcs,
+            _ => false,
+        }
+    }
+}
+
+/// Sequence which ends printer controller mode (`CSI 4 i`)
+const PRINTER_EXIT: &[u8] = b"\x1b[4i";
+
+/// Most bytes diverted to the printer at once
+const MAX_PRINTER_LEN: usize = 1 << 20;
+
+/// Longest time printer controller mode can be active
+const PRINTER_TIMEOUT_SECS: u64 = 10;
+
+/// Printer controller mode started by `CSI 5 i`
+///
+/// Every byte is sent to the printer until `CSI 4 i`, without reaching the
+/// grid. A missing exit sequence would swallow everything the shell writes,
+/// so the mode also ends once too much output was diverted or too much time has
+/// passed.
+struct PrinterController {
+    data: Vec<u8>,
+
+    /// Length of the prefix of `PRINTER_EXIT` matched by the latest bytes
+    matched: usize,
+
+    started: Instant,
+}
+
+impl PrinterController {
+    fn new() -> PrinterController {
+        PrinterController {
+            data: Vec::new(),
+            matched: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Divert a byte to the printer, returns true when the mode was ended
+    fn advance(&mut self, byte: u8) -> bool {
+        if byte == PRINTER_EXIT[self.matched] {
+            self.matched += 1;
+            return self.matched == PRINTER_EXIT.len();
+        }
+
+        self.data.extend_from_slice(&PRINTER_EXIT[..self.matched]);
+        if byte == PRINTER_EXIT[0] {
+            self.matched = 1;
+        } else {
+            self.matched = 0;
+            self.data.push(byte);
+        }
+
+        false
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.data.len() >= MAX_PRINTER_LEN
+            || self.started.elapsed() >= Duration::from_secs(PRINTER_TIMEOUT_SECS)
+    }
 }
 
 /// Helper type that implements `vte::Perform`.
@@ -113,9 +363,13 @@ struct ProcessorState {
 /// Processor creates a Performer when running advance and passes the Performer
 /// to `vte::Parser`.
 struct Performer<'a, H: Handler + TermInfo + 'a, W: io::Write + 'a> {
-    _state: &'a mut ProcessorState,
+    state: &'a mut ProcessorState,
     handler: &'a mut H,
-    writer: &'a mut W
+    writer: &'a mut W,
+
+    /// Byte which is being parsed, the parser doesn't pass on the final
+    /// character of a DCS
+    byte: u8,
 }
 
 impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
@@ -125,11 +379,13 @@ impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
         state: &'b mut ProcessorState,
         handler: &'b mut H,
         writer: &'b mut W,
+        byte: u8,
     ) -> Performer<'b, H, W> {
         Performer {
-            _state: state,
+            state,
             handler,
             writer,
+            byte,
         }
     }
 }
@@ -137,8 +393,17 @@ impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
 impl Default for Processor {
     fn default() -> Processor {
         Processor {
-            state: ProcessorState { preceding_char: None },
+            state: ProcessorState {
+                preceding_char: None,
+                status_request: None,
+                printer: None,
+                csi_params: None,
+            },
             parser: vte::Parser::new(),
+            string: None,
+            after_escape: false,
+            max_string_len: DEFAULT_MAX_STRING_LEN,
+            decoder: Utf8Decoder::new(),
         }
     }
 }
@@ -148,6 +413,29 @@ impl Processor {
         Default::default()
     }
 
+    /// Set the longest OSC or DCS payload which is accepted
+    pub fn set_max_string_len(&mut self, len: usize) {
+        self.max_string_len = len;
+    }
+
+    /// Bytes kept by the processor for sequences which aren't complete yet
+    ///
+    /// The parser of `vte` keeps the parameters and the payload of an OSC in
+    /// arrays of a fixed size, only the buffers of the processor can grow.
+    #[cfg(test)]
+    pub fn buffered_len(&self) -> usize {
+        let state = &self.state;
+        state.status_request.as_ref().map_or(0, Vec::len)
+            + state.csi_params.as_ref().map_or(0, Vec::len)
+            + state.printer.as_ref().map_or(0, |printer| printer.data.len())
+    }
+
+    /// Most bytes which are kept for incomplete sequences
+    #[cfg(test)]
+    pub fn max_buffered_len() -> usize {
+        MAX_STATUS_REQUEST_LEN + MAX_CSI_PARAMS_LEN + MAX_PRINTER_LEN + PRINTER_EXIT.len()
+    }
+
     #[inline]
     pub fn advance<H, W>(
         &mut self,
@@ -158,9 +446,141 @@ impl Processor {
         where H: Handler + TermInfo,
               W: io::Write
     {
-        let mut performer = Performer::new(&mut self.state, handler, writer);
+        if let Some(mut printer) = self.state.printer.take() {
+            if printer.advance(byte) {
+                handler.printer_output(&printer.data);
+                return;
+            }
+
+            if !printer.is_exhausted() {
+                self.state.printer = Some(printer);
+                return;
+            }
+
+            warn!("Printer controller mode was not ended, returning to the terminal");
+            handler.printer_output(&printer.data);
+        }
+
+        if byte < 0x80 && self.decoder.is_idle() {
+            self.parse(handler, byte, writer);
+            return;
+        }
+
+        // Outside of a character, these bytes can't be part of valid UTF-8, but
+        // they're only taken for controls when 8-bit controls were enabled
+        if (0x80..=0x9f).contains(&byte) && self.decoder.is_idle() && handler.eight_bit_controls() {
+            if byte == 0x9b {
+                self.state.csi_params = Some(Vec::new());
+            }
+            self.parse(handler, byte, writer);
+            if self.string.is_none() {
+                // Single byte forms of `ESC ]`, `ESC P` and the like
+                self.string = string_kind(byte - 0x40).map(ControlString::new);
+            }
+            return;
+        }
+
+        // The parser gets valid UTF-8 only, decoded characters are encoded again
+        let mut decoder = self.decoder;
+        decoder.advance(byte, &mut |c| {
+            // C1 controls aren't interpreted, a UTF-8 terminal has no use for them
+            if ('\u{80}'..='\u{9f}').contains(&c) {
+                return;
+            }
+
+            let mut bytes = [0u8; 4];
+            for &byte in c.encode_utf8(&mut bytes).as_bytes() {
+                self.parse(handler, byte, writer);
+            }
+        });
+        self.decoder = decoder;
+    }
+
+    #[inline]
+    fn parse<H, W>(&mut self, handler: &mut H, byte: u8, writer: &mut W)
+        where H: Handler + TermInfo,
+              W: io::Write
+    {
+        let after_escape = self.after_escape;
+        if !self.track_string(byte) {
+            return;
+        }
+        self.track_csi_params(after_escape, byte);
+
+        let mut performer = Performer::new(&mut self.state, handler, writer, byte);
         self.parser.advance(&mut performer, byte);
     }
+
+    /// Keep track of the control string being received
+    ///
+    /// Returns false for bytes of an aborted string, which are discarded.
+    fn track_string(&mut self, byte: u8) -> bool {
+        let after_escape = mem::replace(&mut self.after_escape, byte == 0x1b);
+
+        if let Some(mut string) = self.string.take() {
+            if string.ends_with(byte) {
+                // The parser has to see the escape, since it starts the next sequence
+                return !string.aborted || byte == 0x1b;
+            }
+
+            string.len += 1;
+            if !string.aborted && string.len > self.max_string_len {
+                warn!("Discarding control string longer than {} bytes", self.max_string_len);
+                self.parser = vte::Parser::new();
+                self.state.status_request = None;
+                string.aborted = true;
+            }
+
+            let aborted = string.aborted;
+            self.string = Some(string);
+            return !aborted;
+        }
+
+        if after_escape {
+            self.string = string_kind(byte).map(ControlString::new);
+        }
+
+        true
+    }
+
+    /// Keep the parameter bytes of the CSI sequence being received
+    ///
+    /// The bytes are taken by the dispatch of the sequence.
+    fn track_csi_params(&mut self, after_escape: bool, byte: u8) {
+        let params = &mut self.state.csi_params;
+        match byte {
+            b'[' if after_escape => *params = Some(Vec::new()),
+            0x1b => *params = None,
+            0x30..=0x3f => {
+                if let Some(ref mut params) = *params {
+                    if params.len() < MAX_CSI_PARAMS_LEN {
+                        params.push(byte);
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+/// Check if the parameter at `idx` of a CSI sequence was omitted
+///
+/// Parameters past the kept bytes count as present.
+fn is_omitted_param(params: &[u8], idx: usize) -> bool {
+    if params.len() >= MAX_CSI_PARAMS_LEN {
+        return false;
+    }
+
+    params.split(|&byte| byte == b';').nth(idx).map_or(true, |param| param.is_empty())
+}
+
+/// Kind of the control string started by an escape followed by `byte`
+fn string_kind(byte: u8) -> Option<StringKind> {
+    match byte {
+        b']' => Some(StringKind::Osc),
+        b'P' | b'X' | b'^' | b'_' => Some(StringKind::Dcs),
+        _ => None,
+    }
 }
 
 
@@ -168,6 +588,11 @@ impl Processor {
 pub trait TermInfo {
     fn lines(&self) -> Line;
     fn cols(&self) -> Column;
+
+    /// Raw C1 bytes are interpreted as controls (S8C1T)
+    fn eight_bit_controls(&self) -> bool {
+        false
+    }
 }
 
 /// Type that handles actions from the parser
@@ -178,6 +603,15 @@ pub trait Handler {
     /// OSC to set window title
     fn set_title(&mut self, &str) {}
 
+    /// OSC to report the working directory of the shell
+    fn set_current_dir(&mut self, PathBuf) {}
+
+    /// Send output of printer controller mode to the printer
+    fn printer_output(&mut self, _: &[u8]) {}
+
+    /// Send the text of the screen to the printer
+    fn print_screen(&mut self) {}
+
     /// Set the window's mouse cursor
     fn set_mouse_cursor(&mut self, MouseCursor) {}
 
@@ -207,12 +641,27 @@ pub trait Handler {
 
     /// Identify the terminal (should write back to the pty stream)
     ///
+    /// The intermediate is `Some('>')` when the secondary device attributes
+    /// are requested.
+    ///
     /// TODO this should probably return an io::Result
-    fn identify_terminal<W: io::Write>(&mut self, &mut W) {}
+    fn identify_terminal<W: io::Write>(&mut self, &mut W, Option<char>) {}
 
     // Report device status
     fn device_status<W: io::Write>(&mut self, &mut W, usize) {}
 
+    /// Send the answerback in reply to ENQ
+    fn answerback<W: io::Write>(&mut self, &mut W) {}
+
+    /// Report the size of a cell in pixels (`CSI 16 t`)
+    fn report_cell_size<W: io::Write>(&mut self, &mut W) {}
+
+    /// Resize the window (`CSI 4 t` and `CSI 8 t`)
+    fn resize_window(&mut self, WindowSize) {}
+
+    /// DECRQSS - Report the setting identified by the selector
+    fn request_status_string<W: io::Write>(&mut self, &mut W, &[u8]) {}
+
     /// Move cursor forward `cols`
     fn move_forward(&mut self, Column) {}
 
@@ -246,6 +695,8 @@ pub trait Handler {
     fn substitute(&mut self) {}
 
     /// Newline
+    ///
+    /// A linefeed, which also returns the carriage in line feed/new line mode.
     fn newline(&mut self) {}
 
     /// Set current position as a tabstop
@@ -275,6 +726,12 @@ pub trait Handler {
     /// to the right of the deleted things is shifted left.
     fn delete_chars(&mut self, Column) {}
 
+    /// Shift the lines of the scrolling region `count` columns to the left
+    fn scroll_left(&mut self, Column) {}
+
+    /// Shift the lines of the scrolling region `count` columns to the right
+    fn scroll_right(&mut self, Column) {}
+
     /// Move backward `count` tabs
     fn move_backward_tabs(&mut self, _count: i64) {}
 
@@ -287,18 +744,48 @@ pub trait Handler {
     /// Restore cursor position
     fn restore_cursor_position(&mut self) {}
 
+    /// Set the left and right margins with DECSLRM
+    ///
+    /// The right margin is exclusive, `None` is the right edge of the screen.
+    /// DECSLRM shares `CSI s` with saving the cursor position, which it is
+    /// without left/right margin mode.
+    fn set_horizontal_margins(&mut self, _left: Column, _right: Option<Column>) {
+        self.save_cursor_position();
+    }
+
+    /// Report the state of a mode with DECRQM
+    fn report_mode<W: io::Write>(&mut self, _: &mut W, _private: bool, _mode: i64) {}
+
     /// Clear current line
     fn clear_line(&mut self, _mode: LineClearMode) {}
 
     /// Clear screen
     fn clear_screen(&mut self, _mode: ClearMode) {}
 
+    /// Protect the characters written next from selective erases (DECSCA)
+    fn set_character_protection(&mut self, _protected: bool) {}
+
+    /// Clear the unprotected characters of the current line (DECSEL)
+    fn selective_clear_line(&mut self, _mode: LineClearMode) {}
+
+    /// Clear the unprotected characters of the screen (DECSED)
+    fn selective_clear_screen(&mut self, _mode: ClearMode) {}
+
     /// Clear tab stops
     fn clear_tabs(&mut self, _mode: TabulationClearMode) {}
 
     /// Reset terminal state
     fn reset_state(&mut self) {}
 
+    /// S8C1T or S7C1T - Use 8-bit or 7-bit controls
+    ///
+    /// With 8-bit controls, raw C1 bytes are interpreted and replies are sent
+    /// with single byte C1 controls.
+    fn set_eight_bit_controls(&mut self, bool) {}
+
+    /// Soft terminal reset (DECSTR), which keeps the screen contents
+    fn soft_reset(&mut self) {}
+
     /// Reverse Index
     ///
     /// Move the active position to the same horizontal position on the
@@ -345,12 +832,29 @@ pub trait Handler {
     /// Set the clipboard
     fn set_clipboard(&mut self, &str) {}
 
+    /// Report the contents of the clipboard
+    ///
+    /// The selection is the first parameter of the query, which is echoed.
+    fn report_clipboard<W: io::Write>(&mut self, &mut W, _selection: &[u8]) {}
+
     /// Run the dectest routine
     fn dectest(&mut self) {}
 }
 
+/// Window size requested with XTWINOPS
+///
+/// Dimensions are `None` to keep the current size, and zero to fill the
+/// screen in that direction.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum WindowSize {
+    /// Size of the text area in cells (`CSI 8 ; lines ; columns t`)
+    Cells { lines: Option<usize>, columns: Option<usize> },
+    /// Size of the window in pixels (`CSI 4 ; height ; width t`)
+    Pixels { height: Option<usize>, width: Option<usize> },
+}
+
 /// Describes shape of cursor
-#[derive(Debug, Eq, PartialEq, Copy, Clone, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum CursorStyle {
     /// Cursor is a block like `▒`
     Block,
@@ -372,7 +876,7 @@ impl Default for CursorStyle {
 }
 
 /// Terminal modes
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum Mode {
     /// ?1
     CursorKeys = 1,
@@ -408,6 +912,10 @@ pub enum Mode {
     LineFeedNewLine = 20,
     /// ?25
     ShowCursor = 25,
+    /// ?69
+    ///
+    /// DECLRMM, enables the left and right margins set with DECSLRM.
+    LeftRightMargin = 69,
     /// ?1000
     ReportMouseClicks = 1000,
     /// ?1002
@@ -416,8 +924,16 @@ pub enum Mode {
     ReportAllMouseMotion = 1003,
     /// ?1004
     ReportFocusInOut = 1004,
+    /// ?1005
+    ///
+    /// Coordinates of mouse reports are encoded as UTF-8.
+    Utf8Mouse = 1005,
     /// ?1006
     SgrMouse = 1006,
+    /// ?1015
+    ///
+    /// Mouse reports use decimal parameters, as in urxvt.
+    UrxvtMouse = 1015,
     /// ?1049
     SwapScreenAndSetRestoreCursor = 1049,
     /// ?2004
@@ -437,11 +953,14 @@ impl Mode {
                 7 => Mode::LineWrap,
                 12 => Mode::BlinkingCursor,
                 25 => Mode::ShowCursor,
+                69 => Mode::LeftRightMargin,
                 1000 => Mode::ReportMouseClicks,
                 1002 => Mode::ReportCellMouseMotion,
                 1003 => Mode::ReportAllMouseMotion,
                 1004 => Mode::ReportFocusInOut,
+                1005 => Mode::Utf8Mouse,
                 1006 => Mode::SgrMouse,
+                1015 => Mode::UrxvtMouse,
                 1049 => Mode::SwapScreenAndSetRestoreCursor,
                 2004 => Mode::BracketedPaste,
                 _ => {
@@ -702,7 +1221,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
     #[inline]
     fn print(&mut self, c: char) {
         self.handler.input(c);
-        self._state.preceding_char = Some(c);
+        self.state.preceding_char = Some(c);
     }
 
     #[inline]
@@ -711,32 +1230,51 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             C0::HT => self.handler.put_tab(1),
             C0::BS => self.handler.backspace(),
             C0::CR => self.handler.carriage_return(),
-            C0::LF | C0::VT | C0::FF => self.handler.linefeed(),
+            C0::LF | C0::VT | C0::FF => self.handler.newline(),
             C0::BEL => self.handler.bell(),
+            C0::ENQ => self.handler.answerback(self.writer),
             C0::SUB => self.handler.substitute(),
             C0::SI => self.handler.set_active_charset(CharsetIndex::G0),
             C0::SO => self.handler.set_active_charset(CharsetIndex::G1),
-            C1::NEL => self.handler.newline(),
+            C1::IND => self.handler.linefeed(),
+            C1::NEL => {
+                self.handler.linefeed();
+                self.handler.carriage_return();
+            },
+            C1::RI => self.handler.reverse_index(),
             C1::HTS => self.handler.set_horizontal_tabstop(),
-            C1::DECID => self.handler.identify_terminal(self.writer),
+            C1::DECID => self.handler.identify_terminal(self.writer, None),
             _ => debug!("[unhandled] execute byte={:02x}", byte)
         }
     }
 
     #[inline]
     fn hook(&mut self, params: &[i64], intermediates: &[u8], ignore: bool) {
-        debug!("[unhandled hook] params={:?}, ints: {:?}, ignore: {:?}",
-                     params, intermediates, ignore);
+        // The parser hooks on the final character, which is the current byte
+        let decrqss = intermediates == b"$" && self.byte == b'q';
+        if !ignore && decrqss && params.iter().all(|&param| param == 0) {
+            self.state.status_request = Some(Vec::new());
+        } else {
+            debug!("[unhandled hook] params={:?}, ints: {:?}, ignore: {:?}",
+                         params, intermediates, ignore);
+        }
     }
 
     #[inline]
     fn put(&mut self, byte: u8) {
-        debug!("[unhandled put] byte={:?}", byte);
+        match self.state.status_request {
+            Some(ref mut request) if request.len() < MAX_STATUS_REQUEST_LEN => request.push(byte),
+            Some(_) => (),
+            None => debug!("[unhandled put] byte={:?}", byte),
+        }
     }
 
     #[inline]
     fn unhook(&mut self) {
-        debug!("[unhandled unhook]");
+        match self.state.status_request.take() {
+            Some(request) => self.handler.request_status_string(self.writer, &request),
+            None => debug!("[unhandled unhook]"),
+        }
     }
 
     // TODO replace OSC parsing with parser combinators
@@ -761,11 +1299,11 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
         match params[0] {
             // Set window title
             b"0" | b"2" => {
+                // Semicolons are valid in titles, so they are joined back together
                 if params.len() >= 2 {
-                    if let Ok(utf8_title) = str::from_utf8(params[1]) {
-                        self.handler.set_title(utf8_title);
-                        return;
-                    }
+                    let title = params[1..].join(&b';');
+                    self.handler.set_title(&String::from_utf8_lossy(&title));
+                    return;
                 }
                 unhandled(params);
             },
@@ -774,6 +1312,18 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             // This is ignored, since alacritty has no concept of tabs
             b"1" => return,
 
+            // Set current working directory
+            b"7" => {
+                // Semicolons are valid in paths, so they are joined back together
+                if params.len() >= 2 {
+                    if let Some(path) = parse_file_url(&params[1..].join(&b';')) {
+                        self.handler.set_current_dir(path);
+                        return;
+                    }
+                }
+                unhandled(params);
+            },
+
             // Set color index
             b"4" => {
                 if params.len() > 1 && params.len() % 2 != 0 {
@@ -844,7 +1394,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 }
 
                 match params[2] {
-                    b"?" => unhandled(params),
+                    b"?" => self.handler.report_clipboard(self.writer, params[1]),
                     selection => {
                         if let Ok(string) = base64::decode(selection) {
                             if let Ok(utf8_string) = str::from_utf8(&string) {
@@ -896,6 +1446,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
         action: char
     ) {
         let private = intermediates.get(0).map(|b| *b == b'?').unwrap_or(false);
+        let params = self.state.csi_params.take();
         let handler = &mut self.handler;
         let writer = &mut self.writer;
 
@@ -920,12 +1471,18 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
         }
 
         match action {
+            '@' if intermediates == b" " => {
+                handler.scroll_left(Column(arg_or_default!(idx: 0, default: 1) as usize))
+            },
+            'A' if intermediates == b" " => {
+                handler.scroll_right(Column(arg_or_default!(idx: 0, default: 1) as usize))
+            },
             '@' => handler.insert_blank(Column(arg_or_default!(idx: 0, default: 1) as usize)),
             'A' => {
                 handler.move_up(Line(arg_or_default!(idx: 0, default: 1) as usize));
             },
             'b' => {
-                if let Some(c) = self._state.preceding_char {
+                if let Some(c) = self.state.preceding_char {
                     for _ in 0..arg_or_default!(idx: 0, default: 1) {
                         handler.input(c);
                     }
@@ -935,7 +1492,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 }
             },
             'B' | 'e' => handler.move_down(Line(arg_or_default!(idx: 0, default: 1) as usize)),
-            'c' => handler.identify_terminal(writer),
+            'c' => handler.identify_terminal(writer, intermediates.get(0).map(|&i| i as char)),
             'C' | 'a' => handler.move_forward(Column(arg_or_default!(idx: 0, default: 1) as usize)),
             'D' => handler.move_backward(Column(arg_or_default!(idx: 0, default: 1) as usize)),
             'E' => handler.move_down_and_cr(Line(arg_or_default!(idx: 0, default: 1) as usize)),
@@ -961,11 +1518,15 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                     0 => ClearMode::Below,
                     1 => ClearMode::Above,
                     2 => ClearMode::All,
-                    3 => ClearMode::Saved,
+                    3 if !private => ClearMode::Saved,
                     _ => unhandled!(),
                 };
 
-                handler.clear_screen(mode);
+                if private {
+                    handler.selective_clear_screen(mode);
+                } else {
+                    handler.clear_screen(mode);
+                }
             },
             'K' => {
                 let mode = match arg_or_default!(idx: 0, default: 0) {
@@ -975,7 +1536,11 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                     _ => unhandled!(),
                 };
 
-                handler.clear_line(mode);
+                if private {
+                    handler.selective_clear_line(mode);
+                } else {
+                    handler.clear_line(mode);
+                }
             },
             'S' => handler.scroll_up(Line(arg_or_default!(idx: 0, default: 1) as usize)),
             'T' => handler.scroll_down(Line(arg_or_default!(idx: 0, default: 1) as usize)),
@@ -1094,7 +1659,18 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                     i += 1; // C-for expr
                 }
             }
+            'i' if !private => match arg_or_default!(idx: 0, default: 0) {
+                0 => handler.print_screen(),
+                // Already outside of printer controller mode
+                4 => (),
+                5 => self.state.printer = Some(PrinterController::new()),
+                _ => unhandled!(),
+            },
             'n' => handler.device_status(writer, arg_or_default!(idx: 0, default: 0) as usize),
+            'p' if intermediates == b"!" => handler.soft_reset(),
+            'p' if intermediates.last() == Some(&b'$') => {
+                handler.report_mode(writer, private, arg_or_default!(idx: 0, default: 0))
+            },
             'r' => {
                 if private {
                     unhandled!();
@@ -1110,8 +1686,34 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
 
                 handler.set_scrolling_region(top..bottom);
             },
-            's' => handler.save_cursor_position(),
+            's' => {
+                let left = Column(arg_or_default!(idx: 0, default: 1) as usize - 1);
+                let right = args.get(1).and_then(|&right| {
+                    if right == 0 { None } else { Some(Column(right as usize)) }
+                });
+                handler.set_horizontal_margins(left, right);
+            },
+            't' => {
+                // Omitted sizes are kept, while zero fills the screen
+                let arg = |idx: usize| match params {
+                    Some(ref params) if is_omitted_param(params, idx) => None,
+                    _ => args.get(idx).map(|&arg| arg as usize),
+                };
+                match arg_or_default!(idx: 0, default: 0) {
+                    4 => handler.resize_window(WindowSize::Pixels { height: arg(1), width: arg(2) }),
+                    8 => handler.resize_window(WindowSize::Cells { lines: arg(1), columns: arg(2) }),
+                    16 => handler.report_cell_size(writer),
+                    _ => unhandled!(),
+                }
+            },
             'u' => handler.restore_cursor_position(),
+            'q' if intermediates == b"\"" => {
+                match arg_or_default!(idx: 0, default: 0) {
+                    0 | 2 => handler.set_character_protection(false),
+                    1 => handler.set_character_protection(true),
+                    _ => unhandled!(),
+                }
+            },
             'q' => {
                 let style = match arg_or_default!(idx: 0, default: 0) {
                     0 => None,
@@ -1157,6 +1759,8 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
         }
 
         match byte {
+            b'F' if intermediates == b" " => self.handler.set_eight_bit_controls(false),
+            b'G' if intermediates == b" " => self.handler.set_eight_bit_controls(true),
             b'B' => configure_charset!(StandardCharset::Ascii),
             b'D' => self.handler.linefeed(),
             b'E' => {
@@ -1165,7 +1769,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             }
             b'H' => self.handler.set_horizontal_tabstop(),
             b'M' => self.handler.reverse_index(),
-            b'Z' => self.handler.identify_terminal(self.writer),
+            b'Z' => self.handler.identify_terminal(self.writer, None),
             b'c' => self.handler.reset_state(),
             b'0' => configure_charset!(StandardCharset::SpecialCharacterAndLineDrawing),
             b'7' => self.handler.save_cursor_position(),
@@ -1394,7 +1998,8 @@ pub mod C1 {
 mod tests {
     use std::io;
     use index::{Line, Column};
-    use super::{Processor, Handler, Attr, TermInfo, Color, StandardCharset, CharsetIndex, parse_rgb_color, parse_number};
+    use std::path::PathBuf;
+    use super::{Processor, Handler, Attr, TermInfo, Color, StandardCharset, CharsetIndex, Mode, Utf8Decoder, WindowSize, parse_rgb_color, parse_number, parse_file_url};
     use ::Rgb;
 
     /// The /dev/null of `io::Write`
@@ -1563,6 +2168,291 @@ mod tests {
         assert_eq!(handler.index, CharsetIndex::G1);
     }
 
+    #[derive(Default)]
+    struct PrinterHandler {
+        printed: Vec<Vec<u8>>,
+        input: String,
+    }
+
+    impl Handler for PrinterHandler {
+        fn input(&mut self, c: char) {
+            self.input.push(c);
+        }
+
+        fn printer_output(&mut self, data: &[u8]) {
+            self.printed.push(data.to_vec());
+        }
+    }
+
+    impl TermInfo for PrinterHandler {
+        fn lines(&self) -> Line { Line(24) }
+        fn cols(&self) -> Column { Column(80) }
+    }
+
+    #[test]
+    fn printer_controller_mode() {
+        static BYTES: &'static [u8] = b"a\x1b[5ib\x1b[1m\x1b[4\x1b[4ic";
+        let mut parser = Processor::new();
+        let mut handler = PrinterHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.input, "ac");
+        assert_eq!(handler.printed, vec![b"b\x1b[1m\x1b[4".to_vec()]);
+    }
+
+    #[derive(Default)]
+    struct TitleHandler {
+        titles: Vec<String>,
+        input: String,
+    }
+
+    impl Handler for TitleHandler {
+        fn input(&mut self, c: char) {
+            self.input.push(c);
+        }
+
+        fn set_title(&mut self, title: &str) {
+            self.titles.push(title.to_owned());
+        }
+    }
+
+    impl TermInfo for TitleHandler {
+        fn lines(&self) -> Line { Line(24) }
+        fn cols(&self) -> Column { Column(80) }
+    }
+
+    #[test]
+    fn long_control_strings_are_discarded() {
+        let mut parser = Processor::new();
+        parser.set_max_string_len(16);
+        let mut handler = TitleHandler::default();
+
+        let mut bytes = b"\x1b]0;short\x07".to_vec();
+        bytes.extend_from_slice(b"\x1b]0;");
+        bytes.extend_from_slice(&[b'x'; 64]);
+        bytes.extend_from_slice(b"\x07a\x1bP$");
+        bytes.extend_from_slice(&[b'y'; 64]);
+        bytes.extend_from_slice(b"\x1b\\b\x1b]0;semi;colon\x1b\\");
+
+        for byte in &bytes {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.titles, vec![String::from("short"), String::from("semi;colon")]);
+        assert_eq!(handler.input, "ab");
+    }
+
+    /// Xorshift generator, for reproducible random input
+    struct Random(u32);
+
+    impl Random {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        /// Bytes which are likely to form UTF-8, valid or not
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| {
+                let byte = self.next() as u8;
+                match self.next() % 4 {
+                    0 => 0x80 | (byte & 0x3f),
+                    1 => 0xc0 | (byte & 0x3f),
+                    _ => byte,
+                }
+            }).collect()
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> String {
+        let mut decoder = Utf8Decoder::new();
+        let mut decoded = String::new();
+        for &byte in bytes {
+            decoder.advance(byte, &mut |c| decoded.push(c));
+        }
+
+        if !decoder.is_idle() {
+            decoded.push('\u{fffd}');
+        }
+
+        decoded
+    }
+
+    #[test]
+    fn utf8_decoder_replaces_maximal_subparts() {
+        let cases: &[&[u8]] = &[
+            b"\xc3\xa4\xe2\x9e\x9c\xf0\x9f\x92\x96",
+            // Overlong encodings, surrogates and code points beyond U+10FFFF
+            b"\xc0\xaf\xe0\x80\xaf\xed\xa0\x80\xf4\x90\x80\x80",
+            // Truncated sequences followed by another character
+            b"\xe2\x9ea\xf0\x9f\x92\xc3\xa4\xf0\x9f",
+            b"\x80\xbf\xfe\xff\xf5\x80",
+        ];
+
+        for bytes in cases {
+            assert_eq!(decode(bytes), String::from_utf8_lossy(bytes));
+        }
+    }
+
+    #[test]
+    fn utf8_decoder_matches_lossy_decoding() {
+        let mut random = Random(0x2545_f491);
+        for _ in 0..2000 {
+            let len = (random.next() % 16) as usize;
+            let bytes = random.bytes(len);
+            assert_eq!(decode(&bytes), String::from_utf8_lossy(&bytes), "{:x?}", bytes);
+        }
+    }
+
+    #[derive(Default)]
+    struct WindowSizeHandler {
+        sizes: Vec<WindowSize>,
+    }
+
+    impl Handler for WindowSizeHandler {
+        fn resize_window(&mut self, size: WindowSize) {
+            self.sizes.push(size);
+        }
+    }
+
+    impl TermInfo for WindowSizeHandler {
+        fn lines(&self) -> Line { Line(24) }
+        fn cols(&self) -> Column { Column(80) }
+    }
+
+    #[test]
+    fn window_resize_requests() {
+        let mut parser = Processor::new();
+        let mut handler = WindowSizeHandler::default();
+
+        for byte in b"\x1b[8;24;80t\x1b[8;0t\x1b[4;600;800t\x1b[4t\x1b[8;;80t\x1b[4;0;t" {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.sizes, vec![
+            WindowSize::Cells { lines: Some(24), columns: Some(80) },
+            WindowSize::Cells { lines: Some(0), columns: None },
+            WindowSize::Pixels { height: Some(600), width: Some(800) },
+            WindowSize::Pixels { height: None, width: None },
+            WindowSize::Cells { lines: None, columns: Some(80) },
+            WindowSize::Pixels { height: Some(0), width: None },
+        ]);
+    }
+
+    #[derive(Default)]
+    struct ModeHandler {
+        modes: Vec<Mode>,
+        input: String,
+        eight_bit_controls: bool,
+    }
+
+    impl Handler for ModeHandler {
+        fn input(&mut self, c: char) {
+            self.input.push(c);
+        }
+
+        fn set_eight_bit_controls(&mut self, enabled: bool) {
+            self.eight_bit_controls = enabled;
+        }
+
+        fn set_mode(&mut self, mode: Mode) {
+            self.modes.push(mode);
+        }
+
+        fn unset_mode(&mut self, mode: Mode) {
+            self.modes.push(mode);
+        }
+    }
+
+    impl TermInfo for ModeHandler {
+        fn lines(&self) -> Line { Line(24) }
+        fn cols(&self) -> Column { Column(80) }
+        fn eight_bit_controls(&self) -> bool { self.eight_bit_controls }
+    }
+
+    #[test]
+    fn c1_bytes_are_not_controls() {
+        static BYTES: &'static [u8] = b"a\x9b?1lb\xc2\x9b?1hc\x90q\x9c\xe2\x9e\x9c";
+        let mut parser = Processor::new();
+        let mut handler = ModeHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.input, "a\u{fffd}?1lb?1hc\u{fffd}q\u{fffd}\u{279c}");
+        assert!(handler.modes.is_empty());
+    }
+
+    #[test]
+    fn utf8_continuation_bytes_are_not_c1_controls() {
+        // 'Û' and '➛' end with 0x9b, the byte of CSI
+        static BYTES: &'static [u8] = b"\xc3\x9b?1h\xe2\x9e\x9b?1l\xe2\x9e\x9d0;x\x07";
+        let mut parser = Processor::new();
+        let mut handler = ModeHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.input, "\u{db}?1h\u{279b}?1l\u{279d}0;x");
+        assert!(handler.modes.is_empty());
+    }
+
+    #[test]
+    fn c1_controls_need_eight_bit_mode() {
+        static BYTES: &'static [u8] = b"\x9b?1h\x1b G\x9b?1h\xc3\x9b\x1b F\x9b?1l";
+        let mut parser = Processor::new();
+        let mut handler = ModeHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        // UTF-8 keeps working while single byte controls are interpreted
+        assert_eq!(handler.input, "\u{fffd}?1h\u{db}\u{fffd}?1l");
+        assert_eq!(handler.modes, vec![Mode::CursorKeys]);
+        assert!(!handler.eight_bit_controls);
+    }
+
+    #[test]
+    fn printable_output_cannot_change_modes() {
+        let mut random = Random(0x9e37_79b9);
+        let mut parser = Processor::new();
+        let mut handler = ModeHandler::default();
+
+        let mut expected = String::new();
+        for _ in 0..200 {
+            let len = (random.next() % 64) as usize;
+            let mut bytes: Vec<u8> = random.bytes(len)
+                .into_iter()
+                .filter(|&byte| byte >= 0x20 && byte != 0x7f)
+                .collect();
+            bytes.push(b'.');
+
+            expected.extend(String::from_utf8_lossy(&bytes).chars().filter(|&c| {
+                !('\u{80}'..='\u{9f}').contains(&c)
+            }));
+            for byte in bytes {
+                parser.advance(&mut handler, byte, &mut Void);
+            }
+        }
+
+        assert_eq!(handler.input, expected);
+        assert!(handler.modes.is_empty());
+
+        // The parser isn't stuck in a sequence
+        for &byte in b"\x1b[?1h" {
+            parser.advance(&mut handler, byte, &mut Void);
+        }
+        assert_eq!(handler.modes, vec![Mode::CursorKeys]);
+    }
+
     #[test]
     fn parse_valid_rgb_color() {
         assert_eq!(parse_rgb_color(b"rgb:11/aa/ff"), Some(Rgb { r: 0x11, g: 0xaa, b: 0xff }));
@@ -1583,6 +2473,21 @@ mod tests {
         assert_eq!(parse_number(b"123"), Some(123));
     }
 
+    #[test]
+    fn parse_valid_file_url() {
+        assert_eq!(
+            parse_file_url(b"file://host/home/user/my%20project"),
+            Some(PathBuf::from("/home/user/my project"))
+        );
+        assert_eq!(parse_file_url(b"file:///tmp"), Some(PathBuf::from("/tmp")));
+    }
+
+    #[test]
+    fn parse_invalid_file_url() {
+        assert_eq!(parse_file_url(b"https://host/tmp"), None);
+        assert_eq!(parse_file_url(b"/tmp"), None);
+    }
+
     #[test]
     fn parse_number_too_large() {
         assert_eq!(parse_number(b"321"), None);
@@ -28,37 +28,40 @@ extern crate alacritty;
 
 #[macro_use]
 extern crate log;
+extern crate mio_more;
 #[cfg(target_os = "macos")]
 extern crate dirs;
 
 use std::error::Error;
 use std::sync::Arc;
+use std::time::Instant;
 
 #[cfg(target_os = "macos")]
 use std::env;
 
-#[cfg(not(windows))]
-use std::os::unix::io::AsRawFd;
-
 #[cfg(windows)]
 extern crate winapi;
 #[cfg(windows)]
 use winapi::um::wincon::{AttachConsole, FreeConsole, ATTACH_PARENT_PROCESS};
 
 use alacritty::cli;
-use alacritty::config::{self, Config};
-use alacritty::display::{Display, InitialSize};
+use alacritty::config::{self, Config, StartupMode};
+use alacritty::display::{Display, DisplayCommand, InitialSize};
 use alacritty::event;
-use alacritty::event_loop::{self, EventLoop, Msg};
+use alacritty::event_loop::{self, EventLoop, Msg, QueuedBytes};
 #[cfg(target_os = "macos")]
 use alacritty::locale;
-use alacritty::logging;
+use alacritty::logging::{self, Messages};
+use alacritty::state;
 use alacritty::sync::FairMutex;
-use alacritty::term::Term;
-use alacritty::tty::{self, process_should_exit};
+use alacritty::term::{OnResize, Term};
+use alacritty::tty;
 use alacritty::util::fmt::Red;
+use alacritty::version;
 use alacritty::window::{Window, SetInnerSize, Size, Pixels};
 
+use mio_more::channel::Sender;
+
 fn main() {
     // When linked with the windows subsystem windows won't automatically attach
     // to the console of the parent process, so we do it explicitly. This fails
@@ -70,6 +73,16 @@ fn main() {
     let options = cli::Options::load();
     let config = load_config(&options).update_dynamic_title(&options);
 
+    if options.print_config {
+        print_config(&config.with_cli_overrides(&options));
+        cli::exit(0);
+    }
+
+    // Initialize the logger as soon as possible as to capture output from other
+    // subsystems. The log file is kept when exiting through `die!`.
+    let (log_file, messages) = logging::initialize(&options, &config)
+        .unwrap_or_else(|err| die!("Unable to initialize logger: {}", err));
+
     // Switch to home directory
     #[cfg(target_os = "macos")]
     env::set_current_dir(dirs::home_dir().unwrap()).unwrap();
@@ -78,11 +91,13 @@ fn main() {
     locale::set_locale_environment();
 
     // Run alacritty
-    if let Err(err) = run(config, &options) {
+    if let Err(err) = run(config, &options, messages) {
         die!("Alacritty encountered an unrecoverable error:\n\n\t{}\n", Red(err));
     }
 
     info!("Goodbye.");
+
+    drop(log_file);
 }
 
 /// Load configuration
@@ -99,9 +114,12 @@ fn load_config(options: &cli::Options) -> Config {
                 .unwrap_or_else(|err| die!("Write defaults config failure: {}", err))
         });
 
-    Config::load_from(&*config_path).unwrap_or_else(|err| {
-        eprintln!("Error: {}; Loading default config", err);
-        Config::default()
+    Config::load_from(&*config_path, options.strict_config).unwrap_or_else(|err| match err {
+        config::Error::Strict(_) => die!("{}", err),
+        _ => {
+            eprintln!("Error: {}; Loading default config", err);
+            Config::default()
+        },
     })
 }
 #[cfg(windows)]
@@ -114,7 +132,7 @@ fn load_config(options: &cli::Options) -> Config {
                 .unwrap_or_else(|err| die!("Write defaults config failure: {}", err))
         });
 
-    Config::load_from(&*config_path).unwrap_or_else(|err| match err {
+    Config::load_from(&*config_path, options.strict_config).unwrap_or_else(|err| match err {
         config::Error::NotFound => {
             die!("Config file not found after writing: {}", config_path.display());
         }
@@ -126,21 +144,38 @@ fn load_config(options: &cli::Options) -> Config {
     })
 }
 
+/// Print the resolved configuration as YAML
+///
+/// The header names the file the configuration was loaded from, since the
+/// dump includes every value using its default.
+fn print_config(config: &Config) {
+    match config.path() {
+        Some(path) => println!("# Configuration loaded from {}", path.display()),
+        None => println!("# No configuration file loaded, using the defaults"),
+    }
+
+    match config.to_yaml() {
+        Ok(yaml) => println!("{}", yaml),
+        Err(err) => die!("Unable to serialize config: {}", err),
+    }
+}
+
 /// Run Alacritty
 ///
 /// Creates a window, the terminal state, pty, I/O event loop, input processor,
 /// config change monitor, and runs the main display loop.
-fn run(mut config: Config, options: &cli::Options) -> Result<(), Box<Error>> {
-    // Initialize the logger first as to capture output from other subsystems
-    logging::initialize(options)?;
-
+fn run(mut config: Config, options: &cli::Options, messages: Messages) -> Result<(), Box<Error>> {
     info!("Welcome to Alacritty.");
+    info!("{}", version::Long);
     if let Some(config_path) = config.path() {
         info!("Configuration loaded from {}", config_path.display());
     };
 
     // Create the window where Alacritty will be displayed
-    let mut window = Window::new(&options, config.window())?;
+    let mut window = Window::new(&options, config.window(), config.debug().renderer_preference)?;
+
+    // Warnings and errors are shown in the message bar from now on
+    messages.set_waker(Box::new(window.notifier()));
 
     let dpr = window.hidpi_factor();
     info!("device_pixel_ratio: {}", dpr);
@@ -148,15 +183,36 @@ fn run(mut config: Config, options: &cli::Options) -> Result<(), Box<Error>> {
     // Create a display.
     //
     // The display is responsible for rendering the terminal into the current OpenGL context.
-    let dimensions = options.dimensions()
-        .unwrap_or_else(|| config.dimensions());
-    let mut display = Display::new(&config, InitialSize::Cells(dimensions), dpr)?;
+    //
+    // A size in pixels from the command line or the last session is turned
+    // into lines and columns once the font metrics are known.
+    let remembered_size = if config.window().remember_size() {
+        state::load_window_size()
+    } else {
+        None
+    };
+    let initial_size = match (options.dimensions(), options.window_size(), remembered_size) {
+        (Some(dimensions), ..) => InitialSize::Cells(dimensions),
+        (None, Some((width, height)), _) | (None, None, Some((width, height))) => {
+            InitialSize::Pixels(Size { width: Pixels(width), height: Pixels(height) })
+        },
+        (None, None, None) => InitialSize::Cells(config.dimensions()),
+    };
+    let mut display = Display::new(&config, initial_size, dpr)?;
+    display.set_messages(messages.clone());
     let viewport_size = Size {
         width: Pixels(display.size().width as u32),
         height: Pixels(display.size().height as u32),
     };
     info!("set_inner_size: {}", viewport_size);
     window.set_inner_size(&viewport_size);
+    window.set_resize_increments(display.size());
+
+    match config.window().startup_mode() {
+        StartupMode::Windowed => (),
+        StartupMode::Fullscreen => window.toggle_fullscreen(),
+        StartupMode::SimpleFullscreen => window.toggle_simple_fullscreen(),
+    }
 
     info!(
         "PTY Dimensions: {:?} x {:?}",
@@ -180,45 +236,44 @@ fn run(mut config: Config, options: &cli::Options) -> Result<(), Box<Error>> {
     // The pty forks a process to run the shell on the slave side of the
     // pseudoterminal. A file descriptor for the master side is retained for
     // reading/writing to the shell.
-    let pty = tty::new(&config, options, &display.size(), window_id);
-
-    // Get a reference to something that we can resize
-    //
-    // This exists because rust doesn't know the interface is thread-safe
-    // and we need to be able to resize the PTY from the main thread while the IO
-    // thread owns the EventedRW object.
-    #[cfg(windows)]
-    let resize_handle = unsafe { &mut *pty.winpty.get() };
-    #[cfg(not(windows))]
-    let resize_handle = &mut pty.fd.as_raw_fd();
-
-    // Create the pseudoterminal I/O loop
     //
-    // pty I/O is ran on another thread as to not occupy cycles used by the
-    // renderer and input processing. Note that access to the terminal state is
-    // synchronized since the I/O loop updates the state, and the display
-    // consumes it periodically.
-    let event_loop = EventLoop::new(
-        Arc::clone(&terminal),
-        Box::new(window.notifier()),
-        pty,
-        options.ref_test,
-    );
+    // When benchmarking, a recording is replayed instead of spawning a shell
+    // and the I/O thread is started right away.
+    let start = Instant::now();
+    let (loop_tx, queued_bytes, mut join_io_thread, replay_len, foreground) = match options.benchmark {
+        Some(ref path) => {
+            let replay = tty::Replay::new(path)?;
+            let len = replay.len();
+            let (loop_tx, queued, join) =
+                spawn_event_loop(&terminal, &window, replay, &config, options);
+            (loop_tx, queued, join, Some(len), tty::Foreground::default())
+        },
+        None => {
+            let pty = tty::new(&config, options, &display.size(), window_id);
+            let foreground = pty.foreground();
+            let (loop_tx, queued, join) =
+                spawn_event_loop(&terminal, &window, pty, &config, options);
+            (loop_tx, queued, join, None, foreground)
+        },
+    };
 
-    // The event loop channel allows write requests from the event processor
-    // to be sent to the loop and ultimately written to the pty.
-    let loop_tx = event_loop.channel();
+    // The pty is owned by the I/O thread, so resizes are sent through the
+    // event loop channel and applied there.
+    let mut pty_resizer = event_loop::Notifier(loop_tx.clone(), queued_bytes.clone());
 
     // Event processor
     //
     // Need the Rc<RefCell<_>> here since a ref is shared in the resize callback
     let mut processor = event::Processor::new(
-        event_loop::Notifier(event_loop.channel()),
+        event_loop::Notifier(loop_tx.clone(), queued_bytes.clone()),
+        queued_bytes,
         display.command_channel(),
         options,
         &config,
         options.ref_test,
         display.size().to_owned(),
+        foreground,
+        messages,
     );
 
     // Create a config monitor when config was loaded from path
@@ -230,73 +285,171 @@ fn run(mut config: Config, options: &cli::Options) -> Result<(), Box<Error>> {
         (Some(true), _) |
         // Or if no CLI flag was passed and the config says yes
         (None, true) => config.path()
-                .map(|path| config::Monitor::new(path, window.notifier())),
+                .map(|path| config::Monitor::new(path, options.strict_config, window.notifier())),
         // Otherwise, don't start the monitor
         _ => None,
     };
 
-    // Kick off the I/O thread
-    let _io_thread = event_loop.spawn(None);
-
     info!("Initialisation complete");
 
     // Main display loop
+    let mut frames: u64 = 0;
+    let mut redraw_when_visible = false;
     loop {
         // Process input and window events
-        let mut terminal_lock = processor.process_events(&terminal, &mut window);
+        let mut terminal_lock = match processor.process_events(&terminal, &mut window) {
+            Some(terminal_lock) => terminal_lock,
+            None => continue,
+        };
+
+        // Begin shutdown if the pty was closed
+        if terminal_lock.should_exit() {
+            break;
+        }
 
         // Handle config reloads
         if let Some(new_config) = config_monitor
             .as_ref()
             .and_then(|monitor| monitor.pending_config())
         {
-            config = new_config.update_dynamic_title(options);
+            let new_config = new_config.update_dynamic_title(options);
+            let changes = config.changes(&new_config);
+            config = new_config;
+
+            // Only what changed is rebuilt, a new color doesn't reload the fonts
             display.update_config(&config);
+            if changes.font {
+                display.update_font(&config);
+            }
+            if changes.padding {
+                display.update_padding(&config);
+            }
             processor.update_config(&config);
+            if changes.bindings {
+                processor.update_bindings(&config);
+            }
             terminal_lock.update_config(&config);
             terminal_lock.dirty = true;
         }
 
+        // Follows focus changes and reloads, nothing is done while it's the same
+        window.set_opacity(config.window().opacity(window.is_focused));
+
         // Maybe draw the terminal
         if terminal_lock.needs_draw() {
             // Try to update the position of the input method editor
             let (x, y) = display.current_xim_spot(&terminal_lock);
             window.set_ime_spot(x, y);
 
+            // The rebuilt glyph cache is brought to the current font size by
+            // the resize handling
+            if display.needs_new_context() {
+                if let Err(err) = display.recover_context(&config, &mut window) {
+                    error!("Unable to replace the lost OpenGL context: {}", err);
+                }
+            }
+
             // Handle pending resize (and HiDPI factor change) events
             //
             // The second argument is a list of types that want to be notified
-            // of display size changes.
-            display.handle_resize(&mut terminal_lock, &config, &mut [resize_handle, &mut processor, &mut window]);
+            // of display size changes. While macOS animates the window into
+            // fullscreen, the resizes stay pending until it has its final size,
+            // drawing is kept going to notice the end of the animation.
+            if window.in_fullscreen_transition() {
+                terminal_lock.dirty = true;
+            } else {
+                display.handle_resize(&mut terminal_lock, &config, &mut [&mut pty_resizer, &mut processor, &mut window]);
+            }
 
-            if let Some(title) = terminal_lock.get_next_title() {
-                window.set_title(&title);
+            if let Some(mouse_cursor) = terminal_lock.get_next_mouse_cursor() {
+                window.set_mouse_cursor(mouse_cursor);
             }
 
             if let Some(is_urgent) = terminal_lock.next_is_urgent.take() {
                 // We don't need to set the urgent flag if we already have the
                 // user's attention.
-                if !is_urgent || !window.is_focused {
+                if !is_urgent || (!window.is_focused && config.bell().urgent()) {
                     window.set_urgent(is_urgent);
                 }
             }
 
+            // The new size is picked up by the next `handle_resize` once the
+            // window has been resized. Fullscreen windows keep their size.
+            if let Some(size) = terminal_lock.next_window_size.take() {
+                if !window.is_fullscreen() {
+                    let screen = window.screen_size();
+                    let (width, height) = display.size().requested_window_size(
+                        size,
+                        *screen.width as f32,
+                        *screen.height as f32,
+                    );
+                    window.set_inner_size(&Size {
+                        width: Pixels(width as u32),
+                        height: Pixels(height as u32),
+                    });
+                }
+            }
+
+            if terminal_lock.next_bell_is_audible {
+                terminal_lock.next_bell_is_audible = false;
+                window.ring_bell();
+            }
+
+            // Nothing is drawn while the window can't be seen. The terminal
+            // stays dirty, the frame is drawn once the window is exposed.
+            terminal_lock.occluded = !window.is_visible();
+            if terminal_lock.occluded {
+                redraw_when_visible = true;
+                continue;
+            }
+
+            if redraw_when_visible {
+                display.invalidate();
+                redraw_when_visible = false;
+            }
+
             drop(terminal_lock);
 
             // Draw the current state of the terminal
-            display.draw(&terminal, &config, window.is_focused);
+            if display.draw(&terminal, &config, window.is_focused) {
+                match window.swap_buffers() {
+                    // The window is created again with a new context before the next frame
+                    Err(ref err) if err.is_context_lost() => {
+                        let _ = display.command_channel().send(DisplayCommand::ContextLost);
+                        terminal.lock().dirty = true;
+                    },
+                    result => result.expect("swap buffers"),
+                }
+                frames += 1;
+            }
+        }
+    }
 
-            window.swap_buffers().expect("swap buffers");        }
+    info!("{}", display.frame_stats());
 
-        // Begin shutdown if the flag was raised.
-        if process_should_exit() {
-            break;
-        }
+    // Fullscreen sizes aren't kept, the next window would cover the screen
+    if config.window().remember_size() && !window.is_fullscreen() {
+        let size = display.size();
+        state::save_window_size(size.width as u32, size.height as u32);
+    }
+
+    if let Some(len) = replay_len {
+        let elapsed = start.elapsed();
+        let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        println!(
+            "Replayed {} bytes in {:.3}s ({:.2} MiB/s), {} frames rendered",
+            len,
+            secs,
+            len as f64 / secs / 1024. / 1024.,
+            frames
+        );
     }
 
-    loop_tx
-        .send(Msg::Shutdown)
-        .expect("Error sending shutdown to event loop");
+    // The event loop is already gone if the shutdown was caused by the pty
+    let _ = loop_tx.send(Msg::Shutdown);
+
+    // Closing the pty hangs up the processes which are still running
+    join_io_thread();
 
     // FIXME patch notify library to have a shutdown method
     // config_reloader.join().ok();
@@ -307,3 +460,46 @@ fn run(mut config: Config, options: &cli::Options) -> Result<(), Box<Error>> {
 
     Ok(())
 }
+
+/// Create the pseudoterminal I/O loop and kick off the I/O thread
+///
+/// pty I/O is ran on another thread as to not occupy cycles used by the
+/// renderer and input processing. Note that access to the terminal state is
+/// synchronized since the I/O loop updates the state, and the display
+/// consumes it periodically.
+///
+/// The returned channel allows write requests from the event processor to be
+/// sent to the loop and ultimately written to the pty, the number of bytes
+/// which are still waiting to be written is shared along with it. The returned
+/// closure waits for the I/O thread to finish after a shutdown, then drops the
+/// pty.
+fn spawn_event_loop<T>(
+    terminal: &Arc<FairMutex<Term>>,
+    window: &Window,
+    pty: T,
+    config: &Config,
+    options: &cli::Options,
+) -> (Sender<Msg>, QueuedBytes, Box<FnMut()>)
+    where T: tty::EventedReadWrite + OnResize + Send + 'static
+{
+    let event_loop = EventLoop::new(
+        Arc::clone(terminal),
+        Box::new(window.notifier()),
+        pty,
+        config,
+        options.ref_test,
+    );
+
+    let loop_tx = event_loop.channel();
+    let queued_bytes = event_loop.queued_bytes();
+    let mut io_thread = Some(event_loop.spawn(None));
+    let join = move || {
+        if let Some(io_thread) = io_thread.take() {
+            if io_thread.join().is_err() {
+                error!("The I/O thread panicked");
+            }
+        }
+    };
+
+    (loop_tx, queued_bytes, Box::new(join))
+}
@@ -12,25 +12,50 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 extern crate log;
-use clap::{Arg, App};
+use clap::{Arg, App, AppSettings};
 use index::{Line, Column};
 use config::{Dimensions, Shell};
-use window::{DEFAULT_TITLE, DEFAULT_CLASS};
 use std::path::{Path, PathBuf};
 use std::borrow::Cow;
+use std::io::{self, Write};
+use std::process;
+use version;
+
+/// Default text for the window's title bar, if not overriden.
+///
+/// In X11, this the default value for the `WM_NAME` property.
+pub const DEFAULT_TITLE: &str = "Alacritty";
+
+/// Default text for general window class, X11 specific.
+///
+/// In X11, this is the default value for the `WM_CLASS` property. The
+/// second value of `WM_CLASS` is **never** changed to anything but
+/// the default value.
+///
+/// ```ignore
+/// $ xprop | grep WM_CLASS
+/// WM_CLASS(STRING) = "Alacritty", "Alacritty"
+/// ```
+pub const DEFAULT_CLASS: &str = "Alacritty";
 
 /// Options specified on the command line
 pub struct Options {
     pub live_config_reload: Option<bool>,
     pub print_events: bool,
+    pub persistent_logging: bool,
     pub ref_test: bool,
     pub dimensions: Option<Dimensions>,
+    /// Window size in pixels, from the `WIDTHxHEIGHTpx` form of `--dimensions`
+    pub window_size: Option<(u32, u32)>,
     pub title: Option<String>,
     pub class: Option<String>,
     pub log_level: log::LevelFilter,
     pub command: Option<Shell<'static>>,
     pub working_dir: Option<PathBuf>,
     pub config: Option<PathBuf>,
+    pub benchmark: Option<PathBuf>,
+    pub print_config: bool,
+    pub strict_config: bool,
 }
 
 impl Default for Options {
@@ -38,27 +63,52 @@ impl Default for Options {
         Options {
             live_config_reload: None,
             print_events: false,
+            persistent_logging: false,
             ref_test: false,
             dimensions: None,
+            window_size: None,
             title: None,
             class: None,
             log_level: log::LevelFilter::Warn,
             command: None,
             working_dir: None,
             config: None,
+            benchmark: None,
+            print_config: false,
+            strict_config: false,
         }
     }
 }
 
+/// Exit after printing to the console
+///
+/// On Windows the console of the parent process is released first, otherwise
+/// cmd and powershell don't redraw their prompt.
+pub fn exit(code: i32) -> ! {
+    let _ = io::stdout().flush();
+
+    #[cfg(windows)]
+    unsafe {
+        ::winapi::um::wincon::FreeConsole();
+    }
+
+    process::exit(code)
+}
+
 impl Options {
     /// Build `Options` from command line arguments
     pub fn load() -> Options {
         let mut options = Options::default();
 
         let matches = App::new(crate_name!())
-            .version(crate_version!())
             .author(crate_authors!("\n"))
             .about(crate_description!())
+            // Version information is printed below, to support `--version -v`
+            .setting(AppSettings::DisableVersion)
+            .arg(Arg::with_name("version")
+                .long("version")
+                .short("V")
+                .help("Prints version information, details about the build with -v"))
             .arg(Arg::with_name("ref-test")
                 .long("ref-test")
                 .help("Generates ref test"))
@@ -71,12 +121,17 @@ impl Options {
                  .conflicts_with("live-config-reload"))
             .arg(Arg::with_name("print-events")
                 .long("print-events"))
+            .arg(Arg::with_name("persistent-logging")
+                .long("persistent-logging")
+                .help("Keep the log file after quitting Alacritty"))
             .arg(Arg::with_name("dimensions")
                 .long("dimensions")
                 .short("d")
-                .value_names(&["columns", "lines"])
-                .help("Defines the window dimensions. Falls back to size specified by \
-                       window manager if set to 0x0 [default: 80x24]"))
+                .value_name("COLUMNSxLINES | WIDTHxHEIGHTpx")
+                .min_values(1)
+                .max_values(2)
+                .help("Defines the window dimensions in cells or in pixels. Falls back to \
+                       size specified by window manager if set to 0x0 [default: 80x24]"))
             .arg(Arg::with_name("title")
                 .long("title")
                 .short("t")
@@ -105,6 +160,18 @@ impl Options {
                  .takes_value(true)
                  .help("Specify alternative configuration file \
                        [default: $XDG_CONFIG_HOME/alacritty/alacritty.yml]"))
+            .arg(Arg::with_name("benchmark")
+                 .long("benchmark")
+                 .takes_value(true)
+                 .value_name("recording")
+                 .help("Replay a recording created with --ref-test as fast as possible \
+                       and print performance statistics"))
+            .arg(Arg::with_name("strict-config")
+                 .long("strict-config")
+                 .help("Refuse to load a configuration file with invalid or unknown fields"))
+            .arg(Arg::with_name("print-config")
+                 .long("print-config")
+                 .help("Print the configuration with all values that are used and exit"))
             .arg(Arg::with_name("command")
                 .long("command")
                 .short("e")
@@ -113,7 +180,26 @@ impl Options {
                 .min_values(1)
                 .allow_hyphen_values(true)
                 .help("Command and args to execute (must be last argument)"))
-            .get_matches();
+            .get_matches_safe()
+            .unwrap_or_else(|err| {
+                if err.use_stderr() {
+                    eprintln!("{}", err.message);
+                    exit(1);
+                }
+
+                // Help was requested
+                println!("{}", err.message);
+                exit(0);
+            });
+
+        if matches.is_present("version") {
+            if matches.is_present("v") {
+                println!("{}", version::Long);
+            } else {
+                println!("alacritty {}", version::VERSION);
+            }
+            exit(0);
+        }
 
         if matches.is_present("ref-test") {
             options.ref_test = true;
@@ -123,17 +209,27 @@ impl Options {
             options.print_events = true;
         }
 
+        if matches.is_present("persistent-logging") {
+            options.persistent_logging = true;
+        }
+
         if matches.is_present("live-config-reload") {
             options.live_config_reload = Some(true);
         } else if matches.is_present("no-live-config-reload") {
             options.live_config_reload = Some(false);
         }
 
-        if let Some(mut dimensions) = matches.values_of("dimensions") {
-            let width = dimensions.next().map(|w| w.parse().map(Column));
-            let height = dimensions.next().map(|h| h.parse().map(Line));
-            if let (Some(Ok(width)), Some(Ok(height))) = (width, height) {
-                options.dimensions = Some(Dimensions::new(width, height));
+        if let Some(values) = matches.values_of("dimensions") {
+            let values: Vec<&str> = values.collect();
+            match parse_dimensions(&values) {
+                Some(WindowSize::Cells(dimensions)) => options.dimensions = Some(dimensions),
+                Some(WindowSize::Pixels(width, height)) => {
+                    options.window_size = Some((width, height))
+                },
+                None => {
+                    eprintln!("Invalid dimensions `{}`, expected COLUMNSxLINES or WIDTHxHEIGHTpx",
+                              values.join(" "));
+                },
             }
         }
 
@@ -161,6 +257,18 @@ impl Options {
             options.config = Some(PathBuf::from(path.to_string()));
         }
 
+        if let Some(path) = matches.value_of("benchmark") {
+            options.benchmark = Some(PathBuf::from(path.to_string()));
+        }
+
+        if matches.is_present("strict-config") {
+            options.strict_config = true;
+        }
+
+        if matches.is_present("print-config") {
+            options.print_config = true;
+        }
+
         if let Some(mut args) = matches.values_of("command") {
             // The following unwrap is guaranteed to succeed.
             // If 'command' exists it must also have a first item since
@@ -177,6 +285,11 @@ impl Options {
         self.dimensions
     }
 
+    #[inline]
+    pub fn window_size(&self) -> Option<(u32, u32)> {
+        self.window_size
+    }
+
     pub fn command(&self) -> Option<&Shell> {
         self.command.as_ref()
     }
@@ -185,3 +298,63 @@ impl Options {
         self.config.as_ref().map(|p| Cow::Borrowed(p.as_path()))
     }
 }
+
+/// Size passed to `--dimensions`
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum WindowSize {
+    Cells(Dimensions),
+    Pixels(u32, u32),
+}
+
+/// Parse the values of `--dimensions`
+///
+/// Either `COLUMNSxLINES`, `WIDTHxHEIGHTpx` or columns and lines as separate
+/// values. A pixel size of zero is rejected, since the window manager can't
+/// pick the size then.
+fn parse_dimensions(values: &[&str]) -> Option<WindowSize> {
+    let (width, height, pixels) = match *values {
+        [size] => {
+            let pixels = size.ends_with("px");
+            let mut parts = size.trim_end_matches("px").splitn(2, 'x');
+            (parts.next()?, parts.next()?, pixels)
+        },
+        [columns, lines] => (columns, lines, false),
+        _ => return None,
+    };
+
+    if pixels {
+        let width = width.parse().ok()?;
+        let height = height.parse().ok()?;
+        if width == 0 || height == 0 {
+            return None;
+        }
+        return Some(WindowSize::Pixels(width, height));
+    }
+
+    let columns = width.parse().ok()?;
+    let lines = height.parse().ok()?;
+    Some(WindowSize::Cells(Dimensions::new(Column(columns), Line(lines))))
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Dimensions;
+    use index::{Column, Line};
+
+    use super::{parse_dimensions, WindowSize};
+
+    #[test]
+    fn dimensions_in_cells_and_pixels() {
+        let cells = WindowSize::Cells(Dimensions::new(Column(80), Line(24)));
+        assert_eq!(parse_dimensions(&["80x24"]), Some(cells));
+        assert_eq!(parse_dimensions(&["80", "24"]), Some(cells));
+        assert_eq!(parse_dimensions(&["800x600px"]), Some(WindowSize::Pixels(800, 600)));
+
+        let window_manager = WindowSize::Cells(Dimensions::new(Column(0), Line(0)));
+        assert_eq!(parse_dimensions(&["0x0"]), Some(window_manager));
+        assert_eq!(parse_dimensions(&["0x600px"]), None);
+        assert_eq!(parse_dimensions(&["80", "24px"]), None);
+        assert_eq!(parse_dimensions(&["80"]), None);
+        assert_eq!(parse_dimensions(&["80xpx"]), None);
+    }
+}
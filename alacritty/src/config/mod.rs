@@ -11,23 +11,29 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::mpsc;
 use std::time::Duration;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use ::Rgb;
 pub use font::Size;
+use font::{self, Feature};
 use serde_yaml;
-use serde::{self, de, Deserialize};
+use serde::{self, de, ser, Deserialize, Serialize, Serializer};
 use serde::de::Error as SerdeError;
 use serde::de::{Visitor, MapAccess, Unexpected};
+use serde::ser::SerializeMap;
 use notify::{Watcher, watcher, DebouncedEvent, RecursiveMode};
 use event_loop::WindowNotifier;
 
+#[cfg(feature = "gui")]
 use glutin::ModifiersState;
 
 use cli::Options;
+#[cfg(feature = "gui")]
 use input::{Action, Binding, MouseBinding, KeyBinding};
 use index::{Line, Column};
-use ansi::{CursorStyle, NamedColor, Color};
+use ansi::{CursorStyle, NamedColor, Color, DEFAULT_MAX_STRING_LEN};
+
+mod problems;
 
 const MAX_SCROLLBACK_LINES: u32 = 100_000;
 
@@ -36,7 +42,7 @@ fn true_bool() -> bool {
     true
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Selection {
     pub semantic_escape_chars: String,
     #[serde(default, deserialize_with = "failure_default")]
@@ -52,9 +58,9 @@ impl Default for Selection {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ClickHandler {
-    #[serde(deserialize_with="deserialize_duration_ms")]
+    #[serde(deserialize_with="deserialize_duration_ms", serialize_with="serialize_duration_ms")]
     pub threshold: Duration,
 }
 
@@ -68,49 +74,104 @@ fn default_threshold_ms() -> Duration {
     Duration::from_millis(300)
 }
 
+/// Longest time between the clicks of a multi-click
+///
+/// Longer thresholds are most likely given in the wrong unit.
+const MAX_CLICK_THRESHOLD_MS: u64 = 5000;
+
 fn deserialize_duration_ms<'a, D>(deserializer: D) -> ::std::result::Result<Duration, D::Error>
     where D: de::Deserializer<'a>
 {
     match u64::deserialize(deserializer) {
+        Ok(threshold_ms) if threshold_ms > MAX_CLICK_THRESHOLD_MS => {
+            problems::report(format!(
+                "Click threshold of {}ms is longer than {}ms; Using default value",
+                threshold_ms,
+                MAX_CLICK_THRESHOLD_MS,
+            ));
+            Ok(default_threshold_ms())
+        },
         Ok(threshold_ms) => Ok(Duration::from_millis(threshold_ms)),
         Err(err) => {
-            eprintln!("problem with config: {}; Using default value", err);
+            problems::report(format!("{}; Using default value", err));
             Ok(default_threshold_ms())
         },
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+fn serialize_duration_ms<S>(duration: &Duration, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    let millis = duration.as_secs() * 1000 + u64::from(duration.subsec_nanos() / 1_000_000);
+    serializer.serialize_u64(millis)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Mouse {
     #[serde(default, deserialize_with = "failure_default")]
     pub double_click: ClickHandler,
     #[serde(default, deserialize_with = "failure_default")]
     pub triple_click: ClickHandler,
+    /// Distance in pixels the pointer has to move before a click becomes a selection
+    #[serde(deserialize_with="deserialize_mouse_drag_threshold")]
+    #[serde(default="default_mouse_drag_threshold")]
+    pub drag_threshold: u16,
     #[serde(default, deserialize_with = "failure_default")]
     pub hide_when_typing: bool,
     #[serde(default, deserialize_with = "failure_default")]
     pub url: Url,
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    pub paste_dropped_files: bool,
+    /// Clicks scroll the viewport to the bottom of the scrollback
+    #[serde(default, deserialize_with = "failure_default")]
+    pub scroll_to_bottom_on_click: bool,
 
     // TODO: DEPRECATED
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub faux_scrollback_lines: Option<usize>,
 }
 
-#[derive(Default, Clone, Debug, Deserialize)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Url {
     // Program for opening links
     #[serde(default, deserialize_with = "failure_default")]
     pub launcher: Option<CommandWrapper>,
 
     // Modifier used to open links
-    #[serde(default, deserialize_with = "deserialize_modifiers")]
+    #[cfg(feature = "gui")]
+    #[serde(default, deserialize_with = "deserialize_modifiers", serialize_with = "serialize_modifiers")]
     pub modifiers: ModifiersState,
 }
 
+fn default_mouse_drag_threshold() -> u16 {
+    3
+}
+
+fn deserialize_mouse_drag_threshold<'a, D>(deserializer: D) -> ::std::result::Result<u16, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match u16::deserialize(deserializer) {
+        Ok(threshold) => Ok(threshold),
+        Err(err) => {
+            problems::report(format!("{}; Using default value", err));
+            Ok(default_mouse_drag_threshold())
+        },
+    }
+}
+
+#[cfg(feature = "gui")]
 fn deserialize_modifiers<'a, D>(deserializer: D) -> ::std::result::Result<ModifiersState, D::Error>
     where D: de::Deserializer<'a>
 {
-    ModsWrapper::deserialize(deserializer).map(|wrapper| wrapper.into_inner())
+    problems::with_path("mouse.url.modifiers", || ModsWrapper::deserialize(deserializer))
+        .map(|wrapper| wrapper.into_inner())
+}
+
+#[cfg(feature = "gui")]
+fn serialize_modifiers<S>(mods: &ModifiersState, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    ModsWrapper(*mods).serialize(serializer)
 }
 
 impl Default for Mouse {
@@ -122,8 +183,11 @@ impl Default for Mouse {
             triple_click: ClickHandler {
                 threshold: Duration::from_millis(300),
             },
+            drag_threshold: default_mouse_drag_threshold(),
             hide_when_typing: false,
             url: Url::default(),
+            paste_dropped_files: true,
+            scroll_to_bottom_on_click: false,
             faux_scrollback_lines: None,
         }
     }
@@ -131,7 +195,7 @@ impl Default for Mouse {
 
 /// `VisualBellAnimations` are modeled after a subset of CSS transitions and Robert
 /// Penner's Easing Functions.
-#[derive(Clone, Copy, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VisualBellAnimation {
     Ease,          // CSS
     EaseOut,       // CSS
@@ -143,6 +207,9 @@ pub enum VisualBellAnimation {
     EaseOutExpo,   // Penner
     EaseOutCirc,   // Penner
     Linear,
+
+    /// The visual bell is disabled
+    None,
 }
 
 impl Default for VisualBellAnimation {
@@ -151,7 +218,7 @@ impl Default for VisualBellAnimation {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct VisualBellConfig {
     /// Visual bell animation function
     #[serde(default, deserialize_with = "failure_default")]
@@ -173,7 +240,7 @@ fn deserialize_visual_bell_duration<'a, D>(deserializer: D) -> ::std::result::Re
     match u16::deserialize(deserializer) {
         Ok(duration) => Ok(duration),
         Err(err) => {
-            eprintln!("problem with config: {}; Using default value", err);
+            problems::report(format!("{}; Using default value", err));
             Ok(default_visual_bell_duration())
         },
     }
@@ -202,7 +269,173 @@ impl Default for VisualBellConfig {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BellConfig {
+    /// Request the user's attention when the bell rings in an unfocused window
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    urgent: bool,
+
+    /// Play the alert sound of the system
+    #[serde(default, deserialize_with = "failure_default")]
+    audible: bool,
+}
+
+impl BellConfig {
+    #[inline]
+    pub fn urgent(&self) -> bool {
+        self.urgent
+    }
+
+    #[inline]
+    pub fn audible(&self) -> bool {
+        self.audible
+    }
+}
+
+impl Default for BellConfig {
+    fn default() -> BellConfig {
+        BellConfig {
+            urgent: true,
+            audible: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PrinterConfig {
+    /// Command receiving the printed text on stdin, printing is discarded without one
+    #[serde(default, deserialize_with = "failure_default")]
+    command: Option<CommandWrapper>,
+}
+
+impl PrinterConfig {
+    #[inline]
+    pub fn command(&self) -> Option<&CommandWrapper> {
+        self.command.as_ref()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TerminalConfig {
+    /// Kill the processes of the terminal which are still running after the hangup
+    #[serde(default, deserialize_with = "failure_default")]
+    kill_on_close: bool,
+
+    /// Interpret raw C1 bytes as controls and send 8-bit replies from the start
+    #[serde(default, deserialize_with = "failure_default")]
+    eight_bit_controls: bool,
+
+    /// Reply to ENQ, nothing is sent without one
+    #[serde(default, deserialize_with = "failure_default")]
+    answerback: String,
+
+    /// Access of programs to the clipboard with OSC 52
+    #[serde(default, deserialize_with = "failure_default")]
+    osc52: Osc52,
+
+    /// Don't reply to any of the queries of programs
+    #[serde(default, deserialize_with = "failure_default")]
+    disable_reports: bool,
+}
+
+impl TerminalConfig {
+    #[inline]
+    pub fn kill_on_close(&self) -> bool {
+        self.kill_on_close
+    }
+
+    #[inline]
+    pub fn eight_bit_controls(&self) -> bool {
+        self.eight_bit_controls
+    }
+
+    /// Bytes of the answerback, with its escapes resolved
+    pub fn answerback(&self) -> Vec<u8> {
+        unescape(&self.answerback)
+    }
+
+    #[inline]
+    pub fn osc52(&self) -> Osc52 {
+        self.osc52
+    }
+
+    #[inline]
+    pub fn disable_reports(&self) -> bool {
+        self.disable_reports
+    }
+}
+
+/// Access of programs to the clipboard with OSC 52
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Osc52 {
+    Disabled,
+    /// Programs may set the clipboard, but not read it
+    CopyOnly,
+    /// Programs may also read the clipboard
+    Full,
+}
+
+impl Default for Osc52 {
+    fn default() -> Osc52 {
+        Osc52::CopyOnly
+    }
+}
+
+/// Resolve the escapes `\e`, `\r`, `\n`, `\t` and `\\` of a string
+///
+/// Other backslashes are kept as they are.
+fn unescape(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('e') | Some('E') => '\x1b',
+                Some('r') => '\r',
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('\\') | None => '\\',
+                Some(c) => {
+                    bytes.push(b'\\');
+                    c
+                },
+            },
+            c => c,
+        };
+
+        let mut buf = [0; 4];
+        bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+    bytes
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ScreenDumpConfig {
+    /// Directory the dumps are written to, the home directory by default
+    #[serde(default, deserialize_with = "failure_default")]
+    directory: Option<PathBuf>,
+
+    /// Dump the scrollback history along with the screen
+    #[serde(default, deserialize_with = "failure_default")]
+    scrollback: bool,
+}
+
+impl ScreenDumpConfig {
+    pub fn directory(&self) -> PathBuf {
+        let home = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+        self.directory.clone()
+            .or_else(|| env::var_os(home).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    #[inline]
+    pub fn scrollback(&self) -> bool {
+        self.scrollback
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Shell<'a> {
     program: Cow<'a, str>,
 
@@ -311,19 +544,28 @@ impl<'de> Deserialize<'de> for Decorations {
                     "none" => Ok(Decorations::None),
                     "full" => Ok(Decorations::Full),
                     "true" => {
-                        eprintln!("deprecated decorations boolean value, \
-                                   use one of transparent|buttonless|none|full instead; \
-                                   Falling back to \"full\"");
+                        problems::report_at(
+                            "window.decorations",
+                            "deprecated boolean value, \
+                             use one of transparent|buttonless|none|full instead; \
+                             Falling back to \"full\"",
+                        );
                         Ok(Decorations::Full)
                     },
                     "false" => {
-                        eprintln!("deprecated decorations boolean value, \
-                                   use one of transparent|buttonless|none|full instead; \
-                                   Falling back to \"none\"");
+                        problems::report_at(
+                            "window.decorations",
+                            "deprecated boolean value, \
+                             use one of transparent|buttonless|none|full instead; \
+                             Falling back to \"none\"",
+                        );
                         Ok(Decorations::None)
                     },
                     _ => {
-                        eprintln!("invalid decorations value: {}; Using default value", value);
+                        problems::report_at(
+                            "window.decorations",
+                            format!("invalid value {}; Using default value", value),
+                        );
                         Ok(Decorations::Full)
                     }
                 }
@@ -337,23 +579,35 @@ impl<'de> Deserialize<'de> for Decorations {
                     "none" => Ok(Decorations::None),
                     "full" => Ok(Decorations::Full),
                     "true" => {
-                        eprintln!("deprecated decorations boolean value, \
-                                   use one of none|full instead; \
-                                   Falling back to \"full\"");
+                        problems::report_at(
+                            "window.decorations",
+                            "deprecated boolean value, \
+                             use one of none|full instead; \
+                             Falling back to \"full\"",
+                        );
                         Ok(Decorations::Full)
                     },
                     "false" => {
-                        eprintln!("deprecated decorations boolean value, \
-                                   use one of none|full instead; \
-                                   Falling back to \"none\"");
+                        problems::report_at(
+                            "window.decorations",
+                            "deprecated boolean value, \
+                             use one of none|full instead; \
+                             Falling back to \"none\"",
+                        );
                         Ok(Decorations::None)
                     },
                     "transparent" | "buttonless" => {
-                        eprintln!("macos-only decorations value: {}; Using default value", value);
+                        problems::report_at(
+                            "window.decorations",
+                            format!("macos-only value {}; Using default value", value),
+                        );
                         Ok(Decorations::Full)
                     },
                     _ => {
-                        eprintln!("invalid decorations value: {}; Using default value", value);
+                        problems::report_at(
+                            "window.decorations",
+                            format!("invalid value {}; Using default value", value),
+                        );
                         Ok(Decorations::Full)
                     }
                 }
@@ -364,12 +618,29 @@ impl<'de> Deserialize<'de> for Decorations {
     }
 }
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+impl Serialize for Decorations {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_str(match *self {
+            Decorations::Full => "full",
+            Decorations::Transparent => "transparent",
+            Decorations::Buttonless => "buttonless",
+            Decorations::None => "none",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowConfig {
     /// Initial dimensions
     #[serde(default, deserialize_with = "failure_default")]
     dimensions: Dimensions,
 
+    /// Smallest grid, even when the window is smaller
+    #[serde(default="default_min_dimensions", deserialize_with = "deserialize_min_dimensions")]
+    min_dimensions: Dimensions,
+
     /// Pixel padding
     #[serde(default="default_padding", deserialize_with = "deserialize_padding")]
     padding: Delta<u8>,
@@ -377,6 +648,110 @@ pub struct WindowConfig {
     /// Draw the window with title bar / borders
     #[serde(default)]
     decorations: Decorations,
+
+    /// Option keys treated as Alt on macOS
+    #[serde(default, deserialize_with = "failure_default")]
+    option_as_alt: OptionAsAlt,
+
+    /// Resize the window in whole cells
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    resize_increments: bool,
+
+    /// Template for the window title
+    #[serde(default, deserialize_with = "failure_default")]
+    title_format: Option<String>,
+
+    /// Fullscreen state of the window at startup
+    #[serde(default, deserialize_with = "failure_default")]
+    startup_mode: StartupMode,
+
+    /// Restore the window size of the last session
+    #[serde(default, deserialize_with = "failure_default")]
+    remember_size: bool,
+
+    /// Closing the window needs a confirmation
+    #[serde(default, deserialize_with = "failure_default")]
+    confirm_close: ConfirmClose,
+
+    /// Image replacing the window icon
+    #[serde(default, deserialize_with = "failure_default")]
+    icon: Option<PathBuf>,
+
+    /// Programs may resize the window with escape sequences
+    #[serde(default, deserialize_with = "failure_default")]
+    allow_resize_requests: bool,
+
+    /// Opacity of the whole window, applied by the compositor
+    #[serde(default, deserialize_with = "failure_default")]
+    opacity: Alpha,
+
+    /// Opacity while the window isn't focused, `opacity` without it
+    #[serde(default, deserialize_with = "failure_default")]
+    unfocused_opacity: Option<Alpha>,
+}
+
+/// Close requests which are only followed after another close request
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmClose {
+    Never,
+    Always,
+    /// While a job other than the shell runs in the foreground
+    ForegroundProcess,
+}
+
+impl Default for ConfirmClose {
+    fn default() -> ConfirmClose {
+        ConfirmClose::Never
+    }
+}
+
+/// State of the window after it was created
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StartupMode {
+    Windowed,
+    /// Native fullscreen, in a space of its own on macOS
+    Fullscreen,
+    /// Fullscreen within the current space on macOS, like `Fullscreen` elsewhere
+    SimpleFullscreen,
+}
+
+impl Default for StartupMode {
+    fn default() -> StartupMode {
+        StartupMode::Windowed
+    }
+}
+
+/// Option keys which send Alt instead of composing characters on macOS
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OptionAsAlt {
+    /// Both Option keys compose characters
+    None,
+    OnlyLeft,
+    OnlyRight,
+    Both,
+}
+
+impl Default for OptionAsAlt {
+    fn default() -> OptionAsAlt {
+        OptionAsAlt::None
+    }
+}
+
+fn default_min_dimensions() -> Dimensions {
+    Dimensions::new(Column(2), Line(1))
+}
+
+fn deserialize_min_dimensions<'a, D>(deserializer: D) -> ::std::result::Result<Dimensions, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match Dimensions::deserialize(deserializer) {
+        Ok(dimensions) => Ok(dimensions),
+        Err(err) => {
+            problems::report(format!("{}; Using default value", err));
+            Ok(default_min_dimensions())
+        },
+    }
 }
 
 fn default_padding() -> Delta<u8> {
@@ -389,7 +764,7 @@ fn deserialize_padding<'a, D>(deserializer: D) -> ::std::result::Result<Delta<u8
     match Delta::deserialize(deserializer) {
         Ok(delta) => Ok(delta),
         Err(err) => {
-            eprintln!("problem with config: {}; Using default value", err);
+            problems::report(format!("{}; Using default value", err));
             Ok(default_padding())
         },
     }
@@ -399,31 +774,94 @@ impl WindowConfig {
     pub fn decorations(&self) -> Decorations {
         self.decorations
     }
+
+    #[inline]
+    pub fn min_dimensions(&self) -> Dimensions {
+        self.min_dimensions
+    }
+
+    pub fn option_as_alt(&self) -> OptionAsAlt {
+        self.option_as_alt
+    }
+
+    #[inline]
+    pub fn resize_increments(&self) -> bool {
+        self.resize_increments
+    }
+
+    #[inline]
+    pub fn title_format(&self) -> Option<&str> {
+        self.title_format.as_ref().map(|format| format.as_str())
+    }
+
+    #[inline]
+    pub fn startup_mode(&self) -> StartupMode {
+        self.startup_mode
+    }
+
+    #[inline]
+    pub fn remember_size(&self) -> bool {
+        self.remember_size
+    }
+
+    #[inline]
+    pub fn confirm_close(&self) -> ConfirmClose {
+        self.confirm_close
+    }
+
+    #[inline]
+    pub fn icon(&self) -> Option<&Path> {
+        self.icon.as_ref().map(|path| path.as_path())
+    }
+
+    #[inline]
+    pub fn allow_resize_requests(&self) -> bool {
+        self.allow_resize_requests
+    }
+
+    #[inline]
+    pub fn opacity(&self, focused: bool) -> f32 {
+        match self.unfocused_opacity {
+            Some(opacity) if !focused => opacity.get(),
+            _ => self.opacity.get(),
+        }
+    }
 }
 
 impl Default for WindowConfig {
     fn default() -> Self {
         WindowConfig{
             dimensions: Default::default(),
+            min_dimensions: default_min_dimensions(),
             padding: default_padding(),
             decorations: Default::default(),
+            option_as_alt: Default::default(),
+            resize_increments: true,
+            title_format: None,
+            startup_mode: Default::default(),
+            remember_size: false,
+            confirm_close: Default::default(),
+            icon: None,
+            allow_resize_requests: false,
+            opacity: Alpha::default(),
+            unfocused_opacity: None,
         }
     }
 }
 
 /// Top-level config type
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     /// Initial dimensions
-    #[serde(default, deserialize_with = "failure_default")]
+    #[serde(default, deserialize_with = "failure_default", skip_serializing_if = "Option::is_none")]
     dimensions: Option<Dimensions>,
 
     /// Pixel padding
-    #[serde(default, deserialize_with = "failure_default")]
+    #[serde(default, deserialize_with = "failure_default", skip_serializing_if = "Option::is_none")]
     padding: Option<Delta<u8>>,
 
     /// TERM env variable
-    #[serde(default, deserialize_with = "failure_default")]
+    #[serde(default, deserialize_with = "failure_default", serialize_with = "serialize_env")]
     env: HashMap<String, String>,
 
     /// Font configuration
@@ -450,10 +888,12 @@ pub struct Config {
     window: WindowConfig,
 
     /// Keybindings
+    #[cfg(feature = "gui")]
     #[serde(default, deserialize_with = "failure_default_vec")]
     key_bindings: Vec<KeyBinding>,
 
     /// Bindings for the mouse
+    #[cfg(feature = "gui")]
     #[serde(default, deserialize_with = "failure_default_vec")]
     mouse_bindings: Vec<MouseBinding>,
 
@@ -468,13 +908,29 @@ pub struct Config {
     shell: Option<Shell<'static>>,
 
     /// Path where config was loaded from
-    #[serde(default, deserialize_with = "failure_default")]
+    #[serde(default, deserialize_with = "failure_default", skip_serializing)]
     config_path: Option<PathBuf>,
 
     /// Visual bell configuration
     #[serde(default, deserialize_with = "failure_default")]
     visual_bell: VisualBellConfig,
 
+    /// Bell configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    bell: BellConfig,
+
+    /// Printer configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    printer: PrinterConfig,
+
+    /// Terminal configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    terminal: TerminalConfig,
+
+    /// Screen dump configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    screen_dump: ScreenDumpConfig,
+
     /// Use dynamic title
     #[serde(default="true_bool", deserialize_with = "default_true_bool")]
     dynamic_title: bool,
@@ -483,6 +939,10 @@ pub struct Config {
     #[serde(default="true_bool", deserialize_with = "default_true_bool")]
     live_config_reload: bool,
 
+    /// Send ESC before characters typed with Alt held
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    alt_send_esc: bool,
+
     /// Number of spaces in one tab
     #[serde(default="default_tabspaces", deserialize_with = "deserialize_tabspaces")]
     tabspaces: usize,
@@ -491,27 +951,42 @@ pub struct Config {
     #[serde(default, deserialize_with="failure_default")]
     scrolling: Scrolling,
 
+    /// Touchscreen gestures
+    #[serde(default, deserialize_with="failure_default")]
+    touch: Touch,
+
     /// Cursor configuration
     #[serde(default, deserialize_with="failure_default")]
     cursor: Cursor,
 
+    /// Debugging and performance tuning options
+    #[serde(default, deserialize_with="failure_default")]
+    debug: Debug,
+
     // TODO: DEPRECATED
-    #[serde(default, deserialize_with = "failure_default")]
+    #[serde(default, deserialize_with = "failure_default", skip_serializing_if = "Option::is_none")]
     custom_cursor_colors: Option<bool>,
 
     // TODO: DEPRECATED
-    #[serde(default, deserialize_with = "failure_default")]
+    #[serde(default, deserialize_with = "failure_default", skip_serializing_if = "Option::is_none")]
     hide_cursor_when_typing: Option<bool>,
 
     // TODO: DEPRECATED
-    #[serde(default, deserialize_with = "failure_default")]
+    #[serde(default, deserialize_with = "failure_default", skip_serializing_if = "Option::is_none")]
     cursor_style: Option<CursorStyle>,
 
     // TODO: DEPRECATED
-    #[serde(default, deserialize_with = "failure_default")]
+    #[serde(default, deserialize_with = "failure_default", skip_serializing_if = "Option::is_none")]
     unfocused_hollow_cursor: Option<bool>,
 }
 
+/// Serialize the environment sorted by name
+fn serialize_env<S>(env: &HashMap<String, String>, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    serializer.collect_map(env.iter().collect::<BTreeMap<_, _>>())
+}
+
 fn failure_default_vec<'a, D, T>(deserializer: D) -> ::std::result::Result<Vec<T>, D::Error>
     where D: de::Deserializer<'a>,
           T: Deserialize<'a>
@@ -520,20 +995,21 @@ fn failure_default_vec<'a, D, T>(deserializer: D) -> ::std::result::Result<Vec<T
     let vec = match Vec::<serde_yaml::Value>::deserialize(deserializer) {
         Ok(vec) => vec,
         Err(err) => {
-            eprintln!("problem with config: {}; Using empty vector", err);
+            problems::report(format!("{}; Using empty vector", err));
             return Ok(Vec::new());
         },
     };
 
     // Move to lossy vector
+    let path = problems::sequence_path(&vec);
     let mut bindings: Vec<T> = Vec::new();
-    for value in vec {
-        match T::deserialize(value) {
+    for (index, value) in vec.into_iter().enumerate() {
+        problems::with_path(&format!("{}[{}]", path, index), || match T::deserialize(value) {
             Ok(binding) => bindings.push(binding),
             Err(err) => {
-                eprintln!("problem with config: {}; Skipping value", err);
+                problems::report(format!("{}; Skipping value", err));
             },
-        }
+        });
     }
 
     Ok(bindings)
@@ -549,7 +1025,7 @@ fn deserialize_tabspaces<'a, D>(deserializer: D) -> ::std::result::Result<usize,
     match usize::deserialize(deserializer) {
         Ok(value) => Ok(value),
         Err(err) => {
-            eprintln!("problem with config: {}; Using `8`", err);
+            problems::report(format!("{}; Using `8`", err));
             Ok(default_tabspaces())
         },
     }
@@ -561,7 +1037,7 @@ fn default_true_bool<'a, D>(deserializer: D) -> ::std::result::Result<bool, D::E
     match bool::deserialize(deserializer) {
         Ok(value) => Ok(value),
         Err(err) => {
-            eprintln!("problem with config: {}; Using `true`", err);
+            problems::report(format!("{}; Using `true`", err));
             Ok(true)
         },
     }
@@ -575,18 +1051,18 @@ fn failure_default<'a, D, T>(deserializer: D)
     match T::deserialize(deserializer) {
         Ok(value) => Ok(value),
         Err(err) => {
-            eprintln!("problem with config: {}; Using default value", err);
+            problems::report(format!("{}; Using default value", err));
             Ok(T::default())
         },
     }
 }
 
 #[cfg(not(any(windows, target_os="macos")))]
-static DEFAULT_ALACRITTY_CONFIG: &'static str = include_str!("../alacritty.yml");
+static DEFAULT_ALACRITTY_CONFIG: &'static str = include_str!("../../alacritty.yml");
 #[cfg(target_os="macos")]
-static DEFAULT_ALACRITTY_CONFIG: &'static str = include_str!("../alacritty_macos.yml");
+static DEFAULT_ALACRITTY_CONFIG: &'static str = include_str!("../../alacritty_macos.yml");
 #[cfg(windows)]
-static DEFAULT_ALACRITTY_CONFIG: &'static str = include_str!("../alacritty_windows.yml");
+static DEFAULT_ALACRITTY_CONFIG: &'static str = include_str!("../../alacritty_windows.yml");
 
 impl Default for Config {
     fn default() -> Self {
@@ -596,7 +1072,7 @@ impl Default for Config {
 }
 
 /// Struct for scrolling related settings
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Scrolling {
     #[serde(deserialize_with="deserialize_scrolling_history")]
     #[serde(default="default_scrolling_history")]
@@ -609,6 +1085,12 @@ pub struct Scrolling {
     pub faux_multiplier: u8,
     #[serde(default, deserialize_with="failure_default")]
     pub auto_scroll: bool,
+    /// Keep the lines cleared with `CSI 2 J` in the history
+    #[serde(default, deserialize_with="failure_default")]
+    pub clear_copies_to_history: bool,
+    /// Show the position in the scrollback history while scrolled up
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    pub indicator: bool,
 }
 
 fn default_scrolling_history() -> u32 {
@@ -627,40 +1109,246 @@ impl Default for Scrolling {
             multiplier: default_scrolling_multiplier(),
             faux_multiplier: default_scrolling_multiplier(),
             auto_scroll: false,
+            clear_copies_to_history: false,
+            indicator: true,
         }
     }
 }
 
+/// Struct for touchscreen gesture settings
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Touch {
+    /// Time a finger has to rest on the screen to start a selection
+    #[serde(deserialize_with="deserialize_long_press", serialize_with="serialize_duration_ms")]
+    #[serde(default="default_long_press")]
+    pub long_press: Duration,
+    /// Distance in pixels a finger has to move before it's dragged
+    #[serde(deserialize_with="deserialize_drag_threshold")]
+    #[serde(default="default_drag_threshold")]
+    pub drag_threshold: u16,
+    /// Keep scrolling for a while after a swipe
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    pub momentum: bool,
+}
+
+fn default_long_press() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_drag_threshold() -> u16 {
+    10
+}
+
+impl Default for Touch {
+    fn default() -> Self {
+        Self {
+            long_press: default_long_press(),
+            drag_threshold: default_drag_threshold(),
+            momentum: true,
+        }
+    }
+}
+
+fn deserialize_long_press<'a, D>(deserializer: D) -> ::std::result::Result<Duration, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match u64::deserialize(deserializer) {
+        Ok(long_press_ms) => Ok(Duration::from_millis(long_press_ms)),
+        Err(err) => {
+            problems::report(format!("{}; Using default value", err));
+            Ok(default_long_press())
+        },
+    }
+}
+
+fn deserialize_drag_threshold<'a, D>(deserializer: D) -> ::std::result::Result<u16, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match u16::deserialize(deserializer) {
+        Ok(threshold) => Ok(threshold),
+        Err(err) => {
+            problems::report(format!("{}; Using default value", err));
+            Ok(default_drag_threshold())
+        },
+    }
+}
+
+/// Debugging and performance tuning options
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Debug {
+    /// Maximum number of bytes parsed from the pty in one event loop iteration
+    #[serde(deserialize_with="deserialize_parse_limit")]
+    #[serde(default="default_parse_limit")]
+    pub parse_limit: usize,
+
+    /// Size of the buffer used for reading from the pty
+    #[serde(deserialize_with="deserialize_read_buffer_size")]
+    #[serde(default="default_read_buffer_size")]
+    pub read_buffer_size: usize,
+
+    /// Longest OSC or DCS payload accepted from the pty
+    #[serde(deserialize_with="deserialize_max_string_len")]
+    #[serde(default="default_max_string_len")]
+    pub max_string_len: usize,
+
+    /// Keep the log file after quitting
+    #[serde(default, deserialize_with="failure_default")]
+    pub persistent_logging: bool,
+
+    /// Log levels per module, using the syntax of `$RUST_LOG`
+    #[serde(default, deserialize_with="failure_default")]
+    pub log_filter: Option<String>,
+
+    /// Reject configs with problems instead of using default values
+    #[serde(default, deserialize_with="failure_default")]
+    pub strict: bool,
+
+    /// OpenGL flavor and rendering path, forced for troubleshooting
+    #[serde(default, deserialize_with="failure_default")]
+    pub renderer_preference: RendererPreference,
+}
+
+/// OpenGL flavor and rendering path used by the renderer
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RendererPreference {
+    /// Desktop OpenGL, falling back to OpenGL ES when it's unavailable
+    Auto,
+    OpenGl,
+    /// OpenGL ES, with instanced rendering when it's supported
+    OpenGlEs,
+    /// OpenGL ES, drawing every vertex of every cell
+    OpenGlEsNonInstanced,
+}
+
+impl Default for RendererPreference {
+    fn default() -> RendererPreference {
+        RendererPreference::Auto
+    }
+}
+
+fn default_parse_limit() -> usize {
+    0x10_0000
+}
+
+fn default_read_buffer_size() -> usize {
+    0x1_0000
+}
+
+fn default_max_string_len() -> usize {
+    DEFAULT_MAX_STRING_LEN
+}
+
+impl Default for Debug {
+    fn default() -> Self {
+        Self {
+            parse_limit: default_parse_limit(),
+            read_buffer_size: default_read_buffer_size(),
+            max_string_len: default_max_string_len(),
+            persistent_logging: false,
+            log_filter: None,
+            strict: false,
+            renderer_preference: Default::default(),
+        }
+    }
+}
+
+fn deserialize_parse_limit<'a, D>(deserializer: D) -> ::std::result::Result<usize, D::Error>
+    where D: de::Deserializer<'a>
+{
+    deserialize_nonzero(deserializer, "debug.parse_limit", default_parse_limit())
+}
+
+/// Smallest and largest size of the pty read buffer
+const MIN_READ_BUFFER_SIZE: usize = 0x1000;
+const MAX_READ_BUFFER_SIZE: usize = 0x100_0000;
+
+fn deserialize_read_buffer_size<'a, D>(deserializer: D) -> ::std::result::Result<usize, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match usize::deserialize(deserializer) {
+        Ok(size) if size < MIN_READ_BUFFER_SIZE || size > MAX_READ_BUFFER_SIZE => {
+            let clamped = size.max(MIN_READ_BUFFER_SIZE).min(MAX_READ_BUFFER_SIZE);
+            problems::report_at("debug.read_buffer_size", format!(
+                "{} is outside of {}..={}; Using {}",
+                size, MIN_READ_BUFFER_SIZE, MAX_READ_BUFFER_SIZE, clamped,
+            ));
+            Ok(clamped)
+        },
+        Ok(size) => Ok(size),
+        Err(err) => {
+            problems::report(format!("{}; Using default value", err));
+            Ok(default_read_buffer_size())
+        },
+    }
+}
+
+fn deserialize_max_string_len<'a, D>(deserializer: D) -> ::std::result::Result<usize, D::Error>
+    where D: de::Deserializer<'a>
+{
+    deserialize_nonzero(deserializer, "debug.max_string_len", default_max_string_len())
+}
+
+fn deserialize_nonzero<'a, D>(
+    deserializer: D,
+    name: &str,
+    default: usize,
+) -> ::std::result::Result<usize, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match usize::deserialize(deserializer) {
+        Ok(0) => {
+            problems::report_at(name, "must be greater than 0; Using default value");
+            Ok(default)
+        },
+        Ok(value) => Ok(value),
+        Err(err) => {
+            problems::report(format!("{}; Using default value", err));
+            Ok(default)
+        },
+    }
+}
+
 fn deserialize_scrolling_history<'a, D>(deserializer: D) -> ::std::result::Result<u32, D::Error>
     where D: de::Deserializer<'a>
 {
     match u32::deserialize(deserializer) {
         Ok(lines) => {
             if lines > MAX_SCROLLBACK_LINES {
-                eprintln!(
-                    "problem with config: scrollback size is {}, but expected a maximum of {}; \
+                problems::report_at("scrolling.history", format!(
+                    "scrollback size is {}, but expected a maximum of {}; \
                      Using {1} instead",
                     lines, MAX_SCROLLBACK_LINES,
-                );
+                ));
                 Ok(MAX_SCROLLBACK_LINES)
             } else {
                 Ok(lines)
             }
         },
         Err(err) => {
-            eprintln!("problem with config: {}; Using default value", err);
+            problems::report(format!("{}; Using default value", err));
             Ok(default_scrolling_history())
         },
     }
 }
 
+/// Largest number of lines scrolled per wheel notch
+const MAX_SCROLLING_MULTIPLIER: u8 = 100;
+
 fn deserialize_scrolling_multiplier<'a, D>(deserializer: D) -> ::std::result::Result<u8, D::Error>
     where D: de::Deserializer<'a>
 {
     match u8::deserialize(deserializer) {
+        Ok(lines) if lines > MAX_SCROLLING_MULTIPLIER => {
+            problems::report(format!(
+                "scrolling multiplier {} is too large; Using {}",
+                lines,
+                MAX_SCROLLING_MULTIPLIER
+            ));
+            Ok(MAX_SCROLLING_MULTIPLIER)
+        },
         Ok(lines) => Ok(lines),
         Err(err) => {
-            eprintln!("problem with config: {}; Using default value", err);
+            problems::report(format!("{}; Using default value", err));
             Ok(default_scrolling_multiplier())
         },
     }
@@ -670,15 +1358,18 @@ fn deserialize_scrolling_multiplier<'a, D>(deserializer: D) -> ::std::result::Re
 ///
 /// Our deserialize impl wouldn't be covered by a derive(Deserialize); see the
 /// impl below.
+#[cfg(feature = "gui")]
 #[derive(Debug, Copy, Clone, Hash, Default, Eq, PartialEq)]
 struct ModsWrapper(ModifiersState);
 
+#[cfg(feature = "gui")]
 impl ModsWrapper {
     fn into_inner(self) -> ModifiersState {
         self.0
     }
 }
 
+#[cfg(feature = "gui")]
 impl<'a> de::Deserialize<'a> for ModsWrapper {
     fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
         where D: de::Deserializer<'a>
@@ -702,7 +1393,8 @@ impl<'a> de::Deserialize<'a> for ModsWrapper {
                         "Shift" => res.shift = true,
                         "Alt" | "Option" => res.alt = true,
                         "Control" => res.ctrl = true,
-                        _ => eprintln!("unknown modifier {:?}", modifier),
+                        "" => (),
+                        _ => problems::report(format!("invalid modifier {:?}", modifier)),
                     }
                 }
 
@@ -714,14 +1406,37 @@ impl<'a> de::Deserialize<'a> for ModsWrapper {
     }
 }
 
+#[cfg(feature = "gui")]
+impl Serialize for ModsWrapper {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mods = [
+            (self.0.logo, "Super"),
+            (self.0.shift, "Shift"),
+            (self.0.alt, "Alt"),
+            (self.0.ctrl, "Control"),
+        ];
+        let names: Vec<&str> = mods.iter()
+            .filter(|&&(pressed, _)| pressed)
+            .map(|&(_, name)| name)
+            .collect();
+
+        serializer.serialize_str(&names.join("|"))
+    }
+}
+
+#[cfg(feature = "gui")]
 struct ActionWrapper(::input::Action);
 
+#[cfg(feature = "gui")]
 impl ActionWrapper {
     fn into_inner(self) -> ::input::Action {
         self.0
     }
 }
 
+#[cfg(feature = "gui")]
 impl<'a> de::Deserialize<'a> for ActionWrapper {
     fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
         where D: de::Deserializer<'a>
@@ -732,9 +1447,12 @@ impl<'a> de::Deserialize<'a> for ActionWrapper {
             type Value = ActionWrapper;
 
             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                f.write_str("Paste, Copy, PasteSelection, IncreaseFontSize, DecreaseFontSize, \
-                            ResetFontSize, ScrollPageUp, ScrollPageDown, ScrollToTop, \
-                            ScrollToBottom, ClearHistory, Hide, or Quit")
+                f.write_str("Paste, Copy, CopyAsHtml, PasteSelection, IncreaseFontSize, \
+                            DecreaseFontSize, ResetFontSize, ScrollPageUp, ScrollPageDown, \
+                            ScrollHalfPageUp, ScrollHalfPageDown, ScrollToTop, ScrollToBottom, \
+                            ClearHistory, Hide, ToggleFullscreen, ToggleSimpleFullscreen, \
+                            ToggleSecureInput, DumpFrameStats, DumpScreen, DumpState, \
+                            SimulateContextLoss, Screenshot, or Quit")
             }
 
             fn visit_str<E>(self, value: &str) -> ::std::result::Result<ActionWrapper, E>
@@ -743,16 +1461,27 @@ impl<'a> de::Deserialize<'a> for ActionWrapper {
                 Ok(ActionWrapper(match value {
                     "Paste" => Action::Paste,
                     "Copy" => Action::Copy,
+                    "CopyAsHtml" => Action::CopyAsHtml,
                     "PasteSelection" => Action::PasteSelection,
                     "IncreaseFontSize" => Action::IncreaseFontSize,
                     "DecreaseFontSize" => Action::DecreaseFontSize,
                     "ResetFontSize" => Action::ResetFontSize,
                     "ScrollPageUp" => Action::ScrollPageUp,
                     "ScrollPageDown" => Action::ScrollPageDown,
+                    "ScrollHalfPageUp" => Action::ScrollHalfPageUp,
+                    "ScrollHalfPageDown" => Action::ScrollHalfPageDown,
                     "ScrollToTop" => Action::ScrollToTop,
                     "ScrollToBottom" => Action::ScrollToBottom,
                     "ClearHistory" => Action::ClearHistory,
                     "Hide" => Action::Hide,
+                    "ToggleFullscreen" => Action::ToggleFullscreen,
+                    "ToggleSimpleFullscreen" => Action::ToggleSimpleFullscreen,
+                    "ToggleSecureInput" => Action::ToggleSecureInput,
+                    "DumpFrameStats" => Action::DumpFrameStats,
+                    "DumpScreen" => Action::DumpScreen,
+                    "DumpState" => Action::DumpState,
+                    "SimulateContextLoss" => Action::SimulateContextLoss,
+                    "Screenshot" => Action::Screenshot,
                     "Quit" => Action::Quit,
                     _ => return Err(E::invalid_value(Unexpected::Str(value), &self)),
                 }))
@@ -762,7 +1491,45 @@ impl<'a> de::Deserialize<'a> for ActionWrapper {
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Actions with arguments are serialized as `chars` or `command` of the binding
+#[cfg(feature = "gui")]
+impl Serialize for ActionWrapper {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_str(match self.0 {
+            Action::Paste => "Paste",
+            Action::Copy => "Copy",
+            Action::CopyAsHtml => "CopyAsHtml",
+            Action::PasteSelection => "PasteSelection",
+            Action::IncreaseFontSize => "IncreaseFontSize",
+            Action::DecreaseFontSize => "DecreaseFontSize",
+            Action::ResetFontSize => "ResetFontSize",
+            Action::ScrollPageUp => "ScrollPageUp",
+            Action::ScrollPageDown => "ScrollPageDown",
+            Action::ScrollHalfPageUp => "ScrollHalfPageUp",
+            Action::ScrollHalfPageDown => "ScrollHalfPageDown",
+            Action::ScrollToTop => "ScrollToTop",
+            Action::ScrollToBottom => "ScrollToBottom",
+            Action::ClearHistory => "ClearHistory",
+            Action::Hide => "Hide",
+            Action::ToggleFullscreen => "ToggleFullscreen",
+            Action::ToggleSimpleFullscreen => "ToggleSimpleFullscreen",
+            Action::ToggleSecureInput => "ToggleSecureInput",
+            Action::DumpFrameStats => "DumpFrameStats",
+            Action::DumpScreen => "DumpScreen",
+            Action::DumpState => "DumpState",
+            Action::SimulateContextLoss => "SimulateContextLoss",
+            Action::Screenshot => "Screenshot",
+            Action::Quit => "Quit",
+            Action::Esc(_) | Action::Command(..) => {
+                return Err(<S::Error as ser::Error>::custom("action has arguments"));
+            },
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(untagged)]
 pub enum CommandWrapper {
     Just(String),
@@ -789,13 +1556,16 @@ impl CommandWrapper {
     }
 }
 
+#[cfg(feature = "gui")]
 use ::term::{mode, TermMode};
 
+#[cfg(feature = "gui")]
 struct ModeWrapper {
     pub mode: TermMode,
     pub not_mode: TermMode,
 }
 
+#[cfg(feature = "gui")]
 impl<'a> de::Deserialize<'a> for ModeWrapper {
     fn deserialize<D>(deserializer:  D) -> ::std::result::Result<Self, D::Error>
         where D: de::Deserializer<'a>
@@ -823,7 +1593,8 @@ impl<'a> de::Deserialize<'a> for ModeWrapper {
                         "~AppCursor" => res.not_mode |= mode::TermMode::APP_CURSOR,
                         "AppKeypad" => res.mode |= mode::TermMode::APP_KEYPAD,
                         "~AppKeypad" => res.not_mode |= mode::TermMode::APP_KEYPAD,
-                        _ => eprintln!("unknown mode {:?}", modifier),
+                        "" => (),
+                        _ => problems::report(format!("invalid mode {:?}", modifier)),
                     }
                 }
 
@@ -834,14 +1605,40 @@ impl<'a> de::Deserialize<'a> for ModeWrapper {
     }
 }
 
+#[cfg(feature = "gui")]
+impl Serialize for ModeWrapper {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let modes = [
+            (mode::TermMode::APP_CURSOR, "AppCursor"),
+            (mode::TermMode::APP_KEYPAD, "AppKeypad"),
+        ];
+
+        let mut names = Vec::new();
+        for &(mode, name) in &modes {
+            if self.mode.contains(mode) {
+                names.push(name.to_owned());
+            } else if self.not_mode.contains(mode) {
+                names.push(format!("~{}", name));
+            }
+        }
+
+        serializer.serialize_str(&names.join("|"))
+    }
+}
+
+#[cfg(feature = "gui")]
 struct MouseButton(::glutin::MouseButton);
 
+#[cfg(feature = "gui")]
 impl MouseButton {
     fn into_inner(self) -> ::glutin::MouseButton {
         self.0
     }
 }
 
+#[cfg(feature = "gui")]
 impl<'a> de::Deserialize<'a> for MouseButton {
     fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
         where D: de::Deserializer<'a>
@@ -877,8 +1674,23 @@ impl<'a> de::Deserialize<'a> for MouseButton {
     }
 }
 
+#[cfg(feature = "gui")]
+impl Serialize for MouseButton {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        match self.0 {
+            ::glutin::MouseButton::Left => serializer.serialize_str("Left"),
+            ::glutin::MouseButton::Right => serializer.serialize_str("Right"),
+            ::glutin::MouseButton::Middle => serializer.serialize_str("Middle"),
+            ::glutin::MouseButton::Other(index) => serializer.serialize_str(&index.to_string()),
+        }
+    }
+}
+
 /// Bindings are deserialized into a `RawBinding` before being parsed as a
 /// `KeyBinding` or `MouseBinding`.
+#[cfg(feature = "gui")]
 struct RawBinding {
     key: Option<Key>,
     mouse: Option<::glutin::MouseButton>,
@@ -888,6 +1700,7 @@ struct RawBinding {
     action: Action,
 }
 
+#[cfg(feature = "gui")]
 impl RawBinding {
     fn into_mouse_binding(self) -> ::std::result::Result<MouseBinding, Self> {
         if let Some(mouse) = self.mouse {
@@ -918,6 +1731,7 @@ impl RawBinding {
     }
 }
 
+#[cfg(feature = "gui")]
 impl<'a> de::Deserialize<'a> for RawBinding {
     fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
         where D: de::Deserializer<'a>
@@ -1002,7 +1816,21 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                             }
 
                             let val = map.next_value::<serde_yaml::Value>()?;
-                            if val.is_u64() {
+                            let physical = val.as_mapping()
+                                .and_then(|mapping| mapping.get(&"physical".into()))
+                                .cloned();
+                            if let Some(physical) = physical {
+                                // Key at the position it has on a US layout
+                                let k = Key::deserialize(physical)
+                                    .map_err(V::Error::custom)?;
+                                let scancode = k.to_scancode().ok_or_else(|| {
+                                    <V::Error as Error>::custom(format!(
+                                        "invalid key binding, {:?} has no physical position",
+                                        k
+                                    ))
+                                })?;
+                                key = Some(Key::Scancode(scancode));
+                            } else if val.is_u64() {
                                 let scancode = val.as_u64().unwrap();
                                 if scancode > u64::from(::std::u32::MAX) {
                                     return Err(<V::Error as Error>::custom(format!(
@@ -1022,14 +1850,17 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                                 return Err(<V::Error as Error>::duplicate_field("mods"));
                             }
 
-                            mods = Some(map.next_value::<ModsWrapper>()?.into_inner());
+                            let wrapper = problems::with_path("mods", || map.next_value::<ModsWrapper>())?;
+                            mods = Some(wrapper.into_inner());
                         },
                         Field::Mode => {
                             if mode.is_some() {
                                 return Err(<V::Error as Error>::duplicate_field("mode"));
                             }
 
-                            let mode_deserializer = map.next_value::<ModeWrapper>()?;
+                            let mode_deserializer = problems::with_path("mode", || {
+                                map.next_value::<ModeWrapper>()
+                            })?;
                             mode = Some(mode_deserializer.mode);
                             not_mode = Some(mode_deserializer.not_mode);
                         },
@@ -1118,6 +1949,15 @@ impl<'a> de::Deserialize<'a> for Alpha {
     }
 }
 
+impl Serialize for Alpha {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_f32(self.0)
+    }
+}
+
+#[cfg(feature = "gui")]
 impl<'a> de::Deserialize<'a> for MouseBinding {
     fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
         where D: de::Deserializer<'a>
@@ -1128,6 +1968,7 @@ impl<'a> de::Deserialize<'a> for MouseBinding {
     }
 }
 
+#[cfg(feature = "gui")]
 impl<'a> de::Deserialize<'a> for KeyBinding {
     fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
         where D: de::Deserializer<'a>
@@ -1138,6 +1979,69 @@ impl<'a> de::Deserialize<'a> for KeyBinding {
     }
 }
 
+/// Serialize a binding in the format a `RawBinding` is deserialized from
+///
+/// Modifiers and modes are only written when the binding requires them.
+#[cfg(feature = "gui")]
+fn serialize_binding<S, T, U>(
+    binding: &Binding<T>,
+    trigger_field: &'static str,
+    trigger: U,
+    serializer: S,
+) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer,
+          U: Serialize
+{
+    let mut map = serializer.serialize_map(None)?;
+    map.serialize_entry(trigger_field, &trigger)?;
+
+    if binding.mods != ModifiersState::default() {
+        map.serialize_entry("mods", &ModsWrapper(binding.mods))?;
+    }
+
+    if !binding.mode.is_empty() || !binding.notmode.is_empty() {
+        let mode = ModeWrapper { mode: binding.mode, not_mode: binding.notmode };
+        map.serialize_entry("mode", &mode)?;
+    }
+
+    match binding.action {
+        Action::Esc(ref chars) => map.serialize_entry("chars", chars)?,
+        Action::Command(ref program, ref args) => {
+            let command = if args.is_empty() {
+                CommandWrapper::Just(program.clone())
+            } else {
+                CommandWrapper::WithArgs { program: program.clone(), args: args.clone() }
+            };
+            map.serialize_entry("command", &command)?;
+        },
+        ref action => map.serialize_entry("action", &ActionWrapper(action.clone()))?,
+    }
+
+    map.end()
+}
+
+#[cfg(feature = "gui")]
+impl Serialize for MouseBinding {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serialize_binding(self, "mouse", MouseButton(self.trigger), serializer)
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Serialize for KeyBinding {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        match self.trigger {
+            // Scancodes are written as plain numbers
+            Key::Scancode(scancode) => serialize_binding(self, "key", scancode, serializer),
+            key => serialize_binding(self, "key", key, serializer),
+        }
+    }
+}
+
 /// Errors occurring during config loading
 #[derive(Debug)]
 pub enum Error {
@@ -1155,92 +2059,248 @@ pub enum Error {
 
     /// Not valid yaml or missing parameters
     Yaml(serde_yaml::Error),
+
+    /// Problems with the values in strict mode
+    Strict(Vec<String>),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Colors {
     #[serde(default, deserialize_with = "failure_default")]
     pub primary: PrimaryColors,
     #[serde(default, deserialize_with = "failure_default")]
     pub cursor: CursorColors,
+    #[serde(default, deserialize_with = "failure_default")]
+    pub selection: SelectionColors,
+    #[serde(default, deserialize_with = "failure_default")]
+    pub scroll_indicator: ScrollIndicatorColors,
     pub normal: AnsiColors,
     pub bright: AnsiColors,
     #[serde(default, deserialize_with = "failure_default")]
     pub dim: Option<AnsiColors>,
     #[serde(default, deserialize_with = "failure_default_vec")]
     pub indexed_colors: Vec<IndexedColor>,
+    #[serde(default, deserialize_with = "failure_default")]
+    pub transparent_background_colors: bool,
+    #[serde(default = "default_minimum_contrast", deserialize_with = "deserialize_minimum_contrast")]
+    pub minimum_contrast: f64,
+}
+
+fn default_minimum_contrast() -> f64 {
+    1.0
+}
+
+fn deserialize_minimum_contrast<'a, D>(deserializer: D) -> ::std::result::Result<f64, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match f64::deserialize(deserializer) {
+        Ok(ratio) if ratio >= 1.0 && ratio <= 21.0 => Ok(ratio),
+        Ok(ratio) => {
+            problems::report(format!(
+                "Minimum contrast of {} is outside of 1.0 to 21.0; Using default value",
+                ratio,
+            ));
+            Ok(default_minimum_contrast())
+        },
+        Err(err) => {
+            problems::report(format!("{}; Using default value", err));
+            Ok(default_minimum_contrast())
+        },
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct IndexedColor {
     #[serde(deserialize_with = "deserialize_color_index")]
     pub index: u8,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub color: Rgb,
 }
 
 fn deserialize_color_index<'a, D>(deserializer: D) -> ::std::result::Result<u8, D::Error>
     where D: de::Deserializer<'a>
 {
-    match u8::deserialize(deserializer) {
-        Ok(index) => {
-            if index < 16 {
-                eprintln!(
-                    "problem with config: indexed_color's index is '{}', \
-                     but a value bigger than 15 was expected; \
-                     Ignoring setting",
-                    index
-                );
-
-                // Return value out of range to ignore this color
-                Ok(0)
-            } else {
-                Ok(index)
-            }
-        },
+    match u8::deserialize(deserializer) {
+        Ok(index) => {
+            if index < 16 {
+                problems::report_at("index", format!(
+                    "indexed_color's index is '{}', \
+                     but a value bigger than 15 was expected; \
+                     Use `colors.normal` and `colors.bright` for the first 16 colors; \
+                     Ignoring setting",
+                    index
+                ));
+
+                // Return value out of range to ignore this color
+                Ok(0)
+            } else {
+                Ok(index)
+            }
+        },
+        Err(err) => {
+            problems::report(format!("{}; Ignoring setting", err));
+
+            // Return value out of range to ignore this color
+            Ok(0)
+        },
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Cursor {
+    #[serde(default, deserialize_with = "failure_default")]
+    pub style: CursorStyle,
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    pub unfocused_hollow: bool,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            style: Default::default(),
+            unfocused_hollow: true,
+        }
+    }
+}
+
+/// Color which is either fixed or taken from the cell it's drawn on
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CellColor {
+    CellForeground,
+    CellBackground,
+    Rgb(Rgb),
+}
+
+impl CellColor {
+    #[inline]
+    pub fn rgb(self) -> Option<Rgb> {
+        match self {
+            CellColor::Rgb(rgb) => Some(rgb),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CellColor {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where D: de::Deserializer<'de>
+    {
+        struct CellColorVisitor;
+
+        impl<'a> Visitor<'a> for CellColorVisitor {
+            type Value = CellColor;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("CellForeground, CellBackground or a hex color like '0xffaabb'")
+            }
+
+            fn visit_str<E>(self, value: &str) -> ::std::result::Result<CellColor, E>
+                where E: de::Error
+            {
+                match value {
+                    "CellForeground" => Ok(CellColor::CellForeground),
+                    "CellBackground" => Ok(CellColor::CellBackground),
+                    _ => Rgb::from_str(value)
+                        .map(CellColor::Rgb)
+                        .map_err(|_| E::invalid_value(Unexpected::Str(value), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_str(CellColorVisitor)
+    }
+}
+
+impl Serialize for CellColor {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        match *self {
+            CellColor::CellForeground => serializer.serialize_str("CellForeground"),
+            CellColor::CellBackground => serializer.serialize_str("CellBackground"),
+            CellColor::Rgb(ref rgb) => rgb_to_hex(rgb, serializer),
+        }
+    }
+}
+
+fn deserialize_optional_cell_color<'a, D>(
+    deserializer: D,
+) -> ::std::result::Result<Option<CellColor>, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match Option::<CellColor>::deserialize(deserializer) {
+        Ok(color) => Ok(color),
         Err(err) => {
-            eprintln!("problem with config: {}; Ignoring setting", err);
-
-            // Return value out of range to ignore this color
-            Ok(0)
+            problems::report(format!("{}; Using the colors of the cell", err));
+            Ok(None)
         },
     }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize)]
-pub struct Cursor {
-    #[serde(default, deserialize_with = "failure_default")]
-    pub style: CursorStyle,
-    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
-    pub unfocused_hollow: bool,
+/// Cursor colors, the inverse of the cell under the cursor if unset
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+pub struct CursorColors {
+    #[serde(default, deserialize_with = "deserialize_optional_cell_color")]
+    pub text: Option<CellColor>,
+    #[serde(default, deserialize_with = "deserialize_optional_cell_color")]
+    pub cursor: Option<CellColor>,
 }
 
-impl Default for Cursor {
-    fn default() -> Self {
-        Self {
-            style: Default::default(),
-            unfocused_hollow: true,
-        }
+impl CursorColors {
+    #[inline]
+    pub fn text(&self) -> CellColor {
+        self.text.unwrap_or(CellColor::CellBackground)
+    }
+
+    #[inline]
+    pub fn cursor(&self) -> CellColor {
+        self.cursor.unwrap_or(CellColor::CellForeground)
     }
 }
 
-#[derive(Debug, Copy, Clone, Default, Deserialize)]
-pub struct CursorColors {
+/// Colors of selected cells, the inverse of the cell if unset
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+pub struct SelectionColors {
+    #[serde(default, deserialize_with = "deserialize_optional_cell_color")]
+    pub text: Option<CellColor>,
+    #[serde(default, deserialize_with = "deserialize_optional_cell_color")]
+    pub background: Option<CellColor>,
+}
+
+impl SelectionColors {
+    #[inline]
+    pub fn text(&self) -> CellColor {
+        self.text.unwrap_or(CellColor::CellBackground)
+    }
+
+    #[inline]
+    pub fn background(&self) -> CellColor {
+        self.background.unwrap_or(CellColor::CellForeground)
+    }
+}
+
+/// Colors of the scrollback position indicator, inverse of the primary colors if unset
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+pub struct ScrollIndicatorColors {
     #[serde(default, deserialize_with = "deserialize_optional_color")]
-    pub text: Option<Rgb>,
+    #[serde(serialize_with = "serialize_optional_color")]
+    pub foreground: Option<Rgb>,
     #[serde(default, deserialize_with = "deserialize_optional_color")]
-    pub cursor: Option<Rgb>,
+    #[serde(serialize_with = "serialize_optional_color")]
+    pub background: Option<Rgb>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PrimaryColors {
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub background: Rgb,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub foreground: Rgb,
     #[serde(default, deserialize_with = "deserialize_optional_color")]
+    #[serde(serialize_with = "serialize_optional_color")]
     pub bright_foreground: Option<Rgb>,
     #[serde(default, deserialize_with = "deserialize_optional_color")]
+    #[serde(serialize_with = "serialize_optional_color")]
     pub dim_foreground: Option<Rgb>,
 }
 
@@ -1254,7 +2314,7 @@ fn deserialize_optional_color<'a, D>(deserializer: D) -> ::std::result::Result<O
         },
         Ok(None) => Ok(None),
         Err(err) => {
-            eprintln!("problem with config: {}; Using standard foreground color", err);
+            problems::report(format!("{}; Using standard foreground color", err));
             Ok(None)
         },
     }
@@ -1276,6 +2336,8 @@ impl Default for Colors {
         Colors {
             primary: PrimaryColors::default(),
             cursor: CursorColors::default(),
+            selection: SelectionColors::default(),
+            scroll_indicator: ScrollIndicatorColors::default(),
             normal: AnsiColors {
                 black: Rgb {r: 0x00, g: 0x00, b: 0x00},
                 red: Rgb {r: 0xd5, g: 0x4e, b: 0x53},
@@ -1298,28 +2360,30 @@ impl Default for Colors {
             },
             dim: None,
             indexed_colors: Vec::new(),
+            transparent_background_colors: false,
+            minimum_contrast: default_minimum_contrast(),
         }
     }
 }
 
 /// The 8-colors sections of config
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AnsiColors {
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub black: Rgb,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub red: Rgb,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub green: Rgb,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub yellow: Rgb,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub blue: Rgb,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub magenta: Rgb,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub cyan: Rgb,
-    #[serde(deserialize_with = "rgb_from_hex")]
+    #[serde(deserialize_with = "rgb_from_hex", serialize_with = "rgb_to_hex")]
     pub white: Rgb,
 }
 
@@ -1353,12 +2417,31 @@ fn rgb_from_hex<'a, D>(deserializer: D) -> ::std::result::Result<Rgb, D::Error>
     match rgb {
         Ok(rgb) => Ok(rgb),
         Err(err) => {
-            eprintln!("problem with config: {}; Using color #ff00ff", err);
+            problems::report(format!("{}; Using color #ff00ff", err));
             Ok(Rgb { r: 255, g: 0, b: 255 })
         },
     }
 }
 
+/// Serialize an Rgb as a hex string, the counterpart of `rgb_from_hex`
+fn rgb_to_hex<S>(rgb: &Rgb, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    serializer.serialize_str(&format!("0x{:02x}{:02x}{:02x}", rgb.r, rgb.g, rgb.b))
+}
+
+fn serialize_optional_color<S>(
+    color: &Option<Rgb>,
+    serializer: S,
+) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    match *color {
+        Some(ref rgb) => rgb_to_hex(rgb, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
 impl FromStr for Rgb {
     type Err = ();
     fn from_str(s: &str) -> ::std::result::Result<Rgb, ()> {
@@ -1396,7 +2479,7 @@ impl FromStr for Rgb {
 impl ::std::error::Error for Error {
     fn cause(&self) -> Option<&::std::error::Error> {
         match *self {
-            Error::NotFound | Error::Empty => None,
+            Error::NotFound | Error::Empty | Error::Strict(_) => None,
             Error::ReadingEnvHome(ref err) => Some(err),
             Error::Io(ref err) => Some(err),
             Error::Yaml(ref err) => Some(err),
@@ -1410,6 +2493,7 @@ impl ::std::error::Error for Error {
             Error::ReadingEnvHome(ref err) => err.description(),
             Error::Io(ref err) => err.description(),
             Error::Yaml(ref err) => err.description(),
+            Error::Strict(_) => "invalid config values in strict mode",
         }
     }
 }
@@ -1423,6 +2507,13 @@ impl ::std::fmt::Display for Error {
             },
             Error::Io(ref err) => write!(f, "error reading config file: {}", err),
             Error::Yaml(ref err) => write!(f, "problem with config: {}", err),
+            Error::Strict(ref problems) => {
+                write!(f, "invalid config in strict mode:")?;
+                for problem in problems {
+                    write!(f, "\n    {}", problem)?;
+                }
+                Ok(())
+            },
         }
     }
 }
@@ -1452,6 +2543,22 @@ impl From<serde_yaml::Error> for Error {
 /// Result from config loading
 pub type Result<T> = ::std::result::Result<T, Error>;
 
+/// Parts of a reloaded config which are expensive to apply
+///
+/// Everything else is cheap and applied on every reload.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Changes {
+    /// Fonts, their size or offsets, the glyph cache is rebuilt
+    pub font: bool,
+
+    /// Padding of the window, the grid is resized
+    pub padding: bool,
+
+    /// Key or mouse bindings
+    #[cfg(feature = "gui")]
+    pub bindings: bool,
+}
+
 impl Config {
     /// Get the location of the first found default config file paths
     /// according to the following order:
@@ -1532,10 +2639,12 @@ impl Config {
         self.background_opacity
     }
 
+    #[cfg(feature = "gui")]
     pub fn key_bindings(&self) -> &[KeyBinding] {
         &self.key_bindings[..]
     }
 
+    #[cfg(feature = "gui")]
     pub fn mouse_bindings(&self) -> &[MouseBinding] {
         &self.mouse_bindings[..]
     }
@@ -1557,6 +2666,17 @@ impl Config {
             .unwrap_or(&self.window.padding)
     }
 
+    /// Expensive changes needed to go from this config to `new`
+    pub fn changes(&self, new: &Config) -> Changes {
+        Changes {
+            font: self.font != new.font,
+            padding: self.padding() != new.padding(),
+            #[cfg(feature = "gui")]
+            bindings: self.key_bindings != new.key_bindings
+                || self.mouse_bindings != new.mouse_bindings,
+        }
+    }
+
     #[inline]
     pub fn draw_bold_text_with_bright_colors(&self) -> bool {
         self.draw_bold_text_with_bright_colors
@@ -1592,6 +2712,30 @@ impl Config {
         &self.visual_bell
     }
 
+    /// Get bell config
+    #[inline]
+    pub fn bell(&self) -> &BellConfig {
+        &self.bell
+    }
+
+    /// Get printer config
+    #[inline]
+    pub fn printer(&self) -> &PrinterConfig {
+        &self.printer
+    }
+
+    /// Get terminal config
+    #[inline]
+    pub fn terminal(&self) -> &TerminalConfig {
+        &self.terminal
+    }
+
+    /// Get screen dump config
+    #[inline]
+    pub fn screen_dump(&self) -> &ScreenDumpConfig {
+        &self.screen_dump
+    }
+
     /// Should show render timer
     #[inline]
     pub fn render_timer(&self) -> bool {
@@ -1641,6 +2785,12 @@ impl Config {
         self.live_config_reload
     }
 
+    /// Send ESC before characters typed with Alt held
+    #[inline]
+    pub fn alt_send_esc(&self) -> bool {
+        self.alt_send_esc
+    }
+
     #[inline]
     pub fn dynamic_title(&self) -> bool {
         self.dynamic_title
@@ -1652,16 +2802,42 @@ impl Config {
         self.scrolling
     }
 
+    /// Touchscreen gesture settings
+    #[inline]
+    pub fn touch(&self) -> Touch {
+        self.touch
+    }
+
+    /// Debugging and performance tuning settings
+    #[inline]
+    pub fn debug(&self) -> &Debug {
+        &self.debug
+    }
+
     /// Cursor foreground color
+    ///
+    /// Fixed colors are looked up as `NamedColor::CursorText`, so they can be
+    /// changed by escape sequences.
     #[inline]
-    pub fn cursor_text_color(&self) -> Option<Color> {
-        self.colors.cursor.text.map(|_| Color::Named(NamedColor::CursorText))
+    pub fn cursor_text_color(&self, cell_fg: Color, cell_bg: Color) -> Color {
+        match self.colors.cursor.text() {
+            CellColor::CellForeground => cell_fg,
+            CellColor::CellBackground => cell_bg,
+            CellColor::Rgb(_) => Color::Named(NamedColor::CursorText),
+        }
     }
 
     /// Cursor background color
+    ///
+    /// Fixed colors are looked up as `NamedColor::Cursor`, so they can be
+    /// changed by escape sequences.
     #[inline]
-    pub fn cursor_cursor_color(&self) -> Option<Color> {
-        self.colors.cursor.cursor.map(|_| Color::Named(NamedColor::Cursor))
+    pub fn cursor_cursor_color(&self, cell_fg: Color, cell_bg: Color) -> Color {
+        match self.colors.cursor.cursor() {
+            CellColor::CellForeground => cell_fg,
+            CellColor::CellBackground => cell_bg,
+            CellColor::Rgb(_) => Color::Named(NamedColor::Cursor),
+        }
     }
 
     // Update the history size, used in ref tests
@@ -1669,16 +2845,39 @@ impl Config {
         self.scrolling.history = history;
     }
 
-    pub fn load_from<P: Into<PathBuf>>(path: P) -> Result<Config> {
+    /// Load the config file at `path`
+    ///
+    /// Invalid values are replaced by their defaults with a warning, unless
+    /// `strict` or `debug.strict` is set and they make loading fail.
+    pub fn load_from<P: Into<PathBuf>>(path: P, strict: bool) -> Result<Config> {
         let path = path.into();
         let raw = Config::read_file(path.as_path())?;
-        let mut config: Config = serde_yaml::from_str(&raw)?;
+        let mut config = Config::from_yaml(&raw, strict)?;
         config.config_path = Some(path);
         config.print_deprecation_warnings();
 
         Ok(config)
     }
 
+    fn from_yaml(raw: &str, strict: bool) -> Result<Config> {
+        let (config, problems) = problems::collect(raw, || -> Result<Config> {
+            let config: Config = serde_yaml::from_str(raw)?;
+            problems::report_unknown_fields(&serde_yaml::to_value(&config)?);
+            Ok(config)
+        });
+        let config = config?;
+
+        if !problems.is_empty() && (strict || config.debug.strict) {
+            return Err(Error::Strict(problems));
+        }
+
+        for problem in problems {
+            eprintln!("problem with config: {}", problem);
+        }
+
+        Ok(config)
+    }
+
     /// Overrides the `dynamic_title` configuration based on `--title`.
     pub fn update_dynamic_title(mut self, options: &Options) -> Self {
         if options.title.is_some() {
@@ -1687,6 +2886,43 @@ impl Config {
         self
     }
 
+    /// Overrides the settings which have a command line flag with its value.
+    ///
+    /// Alacritty reads these flags from the `Options` directly, this is only
+    /// needed to show the values which are actually used.
+    pub fn with_cli_overrides(mut self, options: &Options) -> Self {
+        if let Some(dimensions) = options.dimensions() {
+            self.dimensions = None;
+            self.window.dimensions = dimensions;
+        }
+
+        if let Some(live_config_reload) = options.live_config_reload {
+            self.live_config_reload = live_config_reload;
+        }
+
+        if options.persistent_logging {
+            self.debug.persistent_logging = true;
+        }
+
+        if options.strict_config {
+            self.debug.strict = true;
+        }
+
+        if let Some(command) = options.command() {
+            self.shell = Some(Shell::new_with_args(
+                command.program().to_owned(),
+                command.args().to_vec(),
+            ));
+        }
+
+        self
+    }
+
+    /// Serialize the config in the format of the config file
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
     fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
         let mut f = fs::File::open(path)?;
         let mut contents = String::new();
@@ -1744,7 +2980,7 @@ impl Config {
 /// Window Dimensions
 ///
 /// Newtype to avoid passing values incorrectly
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Dimensions {
     /// Window width in character columns
     columns: Column,
@@ -1781,7 +3017,7 @@ impl Dimensions {
 }
 
 /// A delta for a point in a 2 dimensional plane
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(bound(deserialize = "T: Deserialize<'de> + Default"))]
 pub struct Delta<T: Default> {
     /// Horizontal change
@@ -1837,20 +3073,26 @@ impl DeserializeSize for Size {
         match size {
             Ok(size) => Ok(size),
             Err(err) => {
-                eprintln!("problem with config: {}; Using size 12", err);
+                problems::report(format!("{}; Using size 12", err));
                 Ok(Size::new(12.))
             },
         }
     }
 }
 
+fn serialize_size<S>(size: &Size, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    serializer.serialize_f32(size.as_f32_pts())
+}
+
 /// Font config
 ///
 /// Defaults are provided at the level of this struct per platform, but not per
 /// field in this struct. It might be nice in the future to have defaults for
 /// each value independently. Alternatively, maybe erroring when the user
 /// doesn't provide complete config is Ok.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Font {
     /// Font family
     pub normal: FontDescription,
@@ -1862,7 +3104,7 @@ pub struct Font {
     pub bold: FontDescription,
 
     // Font size in points
-    #[serde(deserialize_with="DeserializeSize::deserialize")]
+    #[serde(deserialize_with="DeserializeSize::deserialize", serialize_with="serialize_size")]
     pub size: Size,
 
     /// Extra spacing per character
@@ -1878,6 +3120,104 @@ pub struct Font {
 
     #[serde(default="true_bool", deserialize_with = "default_true_bool")]
     scale_with_dpi: bool,
+
+    /// Use the embedded bitmaps of fonts at sizes they're available for
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    use_embedded_bitmaps: bool,
+
+    /// OpenType features of styles without features of their own
+    #[serde(default, deserialize_with = "deserialize_features", serialize_with = "serialize_features")]
+    features: Vec<Feature>,
+
+    /// Antialiasing, subpixel order and hinting of glyphs
+    #[serde(default, deserialize_with = "failure_default")]
+    rendering: FontRendering,
+
+    /// Blend text with its background in linear light
+    #[serde(default, deserialize_with = "failure_default")]
+    gamma_correct: bool,
+
+    /// Styles synthesized for families without bold or italic faces
+    #[serde(default, deserialize_with = "failure_default")]
+    synthesize: Synthesize,
+}
+
+/// Styles which are synthesized from the regular face
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Synthesize {
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    pub bold: bool,
+
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    pub italic: bool,
+
+    /// Pixels the strokes of bold glyphs are widened by
+    #[serde(default="default_bold_strength", deserialize_with = "deserialize_bold_strength")]
+    pub bold_strength: u8,
+}
+
+impl Default for Synthesize {
+    fn default() -> Synthesize {
+        Synthesize {
+            bold: true,
+            italic: true,
+            bold_strength: default_bold_strength(),
+        }
+    }
+}
+
+fn default_bold_strength() -> u8 {
+    1
+}
+
+fn deserialize_bold_strength<'a, D>(deserializer: D) -> ::std::result::Result<u8, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match u8::deserialize(deserializer) {
+        Ok(strength) => Ok(strength),
+        Err(err) => {
+            problems::report(format!("{}; Using default value", err));
+            Ok(default_bold_strength())
+        },
+    }
+}
+
+/// Rasterization of glyphs, settings which aren't set are left to the system
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FontRendering {
+    #[serde(default, deserialize_with = "failure_default")]
+    antialias: Option<bool>,
+
+    #[serde(default, deserialize_with = "failure_default")]
+    subpixel: Subpixel,
+
+    #[serde(default, deserialize_with = "failure_default")]
+    hinting: Option<Hinting>,
+}
+
+/// Order of the color components of the screen's pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Subpixel {
+    Auto,
+    Rgb,
+    Bgr,
+    /// Grayscale antialiasing
+    None,
+}
+
+impl Default for Subpixel {
+    fn default() -> Subpixel {
+        Subpixel::Auto
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Hinting {
+    None,
+    Slight,
+    Full,
 }
 
 fn default_bold_desc() -> FontDescription {
@@ -1889,10 +3229,15 @@ fn default_italic_desc() -> FontDescription {
 }
 
 /// Description of a single font
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FontDescription {
     pub family: String,
     pub style: Option<String>,
+
+    /// Replaces the shared features of the font
+    #[serde(default, deserialize_with = "deserialize_style_features")]
+    #[serde(serialize_with = "serialize_style_features", skip_serializing_if = "Option::is_none")]
+    features: Option<Vec<Feature>>,
 }
 
 impl FontDescription {
@@ -1900,10 +3245,54 @@ impl FontDescription {
         FontDescription {
             family: family.into(),
             style: None,
+            features: None,
         }
     }
 }
 
+/// Deserialize a list of OpenType features, skipping the invalid ones
+fn deserialize_features<'a, D>(deserializer: D) -> ::std::result::Result<Vec<Feature>, D::Error>
+    where D: de::Deserializer<'a>
+{
+    let features: Vec<String> = failure_default_vec(deserializer)?;
+
+    Ok(features.iter()
+        .filter_map(|feature| match feature.parse() {
+            Ok(feature) => Some(feature),
+            Err(err) => {
+                problems::report(format!("{}; Skipping font feature", err));
+                None
+            },
+        })
+        .collect())
+}
+
+fn deserialize_style_features<'a, D>(
+    deserializer: D
+) -> ::std::result::Result<Option<Vec<Feature>>, D::Error>
+    where D: de::Deserializer<'a>
+{
+    deserialize_features(deserializer).map(Some)
+}
+
+fn serialize_features<S>(features: &[Feature], serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    serializer.collect_seq(features.iter().map(|feature| feature.to_string()))
+}
+
+fn serialize_style_features<S>(
+    features: &Option<Vec<Feature>>,
+    serializer: S,
+) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    match *features {
+        Some(ref features) => serialize_features(features, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
 impl Font {
     /// Get the font size in points
     #[inline]
@@ -1935,6 +3324,46 @@ impl Font {
     pub fn scale_with_dpi(&self) -> bool {
         self.scale_with_dpi
     }
+
+    #[inline]
+    pub fn use_embedded_bitmaps(&self) -> bool {
+        self.use_embedded_bitmaps
+    }
+
+    /// OpenType features of one of the styles of this font
+    #[inline]
+    pub fn features<'a>(&'a self, desc: &'a FontDescription) -> &'a [Feature] {
+        desc.features.as_ref().unwrap_or(&self.features)
+    }
+
+    #[inline]
+    pub fn gamma_correct(&self) -> bool {
+        self.gamma_correct
+    }
+
+    #[inline]
+    pub fn synthesize(&self) -> Synthesize {
+        self.synthesize
+    }
+
+    /// Get rasterization settings of the glyphs
+    pub fn rendering(&self) -> font::Rendering {
+        let rendering = &self.rendering;
+        font::Rendering {
+            antialias: rendering.antialias,
+            subpixel: match rendering.subpixel {
+                Subpixel::Auto => font::Subpixel::Auto,
+                Subpixel::Rgb => font::Subpixel::Rgb,
+                Subpixel::Bgr => font::Subpixel::Bgr,
+                Subpixel::None => font::Subpixel::None,
+            },
+            hinting: rendering.hinting.map(|hinting| match hinting {
+                Hinting::None => font::Hinting::None,
+                Hinting::Slight => font::Hinting::Slight,
+                Hinting::Full => font::Hinting::Full,
+            }),
+        }
+    }
 }
 
 #[cfg(target_os = "macos")]
@@ -1947,6 +3376,11 @@ impl Default for Font {
             size: Size::new(11.0),
             use_thin_strokes: true,
             scale_with_dpi: true,
+            use_embedded_bitmaps: true,
+            features: Vec::new(),
+            rendering: Default::default(),
+            gamma_correct: false,
+            synthesize: Default::default(),
             glyph_offset: Default::default(),
             offset: Default::default(),
         }
@@ -1963,6 +3397,11 @@ impl Default for Font {
             size: Size::new(11.0),
             use_thin_strokes: false,
             scale_with_dpi: true,
+            use_embedded_bitmaps: true,
+            features: Vec::new(),
+            rendering: Default::default(),
+            gamma_correct: false,
+            synthesize: Default::default(),
             glyph_offset: Default::default(),
             offset: Default::default(),
         }
@@ -1981,6 +3420,11 @@ impl Default for Font {
             offset: Default::default(),
             glyph_offset: Default::default(),
             scale_with_dpi: false,
+            use_embedded_bitmaps: true,
+            features: Vec::new(),
+            rendering: Default::default(),
+            gamma_correct: false,
+            synthesize: Default::default(),
         }
     }
 }
@@ -1994,6 +3438,7 @@ pub trait OnConfigReload {
     fn on_config_reload(&mut self);
 }
 
+#[cfg(feature = "gui")]
 impl OnConfigReload for ::window::Notifier {
     fn on_config_reload(&mut self) {
         self.notify();
@@ -2010,7 +3455,7 @@ impl Monitor {
 
         config
     }
-    pub fn new<H, P>(path: P, mut handler: H) -> Monitor
+    pub fn new<H, P>(path: P, strict: bool, mut handler: H) -> Monitor
         where H: OnConfigReload + Send + 'static,
               P: Into<PathBuf>
     {
@@ -2042,7 +3487,7 @@ impl Monitor {
                          | DebouncedEvent::Chmod(path) => {
                             // Reload file
                             if path == config_path {
-                                match Config::load_from(path) {
+                                match Config::load_from(path, strict) {
                                     Ok(config) => {
                                         let _ = config_tx.send(config);
                                         handler.on_config_reload();
@@ -2075,6 +3520,7 @@ mod tests {
     static ALACRITTY_YML: &'static str =
         include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/alacritty.yml"));
 
+    #[cfg(feature = "gui")]
     #[test]
     fn parse_config() {
         let config: Config = ::serde_yaml::from_str(ALACRITTY_YML)
@@ -2106,10 +3552,169 @@ mod tests {
         let config = config.update_dynamic_title(&options);
         assert!(!config.dynamic_title);
     }
+
+    #[test]
+    fn scrolling_multiplier_is_clamped() {
+        let scrolling: super::Scrolling = ::serde_yaml::from_str("{ multiplier: 255, faux_multiplier: 0 }")
+            .expect("deserialize scrolling");
+
+        assert_eq!(scrolling.multiplier, 100);
+        assert_eq!(scrolling.faux_multiplier, 0);
+    }
+
+    #[test]
+    fn read_buffer_size_is_clamped() {
+        let debug: super::Debug = ::serde_yaml::from_str("{ read_buffer_size: 1 }")
+            .expect("deserialize debug");
+        assert_eq!(debug.read_buffer_size, 0x1000);
+
+        let debug: super::Debug = ::serde_yaml::from_str("{ read_buffer_size: 1073741824 }")
+            .expect("deserialize debug");
+        assert_eq!(debug.read_buffer_size, 0x100_0000);
+
+        let debug: super::Debug = ::serde_yaml::from_str("{ read_buffer_size: 262144 }")
+            .expect("deserialize debug");
+        assert_eq!(debug.read_buffer_size, 0x4_0000);
+    }
+
+    #[test]
+    fn unfocused_opacity_defaults_to_opacity() {
+        use super::WindowConfig;
+
+        let window: WindowConfig = ::serde_yaml::from_str("{ opacity: 0.9 }")
+            .expect("deserialize window");
+        assert_eq!((window.opacity(true), window.opacity(false)), (0.9, 0.9));
+
+        let window: WindowConfig = ::serde_yaml::from_str("{ opacity: 2.0, unfocused_opacity: 0.5 }")
+            .expect("deserialize window");
+        assert_eq!((window.opacity(true), window.opacity(false)), (1.0, 0.5));
+    }
+
+    #[test]
+    fn selection_colors_accept_cell_colors() {
+        use super::{CellColor, SelectionColors};
+        use Rgb;
+
+        let colors: SelectionColors = ::serde_yaml::from_str(
+            "{ text: CellBackground, background: '0x102030' }"
+        ).expect("deserialize selection colors");
+
+        assert_eq!(colors.text(), CellColor::CellBackground);
+        assert_eq!(colors.background(), CellColor::Rgb(Rgb { r: 0x10, g: 0x20, b: 0x30 }));
+
+        // Invalid colors use the colors of the cell
+        let colors: SelectionColors = ::serde_yaml::from_str("{ text: CellText }")
+            .expect("deserialize selection colors");
+        assert_eq!(colors.text(), CellColor::CellBackground);
+        assert_eq!(colors.background(), CellColor::CellForeground);
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn physical_key_binding() {
+        use input::KeyBinding;
+        use super::Key;
+
+        let binding: KeyBinding = ::serde_yaml::from_str(
+            "{ key: { physical: C }, mods: Control|Shift, action: Copy }"
+        ).expect("deserialize binding");
+
+        assert_eq!(binding.trigger, Key::Scancode(Key::C.to_scancode().unwrap()));
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn serialized_config_round_trips() {
+        let config: Config = ::serde_yaml::from_str(ALACRITTY_YML)
+            .expect("deserialize config");
+        let yaml = config.to_yaml().expect("serialize config");
+
+        let reloaded: Config = ::serde_yaml::from_str(&yaml)
+            .expect("deserialize serialized config");
+        assert_eq!(reloaded.key_bindings.len(), config.key_bindings.len());
+        assert_eq!(reloaded.mouse_bindings.len(), config.mouse_bindings.len());
+        assert_eq!(reloaded.to_yaml().expect("serialize config"), yaml);
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn serialize_binding() {
+        use input::KeyBinding;
+
+        let yaml = "{ key: { physical: C }, mods: Control|Shift, mode: ~AppCursor, chars: \"\\x03\" }";
+        let binding: KeyBinding = ::serde_yaml::from_str(yaml).expect("deserialize binding");
+        let value = ::serde_yaml::to_value(&binding).expect("serialize binding");
+
+        let expected: ::serde_yaml::Value = ::serde_yaml::from_str(&format!(
+            "{{ key: {}, mods: Shift|Control, mode: ~AppCursor, chars: \"\\x03\" }}",
+            super::Key::C.to_scancode().unwrap()
+        )).unwrap();
+        assert_eq!(value, expected);
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn strict_mode_rejects_problems() {
+        use super::Error;
+
+        let yaml = "font:\n  sise: 12\nrender_timer: yes please\nkey_bindings:\n  \
+                    - { key: A, chars: a }\n  - { key: B, mods: Contrl, chars: b }\n";
+
+        // Without strict mode the problems are replaced by defaults
+        let config = Config::from_yaml(yaml, false).expect("load config");
+        assert!(!config.render_timer);
+        assert_eq!(config.key_bindings.len(), 2);
+
+        let problems = match Config::from_yaml(yaml, true) {
+            Err(Error::Strict(problems)) => problems,
+            _ => panic!("config with problems loaded in strict mode"),
+        };
+        assert_eq!(problems.len(), 3);
+        assert!(problems.iter().any(|p| p.starts_with("render_timer: invalid type")));
+        assert!(problems.contains(
+            &"key_bindings[1].mods: invalid modifier \"Contrl\" at line 6 column 15".to_owned()
+        ));
+        assert!(problems.contains(&"font.sise: unknown field at line 2 column 3".to_owned()));
+
+        // The config file can enable strict mode itself
+        let yaml = "debug:\n  strict: true\nscrolling:\n  history: 1000000\n";
+        assert!(Config::from_yaml(yaml, false).is_err());
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn reload_changes() {
+        use super::Changes;
+
+        let load = |yaml| Config::from_yaml(yaml, false).expect("load config");
+        let config = load("render_timer: false\n");
+
+        let render_timer = load("render_timer: true\n");
+        assert_eq!(config.changes(&render_timer), Changes::default());
+
+        let font = load("font:\n  normal:\n    family: Menlo\n  size: 20.0\n");
+        assert_eq!(config.changes(&font), Changes { font: true, ..Changes::default() });
+
+        let padding = load("window:\n  padding:\n    x: 5\n    y: 5\n");
+        assert_eq!(config.changes(&padding), Changes { padding: true, ..Changes::default() });
+
+        let bindings = load("key_bindings:\n  - { key: A, chars: a }\n");
+        assert_eq!(config.changes(&bindings), Changes { bindings: true, ..Changes::default() });
+    }
+
+    #[test]
+    fn us_layout_round_trip() {
+        use super::{Key, US_LAYOUT};
+
+        for &(scancode, key) in US_LAYOUT {
+            assert_eq!(Key::from_scancode(scancode), Some(key));
+            assert_eq!(key.to_scancode(), Some(scancode));
+        }
+    }
 }
 
 #[cfg_attr(feature = "cargo-clippy", allow(enum_variant_names))]
-#[derive(Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Key {
     Scancode(u32),
     Key1,
@@ -2194,6 +3799,7 @@ pub enum Key {
     Numpad9,
     AbntC1,
     AbntC2,
+    #[serde(alias = "NumpadAdd")]
     Add,
     Apostrophe,
     Apps,
@@ -2205,7 +3811,9 @@ pub enum Key {
     Colon,
     Comma,
     Convert,
+    #[serde(alias = "NumpadDecimal")]
     Decimal,
+    #[serde(alias = "NumpadDivide")]
     Divide,
     Equals,
     Grave,
@@ -2221,6 +3829,7 @@ pub enum Key {
     MediaSelect,
     MediaStop,
     Minus,
+    #[serde(alias = "NumpadMultiply")]
     Multiply,
     Mute,
     MyComputer,
@@ -2246,6 +3855,7 @@ pub enum Key {
     Slash,
     Sleep,
     Stop,
+    #[serde(alias = "NumpadSubtract")]
     Subtract,
     Sysrq,
     Tab,
@@ -2269,6 +3879,7 @@ pub enum Key {
 }
 
 impl Key {
+    #[cfg(feature = "gui")]
     pub fn from_glutin_input(key: ::glutin::VirtualKeyCode) -> Self {
         use glutin::VirtualKeyCode::*;
         // Thank you, vim macros and regex!
@@ -2429,4 +4040,110 @@ impl Key {
             Cut => Key::Cut,
         }
     }
+
+    /// Key located at a physical position, if the US layout was active
+    pub fn from_scancode(scancode: u32) -> Option<Key> {
+        US_LAYOUT.iter()
+            .find(|&&(code, _)| code == scancode)
+            .map(|&(_, key)| key)
+    }
+
+    /// Physical position of a key on the US layout
+    pub fn to_scancode(self) -> Option<u32> {
+        US_LAYOUT.iter()
+            .find(|&&(_, key)| key == self)
+            .map(|&(code, _)| code)
+    }
+
+    /// Numpad key located at a physical position
+    ///
+    /// Without NumLock the layout reports the numpad digits as the keys printed
+    /// below them, like `End` for `Numpad1`.
+    pub fn from_numpad_scancode(scancode: u32) -> Option<Key> {
+        NUMPAD.iter()
+            .find(|&&(code, _)| code == scancode)
+            .map(|&(_, key)| key)
+    }
+
+    /// Numpad key which was pressed, if any
+    ///
+    /// Windows reports scancodes without their extended bit, so the cursor
+    /// and editing keys share their scancodes with the numpad. There only
+    /// keys which are reported as numpad keys by the layout are taken, which
+    /// are the digits with NumLock enabled and the operators.
+    #[cfg(feature = "gui")]
+    pub fn from_numpad_input(input: ::glutin::KeyboardInput) -> Option<Key> {
+        let key = Key::from_numpad_scancode(input.scancode)?;
+        if cfg!(windows) && input.virtual_keycode.map(Key::from_glutin_input) != Some(key) {
+            return None;
+        }
+        Some(key)
+    }
 }
+
+/// Scancodes of the layout dependent keys on a US keyboard
+///
+/// Linux and Windows both use the PC scancode set here.
+#[cfg(not(target_os = "macos"))]
+const US_LAYOUT: &[(u32, Key)] = &[
+    (0x02, Key::Key1), (0x03, Key::Key2), (0x04, Key::Key3), (0x05, Key::Key4),
+    (0x06, Key::Key5), (0x07, Key::Key6), (0x08, Key::Key7), (0x09, Key::Key8),
+    (0x0a, Key::Key9), (0x0b, Key::Key0), (0x0c, Key::Minus), (0x0d, Key::Equals),
+    (0x10, Key::Q), (0x11, Key::W), (0x12, Key::E), (0x13, Key::R),
+    (0x14, Key::T), (0x15, Key::Y), (0x16, Key::U), (0x17, Key::I),
+    (0x18, Key::O), (0x19, Key::P), (0x1a, Key::LBracket), (0x1b, Key::RBracket),
+    (0x1e, Key::A), (0x1f, Key::S), (0x20, Key::D), (0x21, Key::F),
+    (0x22, Key::G), (0x23, Key::H), (0x24, Key::J), (0x25, Key::K),
+    (0x26, Key::L), (0x27, Key::Semicolon), (0x28, Key::Apostrophe), (0x29, Key::Grave),
+    (0x2b, Key::Backslash), (0x2c, Key::Z), (0x2d, Key::X), (0x2e, Key::C),
+    (0x2f, Key::V), (0x30, Key::B), (0x31, Key::N), (0x32, Key::M),
+    (0x33, Key::Comma), (0x34, Key::Period), (0x35, Key::Slash),
+];
+
+/// Scancodes of the numpad keys
+#[cfg(not(any(windows, target_os = "macos")))]
+const NUMPAD: &[(u32, Key)] = &[
+    (0x37, Key::Multiply), (0x47, Key::Numpad7), (0x48, Key::Numpad8), (0x49, Key::Numpad9),
+    (0x4a, Key::Subtract), (0x4b, Key::Numpad4), (0x4c, Key::Numpad5), (0x4d, Key::Numpad6),
+    (0x4e, Key::Add), (0x4f, Key::Numpad1), (0x50, Key::Numpad2), (0x51, Key::Numpad3),
+    (0x52, Key::Numpad0), (0x53, Key::Decimal), (0x60, Key::NumpadEnter),
+    (0x62, Key::Divide), (0x75, Key::NumpadEquals), (0x79, Key::NumpadComma),
+];
+
+/// Scancodes of the numpad keys
+///
+/// Enter and Divide share their scancodes with the main keyboard on Windows.
+#[cfg(windows)]
+const NUMPAD: &[(u32, Key)] = &[
+    (0x37, Key::Multiply), (0x47, Key::Numpad7), (0x48, Key::Numpad8), (0x49, Key::Numpad9),
+    (0x4a, Key::Subtract), (0x4b, Key::Numpad4), (0x4c, Key::Numpad5), (0x4d, Key::Numpad6),
+    (0x4e, Key::Add), (0x4f, Key::Numpad1), (0x50, Key::Numpad2), (0x51, Key::Numpad3),
+    (0x52, Key::Numpad0), (0x53, Key::Decimal),
+];
+
+/// Virtual key codes of the numpad keys
+#[cfg(target_os = "macos")]
+const NUMPAD: &[(u32, Key)] = &[
+    (0x41, Key::Decimal), (0x43, Key::Multiply), (0x45, Key::Add), (0x4b, Key::Divide),
+    (0x4c, Key::NumpadEnter), (0x4e, Key::Subtract), (0x51, Key::NumpadEquals),
+    (0x52, Key::Numpad0), (0x53, Key::Numpad1), (0x54, Key::Numpad2), (0x55, Key::Numpad3),
+    (0x56, Key::Numpad4), (0x57, Key::Numpad5), (0x58, Key::Numpad6), (0x59, Key::Numpad7),
+    (0x5b, Key::Numpad8), (0x5c, Key::Numpad9),
+];
+
+/// Virtual key codes of the layout dependent keys on a US keyboard
+#[cfg(target_os = "macos")]
+const US_LAYOUT: &[(u32, Key)] = &[
+    (0x00, Key::A), (0x01, Key::S), (0x02, Key::D), (0x03, Key::F),
+    (0x04, Key::H), (0x05, Key::G), (0x06, Key::Z), (0x07, Key::X),
+    (0x08, Key::C), (0x09, Key::V), (0x0b, Key::B), (0x0c, Key::Q),
+    (0x0d, Key::W), (0x0e, Key::E), (0x0f, Key::R), (0x10, Key::Y),
+    (0x11, Key::T), (0x12, Key::Key1), (0x13, Key::Key2), (0x14, Key::Key3),
+    (0x15, Key::Key4), (0x16, Key::Key6), (0x17, Key::Key5), (0x18, Key::Equals),
+    (0x19, Key::Key9), (0x1a, Key::Key7), (0x1b, Key::Minus), (0x1c, Key::Key8),
+    (0x1d, Key::Key0), (0x1e, Key::RBracket), (0x1f, Key::O), (0x20, Key::U),
+    (0x21, Key::LBracket), (0x22, Key::I), (0x23, Key::P), (0x25, Key::L),
+    (0x26, Key::J), (0x27, Key::Apostrophe), (0x28, Key::K), (0x29, Key::Semicolon),
+    (0x2a, Key::Backslash), (0x2b, Key::Comma), (0x2c, Key::Slash), (0x2d, Key::N),
+    (0x2e, Key::M), (0x2f, Key::Period), (0x32, Key::Grave),
+];
@@ -0,0 +1,327 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Problems found while loading the config
+//!
+//! Invalid values are replaced by their defaults, so a typo doesn't keep
+//! Alacritty from starting. The problems are collected together with the path
+//! and position of the value, to print them as warnings or to reject the whole
+//! config in strict mode.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+
+use serde_yaml::Value;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+thread_local! {
+    static COLLECTOR: RefCell<Option<Collector>> = RefCell::new(None);
+}
+
+/// Problems of the config which is currently being loaded
+struct Collector {
+    /// Parsed config, to find the values deserialized from a `serde_yaml::Value`
+    root: Value,
+
+    /// Position of every field and list element, by path
+    positions: HashMap<String, Marker>,
+
+    /// Path of the value deserialized by the config's own deserializers
+    path: Vec<String>,
+
+    problems: Vec<String>,
+}
+
+impl Collector {
+    fn add(&mut self, path: &str, message: &str) {
+        let problem = match self.positions.get(path) {
+            _ if path.is_empty() => message.to_owned(),
+            Some(mark) => {
+                format!("{}: {} at line {} column {}", path, message, mark.line(), mark.col() + 1)
+            },
+            None => format!("{}: {}", path, message),
+        };
+        self.problems.push(problem);
+    }
+}
+
+/// Collect the problems reported while `f` deserializes the config `raw`
+pub fn collect<T, F>(raw: &str, f: F) -> (T, Vec<String>)
+    where F: FnOnce() -> T
+{
+    // Syntax errors are left to serde_yaml, which reports them with a position
+    let mut positions = Positions::default();
+    let _ = Parser::new(raw.chars()).load(&mut positions, false);
+
+    let collector = Collector {
+        root: ::serde_yaml::from_str(raw).unwrap_or(Value::Null),
+        positions: positions.positions,
+        path: Vec::new(),
+        problems: Vec::new(),
+    };
+    let previous = COLLECTOR.with(|c| mem::replace(&mut *c.borrow_mut(), Some(collector)));
+
+    let value = f();
+
+    let collector = COLLECTOR.with(|c| mem::replace(&mut *c.borrow_mut(), previous));
+    (value, collector.map(|collector| collector.problems).unwrap_or_default())
+}
+
+/// Report a problem with the value which is currently deserialized
+///
+/// Messages of `serde_yaml` errors already contain the path and position of
+/// the value, unless it was deserialized from a `serde_yaml::Value`. Outside of
+/// `collect` the problem is printed right away.
+pub fn report<S: Into<String>>(message: S) {
+    let message = message.into();
+
+    let unreported = COLLECTOR.with(|c| match *c.borrow_mut() {
+        Some(ref mut collector) => {
+            let path = join(&collector.path);
+            collector.add(&path, &message);
+            None
+        },
+        None => Some(message),
+    });
+
+    if let Some(message) = unreported {
+        eprintln!("problem with config: {}", message);
+    }
+}
+
+/// Report a problem with `field` of the value which is currently deserialized
+///
+/// This is used for the values which are accepted with a warning, since the
+/// path is only known to `serde_yaml` when deserializing them fails.
+pub fn report_at<S: Into<String>>(field: &str, message: S) {
+    with_path(field, || report(message));
+}
+
+/// Run `f` with `field` appended to the path of reported problems
+pub fn with_path<T, F>(field: &str, f: F) -> T
+    where F: FnOnce() -> T
+{
+    COLLECTOR.with(|c| {
+        if let Some(ref mut collector) = *c.borrow_mut() {
+            collector.path.push(field.to_owned());
+        }
+    });
+
+    let value = f();
+
+    COLLECTOR.with(|c| {
+        if let Some(ref mut collector) = *c.borrow_mut() {
+            collector.path.pop();
+        }
+    });
+
+    value
+}
+
+/// Path of a list which is deserialized element by element
+///
+/// Lists deserialized from a `serde_yaml::Value` have no path, so they are
+/// looked up in the parsed config instead. Inside of a value deserialized by
+/// the config's own deserializers the path is relative, which makes it empty.
+pub fn sequence_path(sequence: &[Value]) -> String {
+    COLLECTOR.with(|c| match *c.borrow() {
+        Some(ref collector) if collector.path.is_empty() => {
+            find_sequence(&collector.root, sequence, &mut Vec::new())
+        },
+        _ => None,
+    }).unwrap_or_default()
+}
+
+/// Report the fields of the parsed config which are missing in `known`
+///
+/// `known` is the loaded config serialized again, which contains every field
+/// Alacritty understands.
+pub fn report_unknown_fields(known: &Value) {
+    COLLECTOR.with(|c| {
+        if let Some(ref mut collector) = *c.borrow_mut() {
+            let mut unknown = Vec::new();
+            find_unknown(&collector.root, known, &mut Vec::new(), &mut unknown);
+
+            for path in unknown {
+                collector.add(&path, "unknown field");
+            }
+        }
+    });
+}
+
+fn find_sequence(value: &Value, sequence: &[Value], path: &mut Vec<String>) -> Option<String> {
+    let children: Vec<(String, &Value)> = match *value {
+        Value::Sequence(ref elements) if elements[..] == *sequence => return Some(join(path)),
+        Value::Sequence(ref elements) => {
+            elements.iter().enumerate().map(|(i, value)| (format!("[{}]", i), value)).collect()
+        },
+        Value::Mapping(ref mapping) => {
+            mapping.iter().map(|(key, value)| (key_name(key), value)).collect()
+        },
+        _ => return None,
+    };
+
+    for (segment, child) in children {
+        path.push(segment);
+        let found = find_sequence(child, sequence, path);
+        path.pop();
+
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    None
+}
+
+fn find_unknown(value: &Value, known: &Value, path: &mut Vec<String>, unknown: &mut Vec<String>) {
+    // Lists aren't compared, since the invalid elements are dropped while
+    // loading and the fields of bindings are validated by their deserializer
+    if let (&Value::Mapping(ref mapping), &Value::Mapping(ref known)) = (value, known) {
+        for (key, value) in mapping {
+            path.push(key_name(key));
+            match known.get(key) {
+                Some(known) => find_unknown(value, known, path, unknown),
+                None => unknown.push(join(path)),
+            }
+            path.pop();
+        }
+    }
+}
+
+fn key_name(key: &Value) -> String {
+    match *key {
+        Value::String(ref key) => key.clone(),
+        Value::Number(ref key) => key.to_string(),
+        Value::Bool(key) => key.to_string(),
+        _ => String::from("?"),
+    }
+}
+
+/// Join path segments the way `serde_yaml` does, like `key_bindings[3].mods`
+fn join(segments: &[String]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        if !path.is_empty() && !segment.starts_with('[') {
+            path.push('.');
+        }
+        path.push_str(segment);
+    }
+    path
+}
+
+/// Mapping or sequence the parser is currently inside of
+enum Node {
+    Mapping { key: Option<String> },
+    Sequence { index: usize },
+}
+
+/// Receiver of parser events which records the position of each path
+#[derive(Default)]
+struct Positions {
+    nodes: Vec<Node>,
+    positions: HashMap<String, Marker>,
+}
+
+impl Positions {
+    fn path(&self) -> String {
+        let segments: Vec<String> = self.nodes.iter()
+            .filter_map(|node| match *node {
+                Node::Mapping { ref key } => key.clone(),
+                Node::Sequence { index } => Some(format!("[{}]", index)),
+            })
+            .collect();
+        join(&segments)
+    }
+
+    fn expects_key(&self) -> bool {
+        match self.nodes.last() {
+            Some(&Node::Mapping { key: None }) => true,
+            _ => false,
+        }
+    }
+
+    /// Record the position of the current path, keys take precedence over values
+    fn record(&mut self, mark: Marker) {
+        let path = self.path();
+        self.positions.entry(path).or_insert(mark);
+    }
+
+    /// Move on to the next key or element after a value
+    fn advance(&mut self) {
+        match self.nodes.last_mut() {
+            Some(&mut Node::Mapping { ref mut key }) => *key = None,
+            Some(&mut Node::Sequence { ref mut index }) => *index += 1,
+            None => (),
+        }
+    }
+}
+
+impl MarkedEventReceiver for Positions {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(ref value, ..) if self.expects_key() => {
+                if let Some(&mut Node::Mapping { ref mut key }) = self.nodes.last_mut() {
+                    *key = Some(value.clone());
+                }
+                self.record(mark);
+            },
+            Event::Scalar(..) | Event::Alias(_) => {
+                self.record(mark);
+                self.advance();
+            },
+            Event::MappingStart(_) => {
+                self.record(mark);
+                self.nodes.push(Node::Mapping { key: None });
+            },
+            Event::SequenceStart(_) => {
+                self.record(mark);
+                self.nodes.push(Node::Sequence { index: 0 });
+            },
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.nodes.pop();
+                self.advance();
+            },
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+
+    use super::{collect, report, sequence_path, with_path};
+
+    #[test]
+    fn problems_of_list_elements_have_positions() {
+        let raw = "font:\n  size: 11\nkey_bindings:\n  - { key: A }\n  - { key: B, mods: Contrl }\n";
+
+        let config: Value = ::serde_yaml::from_str(raw).unwrap();
+        let bindings = config["key_bindings"].as_sequence().unwrap();
+
+        let ((), problems) = collect(raw, || {
+            let path = format!("{}[1]", sequence_path(bindings));
+            with_path(&path, || {
+                with_path("mods", || report("invalid modifier \"Contrl\""));
+            });
+        });
+
+        assert_eq!(
+            problems,
+            vec![String::from("key_bindings[1].mods: invalid modifier \"Contrl\" at line 5 column 15")]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Programs started in the background
+//!
+//! URL launchers, commands bound to keys and the printer command are detached
+//! from Alacritty. On Unix they're forked twice, so init reaps them and they
+//! outlive the terminal, with stdio redirected to `/dev/null`. No other file
+//! descriptors are inherited, a browser keeping the master of the pty open
+//! would keep the shell from getting a hangup once Alacritty exits. On Windows
+//! they're started as detached processes in their own process group.
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Variables of the environment which describe the terminal window
+///
+/// Alacritty sets them for the shell, they're removed in case Alacritty was
+/// started from another terminal.
+const TERMINAL_ENV: &[&str] = &["WINDOWID"];
+
+/// Start a program in the background
+pub fn spawn<I, S>(program: &str, args: I) -> io::Result<()>
+    where I: IntoIterator<Item = S>,
+          S: AsRef<OsStr>
+{
+    let mut command = command(program, args);
+    start(&mut command)
+}
+
+/// Start a program in the background, writing `input` to its stdin
+///
+/// This blocks until all of the input was read.
+pub fn spawn_with_input<I, S>(program: &str, args: I, input: &[u8]) -> io::Result<()>
+    where I: IntoIterator<Item = S>,
+          S: AsRef<OsStr>
+{
+    let mut command = command(program, args);
+    command.stdin(Stdio::piped());
+
+    // The pipe is inherited by the detached process, the direct child is gone
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    child.wait().map(|_| ())
+}
+
+fn command<I, S>(program: &str, args: I) -> Command
+    where I: IntoIterator<Item = S>,
+          S: AsRef<OsStr>
+{
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    for var in TERMINAL_ENV {
+        command.env_remove(var);
+    }
+
+    detach(&mut command);
+    command
+}
+
+#[cfg(not(windows))]
+fn start(command: &mut Command) -> io::Result<()> {
+    // Only waits for the exit of the first fork
+    command.spawn()?.wait().map(|_| ())
+}
+
+#[cfg(windows)]
+fn start(command: &mut Command) -> io::Result<()> {
+    command.spawn().map(|_| ())
+}
+
+/// Highest file descriptor which is closed in the detached process
+#[cfg(not(windows))]
+const MAX_FD: libc::c_int = 1 << 16;
+
+#[cfg(not(windows))]
+fn detach(command: &mut Command) {
+    use std::cmp;
+    use std::mem;
+    use std::os::unix::process::CommandExt;
+
+    // Between fork and exec, only async-signal-safe functions are called here
+    command.before_exec(|| unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => (),
+            _ => libc::_exit(0),
+        }
+
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Closing on exec keeps the pipe reporting errors of exec working
+        let mut limit: libc::rlimit = mem::zeroed();
+        let max_fd = if libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) == 0 {
+            cmp::min(limit.rlim_cur, MAX_FD as libc::rlim_t) as libc::c_int
+        } else {
+            MAX_FD
+        };
+        for fd in 3..max_fd {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+
+        Ok(())
+    });
+}
+
+#[cfg(windows)]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+
+    use winapi::um::winbase::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+    command.creation_flags(CREATE_NEW_PROCESS_GROUP | DETACHED_PROCESS);
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{spawn, spawn_with_input};
+
+    #[test]
+    fn input_reaches_the_detached_process() {
+        let path = ::std::env::temp_dir().join(format!("alacritty-daemon-{}", ::std::process::id()));
+        let script = format!("cat > '{}'", path.display());
+        spawn_with_input("sh", &["-c", &script], b"printed").unwrap();
+
+        // The detached process may still be writing
+        for _ in 0..50 {
+            if fs::read(&path).map(|data| data == b"printed").unwrap_or(false) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        assert_eq!(fs::read(&path).unwrap(), b"printed");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_programs_are_reported() {
+        assert!(spawn("alacritty-missing-program", &[] as &[&str]).is_err());
+    }
+}
@@ -14,19 +14,33 @@
 
 //! The display subsystem including window management, font rasterization, and
 //! GPU drawing.
+use std::cmp::min;
+use std::io::BufWriter;
+use std::iter;
+use std::mem;
 use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
+use image::ColorType;
+use image::png::PNGEncoder;
 use parking_lot::MutexGuard;
 
 use Rgb;
 use config::{self, Config};
+use index::{Column, Line};
+use logging::Messages;
 use font::{self, Rasterize};
 use meter::Meter;
 use renderer::{self, GlyphCache, QuadRenderer};
-use term::{Term, SizeInfo, RenderableCell};
-use sync::FairMutex;
+use term::{OnResize, Term, SizeInfo, RenderCache};
+use sync::{FairMutex, SkippingLock};
+use util::create_timestamped_file;
+use util::thread;
 
-use window::{self, Pixels, Size};
+use window::{self, Pixels, Size, Window};
+
+/// Time after which a failed replacement of a lost context is tried again
+const CONTEXT_RETRY_SECS: u64 = 1;
 
 #[derive(Debug)]
 pub enum Error {
@@ -38,6 +52,9 @@ pub enum Error {
 
     /// Error in renderer
     Render(renderer::Error),
+
+    /// Font metrics and offsets resulting in cells smaller than a pixel
+    CellSize(f32, f32),
 }
 
 impl ::std::error::Error for Error {
@@ -46,6 +63,7 @@ impl ::std::error::Error for Error {
             Error::Window(ref err) => Some(err),
             Error::Font(ref err) => Some(err),
             Error::Render(ref err) => Some(err),
+            Error::CellSize(..) => None,
         }
     }
 
@@ -54,6 +72,7 @@ impl ::std::error::Error for Error {
             Error::Window(ref err) => err.description(),
             Error::Font(ref err) => err.description(),
             Error::Render(ref err) => err.description(),
+            Error::CellSize(..) => "font offset is too small",
         }
     }
 }
@@ -64,6 +83,9 @@ impl ::std::fmt::Display for Error {
             Error::Window(ref err) => err.fmt(f),
             Error::Font(ref err) => err.fmt(f),
             Error::Render(ref err) => err.fmt(f),
+            Error::CellSize(width, height) => {
+                write!(f, "font offset is too small; cells would be {} x {} pixels", width, height)
+            },
         }
     }
 }
@@ -89,6 +111,10 @@ impl From<renderer::Error> for Error {
 pub enum DisplayCommand {
     NewSize(u32, u32),
     NewHiDPIFactor(f32),
+    DumpFrameStats,
+    Screenshot,
+    /// The GL objects are gone, the renderer has to be rebuilt
+    ContextLost,
 }
 
 /// The display wraps a font rasterizer and GPU renderer
@@ -100,12 +126,28 @@ pub struct Display {
     tx: mpsc::Sender<DisplayCommand>,
     meter: Meter,
     font_size: font::Size,
+    dpr: f32,
+
+    /// The glyph cache and cell size are updated with the next resize
+    font_changed: bool,
+
     size_info: SizeInfo,
-}
+    render_cache: RenderCache,
+
+    /// Frames are skipped while the terminal is locked by the I/O thread
+    draw_lock: SkippingLock,
+
+    /// Write the next frame to a PNG file
+    screenshot_pending: bool,
+
+    /// The context was lost, nothing is drawn until it's replaced
+    context_lost: bool,
 
-/// Types that are interested in when the display is resized
-pub trait OnResize {
-    fn on_resize(&mut self, size: &SizeInfo);
+    /// Time of the last attempt to replace a lost context
+    last_recovery: Option<Instant>,
+
+    /// Message bar, which shows the frame stats when they're dumped
+    messages: Messages,
 }
 
 pub enum InitialSize {
@@ -114,8 +156,50 @@ pub enum InitialSize {
 }
 
 impl Display {
+    /// Apply the cheap parts of a reloaded config, colors are redrawn with the next frame
     pub fn update_config(&mut self, config: &Config) {
         self.render_timer = config.render_timer();
+        self.render_cache.invalidate();
+    }
+
+    /// Rebuild the glyph cache for the fonts of a reloaded config
+    ///
+    /// The rebuild happens with the next `handle_resize`, glyphs rasterized with
+    /// the old fonts must not be reused. The offsets change the size of the
+    /// cells as well.
+    pub fn update_font(&mut self, config: &Config) {
+        let font_rendering = Self::font_rendering(config, &self.renderer);
+        self.glyph_cache.set_rendering(font_rendering);
+        self.font_changed = true;
+    }
+
+    /// Resize the grid to the padding of a reloaded config
+    ///
+    /// The window keeps its size, unless the minimum grid doesn't fit into it
+    /// with the new padding. The grid dimensions and the viewport of the
+    /// renderer are computed again with the resize.
+    pub fn update_padding(&mut self, config: &Config) {
+        self.size_info.padding_x = f32::from(config.padding().x);
+        self.size_info.padding_y = f32::from(config.padding().y);
+        self.renderer.update_padding(config);
+
+        let min_dimensions = config.window().min_dimensions();
+        self.size_info.clamp_to_grid(min_dimensions.columns_u32(), min_dimensions.lines_u32());
+
+        let (width, height) = (self.size_info.width as u32, self.size_info.height as u32);
+        let _ = self.tx.send(DisplayCommand::NewSize(width, height));
+    }
+
+    /// Rasterization of glyphs, in grayscale unless the renderer blends subpixels
+    fn font_rendering(config: &Config, renderer: &QuadRenderer) -> font::Rendering {
+        let mut rendering = config.font().rendering();
+        if !renderer.blends_subpixels() && rendering.subpixel != font::Subpixel::None {
+            if rendering.subpixel != font::Subpixel::Auto {
+                warn!("Subpixel antialiasing isn't supported by the renderer, using grayscale");
+            }
+            rendering.subpixel = font::Subpixel::None;
+        }
+        rendering
     }
 
     /// Get size info about the display
@@ -136,8 +220,9 @@ impl Display {
         // those to calculate the actual size if needed.
         let zero_size = Size { width: Pixels(0), height: Pixels(0) };
         let mut renderer = QuadRenderer::new(&config, zero_size)?;
+        let font_rendering = Self::font_rendering(config, &renderer);
         let (glyph_cache, cell_width, cell_height) =
-            Self::new_glyph_cache(dpr, &mut renderer, config)?;
+            Self::new_glyph_cache(dpr, &mut renderer, config, font_rendering)?;
         let size = match size {
             InitialSize::Cells(dimensions) => {
                 let width = cell_width as u32 * dimensions.columns_u32();
@@ -149,10 +234,9 @@ impl Display {
             },
             InitialSize::Pixels(size) => size,
         };
-        renderer.resize(size.width.0 as _, size.height.0 as _);
         info!("Cell Size: ({} x {})", cell_width, cell_height);
 
-        let size_info = SizeInfo {
+        let mut size_info = SizeInfo {
             width: size.width.0 as f32,
             height: size.height.0 as f32,
             cell_width: cell_width as f32,
@@ -160,6 +244,9 @@ impl Display {
             padding_x: f32::from(config.padding().x),
             padding_y: f32::from(config.padding().y),
         };
+        let min_dimensions = config.window().min_dimensions();
+        size_info.clamp_to_grid(min_dimensions.columns_u32(), min_dimensions.lines_u32());
+        renderer.resize(size_info.width as _, size_info.height as _);
 
         // Channel for resize events
         //
@@ -189,15 +276,31 @@ impl Display {
             rx,
             meter: Meter::new(),
             font_size: font::Size::new(0.),
+            dpr,
+            font_changed: false,
             size_info,
+            render_cache: RenderCache::default(),
+            draw_lock: SkippingLock::default(),
+            screenshot_pending: false,
+            context_lost: false,
+            last_recovery: None,
+            messages: Messages::default(),
         })
     }
 
-    fn new_glyph_cache(dpr: f32, renderer: &mut QuadRenderer, config: &Config)
-        -> Result<(GlyphCache, f32, f32), Error>
-    {
+    fn new_glyph_cache(
+        dpr: f32,
+        renderer: &mut QuadRenderer,
+        config: &Config,
+        rendering: font::Rendering,
+    ) -> Result<(GlyphCache, f32, f32), Error> {
         let font = config.font().clone();
-        let rasterizer = font::Rasterizer::new(dpr, config.use_thin_strokes())?;
+        let rasterizer = font::Rasterizer::new(
+            dpr,
+            config.use_thin_strokes(),
+            config.font().use_embedded_bitmaps(),
+            rendering,
+        )?;
 
         // Initialize glyph cache
         let glyph_cache = {
@@ -223,7 +326,7 @@ impl Display {
 
         // Prevent invalid cell sizes
         if cell_width < 1. || cell_height < 1. {
-            panic!("font offset is too small");
+            return Err(Error::CellSize(cell_width, cell_height));
         }
 
         Ok((glyph_cache, cell_width.floor(), cell_height.floor()))
@@ -237,8 +340,64 @@ impl Display {
         });
 
         let metrics = cache.font_metrics();
-        self.size_info.cell_width = ((metrics.average_advance + f64::from(config.font().offset().x)) as f32).floor();
-        self.size_info.cell_height = ((metrics.line_height + f64::from(config.font().offset().y)) as f32).floor();
+        let cell_width = ((metrics.average_advance + f64::from(config.font().offset().x)) as f32).floor();
+        let cell_height = ((metrics.line_height + f64::from(config.font().offset().y)) as f32).floor();
+
+        // Offsets reloaded from the config are only checked here
+        if cell_width < 1. || cell_height < 1. {
+            error!("{}; Keeping the previous cell size", Error::CellSize(cell_width, cell_height));
+        } else {
+            self.size_info.cell_width = cell_width;
+            self.size_info.cell_height = cell_height;
+        }
+
+        // Changing the font at runtime must not result in cells without pixels
+        let min_dimensions = config.window().min_dimensions();
+        self.size_info.clamp_to_grid(min_dimensions.columns_u32(), min_dimensions.lines_u32());
+    }
+
+    /// Whether a lost context has to be replaced before the next frame
+    ///
+    /// Failed replacements are only tried again after a second.
+    pub fn needs_new_context(&self) -> bool {
+        let retry = Duration::from_secs(CONTEXT_RETRY_SECS);
+        self.context_lost && self.last_recovery.map_or(true, |time| time.elapsed() >= retry)
+    }
+
+    /// Replace the context which was lost, like after a GPU reset
+    ///
+    /// A reset context can't be used anymore, so the window is created again
+    /// with a new one and the renderer is rebuilt in it. The textures of the
+    /// glyph atlas are gone as well, so the glyph cache is rebuilt too. The
+    /// terminal isn't touched, all of it is drawn again with the next frame.
+    pub fn recover_context(&mut self, config: &Config, window: &mut Window) -> Result<(), Error> {
+        warn!("The OpenGL context was lost, creating the window again");
+        self.last_recovery = Some(Instant::now());
+
+        window.recreate_context(config.window(), config.debug().renderer_preference)?;
+
+        let size = Size {
+            width: Pixels(self.size_info.width as u32),
+            height: Pixels(self.size_info.height as u32),
+        };
+        let mut renderer = QuadRenderer::new(config, size)?;
+        let font_rendering = Self::font_rendering(config, &renderer);
+        let (glyph_cache, _, _) =
+            Self::new_glyph_cache(self.dpr, &mut renderer, config, font_rendering)?;
+        mem::replace(&mut self.renderer, renderer).abandon();
+        self.glyph_cache = glyph_cache;
+        self.context_lost = false;
+
+        // The new cache has the configured font size, not the current one
+        self.font_changed = true;
+        self.render_cache.invalidate();
+
+        Ok(())
+    }
+
+    /// Show the messages of the display in the message bar
+    pub fn set_messages(&mut self, messages: Messages) {
+        self.messages = messages;
     }
 
     #[inline]
@@ -263,13 +422,26 @@ impl Display {
         while let Ok(sz) = self.rx.try_recv() {
             match sz {
                 DisplayCommand::NewSize(w, h) => new_size = Some((w, h)),
-                DisplayCommand::NewHiDPIFactor(dpr) => new_dpr = Some(dpr)
+                DisplayCommand::NewHiDPIFactor(dpr) => new_dpr = Some(dpr),
+                // Shown in the message bar as well, since it was asked for
+                DisplayCommand::DumpFrameStats => {
+                    let stats = self.frame_stats();
+                    info!("{}", stats);
+                    self.messages.push(stats);
+                },
+                DisplayCommand::Screenshot => self.screenshot_pending = true,
+                DisplayCommand::ContextLost => self.context_lost = true,
             }
         }
 
-        // Font size modification detected
-        if terminal.font_size != self.font_size || new_dpr.is_some() {
+        if let Some(dpr) = new_dpr {
+            self.dpr = dpr;
+        }
+
+        // Font size or font config modification detected
+        if terminal.font_size != self.font_size || new_dpr.is_some() || self.font_changed {
             self.font_size = terminal.font_size;
+            self.font_changed = false;
             self.update_glyph_cache(config, new_dpr);
 
             if new_size == None {
@@ -284,6 +456,10 @@ impl Display {
             self.size_info.width = w as f32;
             self.size_info.height = h as f32;
 
+            // Windows too small for the minimum grid just cut off the last cells
+            let min_dimensions = config.window().min_dimensions();
+            self.size_info.clamp_to_grid(min_dimensions.columns_u32(), min_dimensions.lines_u32());
+
             let size = &self.size_info;
             terminal.resize(size);
 
@@ -292,28 +468,46 @@ impl Display {
             }
 
             self.renderer.resize(w as i32, h as i32);
+            self.render_cache.invalidate();
         }
     }
 
+    /// Draw every line again with the next frame, instead of only the changed ones
+    pub fn invalidate(&mut self) {
+        self.render_cache.invalidate();
+    }
+
     /// Draw the screen
     ///
     /// A reference to Term whose state is being drawn must be provided.
     ///
-    /// This call may block if vsync is enabled
-    pub fn draw(&mut self, terminal: &FairMutex<Term>, config: &Config, window_focused: bool) {
-        let mut terminal = terminal.lock();
+    /// This call may block if vsync is enabled. While the I/O thread keeps the
+    /// terminal locked, nothing is drawn and false is returned. The terminal
+    /// stays dirty, so the frame is drawn in one of the next iterations.
+    pub fn draw(&mut self, terminal: &FairMutex<Term>, config: &Config, window_focused: bool) -> bool {
+        let mut terminal = match self.draw_lock.lock(terminal) {
+            Some(terminal) => terminal,
+            None => return false,
+        };
+
+        // The lost context is replaced with the next iteration, after a
+        // failed attempt it's tried again with the next event
+        if self.context_lost {
+            terminal.dirty = self.needs_new_context();
+            return false;
+        }
+
         let size_info = *terminal.size_info();
         let visual_bell_intensity = terminal.visual_bell.intensity();
 
-        let grid_cells: Vec<RenderableCell> = terminal
-            .renderable_cells(config, window_focused)
-            .collect();
+        terminal.update_render_cache(config, window_focused, &mut self.render_cache);
 
         // Clear dirty flag
         terminal.dirty = !terminal.visual_bell.completed();
 
         {
             let glyph_cache = &mut self.glyph_cache;
+            let render_cache = &self.render_cache;
 
             // Draw grid
             {
@@ -323,13 +517,19 @@ impl Display {
                     api.clear(terminal.background_color());
 
                     // Draw the grid
-                    api.render_cells(grid_cells.iter(), glyph_cache);
+                    api.render_cells(render_cache.cells(), glyph_cache);
+
+                    // Draw the cursor over the grid
+                    if let Some(cursor) = render_cache.cursor() {
+                        api.render_cursor(&cursor);
+                    }
                 });
             }
 
             // Draw render timer
             if self.render_timer {
-                let timing = format!("{:.3} usec", self.meter.average());
+                let p99 = self.meter.stats().map(|stats| stats.p99).unwrap_or(0.);
+                let timing = format!("{:.3} usec, p99 {:.3} usec", self.meter.average(), p99);
                 let color = Rgb {
                     r: 0xd5,
                     g: 0x4e,
@@ -337,17 +537,137 @@ impl Display {
                 };
                 self.renderer
                     .with_api(config, &size_info, visual_bell_intensity, |mut api| {
-                        api.render_string(&timing[..], glyph_cache, color);
+                        api.render_string(
+                            &timing[..],
+                            Line(23),
+                            Column(0),
+                            glyph_cache,
+                            Rgb { r: 0, g: 0, b: 0 },
+                            color,
+                        );
+                    });
+            }
+
+            // Draw the scrollback position over the top right cells
+            let display_offset = terminal.grid().display_offset();
+            if display_offset != 0 && config.scrolling().indicator {
+                let position = format!("[{}/{}]", display_offset, terminal.grid().scroll_limit());
+                let cols = size_info.cols().0;
+                let len = position.chars().count();
+
+                // Keep the end of the position when it doesn't fit
+                let skip = len.saturating_sub(cols);
+                let position: String = position.chars().skip(skip).collect();
+                let col = Column(cols.saturating_sub(len));
+
+                let colors = config.colors();
+                let fg = colors.scroll_indicator.foreground.unwrap_or(colors.primary.background);
+                let bg = colors.scroll_indicator.background.unwrap_or(colors.primary.foreground);
+                self.renderer
+                    .with_api(config, &size_info, visual_bell_intensity, |mut api| {
+                        api.render_string(&position, Line(0), col, glyph_cache, fg, bg);
+                    });
+            }
+
+            // Draw the message over the last line, cut to its width
+            if let Some(ref message) = terminal.message {
+                let cols = size_info.cols().0;
+                let mut message: String = message.chars().take(cols).collect();
+                let len = message.chars().count();
+                message.extend(iter::repeat(' ').take(cols - len));
+
+                let colors = config.colors();
+                let fg = colors.primary.background;
+                let bg = colors.normal.red;
+                let line = size_info.lines() - 1;
+                self.renderer
+                    .with_api(config, &size_info, visual_bell_intensity, |mut api| {
+                        api.render_string(&message, line, Column(0), glyph_cache, fg, bg);
                     });
             }
         }
+
+        // Resets are only noticed after drawing, the frame is drawn again
+        // once the context was replaced
+        if !self.context_lost && self.renderer.is_context_lost() {
+            self.context_lost = true;
+            terminal.dirty = true;
+        }
+
+        if self.screenshot_pending {
+            self.screenshot_pending = false;
+            drop(terminal);
+            self.write_screenshot(config);
+        }
+
+        true
+    }
+
+    /// Write the frame which was just drawn to a PNG file
+    ///
+    /// The frame is read back before it's presented, in the framebuffer's
+    /// full resolution. Encoding a large frame takes a while, it's done by a
+    /// thread of its own so the next frames aren't delayed.
+    fn write_screenshot(&self, config: &Config) {
+        let width = self.size_info.width as usize;
+        let height = self.size_info.height as usize;
+        let mut pixels = self.renderer.read_pixels(width, height);
+        let opaque = config.background_opacity().get() >= 1.0;
+        let directory = config.screen_dump().directory();
+
+        thread::spawn_named("screenshot encoder", move || {
+            // The alpha channel is only shown with a transparent background
+            if opaque {
+                for pixel in pixels.chunks_mut(4) {
+                    pixel[3] = 0xff;
+                }
+            }
+
+            let written = create_timestamped_file(&directory, "png").and_then(|(file, path)| {
+                PNGEncoder::new(BufWriter::new(file))
+                    .encode(&pixels, width as u32, height as u32, ColorType::RGBA(8))?;
+                Ok(path)
+            });
+
+            // Logged as a warning, so it's shown in the message bar
+            match written {
+                Ok(path) => warn!("Wrote a screenshot to {}", path.display()),
+                Err(err) => {
+                    error!("Unable to write a screenshot to {}: {}", directory.display(), err)
+                },
+            }
+        });
+    }
+
+    /// Summary of the frame times
+    pub fn frame_stats(&self) -> String {
+        match self.meter.stats() {
+            Some(stats) => format!(
+                "Frame times of the last {} frames: p50 {:.3} usec, p95 {:.3} usec, \
+                 p99 {:.3} usec, max {:.3} usec; worst of {} frames {:.3} usec",
+                stats.samples,
+                stats.p50,
+                stats.p95,
+                stats.p99,
+                stats.max,
+                self.meter.count(),
+                self.meter.worst()
+            ),
+            None => String::from("No frames were drawn"),
+        }
     }
 
     /// Adjust the XIM editor position according to the new location of the cursor
+    ///
+    /// While composing, the position follows the end of the preedit string.
     pub fn current_xim_spot(&mut self, terminal: &Term) -> (i32, i32) {
-        use index::{Column, Line, Point};
+        use index::Point;
         use term::SizeInfo;
         let Point{line: Line(row), col: Column(col)} = terminal.cursor().point;
+        let col = match terminal.preedit() {
+            Some(preedit) => min(col + preedit.width(), terminal.grid().num_cols().0 - 1),
+            None => col,
+        };
         let SizeInfo{cell_width: cw,
                     cell_height: ch,
                     padding_x: px,
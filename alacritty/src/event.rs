@@ -1,9 +1,10 @@
 //! Process window events
 use std::borrow::Cow;
 use std::fs::File;
-use std::io::Write;
-use std::sync::mpsc;
-use std::time::{Instant};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
 
 use serde_json as json;
 use parking_lot::MutexGuard;
@@ -12,17 +13,22 @@ use copypasta::{Clipboard, Load, Store, Buffer as ClipboardBuffer};
 
 use ansi::{Handler, ClearMode};
 use grid::Scroll;
-use config::{self, Config};
+use config::{self, Config, ConfirmClose, OptionAsAlt};
 use cli::Options;
-use display::{OnResize, DisplayCommand};
-use index::{Line, Column, Side, Point};
-use input::{self, MouseBinding, KeyBinding};
+use display::DisplayCommand;
+use event_loop::{QueuedBytes, WindowNotifier};
+use index::{Side, Point};
+use logging::Messages;
+use input::{self, AltKeys, MouseBinding, KeyBinding, Mouse, TouchState, WindowChanges};
+use paste::{self, Paste};
 use selection::Selection;
-use sync::FairMutex;
-use term::{Term, SizeInfo, TermMode, Search};
-use util::limit;
+use sync::{FairMutex, SkippingLock};
+use term::{OnResize, Term, SizeInfo, TermMode, Search};
+use title::Title;
+use tty::Foreground;
+use util::{create_timestamped_file, limit, thread};
 use util::fmt::Red;
-use window::Window;
+use window::{self, Window};
 
 /// Byte sequences are sent to a `Notify` in response to some events
 pub trait Notify {
@@ -37,10 +43,15 @@ pub struct ActionContext<'a, N: 'a> {
     pub terminal: &'a mut Term,
     pub size_info: &'a SizeInfo,
     pub mouse: &'a mut Mouse,
+    pub touch: &'a mut TouchState,
     pub received_count: &'a mut usize,
     pub suppress_chars: &'a mut bool,
     pub last_modifiers: &'a mut ModifiersState,
+    pub alt_keys: &'a mut AltKeys,
     pub window_changes: &'a mut WindowChanges,
+    pub display_tx: &'a mpsc::Sender<DisplayCommand>,
+    pub screen_dump_config: &'a config::ScreenDumpConfig,
+    pub paste: &'a mut Option<Paste>,
 }
 
 impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
@@ -48,6 +59,33 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         self.notifier.notify(val);
     }
 
+    fn start_paste(&mut self, paste: Paste) {
+        if let Some(message) = paste.message() {
+            self.terminal.message = Some(message);
+        }
+
+        *self.paste = Some(paste);
+        self.terminal.dirty = true;
+    }
+
+    fn cancel_paste(&mut self) -> bool {
+        let mut paste = match self.paste.take() {
+            Some(paste) => paste,
+            None => return false,
+        };
+
+        if let Some(end) = paste.abort() {
+            self.notifier.notify(end);
+        }
+
+        if paste.message().is_some() {
+            self.terminal.message = None;
+        }
+
+        self.terminal.dirty = true;
+        true
+    }
+
     fn terminal_mode(&self) -> TermMode {
         *self.terminal.mode()
     }
@@ -87,6 +125,20 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         }
     }
 
+    fn copy_selection_as_html(&self) {
+        let html = self.terminal.selection_to_html();
+        let plain = self.terminal.selection_to_string();
+        if let (Some(html), Some(plain)) = (html, plain) {
+            if !plain.is_empty() {
+                Clipboard::new()
+                    .and_then(|mut clipboard| clipboard.store_primary_html(html, plain))
+                    .unwrap_or_else(|err| {
+                        warn!("Error storing selection to clipboard. {}", Red(err));
+                    });
+            }
+        }
+    }
+
     fn selection_is_empty(&self) -> bool {
         self.terminal.selection().as_ref().map(|s| s.is_empty()).unwrap_or(true)
     }
@@ -123,6 +175,10 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         self.terminal.url_search(point)
     }
 
+    fn highlight_url(&mut self, point: Option<Point<usize>>) {
+        self.terminal.highlight_url(point);
+    }
+
     fn line_selection(&mut self, point: Point) {
         let point = self.terminal.visible_to_buffer(point);
         *self.terminal.selection_mut() = Some(Selection::lines(point));
@@ -151,6 +207,16 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         self.mouse
     }
 
+    #[inline]
+    fn touch_mut(&mut self) -> &mut TouchState {
+        self.touch
+    }
+
+    #[inline]
+    fn touch(&self) -> &TouchState {
+        self.touch
+    }
+
     #[inline]
     fn received_count(&mut self) -> &mut usize {
         &mut self.received_count
@@ -166,74 +232,265 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         &mut self.last_modifiers
     }
 
+    #[inline]
+    fn alt_keys(&mut self) -> &mut AltKeys {
+        &mut self.alt_keys
+    }
+
     #[inline]
     fn hide_window(&mut self) {
         self.window_changes.hide = true;
     }
-}
 
-/// The ActionContext can't really have direct access to the Window
-/// with the current design. Event handlers that want to change the
-/// window must set these flags instead. The processor will trigger
-/// the actual changes.
-pub struct WindowChanges {
-    pub hide: bool,
+    #[inline]
+    fn toggle_fullscreen(&mut self) {
+        self.window_changes.toggle_fullscreen = true;
+    }
+
+    #[inline]
+    fn toggle_simple_fullscreen(&mut self) {
+        self.window_changes.toggle_simple_fullscreen = true;
+    }
+
+    #[inline]
+    fn toggle_secure_input(&mut self) {
+        self.window_changes.toggle_secure_input = true;
+    }
+
+    /// The meter is owned by the display, which handles the command before drawing
+    #[inline]
+    fn dump_frame_stats(&mut self) {
+        if self.display_tx.send(DisplayCommand::DumpFrameStats).is_ok() {
+            self.terminal.dirty = true;
+        }
+    }
+
+    /// The frame is read back by the display, right after it's drawn
+    fn screenshot(&mut self) {
+        if self.display_tx.send(DisplayCommand::Screenshot).is_ok() {
+            self.terminal.dirty = true;
+        }
+    }
+
+    /// The window and the renderer are created again before the next frame, like after a GPU reset
+    fn simulate_context_loss(&mut self) {
+        if self.display_tx.send(DisplayCommand::ContextLost).is_ok() {
+            self.terminal.dirty = true;
+        }
+    }
+
+    /// The path is logged as a warning, so it's shown in the message bar
+    fn dump_screen(&mut self) {
+        let directory = self.screen_dump_config.directory();
+        let contents = self.terminal.contents_to_string(self.screen_dump_config.scrollback());
+        match write_screen_dump(&directory, "txt", &contents) {
+            Ok(path) => warn!("Wrote the screen to {}", path.display()),
+            Err(err) => error!("Unable to write the screen to {}: {}", directory.display(), err),
+        }
+    }
+
+    /// The cells are left out, they aren't needed to reproduce most problems
+    ///
+    /// The path is logged as a warning, so it's shown in the message bar.
+    fn dump_state(&mut self) {
+        let directory = self.screen_dump_config.directory();
+        let contents = match json::to_string_pretty(&self.terminal.state_dump(false)) {
+            Ok(contents) => contents,
+            Err(err) => return error!("Unable to serialize the terminal state: {}", err),
+        };
+        match write_screen_dump(&directory, "json", &contents) {
+            Ok(path) => warn!("Wrote the terminal state to {}", path.display()),
+            Err(err) => {
+                error!("Unable to write the terminal state to {}: {}", directory.display(), err)
+            },
+        }
+    }
 }
 
-impl WindowChanges {
-    fn clear(&mut self) {
-        self.hide = false;
+impl<'a, N: Notify + 'a> ActionContext<'a, N> {
+    /// Write the next chunk of a long paste once the io thread caught up
+    ///
+    /// Returns the time after which the main loop has to check again, it's
+    /// woken up by a timer until the paste is done. That keeps the paste going
+    /// while the window is hidden and nothing is drawn.
+    fn continue_paste(&mut self, queued_bytes: usize) -> Option<Duration> {
+        let mut paste = self.paste.take()?;
+
+        if queued_bytes < paste::CHUNK_SIZE {
+            if let Some(chunk) = paste.next_chunk() {
+                self.notifier.notify(chunk);
+            }
+        }
+
+        let message = paste.message();
+        if message.is_some() {
+            self.terminal.dirty = true;
+        }
+
+        if paste.is_finished() {
+            if message.is_some() {
+                self.terminal.message = None;
+            }
+            None
+        } else {
+            if message.is_some() {
+                self.terminal.message = message;
+            }
+            *self.paste = Some(paste);
+            Some(Duration::from_millis(PASTE_RETRY_MS))
+        }
     }
 }
 
-impl Default for WindowChanges {
-    fn default() -> WindowChanges {
-        WindowChanges {
-            hide: false,
+/// Write a screen dump to a new file named after the current time
+fn write_screen_dump(directory: &Path, extension: &str, contents: &str) -> io::Result<PathBuf> {
+    let (mut file, path) = create_timestamped_file(directory, extension)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(path)
+}
+
+/// Time after which the main loop checks if the next chunk of a paste can be written
+const PASTE_RETRY_MS: u64 = 2;
+
+/// Time in which a close request has to be repeated to close the window
+const CONFIRM_CLOSE_TIMEOUT_MS: u64 = 3000;
+
+const CONFIRM_CLOSE_MESSAGE: &str = "Close the window again to quit";
+
+/// Close requests which wait for a confirmation
+struct CloseConfirmation {
+    mode: ConfirmClose,
+    foreground: Foreground,
+
+    /// Time of the close request which needs to be confirmed
+    requested_at: Option<Instant>,
+}
+
+impl CloseConfirmation {
+    /// Check whether a close request may close the window
+    ///
+    /// Otherwise a message asks to repeat the request. The foreground process
+    /// is only looked up here, since that's a system call.
+    fn confirm(&mut self, terminal: &mut Term) -> bool {
+        let needed = match self.mode {
+            ConfirmClose::Never => false,
+            ConfirmClose::Always => true,
+            ConfirmClose::ForegroundProcess => self.foreground.has_job(),
+        };
+
+        let timeout = Duration::from_millis(CONFIRM_CLOSE_TIMEOUT_MS);
+        if !needed || self.requested_at.map_or(false, |at| at.elapsed() < timeout) {
+            return true;
+        }
+
+        self.requested_at = Some(Instant::now());
+        terminal.message = Some(CONFIRM_CLOSE_MESSAGE.to_owned());
+        terminal.dirty = true;
+        false
+    }
+
+    /// Time until the message is cleared, once the request was not repeated
+    fn tick(&mut self, terminal: &mut Term) -> Option<Duration> {
+        let elapsed = self.requested_at?.elapsed();
+        let timeout = Duration::from_millis(CONFIRM_CLOSE_TIMEOUT_MS);
+        if elapsed < timeout {
+            return Some(timeout - elapsed);
         }
+
+        self.requested_at = None;
+        terminal.message = None;
+        terminal.dirty = true;
+        None
     }
 }
 
-pub enum ClickState {
-    None,
-    Click,
-    DoubleClick,
-    TripleClick,
+/// Time a logged warning or error stays in the message bar
+const LOG_MESSAGE_TIMEOUT_MS: u64 = 10_000;
+
+/// Warnings and errors of the log, which are shown in the message bar
+struct LogMessages {
+    messages: Messages,
+
+    /// Message which is shown and the time it was logged at
+    shown: Option<(String, Instant)>,
+}
+
+impl LogMessages {
+    /// Show the latest message, returns the time until it's cleared again
+    fn tick(&mut self, terminal: &mut Term) -> Option<Duration> {
+        if let Some(message) = self.messages.take() {
+            terminal.message = Some(message.clone());
+            terminal.dirty = true;
+            self.shown = Some((message, Instant::now()));
+        }
+
+        let elapsed = self.shown.as_ref()?.1.elapsed();
+        let timeout = Duration::from_millis(LOG_MESSAGE_TIMEOUT_MS);
+        if elapsed < timeout {
+            return Some(timeout - elapsed);
+        }
+
+        // A message which replaced it in the meantime is left alone
+        if let Some((message, _)) = self.shown.take() {
+            if terminal.message.as_ref() == Some(&message) {
+                terminal.message = None;
+                terminal.dirty = true;
+            }
+        }
+        None
+    }
 }
 
-/// State of the mouse
-pub struct Mouse {
-    pub x: usize,
-    pub y: usize,
-    pub left_button_state: ElementState,
-    pub middle_button_state: ElementState,
-    pub right_button_state: ElementState,
-    pub last_click_timestamp: Instant,
-    pub click_state: ClickState,
-    pub scroll_px: i32,
-    pub line: Line,
-    pub column: Column,
-    pub cell_side: Side,
-    pub lines_scrolled: f32,
-    pub block_url_launcher: bool,
+/// Thread which wakes up the main loop at a deadline
+///
+/// One thread serves all timeouts of the processor, it waits for the earliest
+/// deadline it was sent.
+struct WakeupTimer {
+    tx: mpsc::Sender<Instant>,
+
+    /// Latest deadline which was sent to the thread
+    scheduled: Option<Instant>,
 }
 
-impl Default for Mouse {
-    fn default() -> Mouse {
-        Mouse {
-            x: 0,
-            y: 0,
-            last_click_timestamp: Instant::now(),
-            left_button_state: ElementState::Released,
-            middle_button_state: ElementState::Released,
-            right_button_state: ElementState::Released,
-            click_state: ClickState::None,
-            scroll_px: 0,
-            line: Line(0),
-            column: Column(0),
-            cell_side: Side::Left,
-            lines_scrolled: 0.0,
-            block_url_launcher: false,
+impl WakeupTimer {
+    fn new(notifier: window::Notifier) -> WakeupTimer {
+        let (tx, rx) = mpsc::channel::<Instant>();
+
+        thread::spawn_named("wakeup timer", move || {
+            let mut deadline: Option<Instant> = None;
+            loop {
+                let received = match deadline {
+                    Some(at) => {
+                        let now = Instant::now();
+                        if at <= now {
+                            notifier.notify();
+                            deadline = None;
+                            continue;
+                        }
+                        rx.recv_timeout(at - now)
+                    },
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+
+                match received {
+                    Ok(at) => deadline = Some(deadline.map_or(at, |current| current.min(at))),
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        WakeupTimer { tx, scheduled: None }
+    }
+
+    /// Wake up the main loop after `delay`, unless it's woken up before anyway
+    fn schedule(&mut self, delay: Duration) {
+        let now = Instant::now();
+        let deadline = now + delay;
+        let pending = self.scheduled.map_or(false, |at| at > now && at <= deadline);
+        if !pending {
+            let _ = self.tx.send(deadline);
+            self.scheduled = Some(deadline);
         }
     }
 }
@@ -247,10 +504,12 @@ pub struct Processor<N> {
     mouse_bindings: Vec<MouseBinding>,
     mouse_config: config::Mouse,
     scrolling_config: config::Scrolling,
+    touch_config: config::Touch,
     print_events: bool,
     wait_for_event: bool,
     notifier: N,
     mouse: Mouse,
+    touch: TouchState,
     display_tx: mpsc::Sender<DisplayCommand>,
     ref_test: bool,
     size_info: SizeInfo,
@@ -259,9 +518,27 @@ pub struct Processor<N> {
     received_count: usize,
     suppress_chars: bool,
     last_modifiers: ModifiersState,
+    alt_keys: AltKeys,
     pending_events: Vec<Event>,
+    dropped_files: Vec<PathBuf>,
     window_changes: WindowChanges,
     save_to_clipboard: bool,
+    alt_send_esc: bool,
+    option_as_alt: OptionAsAlt,
+    screen_dump_config: config::ScreenDumpConfig,
+    /// Wakes up the event loop to continue scrolling, started with the first timeout
+    wakeup_timer: Option<WakeupTimer>,
+    close_confirmation: CloseConfirmation,
+    log_messages: LogMessages,
+    /// The window title can include the program in the foreground of the pty
+    title: Title,
+    /// Modifiers held after the last keyboard or mouse event
+    modifiers: ModifiersState,
+    /// Paste which is still being written
+    paste: Option<Paste>,
+    queued_bytes: QueuedBytes,
+    /// Wakeups are skipped while the terminal is locked by the I/O thread
+    terminal_lock: SkippingLock,
 }
 
 /// Notify that the terminal was resized
@@ -280,32 +557,54 @@ impl<N: Notify> Processor<N> {
     /// pty.
     pub fn new(
         notifier: N,
+        queued_bytes: QueuedBytes,
         display_tx: mpsc::Sender<DisplayCommand>,
         options: &Options,
         config: &Config,
         ref_test: bool,
         size_info: SizeInfo,
+        foreground: Foreground,
+        messages: Messages,
     ) -> Processor<N> {
         Processor {
             key_bindings: config.key_bindings().to_vec(),
             mouse_bindings: config.mouse_bindings().to_vec(),
             mouse_config: config.mouse().to_owned(),
             scrolling_config: config.scrolling(),
+            touch_config: config.touch(),
             print_events: options.print_events,
             wait_for_event: true,
             notifier,
             display_tx,
             ref_test,
             mouse: Default::default(),
+            touch: Default::default(),
             size_info,
             hide_mouse_when_typing: config.hide_mouse_when_typing(),
             hide_mouse: false,
             received_count: 0,
             suppress_chars: false,
             last_modifiers: Default::default(),
+            alt_keys: Default::default(),
             pending_events: Vec::with_capacity(4),
+            dropped_files: Vec::new(),
             window_changes: Default::default(),
             save_to_clipboard: config.selection().save_to_clipboard,
+            alt_send_esc: config.alt_send_esc(),
+            option_as_alt: config.window().option_as_alt(),
+            screen_dump_config: config.screen_dump().clone(),
+            wakeup_timer: None,
+            title: Title::new(config, foreground.clone()),
+            close_confirmation: CloseConfirmation {
+                mode: config.window().confirm_close(),
+                foreground,
+                requested_at: None,
+            },
+            log_messages: LogMessages { messages, shown: None },
+            modifiers: Default::default(),
+            paste: None,
+            queued_bytes,
+            terminal_lock: SkippingLock::default(),
         }
     }
 
@@ -319,6 +618,9 @@ impl<N: Notify> Processor<N> {
         display_tx: &mpsc::Sender<DisplayCommand>,
         hide_mouse: &mut bool,
         window_is_focused: &mut bool,
+        dropped_files: &mut Vec<PathBuf>,
+        close_confirmation: &mut CloseConfirmation,
+        modifiers: &mut ModifiersState,
     ) {
         match event {
             // Pass on device events
@@ -327,6 +629,10 @@ impl<N: Notify> Processor<N> {
                 use glutin::WindowEvent::*;
                 match event {
                     CloseRequested => {
+                        if !close_confirmation.confirm(processor.ctx.terminal) {
+                            return;
+                        }
+
                         if ref_test {
                             // dump grid state
                             let mut grid = processor.ctx.terminal.grid().clone();
@@ -347,8 +653,9 @@ impl<N: Notify> Processor<N> {
                                 .expect("write size.json");
                         }
 
-                        // FIXME should do a more graceful shutdown
-                        ::std::process::exit(0);
+                        // Shut down through the main loop, so the log file
+                        // can be cleaned up
+                        processor.ctx.terminal.exit();
                     },
                     Resized(w, h) => {
                         display_tx.send(DisplayCommand::NewSize(w, h)).expect("send new size");
@@ -359,6 +666,14 @@ impl<N: Notify> Processor<N> {
                         processor.ctx.terminal.dirty = true;
                     }
                     KeyboardInput { input, .. } => {
+                        // Holding the URL modifiers underlines the URL below
+                        // the mouse, without moving it
+                        let key_modifiers = input::modifiers_after(input);
+                        if key_modifiers != *modifiers {
+                            *modifiers = key_modifiers;
+                            processor.update_url_highlight(key_modifiers);
+                        }
+
                         processor.process_key(input);
                         if input.state == ElementState::Pressed {
                             // Hide cursor while typing
@@ -368,44 +683,60 @@ impl<N: Notify> Processor<N> {
                     ReceivedCharacter(c) => {
                         processor.received_char(c);
                     },
-                    MouseInput { state, button, modifiers, .. } => {
+                    MouseInput { state, button, modifiers: mouse_modifiers, .. } => {
+                        *modifiers = mouse_modifiers;
                         if !cfg!(target_os = "macos") || *window_is_focused {
                             *hide_mouse = false;
-                            processor.mouse_input(state, button, modifiers);
+                            processor.mouse_input(state, button, mouse_modifiers);
                             processor.ctx.terminal.dirty = true;
                         }
                     },
-                    CursorMoved { position: (x, y), modifiers, .. } => {
+                    CursorMoved { position: (x, y), modifiers: mouse_modifiers, .. } => {
+                        *modifiers = mouse_modifiers;
+
+                        // The pointer can be outside of the window while dragging
+                        processor.update_selection_scroll(y as i32);
+
                         let x = limit(x as i32, 0, processor.ctx.size_info.width as i32);
                         let y = limit(y as i32, 0, processor.ctx.size_info.height as i32);
 
                         *hide_mouse = false;
-                        processor.mouse_moved(x as usize, y as usize, modifiers);
+                        processor.mouse_moved(x as usize, y as usize, mouse_modifiers);
                     },
                     MouseWheel { delta, phase, modifiers, .. } => {
                         *hide_mouse = false;
                         processor.on_mouse_wheel(delta, phase, modifiers);
                     },
+                    Touch(touch) => {
+                        let (x, y) = touch.location;
+                        processor.on_touch(touch.id, touch.phase, x, y);
+                    },
                     Refresh => {
                         processor.ctx.terminal.dirty = true;
+                        processor.ctx.window_changes.refresh_visibility = true;
                     },
                     Focused(is_focused) => {
                         *window_is_focused = is_focused;
+                        processor.ctx.window_changes.refresh_visibility = true;
 
                         if is_focused {
                             processor.ctx.terminal.dirty = true;
                             processor.ctx.terminal.next_is_urgent = Some(false);
                         } else {
                             processor.ctx.terminal.dirty = true;
+                            processor.ctx.terminal.highlight_url(None);
                             *hide_mouse = false;
                         }
 
                         processor.on_focus_change(is_focused);
                     },
+                    CursorLeft { .. } => {
+                        processor.ctx.terminal.highlight_url(None);
+                    },
                     DroppedFile(path) => {
-                        use input::ActionContext;
-                        let path: String = path.to_string_lossy().into();
-                        processor.ctx.write_to_pty(path.into_bytes());
+                        // Every file is a separate event, they're pasted together
+                        // once all events have been processed
+                        dropped_files.push(path);
                     }
                     _ => (),
                 }
@@ -418,11 +749,14 @@ impl<N: Notify> Processor<N> {
 
     /// Process events. When `wait_for_event` is set, this method is guaranteed
     /// to process at least one event.
+    ///
+    /// Returns `None` when there were only wakeups while the I/O thread kept
+    /// the terminal locked, they're handled by one of the next calls.
     pub fn process_events<'a>(
         &mut self,
         term: &'a FairMutex<Term>,
         window: &mut Window
-    ) -> MutexGuard<'a, Term> {
+    ) -> Option<MutexGuard<'a, Term>> {
         // Terminal is lazily initialized the first time an event is returned
         // from the blocking WaitEventsIterator. Otherwise, the pty reader would
         // be blocked the entire time we wait for input!
@@ -451,26 +785,51 @@ impl<N: Notify> Processor<N> {
                 });
             }
 
-            terminal = term.lock();
+            // Input is applied right away. Wakeups by the I/O thread only ask
+            // for a frame, which doesn't wait while it's parsing output.
+            {
+                let pending_events = &mut self.pending_events;
+                window.poll_events(|event| pending_events.push(event));
+            }
+            let has_input = self.pending_events.iter().any(|event| match *event {
+                Event::Awakened => false,
+                _ => true,
+            });
+            let locked = if has_input { Some(term.lock()) } else { self.terminal_lock.lock(term) };
+            terminal = match locked {
+                Some(terminal) => terminal,
+                None => {
+                    self.wait_for_event = false;
+                    return None;
+                },
+            };
 
             context = ActionContext {
                 terminal: &mut terminal,
                 notifier: &mut self.notifier,
                 mouse: &mut self.mouse,
+                touch: &mut self.touch,
                 size_info: &self.size_info,
                 received_count: &mut self.received_count,
                 suppress_chars: &mut self.suppress_chars,
                 last_modifiers: &mut self.last_modifiers,
+                alt_keys: &mut self.alt_keys,
                 window_changes: &mut self.window_changes,
+                display_tx,
+                screen_dump_config: &self.screen_dump_config,
+                paste: &mut self.paste,
             };
 
             processor = input::Processor {
                 ctx: context,
                 scrolling_config: &self.scrolling_config,
+                touch_config: &self.touch_config,
                 mouse_config: &self.mouse_config,
                 key_bindings: &self.key_bindings[..],
                 mouse_bindings: &self.mouse_bindings[..],
                 save_to_clipboard: self.save_to_clipboard,
+                alt_send_esc: self.alt_send_esc,
+                option_as_alt: self.option_as_alt,
             };
 
             let mut window_is_focused = window.is_focused;
@@ -479,6 +838,9 @@ impl<N: Notify> Processor<N> {
             // ends.
             {
                 let hide_mouse = &mut self.hide_mouse;
+                let dropped_files = &mut self.dropped_files;
+                let close_confirmation = &mut self.close_confirmation;
+                let modifiers = &mut self.modifiers;
                 let mut process = |event| {
                     if print_events {
                         println!("glutin event: {:?}", event);
@@ -490,6 +852,9 @@ impl<N: Notify> Processor<N> {
                         display_tx,
                         hide_mouse,
                         &mut window_is_focused,
+                        dropped_files,
+                        close_confirmation,
+                        modifiers,
                     );
                 };
 
@@ -500,6 +865,34 @@ impl<N: Notify> Processor<N> {
                 window.poll_events(process);
             }
 
+            processor.paste_dropped_files(&self.dropped_files);
+            self.dropped_files.clear();
+
+            let paste = processor.ctx.continue_paste(self.queued_bytes.get());
+
+            if let Some(title) = self.title.next_title(processor.ctx.terminal) {
+                window.set_title(&title);
+            }
+
+            // Keep scrolling while a selection is dragged past the grid or
+            // after a swipe, detect long presses of a resting finger, clear
+            // the confirmation to close and logged messages and refresh the
+            // title
+            let close = self.close_confirmation.tick(processor.ctx.terminal);
+            let log_message = self.log_messages.tick(processor.ctx.terminal);
+            let title = self.title.next_refresh();
+            let scroll = processor.selection_scroll_tick();
+            let touch = processor.touch_tick();
+            let delay = [scroll, touch, close, log_message, title, paste]
+                .iter()
+                .filter_map(|&delay| delay)
+                .min();
+            if let Some(delay) = delay {
+                self.wakeup_timer
+                    .get_or_insert_with(|| WakeupTimer::new(window.notifier()))
+                    .schedule(delay);
+            }
+
             if self.hide_mouse_when_typing {
                 window.set_mouse_visible(!self.hide_mouse);
             }
@@ -515,16 +908,94 @@ impl<N: Notify> Processor<N> {
             window.hide();
         }
 
+        if self.window_changes.toggle_fullscreen {
+            window.toggle_fullscreen();
+        }
+
+        if self.window_changes.toggle_simple_fullscreen {
+            window.toggle_simple_fullscreen();
+        }
+
+        if self.window_changes.toggle_secure_input {
+            window.toggle_secure_input();
+        }
+
+        if self.window_changes.refresh_visibility {
+            window.refresh_visibility();
+        }
+
         self.window_changes.clear();
-        self.wait_for_event = !terminal.dirty;
 
-        terminal
+        // Frames aren't drawn while the window can't be seen, so there's
+        // nothing to keep the loop going for
+        self.wait_for_event = !terminal.dirty || terminal.occluded;
+
+        Some(terminal)
     }
 
     pub fn update_config(&mut self, config: &Config) {
-        self.key_bindings = config.key_bindings().to_vec();
-        self.mouse_bindings = config.mouse_bindings().to_vec();
         self.mouse_config = config.mouse().to_owned();
+        self.scrolling_config = config.scrolling();
+        self.touch_config = config.touch();
         self.save_to_clipboard = config.selection().save_to_clipboard;
+        self.alt_send_esc = config.alt_send_esc();
+        self.option_as_alt = config.window().option_as_alt();
+        self.screen_dump_config = config.screen_dump().clone();
+        self.close_confirmation.mode = config.window().confirm_close();
+        self.title.update_config(config);
+    }
+
+    /// Swap the key and mouse bindings for the ones of a reloaded config
+    pub fn update_bindings(&mut self, config: &Config) {
+        self.key_bindings = config.key_bindings().to_vec();
+        self.mouse_bindings = config.mouse_bindings().to_vec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use config::{Config, ConfirmClose};
+    use term::{SizeInfo, Term};
+    use tty::Foreground;
+
+    use super::{CloseConfirmation, CONFIRM_CLOSE_TIMEOUT_MS};
+
+    #[test]
+    fn close_requests_are_confirmed() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Config::default(), size);
+        let mut confirmation = CloseConfirmation {
+            mode: ConfirmClose::Always,
+            foreground: Foreground::default(),
+            requested_at: None,
+        };
+
+        assert!(!confirmation.confirm(&mut term));
+        assert!(term.message.is_some());
+        assert!(confirmation.confirm(&mut term));
+
+        // The message is cleared once the request wasn't repeated in time
+        let timeout = Duration::from_millis(CONFIRM_CLOSE_TIMEOUT_MS);
+        confirmation.requested_at = Some(Instant::now() - timeout);
+        assert_eq!(confirmation.tick(&mut term), None);
+        assert!(term.message.is_none());
+        assert!(!confirmation.confirm(&mut term));
+
+        // Without a job, only the shell would be hung up
+        confirmation.mode = ConfirmClose::ForegroundProcess;
+        confirmation.requested_at = None;
+        assert!(confirmation.confirm(&mut term));
+
+        confirmation.mode = ConfirmClose::Never;
+        assert!(confirmation.confirm(&mut term));
     }
 }
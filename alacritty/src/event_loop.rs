@@ -1,21 +1,27 @@
 //! The main event loop which performs I/O on the pseudoterminal
 use std::borrow::Cow;
+use std::cmp::min;
 use std::collections::VecDeque;
 use std::io::{self, ErrorKind, Read, Write};
 use std::fs::File;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::marker::Send;
+use std::time::Duration;
 
 use mio::{self, Events, PollOpt, Ready};
 use mio_more::channel::{self, Receiver, Sender};
+use parking_lot::MutexGuard;
 
 #[cfg(not(windows))]
 use mio::unix::UnixReady;
 
 use ansi;
+use config::Config;
+#[cfg(feature = "gui")]
 use event;
-use tty;
-use term::Term;
+use tty::{self, ChildEvent};
+use term::{OnResize, SizeInfo, Term};
 use util::thread;
 use sync::FairMutex;
 
@@ -32,6 +38,9 @@ pub enum Msg {
 
     /// Indicates that the `EventLoop` should shut down, as Alacritty is shutting down
     Shutdown,
+
+    /// Instruction to resize the pty
+    Resize(SizeInfo),
 }
 
 /// The main event!.. loop.
@@ -46,6 +55,10 @@ pub struct EventLoop<T: tty::EventedReadWrite> {
     terminal: Arc<FairMutex<Term>>,
     window: Box<WindowNotifier + Send>,
     ref_test: bool,
+    parse_limit: usize,
+    read_buffer_size: usize,
+    max_string_len: usize,
+    queued: QueuedBytes,
 }
 
 /// Helper type which tracks how much of a buffer has been written.
@@ -82,10 +95,46 @@ pub struct State {
     write_list: VecDeque<Cow<'static, [u8]>>,
     writing: Option<Writing>,
     parser: ansi::Processor,
+
+    /// Bytes parsed since the renderer last presented a frame
+    unpresented: usize,
+
+    /// Bytes waiting to be written to the pty
+    queued: usize,
 }
 
-pub struct Notifier(pub Sender<Msg>);
+/// Sends input and resizes to the `EventLoop`
+///
+/// The input is counted in the `QueuedBytes` of the event loop before it's
+/// sent.
+pub struct Notifier(pub Sender<Msg>, pub QueuedBytes);
+
+/// Number of bytes of input and replies which weren't written to the pty yet
+///
+/// Input is counted by the sender, before it's received by the `EventLoop`.
+/// The main thread only writes the next chunk of a long paste once almost
+/// nothing is left, so the write queue never fills up with it.
+#[derive(Debug, Default, Clone)]
+pub struct QueuedBytes(Arc<AtomicUsize>);
 
+impl QueuedBytes {
+    #[inline]
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn add(&self, bytes: usize) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn sub(&self, bytes: usize) {
+        self.0.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "gui")]
 impl event::Notify for Notifier {
     fn notify<B>(&mut self, bytes: B)
     where B: Into<Cow<'static, [u8]>>,
@@ -95,18 +144,29 @@ impl event::Notify for Notifier {
         if bytes.len() == 0 {
             return
         }
+        self.1.add(bytes.len());
         if self.0.send(Msg::Input(bytes)).is_err() {
             panic!("expected send event loop msg");
         }
     }
 }
 
+impl OnResize for Notifier {
+    fn on_resize(&mut self, size: &SizeInfo) {
+        if self.0.send(Msg::Resize(*size)).is_err() {
+            warn!("Unable to send resize to the event loop");
+        }
+    }
+}
+
 impl Default for State {
     fn default() -> State {
         State {
             write_list: VecDeque::new(),
             parser: ansi::Processor::new(),
             writing: None,
+            unpresented: 0,
+            queued: 0,
         }
     }
 }
@@ -140,6 +200,74 @@ impl State {
     fn set_current(&mut self, new: Option<Writing>) {
         self.writing = new;
     }
+
+    /// Add bytes to the write queue
+    ///
+    /// Input is dropped once the queue is full, since the child isn't reading
+    /// it anyways. Returns false for dropped input.
+    fn queue(&mut self, input: Cow<'static, [u8]>) -> bool {
+        if self.queued + input.len() > MAX_QUEUED {
+            warn!("Write queue is full, dropping {} bytes of input", input.len());
+            return false;
+        }
+
+        self.queued += input.len();
+        self.write_list.push_back(input);
+        true
+    }
+
+    /// Add replies of the terminal to the write queue
+    ///
+    /// Replies are never dropped, the application might wait for them. They
+    /// count towards the limit of the queue, so input is dropped instead.
+    fn queue_replies(&mut self, replies: Vec<u8>) {
+        self.queued += replies.len();
+        self.write_list.push_back(Cow::Owned(replies));
+    }
+
+    /// Write queued bytes until the writer would block
+    ///
+    /// At most `MAX_WRITE` bytes are written per call, so reading from the pty
+    /// isn't held up by huge writes to a slow reader.
+    fn write_to<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.ensure_next();
+
+        let mut written = 0;
+        'write_many: while let Some(mut current) = self.take_current() {
+            'write_one: loop {
+                if written >= MAX_WRITE {
+                    self.set_current(Some(current));
+                    break 'write_many;
+                }
+
+                let len = min(current.remaining_bytes().len(), MAX_WRITE - written);
+                match writer.write(&current.remaining_bytes()[..len]) {
+                    Ok(0) => {
+                        self.set_current(Some(current));
+                        break 'write_many;
+                    }
+                    Ok(n) => {
+                        current.advance(n);
+                        written += n;
+                        self.queued -= n;
+                        if current.finished() {
+                            self.goto_next();
+                            break 'write_one;
+                        }
+                    }
+                    Err(err) => {
+                        self.set_current(Some(current));
+                        match err.kind() {
+                            ErrorKind::Interrupted | ErrorKind::WouldBlock => break 'write_many,
+                            _ => return Err(err),
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Writing {
@@ -167,15 +295,28 @@ impl Writing {
 /// `mio::Token` for the event loop channel
 const CHANNEL: mio::Token = mio::Token(0);
 
+/// Maximum number of bytes queued for writing to the pty
+const MAX_QUEUED: usize = 0x100_0000;
+
+/// Maximum number of bytes written to the pty in one event loop iteration
+const MAX_WRITE: usize = 0x1_0000;
+
+/// Number of iterations worth of output parsed before the renderer must catch up
+const MAX_UNPRESENTED_ITERATIONS: usize = 4;
+
+/// How often to check for a presented frame while reading is paused
+const THROTTLE_INTERVAL_MS: u64 = 2;
+
 impl<T> EventLoop<T>
     where
-        T: tty::EventedReadWrite + Send + 'static,
+        T: tty::EventedReadWrite + OnResize + Send + 'static,
 {
     /// Create a new event loop
     pub fn new(
         terminal: Arc<FairMutex<Term>>,
         window: Box<WindowNotifier + Send>,
         pty: T,
+        config: &Config,
         ref_test: bool,
     ) -> EventLoop<T> {
         let (tx, rx) = channel::channel();
@@ -187,6 +328,10 @@ impl<T> EventLoop<T>
             terminal,
             window,
             ref_test,
+            parse_limit: config.debug().parse_limit,
+            read_buffer_size: config.debug().read_buffer_size,
+            max_string_len: config.debug().max_string_len,
+            queued: QueuedBytes::default(),
         }
     }
 
@@ -194,21 +339,31 @@ impl<T> EventLoop<T>
         self.tx.clone()
     }
 
+    pub fn queued_bytes(&self) -> QueuedBytes {
+        self.queued.clone()
+    }
+
     // Drain the channel
     //
     // Returns a `DrainResult` indicating the result of receiving from the channel
     //
-    fn drain_recv_channel(&self, state: &mut State) -> DrainResult {
+    fn drain_recv_channel(&mut self, state: &mut State) -> DrainResult {
         let mut received_item = false;
         while let Ok(msg) = self.rx.try_recv() {
             received_item = true;
             match msg {
                 Msg::Input(input) => {
-                    state.write_list.push_back(input);
+                    let len = input.len();
+                    if !state.queue(input) {
+                        self.queued.sub(len);
+                    }
                 }
                 Msg::Shutdown => {
                     return DrainResult::Shutdown;
                 }
+                Msg::Resize(size) => {
+                    self.pty.on_resize(&size);
+                }
             }
         }
 
@@ -245,17 +400,30 @@ impl<T> EventLoop<T>
     {
         const MAX_READ: usize = 0x1_0000;
         let mut processed = 0;
+        let mut locked = 0;
         let mut terminal = None;
 
+        // Replies are queued like input, a long one like an OSC 52 answer
+        // wouldn't fit into the pty at once
+        let mut replies = Vec::new();
+
         // Flag to keep track if wakeup has already been sent
         let mut send_wakeup = false;
 
+        // Errors are returned only after the parsed output was handed to the terminal
+        let mut result = Ok(());
+
         loop {
             match self.pty.reader().read(&mut buf[..]) {
-                Ok(0) => break,
+                // The slave side of the pty has been closed
+                Ok(0) => {
+                    result = Err(ErrorKind::UnexpectedEof.into());
+                    break;
+                },
                 Ok(got) => {
                     // Record bytes read; used to limit time spent in pty_read.
                     processed += got;
+                    locked += got;
 
                     // Send a copy of bytes read to a subscriber. Used for
                     // example with ref test recording.
@@ -267,11 +435,17 @@ impl<T> EventLoop<T>
                     // Get reference to terminal. Lock is acquired on initial
                     // iteration and held until there's no bytes left to parse
                     // or we've reached MAX_READ.
-                    let terminal = if terminal.is_none() {
+                    let term = if terminal.is_none() {
                         terminal = Some(self.terminal.lock());
-                        let terminal = terminal.as_mut().unwrap();
-                        send_wakeup = !terminal.dirty;
-                        terminal
+                        let term = terminal.as_mut().unwrap();
+                        send_wakeup = !term.dirty || term.occluded;
+
+                        // No draw is pending, so the last frame has been presented
+                        if send_wakeup {
+                            state.unpresented = 0;
+                        }
+
+                        term
                     } else {
                         terminal.as_mut().unwrap()
                     };
@@ -280,74 +454,113 @@ impl<T> EventLoop<T>
                     for byte in &buf[..got] {
                         state
                             .parser
-                            .advance(&mut **terminal, *byte, &mut self.pty.writer());
+                            .advance(&mut **term, *byte, &mut replies);
                     }
+                    state.unpresented += got;
 
-                    // Exit if we've processed enough bytes
-                    if processed > MAX_READ {
+                    // Exit if we've processed enough bytes for this iteration
+                    if processed >= self.parse_limit {
                         break;
                     }
+
+                    // Release the lock between chunks, so the renderer and
+                    // input handling aren't starved.
+                    if locked >= MAX_READ {
+                        if let Some(term) = terminal.take() {
+                            self.release_terminal(term, send_wakeup);
+                        }
+                        locked = 0;
+                    }
                 }
                 Err(err) => match err.kind() {
-                    ErrorKind::Interrupted | ErrorKind::WouldBlock => {
+                    ErrorKind::Interrupted => continue,
+                    ErrorKind::WouldBlock => break,
+                    _ => {
+                        result = Err(err);
                         break;
                     }
-                    _ => return Err(err),
                 },
             }
         }
 
-        // Only request a draw if one hasn't already been requested.
-        if let Some(mut terminal) = terminal {
-            if send_wakeup {
-                self.window.notify();
-                terminal.dirty = true;
-            }
+        if let Some(term) = terminal {
+            self.release_terminal(term, send_wakeup);
         }
 
-        Ok(())
+        if !replies.is_empty() {
+            self.queued.add(replies.len());
+            state.queue_replies(replies);
+        }
+
+        result
+    }
+
+    /// Unlock the terminal after parsing
+    ///
+    /// Only requests a draw if one hasn't already been requested.
+    #[inline]
+    fn release_terminal(&self, mut terminal: MutexGuard<Term>, send_wakeup: bool) {
+        if send_wakeup {
+            self.window.notify();
+            terminal.dirty = true;
+        }
+    }
+
+    /// Check if reading from the pty should be paused
+    ///
+    /// Once more output has been parsed than the renderer was able to present,
+    /// the pty isn't read from until the next frame has been drawn. The kernel's
+    /// pty buffer will then throttle the child process.
+    fn should_throttle(&self, state: &mut State) -> bool {
+        if state.unpresented < self.parse_limit * MAX_UNPRESENTED_ITERATIONS {
+            return false;
+        }
+
+        let terminal = self.terminal.lock();
+        if terminal.dirty && !terminal.occluded {
+            true
+        } else {
+            state.unpresented = 0;
+            false
+        }
     }
 
     #[inline]
     fn pty_write(&mut self, state: &mut State) -> io::Result<()> {
-        state.ensure_next();
+        let queued = state.queued;
+        let result = state.write_to(self.pty.writer());
+        self.queued.sub(queued - state.queued);
+        result
+    }
 
-        'write_many: while let Some(mut current) = state.take_current() {
-            'write_one: loop {
-                match self.pty.writer().write(current.remaining_bytes()) {
-                    Ok(0) => {
-                        state.set_current(Some(current));
-                        break 'write_many;
-                    }
-                    Ok(n) => {
-                        current.advance(n);
-                        if current.finished() {
-                            state.goto_next();
-                            break 'write_one;
-                        }
-                    }
-                    Err(err) => {
-                        state.set_current(Some(current));
-                        match err.kind() {
-                            ErrorKind::Interrupted | ErrorKind::WouldBlock => break 'write_many,
-                            _ => return Err(err),
-                        }
-                    }
-                }
-            }
+    /// Close the terminal after a failed pty read or write
+    fn pty_error(&mut self, err: &io::Error) {
+        if is_child_gone(err) {
+            info!("Child process closed the pty: {}", err);
+        } else {
+            error!("Event loop exitting due to error: {}", err);
         }
 
-        Ok(())
+        self.exit();
+    }
+
+    /// Tell the main thread that the terminal should be closed
+    fn exit(&mut self) {
+        self.terminal.lock().exit();
+        self.window.notify();
     }
 
     pub fn spawn(mut self, state: Option<State>) -> thread::JoinHandle<(Self, State)> {
         thread::spawn_named("pty reader", move || {
             let mut state = state.unwrap_or_else(Default::default);
-            let mut buf = [0u8; 0x1000];
+            state.parser.set_max_string_len(self.max_string_len);
+
+            // The read buffer is allocated once and reused for the lifetime of the loop
+            let mut buf = vec![0u8; self.read_buffer_size];
 
             let poll_opts = PollOpt::edge() | PollOpt::oneshot();
 
-            let tokens = [1, 2];
+            let tokens = [1, 2, 3];
 
             self.poll
                 .register(&self.rx, CHANNEL, Ready::readable(), poll_opts)
@@ -365,51 +578,66 @@ impl<T> EventLoop<T>
                 None
             };
 
+            let mut throttled = false;
+
             'event_loop: loop {
-                if let Err(err) = self.poll.poll(&mut events, None) {
+                // Wake up regularly while throttled, to resume once a frame was drawn
+                let timeout = if throttled {
+                    Some(Duration::from_millis(THROTTLE_INTERVAL_MS))
+                } else {
+                    None
+                };
+
+                if let Err(err) = self.poll.poll(&mut events, timeout) {
                     match err.kind() {
                         ErrorKind::Interrupted => continue,
                         _ => panic!("EventLoop polling error: {:?}", err),
                     }
                 }
 
+                // Handle the channel before any pty events, so a pending resize
+                // is applied before more of the child's output is parsed.
+                if events.iter().any(|event| event.token() == CHANNEL)
+                    && !self.channel_event(&mut state)
+                {
+                    // Shutdown was requested by the main thread
+                    break 'event_loop;
+                }
+
+                // The exit of the child is handled after all other events, so
+                // the pty is still serviced when both are ready at once
+                let mut exit_code = None;
+
                 for event in events.iter() {
                     match event.token() {
-                        CHANNEL => if !self.channel_event(&mut state) {
-                            break 'event_loop;
+                        CHANNEL => (),
+                        token if Some(token) == self.pty.child_event_token() => {
+                            if let Some(ChildEvent::Exited(code)) = self.pty.next_child_event() {
+                                exit_code = Some(code);
+                            }
                         },
                         token if token == self.pty.read_token() || token == self.pty.write_token() => {
+                            // Read whatever output is left before closing the terminal
                             #[cfg(unix)]
-                                {
-                                    if UnixReady::from(event.readiness()).is_hup() {
-                                        break 'event_loop;
-                                    }
-                                }
-                            if event.readiness().is_readable() {
-                                if let Err(err) = self.pty_read(&mut state, &mut buf, pipe.as_mut())
-                                    {
-                                        error!(
-                                            "Event loop exitting due to error: {} [{}:{}]",
-                                            err,
-                                            file!(),
-                                            line!()
-                                        );
-                                        break 'event_loop;
-                                    }
-
-                                if ::tty::process_should_exit() {
+                            let hup = UnixReady::from(event.readiness()).is_hup();
+                            #[cfg(not(unix))]
+                            let hup = false;
+
+                            if event.readiness().is_readable() || hup {
+                                if let Err(err) = self.pty_read(&mut state, &mut buf, pipe.as_mut()) {
+                                    self.pty_error(&err);
                                     break 'event_loop;
                                 }
                             }
 
+                            if hup {
+                                self.exit();
+                                break 'event_loop;
+                            }
+
                             if event.readiness().is_writable() {
                                 if let Err(err) = self.pty_write(&mut state) {
-                                    error!(
-                                        "Event loop exitting due to error: {} [{}:{}]",
-                                        err,
-                                        file!(),
-                                        line!()
-                                    );
+                                    self.pty_error(&err);
                                     break 'event_loop;
                                 }
                             }
@@ -418,8 +646,32 @@ impl<T> EventLoop<T>
                     }
                 }
 
+                // Without a token for the child, its state has to be checked manually
+                if self.pty.child_event_token().is_none() {
+                    if let Some(ChildEvent::Exited(code)) = self.pty.next_child_event() {
+                        exit_code = Some(code);
+                    }
+                }
+
+                if let Some(code) = exit_code {
+                    info!("Child process exited with status {}", code);
+
+                    // Parse the output written before the child exited
+                    let _ = self.pty_read(&mut state, &mut buf, pipe.as_mut());
+
+                    self.exit();
+                    break 'event_loop;
+                }
+
+                // Register read interest unless the renderer can't keep up
+                throttled = self.should_throttle(&mut state);
+                let mut interest = if throttled {
+                    Ready::empty()
+                } else {
+                    Ready::readable()
+                };
+
                 // Register write interest if necessary
-                let mut interest = Ready::readable();
                 if state.needs_write() {
                     interest.insert(Ready::writable());
                 }
@@ -436,3 +688,159 @@ impl<T> EventLoop<T>
         })
     }
 }
+
+/// Check if a pty error means that the child process is gone
+///
+/// Once the slave side of the pty is closed, reads return EOF on some platforms
+/// and fail with EIO on others, like Linux and macOS.
+fn is_child_gone(err: &io::Error) -> bool {
+    match err.kind() {
+        ErrorKind::UnexpectedEof | ErrorKind::BrokenPipe => true,
+        #[cfg(unix)]
+        _ if err.raw_os_error() == Some(::libc::EIO) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{State, MAX_QUEUED, MAX_WRITE};
+
+    #[test]
+    fn write_queue_is_bounded() {
+        let mut state = State::default();
+
+        state.queue(Cow::Owned(vec![b'a'; MAX_QUEUED - 1]));
+        assert_eq!(state.queued, MAX_QUEUED - 1);
+
+        // Input which doesn't fit is dropped entirely
+        assert!(!state.queue(Cow::Borrowed(b"ab")));
+        assert_eq!(state.queued, MAX_QUEUED - 1);
+        assert_eq!(state.write_list.len(), 1);
+
+        assert!(state.queue(Cow::Borrowed(b"a")));
+        assert_eq!(state.queued, MAX_QUEUED);
+        assert_eq!(state.write_list.len(), 2);
+
+        // Replies are queued even when the queue is full
+        state.queue_replies(b"\x1b[0n".to_vec());
+        assert_eq!(state.queued, MAX_QUEUED + 4);
+        assert_eq!(state.write_list.len(), 3);
+    }
+
+    #[test]
+    fn writes_are_chunked() {
+        let mut state = State::default();
+        state.queue(Cow::Owned(vec![b'a'; 3 * MAX_WRITE]));
+
+        let mut sink = Vec::new();
+        state.write_to(&mut sink).unwrap();
+        assert_eq!(sink.len(), MAX_WRITE);
+        assert!(state.needs_write());
+
+        state.write_to(&mut sink).unwrap();
+        state.write_to(&mut sink).unwrap();
+        assert_eq!(sink.len(), 3 * MAX_WRITE);
+        assert_eq!(state.queued, 0);
+        assert!(!state.needs_write());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn slow_reader_does_not_lose_bytes() {
+        use std::io::{ErrorKind, Read};
+        use std::os::unix::net::UnixStream;
+
+        // The peer doesn't read anything until the socket buffer is full
+        let (mut pty, mut child) = UnixStream::pair().unwrap();
+        pty.set_nonblocking(true).unwrap();
+        child.set_nonblocking(true).unwrap();
+
+        let input: Vec<u8> = (0..64 * MAX_WRITE).map(|i| i as u8).collect();
+        let mut state = State::default();
+        state.queue(Cow::Owned(input.clone()));
+
+        // Write until the socket buffer is full
+        let mut queued = state.queued;
+        loop {
+            state.write_to(&mut pty).unwrap();
+            if state.queued == queued {
+                break;
+            }
+            queued = state.queued;
+        }
+        assert!(state.needs_write());
+
+        // Once the peer starts reading, everything is delivered in order
+        let mut received = Vec::new();
+        let mut buf = [0u8; 0x1000];
+        while received.len() < input.len() {
+            loop {
+                match child.read(&mut buf) {
+                    Ok(n) => received.extend_from_slice(&buf[..n]),
+                    Err(ref err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) => panic!("read from socket failed: {}", err),
+                }
+            }
+
+            state.write_to(&mut pty).unwrap();
+        }
+
+        assert_eq!(state.queued, 0);
+        assert_eq!(received, input);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn child_killed_mid_output() {
+        use std::sync::Arc;
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        use cli::Options;
+        use config::{Config, Shell};
+        use sync::FairMutex;
+        use term::{SizeInfo, Term};
+        use tty;
+
+        use super::{EventLoop, WindowNotifier};
+
+        struct Mock;
+
+        impl WindowNotifier for Mock {
+            fn notify(&self) {}
+        }
+
+        let config = Config::default();
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+
+        // The child is killed by a background job while it's flooding the pty
+        let mut options = Options::default();
+        options.command = Some(Shell::new_with_args(
+            "sh",
+            vec!["-c".into(), "(sleep 0.1; kill -9 $$) & exec yes".into()],
+        ));
+
+        let terminal = Arc::new(FairMutex::new(Term::new(&config, size)));
+        let pty = tty::new(&config, &options, &&size, None);
+        let event_loop = EventLoop::new(Arc::clone(&terminal), Box::new(Mock), pty, &config, false);
+        let io_thread = event_loop.spawn(None);
+
+        let start = Instant::now();
+        while !terminal.lock().should_exit() {
+            assert!(start.elapsed() < Duration::from_secs(10), "event loop did not exit");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        io_thread.join().unwrap();
+    }
+}
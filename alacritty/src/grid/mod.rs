@@ -97,6 +97,16 @@ pub struct Grid<T> {
 
     #[serde(default)]
     max_scroll_limit: usize,
+
+    /// Lines of the active area which were modified since the damage was reset
+    ///
+    /// Lines without entry are considered damaged.
+    #[serde(skip)]
+    damage: Vec<bool>,
+
+    /// Allocation of a line which entered the history, for a recycled line
+    #[serde(skip)]
+    spare: Vec<T>,
 }
 
 pub struct GridIterator<'a, T: 'a> {
@@ -107,13 +117,24 @@ pub struct GridIterator<'a, T: 'a> {
     pub cur: Point<usize>,
 }
 
-#[derive(Copy, Clone)]
+/// Position of the viewport to scroll to
+///
+/// Every target is clamped to the history, the viewport never moves above its
+/// oldest line or below the live screen.
+#[derive(Copy, Clone, Debug)]
 pub enum Scroll {
+    /// Move up by a number of lines, down if negative
     Lines(isize),
     PageUp,
     PageDown,
+    HalfPageUp,
+    HalfPageDown,
     Top,
     Bottom,
+    /// Set the display offset to a number of lines above the live screen
+    Offset(usize),
+    /// Show a buffer line in the middle of the viewport, for jumping to matches
+    Center(usize),
 }
 
 #[derive(Copy, Clone)]
@@ -123,7 +144,7 @@ pub enum ViewportPosition {
     Below,
 }
 
-impl<T: Copy + Clone> Grid<T> {
+impl<T: Copy + Clone + Default + PartialEq> Grid<T> {
     pub fn new(lines: index::Line, cols: index::Column, scrollback: usize, template: T) -> Grid<T> {
         let raw = Storage::with_capacity(lines, Row::new(cols, &template));
         Grid {
@@ -134,6 +155,8 @@ impl<T: Copy + Clone> Grid<T> {
             scroll_limit: 0,
             selection: None,
             max_scroll_limit: scrollback,
+            damage: Vec::new(),
+            spare: Vec::new(),
         }
     }
 
@@ -160,35 +183,34 @@ impl<T: Copy + Clone> Grid<T> {
     }
 
     /// Update the size of the scrollback history
-    pub fn update_history(&mut self, history_size: usize, template: &T)
-    {
-        self.raw.update_history(history_size, Row::new(self.cols, &template));
+    ///
+    /// Shrinking the history drops the oldest lines and keeps the viewport
+    /// within the remaining history.
+    pub fn update_history(&mut self, history_size: usize) {
+        self.raw.shrink_history(history_size);
+        self.max_scroll_limit = history_size;
         self.scroll_limit = min(self.scroll_limit, history_size);
+        self.display_offset = min(self.display_offset, self.scroll_limit);
+        self.truncate_selection();
     }
 
     pub fn scroll_display(&mut self, scroll: Scroll) {
-        match scroll {
-            Scroll::Lines(count) => {
-                self.display_offset = min(
-                    max((self.display_offset as isize) + count, 0isize) as usize,
-                    self.scroll_limit
-                );
-            },
-            Scroll::PageUp => {
-                self.display_offset = min(
-                    self.display_offset + self.lines.0,
-                    self.scroll_limit
-                );
-            },
-            Scroll::PageDown => {
-                self.display_offset -= min(
-                    self.display_offset,
-                    self.lines.0
-                );
-            },
-            Scroll::Top => self.display_offset = self.scroll_limit,
-            Scroll::Bottom => self.display_offset = 0,
-        }
+        let lines = self.lines.0 as isize;
+        let offset = self.display_offset as isize;
+        let target = match scroll {
+            Scroll::Lines(count) => offset + count,
+            Scroll::PageUp => offset + lines,
+            Scroll::PageDown => offset - lines,
+            Scroll::HalfPageUp => offset + lines / 2,
+            Scroll::HalfPageDown => offset - lines / 2,
+            Scroll::Top => self.scroll_limit as isize,
+            Scroll::Bottom => 0,
+            Scroll::Offset(offset) => offset as isize,
+            // The buffer line at the bottom of the viewport is the offset
+            Scroll::Center(line) => line as isize - (lines - 1) / 2,
+        };
+
+        self.display_offset = min(max(target, 0) as usize, self.scroll_limit);
     }
 
     pub fn resize(
@@ -202,6 +224,8 @@ impl<T: Copy + Clone> Grid<T> {
             return;
         }
 
+        self.damage_all();
+
         match self.lines.cmp(&lines) {
             Ordering::Less => self.grow_lines(lines, template),
             Ordering::Greater => self.shrink_lines(lines),
@@ -226,7 +250,10 @@ impl<T: Copy + Clone> Grid<T> {
                 max(self.scroll_limit - history_size, MIN_INIT_SIZE),
                 self.max_scroll_limit - history_size,
             );
-            self.raw.initialize(new, Row::new(self.cols, template));
+            // The lines are reset before they're shown, so they start trimmed
+            let mut row = Row::new(self.cols, template);
+            row.trim();
+            self.raw.initialize(new, row);
         }
     }
 
@@ -257,6 +284,7 @@ impl<T: Copy + Clone> Grid<T> {
         }
 
         self.scroll_limit = self.scroll_limit.saturating_sub(*lines_added);
+        self.display_offset = min(self.display_offset, self.scroll_limit);
     }
 
     fn grow_cols(&mut self, cols: index::Column, template: &T) {
@@ -310,6 +338,8 @@ impl<T: Copy + Clone> Grid<T> {
         positions: index::Line,
         template: &T,
     ) {
+        self.damage_lines(region.clone());
+
         // Whether or not there is a scrolling region active, as long as it
         // starts at the top, we can do a full rotation which just involves
         // changing the start index.
@@ -325,6 +355,12 @@ impl<T: Copy + Clone> Grid<T> {
 
             self.decrease_scroll_limit(*positions);
 
+            // The newest lines of the history are moved back into the region
+            self.display_offset = min(
+                self.display_offset.saturating_sub(*positions),
+                self.scroll_limit,
+            );
+
             // Now, restore any scroll region lines
             let lines = self.lines;
             for i in IndexRange(region.end .. lines) {
@@ -356,23 +392,24 @@ impl<T: Copy + Clone> Grid<T> {
         positions: index::Line,
         template: &T
     ) {
+        self.damage_lines(region.clone());
+
         if region.start == Line(0) {
-            // Update display offset when not pinned to active area
+            self.increase_scroll_limit(*positions, template);
+
+            // Lines viewed in the history stay in place while new lines enter
+            // it, until they are dropped from a full history
             if self.display_offset != 0 {
-                self.display_offset = min(
-                    self.display_offset + *positions,
-                    self.len() - self.num_lines().0,
-                );
+                self.display_offset = min(self.display_offset + *positions, self.scroll_limit);
             }
 
-            self.increase_scroll_limit(*positions, template);
-
             // Rotate the entire line buffer. If there's a scrolling region
             // active, the bottom lines are restored in the next step.
             self.raw.rotate(-(*positions as isize));
             if let Some(ref mut selection) = self.selection {
                 selection.rotate(*positions as isize);
             }
+            self.truncate_selection();
 
             // // This next loop swaps "fixed" lines outside of a scroll region
             // // back into place after the rotation. The work is done in buffer-
@@ -387,8 +424,14 @@ impl<T: Copy + Clone> Grid<T> {
             // Finally, reset recycled lines
             //
             // Recycled lines are just above the end of the scrolling region.
+            // Lines which entered the history aren't modified anymore, they're
+            // trimmed and a recycled line takes over their allocation.
+            let entered = min(*positions, self.scroll_limit);
             for i in 0..*positions {
-                self.raw[i + fixed_lines].reset(&template);
+                if i < entered {
+                    self.raw[*self.lines + i].trim_into(&mut self.spare);
+                }
+                self.raw[i + fixed_lines].reset_with(&template, &mut self.spare);
             }
         } else {
             // Subregion rotation
@@ -422,6 +465,22 @@ impl<T> Grid<T> {
 
     pub fn clear_history(&mut self) {
         self.scroll_limit = 0;
+        self.truncate_selection();
+
+        // The viewport can't stay in the history which was dropped
+        if self.display_offset != 0 {
+            self.display_offset = 0;
+            self.damage_all();
+        }
+    }
+
+    /// Remove the lines which are no longer in the buffer from the selection
+    fn truncate_selection(&mut self) {
+        let max_line = (self.scroll_limit + *self.lines) as isize - 1;
+        let selected = self.selection.as_mut().map(|selection| selection.truncate(max_line));
+        if selected == Some(false) {
+            self.selection = None;
+        }
     }
 
     #[inline]
@@ -429,6 +488,36 @@ impl<T> Grid<T> {
         self.scroll_limit
     }
 
+    #[inline]
+    pub fn display_offset(&self) -> usize {
+        self.display_offset
+    }
+
+    /// Whether a line of the active area was modified since the damage was reset
+    #[inline]
+    pub fn is_damaged(&self, line: index::Line) -> bool {
+        self.damage.get(line.0).cloned().unwrap_or(true)
+    }
+
+    /// Mark all lines of the active area as undamaged
+    pub fn reset_damage(&mut self) {
+        self.damage.clear();
+        self.damage.resize(self.lines.0, false);
+    }
+
+    /// Mark all lines of the active area as damaged
+    pub fn damage_all(&mut self) {
+        self.damage.clear();
+    }
+
+    #[inline]
+    fn damage_lines(&mut self, lines: Range<index::Line>) {
+        let end = min(lines.end.0, self.damage.len());
+        for damaged in self.damage.iter_mut().take(end).skip(lines.start.0) {
+            *damaged = true;
+        }
+    }
+
     /// Total number of lines in the buffer, this includes scrollback + visible lines
     #[inline]
     pub fn len(&self) -> usize {
@@ -516,6 +605,7 @@ impl<T> Index<usize> for Grid<T> {
 impl<T> IndexMut<index::Line> for Grid<T> {
     #[inline]
     fn index_mut(&mut self, index: index::Line) -> &mut Row<T> {
+        self.damage_lines(index..index + 1);
         &mut self.raw[index]
     }
 }
@@ -529,7 +619,7 @@ impl<'point, T> Index<&'point Point> for Grid<T> {
     }
 }
 
-impl<'point, T> IndexMut<&'point Point> for Grid<T> {
+impl<'point, T: Copy> IndexMut<&'point Point> for Grid<T> {
     #[inline]
     fn index_mut<'a, 'b>(&'a mut self, point: &'b Point) -> &'a mut T {
         &mut self[point.line][point.col]
@@ -558,7 +648,7 @@ pub struct RegionMut<'a, T: 'a> {
     raw: &'a mut Storage<T>,
 }
 
-impl<'a, T> RegionMut<'a, T> {
+impl<'a, T: Copy> RegionMut<'a, T> {
     /// Call the provided function for every item in this region
     pub fn each<F: Fn(&mut T)>(self, func: F) {
         for row in self {
@@ -592,6 +682,7 @@ impl<T> IndexRegion<Range<Line>, T> for Grid<T> {
         assert!(index.start < self.num_lines());
         assert!(index.end <= self.num_lines());
         assert!(index.start <= index.end);
+        self.damage_lines(index.clone());
         RegionMut {
             start: index.start,
             end: index.end,
@@ -611,6 +702,7 @@ impl<T> IndexRegion<RangeTo<Line>, T> for Grid<T> {
     }
     fn region_mut(&mut self, index: RangeTo<Line>) -> RegionMut<T> {
         assert!(index.end <= self.num_lines());
+        self.damage_lines(Line(0)..index.end);
         RegionMut {
             start: Line(0),
             end: index.end,
@@ -630,6 +722,8 @@ impl<T> IndexRegion<RangeFrom<Line>, T> for Grid<T> {
     }
     fn region_mut(&mut self, index: RangeFrom<Line>) -> RegionMut<T> {
         assert!(index.start < self.num_lines());
+        let lines = self.num_lines();
+        self.damage_lines(index.start..lines);
         RegionMut {
             start: index.start,
             end: self.num_lines(),
@@ -648,6 +742,7 @@ impl<T> IndexRegion<RangeFull, T> for Grid<T> {
     }
 
     fn region_mut(&mut self, _: RangeFull) -> RegionMut<T> {
+        self.damage_all();
         RegionMut {
             start: Line(0),
             end: self.num_lines(),
@@ -756,6 +851,24 @@ impl<'a, T: 'a> DisplayIter<'a, T> {
     pub fn line(&self) -> Line {
         self.line
     }
+
+    /// The current line only contains default cells
+    #[inline]
+    pub fn is_blank_line(&self) -> bool {
+        self.grid.raw[self.offset].is_blank()
+    }
+
+    /// Move to the start of the next line, skipping the rest of the current one
+    pub fn skip_line(&mut self) {
+        if self.offset == self.limit {
+            self.col = self.grid.num_cols();
+        } else {
+            self.offset -= 1;
+
+            self.col = Column(0);
+            self.line = Line(*self.grid.lines - 1 - (self.offset - self.limit));
+        }
+    }
 }
 
 impl<'a, T: Copy + 'a> Iterator for DisplayIter<'a, T> {
@@ -17,11 +17,17 @@
 use std::ops::{Index, IndexMut};
 use std::ops::{Range, RangeTo, RangeFrom, RangeFull, RangeToInclusive};
 use std::cmp::{max, min};
-use std::slice;
+use std::{mem, slice};
 
 use index::Column;
 
 /// A row in the grid
+///
+/// Rows in the history are mostly empty, so the default cells at their end
+/// are trimmed and not kept in memory. Indexing and iterating a row still
+/// covers all of its columns, the trimmed cells are stored again once the row
+/// is modified. Ranges of columns can only be borrowed while they're stored,
+/// trimmed rows are read by column or through `iter`.
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Row<T> {
     inner: Vec<T>,
@@ -33,80 +39,232 @@ pub struct Row<T> {
     ///
     /// - Zero means there are no occupied entries
     /// - 1 means there is a value at index zero, but nowhere else
-    /// - `occ == inner.len` means every value is occupied
+    /// - `occ == len` means every value is occupied
+    ///
+    /// Trimming the row doesn't change it, trimmed cells which were written
+    /// stay occupied.
     pub(crate) occ: usize,
+
+    /// Number of default cells after `inner` which aren't stored
+    #[serde(default)]
+    trimmed: usize,
+
+    /// All cells after `occ` are default cells, only the occupied ones have to
+    /// be looked at when trimming
+    #[serde(skip)]
+    blank_tail: bool,
+
+    /// The default cell, which is returned for trimmed columns
+    #[serde(skip)]
+    blank: T,
 }
 
 impl<T: PartialEq> PartialEq for Row<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.inner == other.inner
+        self.len() == other.len() && self.iter().eq(other.iter())
     }
 }
 
-impl<T: Copy + Clone> Row<T> {
+impl<T: Copy + Clone + Default + PartialEq> Row<T> {
     pub fn new(columns: Column, template: &T) -> Row<T> {
         Row {
             inner: vec![*template; *columns],
             occ: 0,
+            trimmed: 0,
+            blank_tail: *template == T::default(),
+            blank: T::default(),
         }
     }
 
     pub fn grow(&mut self, cols: Column, template: &T) {
         assert!(self.len() < * cols);
 
+        // A trimmed row stays trimmed when it's widened with default cells
+        if self.trimmed > 0 && *template == self.blank {
+            self.trimmed = *cols - self.inner.len();
+            return;
+        }
+
+        self.blank_tail &= *template == self.blank;
+        self.materialize();
         while self.len() != *cols {
             self.inner.push(*template);
         }
     }
 
+    /// Reset the row like `reset`, trimmed cells are stored again in the
+    /// allocation of `spare` when it's large enough
+    pub fn reset_with(&mut self, other: &T, spare: &mut Vec<T>) {
+        if self.trimmed > 0 && spare.capacity() >= self.len() {
+            let len = self.len();
+            spare.clear();
+            spare.extend_from_slice(&self.inner);
+            spare.resize(len, self.blank);
+            mem::swap(&mut self.inner, spare);
+            self.trimmed = 0;
+        }
+
+        self.reset(other);
+    }
+
     /// Resets contents to the contents of `other`
     #[inline(never)]
     pub fn reset(&mut self, other: &T) {
+        self.materialize();
+
         let occ = self.occ;
         for item in &mut self.inner[..occ] {
             *item = *other;
         }
 
         self.occ = 0;
+        self.blank_tail &= *other == self.blank;
+    }
+
+    /// Free the memory of the default cells at the end of the row
+    pub fn trim(&mut self) {
+        self.trim_into(&mut Vec::new());
+    }
+
+    /// Trim the row, the allocation of all of its cells is moved to `spare`
+    ///
+    /// The spare allocation is used by `reset_with` for another row, rows
+    /// entering the history hand theirs to the rows which are recycled.
+    pub fn trim_into(&mut self, spare: &mut Vec<T>) {
+        let end = if self.blank_tail { min(self.occ, self.inner.len()) } else { self.inner.len() };
+        let stored = self.inner[..end].iter()
+            .rposition(|cell| *cell != self.blank)
+            .map_or(0, |index| index + 1);
+
+        if stored < self.inner.len() {
+            self.trimmed += self.inner.len() - stored;
+            let cells = self.inner[..stored].to_vec();
+            *spare = mem::replace(&mut self.inner, cells);
+        }
+
+        // Only trimmed cells are left after the occupied ones
+        self.blank_tail |= stored <= self.occ;
     }
 }
 
 #[cfg_attr(feature = "cargo-clippy", allow(len_without_is_empty))]
 impl<T> Row<T> {
     pub fn shrink(&mut self, cols: Column) {
-        while self.len() != *cols {
-            self.inner.pop();
-        }
+        let excess = self.len() - *cols;
+        self.trimmed -= min(self.trimmed, excess);
+        self.inner.truncate(*cols);
 
         self.occ = min(self.occ, *cols);
     }
 
+    /// Number of columns, including the trimmed ones
     pub fn len(&self) -> usize {
-        self.inner.len()
+        self.inner.len() + self.trimmed
+    }
+
+    /// All cells are default cells
+    ///
+    /// Rows which were never written since they were created or reset, or
+    /// which have been trimmed completely, are blank. Written rows aren't,
+    /// even when their cells are default cells again.
+    #[inline]
+    pub fn is_blank(&self) -> bool {
+        self.inner.is_empty() || (self.occ == 0 && self.blank_tail)
     }
 
-    pub fn iter(&self) -> slice::Iter<T> {
-        self.inner.iter()
+    pub fn iter(&self) -> Iter<T> {
+        Iter {
+            row: self,
+            start: 0,
+            end: self.len(),
+        }
+    }
+}
+
+impl<T: Copy> Row<T> {
+    /// Store the trimmed cells again, before the row is modified
+    #[inline]
+    fn materialize(&mut self) {
+        if self.trimmed > 0 {
+            let len = self.len();
+            let blank = self.blank;
+            self.inner.resize(len, blank);
+            self.trimmed = 0;
+        }
+    }
+
+    /// All cells of the row as one slice, to move cells within the row
+    ///
+    /// Trimmed cells are stored again first, so every column is part of the
+    /// slice. All of them count as occupied afterwards.
+    #[inline]
+    pub fn cells_mut(&mut self) -> &mut [T] {
+        self.materialize();
+        self.occ = self.len();
+        &mut self.inner[..]
     }
 }
 
+/// Iterator over all columns of a row, including the trimmed ones
+pub struct Iter<'a, T: 'a> {
+    row: &'a Row<T>,
+    start: usize,
+    end: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a T> {
+        if self.start == self.end {
+            return None;
+        }
+
+        let row = self.row;
+        self.start += 1;
+        Some(&row[Column(self.start - 1)])
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.start == self.end {
+            return None;
+        }
+
+        let row = self.row;
+        self.end -= 1;
+        Some(&row[Column(self.end)])
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
 
 impl<'a, T> IntoIterator for &'a Row<T> {
     type Item = &'a T;
-    type IntoIter = slice::Iter<'a, T>;
+    type IntoIter = Iter<'a, T>;
 
     #[inline]
-    fn into_iter(self) -> slice::Iter<'a, T> {
+    fn into_iter(self) -> Iter<'a, T> {
         self.iter()
     }
 }
 
-impl<'a, T> IntoIterator for &'a mut Row<T> {
+impl<'a, T: Copy> IntoIterator for &'a mut Row<T> {
     type Item = &'a mut T;
     type IntoIter = slice::IterMut<'a, T>;
 
     #[inline]
     fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.materialize();
         self.occ = self.len();
         self.inner.iter_mut()
     }
@@ -117,13 +275,20 @@ impl<T> Index<Column> for Row<T> {
 
     #[inline]
     fn index(&self, index: Column) -> &T {
-        &self.inner[index.0]
+        match self.inner.get(index.0) {
+            Some(cell) => cell,
+            None => {
+                assert!(index.0 < self.len(), "column {} is out of bounds", index.0);
+                &self.blank
+            },
+        }
     }
 }
 
-impl<T> IndexMut<Column> for Row<T> {
+impl<T: Copy> IndexMut<Column> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: Column) -> &mut T {
+        self.materialize();
         self.occ = max(self.occ, *index + 1);
         &mut self.inner[index.0]
     }
@@ -131,20 +296,33 @@ impl<T> IndexMut<Column> for Row<T> {
 
 // -----------------------------------------------------------------------------
 // Index ranges of columns
+//
+// Reading a range requires its cells to be stored, so the slice always covers
+// the whole range. Mutable ranges store the trimmed cells again.
 // -----------------------------------------------------------------------------
 
+impl<T> Row<T> {
+    #[inline]
+    fn stored(&self, start: usize, end: usize) -> &[T] {
+        assert!(start <= end && end <= self.len(), "columns {}..{} are out of bounds", start, end);
+        assert!(end <= self.inner.len(), "columns {}..{} are trimmed", start, end);
+        &self.inner[start..end]
+    }
+}
+
 impl<T> Index<Range<Column>> for Row<T> {
     type Output = [T];
 
     #[inline]
     fn index(&self, index: Range<Column>) -> &[T] {
-        &self.inner[(index.start.0)..(index.end.0)]
+        self.stored(index.start.0, index.end.0)
     }
 }
 
-impl<T> IndexMut<Range<Column>> for Row<T> {
+impl<T: Copy> IndexMut<Range<Column>> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: Range<Column>) -> &mut [T] {
+        self.materialize();
         self.occ = max(self.occ, *index.end);
         &mut self.inner[(index.start.0)..(index.end.0)]
     }
@@ -155,13 +333,14 @@ impl<T> Index<RangeTo<Column>> for Row<T> {
 
     #[inline]
     fn index(&self, index: RangeTo<Column>) -> &[T] {
-        &self.inner[..(index.end.0)]
+        self.stored(0, index.end.0)
     }
 }
 
-impl<T> IndexMut<RangeTo<Column>> for Row<T> {
+impl<T: Copy> IndexMut<RangeTo<Column>> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: RangeTo<Column>) -> &mut [T] {
+        self.materialize();
         self.occ = max(self.occ, *index.end);
         &mut self.inner[..(index.end.0)]
     }
@@ -172,13 +351,14 @@ impl<T> Index<RangeFrom<Column>> for Row<T> {
 
     #[inline]
     fn index(&self, index: RangeFrom<Column>) -> &[T] {
-        &self.inner[(index.start.0)..]
+        self.stored(index.start.0, self.len())
     }
 }
 
-impl<T> IndexMut<RangeFrom<Column>> for Row<T> {
+impl<T: Copy> IndexMut<RangeFrom<Column>> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: RangeFrom<Column>) -> &mut [T] {
+        self.materialize();
         self.occ = self.len();
         &mut self.inner[(index.start.0)..]
     }
@@ -189,15 +369,14 @@ impl<T> Index<RangeFull> for Row<T> {
 
     #[inline]
     fn index(&self, _: RangeFull) -> &[T] {
-        &self.inner[..]
+        self.stored(0, self.len())
     }
 }
 
-impl<T> IndexMut<RangeFull> for Row<T> {
+impl<T: Copy> IndexMut<RangeFull> for Row<T> {
     #[inline]
     fn index_mut(&mut self, _: RangeFull) -> &mut [T] {
-        self.occ = self.len();
-        &mut self.inner[..]
+        self.cells_mut()
     }
 }
 
@@ -206,14 +385,156 @@ impl<T> Index<RangeToInclusive<Column>> for Row<T> {
 
     #[inline]
     fn index(&self, index: RangeToInclusive<Column>) -> &[T] {
-        &self.inner[..=(index.end.0)]
+        self.stored(0, index.end.0 + 1)
     }
 }
 
-impl<T> IndexMut<RangeToInclusive<Column>> for Row<T> {
+impl<T: Copy> IndexMut<RangeToInclusive<Column>> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: RangeToInclusive<Column>) -> &mut [T] {
+        self.materialize();
         self.occ = max(self.occ, *index.end);
         &mut self.inner[..=(index.end.0)]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use index::Column;
+
+    use super::Row;
+
+    fn written_row() -> Row<usize> {
+        let mut row = Row::new(Column(10), &0);
+        row[Column(1)] = 5;
+        row[Column(3)] = 7;
+        row
+    }
+
+    #[test]
+    fn trimmed_rows_keep_their_cells() {
+        let mut row = written_row();
+        row.trim();
+
+        assert_eq!(row.inner.len(), 4);
+        assert_eq!(row.len(), 10);
+        assert_eq!(row.occ, 4);
+        assert_eq!(row[Column(9)], 0);
+        assert_eq!(row.iter().cloned().collect::<Vec<_>>(), vec![0, 5, 0, 7, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(row.iter().rev().count(), 10);
+        assert!(row == written_row());
+
+        // Modifying the row stores the trimmed cells again
+        row[Column(8)] = 2;
+        assert_eq!(row.inner.len(), 10);
+        assert_eq!(row.iter().cloned().collect::<Vec<_>>(), vec![0, 5, 0, 7, 0, 0, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn only_default_cells_are_trimmed() {
+        let mut row = Row::new(Column(10), &3);
+        row[Column(1)] = 0;
+        row.trim();
+        assert_eq!(row.inner.len(), 10);
+
+        row.reset(&0);
+        row.trim();
+        assert_eq!(row.inner.len(), 10);
+
+        // All cells were reset to the default once the row is written again
+        row[..].iter_mut().for_each(|cell| *cell = 0);
+        row.trim();
+        assert!(row.inner.is_empty());
+    }
+
+    #[test]
+    fn blank_rows_are_tracked() {
+        let mut row = Row::new(Column(10), &0);
+        assert!(row.is_blank());
+
+        row[Column(3)] = 0;
+        assert!(!row.is_blank());
+
+        row.reset(&0);
+        assert!(row.is_blank());
+
+        // Cells reset to another template aren't default cells
+        row[Column(3)] = 7;
+        row.reset(&1);
+        assert!(!row.is_blank());
+        assert!(!Row::new(Column(10), &1).is_blank());
+
+        let mut row = written_row();
+        row[Column(1)] = 0;
+        row[Column(3)] = 0;
+        row.trim();
+        assert!(row.is_blank());
+    }
+
+    #[test]
+    fn resized_rows_stay_trimmed() {
+        let mut row = written_row();
+        row.trim();
+
+        // The new columns were never written
+        row.grow(Column(15), &0);
+        assert_eq!((row.inner.len(), row.len(), row.occ), (4, 15, 4));
+
+        row.shrink(Column(6));
+        assert_eq!((row.inner.len(), row.len(), row.occ), (4, 6, 4));
+
+        row.shrink(Column(2));
+        assert_eq!((row.inner.len(), row.len(), row.occ), (2, 2, 2));
+        assert_eq!(row[..], [0, 5]);
+    }
+
+    #[test]
+    fn ranges_cover_all_columns() {
+        let mut row = written_row();
+        row.trim();
+
+        assert_eq!(row[..Column(4)], [0, 5, 0, 7]);
+
+        // Mutable ranges store the trimmed cells again
+        assert_eq!((&mut row[Column(2)..]).len(), 8);
+        assert_eq!(row[Column(2)..Column(6)], [0, 7, 0, 0]);
+        assert_eq!(row[..].len(), 10);
+    }
+
+    #[test]
+    fn cells_mut_stores_trimmed_cells() {
+        let mut row = written_row();
+        row.trim();
+
+        let cells = row.cells_mut();
+        assert_eq!(cells.len(), 10);
+        cells[9] = 2;
+        assert_eq!(row.iter().cloned().collect::<Vec<_>>(), vec![0, 5, 0, 7, 0, 0, 0, 0, 0, 2]);
+        assert_eq!((row.inner.len(), row.occ), (10, 10));
+    }
+
+    #[test]
+    #[should_panic(expected = "are trimmed")]
+    fn trimmed_ranges_are_not_borrowed() {
+        let mut row = written_row();
+        row.trim();
+        let _ = &row[Column(2)..Column(6)];
+    }
+
+    #[test]
+    fn recycled_rows_reuse_allocations() {
+        let mut spare = Vec::new();
+        let mut history = written_row();
+        let allocation = history.inner.as_ptr();
+        history.trim_into(&mut spare);
+        assert_eq!(history.inner.len(), 4);
+        assert_eq!(spare.as_ptr(), allocation);
+
+        let mut recycled = written_row();
+        recycled.trim();
+        recycled.reset_with(&0, &mut spare);
+        assert_eq!(recycled.inner.as_ptr(), allocation);
+        assert_eq!(recycled.iter().cloned().collect::<Vec<_>>(), vec![0; 10]);
+        assert!(history == written_row());
+    }
+}
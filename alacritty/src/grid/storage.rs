@@ -11,6 +11,7 @@
 /// Because certain Vec operations are no longer valid on this type, no Deref
 /// implementation is provided. Anything from Vec that should be exposed must be
 /// done so manually.
+use std::iter;
 use std::ops::{Index, IndexMut};
 use std::slice;
 
@@ -94,16 +95,17 @@ impl<T> Storage<T> {
         }
     }
 
-    /// Update the size of the scrollback history
-    pub fn update_history(&mut self, history_size: usize, template_row: Row<T>)
-    where
-        T: Clone,
-    {
+    /// Drop the oldest lines of the scrollback history exceeding `history_size`
+    ///
+    /// Growing the history doesn't need any changes, since new lines are
+    /// initialized as they are scrolled into the history.
+    pub fn shrink_history(&mut self, history_size: usize) {
         let current_history = self.len - (self.visible_lines.0 + 1);
-        if history_size > current_history {
-            self.grow_lines(history_size - current_history, template_row);
-        } else if history_size < current_history {
+        if history_size < current_history {
             self.shrink_lines(current_history - history_size);
+
+            // Hidden lines would otherwise be exposed again when growing
+            self.truncate();
         }
     }
 
@@ -176,14 +178,14 @@ impl<T> Storage<T> {
     }
 
     /// Dynamically grow the storage buffer at runtime
+    ///
+    /// The new rows are inserted in front of the zero, which makes them the
+    /// oldest lines of the history. Only the rows after the zero are moved.
     pub fn initialize(&mut self, num_rows: usize, template_row: Row<T>)
         where T: Clone
     {
-        let mut new = vec![template_row; num_rows];
-
-        let mut split = self.inner.split_off(self.zero);
-        self.inner.append(&mut new);
-        self.inner.append(&mut split);
+        let zero = self.zero;
+        self.inner.splice(zero..zero, iter::repeat(template_row).take(num_rows));
 
         self.zero += num_rows;
         self.len += num_rows;
@@ -215,37 +217,10 @@ impl<T> Storage<T> {
         self.inner.swap(a, b);
     }
 
-    /// Swap implementation for Row<T>.
-    ///
-    /// Exploits the known size of Row<T> to produce a slightly more efficient
-    /// swap than going through slice::swap.
-    ///
-    /// The default implementation from swap generates 8 movups and 4 movaps
-    /// instructions. This implementation achieves the swap in only 8 movups
-    /// instructions.
     pub fn swap(&mut self, a: usize, b: usize) {
-        assert_eq_size!(Row<T>, [usize; 4]);
-
         let a = self.compute_index(a);
         let b = self.compute_index(b);
-
-        unsafe {
-            // Cast to a qword array to opt out of copy restrictions and avoid
-            // drop hazards. Byte array is no good here since for whatever
-            // reason LLVM won't optimized it.
-            let a_ptr = self.inner.as_mut_ptr().add(a) as *mut usize;
-            let b_ptr = self.inner.as_mut_ptr().add(b) as *mut usize;
-
-            // Copy 1 qword at a time
-            //
-            // The optimizer unrolls this loop and vectorizes it.
-            let mut tmp: usize;
-            for i in 0..4 {
-                tmp = *a_ptr.offset(i);
-                *a_ptr.offset(i) = *b_ptr.offset(i);
-                *b_ptr.offset(i) = tmp;
-            }
-        }
+        self.inner.swap(a, b);
     }
 
     /// Iterate over *all* entries in the underlying buffer
@@ -14,7 +14,7 @@
 
 //! Tests for the Gird
 
-use super::{Grid, BidirectionalIterator};
+use super::{Grid, BidirectionalIterator, Scroll};
 use index::{Point, Line, Column};
 
 // Scroll up moves lines upwards
@@ -139,3 +139,259 @@ fn test_iter() {
     assert_eq!(None, final_iter.next());
     assert_eq!(Some(&23), final_iter.prev());
 }
+
+/// Scroll `count` lines into the history, numbering them from `start`
+fn scroll_numbered(grid: &mut Grid<usize>, start: usize, count: usize) {
+    let lines = grid.num_lines();
+    for i in start..start + count {
+        grid.scroll_up(&(Line(0)..lines), Line(1), &0);
+        grid[lines - 1][Column(0)] = i;
+    }
+}
+
+#[test]
+fn shrink_history_while_scrolled_up() {
+    let mut grid = Grid::new(Line(3), Column(1), 10, 0);
+    scroll_numbered(&mut grid, 0, 13);
+    grid.scroll_display(Scroll::Top);
+    assert_eq!(grid.display_offset, 10);
+
+    grid.update_history(4);
+
+    // The oldest lines are dropped and the viewport stays within the history
+    assert_eq!(grid.scroll_limit(), 4);
+    assert_eq!(grid.display_offset, 4);
+    assert_eq!(grid.len(), 7);
+    let lines: Vec<usize> = (0..grid.len()).map(|i| grid[i][Column(0)]).collect();
+    assert_eq!(lines, vec![12, 11, 10, 9, 8, 7, 6]);
+    assert_eq!(grid.display_iter().count(), 3);
+}
+
+#[test]
+fn grow_history_then_fill() {
+    let mut grid = Grid::new(Line(3), Column(1), 10, 0);
+    scroll_numbered(&mut grid, 0, 13);
+    grid.update_history(4);
+    grid.update_history(8);
+
+    // Nothing is lost when growing, new lines are kept until the new limit
+    assert_eq!(grid.scroll_limit(), 4);
+    scroll_numbered(&mut grid, 13, 10);
+    assert_eq!(grid.scroll_limit(), 8);
+
+    let lines: Vec<usize> = (0..grid.len()).map(|i| grid[i][Column(0)]).collect();
+    assert_eq!(lines, (12..23).rev().collect::<Vec<_>>());
+}
+
+#[test]
+fn damage_tracks_modified_lines() {
+    let mut grid = Grid::new(Line(4), Column(2), 0, 0);
+    grid.reset_damage();
+
+    grid[Line(1)][Column(0)] = 1;
+    let damaged: Vec<bool> = (0..4).map(|i| grid.is_damaged(Line(i))).collect();
+    assert_eq!(damaged, vec![false, true, false, false]);
+
+    // Scrolling moves every line of the region
+    grid.reset_damage();
+    grid.scroll_up(&(Line(2)..Line(4)), Line(1), &0);
+    let damaged: Vec<bool> = (0..4).map(|i| grid.is_damaged(Line(i))).collect();
+    assert_eq!(damaged, vec![false, false, true, true]);
+
+    grid.resize(Line(5), Column(2), &0);
+    assert!((0..5).all(|i| grid.is_damaged(Line(i))));
+}
+
+/// Values of the lines in the viewport, from the top
+fn visible_lines(grid: &Grid<usize>) -> Vec<usize> {
+    let offset = grid.display_offset();
+    (0..*grid.num_lines()).rev().map(|i| grid[offset + i][Column(0)]).collect()
+}
+
+#[test]
+fn scrolled_viewport_stays_in_place() {
+    let mut grid = Grid::new(Line(3), Column(1), 10, 0);
+    scroll_numbered(&mut grid, 0, 6);
+    grid.scroll_display(Scroll::Lines(2));
+    let viewed = visible_lines(&grid);
+
+    scroll_numbered(&mut grid, 6, 3);
+    assert_eq!(grid.display_offset(), 5);
+    assert_eq!(visible_lines(&grid), viewed);
+
+    // Scroll regions below the top don't add lines to the history
+    grid.scroll_up(&(Line(1)..Line(3)), Line(1), &0);
+    assert_eq!(grid.display_offset(), 5);
+
+    // Once the history is full, the oldest lines are dropped from the viewport
+    scroll_numbered(&mut grid, 9, 20);
+    assert_eq!(grid.display_offset(), grid.scroll_limit());
+    assert_eq!(visible_lines(&grid), vec![16, 17, 18]);
+}
+
+#[test]
+fn viewport_stays_within_the_history() {
+    let mut grid = Grid::new(Line(5), Column(1), 4, 0);
+    scroll_numbered(&mut grid, 0, 3);
+    grid.scroll_display(Scroll::Top);
+    assert_eq!(grid.display_offset(), 3);
+
+    // Growing pulls lines out of the history
+    grid.resize(Line(7), Column(1), &0);
+    assert_eq!(grid.display_offset(), 1);
+
+    // Scrolling down the whole screen consumes the newest lines of the history
+    grid.scroll_down(&(Line(0)..Line(7)), Line(1), &0);
+    assert_eq!(grid.display_offset(), 0);
+    assert_eq!(grid.display_iter().count(), 7);
+}
+
+#[test]
+fn scroll_targets_are_clamped() {
+    // Grid with 5 lines and 10 lines of history, starting 4 lines up
+    let cases = [
+        (Scroll::Lines(3), 7),
+        (Scroll::Lines(-3), 1),
+        (Scroll::Lines(20), 10),
+        (Scroll::Lines(-20), 0),
+        (Scroll::PageUp, 9),
+        (Scroll::PageDown, 0),
+        (Scroll::HalfPageUp, 6),
+        (Scroll::HalfPageDown, 2),
+        (Scroll::Top, 10),
+        (Scroll::Bottom, 0),
+        (Scroll::Offset(8), 8),
+        (Scroll::Offset(11), 10),
+        // Middle line of the viewport is two lines above its bottom
+        (Scroll::Center(8), 6),
+        (Scroll::Center(1), 0),
+        (Scroll::Center(14), 10),
+    ];
+
+    for &(scroll, offset) in &cases {
+        let mut grid = Grid::new(Line(5), Column(1), 10, 0);
+        scroll_numbered(&mut grid, 0, 10);
+        grid.scroll_display(Scroll::Offset(4));
+        assert_eq!(grid.display_offset(), 4);
+
+        grid.scroll_display(scroll);
+        assert_eq!(grid.display_offset(), offset, "{:?}", scroll);
+    }
+
+    // The centered line is in the middle of the viewport
+    let mut grid = Grid::new(Line(5), Column(1), 10, 0);
+    scroll_numbered(&mut grid, 0, 10);
+    grid.scroll_display(Scroll::Center(8));
+    assert_eq!(visible_lines(&grid)[2], grid[8][Column(0)]);
+}
+
+#[test]
+fn output_while_scrolling_stays_in_bounds() {
+    let mut state = 0x2545_f491_u32;
+    let mut random = move |max: u32| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state % max
+    };
+
+    let mut grid = Grid::new(Line(4), Column(2), 6, 0);
+    for i in 0..2000 {
+        let lines = grid.num_lines();
+        match random(7) {
+            0 | 1 => {
+                let start = Line(random(2) as usize);
+                grid.scroll_up(&(start..lines), Line(1 + random(2) as usize), &i);
+            },
+            2 => {
+                let start = Line(random(2) as usize);
+                grid.scroll_down(&(start..lines), Line(1), &i);
+            },
+            3 => grid.scroll_display(Scroll::Lines(random(5) as isize - 2)),
+            4 => grid.scroll_display(Scroll::Top),
+            5 => grid.resize(Line(3 + random(3) as usize), Column(2), &i),
+            _ => grid.update_history(random(8) as usize),
+        }
+
+        assert!(grid.display_offset() <= grid.scroll_limit());
+        assert!(grid.scroll_limit() + *grid.num_lines() <= grid.len());
+        assert_eq!(grid.display_iter().count(), 2 * *grid.num_lines());
+    }
+}
+
+/// Lines of a grid which is scrolled by moving the lines themselves
+struct ShiftedLines {
+    history: Vec<usize>,
+    screen: Vec<usize>,
+    max_history: usize,
+}
+
+impl ShiftedLines {
+    fn scroll_up(&mut self, start: usize, end: usize, positions: usize, new: usize) {
+        let removed: Vec<usize> = self.screen.drain(start..start + positions).collect();
+        for _ in 0..positions {
+            self.screen.insert(end - positions, new);
+        }
+
+        if start == 0 {
+            self.history.extend(removed);
+            let excess = self.history.len().saturating_sub(self.max_history);
+            self.history.drain(..excess);
+        }
+    }
+
+    fn scroll_down(&mut self, start: usize, end: usize, positions: usize, new: usize) {
+        self.screen.drain(end - positions..end);
+        for _ in 0..positions {
+            self.screen.insert(start, new);
+        }
+
+        if start == 0 {
+            let len = self.history.len().saturating_sub(positions);
+            self.history.truncate(len);
+        }
+    }
+}
+
+#[test]
+fn rotations_match_shifted_lines() {
+    let mut state = 0x9e37_79b9_u32;
+    let mut random = move |max: u32| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        (state % max) as usize
+    };
+
+    let mut grid = Grid::new(Line(6), Column(1), 20, 0);
+    let mut shifted = ShiftedLines {
+        history: Vec::new(),
+        screen: vec![0; 6],
+        max_history: 20,
+    };
+
+    for i in 1..5000 {
+        let start = random(3);
+        let end = 6 - random(2);
+        let positions = 1 + random((end - start) as u32 - 1);
+
+        // The new lines are numbered, since recycled lines are reset to the template
+        let new = if random(4) == 0 {
+            grid.scroll_down(&(Line(start)..Line(end)), Line(positions), &0);
+            shifted.scroll_down(start, end, positions, i);
+            start..start + positions
+        } else {
+            grid.scroll_up(&(Line(start)..Line(end)), Line(positions), &0);
+            shifted.scroll_up(start, end, positions, i);
+            end - positions..end
+        };
+        for line in new {
+            grid[Line(line)][Column(0)] = i;
+        }
+
+        assert_eq!(visible_lines(&grid), shifted.screen);
+
+        let history: Vec<usize> = (0..grid.scroll_limit()).rev().map(|i| grid[6 + i][Column(0)]).collect();
+        assert_eq!(history, shifted.history);
+    }
+}
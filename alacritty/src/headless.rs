@@ -0,0 +1,153 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Terminal emulation without a window
+//!
+//! The `ansi`, `grid`, `index`, `selection` and `term` modules don't depend on
+//! the window, the renderer or the font rasterizer, which are left out when
+//! the library is built without the `gui` feature. The `Emulator` ties the
+//! parser to a `Term`, for programs which only feed it bytes and look at the
+//! resulting cells and modes through `Emulator::term`.
+use std::io;
+
+use ansi;
+use config::Config;
+use term::{SizeInfo, Term};
+
+/// Receiver of the bytes the terminal sends back to the program
+///
+/// These are the replies to queries like device attributes and cursor position
+/// reports, which Alacritty itself writes to the pty. A single reply may be
+/// passed on in several pieces.
+pub trait Responder {
+    fn respond(&mut self, bytes: &[u8]);
+}
+
+impl Responder for Vec<u8> {
+    fn respond(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+/// Drops all responses
+impl Responder for io::Sink {
+    fn respond(&mut self, _bytes: &[u8]) {}
+}
+
+/// Passes the writes of the parser on to a `Responder`
+struct Responses<'a, R: 'a>(&'a mut R);
+
+impl<'a, R: Responder> io::Write for Responses<'a, R> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.respond(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A terminal which is driven by feeding it bytes
+pub struct Emulator<R> {
+    term: Term,
+    parser: ansi::Processor,
+    responder: R,
+}
+
+impl<R: Responder> Emulator<R> {
+    /// Emulator with the default config
+    pub fn new(size: SizeInfo, responder: R) -> Emulator<R> {
+        Emulator::with_config(&Config::default(), size, responder)
+    }
+
+    pub fn with_config(config: &Config, size: SizeInfo, responder: R) -> Emulator<R> {
+        Emulator {
+            term: Term::new(config, size),
+            parser: ansi::Processor::new(),
+            responder,
+        }
+    }
+
+    /// Parse output of the program running in the terminal
+    pub fn advance(&mut self, bytes: &[u8]) {
+        let mut responses = Responses(&mut self.responder);
+        for &byte in bytes {
+            self.parser.advance(&mut self.term, byte, &mut responses);
+        }
+    }
+
+    pub fn resize(&mut self, size: &SizeInfo) {
+        self.term.resize(size);
+    }
+
+    #[inline]
+    pub fn term(&self) -> &Term {
+        &self.term
+    }
+
+    #[inline]
+    pub fn term_mut(&mut self) -> &mut Term {
+        &mut self.term
+    }
+
+    #[inline]
+    pub fn responder(&self) -> &R {
+        &self.responder
+    }
+
+    #[inline]
+    pub fn responder_mut(&mut self) -> &mut R {
+        &mut self.responder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use index::{Column, Line};
+    use term::{SizeInfo, TermMode};
+
+    use super::Emulator;
+
+    fn size() -> SizeInfo {
+        SizeInfo {
+            width: 10.0,
+            height: 3.0,
+            cell_width: 1.0,
+            cell_height: 1.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        }
+    }
+
+    #[test]
+    fn bytes_are_parsed_into_the_grid() {
+        let mut emulator = Emulator::new(size(), Vec::new());
+        emulator.advance(b"hi\r\n\x1b[?1h");
+
+        let grid = emulator.term().grid();
+        assert_eq!(grid[Line(0)][Column(0)].c, 'h');
+        assert_eq!(grid[Line(0)][Column(1)].c, 'i');
+        assert_eq!(emulator.term().cursor().point.line, Line(1));
+        assert!(emulator.term().mode().contains(TermMode::APP_CURSOR));
+    }
+
+    #[test]
+    fn responses_are_passed_to_the_responder() {
+        let mut emulator = Emulator::new(size(), Vec::new());
+        emulator.advance(b"abc\x1b[6n\x1b[5n");
+
+        assert_eq!(&emulator.responder()[..], &b"\x1b[1;4R\x1b[0n"[..]);
+    }
+}
@@ -19,24 +19,222 @@
 //! needs to be tracked. Additionally, we need a bit of a state machine to
 //! determine what to do when a non-modifier key is pressed.
 use std::borrow::Cow;
+use std::cmp::min;
 use std::mem;
-use std::process::Command;
-use std::time::Instant;
-#[cfg(not(windows))]
-use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use copypasta::{Clipboard, Load, Buffer as ClipboardBuffer};
 use glutin::{ElementState, MouseButton, TouchPhase, MouseScrollDelta, ModifiersState, KeyboardInput};
+use glutin::VirtualKeyCode;
 
-use config::{self, Key};
+use config::{self, Key, OptionAsAlt};
+use daemon;
 use grid::Scroll;
-use event::{ClickState, Mouse};
 use index::{Line, Column, Side, Point};
-use term::SizeInfo;
+use keyboard_layout;
+use paste::Paste;
+use term::{SizeInfo, FONT_SIZE_STEP};
 use term::mode::TermMode;
 use util::fmt::Red;
+use util::shell_escape;
 
-pub const FONT_SIZE_STEP: f32 = 0.5;
+/// Milliseconds between scrolls while a selection is dragged past the grid
+const SELECTION_SCROLL_INTERVAL_MS: u64 = 50;
+
+/// Milliseconds between scrolls while the viewport keeps moving after a swipe
+const MOMENTUM_INTERVAL_MS: u64 = 16;
+
+/// Seconds after which the velocity of a swipe is halved
+const MOMENTUM_HALF_LIFE: f64 = 0.3;
+
+/// Velocity in pixels per second below which momentum scrolling stops
+const MOMENTUM_MIN_VELOCITY: f64 = 30.;
+
+/// Milliseconds a finger may rest before lifting it still starts momentum scrolling
+const MOMENTUM_MAX_REST_MS: u64 = 100;
+
+/// Largest zero-based coordinate of mouse reports in the X10 encoding
+const MAX_X10_MOUSE_COORD: usize = 255 - 32 - 1;
+
+/// Largest zero-based coordinate of mouse reports encoded as UTF-8, the last
+/// one which fits into two bytes
+const MAX_UTF8_MOUSE_COORD: usize = 0x7ff - 32 - 1;
+
+/// The ActionContext can't really have direct access to the Window
+/// with the current design. Event handlers that want to change the
+/// window must set these flags instead. The processor will trigger
+/// the actual changes.
+pub struct WindowChanges {
+    pub hide: bool,
+    pub toggle_fullscreen: bool,
+    pub toggle_simple_fullscreen: bool,
+    pub toggle_secure_input: bool,
+    /// The window was exposed or changed its state
+    pub refresh_visibility: bool,
+}
+
+impl WindowChanges {
+    pub fn clear(&mut self) {
+        self.hide = false;
+        self.toggle_fullscreen = false;
+        self.toggle_simple_fullscreen = false;
+        self.toggle_secure_input = false;
+        self.refresh_visibility = false;
+    }
+}
+
+impl Default for WindowChanges {
+    fn default() -> WindowChanges {
+        WindowChanges {
+            hide: false,
+            toggle_fullscreen: false,
+            toggle_simple_fullscreen: false,
+            toggle_secure_input: false,
+            refresh_visibility: false,
+        }
+    }
+}
+
+pub enum ClickState {
+    None,
+    Click,
+    DoubleClick,
+    TripleClick,
+}
+
+/// State of the mouse
+pub struct Mouse {
+    pub x: usize,
+    pub y: usize,
+    pub left_button_state: ElementState,
+    pub middle_button_state: ElementState,
+    pub right_button_state: ElementState,
+    pub last_click_timestamp: Instant,
+    pub click_state: ClickState,
+
+    /// Position of the last press of the left button
+    pub press_x: usize,
+    pub press_y: usize,
+
+    /// The pointer moved past the drag threshold since the left button was pressed
+    pub dragging: bool,
+
+    pub scroll_px: i32,
+    pub horizontal_scroll_px: i32,
+    pub line: Line,
+    pub column: Column,
+    pub cell_side: Side,
+    pub lines_scrolled: f32,
+    pub columns_scrolled: f32,
+    pub block_url_launcher: bool,
+
+    /// The pressed buttons are reported to the application
+    ///
+    /// This is decided when a button is pressed, so pressing or releasing
+    /// Shift while dragging doesn't switch between selecting and reporting.
+    pub report_buttons: bool,
+
+    /// Cell of the last report sent to the application
+    ///
+    /// Motion is only reported once the pointer enters another cell.
+    pub last_report_cell: Option<Point>,
+
+    /// Lines scrolled per tick while a selection is dragged past the grid
+    pub selection_scroll: isize,
+    pub last_selection_scroll: Option<Instant>,
+}
+
+impl Default for Mouse {
+    fn default() -> Mouse {
+        Mouse {
+            x: 0,
+            y: 0,
+            last_click_timestamp: Instant::now(),
+            left_button_state: ElementState::Released,
+            middle_button_state: ElementState::Released,
+            right_button_state: ElementState::Released,
+            click_state: ClickState::None,
+            press_x: 0,
+            press_y: 0,
+            dragging: false,
+            scroll_px: 0,
+            horizontal_scroll_px: 0,
+            line: Line(0),
+            column: Column(0),
+            cell_side: Side::Left,
+            lines_scrolled: 0.0,
+            columns_scrolled: 0.0,
+            block_url_launcher: false,
+            report_buttons: false,
+            last_report_cell: None,
+            selection_scroll: 0,
+            last_selection_scroll: None,
+        }
+    }
+}
+
+/// Gesture performed with the fingers on a touchscreen
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Gesture {
+    None,
+    /// A finger rests on the screen, it's not known yet what it will do
+    Tap,
+    /// Two fingers rest on the screen
+    TwoFingerTap,
+    /// A finger drags the viewport
+    Scroll,
+    /// The viewport keeps scrolling after the finger was lifted
+    Momentum,
+    /// A finger extends the selection
+    Select,
+    /// The fingers don't form any gesture, they're ignored until lifted
+    Ignored,
+}
+
+/// A finger on a touchscreen
+#[derive(Debug, Copy, Clone)]
+pub struct Finger {
+    pub id: u64,
+    /// Position at which the finger touched the screen
+    pub start_x: f64,
+    pub start_y: f64,
+}
+
+/// State of the touchscreen
+///
+/// This is tracked separately from the mouse, so devices which report touch
+/// and mouse events don't mix up their state.
+pub struct TouchState {
+    /// Fingers which are on the screen, the first one performs the gesture
+    pub fingers: Vec<Finger>,
+    pub gesture: Gesture,
+    /// Time at which the first finger touched the screen
+    pub start: Instant,
+    /// Last position of the finger performing the gesture
+    pub x: f64,
+    pub y: f64,
+    /// Pixels which didn't add up to a full line yet
+    pub scroll_px: f64,
+    /// Vertical velocity of the scrolled viewport in pixels per second
+    pub velocity: f64,
+    pub last_update: Instant,
+}
+
+impl Default for TouchState {
+    fn default() -> TouchState {
+        TouchState {
+            fingers: Vec::new(),
+            gesture: Gesture::None,
+            start: Instant::now(),
+            x: 0.,
+            y: 0.,
+            scroll_px: 0.,
+            velocity: 0.,
+            last_update: Instant::now(),
+        }
+    }
+}
 
 /// Processes input from glutin.
 ///
@@ -49,15 +247,45 @@ pub struct Processor<'a, A: 'a> {
     pub mouse_bindings: &'a [MouseBinding],
     pub mouse_config: &'a config::Mouse,
     pub scrolling_config: &'a config::Scrolling,
+    pub touch_config: &'a config::Touch,
     pub ctx: A,
     pub save_to_clipboard: bool,
+    pub alt_send_esc: bool,
+    pub option_as_alt: OptionAsAlt,
+}
+
+/// Direction of scrolling
+#[derive(Debug, Copy, Clone)]
+enum Axis {
+    Vertical,
+    Horizontal,
+}
+
+/// Alt keys which are currently held down
+#[derive(Debug, Default, Copy, Clone)]
+pub struct AltKeys {
+    pub left: bool,
+    pub right: bool,
 }
 
 pub trait ActionContext {
     fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, B);
+
+    /// Write input of the user to the pty
+    ///
+    /// A paste which is still being written is cancelled first, the input
+    /// would end up in the middle of the pasted text otherwise.
+    fn write_input<B: Into<Cow<'static, [u8]>>>(&mut self, input: B) {
+        self.cancel_paste();
+        self.write_to_pty(input);
+    }
+
+    fn start_paste(&mut self, paste: Paste);
+    fn cancel_paste(&mut self) -> bool;
     fn terminal_mode(&self) -> TermMode;
     fn size_info(&self) -> SizeInfo;
     fn copy_selection(&self, ClipboardBuffer);
+    fn copy_selection_as_html(&self);
     fn clear_selection(&mut self);
     fn update_selection(&mut self, point: Point, side: Side);
     fn simple_selection(&mut self, point: Point, side: Side);
@@ -67,21 +295,33 @@ pub trait ActionContext {
     fn mouse_mut(&mut self) -> &mut Mouse;
     fn mouse(&self) -> &Mouse;
     fn mouse_coords(&self) -> Option<Point>;
+    fn touch_mut(&mut self) -> &mut TouchState;
+    fn touch(&self) -> &TouchState;
     fn received_count(&mut self) -> &mut usize;
     fn suppress_chars(&mut self) -> &mut bool;
     fn last_modifiers(&mut self) -> &mut ModifiersState;
+    fn alt_keys(&mut self) -> &mut AltKeys;
     fn change_font_size(&mut self, delta: f32);
     fn reset_font_size(&mut self);
     fn scroll(&mut self, scroll: Scroll);
     fn clear_history(&mut self);
     fn hide_window(&mut self);
+    fn toggle_fullscreen(&mut self);
+    fn toggle_simple_fullscreen(&mut self);
+    fn toggle_secure_input(&mut self);
+    fn dump_frame_stats(&mut self);
+    fn dump_screen(&mut self);
+    fn dump_state(&mut self);
+    fn simulate_context_loss(&mut self);
+    fn screenshot(&mut self);
     fn url(&self, _: Point<usize>) -> Option<String>;
+    fn highlight_url(&mut self, _: Option<Point<usize>>);
 }
 
 /// Describes a state and action to take in that state
 ///
 /// This is the shared component of `MouseBinding` and `KeyBinding`
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Binding<T> {
     /// Modifier keys required to activate binding
     pub mods: ModifiersState,
@@ -154,7 +394,7 @@ impl<T> Binding<T> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Action {
     /// Write an escape sequence
     Esc(String),
@@ -165,6 +405,9 @@ pub enum Action {
     // Store current selection into clipboard
     Copy,
 
+    /// Store current selection into clipboard as HTML, keeping the colors
+    CopyAsHtml,
+
     /// Paste contents of selection buffer
     PasteSelection,
 
@@ -183,6 +426,12 @@ pub enum Action {
     /// Scroll exactly one page down
     ScrollPageDown,
 
+    /// Scroll half a page up
+    ScrollHalfPageUp,
+
+    /// Scroll half a page down
+    ScrollHalfPageDown,
+
     /// Scroll all the way to the top
     ScrollToTop,
 
@@ -198,6 +447,30 @@ pub enum Action {
     /// Hides the Alacritty window
     Hide,
 
+    /// Toggle fullscreen, in a space of its own on macOS
+    ToggleFullscreen,
+
+    /// Toggle fullscreen within the current space on macOS
+    ToggleSimpleFullscreen,
+
+    /// Toggle secure keyboard entry on macOS
+    ToggleSecureInput,
+
+    /// Write the statistics of the frame times to the log
+    DumpFrameStats,
+
+    /// Write the text of the screen to a file
+    DumpScreen,
+
+    /// Write the internal state of the terminal to a JSON file
+    DumpState,
+
+    /// Create the window and the renderer again like after the GL context was lost
+    SimulateContextLoss,
+
+    /// Write the rendered terminal to a PNG file
+    Screenshot,
+
     /// Quits Alacritty.
     Quit,
 }
@@ -208,17 +481,20 @@ impl Action {
         match *self {
             Action::Esc(ref s) => {
                 ctx.scroll(Scroll::Bottom);
-                ctx.write_to_pty(s.clone().into_bytes())
+                ctx.write_input(s.clone().into_bytes())
             },
             Action::Copy => {
                 ctx.copy_selection(ClipboardBuffer::Primary);
             },
+            Action::CopyAsHtml => {
+                ctx.copy_selection_as_html();
+            },
             Action::Paste => {
                 Clipboard::new()
                     .and_then(|clipboard| clipboard.load_primary() )
                     .map(|contents| { self.paste(ctx, &contents) })
                     .unwrap_or_else(|err| {
-                        eprintln!("Error loading data from clipboard. {}", Red(err));
+                        warn!("Error loading data from clipboard. {}", Red(err));
                     });
             },
             Action::PasteSelection => {
@@ -235,35 +511,38 @@ impl Action {
             Action::Command(ref program, ref args) => {
                 trace!("running command: {} {:?}", program, args);
 
-                #[cfg(not(windows))]
-                let spawned = Command::new(program)
-                    .args(args)
-                    .before_exec(|| {
-                        // Detach forked process from Alacritty. This will cause
-                        // init or whatever to clean up child processes for us.
-                        unsafe { ::libc::daemon(1, 0); }
-                        Ok(())
-                    })
-                    .spawn();
-
-                #[cfg(windows)]
-                let spawned = Command::new(program)
-                    .args(args)
-                    .spawn();
-
-                match spawned
-                {
-                    Ok(child) => {
-                        debug!("spawned new proc with pid: {}", child.id());
-                    },
-                    Err(err) => {
-                        warn!("couldn't run command: {}", err);
-                    },
+                match daemon::spawn(program, args) {
+                    Ok(()) => debug!("Launched: {} {:?}", program, args),
+                    Err(err) => warn!("couldn't run command: {}", err),
                 }
             },
             Action::Hide => {
                 ctx.hide_window();
             },
+            Action::ToggleFullscreen => {
+                ctx.toggle_fullscreen();
+            },
+            Action::ToggleSimpleFullscreen => {
+                ctx.toggle_simple_fullscreen();
+            },
+            Action::ToggleSecureInput => {
+                ctx.toggle_secure_input();
+            },
+            Action::DumpFrameStats => {
+                ctx.dump_frame_stats();
+            },
+            Action::DumpScreen => {
+                ctx.dump_screen();
+            },
+            Action::DumpState => {
+                ctx.dump_state();
+            },
+            Action::SimulateContextLoss => {
+                ctx.simulate_context_loss();
+            },
+            Action::Screenshot => {
+                ctx.screenshot();
+            },
             Action::Quit => {
                 // FIXME should do a more graceful shutdown
                 ::std::process::exit(0);
@@ -283,6 +562,12 @@ impl Action {
             Action::ScrollPageDown => {
                 ctx.scroll(Scroll::PageDown);
             },
+            Action::ScrollHalfPageUp => {
+                ctx.scroll(Scroll::HalfPageUp);
+            },
+            Action::ScrollHalfPageDown => {
+                ctx.scroll(Scroll::HalfPageDown);
+            },
             Action::ScrollToTop => {
                 ctx.scroll(Scroll::Top);
             },
@@ -295,19 +580,19 @@ impl Action {
         }
     }
 
+    /// Write the first chunk of a paste, the rest is written by the event loop
     fn paste<A: ActionContext>(&self, ctx: &mut A, contents: &str) {
-        if ctx.terminal_mode().contains(TermMode::BRACKETED_PASTE) {
-            ctx.write_to_pty(&b"\x1b[200~"[..]);
-            ctx.write_to_pty(contents.replace("\x1b","").into_bytes());
-            ctx.write_to_pty(&b"\x1b[201~"[..]);
-        } else {
-            // In non-bracketed (ie: normal) mode, terminal applications cannot distinguish
-            // pasted data from keystrokes.
-            // In theory, we should construct the keystrokes needed to produce the data we are
-            // pasting... since that's neither practical nor sensible (and probably an impossible
-            // task to solve in a general way), we'll just replace line breaks (windows and unix
-            // style) with a singe carriage return (\r, which is what the Enter key produces).
-            ctx.write_to_pty(contents.replace("\r\n","\r").replace("\n","\r").into_bytes());
+        // A new paste ends the previous one, so brackets aren't nested
+        ctx.cancel_paste();
+
+        let bracketed = ctx.terminal_mode().contains(TermMode::BRACKETED_PASTE);
+        let mut paste = Paste::new(contents, bracketed);
+        if let Some(chunk) = paste.next_chunk() {
+            ctx.write_to_pty(chunk);
+        }
+
+        if !paste.is_finished() {
+            ctx.start_paste(paste);
         }
     }
 }
@@ -333,6 +618,23 @@ impl From<&'static str> for Action {
     }
 }
 
+/// Modifiers which are held after a key event
+///
+/// Not every platform includes the change of a modifier key in the modifiers
+/// of its own event.
+pub fn modifiers_after(input: KeyboardInput) -> ModifiersState {
+    let mut modifiers = input.modifiers;
+    let pressed = input.state == ElementState::Pressed;
+    match input.virtual_keycode {
+        Some(VirtualKeyCode::LShift) | Some(VirtualKeyCode::RShift) => modifiers.shift = pressed,
+        Some(VirtualKeyCode::LControl) | Some(VirtualKeyCode::RControl) => modifiers.ctrl = pressed,
+        Some(VirtualKeyCode::LAlt) | Some(VirtualKeyCode::RAlt) => modifiers.alt = pressed,
+        Some(VirtualKeyCode::LWin) | Some(VirtualKeyCode::RWin) => modifiers.logo = pressed,
+        _ => (),
+    }
+    modifiers
+}
+
 impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     #[inline]
     pub fn mouse_moved(&mut self, x: usize, y: usize, modifiers: ModifiersState) {
@@ -348,7 +650,8 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         let prev_col = mem::replace(&mut self.ctx.mouse_mut().column, point.col);
 
         let motion_mode = TermMode::MOUSE_MOTION | TermMode::MOUSE_DRAG;
-        let report_mode = TermMode::MOUSE_REPORT_CLICK | motion_mode;
+
+        self.update_url_highlight(modifiers);
 
         // Don't launch URLs if mouse has moved
         if prev_line != self.ctx.mouse().line
@@ -359,8 +662,19 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
 
         if self.ctx.mouse().left_button_state == ElementState::Pressed
-            && (modifiers.shift || !self.ctx.terminal_mode().intersects(report_mode))
+            && !self.ctx.mouse().report_buttons
         {
+            // A pointer jittering during a click doesn't select a cell
+            if !self.ctx.mouse().dragging {
+                let mouse = self.ctx.mouse();
+                let dx = x as f64 - mouse.press_x as f64;
+                let dy = y as f64 - mouse.press_y as f64;
+                if dx.hypot(dy) <= f64::from(self.mouse_config.drag_threshold) {
+                    return;
+                }
+                self.ctx.mouse_mut().dragging = true;
+            }
+
             self.ctx.update_selection(
                 Point {
                     line: point.line,
@@ -370,24 +684,88 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
             );
         } else if self.ctx.terminal_mode().intersects(motion_mode)
             // Only report motion when changing cells
-            && (prev_line != self.ctx.mouse().line || prev_col != self.ctx.mouse().column)
+            && self.ctx.mouse().last_report_cell != Some(point)
             && size_info.contains_point(x, y)
         {
+            let report_buttons = self.ctx.mouse().report_buttons;
             if self.ctx.mouse().left_button_state == ElementState::Pressed {
                 self.mouse_report(32, ElementState::Pressed, modifiers);
             } else if self.ctx.mouse().middle_button_state == ElementState::Pressed {
-                self.mouse_report(33, ElementState::Pressed, modifiers);
+                if report_buttons {
+                    self.mouse_report(33, ElementState::Pressed, modifiers);
+                }
             } else if self.ctx.mouse().right_button_state == ElementState::Pressed {
-                self.mouse_report(34, ElementState::Pressed, modifiers);
+                if report_buttons {
+                    self.mouse_report(34, ElementState::Pressed, modifiers);
+                }
             } else if self.ctx.terminal_mode().contains(TermMode::MOUSE_MOTION) {
                 self.mouse_report(35, ElementState::Pressed, modifiers);
             }
         }
     }
 
+    /// Start or stop scrolling while a selection is dragged past the grid
+    ///
+    /// The viewport is scrolled by one line per tick for every line the pointer
+    /// is past the grid, so it can lie outside of the window here.
+    pub fn update_selection_scroll(&mut self, y: i32) {
+        let size_info = self.ctx.size_info();
+        let selecting = self.ctx.mouse().left_button_state == ElementState::Pressed
+            && !self.ctx.mouse().report_buttons;
+
+        let cell_height = size_info.cell_height as i32;
+        let top = size_info.padding_y as i32;
+        let bottom = top + size_info.lines().0 as i32 * cell_height;
+
+        let lines = if !selecting {
+            0
+        } else if y < top {
+            ((top - y) / cell_height + 1) as isize
+        } else if y >= bottom {
+            -((y - bottom) / cell_height + 1) as isize
+        } else {
+            0
+        };
+
+        let mouse = self.ctx.mouse_mut();
+        if lines == 0 {
+            mouse.last_selection_scroll = None;
+        }
+        mouse.selection_scroll = lines;
+    }
+
+    /// Scroll the viewport while a selection is dragged past the grid
+    ///
+    /// Returns the time until the next scroll is due, if scrolling continues.
+    pub fn selection_scroll_tick(&mut self) -> Option<Duration> {
+        let lines = self.ctx.mouse().selection_scroll;
+        if lines == 0 {
+            return None;
+        }
+
+        let interval = Duration::from_millis(SELECTION_SCROLL_INTERVAL_MS);
+        let now = Instant::now();
+        if let Some(last) = self.ctx.mouse().last_selection_scroll {
+            let elapsed = now.duration_since(last);
+            if elapsed < interval {
+                return Some(interval - elapsed);
+            }
+        }
+
+        // Scrolling extends the selection to the pointer's new position
+        self.ctx.mouse_mut().last_selection_scroll = Some(now);
+        self.ctx.scroll(Scroll::Lines(lines));
+
+        Some(interval)
+    }
+
     fn get_mouse_side(&self) -> Side {
+        self.side_at(self.ctx.mouse().x)
+    }
+
+    /// Side of the cell at the horizontal position `x`
+    fn side_at(&self, x: usize) -> Side {
         let size_info = self.ctx.size_info();
-        let x = self.ctx.mouse().x;
 
         let cell_x = x.saturating_sub(size_info.padding_x as usize) % size_info.cell_width as usize;
         let half_cell_width = (size_info.cell_width / 2.0) as usize;
@@ -407,19 +785,32 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
 
     pub fn normal_mouse_report(&mut self, button: u8) {
         let (line, column) = (self.ctx.mouse().line, self.ctx.mouse().column);
+        let utf8 = self.ctx.terminal_mode().contains(TermMode::UTF8_MOUSE);
 
-        if line < Line(223) && column < Column(223) {
-            let msg = vec![
-                b'\x1b',
-                b'[',
-                b'M',
-                32 + button,
-                32 + 1 + column.0 as u8,
-                32 + 1 + line.0 as u8,
-            ];
+        let mut msg = vec![b'\x1b', b'[', b'M', 32 + button];
 
-            self.ctx.write_to_pty(msg);
+        // Coordinates past the largest one which can be encoded saturate
+        for &coord in &[column.0, line.0] {
+            if utf8 {
+                let value = 32 + 1 + min(coord, MAX_UTF8_MOUSE_COORD);
+                if value < 0x80 {
+                    msg.push(value as u8);
+                } else {
+                    msg.push(0xc0 | (value >> 6) as u8);
+                    msg.push(0x80 | (value & 0x3f) as u8);
+                }
+            } else {
+                msg.push(32 + 1 + min(coord, MAX_X10_MOUSE_COORD) as u8);
+            }
         }
+
+        self.ctx.write_input(msg);
+    }
+
+    pub fn urxvt_mouse_report(&mut self, button: u8) {
+        let (line, column) = (self.ctx.mouse().line, self.ctx.mouse().column);
+        let msg = format!("\x1b[{};{};{}M", 32 + button, column + 1, line + 1);
+        self.ctx.write_input(msg.into_bytes());
     }
 
     pub fn sgr_mouse_report(&mut self, button: u8, state: ElementState) {
@@ -430,10 +821,13 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         };
 
         let msg = format!("\x1b[<{};{};{}{}", button, column + 1, line + 1, c);
-        self.ctx.write_to_pty(msg.into_bytes());
+        self.ctx.write_input(msg.into_bytes());
     }
 
     pub fn mouse_report(&mut self, button: u8, state: ElementState, modifiers: ModifiersState) {
+        let mouse = self.ctx.mouse_mut();
+        mouse.last_report_cell = Some(Point::new(mouse.line, mouse.column));
+
         // Calculate modifiers value
         let mut mods = 0;
         if modifiers.shift {
@@ -447,12 +841,20 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
 
         // Report mouse events
-        if self.ctx.terminal_mode().contains(TermMode::SGR_MOUSE) {
+        let mode = self.ctx.terminal_mode();
+        if mode.contains(TermMode::SGR_MOUSE) {
             self.sgr_mouse_report(button + mods, state);
-        } else if let ElementState::Released = state {
-            self.normal_mouse_report(3 + mods);
+            return;
+        }
+
+        let button = match state {
+            ElementState::Released => 3 + mods,
+            ElementState::Pressed => button + mods,
+        };
+        if mode.contains(TermMode::URXVT_MOUSE) {
+            self.urxvt_mouse_report(button);
         } else {
-            self.normal_mouse_report(button + mods);
+            self.normal_mouse_report(button);
         }
     }
 
@@ -473,6 +875,41 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         let elapsed = self.ctx.mouse().last_click_timestamp.elapsed();
         self.ctx.mouse_mut().last_click_timestamp = now;
 
+        // The click applies to the bottom of the scrollback, where the
+        // selection starts and reports refer to
+        if self.mouse_config.scroll_to_bottom_on_click {
+            self.ctx.scroll(Scroll::Bottom);
+        }
+
+        // Shift bypasses the application's mouse reporting
+        let report_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        let report_buttons = !modifiers.shift && self.ctx.terminal_mode().intersects(report_modes);
+        self.ctx.mouse_mut().report_buttons = report_buttons;
+
+        if report_buttons {
+            // Clicks reported to the application don't count towards multi-clicks
+            self.ctx.mouse_mut().click_state = ClickState::None;
+            self.ctx.mouse_mut().block_url_launcher = !self.ctx.selection_is_empty();
+            self.ctx.clear_selection();
+
+            match button {
+                MouseButton::Left   => self.mouse_report(0, ElementState::Pressed, modifiers),
+                MouseButton::Middle => self.mouse_report(1, ElementState::Pressed, modifiers),
+                MouseButton::Right  => self.mouse_report(2, ElementState::Pressed, modifiers),
+                // Can't properly report more than three buttons.
+                MouseButton::Other(_) => (),
+            };
+            return;
+        }
+
+        if button == MouseButton::Left {
+            let mouse = self.ctx.mouse_mut();
+            mouse.press_x = mouse.x;
+            mouse.press_y = mouse.y;
+            mouse.dragging = false;
+        }
+
+        // A threshold of zero never matches, which disables multi-clicks
         self.ctx.mouse_mut().click_state = match self.ctx.mouse().click_state {
             ClickState::Click if elapsed < self.mouse_config.double_click.threshold => {
                 self.ctx.mouse_mut().block_url_launcher = true;
@@ -496,27 +933,19 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
                     self.ctx.simple_selection(point, side);
                 }
 
-                let report_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
-                if !modifiers.shift && self.ctx.terminal_mode().intersects(report_modes) {
-                    match button {
-                        MouseButton::Left   => self.mouse_report(0, ElementState::Pressed, modifiers),
-                        MouseButton::Middle => self.mouse_report(1, ElementState::Pressed, modifiers),
-                        MouseButton::Right  => self.mouse_report(2, ElementState::Pressed, modifiers),
-                        // Can't properly report more than three buttons.
-                        MouseButton::Other(_) => (),
-                    };
-                    return;
-                }
-
                 ClickState::Click
             }
         };
     }
 
     pub fn on_mouse_release(&mut self, button: MouseButton, modifiers: ModifiersState) {
-        let report_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
-        if !modifiers.shift && self.ctx.terminal_mode().intersects(report_modes)
-        {
+        if button == MouseButton::Left {
+            self.ctx.mouse_mut().selection_scroll = 0;
+            self.ctx.mouse_mut().last_selection_scroll = None;
+        }
+
+        // Releases are reported if the press was, regardless of Shift
+        if self.ctx.mouse().report_buttons {
             match button {
                 MouseButton::Left   => self.mouse_report(0, ElementState::Released, modifiers),
                 MouseButton::Middle => self.mouse_report(1, ElementState::Released, modifiers),
@@ -535,6 +964,22 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         self.ctx.copy_selection(ClipboardBuffer::Selection);
     }
 
+    /// Underline the URL below the mouse while it can be launched
+    ///
+    /// Without modifiers in the config, URLs are underlined whenever the mouse
+    /// is above them.
+    pub fn update_url_highlight(&mut self, modifiers: ModifiersState) {
+        let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        let active = self.mouse_config.url.launcher.is_some()
+            && self.mouse_config.url.modifiers.relaxed_eq(modifiers)
+            && self.ctx.mouse().left_button_state == ElementState::Released
+            // Clicks are reported instead, unless Shift is held
+            && (modifiers.shift || !self.ctx.terminal_mode().intersects(mouse_modes));
+
+        let point = if active { self.ctx.mouse_coords() } else { None };
+        self.ctx.highlight_url(point.map(Into::into));
+    }
+
     // Spawn URL launcher when clicking on URLs
     fn launch_url(&self, modifiers: ModifiersState) -> Option<()> {
         if !self.mouse_config.url.modifiers.relaxed_eq(modifiers)
@@ -550,8 +995,8 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         let mut args = launcher.args().to_vec();
         args.push(text);
 
-        match Command::new(launcher.program()).args(&args).spawn() {
-            Ok(_) => debug!("Launched: {} {:?}", launcher.program(), args),
+        match daemon::spawn(launcher.program(), &args) {
+            Ok(()) => debug!("Launched: {} {:?}", launcher.program(), args),
             Err(_) => warn!("Unable to launch: {} {:?}", launcher.program(), args),
         }
 
@@ -560,7 +1005,11 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
 
     pub fn on_mouse_wheel(&mut self, delta: MouseScrollDelta, phase: TouchPhase, modifiers: ModifiersState) {
         match delta {
-            MouseScrollDelta::LineDelta(_columns, lines) => {
+            MouseScrollDelta::LineDelta(columns, lines) => {
+                let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+
+                // Fractional deltas of high resolution wheels are accumulated until
+                // they add up to a full notch
                 let to_scroll = self.ctx.mouse().lines_scrolled + lines;
                 let code = if to_scroll > 0.0 {
                     64
@@ -569,33 +1018,47 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
                 };
 
                 let scrolling_multiplier = self.scrolling_config.multiplier;
+                let faux_multiplier = self.faux_multiplier();
                 for _ in 0..(to_scroll.abs() as usize) {
-                    self.scroll_terminal(code, modifiers, scrolling_multiplier)
+                    if modifiers.shift && !self.ctx.terminal_mode().intersects(mouse_modes) {
+                        // Shift scrolls the scrollback by a page for every notch
+                        self.ctx.scroll(if code == 64 { Scroll::PageUp } else { Scroll::PageDown });
+                    } else {
+                        self.scroll_terminal(code, modifiers, scrolling_multiplier, faux_multiplier)
+                    }
                 }
 
                 self.ctx.mouse_mut().lines_scrolled = to_scroll % 1.0;
+
+                // Tilting the wheel scrolls to the right for positive deltas
+                let to_scroll = self.ctx.mouse().columns_scrolled + columns;
+                let code = if to_scroll > 0.0 {
+                    67
+                } else {
+                    66
+                };
+
+                for _ in 0..(to_scroll.abs() as usize) {
+                    self.scroll_terminal(code, modifiers, scrolling_multiplier, faux_multiplier)
+                }
+
+                self.ctx.mouse_mut().columns_scrolled = to_scroll % 1.0;
             },
-            MouseScrollDelta::PixelDelta(_x, y) => {
+            MouseScrollDelta::PixelDelta(x, y) => {
                 match phase {
                     TouchPhase::Started => {
                         // Reset offset to zero
                         self.ctx.mouse_mut().scroll_px = 0;
+                        self.ctx.mouse_mut().horizontal_scroll_px = 0;
                     },
                     TouchPhase::Moved => {
-                        self.ctx.mouse_mut().scroll_px += y as i32;
-                        let height = self.ctx.size_info().cell_height as i32;
-
-                        while self.ctx.mouse().scroll_px.abs() >= height {
-                            let code = if self.ctx.mouse().scroll_px > 0 {
-                                self.ctx.mouse_mut().scroll_px -= height;
-                                64
-                            } else {
-                                self.ctx.mouse_mut().scroll_px += height;
-                                65
-                            };
-
-                            self.scroll_terminal(code, modifiers, 1)
-                        }
+                        let scroll_px = self.ctx.mouse().scroll_px + y as i32;
+                        self.ctx.mouse_mut().scroll_px =
+                            self.scroll_pixels(scroll_px, Axis::Vertical, modifiers);
+
+                        let scroll_px = self.ctx.mouse().horizontal_scroll_px + x as i32;
+                        self.ctx.mouse_mut().horizontal_scroll_px =
+                            self.scroll_pixels(scroll_px, Axis::Horizontal, modifiers);
                     },
                     _ => (),
                 }
@@ -603,31 +1066,80 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
-    fn scroll_terminal(&mut self, code: u8, modifiers: ModifiersState, scroll_multiplier: u8) {
-        debug_assert!(code == 64 || code == 65);
+    /// Scroll by one line or column per cell height or width of `pixels`
+    ///
+    /// This is used for both scrollback and faux scrolling. Returns the
+    /// pixels which didn't add up to a full cell.
+    fn scroll_pixels(&mut self, mut pixels: i32, axis: Axis, modifiers: ModifiersState) -> i32 {
+        let size_info = self.ctx.size_info();
+        let (step, forward, backward) = match axis {
+            Axis::Vertical => (size_info.cell_height as i32, 64, 65),
+            Axis::Horizontal => (size_info.cell_width as i32, 67, 66),
+        };
+
+        while pixels.abs() >= step {
+            let code = if pixels > 0 {
+                pixels -= step;
+                forward
+            } else {
+                pixels += step;
+                backward
+            };
 
-        let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+            self.scroll_terminal(code, modifiers, 1, 1)
+        }
 
+        pixels
+    }
+
+    /// Number of arrow keys sent per scrolled line in the alternate screen
+    fn faux_multiplier(&self) -> u8 {
         // Make sure the new and deprecated setting are both allowed
-        let faux_scrolling_lines = self.mouse_config
-            .faux_scrollback_lines
-            .unwrap_or(self.scrolling_config.faux_multiplier as usize);
+        match self.mouse_config.faux_scrollback_lines {
+            Some(lines) => min(lines, usize::from(::std::u8::MAX)) as u8,
+            None => self.scrolling_config.faux_multiplier,
+        }
+    }
+
+    fn scroll_terminal(
+        &mut self,
+        code: u8,
+        modifiers: ModifiersState,
+        scroll_multiplier: u8,
+        faux_multiplier: u8,
+    ) {
+        debug_assert!((64..=67).contains(&code));
+
+        let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
 
-        if self.ctx.terminal_mode().intersects(mouse_modes) {
+        if !modifiers.shift && self.ctx.terminal_mode().intersects(mouse_modes) {
+            // Buttons 4 to 7 are reported as 64 to 67
             self.mouse_report(code, ElementState::Pressed, modifiers);
         } else if self.ctx.terminal_mode().contains(TermMode::ALT_SCREEN)
-            && faux_scrolling_lines > 0 && !modifiers.shift
+            && self.faux_multiplier() > 0 && !modifiers.shift
         {
-            // Faux scrolling
-            let cmd = code + 1; // 64 + 1 = A, 65 + 1 = B
-            let mut content = Vec::with_capacity(faux_scrolling_lines as usize * 3);
-            for _ in 0..faux_scrolling_lines {
+            // Faux scrolling, encoded like the arrow keys in the current cursor mode
+            let cmd = match code {
+                64 => b'A',
+                65 => b'B',
+                66 => b'D',
+                _ => b'C',
+            };
+            let prefix = if self.ctx.terminal_mode().contains(TermMode::APP_CURSOR) {
+                b'O'
+            } else {
+                b'['
+            };
+
+            let mut content = Vec::with_capacity(faux_multiplier as usize * 3);
+            for _ in 0..faux_multiplier {
                 content.push(0x1b);
-                content.push(b'O');
+                content.push(prefix);
                 content.push(cmd);
             }
-            self.ctx.write_to_pty(content);
-        } else {
+            self.ctx.write_input(content);
+        } else if code <= 65 {
+            // The scrollback can't be scrolled horizontally
             for _ in 0..scroll_multiplier {
                 // Transform the reported button codes 64 and 65 into 1 and -1 lines to scroll
                 self.ctx.scroll(Scroll::Lines(-(code as isize * 2 - 129)));
@@ -635,6 +1147,215 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
+    /// Process a touch event of the finger `id` at `x`, `y`
+    pub fn on_touch(&mut self, id: u64, phase: TouchPhase, x: f64, y: f64) {
+        // Positions can lie outside of the window while dragging
+        let (x, y) = (x.max(0.), y.max(0.));
+
+        match phase {
+            TouchPhase::Started => self.on_touch_start(id, x, y),
+            TouchPhase::Moved => self.on_touch_motion(id, x, y),
+            TouchPhase::Ended => self.on_touch_end(id, true),
+            TouchPhase::Cancelled => self.on_touch_end(id, false),
+        }
+    }
+
+    fn on_touch_start(&mut self, id: u64, x: f64, y: f64) {
+        let touch = self.ctx.touch_mut();
+        touch.fingers.push(Finger { id, start_x: x, start_y: y });
+
+        if touch.fingers.len() == 1 {
+            // This also stops momentum scrolling
+            let now = Instant::now();
+            touch.gesture = Gesture::Tap;
+            touch.start = now;
+            touch.x = x;
+            touch.y = y;
+            touch.scroll_px = 0.;
+            touch.velocity = 0.;
+            touch.last_update = now;
+        } else if touch.gesture == Gesture::Tap && touch.fingers.len() == 2 {
+            touch.gesture = Gesture::TwoFingerTap;
+        } else if touch.gesture != Gesture::Scroll && touch.gesture != Gesture::Select {
+            // More fingers don't interrupt scrolling or selecting
+            touch.gesture = Gesture::Ignored;
+        }
+    }
+
+    fn on_touch_motion(&mut self, id: u64, x: f64, y: f64) {
+        let (primary, moved) = {
+            let touch = self.ctx.touch();
+            let finger = match touch.fingers.iter().find(|finger| finger.id == id) {
+                Some(finger) => finger,
+                None => return,
+            };
+
+            let distance = (x - finger.start_x).hypot(y - finger.start_y);
+            let moved = distance > f64::from(self.touch_config.drag_threshold);
+            (touch.fingers[0].id == id, moved)
+        };
+
+        // Only the first finger performs the gesture
+        let prev_y = self.ctx.touch().y;
+        if primary {
+            self.ctx.touch_mut().x = x;
+            self.ctx.touch_mut().y = y;
+        }
+
+        let gesture = self.ctx.touch().gesture;
+        match gesture {
+            Gesture::Tap if moved => {
+                if self.ctx.touch().start.elapsed() >= self.touch_config.long_press {
+                    self.start_touch_selection();
+                    self.update_touch_selection(x, y);
+                } else {
+                    self.ctx.touch_mut().gesture = Gesture::Scroll;
+                    self.ctx.touch_mut().last_update = Instant::now();
+                }
+            },
+            Gesture::TwoFingerTap if moved => {
+                self.ctx.touch_mut().gesture = Gesture::Ignored;
+            },
+            Gesture::Scroll if primary => {
+                let now = Instant::now();
+                {
+                    // Motion events don't arrive at a steady rate, so the
+                    // velocity is smoothed a bit
+                    let touch = self.ctx.touch_mut();
+                    let elapsed = now.duration_since(touch.last_update);
+                    let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+                    if elapsed > 0. {
+                        touch.velocity = (touch.velocity + (y - prev_y) / elapsed) / 2.;
+                    }
+                    touch.last_update = now;
+                }
+
+                self.touch_scroll(y - prev_y);
+            },
+            Gesture::Select if primary => self.update_touch_selection(x, y),
+            _ => (),
+        }
+    }
+
+    fn on_touch_end(&mut self, id: u64, lifted: bool) {
+        let primary = self.ctx.touch().fingers.first().map(|finger| finger.id) == Some(id);
+        self.ctx.touch_mut().fingers.retain(|finger| finger.id != id);
+        let fingers_left = !self.ctx.touch().fingers.is_empty();
+
+        let current = self.ctx.touch().gesture;
+        let gesture = match current {
+            // The gesture is done once the first finger is lifted, taps once all are
+            Gesture::TwoFingerTap if fingers_left => return,
+            Gesture::Scroll | Gesture::Select if !primary => return,
+            Gesture::TwoFingerTap => {
+                if lifted && self.ctx.touch().start.elapsed() < self.touch_config.long_press {
+                    Action::PasteSelection.execute(&mut self.ctx, false);
+                }
+                Gesture::None
+            },
+            Gesture::Select => {
+                if lifted {
+                    if self.save_to_clipboard {
+                        self.ctx.copy_selection(ClipboardBuffer::Primary);
+                    }
+                    self.ctx.copy_selection(ClipboardBuffer::Selection);
+                }
+                Gesture::None
+            },
+            Gesture::Scroll if lifted && self.has_momentum() => {
+                self.ctx.touch_mut().last_update = Instant::now();
+                Gesture::Momentum
+            },
+            _ => Gesture::None,
+        };
+
+        // Fingers which are still on the screen can't start a new gesture
+        self.ctx.touch_mut().gesture = if fingers_left { Gesture::Ignored } else { gesture };
+    }
+
+    /// Check if a lifted finger was swiped fast enough to keep scrolling
+    fn has_momentum(&self) -> bool {
+        let touch = self.ctx.touch();
+        let rest = Duration::from_millis(MOMENTUM_MAX_REST_MS);
+
+        self.touch_config.momentum
+            && touch.last_update.elapsed() < rest
+            && touch.velocity.abs() >= MOMENTUM_MIN_VELOCITY
+    }
+
+    /// Scroll the viewport by the pixels the finger moved
+    ///
+    /// Applications which report the mouse receive wheel events at the
+    /// position of the finger instead.
+    fn touch_scroll(&mut self, pixels: f64) {
+        let (x, y) = (self.ctx.touch().x, self.ctx.touch().y);
+        let point = self.ctx.size_info().pixels_to_coords(x as usize, y as usize);
+        let line = mem::replace(&mut self.ctx.mouse_mut().line, point.line);
+        let column = mem::replace(&mut self.ctx.mouse_mut().column, point.col);
+
+        let pixels = self.ctx.touch().scroll_px + pixels;
+        let modifiers = ModifiersState::default();
+        let remaining = self.scroll_pixels(pixels as i32, Axis::Vertical, modifiers);
+        self.ctx.touch_mut().scroll_px = f64::from(remaining) + pixels.fract();
+
+        self.ctx.mouse_mut().line = line;
+        self.ctx.mouse_mut().column = column;
+    }
+
+    /// Select the word under the resting finger
+    fn start_touch_selection(&mut self) {
+        let (x, y) = (self.ctx.touch().x, self.ctx.touch().y);
+        let point = self.ctx.size_info().pixels_to_coords(x as usize, y as usize);
+
+        self.ctx.clear_selection();
+        self.ctx.semantic_selection(point);
+        self.ctx.touch_mut().gesture = Gesture::Select;
+    }
+
+    fn update_touch_selection(&mut self, x: f64, y: f64) {
+        let point = self.ctx.size_info().pixels_to_coords(x as usize, y as usize);
+        let side = self.side_at(x as usize);
+        self.ctx.update_selection(point, side);
+    }
+
+    /// Continue touch gestures which don't wait for new events
+    ///
+    /// Returns the time until the gesture has to be updated again.
+    pub fn touch_tick(&mut self) -> Option<Duration> {
+        let gesture = self.ctx.touch().gesture;
+        match gesture {
+            Gesture::Tap => {
+                let elapsed = self.ctx.touch().start.elapsed();
+                if elapsed >= self.touch_config.long_press {
+                    self.start_touch_selection();
+                    None
+                } else {
+                    Some(self.touch_config.long_press - elapsed)
+                }
+            },
+            Gesture::Momentum => {
+                let now = Instant::now();
+                let pixels = {
+                    let touch = self.ctx.touch_mut();
+                    let elapsed = now.duration_since(touch.last_update);
+                    let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+                    touch.last_update = now;
+                    touch.velocity *= 0.5f64.powf(elapsed / MOMENTUM_HALF_LIFE);
+                    touch.velocity * elapsed
+                };
+
+                if self.ctx.touch().velocity.abs() < MOMENTUM_MIN_VELOCITY {
+                    self.ctx.touch_mut().gesture = Gesture::None;
+                    return None;
+                }
+
+                self.touch_scroll(pixels);
+                Some(Duration::from_millis(MOMENTUM_INTERVAL_MS))
+            },
+            _ => None,
+        }
+    }
+
     pub fn on_focus_change(&mut self, is_focused: bool) {
         if self.ctx.terminal_mode().contains(TermMode::FOCUS_IN_OUT) {
             let chr = if is_focused {
@@ -644,10 +1365,27 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
             };
 
             let msg = format!("\x1b[{}", chr);
-            self.ctx.write_to_pty(msg.into_bytes());
+            self.ctx.write_input(msg.into_bytes());
         }
     }
 
+    /// Paste the paths of files dropped onto the window
+    ///
+    /// Every path is escaped for the shell, multiple paths are separated by
+    /// spaces.
+    pub fn paste_dropped_files(&mut self, paths: &[PathBuf]) {
+        if !self.mouse_config.paste_dropped_files || paths.is_empty() {
+            return;
+        }
+
+        let paths = paths.iter()
+            .map(|path| shell_escape(&path.to_string_lossy()).into_owned())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Action::Paste.paste(&mut self.ctx, &paths);
+    }
+
     pub fn mouse_input(&mut self, state: ElementState, button: MouseButton, modifiers: ModifiersState) {
         let prev_state = match button {
             MouseButton::Left     => Some(mem::replace(&mut self.ctx.mouse_mut().left_button_state, state)),
@@ -663,6 +1401,7 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
                     ElementState::Pressed  => self.on_mouse_press(button, modifiers),
                     ElementState::Released => self.on_mouse_release(button, modifiers),
                 };
+                self.update_url_highlight(modifiers);
             }
         }
 
@@ -677,13 +1416,27 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     ///
     /// If a keybinding was run, returns true. Otherwise returns false.
     pub fn process_key(&mut self, input: KeyboardInput) {
+        self.update_alt_keys(input);
+
         match input.state {
             ElementState::Pressed => {
                 *self.ctx.last_modifiers() = input.modifiers;
                 *self.ctx.received_count() = 0;
                 *self.ctx.suppress_chars() = false;
 
-                if self.process_key_bindings(input) {
+                // Escape only cancels a paste which is still being written
+                let escape = input.virtual_keycode == Some(VirtualKeyCode::Escape);
+                if escape && self.ctx.cancel_paste() {
+                    *self.ctx.suppress_chars() = true;
+                    return;
+                }
+
+                if self.process_numpad_bindings(input)
+                    || self.process_app_keypad(input)
+                    || self.process_key_bindings(input)
+                    || self.process_special_key(input)
+                    || self.process_option_as_alt(input)
+                {
                     *self.ctx.suppress_chars() = true;
                 }
             },
@@ -691,6 +1444,145 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
+    /// Keep track of which of the Alt keys are held down
+    fn update_alt_keys(&mut self, input: KeyboardInput) {
+        let pressed = input.state == ElementState::Pressed;
+        let alt_keys = self.ctx.alt_keys();
+        match input.virtual_keycode {
+            Some(VirtualKeyCode::LAlt) => alt_keys.left = pressed,
+            Some(VirtualKeyCode::RAlt) => alt_keys.right = pressed,
+            // Releases can be missed while the window is unfocused
+            _ if !input.modifiers.alt => *alt_keys = AltKeys::default(),
+            _ => (),
+        }
+    }
+
+    /// Whether Alt should act as Meta for the characters which are typed
+    ///
+    /// On macOS the Option keys compose characters unless they are listed in
+    /// `window.option_as_alt`.
+    fn alt_is_meta(&mut self) -> bool {
+        if !cfg!(target_os = "macos") {
+            return true;
+        }
+
+        let alt_keys = *self.ctx.alt_keys();
+        match self.option_as_alt {
+            OptionAsAlt::None => false,
+            OptionAsAlt::OnlyLeft => alt_keys.left,
+            OptionAsAlt::OnlyRight => alt_keys.right,
+            OptionAsAlt::Both => true,
+        }
+    }
+
+    /// Send the character without Option applied when Option acts as Alt
+    ///
+    /// The character received from macOS already has the composition of the
+    /// Option key applied, so the unmodified character is looked up in the
+    /// current keyboard layout. The US layout is only used when that fails.
+    ///
+    /// Returns true if the key was handled.
+    fn process_option_as_alt(&mut self, input: KeyboardInput) -> bool {
+        if !cfg!(target_os = "macos") || !input.modifiers.alt || !self.alt_is_meta() {
+            return false;
+        }
+
+        let shift = input.modifiers.shift;
+        let c = keyboard_layout::unmodified_char(input.scancode, shift)
+            .or_else(|| input.virtual_keycode.and_then(|key| base_char(key, shift)));
+        let c = match c {
+            Some(c) => c,
+            None => return false,
+        };
+
+        self.ctx.scroll(Scroll::Bottom);
+        self.ctx.clear_selection();
+
+        let mut bytes = Vec::with_capacity(5);
+        if self.alt_send_esc {
+            bytes.push(b'\x1b');
+        }
+        let mut buf = [0; 4];
+        bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        self.ctx.write_input(bytes);
+
+        true
+    }
+
+    /// Execute the bindings of a numpad key
+    ///
+    /// Numpad keys are matched before the key produced by the layout, so they
+    /// can be bound separately from the main keyboard regardless of NumLock.
+    ///
+    /// Returns true if an action is executed.
+    fn process_numpad_bindings(&mut self, input: KeyboardInput) -> bool {
+        match Key::from_numpad_input(input) {
+            Some(key) => self.process_bindings_for_key(input.modifiers, &key),
+            None => false,
+        }
+    }
+
+    /// Send the application keypad sequence of a numpad key
+    ///
+    /// With NumLock enabled the numpad digits still type digits.
+    ///
+    /// Returns true if the key was handled.
+    fn process_app_keypad(&mut self, input: KeyboardInput) -> bool {
+        let mods = input.modifiers;
+        if !self.ctx.terminal_mode().contains(TermMode::APP_KEYPAD)
+            || mods.ctrl || mods.alt || mods.logo
+        {
+            return false;
+        }
+
+        let key = match Key::from_numpad_input(input) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        let c = match app_keypad_char(key) {
+            Some(c) => c,
+            None => return false,
+        };
+
+        // The layout only reports the numpad digits as digits with NumLock
+        // enabled, macOS has no NumLock and always reports them as digits
+        let numlock = !cfg!(target_os = "macos")
+            && input.virtual_keycode.map(Key::from_glutin_input) == Some(key);
+        match key {
+            Key::Add | Key::Subtract | Key::Multiply | Key::Divide | Key::NumpadEnter
+            | Key::NumpadComma | Key::NumpadEquals => (),
+            _ if numlock => return false,
+            _ => (),
+        }
+
+        self.ctx.scroll(Scroll::Bottom);
+        self.ctx.clear_selection();
+        self.ctx.write_input(vec![b'\x1b', b'O', c]);
+
+        true
+    }
+
+    /// Send the sequence of a cursor, editing or function key
+    ///
+    /// Key bindings take precedence, so these sequences can still be changed.
+    ///
+    /// Returns true if the key was handled.
+    fn process_special_key(&mut self, input: KeyboardInput) -> bool {
+        let mode = self.ctx.terminal_mode();
+        let bytes = match input.virtual_keycode
+            .and_then(|key| special_key_sequence(key, input.modifiers, mode))
+        {
+            Some(bytes) => bytes,
+            None => return false,
+        };
+
+        self.ctx.scroll(Scroll::Bottom);
+        self.ctx.write_input(bytes);
+
+        true
+    }
+
     /// Process a received character
     pub fn received_char(&mut self, c: char) {
         if !*self.ctx.suppress_chars() {
@@ -698,8 +1590,13 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
             self.ctx.clear_selection();
 
             let utf8_len = c.len_utf8();
-            if *self.ctx.received_count() == 0 && self.ctx.last_modifiers().alt && utf8_len == 1 {
-                self.ctx.write_to_pty(b"\x1b".to_vec());
+            if *self.ctx.received_count() == 0
+                && self.ctx.last_modifiers().alt
+                && self.alt_send_esc
+                && utf8_len == 1
+                && self.alt_is_meta()
+            {
+                self.ctx.write_input(b"\x1b".to_vec());
             }
 
             let mut bytes = Vec::with_capacity(utf8_len);
@@ -708,7 +1605,7 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
                 c.encode_utf8(&mut bytes[..]);
             }
 
-            self.ctx.write_to_pty(bytes);
+            self.ctx.write_input(bytes);
 
             *self.ctx.received_count() += 1;
         }
@@ -719,26 +1616,41 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     /// The provided mode, mods, and key must match what is allowed by a binding
     /// for its action to be executed.
     ///
+    /// Bindings are matched against the key produced by the active layout
+    /// first. If none of them match, the key at the same physical position on
+    /// a US layout is tried, followed by the raw scancode. This keeps bindings
+    /// like `Control|Shift + C` working with non-latin layouts.
+    ///
     /// Returns true if an action is executed.
     fn process_key_bindings(&mut self, input: KeyboardInput) -> bool {
+        let layout_key = input.virtual_keycode.map(Key::from_glutin_input);
+        if let Some(key) = layout_key {
+            if self.process_bindings_for_key(input.modifiers, &key) {
+                return true;
+            }
+        }
+
+        // Shift and plain key presses are left alone, so dead keys keep composing
+        let mods = input.modifiers;
+        if mods.ctrl || mods.alt || mods.logo {
+            let us_key = Key::from_scancode(input.scancode).filter(|key| Some(*key) != layout_key);
+            if let Some(key) = us_key {
+                if self.process_bindings_for_key(mods, &key) {
+                    return true;
+                }
+            }
+        }
+
+        self.process_bindings_for_key(mods, &Key::Scancode(input.scancode))
+    }
+
+    /// Execute all key bindings triggered by a key
+    ///
+    /// Returns true if an action is executed.
+    fn process_bindings_for_key(&mut self, mods: ModifiersState, key: &Key) -> bool {
         let mut has_binding = false;
         for binding in self.key_bindings {
-            let is_triggered = match binding.trigger {
-                Key::Scancode(_) => binding.is_triggered_by(
-                    self.ctx.terminal_mode(),
-                    input.modifiers,
-                    &Key::Scancode(input.scancode),
-                    false,
-                ),
-                _ => if let Some(key) = input.virtual_keycode {
-                    let key = Key::from_glutin_input(key);
-                    binding.is_triggered_by(self.ctx.terminal_mode(), input.modifiers, &key, false)
-                } else {
-                    false
-                },
-            };
-
-            if is_triggered {
+            if binding.is_triggered_by(self.ctx.terminal_mode(), mods, key, false) {
                 // binding was triggered; run the action
                 binding.execute(&mut self.ctx, false);
                 has_binding = true;
@@ -773,21 +1685,99 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     }
 }
 
+/// Final character of the `SS3` sequence sent by a numpad key in application
+/// keypad mode
+fn app_keypad_char(key: Key) -> Option<u8> {
+    let c = match key {
+        Key::Numpad0 => b'p', Key::Numpad1 => b'q', Key::Numpad2 => b'r',
+        Key::Numpad3 => b's', Key::Numpad4 => b't', Key::Numpad5 => b'u',
+        Key::Numpad6 => b'v', Key::Numpad7 => b'w', Key::Numpad8 => b'x',
+        Key::Numpad9 => b'y', Key::Decimal => b'n', Key::NumpadEnter => b'M',
+        Key::Add => b'k', Key::Subtract => b'm', Key::Multiply => b'j',
+        Key::Divide => b'o', Key::NumpadComma => b'l', Key::NumpadEquals => b'X',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Sequence sent by a cursor, editing or function key, as xterm encodes it
+///
+/// Modifiers are sent as the parameter `1 + bitmask`, with Shift as 1, Alt as
+/// 2, Control as 4 and Command/Super as 8. Without modifiers, F1 to F4 use
+/// `SS3`, as do the cursor keys, Home and End in application cursor mode.
+fn special_key_sequence(key: VirtualKeyCode, mods: ModifiersState, mode: TermMode) -> Option<Vec<u8>> {
+    use glutin::VirtualKeyCode::*;
+
+    let (number, end) = match key {
+        Up => (1, 'A'), Down => (1, 'B'), Right => (1, 'C'), Left => (1, 'D'),
+        End => (1, 'F'), Home => (1, 'H'),
+        F1 => (1, 'P'), F2 => (1, 'Q'), F3 => (1, 'R'), F4 => (1, 'S'),
+        Insert => (2, '~'), Delete => (3, '~'), PageUp => (5, '~'), PageDown => (6, '~'),
+        F5 => (15, '~'), F6 => (17, '~'), F7 => (18, '~'), F8 => (19, '~'),
+        F9 => (20, '~'), F10 => (21, '~'), F11 => (23, '~'), F12 => (24, '~'),
+        _ => return None,
+    };
+
+    let modifier = 1
+        + mods.shift as u8
+        + mods.alt as u8 * 2
+        + mods.ctrl as u8 * 4
+        + mods.logo as u8 * 8;
+
+    let sequence = match end {
+        '~' if modifier == 1 => format!("\x1b[{}~", number),
+        _ if modifier > 1 => format!("\x1b[{};{}{}", number, modifier, end),
+        'P'..='S' => format!("\x1bO{}", end),
+        _ if mode.contains(TermMode::APP_CURSOR) => format!("\x1bO{}", end),
+        _ => format!("\x1b[{}", end),
+    };
+
+    Some(sequence.into_bytes())
+}
+
+/// Character produced by a key of the US layout without Option applied
+fn base_char(key: VirtualKeyCode, shift: bool) -> Option<char> {
+    use glutin::VirtualKeyCode::*;
+
+    let (c, shifted) = match key {
+        A => ('a', 'A'), B => ('b', 'B'), C => ('c', 'C'), D => ('d', 'D'),
+        E => ('e', 'E'), F => ('f', 'F'), G => ('g', 'G'), H => ('h', 'H'),
+        I => ('i', 'I'), J => ('j', 'J'), K => ('k', 'K'), L => ('l', 'L'),
+        M => ('m', 'M'), N => ('n', 'N'), O => ('o', 'O'), P => ('p', 'P'),
+        Q => ('q', 'Q'), R => ('r', 'R'), S => ('s', 'S'), T => ('t', 'T'),
+        U => ('u', 'U'), V => ('v', 'V'), W => ('w', 'W'), X => ('x', 'X'),
+        Y => ('y', 'Y'), Z => ('z', 'Z'),
+        Key1 => ('1', '!'), Key2 => ('2', '@'), Key3 => ('3', '#'), Key4 => ('4', '$'),
+        Key5 => ('5', '%'), Key6 => ('6', '^'), Key7 => ('7', '&'), Key8 => ('8', '*'),
+        Key9 => ('9', '('), Key0 => ('0', ')'),
+        Minus => ('-', '_'), Equals => ('=', '+'), LBracket => ('[', '{'),
+        RBracket => (']', '}'), Backslash => ('\\', '|'), Semicolon => (';', ':'),
+        Apostrophe => ('\'', '"'), Grave => ('`', '~'), Comma => (',', '<'),
+        Period => ('.', '>'), Slash => ('/', '?'),
+        _ => return None,
+    };
+
+    Some(if shift { shifted } else { c })
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
     use std::time::Duration;
 
     use glutin::{VirtualKeyCode, Event, WindowEvent, ElementState, MouseButton, ModifiersState};
+    use glutin::{MouseScrollDelta, TouchPhase};
 
+    use ansi::{self, Handler};
     use term::{SizeInfo, Term, TermMode};
-    use event::{Mouse, ClickState, WindowChanges};
-    use config::{self, Config, ClickHandler};
-    use index::{Point, Side};
+    use input::{Mouse, ClickState, Gesture, TouchState, WindowChanges};
+    use config::{self, Config, ClickHandler, OptionAsAlt};
+    use index::{Column, Line, Point, Side};
     use selection::Selection;
     use grid::Scroll;
+    use paste::{Paste, CHUNK_SIZE};
 
-    use super::{Action, Binding, Processor};
+    use super::{Action, AltKeys, Binding, Processor};
     use copypasta::Buffer as ClipboardBuffer;
 
     const KEY: VirtualKeyCode = VirtualKeyCode::Key0;
@@ -799,21 +1789,61 @@ mod tests {
         None,
     }
 
-    struct ActionContext<'a> {
-        pub terminal: &'a mut Term,
-        pub selection: &'a mut Option<Selection>,
-        pub size_info: &'a SizeInfo,
-        pub mouse: &'a mut Mouse,
+    struct ActionContext {
+        pub terminal: Term,
+        pub selection: Option<Selection>,
+        pub size_info: SizeInfo,
+        pub mouse: Mouse,
+        pub touch: TouchState,
         pub last_action: MultiClick,
         pub received_count: usize,
         pub suppress_chars: bool,
         pub last_modifiers: ModifiersState,
-        pub window_changes: &'a mut WindowChanges,
+        pub alt_keys: AltKeys,
+        pub window_changes: WindowChanges,
+        pub written: Vec<u8>,
+        pub paste: Option<Paste>,
     }
 
-    impl <'a>super::ActionContext for ActionContext<'a> {
-        fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, _val: B) {
-            // STUBBED
+    impl ActionContext {
+        fn new(terminal: Term) -> ActionContext {
+            ActionContext {
+                size_info: *terminal.size_info(),
+                terminal,
+                selection: None,
+                mouse: Mouse::default(),
+                touch: TouchState::default(),
+                last_action: MultiClick::None,
+                received_count: 0,
+                suppress_chars: false,
+                last_modifiers: ModifiersState::default(),
+                alt_keys: AltKeys::default(),
+                window_changes: WindowChanges::default(),
+                written: Vec::new(),
+                paste: None,
+            }
+        }
+    }
+
+    impl super::ActionContext for ActionContext {
+        fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, val: B) {
+            self.written.extend_from_slice(&val.into());
+        }
+
+        fn start_paste(&mut self, paste: Paste) {
+            self.paste = Some(paste);
+        }
+
+        fn cancel_paste(&mut self) -> bool {
+            match self.paste.take() {
+                Some(mut paste) => {
+                    if let Some(end) = paste.abort() {
+                        self.written.extend_from_slice(end);
+                    }
+                    true
+                },
+                None => false,
+            }
         }
 
         fn terminal_mode(&self) -> TermMode {
@@ -821,13 +1851,17 @@ mod tests {
         }
 
         fn size_info(&self) -> SizeInfo {
-            *self.size_info
+            self.size_info
         }
 
         fn copy_selection(&self, _buffer: ClipboardBuffer) {
             // STUBBED
         }
 
+        fn copy_selection_as_html(&self) {
+            // STUBBED
+        }
+
         fn clear_selection(&mut self) {}
         fn update_selection(&mut self, _point: Point, _side: Side) {}
         fn simple_selection(&mut self, _point: Point, _side: Side) {}
@@ -855,18 +1889,31 @@ mod tests {
 
         #[inline]
         fn mouse_mut(&mut self) -> &mut Mouse {
-            self.mouse
+            &mut self.mouse
+        }
+
+        #[inline]
+        fn touch_mut(&mut self) -> &mut TouchState {
+            &mut self.touch
+        }
+
+        #[inline]
+        fn touch(&self) -> &TouchState {
+            &self.touch
         }
 
         #[inline]
         fn mouse(&self) -> &Mouse {
-            self.mouse
+            &self.mouse
         }
 
         fn url(&self, _: Point<usize>) -> Option<String> {
             None
         }
 
+        fn highlight_url(&mut self, _: Option<Point<usize>>) {
+        }
+
         fn received_count(&mut self) -> &mut usize {
             &mut self.received_count
         }
@@ -876,6 +1923,9 @@ mod tests {
         fn last_modifiers(&mut self) -> &mut ModifiersState {
             &mut self.last_modifiers
         }
+        fn alt_keys(&mut self) -> &mut AltKeys {
+            &mut self.alt_keys
+        }
         fn change_font_size(&mut self, _delta: f32) {
         }
         fn reset_font_size(&mut self) {
@@ -884,6 +1934,68 @@ mod tests {
         }
         fn hide_window(&mut self) {
         }
+
+        fn toggle_fullscreen(&mut self) {
+        }
+
+        fn toggle_simple_fullscreen(&mut self) {
+        }
+
+        fn toggle_secure_input(&mut self) {
+        }
+
+        fn dump_frame_stats(&mut self) {
+        }
+
+        fn dump_screen(&mut self) {
+        }
+
+        fn dump_state(&mut self) {
+        }
+
+        fn simulate_context_loss(&mut self) {
+        }
+
+        fn screenshot(&mut self) {
+        }
+    }
+
+    /// Configs borrowed by the processor of a test
+    #[derive(Default)]
+    struct Configs {
+        mouse: config::Mouse,
+        scrolling: config::Scrolling,
+        touch: config::Touch,
+    }
+
+    impl Configs {
+        /// Processor without any bindings
+        fn processor(&self, ctx: ActionContext) -> Processor<ActionContext> {
+            Processor {
+                ctx,
+                mouse_config: &self.mouse,
+                scrolling_config: &self.scrolling,
+                touch_config: &self.touch,
+                key_bindings: &[],
+                mouse_bindings: &[],
+                save_to_clipboard: false,
+                alt_send_esc: true,
+                option_as_alt: OptionAsAlt::None,
+            }
+        }
+    }
+
+    /// Terminal with 7 columns and 17 lines of 3x3 pixel cells
+    fn terminal() -> Term {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        Term::new(&Config::default(), size)
     }
 
     macro_rules! test_clickstate {
@@ -897,33 +2009,9 @@ mod tests {
             #[test]
             fn $name() {
                 let config = Config::default();
-                let size = SizeInfo {
-                    width: 21.0,
-                    height: 51.0,
-                    cell_width: 3.0,
-                    cell_height: 3.0,
-                    padding_x: 0.0,
-                    padding_y: 0.0,
-                };
 
-                let mut terminal = Term::new(&config, size);
-
-                let mut mouse = Mouse::default();
-                mouse.click_state = $initial_state;
-
-                let mut selection = None;
-
-                let context = ActionContext {
-                    terminal: &mut terminal,
-                    selection: &mut selection,
-                    mouse: &mut mouse,
-                    size_info: &size,
-                    last_action: MultiClick::None,
-                    received_count: 0,
-                    suppress_chars: false,
-                    last_modifiers: ModifiersState::default(),
-                    window_changes: &mut WindowChanges::default(),
-                };
+                let mut context = ActionContext::new(terminal());
+                context.mouse.click_state = $initial_state;
 
                 let mut processor = Processor {
                     ctx: context,
@@ -934,21 +2022,27 @@ mod tests {
                         triple_click: ClickHandler {
                             threshold: Duration::from_millis(1000),
                         },
+                        drag_threshold: 3,
                         hide_when_typing: false,
                         faux_scrollback_lines: None,
                         url: Default::default(),
+                        paste_dropped_files: true,
+                        scroll_to_bottom_on_click: false,
                     },
                     scrolling_config: &config::Scrolling::default(),
+                    touch_config: &config::Touch::default(),
                     key_bindings: &config.key_bindings()[..],
                     mouse_bindings: &config.mouse_bindings()[..],
                     save_to_clipboard: config.selection().save_to_clipboard,
+                    alt_send_esc: config.alt_send_esc(),
+                    option_as_alt: config.window().option_as_alt(),
                 };
 
                 if let Event::WindowEvent { event: WindowEvent::MouseInput { state, button, modifiers, .. }, .. } = $input {
                     processor.mouse_input(state, button, modifiers);
                 };
 
-                assert!(match mouse.click_state {
+                assert!(match processor.ctx.mouse.click_state {
                     $end_state => processor.ctx.last_action == $last_action,
                     _ => false
                 });
@@ -975,6 +2069,577 @@ mod tests {
         }
     }
 
+    fn faux_scroll(alt_screen: bool, app_cursor: bool, delta: MouseScrollDelta) -> Vec<u8> {
+        let mut terminal = terminal();
+        if alt_screen {
+            terminal.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        }
+        if app_cursor {
+            terminal.set_mode(ansi::Mode::CursorKeys);
+        }
+
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal));
+
+        processor.on_mouse_wheel(delta, TouchPhase::Started, ModifiersState::default());
+        processor.on_mouse_wheel(delta, TouchPhase::Moved, ModifiersState::default());
+        processor.ctx.written
+    }
+
+    #[test]
+    fn faux_scrolling_respects_cursor_mode() {
+        let notch = MouseScrollDelta::LineDelta(0., 1.);
+
+        assert_eq!(faux_scroll(true, false, notch), b"\x1b[A\x1b[A\x1b[A\x1b[A\x1b[A\x1b[A".to_vec());
+        assert_eq!(faux_scroll(true, true, notch), b"\x1bOA\x1bOA\x1bOA\x1bOA\x1bOA\x1bOA".to_vec());
+
+        // Nothing is sent outside of the alternate screen
+        assert!(faux_scroll(false, false, notch).is_empty());
+    }
+
+    #[test]
+    fn faux_scrolling_with_pixel_delta() {
+        // One line per cell height
+        let swipe = MouseScrollDelta::PixelDelta(0., -7.);
+        assert_eq!(faux_scroll(true, false, swipe), b"\x1b[B\x1b[B".to_vec());
+    }
+
+    #[test]
+    fn faux_scrolling_horizontally() {
+        let tilt = MouseScrollDelta::LineDelta(1., 0.);
+        assert_eq!(faux_scroll(true, false, tilt), b"\x1b[C\x1b[C\x1b[C\x1b[C\x1b[C\x1b[C".to_vec());
+
+        // One column per cell width
+        let swipe = MouseScrollDelta::PixelDelta(-7., 0.);
+        assert_eq!(faux_scroll(true, true, swipe), b"\x1bOD\x1bOD".to_vec());
+
+        // The scrollback can't be scrolled horizontally
+        assert!(faux_scroll(false, false, tilt).is_empty());
+    }
+
+    #[test]
+    fn selection_scrolls_past_grid() {
+        let mut context = ActionContext::new(terminal());
+        context.mouse.left_button_state = ElementState::Pressed;
+        let configs = Configs::default();
+        let mut processor = configs.processor(context);
+
+        // Rate depends on the distance to the grid
+        processor.update_selection_scroll(-4);
+        assert_eq!(processor.ctx.mouse.selection_scroll, 2);
+        processor.update_selection_scroll(51);
+        assert_eq!(processor.ctx.mouse.selection_scroll, -1);
+
+        // Only one scroll per interval
+        assert!(processor.selection_scroll_tick().is_some());
+        assert!(processor.ctx.mouse.last_selection_scroll.is_some());
+        assert!(processor.selection_scroll_tick().unwrap() <= Duration::from_millis(50));
+
+        // Back inside of the grid
+        processor.update_selection_scroll(20);
+        assert!(processor.selection_scroll_tick().is_none());
+
+        // Releasing the button stops scrolling
+        processor.update_selection_scroll(-1);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, ModifiersState::default());
+        assert!(processor.selection_scroll_tick().is_none());
+    }
+
+    #[test]
+    fn small_movements_do_not_start_a_selection() {
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal()));
+
+        let none = ModifiersState::default();
+        processor.mouse_moved(4, 4, none);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+
+        // The pointer moved into the next cell, but not far enough
+        processor.mouse_moved(6, 6, none);
+        assert!(!processor.ctx.mouse.dragging);
+
+        processor.mouse_moved(9, 4, none);
+        assert!(processor.ctx.mouse.dragging);
+
+        // Every press starts over
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        assert!(!processor.ctx.mouse.dragging);
+    }
+
+    #[test]
+    fn shift_bypasses_mouse_reporting() {
+        let mut terminal = terminal();
+        terminal.set_mode(ansi::Mode::ReportCellMouseMotion);
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal));
+
+        let shift = ModifiersState { shift: true, ctrl: false, alt: false, logo: false };
+        let none = ModifiersState::default();
+
+        // Releasing Shift mid-drag doesn't report the drag or the release
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, shift);
+        processor.mouse_moved(10, 10, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        assert!(processor.ctx.written.is_empty());
+
+        // Shift clicks count towards multi-clicks
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, shift);
+        assert!(match processor.ctx.mouse.click_state {
+            ClickState::DoubleClick => true,
+            _ => false,
+        });
+        processor.mouse_input(ElementState::Released, MouseButton::Left, shift);
+
+        // Pressing Shift mid-drag still reports the drag and the release
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        processor.mouse_moved(1, 1, shift);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, shift);
+        assert_eq!(processor.ctx.written, b"\x1b[M $$\x1b[MD!!\x1b[M'!!".to_vec());
+
+        // Horizontal scrolling is reported as buttons 6 and 7
+        processor.ctx.written.clear();
+        processor.on_mouse_wheel(MouseScrollDelta::LineDelta(-1., 0.), TouchPhase::Moved, none);
+        processor.on_mouse_wheel(MouseScrollDelta::LineDelta(1., 0.), TouchPhase::Moved, none);
+        assert_eq!(processor.ctx.written, b"\x1b[Mb!!\x1b[Mc!!".to_vec());
+    }
+
+    fn click_with_encoding(set: &[ansi::Mode], unset: &[ansi::Mode]) -> Vec<u8> {
+        let size = SizeInfo { width: 1200.0, ..*terminal().size_info() };
+        let mut terminal = Term::new(&Config::default(), size);
+        terminal.set_mode(ansi::Mode::ReportMouseClicks);
+        for &mode in set {
+            terminal.set_mode(mode);
+        }
+        for &mode in unset {
+            terminal.unset_mode(mode);
+        }
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal));
+
+        // Column 301 and line 3 of a 400 column window
+        let none = ModifiersState::default();
+        processor.mouse_moved(901, 7, none);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        processor.ctx.written
+    }
+
+    /// Display offset of a terminal scrolled to the top of its history, after
+    /// moving the pointer and after clicking
+    fn click_while_scrolled(scroll_to_bottom_on_click: bool) -> (usize, usize) {
+        let mut terminal = terminal();
+        let mut parser = ansi::Processor::new();
+        for byte in (0..20).flat_map(|_| b"a\r\n".iter()) {
+            parser.advance(&mut terminal, *byte, &mut ::std::io::sink());
+        }
+        terminal.scroll_display(Scroll::Top);
+        assert_eq!(terminal.grid().display_offset(), 4);
+
+        let mut configs = Configs::default();
+        configs.mouse.scroll_to_bottom_on_click = scroll_to_bottom_on_click;
+        let mut processor = configs.processor(ActionContext::new(terminal));
+
+        let none = ModifiersState::default();
+        processor.mouse_moved(10, 10, none);
+        processor.mouse_moved(12, 30, none);
+        let moved = processor.ctx.terminal.grid().display_offset();
+
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        (moved, processor.ctx.terminal.grid().display_offset())
+    }
+
+    #[test]
+    fn clicks_can_scroll_to_the_bottom() {
+        assert_eq!(click_while_scrolled(false), (4, 4));
+        assert_eq!(click_while_scrolled(true), (4, 0));
+    }
+
+    #[test]
+    fn mouse_report_encodings() {
+        use ansi::Mode::{SgrMouse, Utf8Mouse, UrxvtMouse};
+
+        // The X10 bytes saturate at column 223
+        assert_eq!(click_with_encoding(&[], &[]), b"\x1b[M \xff#\x1b[M#\xff#".to_vec());
+
+        let utf8 = click_with_encoding(&[Utf8Mouse], &[]);
+        assert_eq!(utf8, b"\x1b[M \xc5\x8d#\x1b[M#\xc5\x8d#".to_vec());
+        let coords: Vec<u32> = ::std::str::from_utf8(&utf8[4..7]).unwrap()
+            .chars()
+            .map(|c| c as u32 - 32)
+            .collect();
+        assert_eq!(coords, vec![301, 3]);
+
+        let urxvt = b"\x1b[32;301;3M\x1b[35;301;3M".to_vec();
+        assert_eq!(click_with_encoding(&[UrxvtMouse], &[]), urxvt);
+
+        let sgr = b"\x1b[<0;301;3M\x1b[<0;301;3m".to_vec();
+        assert_eq!(click_with_encoding(&[SgrMouse], &[]), sgr);
+
+        // The encoding set last is used
+        assert_eq!(click_with_encoding(&[SgrMouse, UrxvtMouse], &[]), urxvt);
+        assert_eq!(click_with_encoding(&[UrxvtMouse, SgrMouse], &[]), sgr);
+        assert_eq!(click_with_encoding(&[UrxvtMouse, Utf8Mouse], &[]), utf8);
+
+        // Resetting it falls back to X10, resetting another one does nothing
+        let x10 = click_with_encoding(&[], &[]);
+        assert_eq!(click_with_encoding(&[SgrMouse, UrxvtMouse], &[UrxvtMouse]), x10);
+        assert_eq!(click_with_encoding(&[SgrMouse], &[Utf8Mouse]), sgr);
+    }
+
+    #[test]
+    fn motion_is_reported_once_per_cell() {
+        let mut terminal = terminal();
+        terminal.set_mode(ansi::Mode::ReportMouseClicks);
+        terminal.set_mode(ansi::Mode::ReportCellMouseMotion);
+        terminal.set_mode(ansi::Mode::ReportAllMouseMotion);
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal));
+
+        let none = ModifiersState::default();
+
+        // Any-event tracking reports motion without buttons
+        processor.mouse_moved(1, 1, none);
+        processor.mouse_moved(2, 2, none);
+        processor.mouse_moved(4, 1, none);
+        assert_eq!(processor.ctx.written, b"\x1b[MC!!\x1b[MC\"!".to_vec());
+
+        // It takes precedence over button-event tracking
+        processor.ctx.written.clear();
+        processor.ctx.terminal.unset_mode(ansi::Mode::ReportCellMouseMotion);
+        processor.mouse_moved(1, 4, none);
+        assert_eq!(processor.ctx.written, b"\x1b[MC!\"".to_vec());
+
+        // Button-event tracking only reports motion while a button is held
+        processor.ctx.written.clear();
+        processor.ctx.terminal.set_mode(ansi::Mode::ReportCellMouseMotion);
+        processor.ctx.terminal.unset_mode(ansi::Mode::ReportAllMouseMotion);
+        processor.mouse_moved(4, 4, none);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        processor.mouse_moved(5, 5, none);
+        processor.mouse_moved(7, 4, none);
+        assert_eq!(processor.ctx.written, b"\x1b[M \"\"\x1b[M@#\"".to_vec());
+
+        // Click tracking doesn't report motion at all
+        processor.ctx.written.clear();
+        processor.ctx.terminal.unset_mode(ansi::Mode::ReportCellMouseMotion);
+        processor.mouse_moved(10, 4, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        assert_eq!(processor.ctx.written, b"\x1b[M#$\"".to_vec());
+    }
+
+    #[test]
+    fn touch_gestures() {
+        let mut terminal = terminal();
+        terminal.set_mode(ansi::Mode::ReportCellMouseMotion);
+        let mut configs = Configs::default();
+        configs.touch.momentum = false;
+        let touch_config = configs.touch;
+        let mut processor = configs.processor(ActionContext::new(terminal));
+
+        // Dragging is reported as mouse wheel at the finger's position
+        processor.on_touch(0, TouchPhase::Started, 10., 10.);
+        processor.on_touch(0, TouchPhase::Moved, 10., 22.);
+        assert_eq!(processor.ctx.touch.gesture, Gesture::Scroll);
+        processor.on_touch(0, TouchPhase::Moved, 10., 29.);
+        processor.on_touch(0, TouchPhase::Ended, 10., 29.);
+        assert_eq!(processor.ctx.written, b"\x1b[M`$*\x1b[M`$*".to_vec());
+        assert_eq!(processor.ctx.touch.gesture, Gesture::None);
+
+        // The mouse position is left alone
+        assert_eq!(processor.ctx.mouse.line, Line(0));
+        assert_eq!(processor.ctx.mouse.column, Column(0));
+
+        // Resting a finger selects the word underneath
+        processor.on_touch(1, TouchPhase::Started, 4., 4.);
+        assert!(processor.touch_tick().is_some());
+        processor.ctx.touch.start -= touch_config.long_press;
+        assert!(processor.touch_tick().is_none());
+        assert_eq!(processor.ctx.touch.gesture, Gesture::Select);
+        assert!(processor.ctx.last_action == MultiClick::DoubleClick);
+        processor.on_touch(1, TouchPhase::Ended, 4., 4.);
+
+        // Moving one of two fingers isn't a tap
+        processor.on_touch(2, TouchPhase::Started, 4., 4.);
+        processor.on_touch(3, TouchPhase::Started, 10., 4.);
+        assert_eq!(processor.ctx.touch.gesture, Gesture::TwoFingerTap);
+        processor.on_touch(3, TouchPhase::Moved, 10., 40.);
+        assert_eq!(processor.ctx.touch.gesture, Gesture::Ignored);
+        processor.on_touch(2, TouchPhase::Ended, 4., 4.);
+        processor.on_touch(3, TouchPhase::Ended, 10., 40.);
+        assert_eq!(processor.ctx.touch.gesture, Gesture::None);
+    }
+
+    #[test]
+    fn paste_normalizes_line_endings() {
+        let mut context = ActionContext::new(terminal());
+
+        Action::Paste.paste(&mut context, "a\r\nb\nc");
+        assert_eq!(context.written, b"a\rb\rc".to_vec());
+
+        context.written.clear();
+        context.terminal.set_mode(ansi::Mode::BracketedPaste);
+        Action::Paste.paste(&mut context, "a\r\nb\nc");
+        assert_eq!(context.written, b"\x1b[200~a\nb\nc\x1b[201~".to_vec());
+        assert!(context.paste.is_none());
+    }
+
+    #[test]
+    fn escape_cancels_long_paste() {
+        let mut terminal = terminal();
+        terminal.set_mode(ansi::Mode::BracketedPaste);
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal));
+
+        // Only the first chunk is written right away
+        Action::Paste.paste(&mut processor.ctx, &"a".repeat(CHUNK_SIZE * 3));
+        assert_eq!(processor.ctx.written.len(), CHUNK_SIZE + 6);
+        assert!(processor.ctx.written.starts_with(b"\x1b[200~"));
+        assert!(processor.ctx.paste.is_some());
+
+        // Escape closes the bracketed paste instead of being sent
+        let escape = ::glutin::KeyboardInput {
+            scancode: 0x01,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::Escape),
+            modifiers: ModifiersState::default(),
+        };
+        processor.process_key(escape);
+        processor.received_char('\x1b');
+        assert!(processor.ctx.written.ends_with(b"a\x1b[201~"));
+        assert!(processor.ctx.paste.is_none());
+
+        // Without a paste it's an ordinary key again
+        processor.ctx.written.clear();
+        processor.process_key(escape);
+        processor.received_char('\x1b');
+        assert_eq!(processor.ctx.written, b"\x1b".to_vec());
+    }
+
+    #[test]
+    fn typing_ends_long_paste() {
+        let key = ::glutin::KeyboardInput {
+            scancode: 0x2d,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::X),
+            modifiers: ModifiersState::default(),
+        };
+
+        for &bracketed in &[true, false] {
+            let mut terminal = terminal();
+            if bracketed {
+                terminal.set_mode(ansi::Mode::BracketedPaste);
+            }
+            let configs = Configs::default();
+            let mut processor = configs.processor(ActionContext::new(terminal));
+
+            Action::Paste.paste(&mut processor.ctx, &"a".repeat(CHUNK_SIZE * 3));
+            assert!(processor.ctx.paste.is_some());
+
+            // The key is written after the paste, never in the middle of it
+            processor.process_key(key);
+            processor.received_char('x');
+            let end: &[u8] = if bracketed { b"a\x1b[201~x" } else { b"ax" };
+            assert!(processor.ctx.written.ends_with(end));
+            assert!(processor.ctx.paste.is_none());
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn type_with_alt(alt_send_esc: bool, key: VirtualKeyCode, c: char) -> Vec<u8> {
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal()));
+        processor.alt_send_esc = alt_send_esc;
+
+        let modifiers = ModifiersState { shift: false, ctrl: false, alt: true, logo: false };
+        for &key in &[VirtualKeyCode::LAlt, key] {
+            processor.process_key(::glutin::KeyboardInput {
+                scancode: 0,
+                state: ElementState::Pressed,
+                virtual_keycode: Some(key),
+                modifiers,
+            });
+        }
+        processor.received_char(c);
+        processor.ctx.written
+    }
+
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn alt_send_esc() {
+        assert_eq!(type_with_alt(true, VirtualKeyCode::B, 'b'), b"\x1bb".to_vec());
+        assert_eq!(type_with_alt(false, VirtualKeyCode::B, 'b'), b"b".to_vec());
+
+        // Characters outside of ASCII are never prefixed
+        assert_eq!(type_with_alt(true, VirtualKeyCode::N, 'ñ'), "ñ".as_bytes().to_vec());
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn press_numpad(app_keypad: bool, scancode: u32, key: VirtualKeyCode, c: char) -> Vec<u8> {
+        let mut terminal = terminal();
+        if app_keypad {
+            terminal.set_keypad_application_mode();
+        }
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal));
+
+        processor.process_key(::glutin::KeyboardInput {
+            scancode,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::default(),
+        });
+        processor.received_char(c);
+        processor.ctx.written
+    }
+
+    #[test]
+    #[cfg(not(any(windows, target_os = "macos")))]
+    fn app_keypad() {
+        // Numpad1 with NumLock disabled is reported as End
+        assert_eq!(press_numpad(true, 0x4f, VirtualKeyCode::End, '1'), b"\x1bOq".to_vec());
+        assert_eq!(press_numpad(true, 0x60, VirtualKeyCode::NumpadEnter, '\r'), b"\x1bOM".to_vec());
+
+        // NumLock keeps typing digits, while the operators are still encoded
+        assert_eq!(press_numpad(true, 0x4f, VirtualKeyCode::Numpad1, '1'), b"1".to_vec());
+        assert_eq!(press_numpad(true, 0x4e, VirtualKeyCode::Add, '+'), b"\x1bOk".to_vec());
+
+        assert_eq!(press_numpad(false, 0x4e, VirtualKeyCode::Add, '+'), b"+".to_vec());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn app_keypad_keeps_dedicated_cursor_keys() {
+        let config = Config::default();
+        let mut terminal = terminal();
+        terminal.set_keypad_application_mode();
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal));
+        processor.key_bindings = &config.key_bindings()[..];
+
+        // The dedicated Up key is reported with the scancode of Numpad8
+        processor.process_key(::glutin::KeyboardInput {
+            scancode: 0x48,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::Up),
+            modifiers: ModifiersState::default(),
+        });
+        assert_eq!(processor.ctx.written, b"\x1b[A".to_vec());
+
+        // The operators of the numpad are still encoded
+        assert_eq!(press_numpad(true, 0x4e, VirtualKeyCode::Add, '+'), b"\x1bOk".to_vec());
+    }
+
+    fn press_key(app_cursor: bool, key: VirtualKeyCode, mods: &str) -> Vec<u8> {
+        let config = Config::default();
+        let mut terminal = terminal();
+        if app_cursor {
+            terminal.set_mode(ansi::Mode::CursorKeys);
+        }
+        let configs = Configs::default();
+        let mut processor = configs.processor(ActionContext::new(terminal));
+        processor.key_bindings = &config.key_bindings()[..];
+
+        processor.process_key(::glutin::KeyboardInput {
+            scancode: 0,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState {
+                shift: mods.contains('S'),
+                ctrl: mods.contains('C'),
+                alt: mods.contains('A'),
+                logo: mods.contains('L'),
+            },
+        });
+        processor.ctx.written
+    }
+
+    #[test]
+    fn key_encodings() {
+        use glutin::VirtualKeyCode::*;
+
+        // Modifiers are Shift, Control, Alt and Logo (Command/Super)
+        let encodings: &[(VirtualKeyCode, &str, bool, &[u8])] = &[
+            (Up, "", false, b"\x1b[A"),
+            (Down, "", false, b"\x1b[B"),
+            (Right, "", false, b"\x1b[C"),
+            (Left, "", false, b"\x1b[D"),
+            (End, "", false, b"\x1b[F"),
+            (Home, "", false, b"\x1b[H"),
+            (Up, "", true, b"\x1bOA"),
+            (Down, "", true, b"\x1bOB"),
+            (Right, "", true, b"\x1bOC"),
+            (Left, "", true, b"\x1bOD"),
+            (End, "", true, b"\x1bOF"),
+            (Home, "", true, b"\x1bOH"),
+
+            // Modified cursor keys don't depend on the cursor mode
+            (Left, "S", false, b"\x1b[1;2D"),
+            (Left, "A", false, b"\x1b[1;3D"),
+            (Left, "SA", false, b"\x1b[1;4D"),
+            (Left, "C", false, b"\x1b[1;5D"),
+            (Left, "SC", false, b"\x1b[1;6D"),
+            (Left, "CA", false, b"\x1b[1;7D"),
+            (Left, "SCA", false, b"\x1b[1;8D"),
+            (Left, "L", false, b"\x1b[1;9D"),
+            (Left, "SCAL", false, b"\x1b[1;16D"),
+            (Left, "C", true, b"\x1b[1;5D"),
+            (Right, "C", true, b"\x1b[1;5C"),
+            (Up, "C", false, b"\x1b[1;5A"),
+            (Down, "S", true, b"\x1b[1;2B"),
+            (Home, "S", false, b"\x1b[1;2H"),
+            (Home, "C", true, b"\x1b[1;5H"),
+            (End, "A", false, b"\x1b[1;3F"),
+            (End, "SC", true, b"\x1b[1;6F"),
+
+            (Insert, "", false, b"\x1b[2~"),
+            (Delete, "", false, b"\x1b[3~"),
+            (PageUp, "", false, b"\x1b[5~"),
+            (PageDown, "", true, b"\x1b[6~"),
+            (Delete, "C", false, b"\x1b[3;5~"),
+            (PageUp, "S", false, b"\x1b[5;2~"),
+            (PageUp, "C", false, b"\x1b[5;5~"),
+            (PageDown, "A", false, b"\x1b[6;3~"),
+
+            (F1, "", false, b"\x1bOP"),
+            (F2, "", true, b"\x1bOQ"),
+            (F3, "", false, b"\x1bOR"),
+            (F4, "", false, b"\x1bOS"),
+            (F5, "", false, b"\x1b[15~"),
+            (F6, "", false, b"\x1b[17~"),
+            (F7, "", false, b"\x1b[18~"),
+            (F8, "", false, b"\x1b[19~"),
+            (F9, "", false, b"\x1b[20~"),
+            (F10, "", false, b"\x1b[21~"),
+            (F11, "", false, b"\x1b[23~"),
+            (F12, "", false, b"\x1b[24~"),
+            (F1, "S", false, b"\x1b[1;2P"),
+            (F4, "C", true, b"\x1b[1;5S"),
+            (F1, "A", false, b"\x1b[1;3P"),
+            (F2, "L", false, b"\x1b[1;9Q"),
+            (F5, "S", false, b"\x1b[15;2~"),
+            (F8, "A", false, b"\x1b[19;3~"),
+            (F12, "C", false, b"\x1b[24;5~"),
+            (F10, "SC", false, b"\x1b[21;6~"),
+
+            (Tab, "S", false, b"\x1b[Z"),
+            (Back, "", false, b"\x7f"),
+            (Back, "A", false, b"\x1b\x7f"),
+        ];
+
+        for &(key, mods, app_cursor, expected) in encodings {
+            assert_eq!(
+                press_key(app_cursor, key, mods),
+                expected.to_vec(),
+                "{:?} with {:?}, application cursor mode {}",
+                key,
+                mods,
+                app_cursor
+            );
+        }
+    }
+
     test_clickstate! {
         name: single_click,
         initial_state: ClickState::None,
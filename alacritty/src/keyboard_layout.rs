@@ -0,0 +1,121 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Characters of keys in the current keyboard layout
+//!
+//! The characters received on macOS have the composition of the Option key
+//! applied. When Option acts as Alt, the character of the key without Option
+//! is looked up in the layout which is currently selected instead.
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::os::raw::{c_ulong, c_void};
+
+    pub type OSStatus = i32;
+    pub type UniChar = u16;
+    pub type UniCharCount = c_ulong;
+    pub type CFTypeRef = *const c_void;
+
+    /// `kUCKeyActionDown`
+    pub const KEY_ACTION_DOWN: u16 = 0;
+
+    /// `kUCKeyTranslateNoDeadKeysMask`, dead keys produce their character
+    pub const NO_DEAD_KEYS: u32 = 1;
+
+    /// `shiftKey >> 8`, the modifier state expected by `UCKeyTranslate`
+    pub const SHIFT: u32 = 0x02;
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        pub static kTISPropertyUnicodeKeyLayoutData: CFTypeRef;
+
+        pub fn TISCopyCurrentKeyboardLayoutInputSource() -> CFTypeRef;
+        pub fn TISGetInputSourceProperty(source: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+        pub fn LMGetKbdType() -> u8;
+        pub fn UCKeyTranslate(
+            layout: *const u8,
+            virtual_key_code: u16,
+            key_action: u16,
+            modifier_key_state: u32,
+            keyboard_type: u32,
+            key_translate_options: u32,
+            dead_key_state: *mut u32,
+            max_string_length: UniCharCount,
+            actual_string_length: *mut UniCharCount,
+            unicode_string: *mut UniChar,
+        ) -> OSStatus;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFDataGetBytePtr(data: CFTypeRef) -> *const u8;
+        pub fn CFRelease(cf: CFTypeRef);
+    }
+}
+
+/// Character of a key without any modifiers except Shift
+///
+/// The scancode is the virtual key code of macOS. Returns `None` when the key
+/// doesn't produce a printable character.
+#[cfg(target_os = "macos")]
+pub fn unmodified_char(scancode: u32, shift: bool) -> Option<char> {
+    unsafe {
+        let source = ffi::TISCopyCurrentKeyboardLayoutInputSource();
+        if source.is_null() {
+            return None;
+        }
+
+        // The data is owned by the input source
+        let data = ffi::TISGetInputSourceProperty(source, ffi::kTISPropertyUnicodeKeyLayoutData);
+        if data.is_null() {
+            ffi::CFRelease(source);
+            return None;
+        }
+
+        let mut dead_key_state = 0;
+        let mut chars = [0; 4];
+        let mut len = 0;
+        let status = ffi::UCKeyTranslate(
+            ffi::CFDataGetBytePtr(data),
+            scancode as u16,
+            ffi::KEY_ACTION_DOWN,
+            if shift { ffi::SHIFT } else { 0 },
+            u32::from(ffi::LMGetKbdType()),
+            ffi::NO_DEAD_KEYS,
+            &mut dead_key_state,
+            chars.len() as ffi::UniCharCount,
+            &mut len,
+            chars.as_mut_ptr(),
+        );
+        ffi::CFRelease(source);
+
+        if status != 0 || len == 0 {
+            return None;
+        }
+
+        let mut decoded = ::std::char::decode_utf16(chars[..len as usize].iter().cloned());
+        match (decoded.next(), decoded.next()) {
+            (Some(Ok(c)), None) if !c.is_control() => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// Character of a key without any modifiers except Shift
+///
+/// Only macOS composes characters with the Option key.
+#[cfg(not(target_os = "macos"))]
+pub fn unmodified_char(_scancode: u32, _shift: bool) -> Option<char> {
+    None
+}
@@ -21,7 +21,6 @@
 #[macro_use] extern crate clap;
 #[macro_use] extern crate log;
 #[macro_use] extern crate serde_derive;
-#[macro_use] extern crate static_assertions;
 
 #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly",
           target_os = "openbsd"))]
@@ -35,22 +34,23 @@ extern crate winapi;
 extern crate winpty;
 #[cfg(windows)]
 extern crate dunce;
-#[cfg(windows)]
+#[cfg(all(windows, feature = "gui"))]
 extern crate winit;
-#[cfg(windows)]
-extern crate image;
 
 #[cfg(target_os = "macos")]
 #[macro_use]
 extern crate objc;
 
 extern crate arraydeque;
+#[cfg(feature = "gui")]
 extern crate cgmath;
+#[cfg(feature = "gui")]
+extern crate image;
 extern crate copypasta;
-extern crate env_logger;
 extern crate errno;
 extern crate fnv;
 extern crate font;
+#[cfg(feature = "gui")]
 extern crate glutin;
 extern crate libc;
 extern crate mio;
@@ -66,6 +66,7 @@ extern crate xdg;
 extern crate base64;
 extern crate terminfo;
 extern crate url;
+extern crate yaml_rust;
 
 #[macro_use]
 pub mod macros;
@@ -73,21 +74,34 @@ pub mod macros;
 pub mod ansi;
 pub mod cli;
 pub mod config;
+pub mod daemon;
+#[cfg(feature = "gui")]
 pub mod display;
+#[cfg(feature = "gui")]
 pub mod event;
 pub mod event_loop;
 pub mod grid;
+pub mod headless;
 pub mod index;
+#[cfg(feature = "gui")]
 pub mod input;
+pub mod keyboard_layout;
 pub mod locale;
 pub mod logging;
 pub mod meter;
+pub mod paste;
+#[cfg(feature = "gui")]
 pub mod renderer;
+pub mod secure_input;
 pub mod selection;
+pub mod state;
 pub mod sync;
 pub mod term;
+pub mod title;
 pub mod tty;
 pub mod util;
+pub mod version;
+#[cfg(feature = "gui")]
 pub mod window;
 
 use std::ops::Mul;
@@ -100,15 +114,38 @@ pub use term::Term;
 pub enum MouseCursor {
     Arrow,
     Text,
+    Hand,
 }
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default, Hash, Serialize, Deserialize)]
 pub struct Rgb {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+impl Rgb {
+    /// Relative luminance, as defined by WCAG 2.0
+    pub fn luminance(self) -> f64 {
+        let channel = |value: u8| {
+            let value = f64::from(value) / 255.;
+            if value <= 0.03928 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        0.2126 * channel(self.r) + 0.7152 * channel(self.g) + 0.0722 * channel(self.b)
+    }
+
+    /// Contrast ratio between two colors, from 1 for equal colors to 21
+    pub fn contrast(self, other: Rgb) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+}
+
 // a multiply function for Rgb, as the default dim is just *2/3
 impl Mul<f32> for Rgb {
     type Output = Rgb;
@@ -127,6 +164,7 @@ impl Mul<f32> for Rgb {
 }
 
 
+#[cfg(feature = "gui")]
 #[allow(unused_mut)]
 pub mod gl {
     #![allow(non_upper_case_globals)]
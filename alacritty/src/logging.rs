@@ -15,52 +15,372 @@
 //! Logging for alacritty.
 //!
 //! The main executable is supposed to call `initialize()` exactly once during
-//! startup. All logging messages are written to stdout, given that their
-//! log-level is sufficient for the level configured in `cli::Options`.
-use log;
-use std::sync;
-use std::io;
+//! startup. All logging messages are written to stdout and a log file in the
+//! temporary directory, given that their log-level is sufficient for the
+//! filter configured through `cli::Options`, the config or `$RUST_LOG`.
+//!
+//! Records are written by a background thread, so logging never blocks the
+//! pty I/O thread. When the writer can't keep up, records are dropped.
+//!
+//! Warnings and errors of alacritty are also kept for the message bar, see
+//! `Messages`.
+use log::{self, Level, LevelFilter, Metadata, Record};
+use std::cell::RefCell;
+use std::cmp::max;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
 use cli;
+use config::Config;
+use event_loop::WindowNotifier;
+use util::thread;
+
+/// Maximum number of records waiting to be written
+const QUEUE_SIZE: usize = 1024;
+
+/// Messages handled by the writer thread
+enum Msg {
+    Record(String),
+    Flush(mpsc::Sender<()>),
+}
+
+thread_local! {
+    /// Sender of the current thread, it's cloned once so records are sent
+    /// without taking the lock of the logger
+    static SENDER: RefCell<Option<SyncSender<Msg>>> = RefCell::new(None);
+}
+
+/// Log levels per module, using the syntax of `$RUST_LOG`
+///
+/// A comma separated list of `module=level` directives, where a directive
+/// without module sets the level for all of alacritty.
+#[derive(Debug, PartialEq)]
+pub struct Filter {
+    default: LevelFilter,
+    directives: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    pub fn parse(spec: &str, default: LevelFilter) -> Filter {
+        let mut filter = Filter { default, directives: Vec::new() };
+
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let module = parts.next().unwrap_or("");
+            match parts.next() {
+                Some(level) => match level.parse() {
+                    Ok(level) => filter.directives.push((module.to_owned(), level)),
+                    Err(_) => eprintln!("Ignoring invalid log directive: {}", directive),
+                },
+                None => match module.parse() {
+                    Ok(level) => filter.default = level,
+                    // A module without level enables all of its records
+                    Err(_) => filter.directives.push((module.to_owned(), LevelFilter::Trace)),
+                },
+            }
+        }
+
+        // The most specific directive is matched first
+        filter.directives.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+        filter
+    }
+
+    /// Most verbose level enabled for any module
+    fn max_level(&self) -> LevelFilter {
+        self.directives.iter().fold(self.default, |level, directive| max(level, directive.1))
+    }
+
+    fn enabled(&self, target: &str, level: Level) -> bool {
+        let directive = self.directives.iter().find(|directive| {
+            let module = &directive.0;
+            target == module || (target.starts_with(module.as_str())
+                && target[module.len()..].starts_with("::"))
+        });
+
+        match directive {
+            Some(directive) => level <= directive.1,
+            // Other crates are silent unless they have a directive
+            None => target.starts_with("alacritty") && level <= self.default,
+        }
+    }
+}
+
+/// Warnings and errors which are waiting to be shown in the message bar
+#[derive(Clone, Default)]
+pub struct Messages {
+    inner: Arc<Mutex<MessageQueue>>,
+}
+
+#[derive(Default)]
+struct MessageQueue {
+    /// Only the latest message is shown
+    pending: Option<String>,
+
+    /// Wakes up the main loop, which shows the message
+    waker: Option<Box<WindowNotifier + Send>>,
+}
+
+impl Messages {
+    /// Wake up the main loop whenever a message is logged
+    pub fn set_waker(&self, waker: Box<WindowNotifier + Send>) {
+        if let Ok(mut queue) = self.inner.lock() {
+            queue.waker = Some(waker);
+        }
+    }
+
+    /// Latest message which was logged since the last call
+    pub fn take(&self) -> Option<String> {
+        self.inner.lock().ok().and_then(|mut queue| queue.pending.take())
+    }
+
+    /// Show a message which wasn't logged as a warning or error
+    pub fn push(&self, message: String) {
+        if let Ok(mut queue) = self.inner.lock() {
+            queue.pending = Some(message);
+            if let Some(ref waker) = queue.waker {
+                waker.notify();
+            }
+        }
+    }
+}
 
-pub struct Logger<T> {
-    level: log::LevelFilter,
-    output: sync::Mutex<T>
+/// Check if a record is shown in the message bar
+///
+/// Only alacritty's own warnings and errors are, the ones of other crates
+/// usually aren't actionable.
+fn is_message(metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Warn && metadata.target().starts_with("alacritty")
 }
 
-impl<T: Send + io::Write> Logger<T> {
+pub struct Logger {
+    filter: Filter,
+    start: Instant,
+
+    /// Only locked once by each thread, to clone its own sender
+    sender: Mutex<SyncSender<Msg>>,
+
+    dropped: Arc<AtomicUsize>,
+    messages: Messages,
+}
+
+impl Logger {
     // False positive, see: https://github.com/rust-lang-nursery/rust-clippy/issues/734
     #[cfg_attr(feature = "cargo-clippy", allow(new_ret_no_self))]
-    pub fn new(output: T, level: log::LevelFilter) -> Logger<io::LineWriter<T>> {
-        log::set_max_level(level);
+    pub fn new(filter: Filter, file: Option<File>, messages: Messages) -> Logger {
+        log::set_max_level(filter.max_level());
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let writer_dropped = Arc::clone(&dropped);
+        thread::spawn_named("log writer", move || {
+            write_records(&receiver, file.map(LineWriter::new), &writer_dropped);
+        });
+
         Logger {
-            level,
-            output: sync::Mutex::new(io::LineWriter::new(output))
+            filter,
+            start: Instant::now(),
+            sender: Mutex::new(sender),
+            dropped,
+            messages,
         }
     }
+
+    /// Run `f` with the sender of the current thread
+    ///
+    /// Returns `None` when the thread is shutting down or the lock of the
+    /// logger was poisoned.
+    fn with_sender<F, R>(&self, f: F) -> Option<R>
+        where F: FnOnce(&SyncSender<Msg>) -> R
+    {
+        SENDER.try_with(|sender| {
+            let mut sender = sender.borrow_mut();
+            if sender.is_none() {
+                *sender = self.sender.lock().ok().map(|sender| sender.clone());
+            }
+            sender.as_ref().map(f)
+        }).unwrap_or(None)
+    }
 }
 
-impl<T: Send + io::Write> log::Log for Logger<T> {
-    fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= self.level
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata.target(), metadata.level())
     }
 
-    fn log(&self, record: &log::Record) {
-        if self.enabled(record.metadata()) && record.target().starts_with("alacritty") {
-            if let Ok(ref mut writer) = self.output.lock() {
-                let _ = writer.write_all(format!("{}\n", record.args()).as_ref());
-            }
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if is_message(record.metadata()) {
+            self.messages.push(format!("{}: {}", record.level(), record.args()));
+        }
+
+        let elapsed = self.start.elapsed();
+        let current = thread::current();
+        let line = format!(
+            "[{:>5}.{:06}] [{:<5}] [{}] {}\n",
+            elapsed.as_secs(),
+            elapsed.subsec_micros(),
+            record.level(),
+            current.name().unwrap_or("unnamed"),
+            record.args()
+        );
+
+        let sent = self.with_sender(|sender| sender.try_send(Msg::Record(line)));
+        if let Some(Err(TrySendError::Full(_))) = sent {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {
+        let (ack, done) = mpsc::channel();
+        let sent = self.with_sender(|sender| sender.send(Msg::Flush(ack)).is_ok());
+        if let Some(true) = sent {
+            let _ = done.recv();
+        }
+    }
+}
+
+fn write_records(receiver: &Receiver<Msg>, mut file: Option<LineWriter<File>>, dropped: &AtomicUsize) {
+    let stdout = io::stdout();
+
+    for msg in receiver {
+        match msg {
+            Msg::Record(line) => {
+                let mut stdout = stdout.lock();
+
+                let count = dropped.swap(0, Ordering::Relaxed);
+                if count > 0 {
+                    let warning = format!("{} log messages were dropped\n", count);
+                    let _ = stdout.write_all(warning.as_bytes());
+                    if let Some(ref mut file) = file {
+                        let _ = file.write_all(warning.as_bytes());
+                    }
+                }
+
+                let _ = stdout.write_all(line.as_bytes());
+                if let Some(ref mut file) = file {
+                    let _ = file.write_all(line.as_bytes());
+                }
+            },
+            Msg::Flush(ack) => {
+                let _ = stdout.lock().flush();
+                if let Some(ref mut file) = file {
+                    let _ = file.flush();
+                }
+                let _ = ack.send(());
+            },
+        }
+    }
+}
+
+/// Log file which is removed again on a clean shutdown
+///
+/// The file is left behind when Alacritty crashes, or when persistent logging
+/// is enabled.
+pub struct LogFile {
+    path: PathBuf,
+    persistent: bool,
+}
+
+impl LogFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LogFile {
+    fn drop(&mut self) {
+        log::logger().flush();
+
+        if !self.persistent {
+            let _ = fs::remove_file(&self.path);
         }
     }
+}
+
+/// Install the logger
+///
+/// Returns the log file, which is removed again when it's dropped, and the
+/// messages for the message bar.
+pub fn initialize(
+    options: &cli::Options,
+    config: &Config,
+) -> Result<(Option<LogFile>, Messages), log::SetLoggerError> {
+    // The filter from the environment takes precedence over the config
+    let spec = env::var("RUST_LOG").ok()
+        .or_else(|| config.debug().log_filter.clone())
+        .unwrap_or_default();
+    let filter = Filter::parse(&spec, options.log_level);
 
-    fn flush(&self) {}
+    let path = env::temp_dir().join(format!("Alacritty-{}.log", process::id()));
+    let (file, log_file) = match File::create(&path) {
+        Ok(file) => {
+            println!("Created log file at {}", path.display());
+            let persistent = options.persistent_logging || config.debug().persistent_logging;
+            (Some(file), Some(LogFile { path, persistent }))
+        },
+        Err(err) => {
+            eprintln!("Unable to create log file at {}: {}", path.display(), err);
+            (None, None)
+        },
+    };
+
+    let messages = Messages::default();
+    log::set_boxed_logger(Box::new(Logger::new(filter, file, messages.clone())))?;
+
+    Ok((log_file, messages))
 }
 
-pub fn initialize(options: &cli::Options) -> Result<(), log::SetLoggerError> {
-    // Use env_logger if RUST_LOG environment variable is defined. Otherwise,
-    // use the alacritty-only logger.
-    if ::std::env::var("RUST_LOG").is_ok() {
-        ::env_logger::try_init()
-    } else {
-        log::set_boxed_logger(Box::new(Logger::new(io::stdout(), options.log_level)))
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter, MetadataBuilder};
+
+    use super::{is_message, Filter, Messages};
+
+    #[test]
+    fn filter_directives() {
+        let filter = Filter::parse("info, alacritty::event_loop=trace,glutin", LevelFilter::Warn);
+
+        assert!(filter.enabled("alacritty::display", Level::Info));
+        assert!(!filter.enabled("alacritty::display", Level::Debug));
+        assert!(filter.enabled("alacritty::event_loop", Level::Trace));
+        assert!(!filter.enabled("alacritty::event_loop_other", Level::Debug));
+        assert!(filter.enabled("glutin::platform", Level::Trace));
+        assert!(!filter.enabled("mio", Level::Error));
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn filter_invalid_level() {
+        let filter = Filter::parse("alacritty=loud", LevelFilter::Error);
+        assert_eq!(filter, Filter { default: LevelFilter::Error, directives: Vec::new() });
+    }
+
+    #[test]
+    fn warnings_are_messages() {
+        let metadata = |target, level| MetadataBuilder::new().target(target).level(level).build();
+
+        assert!(is_message(&metadata("alacritty::config", Level::Warn)));
+        assert!(is_message(&metadata("alacritty::display", Level::Error)));
+        assert!(!is_message(&metadata("alacritty::config", Level::Info)));
+        assert!(!is_message(&metadata("glutin::platform", Level::Error)));
+
+        // Only the latest message is shown
+        let messages = Messages::default();
+        messages.push("first".into());
+        messages.push("second".into());
+        assert_eq!(messages.take(), Some("second".into()));
+        assert_eq!(messages.take(), None);
     }
 }
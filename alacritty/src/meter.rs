@@ -14,7 +14,8 @@
 //
 //! Rendering time meter
 //!
-//! Used to track rendering times and provide moving averages.
+//! Used to track rendering times and provide moving averages, together with
+//! percentiles of the recent samples to find the occasional slow frames.
 //!
 //! # Examples
 //!
@@ -30,11 +31,20 @@
 //! // Get the moving average. The meter tracks a fixed number of samples, and
 //! // the average won't mean much until it's filled up at least once.
 //! println!("Average time: {}", meter.average());
+//!
+//! // Get the percentiles of the recent samples
+//! if let Some(stats) = meter.stats() {
+//!     println!("99th percentile: {}", stats.p99);
+//! }
 
+use std::mem;
 use std::time::{Instant, Duration};
 
 const NUM_SAMPLES: usize = 10;
 
+/// Number of recent samples the percentiles are computed from
+const NUM_HISTORY: usize = 1024;
+
 /// The meter
 #[derive(Default)]
 pub struct Meter {
@@ -46,6 +56,33 @@ pub struct Meter {
 
     /// Index of next time to update.
     index: usize,
+
+    /// Ring buffer of the last `NUM_HISTORY` samples in microseconds
+    history: Vec<f64>,
+
+    /// Index of the oldest sample, once the history is full
+    history_index: usize,
+
+    /// The history in ascending order, kept sorted as samples are added
+    sorted: Vec<f64>,
+
+    /// Longest sample since the meter was created
+    worst: f64,
+
+    /// Number of samples since the meter was created
+    count: u64,
+}
+
+/// Statistics of the recent samples in microseconds
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stats {
+    /// Number of samples the statistics are computed from
+    pub samples: usize,
+
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
 }
 
 /// Sampler
@@ -97,6 +134,41 @@ impl Meter {
         self.avg
     }
 
+    /// Get the percentiles of the last `NUM_HISTORY` samples
+    ///
+    /// Returns `None` before the first sample.
+    pub fn stats(&self) -> Option<Stats> {
+        if self.history.is_empty() {
+            return None;
+        }
+
+        let sorted = &self.sorted;
+
+        // Nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.max(1) - 1]
+        };
+
+        Some(Stats {
+            samples: sorted.len(),
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+
+    /// Get the longest sample duration in microseconds since the meter was created
+    pub fn worst(&self) -> f64 {
+        self.worst
+    }
+
+    /// Get the number of samples since the meter was created
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
     /// Add a sample
     ///
     /// Used by Sampler::drop.
@@ -111,5 +183,68 @@ impl Meter {
         self.avg -= prev / NUM_SAMPLES as f64;
         self.avg += usec / NUM_SAMPLES as f64;
         self.index = (self.index + 1) % NUM_SAMPLES;
+
+        if self.history.len() < NUM_HISTORY {
+            self.history.push(usec);
+        } else {
+            let oldest = mem::replace(&mut self.history[self.history_index], usec);
+            self.history_index = (self.history_index + 1) % NUM_HISTORY;
+
+            let position = sorted_position(&self.sorted, oldest);
+            self.sorted.remove(position);
+        }
+
+        let position = sorted_position(&self.sorted, usec);
+        self.sorted.insert(position, usec);
+
+        if usec > self.worst {
+            self.worst = usec;
+        }
+        self.count += 1;
+    }
+}
+
+/// Index of `sample` in the sorted samples, or where it has to be inserted
+fn sorted_position(sorted: &[f64], sample: f64) -> usize {
+    sorted
+        .binary_search_by(|probe| probe.partial_cmp(&sample).unwrap())
+        .unwrap_or_else(|position| position)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Meter, NUM_HISTORY};
+
+    #[test]
+    fn percentiles_of_recent_samples() {
+        let mut meter = Meter::new();
+        assert_eq!(meter.stats(), None);
+
+        for usec in 1..=100 {
+            meter.add_sample(Duration::from_micros(usec));
+        }
+
+        let stats = meter.stats().unwrap();
+        assert_eq!(stats.samples, 100);
+        assert_eq!(stats.p50, 50.);
+        assert_eq!(stats.p95, 95.);
+        assert_eq!(stats.p99, 99.);
+        assert_eq!(stats.max, 100.);
+
+        // The slow sample is pushed out of the history, but stays the worst one
+        meter.add_sample(Duration::from_millis(50));
+        assert_eq!(meter.stats().unwrap().max, 50_000.);
+
+        for _ in 0..NUM_HISTORY {
+            meter.add_sample(Duration::from_micros(10));
+        }
+
+        let stats = meter.stats().unwrap();
+        assert_eq!(stats.samples, NUM_HISTORY);
+        assert_eq!(stats.max, 10.);
+        assert_eq!(meter.worst(), 50_000.);
+        assert_eq!(meter.count(), 101 + NUM_HISTORY as u64);
     }
 }
@@ -0,0 +1,195 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Pastes which are written to the pty in chunks
+//!
+//! Sending megabytes to the io thread at once blocks the window until all of
+//! it went through the channel, and fills the write queue faster than the
+//! child can read it. Pastes are split into chunks instead, the first one is
+//! written right away and the rest one at a time while the main loop runs and
+//! the write queue is almost empty.
+
+/// Maximum number of bytes written to the pty at once
+pub const CHUNK_SIZE: usize = 0x1_0000;
+
+/// Pastes which are longer show their progress in the message bar
+const PROGRESS_THRESHOLD: usize = 0x10_0000;
+
+const BRACKET_START: &[u8] = b"\x1b[200~";
+const BRACKET_END: &[u8] = b"\x1b[201~";
+
+/// Text of a paste which isn't completely written yet
+#[derive(Debug)]
+pub struct Paste {
+    text: String,
+    bracketed: bool,
+
+    /// Bytes of the text which were written
+    written: usize,
+
+    /// The first chunk was written, with the opening bracket
+    started: bool,
+}
+
+impl Paste {
+    /// Prepare the text of a paste for the application
+    pub fn new(contents: &str, bracketed: bool) -> Paste {
+        let text = if bracketed {
+            // Applications expect unix line endings inside of a bracketed
+            // paste, even when the text was copied on Windows
+            contents.replace("\x1b", "").replace("\r\n", "\n")
+        } else {
+            // In non-bracketed (ie: normal) mode, terminal applications cannot distinguish
+            // pasted data from keystrokes.
+            // In theory, we should construct the keystrokes needed to produce the data we are
+            // pasting... since that's neither practical nor sensible (and probably an impossible
+            // task to solve in a general way), we'll just replace line breaks (windows and unix
+            // style) with a singe carriage return (\r, which is what the Enter key produces).
+            contents.replace("\r\n", "\r").replace("\n", "\r")
+        };
+
+        Paste { text, bracketed, written: 0, started: false }
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.started && self.written == self.text.len()
+    }
+
+    /// Bytes to write next, `None` once everything was written
+    ///
+    /// Chunks end at character boundaries. The bracketed paste sequences are
+    /// part of the first and the last chunk.
+    pub fn next_chunk(&mut self) -> Option<Vec<u8>> {
+        if self.is_finished() {
+            return None;
+        }
+
+        let mut end = (self.written + CHUNK_SIZE).min(self.text.len());
+        while !self.text.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let mut chunk = Vec::with_capacity(end - self.written + BRACKET_START.len() * 2);
+        if self.bracketed && !self.started {
+            chunk.extend_from_slice(BRACKET_START);
+        }
+        chunk.extend_from_slice(self.text[self.written..end].as_bytes());
+        if self.bracketed && end == self.text.len() {
+            chunk.extend_from_slice(BRACKET_END);
+        }
+
+        self.started = true;
+        self.written = end;
+        Some(chunk)
+    }
+
+    /// Stop writing the paste
+    ///
+    /// Returns the bytes which close a bracketed paste that was started, the
+    /// application would wait for its end otherwise.
+    pub fn abort(&mut self) -> Option<&'static [u8]> {
+        let unterminated = self.bracketed && self.started && !self.is_finished();
+        self.started = true;
+        self.written = self.text.len();
+
+        if unterminated {
+            Some(BRACKET_END)
+        } else {
+            None
+        }
+    }
+
+    /// Progress which is shown for long pastes
+    pub fn message(&self) -> Option<String> {
+        if self.text.len() <= PROGRESS_THRESHOLD {
+            return None;
+        }
+
+        let kib = |bytes: usize| bytes / 1024;
+        Some(format!(
+            "Pasting {} of {} KiB, press Escape to cancel",
+            kib(self.written),
+            kib(self.text.len()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Paste, CHUNK_SIZE, PROGRESS_THRESHOLD};
+
+    fn all_chunks(paste: &mut Paste) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = paste.next_chunk() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    #[test]
+    fn brackets_surround_all_chunks() {
+        let text = "a".repeat(CHUNK_SIZE * 2 + 1);
+        let chunks = all_chunks(&mut Paste::new(&text, true));
+
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].starts_with(b"\x1b[200~"));
+        assert_eq!(chunks[0].len(), CHUNK_SIZE + 6);
+        assert!(!chunks[1].starts_with(b"\x1b[200~") && !chunks[1].ends_with(b"\x1b[201~"));
+        assert_eq!(chunks[2], b"a\x1b[201~".to_vec());
+
+        // Empty pastes are still bracketed
+        assert_eq!(all_chunks(&mut Paste::new("", true)), vec![b"\x1b[200~\x1b[201~".to_vec()]);
+        assert_eq!(all_chunks(&mut Paste::new("", false)), vec![Vec::new()]);
+    }
+
+    #[test]
+    fn chunks_end_at_character_boundaries() {
+        let text = format!("{}ü{}", "a".repeat(CHUNK_SIZE - 1), "b");
+        let chunks = all_chunks(&mut Paste::new(&text, false));
+
+        assert_eq!(chunks[0].len(), CHUNK_SIZE - 1);
+        assert_eq!(chunks[1], "üb".as_bytes().to_vec());
+    }
+
+    #[test]
+    fn abort_closes_bracketed_paste() {
+        let text = "a".repeat(CHUNK_SIZE * 2);
+
+        let mut paste = Paste::new(&text, true);
+        paste.next_chunk();
+        assert_eq!(paste.abort(), Some(&b"\x1b[201~"[..]));
+        assert!(paste.is_finished());
+        assert_eq!(paste.next_chunk(), None);
+
+        // Nothing is left to close once the last chunk was written
+        let mut paste = Paste::new("a", true);
+        paste.next_chunk();
+        assert_eq!(paste.abort(), None);
+
+        let mut paste = Paste::new(&text, false);
+        paste.next_chunk();
+        assert_eq!(paste.abort(), None);
+    }
+
+    #[test]
+    fn progress_of_long_pastes() {
+        assert_eq!(Paste::new(&"a".repeat(PROGRESS_THRESHOLD), false).message(), None);
+
+        let mut paste = Paste::new(&"a".repeat(PROGRESS_THRESHOLD * 2), false);
+        paste.next_chunk();
+        let message = paste.message().unwrap();
+        assert!(message.starts_with("Pasting 64 of 2048 KiB"));
+    }
+}
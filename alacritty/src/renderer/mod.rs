@@ -12,10 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fmt;
 use std::fs::File;
 use std::hash::BuildHasherDefault;
 use std::io::{self, Read};
-use std::mem::size_of;
+use std::mem::{self, size_of};
+use std::os::raw::c_void;
 use std::path::PathBuf;
 use std::ptr;
 use std::sync::mpsc;
@@ -24,13 +27,15 @@ use std::time::Duration;
 use cgmath;
 use fnv::FnvHasher;
 use font::{self, FontDesc, FontKey, GlyphKey, Rasterize, RasterizedGlyph, Rasterizer};
+use font::synthesis;
 use gl::types::*;
 use gl;
 use index::{Column, Line, RangeInclusive};
 use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
 
-use config::{self, Config, Delta};
-use term::{self, cell, RenderableCell};
+use config::{self, Config, Delta, RendererPreference};
+use ansi::CursorStyle;
+use term::{self, cell, RenderableCell, RenderableCursor};
 use window::{Pixels, Size};
 
 use Rgb;
@@ -38,12 +43,183 @@ use Rgb;
 // Shader paths for live reload
 static TEXT_SHADER_F_PATH: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.f.glsl");
 static TEXT_SHADER_V_PATH: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.v.glsl");
+static TEXT_SHADER_ES_F_PATH: &'static str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/res/text_es.f.glsl");
+static TEXT_SHADER_ES_V_PATH: &'static str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/res/text_es.v.glsl");
 
 // Shader source which is used when live-shader-reload feature is disable
 static TEXT_SHADER_F: &'static str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.f.glsl"));
 static TEXT_SHADER_V: &'static str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.v.glsl"));
+static TEXT_SHADER_ES_F: &'static str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/text_es.f.glsl"));
+static TEXT_SHADER_ES_V: &'static str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/text_es.v.glsl"));
+
+/// Vertex attributes by location, the OpenGL ES shaders can't declare them
+const ATTRIBUTES: [&[u8]; 6] = [
+    b"position\0",
+    b"gridCoords\0",
+    b"glyph\0",
+    b"uv\0",
+    b"textColor\0",
+    b"backgroundColor\0",
+];
+
+/// Extensions for instanced rendering on OpenGL ES 2.0, with their function suffix
+const INSTANCING_EXTENSIONS: [(&str, &str); 2] = [
+    ("GL_EXT_instanced_arrays", "EXT"),
+    ("GL_ANGLE_instanced_arrays", "ANGLE"),
+];
+
+/// Extension for vertex array objects on OpenGL ES 2.0
+const VERTEX_ARRAY_EXTENSION: &str = "GL_OES_vertex_array_object";
+
+/// Load the OpenGL functions of the current context
+///
+/// OpenGL ES 2.0 only has instanced rendering and vertex array objects as
+/// extensions, so their functions are loaded in place of the core ones.
+pub fn load_gl<F>(get_proc_address: F)
+    where F: Fn(&str) -> *const c_void
+{
+    gl::load_with(|symbol| get_proc_address(symbol));
+
+    let (gles, version) = parse_gl_version(&gl_string(gl::VERSION));
+    if !gles || version.0 >= 3 {
+        return;
+    }
+
+    let extensions = gl_extensions();
+    let load_suffixed = |symbol: &str, suffix: &str| {
+        get_proc_address(&format!("{}{}", symbol, suffix))
+    };
+
+    let instancing = INSTANCING_EXTENSIONS
+        .iter()
+        .find(|&&(extension, _)| extensions.iter().any(|e| e == extension));
+    if let Some(&(_, suffix)) = instancing {
+        gl::VertexAttribDivisor::load_with(|symbol| load_suffixed(symbol, suffix));
+        gl::DrawElementsInstanced::load_with(|symbol| load_suffixed(symbol, suffix));
+    }
+
+    if extensions.iter().any(|e| e == VERTEX_ARRAY_EXTENSION) {
+        gl::GenVertexArrays::load_with(|symbol| load_suffixed(symbol, "OES"));
+        gl::BindVertexArray::load_with(|symbol| load_suffixed(symbol, "OES"));
+    }
+}
+
+/// OpenGL features used for rendering with the current context
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct RenderPath {
+    /// OpenGL ES shaders, blending text with a single alpha value
+    ///
+    /// Desktop OpenGL uses dual-source blending instead, which blends every
+    /// color component of subpixel antialiased glyphs separately.
+    gles: bool,
+
+    /// Version of the context
+    version: (u32, u32),
+
+    /// Cells are instances of a single quad, instead of four vertices each
+    instancing: bool,
+
+    vertex_arrays: bool,
+}
+
+impl RenderPath {
+    /// Pick the rendering path for the current context
+    ///
+    /// The desktop OpenGL context is at least version 3.3, which has
+    /// everything the renderer uses.
+    fn detect(preference: RendererPreference) -> RenderPath {
+        let (gles, version) = parse_gl_version(&gl_string(gl::VERSION));
+        if !gles {
+            return RenderPath { gles, version, instancing: true, vertex_arrays: true };
+        }
+
+        let es3 = version.0 >= 3;
+        let extensions = gl_extensions();
+        let has_extension = |name: &str| extensions.iter().any(|e| e == name);
+
+        let instancing = preference != RendererPreference::OpenGlEsNonInstanced
+            && (es3 || INSTANCING_EXTENSIONS.iter().any(|&(e, _)| has_extension(e)))
+            && gl::VertexAttribDivisor::is_loaded()
+            && gl::DrawElementsInstanced::is_loaded();
+        let vertex_arrays = (es3 || has_extension(VERTEX_ARRAY_EXTENSION))
+            && gl::GenVertexArrays::is_loaded()
+            && gl::BindVertexArray::is_loaded();
+
+        RenderPath { gles, version, instancing, vertex_arrays }
+    }
+
+    /// Maximum number of cells drawn at once
+    ///
+    /// Without instanced rendering every vertex needs an index, which is
+    /// limited to 16 bits on OpenGL ES 2.0.
+    fn batch_max(&self) -> usize {
+        if self.instancing {
+            BATCH_MAX
+        } else {
+            NON_INSTANCED_BATCH_MAX
+        }
+    }
+}
+
+impl fmt::Display for RenderPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (api, blending) = if self.gles {
+            ("OpenGL ES", "alpha")
+        } else {
+            ("OpenGL", "dual-source")
+        };
+        let instancing = if self.instancing { "with" } else { "without" };
+
+        write!(
+            f,
+            "{} {}.{}, {} blending, {} instanced rendering",
+            api, self.version.0, self.version.1, blending, instancing
+        )
+    }
+}
+
+/// Whether the version is OpenGL ES, and its major and minor number
+///
+/// Desktop versions look like `4.6.0 NVIDIA 390.87`, OpenGL ES versions
+/// like `OpenGL ES 3.2 Mesa 18.2.2`.
+fn parse_gl_version(version: &str) -> (bool, (u32, u32)) {
+    let gles = version.starts_with("OpenGL ES");
+
+    let number = version
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or("");
+    let mut parts = number.split('.').map(|part| {
+        let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().unwrap_or(0)
+    });
+    let major = parts.next().unwrap_or(0);
+    let minor = parts.next().unwrap_or(0);
+
+    (gles, (major, minor))
+}
+
+fn gl_string(name: GLenum) -> String {
+    unsafe {
+        let string = gl::GetString(name);
+        if string.is_null() {
+            return String::new();
+        }
+
+        CStr::from_ptr(string as *const _).to_string_lossy().into_owned()
+    }
+}
+
+/// Extensions of an OpenGL ES context
+fn gl_extensions() -> Vec<String> {
+    gl_string(gl::EXTENSIONS).split_whitespace().map(str::to_owned).collect()
+}
 
 /// `LoadGlyph` allows for copying a rasterized glyph into graphics memory
 pub trait LoadGlyph {
@@ -117,13 +293,27 @@ pub struct ShaderProgram {
 
     /// Background pass flag
     ///
-    /// Rendering is split into two passes; 1 for backgrounds, and one for text
+    /// Rendering is split into two passes; 1 for backgrounds, and one for text.
+    /// Rectangles like the cursor are drawn by a third pass, see `Pass`.
     u_background: GLint,
 
+    /// Gamma-correct text blending flag
+    u_gamma_correct: GLint,
+
     padding_x: u8,
     padding_y: u8,
 }
 
+/// Value of the background pass flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pass {
+    Text = 0,
+    Background = 1,
+    /// Solid rectangles within cells, the glyph offset and size of an
+    /// instance are the position and size of its rectangle
+    Rects = 2,
+}
+
 #[derive(Debug, Clone)]
 pub struct Glyph {
     tex_id: GLuint,
@@ -137,6 +327,14 @@ pub struct Glyph {
     uv_height: f32,
 }
 
+/// Style synthesized from the regular font, for families without that style
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Synthesis {
+    /// Bold, with the strength of the emboldening in pixels
+    Bold(u8),
+    Italic,
+}
+
 /// Naïve glyph cache
 ///
 /// Currently only keyed by `char`, and thus not possible to hold different
@@ -157,6 +355,12 @@ pub struct GlyphCache {
     /// bold font
     bold_key: FontKey,
 
+    /// Keys of synthesized styles, with the font they're synthesized from
+    synthesized: HashMap<FontKey, (FontKey, Synthesis)>,
+
+    /// Synthesized glyphs are clipped to the width of the cells
+    cell_width: i32,
+
     /// font size
     font_size: font::Size,
 
@@ -175,7 +379,9 @@ impl GlyphCache {
     where
         L: LoadGlyph,
     {
-        let (regular, bold, italic) = Self::compute_font_keys(font, &mut rasterizer)?;
+        let mut synthesized = HashMap::new();
+        let (regular, bold, italic) =
+            Self::compute_font_keys(font, &mut rasterizer, &mut synthesized)?;
 
         // Need to load at least one glyph for the face before calling metrics.
         // The glyph requested here ('m' at the time of writing) has no special
@@ -191,6 +397,8 @@ impl GlyphCache {
             font_key: regular,
             bold_key: bold,
             italic_key: italic,
+            synthesized,
+            cell_width: Self::cell_width(font, &metrics),
             glyph_offset: *font.glyph_offset(),
             metrics,
         };
@@ -214,42 +422,81 @@ impl GlyphCache {
     }
 
     /// Computes font keys for (Regular, Bold, Italic)
+    ///
+    /// Bold and italic fonts which fell back to the regular font get keys of
+    /// their own in `synthesized`, unless their synthesis is disabled.
     fn compute_font_keys(
         font: &config::Font,
         rasterizer: &mut Rasterizer,
+        synthesized: &mut HashMap<FontKey, (FontKey, Synthesis)>,
     ) -> Result<(FontKey, FontKey, FontKey), font::Error> {
         let size = font.size();
 
         // Load regular font
-        let regular_desc = Self::make_desc(&font.normal, font::Slant::Normal, font::Weight::Normal);
+        let regular_desc = Self::make_desc(font, &font.normal, font::Slant::Normal, font::Weight::Normal);
 
-        let regular = rasterizer.load_font(&regular_desc, size)?;
+        let regular = Self::load_or_default(rasterizer, &regular_desc, size)?;
 
         // helper to load a description if it is not the regular_desc
         let mut load_or_regular = |desc: FontDesc| {
             if desc == regular_desc {
                 regular
             } else {
-                rasterizer
-                    .load_font(&desc, size)
-                    .unwrap_or_else(|_| regular)
+                Self::load_or_default(rasterizer, &desc, size).unwrap_or_else(|_| regular)
             }
         };
 
         // Load bold font
-        let bold_desc = Self::make_desc(&font.bold, font::Slant::Normal, font::Weight::Bold);
+        let bold_desc = Self::make_desc(font, &font.bold, font::Slant::Normal, font::Weight::Bold);
 
         let bold = load_or_regular(bold_desc);
 
         // Load italic font
-        let italic_desc = Self::make_desc(&font.italic, font::Slant::Italic, font::Weight::Normal);
+        let italic_desc = Self::make_desc(font, &font.italic, font::Slant::Italic, font::Weight::Normal);
 
         let italic = load_or_regular(italic_desc);
 
+        let synthesize = font.synthesize();
+        let mut synthesized_key = |key: FontKey, enabled: bool, synthesis: Synthesis| {
+            if key != regular || !enabled {
+                return key;
+            }
+
+            let synthetic = FontKey::next();
+            synthesized.insert(synthetic, (regular, synthesis));
+            synthetic
+        };
+        let bold_synthesis = Synthesis::Bold(synthesize.bold_strength);
+        let bold = synthesized_key(bold, synthesize.bold, bold_synthesis);
+        let italic = synthesized_key(italic, synthesize.italic, Synthesis::Italic);
+
         Ok((regular, bold, italic))
     }
 
+    /// Width of the cells in pixels
+    fn cell_width(font: &config::Font, metrics: &font::Metrics) -> i32 {
+        (metrics.average_advance + f64::from(font.offset().x)).floor() as i32
+    }
+
+    /// Load a font, falling back to the default family of the platform
+    fn load_or_default(
+        rasterizer: &mut Rasterizer,
+        desc: &FontDesc,
+        size: font::Size,
+    ) -> Result<FontKey, font::Error> {
+        rasterizer.load_font(desc, size).or_else(|err| {
+            let default = desc.with_family(font::DEFAULT_FAMILY);
+            if default == *desc {
+                return Err(err);
+            }
+
+            warn!("{}; falling back to {}", err, font::DEFAULT_FAMILY);
+            rasterizer.load_font(&default, size)
+        })
+    }
+
     fn make_desc(
+        font: &config::Font,
         desc: &config::FontDescription,
         slant: font::Slant,
         weight: font::Weight,
@@ -259,7 +506,13 @@ impl GlyphCache {
         } else {
             font::Style::Description { slant, weight }
         };
-        FontDesc::new(&desc.family[..], style)
+        FontDesc::new(&desc.family[..], style).with_features(font.features(desc).to_vec())
+    }
+
+    /// Change how glyphs are rasterized, which takes effect once the cache is
+    /// rebuilt with `update_font_size`
+    pub fn set_rendering(&mut self, rendering: font::Rendering) {
+        self.rasterizer.set_rendering(rendering);
     }
 
     pub fn font_metrics(&self) -> font::Metrics {
@@ -274,11 +527,24 @@ impl GlyphCache {
         let glyph_offset = self.glyph_offset;
         let rasterizer = &mut self.rasterizer;
         let metrics = &self.metrics;
+        let synthesized = &self.synthesized;
+        let cell_width = self.cell_width;
         self.cache
             .entry(glyph_key)
             .or_insert_with(|| {
-                let mut rasterized = rasterizer.get_glyph(glyph_key)
-                    .unwrap_or_else(|_| Default::default());
+                let mut rasterized = match synthesized.get(&glyph_key.font_key) {
+                    Some(&(font_key, synthesis)) => {
+                        let regular = rasterizer.get_glyph(GlyphKey { font_key, ..glyph_key })
+                            .unwrap_or_else(|_| Default::default());
+                        match synthesis {
+                            Synthesis::Bold(strength) => {
+                                synthesis::embolden(&regular, strength, cell_width)
+                            },
+                            Synthesis::Italic => synthesis::oblique(&regular, cell_width),
+                        }
+                    },
+                    None => rasterizer.get_glyph(glyph_key).unwrap_or_else(|_| Default::default()),
+                };
 
                 rasterized.left += i32::from(glyph_offset.x);
                 rasterized.top += i32::from(glyph_offset.y);
@@ -301,7 +567,9 @@ impl GlyphCache {
         // Recompute font keys
         let font = font.to_owned().with_size(size);
         info!("Font size changed: {:?}", font.size);
-        let (regular, bold, italic) = Self::compute_font_keys(&font, &mut self.rasterizer)?;
+        self.synthesized.clear();
+        let (regular, bold, italic) =
+            Self::compute_font_keys(&font, &mut self.rasterizer, &mut self.synthesized)?;
 
         if let Some(dpr) = new_dpr {
             self.rasterizer.set_device_pixel_ratio(dpr);
@@ -311,9 +579,11 @@ impl GlyphCache {
         let metrics = self.rasterizer.metrics(regular, size)?;
 
         self.font_size = font.size;
+        self.glyph_offset = *font.glyph_offset();
         self.font_key = regular;
         self.bold_key = bold;
         self.italic_key = italic;
+        self.cell_width = Self::cell_width(&font, &metrics);
         self.metrics = metrics;
 
         self.load_glyphs_for_font(regular, loader);
@@ -324,7 +594,7 @@ impl GlyphCache {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[repr(C)]
 struct InstanceData {
     // coords
@@ -353,9 +623,19 @@ struct InstanceData {
     bg_a: f32,
 }
 
+/// Corner of a cell, for drawing without instanced rendering
+#[derive(Debug)]
+#[repr(C)]
+struct CellVertex {
+    x: f32,
+    y: f32,
+    cell: InstanceData,
+}
+
 #[derive(Debug)]
 pub struct QuadRenderer {
     program: ShaderProgram,
+    path: RenderPath,
     vao: GLuint,
     vbo: GLuint,
     ebo: GLuint,
@@ -364,17 +644,21 @@ pub struct QuadRenderer {
     current_atlas: usize,
     active_tex: GLuint,
     batch: Batch,
+    vertices: Vec<CellVertex>,
     rx: mpsc::Receiver<Msg>,
 }
 
 #[derive(Debug)]
 pub struct RenderApi<'a> {
     active_tex: &'a mut GLuint,
+    path: RenderPath,
     batch: &'a mut Batch,
+    vertices: &'a mut Vec<CellVertex>,
     atlas: &'a mut Vec<Atlas>,
     current_atlas: &'a mut usize,
     program: &'a mut ShaderProgram,
     config: &'a Config,
+    size_info: &'a term::SizeInfo,
     visual_bell_intensity: f32,
 }
 
@@ -395,17 +679,50 @@ pub struct PackedVertex {
 pub struct Batch {
     tex: GLuint,
     instances: Vec<InstanceData>,
+    max: usize,
 }
 
 impl Batch {
     #[inline]
-    pub fn new() -> Batch {
+    pub fn new(max: usize) -> Batch {
         Batch {
             tex: 0,
-            instances: Vec::with_capacity(BATCH_MAX),
+            instances: Vec::with_capacity(max),
+            max,
         }
     }
 
+    /// Add a solid rectangle of the cursor, which is drawn by `Pass::Rects`
+    pub fn add_rect(&mut self, cursor: &RenderableCursor, rect: &CursorRect, tex: GLuint) {
+        if self.is_empty() {
+            self.tex = tex;
+        }
+
+        self.instances.push(InstanceData {
+            col: cursor.column.0 as f32,
+            row: cursor.line.0 as f32,
+
+            top: rect.y,
+            left: rect.x,
+            width: rect.width,
+            height: rect.height,
+
+            uv_bot: 0.0,
+            uv_left: 0.0,
+            uv_width: 0.0,
+            uv_height: 0.0,
+
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+
+            bg_r: f32::from(cursor.color.r),
+            bg_g: f32::from(cursor.color.g),
+            bg_b: f32::from(cursor.color.b),
+            bg_a: 1.0,
+        });
+    }
+
     pub fn add_item(&mut self, cell: &RenderableCell, glyph: &Glyph) {
         if self.is_empty() {
             self.tex = glyph.tex_id;
@@ -448,7 +765,7 @@ impl Batch {
 
     #[inline]
     pub fn capacity(&self) -> usize {
-        BATCH_MAX
+        self.max
     }
 
     #[inline]
@@ -467,14 +784,106 @@ impl Batch {
     }
 }
 
+/// Width of the beam and height of the underline, relative to the cell width
+const CURSOR_THICKNESS: f32 = 0.15;
+
+/// Rectangle of a cursor, relative to the top-left of the cursor's cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CursorRect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+/// Rectangles drawn for a cursor covering `cells` cells
+///
+/// The block cursor has none, its cell is drawn in the cursor colors instead,
+/// which keeps the text blended with the cursor color.
+fn cursor_rects(
+    shape: CursorStyle,
+    cells: usize,
+    cell_width: f32,
+    cell_height: f32,
+) -> Vec<CursorRect> {
+    let width = cell_width * cells as f32;
+    let thickness = (cell_width * CURSOR_THICKNESS).floor().max(1.0);
+    let rect = |x, y, width, height| CursorRect { x, y, width, height };
+
+    match shape {
+        CursorStyle::Block => Vec::new(),
+        CursorStyle::Beam => vec![rect(0.0, 0.0, thickness, cell_height)],
+        CursorStyle::Underline => {
+            vec![rect(0.0, cell_height - thickness, width, thickness)]
+        },
+        CursorStyle::HollowBlock => {
+            let sides = (cell_height - 2.0 * thickness).max(0.0);
+            vec![
+                rect(0.0, 0.0, width, thickness),
+                rect(0.0, cell_height - thickness, width, thickness),
+                rect(0.0, thickness, thickness, sides),
+                rect(width - thickness, thickness, thickness, sides),
+            ]
+        },
+    }
+}
+
 /// Maximum items to be drawn in a batch.
 const BATCH_MAX: usize = 0x1_0000;
+/// Maximum items to be drawn in a batch without instanced rendering
+const NON_INSTANCED_BATCH_MAX: usize = 0x4000;
 const ATLAS_SIZE: i32 = 1024;
 
+/// Top right, Bottom right, Bottom left, Top left
+const QUAD_VERTICES: [PackedVertex; 4] = [
+    PackedVertex { x: 1.0, y: 1.0 },
+    PackedVertex { x: 1.0, y: 0.0 },
+    PackedVertex { x: 0.0, y: 0.0 },
+    PackedVertex { x: 0.0, y: 1.0 },
+];
+const QUAD_INDICES: [u16; 6] = [0, 1, 3, 1, 2, 3];
+
+/// Point the attributes of the cells at the `InstanceData` which is `offset`
+/// bytes into every element of the bound array buffer
+unsafe fn cell_attributes(stride: usize, offset: usize, instanced: bool) {
+    // Location, number of floats and index of the first float in `InstanceData`
+    let attributes = [
+        // coords
+        (1, 2, 0),
+        // glyphoffset
+        (2, 4, 2),
+        // uv
+        (3, 4, 6),
+        // color
+        (4, 3, 10),
+        // background color
+        (5, 4, 13),
+    ];
+
+    for &(location, size, start) in &attributes {
+        gl::VertexAttribPointer(
+            location,
+            size,
+            gl::FLOAT,
+            gl::FALSE,
+            stride as i32,
+            (offset + start * size_of::<f32>()) as *const _,
+        );
+        gl::EnableVertexAttribArray(location);
+        if instanced {
+            gl::VertexAttribDivisor(location, 1);
+        }
+    }
+}
+
 impl QuadRenderer {
     // TODO should probably hand this a transform instead of width/height
     pub fn new(config: &Config, size: Size<Pixels<u32>>) -> Result<QuadRenderer, Error> {
-        let program = ShaderProgram::new(config, size)?;
+        let path = RenderPath::detect(config.debug().renderer_preference);
+        info!("Rendering with {} on {}", path, gl_string(gl::RENDERER));
+
+        let program = ShaderProgram::new(config, size, path.gles)?;
+        let batch_max = path.batch_max();
 
         let mut vao: GLuint = 0;
         let mut vbo: GLuint = 0;
@@ -484,141 +893,128 @@ impl QuadRenderer {
 
         unsafe {
             gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
-            gl::Enable(gl::MULTISAMPLE);
+            if path.gles {
+                // Colors are premultiplied
+                gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+            } else {
+                gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
+                gl::Enable(gl::MULTISAMPLE);
+            }
 
-            gl::GenVertexArrays(1, &mut vao);
+            // Without vertex array objects the attributes are part of the
+            // global state, which isn't touched by anything else
+            if path.vertex_arrays {
+                gl::GenVertexArrays(1, &mut vao);
+                gl::BindVertexArray(vao);
+            }
             gl::GenBuffers(1, &mut vbo);
             gl::GenBuffers(1, &mut ebo);
             gl::GenBuffers(1, &mut vbo_instance);
-            gl::BindVertexArray(vao);
-
-            // ----------------------------
-            // setup vertex position buffer
-            // ----------------------------
-            // Top right, Bottom right, Bottom left, Top left
-            let vertices = [
-                PackedVertex { x: 1.0, y: 1.0 },
-                PackedVertex { x: 1.0, y: 0.0 },
-                PackedVertex { x: 0.0, y: 0.0 },
-                PackedVertex { x: 0.0, y: 1.0 },
-            ];
-
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-
-            gl::VertexAttribPointer(
-                0,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<PackedVertex>() as i32,
-                ptr::null(),
-            );
-            gl::EnableVertexAttribArray(0);
 
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (size_of::<PackedVertex>() * vertices.len()) as GLsizeiptr,
-                vertices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
+            if path.instancing {
+                // ----------------------------
+                // setup vertex position buffer
+                // ----------------------------
+                gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+                gl::VertexAttribPointer(
+                    0,
+                    2,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    size_of::<PackedVertex>() as i32,
+                    ptr::null(),
+                );
+                gl::EnableVertexAttribArray(0);
 
-            // ---------------------
-            // Set up element buffer
-            // ---------------------
-            let indices: [u32; 6] = [0, 1, 3, 1, 2, 3];
-
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (6 * size_of::<u32>()) as isize,
-                indices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (size_of::<PackedVertex>() * QUAD_VERTICES.len()) as GLsizeiptr,
+                    QUAD_VERTICES.as_ptr() as *const _,
+                    gl::STATIC_DRAW,
+                );
 
-            // ----------------------------
-            // Setup vertex instance buffer
-            // ----------------------------
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo_instance);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (BATCH_MAX * size_of::<InstanceData>()) as isize,
-                ptr::null(),
-                gl::STREAM_DRAW,
-            );
-            // coords
-            gl::VertexAttribPointer(
-                1,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                ptr::null(),
-            );
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribDivisor(1, 1);
-            // glyphoffset
-            gl::VertexAttribPointer(
-                2,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (2 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribDivisor(2, 1);
-            // uv
-            gl::VertexAttribPointer(
-                3,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (6 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(3);
-            gl::VertexAttribDivisor(3, 1);
-            // color
-            gl::VertexAttribPointer(
-                4,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (10 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(4);
-            gl::VertexAttribDivisor(4, 1);
-            // color
-            gl::VertexAttribPointer(
-                5,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (13 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(5);
-            gl::VertexAttribDivisor(5, 1);
+                // ---------------------
+                // Set up element buffer
+                // ---------------------
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+                gl::BufferData(
+                    gl::ELEMENT_ARRAY_BUFFER,
+                    (QUAD_INDICES.len() * size_of::<u16>()) as isize,
+                    QUAD_INDICES.as_ptr() as *const _,
+                    gl::STATIC_DRAW,
+                );
+
+                // ----------------------------
+                // Setup vertex instance buffer
+                // ----------------------------
+                gl::BindBuffer(gl::ARRAY_BUFFER, vbo_instance);
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (batch_max * size_of::<InstanceData>()) as isize,
+                    ptr::null(),
+                    gl::STREAM_DRAW,
+                );
+                cell_attributes(size_of::<InstanceData>(), 0, true);
+            } else {
+                // ---------------------------------------------
+                // Set up element buffer for the corners of cells
+                // ---------------------------------------------
+                let indices = (0..batch_max)
+                    .flat_map(|cell| QUAD_INDICES.iter().map(move |&i| (4 * cell) as u16 + i))
+                    .collect::<Vec<u16>>();
+
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+                gl::BufferData(
+                    gl::ELEMENT_ARRAY_BUFFER,
+                    (indices.len() * size_of::<u16>()) as isize,
+                    indices.as_ptr() as *const _,
+                    gl::STATIC_DRAW,
+                );
+
+                // ------------------------------------------
+                // Setup vertex buffer with the data of cells
+                // ------------------------------------------
+                gl::BindBuffer(gl::ARRAY_BUFFER, vbo_instance);
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (4 * batch_max * size_of::<CellVertex>()) as isize,
+                    ptr::null(),
+                    gl::STREAM_DRAW,
+                );
 
-            gl::BindVertexArray(0);
+                gl::VertexAttribPointer(
+                    0,
+                    2,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    size_of::<CellVertex>() as i32,
+                    ptr::null(),
+                );
+                gl::EnableVertexAttribArray(0);
+                cell_attributes(size_of::<CellVertex>(), 2 * size_of::<f32>(), false);
+            }
+
+            if path.vertex_arrays {
+                gl::BindVertexArray(0);
+            }
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
 
         let (msg_tx, msg_rx) = mpsc::channel();
 
         if cfg!(feature = "live-shader-reload") {
+            let (vertex_path, fragment_path) = shader_paths(path.gles);
             ::std::thread::spawn(move || {
                 let (tx, rx) = ::std::sync::mpsc::channel();
                 // The Duration argument is a debouncing period.
                 let mut watcher =
                     watcher(tx, Duration::from_millis(10)).expect("create file watcher");
                 watcher
-                    .watch(TEXT_SHADER_F_PATH, RecursiveMode::NonRecursive)
+                    .watch(fragment_path, RecursiveMode::NonRecursive)
                     .expect("watch fragment shader");
                 watcher
-                    .watch(TEXT_SHADER_V_PATH, RecursiveMode::NonRecursive)
+                    .watch(vertex_path, RecursiveMode::NonRecursive)
                     .expect("watch vertex shader");
 
                 loop {
@@ -639,6 +1035,7 @@ impl QuadRenderer {
 
         let mut renderer = QuadRenderer {
             program,
+            path,
             vao,
             vbo,
             ebo,
@@ -646,7 +1043,8 @@ impl QuadRenderer {
             atlas: Vec::new(),
             current_atlas: 0,
             active_tex: 0,
-            batch: Batch::new(),
+            batch: Batch::new(batch_max),
+            vertices: Vec::with_capacity(4 * batch_max),
             rx: msg_rx,
         };
 
@@ -685,7 +1083,14 @@ impl QuadRenderer {
             self.program.set_term_uniforms(props);
             self.program.set_visual_bell(visual_bell_intensity as _);
 
-            gl::BindVertexArray(self.vao);
+            // Text is blended with the cell's background, which is only what
+            // ends up on the screen when the window is opaque
+            let opaque = config.background_opacity().get() >= 1.0;
+            self.program.set_gamma_correct(config.font().gamma_correct() && opaque);
+
+            if self.path.vertex_arrays {
+                gl::BindVertexArray(self.vao);
+            }
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
             gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_instance);
             gl::ActiveTexture(gl::TEXTURE0);
@@ -693,18 +1098,23 @@ impl QuadRenderer {
 
         let res = func(RenderApi {
             active_tex: &mut self.active_tex,
+            path: self.path,
             batch: &mut self.batch,
+            vertices: &mut self.vertices,
             atlas: &mut self.atlas,
             current_atlas: &mut self.current_atlas,
             program: &mut self.program,
             visual_bell_intensity: visual_bell_intensity as _,
             config,
+            size_info: props,
         });
 
         unsafe {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
+            if self.path.vertex_arrays {
+                gl::BindVertexArray(0);
+            }
 
             self.program.deactivate();
         }
@@ -729,7 +1139,7 @@ impl QuadRenderer {
 
     pub fn reload_shaders(&mut self, config: &Config, size: Size<Pixels<u32>>) {
         warn!("Reloading shaders ...");
-        let program = match ShaderProgram::new(config, size) {
+        let program = match ShaderProgram::new(config, size, self.path.gles) {
             Ok(program) => {
                 warn!(" ... OK");
                 program
@@ -755,6 +1165,59 @@ impl QuadRenderer {
         self.program = program;
     }
 
+    /// Whether text is blended per color component, as subpixel antialiased
+    /// glyphs require
+    pub fn blends_subpixels(&self) -> bool {
+        !self.path.gles
+    }
+
+    /// Drop the renderer without deleting its objects
+    ///
+    /// The objects were destroyed along with a context which was lost, their
+    /// names might belong to the objects of a new context by now.
+    pub fn abandon(self) {
+        mem::forget(self);
+    }
+
+    /// Whether a reset of the GPU destroyed the objects of the context
+    ///
+    /// Only robust contexts report resets, others always look intact.
+    pub fn is_context_lost(&self) -> bool {
+        gl::GetGraphicsResetStatus::is_loaded()
+            && unsafe { gl::GetGraphicsResetStatus() } != gl::NO_ERROR
+    }
+
+    /// Pixels of the frame drawn last, as RGBA rows from top to bottom
+    pub fn read_pixels(&self, width: usize, height: usize) -> Vec<u8> {
+        let stride = width * 4;
+        let mut pixels = vec![0u8; stride * height];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+
+        // The rows of the framebuffer start at the bottom
+        let mut flipped = Vec::with_capacity(pixels.len());
+        for row in pixels.chunks(stride).rev() {
+            flipped.extend_from_slice(row);
+        }
+        flipped
+    }
+
+    /// Use the padding of a reloaded config, it's applied with the next resize
+    pub fn update_padding(&mut self, config: &Config) {
+        self.program.padding_x = config.padding().x;
+        self.program.padding_y = config.padding().y;
+    }
+
     pub fn resize(&mut self, width: i32, height: i32) {
         let padding_x = i32::from(self.program.padding_x);
         let padding_y = i32::from(self.program.padding_y);
@@ -791,14 +1254,7 @@ impl<'a> RenderApi<'a> {
     }
 
     fn render_batch(&mut self) {
-        unsafe {
-            gl::BufferSubData(
-                gl::ARRAY_BUFFER,
-                0,
-                self.batch.size() as isize,
-                self.batch.instances.as_ptr() as *const _,
-            );
-        }
+        self.upload_batch();
 
         // Bind texture if necessary
         if *self.active_tex != self.batch.tex {
@@ -809,32 +1265,116 @@ impl<'a> RenderApi<'a> {
         }
 
         unsafe {
-            self.program.set_background_pass(true);
+            // Backgrounds replace the window background, the dual-source
+            // blending does the same by using a mask of ones
+            if self.path.gles {
+                gl::Disable(gl::BLEND);
+            }
+            self.program.set_pass(Pass::Background);
+            self.draw_batch();
+
+            if self.path.gles {
+                gl::Enable(gl::BLEND);
+            }
+            self.program.set_pass(Pass::Text);
+            self.draw_batch();
+        }
+
+        self.batch.clear();
+    }
+
+    /// Draw the cursor over the cells rendered so far
+    ///
+    /// The cursor is a few solid rectangles, which don't depend on the font.
+    pub fn render_cursor(&mut self, cursor: &RenderableCursor) {
+        let rects = cursor_rects(
+            cursor.shape,
+            cursor.width,
+            self.size_info.cell_width,
+            self.size_info.cell_height,
+        );
+        if rects.is_empty() {
+            return;
+        }
+
+        if !self.batch.is_empty() {
+            self.render_batch();
+        }
+
+        // The texture isn't sampled by the rectangles
+        let tex = *self.active_tex;
+        for rect in &rects {
+            self.batch.add_rect(cursor, rect, tex);
+        }
+
+        self.upload_batch();
+        unsafe {
+            self.program.set_pass(Pass::Rects);
+            self.draw_batch();
+        }
+
+        self.batch.clear();
+    }
+
+    /// Copy the instances of the batch to the bound array buffer
+    fn upload_batch(&mut self) {
+        if self.path.instancing {
+            unsafe {
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    0,
+                    self.batch.size() as isize,
+                    self.batch.instances.as_ptr() as *const _,
+                );
+            }
+        } else {
+            self.vertices.clear();
+            for cell in &self.batch.instances {
+                for corner in &QUAD_VERTICES {
+                    self.vertices.push(CellVertex { x: corner.x, y: corner.y, cell: *cell });
+                }
+            }
+
+            unsafe {
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    0,
+                    (self.vertices.len() * size_of::<CellVertex>()) as isize,
+                    self.vertices.as_ptr() as *const _,
+                );
+            }
+        }
+    }
+
+    unsafe fn draw_batch(&self) {
+        if self.path.instancing {
             gl::DrawElementsInstanced(
                 gl::TRIANGLES,
-                6,
-                gl::UNSIGNED_INT,
+                QUAD_INDICES.len() as GLsizei,
+                gl::UNSIGNED_SHORT,
                 ptr::null(),
                 self.batch.len() as GLsizei,
             );
-            self.program.set_background_pass(false);
-            gl::DrawElementsInstanced(
+        } else {
+            gl::DrawElements(
                 gl::TRIANGLES,
-                6,
-                gl::UNSIGNED_INT,
+                (QUAD_INDICES.len() * self.batch.len()) as GLsizei,
+                gl::UNSIGNED_SHORT,
                 ptr::null(),
-                self.batch.len() as GLsizei,
             );
         }
-
-        self.batch.clear();
     }
-    /// Render a string in a predefined location. Used for printing render time for profiling and
-    /// optimization.
-    pub fn render_string(&mut self, string: &str, glyph_cache: &mut GlyphCache, color: Rgb) {
-        let line = Line(23);
-        let col = Column(0);
 
+    /// Draw a string over the cells starting at `line` and `col`
+    pub fn render_string(
+        &mut self,
+        string: &str,
+        line: Line,
+        col: Column,
+        glyph_cache: &mut GlyphCache,
+        fg: Rgb,
+        bg: Rgb,
+    ) {
         let cells = string
             .chars()
             .enumerate()
@@ -842,8 +1382,8 @@ impl<'a> RenderApi<'a> {
                 line,
                 column: col + i,
                 c,
-                bg: color,
-                fg: Rgb { r: 0, g: 0, b: 0 },
+                bg,
+                fg,
                 flags: cell::Flags::empty(),
                 bg_alpha: 1.0,
             })
@@ -1010,21 +1550,20 @@ impl ShaderProgram {
     pub fn new(
         config: &Config,
         size: Size<Pixels<u32>>,
+        gles: bool,
     ) -> Result<ShaderProgram, ShaderCreationError> {
-        let vertex_source = if cfg!(feature = "live-shader-reload") {
-            None
+        let (vertex_path, fragment_path) = shader_paths(gles);
+        let (vertex_source, frag_source) = if cfg!(feature = "live-shader-reload") {
+            (None, None)
+        } else if gles {
+            (Some(TEXT_SHADER_ES_V), Some(TEXT_SHADER_ES_F))
         } else {
-            Some(TEXT_SHADER_V)
+            (Some(TEXT_SHADER_V), Some(TEXT_SHADER_F))
         };
         let vertex_shader =
-            ShaderProgram::create_shader(TEXT_SHADER_V_PATH, gl::VERTEX_SHADER, vertex_source)?;
-        let frag_source = if cfg!(feature = "live-shader-reload") {
-            None
-        } else {
-            Some(TEXT_SHADER_F)
-        };
+            ShaderProgram::create_shader(vertex_path, gl::VERTEX_SHADER, vertex_source)?;
         let fragment_shader =
-            ShaderProgram::create_shader(TEXT_SHADER_F_PATH, gl::FRAGMENT_SHADER, frag_source)?;
+            ShaderProgram::create_shader(fragment_path, gl::FRAGMENT_SHADER, frag_source)?;
         let program = ShaderProgram::create_program(vertex_shader, fragment_shader)?;
 
         unsafe {
@@ -1048,13 +1587,14 @@ impl ShaderProgram {
         }
 
         // get uniform locations
-        let (projection, term_dim, cell_dim, visual_bell, background) = unsafe {
+        let (projection, term_dim, cell_dim, visual_bell, background, gamma_correct) = unsafe {
             (
                 gl::GetUniformLocation(program, cptr!(b"projection\0")),
                 gl::GetUniformLocation(program, cptr!(b"termDim\0")),
                 gl::GetUniformLocation(program, cptr!(b"cellDim\0")),
                 gl::GetUniformLocation(program, cptr!(b"visualBell\0")),
                 gl::GetUniformLocation(program, cptr!(b"backgroundPass\0")),
+                gl::GetUniformLocation(program, cptr!(b"gammaCorrect\0")),
             )
         };
 
@@ -1067,6 +1607,7 @@ impl ShaderProgram {
             u_cell_dim: cell_dim,
             u_visual_bell: visual_bell,
             u_background: background,
+            u_gamma_correct: gamma_correct,
             padding_x: config.padding().x,
             padding_y: config.padding().y,
         };
@@ -1126,11 +1667,17 @@ impl ShaderProgram {
         }
     }
 
-    fn set_background_pass(&self, background_pass: bool) {
-        let value = if background_pass { 1 } else { 0 };
+    fn set_pass(&self, pass: Pass) {
+        unsafe {
+            gl::Uniform1i(self.u_background, pass as GLint);
+        }
+    }
+
+    fn set_gamma_correct(&self, gamma_correct: bool) {
+        let value = if gamma_correct { 1 } else { 0 };
 
         unsafe {
-            gl::Uniform1i(self.u_background, value);
+            gl::Uniform1i(self.u_gamma_correct, value);
         }
     }
 
@@ -1139,6 +1686,9 @@ impl ShaderProgram {
             let program = gl::CreateProgram();
             gl::AttachShader(program, vertex);
             gl::AttachShader(program, fragment);
+            for (location, name) in ATTRIBUTES.iter().enumerate() {
+                gl::BindAttribLocation(program, location as GLuint, name.as_ptr() as *const _);
+            }
             gl::LinkProgram(program);
 
             let mut success: GLint = 0;
@@ -1195,6 +1745,22 @@ impl ShaderProgram {
     }
 }
 
+impl Drop for QuadRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            for atlas in &self.atlas {
+                gl::DeleteTextures(1, &atlas.id);
+            }
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteBuffers(1, &self.ebo);
+            gl::DeleteBuffers(1, &self.vbo_instance);
+            if self.path.vertex_arrays {
+                gl::DeleteVertexArrays(1, &self.vao);
+            }
+        }
+    }
+}
+
 impl Drop for ShaderProgram {
     fn drop(&mut self) {
         unsafe {
@@ -1259,6 +1825,15 @@ fn get_shader_info_log(shader: GLuint) -> String {
     String::from_utf8(buf).unwrap()
 }
 
+/// Paths of the vertex and fragment shader
+fn shader_paths(gles: bool) -> (&'static str, &'static str) {
+    if gles {
+        (TEXT_SHADER_ES_V_PATH, TEXT_SHADER_ES_F_PATH)
+    } else {
+        (TEXT_SHADER_V_PATH, TEXT_SHADER_F_PATH)
+    }
+}
+
 fn read_file(path: &str) -> Result<String, io::Error> {
     let mut f = File::open(path)?;
     let mut buf = String::new();
@@ -1513,3 +2088,40 @@ impl Atlas {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ansi::CursorStyle;
+
+    use super::{cursor_rects, parse_gl_version, CursorRect};
+
+    #[test]
+    fn gl_versions() {
+        assert_eq!(parse_gl_version("4.6.0 NVIDIA 390.87"), (false, (4, 6)));
+        assert_eq!(parse_gl_version("3.3 (Core Profile) Mesa 18.2.2"), (false, (3, 3)));
+        assert_eq!(parse_gl_version("OpenGL ES 2.0 Mesa 18.2.2"), (true, (2, 0)));
+        assert_eq!(parse_gl_version("OpenGL ES 3.1-build.23"), (true, (3, 1)));
+        assert_eq!(parse_gl_version(""), (false, (0, 0)));
+    }
+
+    #[test]
+    fn cursor_shapes() {
+        let rect = |x, y, width, height| CursorRect { x, y, width, height };
+
+        assert!(cursor_rects(CursorStyle::Block, 1, 10.0, 20.0).is_empty());
+        assert_eq!(cursor_rects(CursorStyle::Beam, 2, 10.0, 20.0), vec![rect(0.0, 0.0, 1.0, 20.0)]);
+        assert_eq!(
+            cursor_rects(CursorStyle::Underline, 2, 20.0, 40.0),
+            vec![rect(0.0, 37.0, 40.0, 3.0)]
+        );
+        assert_eq!(cursor_rects(CursorStyle::HollowBlock, 1, 20.0, 40.0), vec![
+            rect(0.0, 0.0, 20.0, 3.0),
+            rect(0.0, 37.0, 20.0, 3.0),
+            rect(0.0, 3.0, 3.0, 34.0),
+            rect(17.0, 3.0, 3.0, 34.0),
+        ]);
+
+        // Small cells still have a visible cursor
+        assert_eq!(cursor_rects(CursorStyle::Beam, 1, 4.0, 8.0), vec![rect(0.0, 0.0, 1.0, 8.0)]);
+    }
+}
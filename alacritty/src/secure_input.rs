@@ -0,0 +1,86 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Secure keyboard entry on macOS
+//!
+//! While secure keyboard entry is enabled, other processes can't read the
+//! keyboard input through event taps. macOS keeps a per process count of the
+//! requests to enable it, so every `SecureInput` guard enables it once and
+//! disables it again when dropped, which also happens when unwinding from a
+//! panic.
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+/// Number of guards which are currently alive
+static ACTIVE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    pub type OSStatus = i32;
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        pub fn EnableSecureEventInput() -> OSStatus;
+        pub fn DisableSecureEventInput() -> OSStatus;
+    }
+}
+
+/// Guard which keeps secure keyboard entry enabled while it is alive
+pub struct SecureInput {
+    _private: (),
+}
+
+impl SecureInput {
+    /// Enable secure keyboard entry
+    ///
+    /// Returns `None` when secure keyboard entry isn't available.
+    #[cfg(target_os = "macos")]
+    pub fn enable() -> Option<SecureInput> {
+        let status = unsafe { ffi::EnableSecureEventInput() };
+        if status != 0 {
+            warn!("Unable to enable secure keyboard entry: {}", status);
+            return None;
+        }
+
+        ACTIVE.fetch_add(1, Ordering::SeqCst);
+        info!("Enabled secure keyboard entry");
+
+        Some(SecureInput { _private: () })
+    }
+
+    /// Enable secure keyboard entry
+    ///
+    /// Returns `None` when secure keyboard entry isn't available.
+    #[cfg(not(target_os = "macos"))]
+    pub fn enable() -> Option<SecureInput> {
+        warn!("Secure keyboard entry is only supported on macOS");
+        None
+    }
+
+    /// Whether secure keyboard entry is enabled by any guard
+    pub fn is_active() -> bool {
+        ACTIVE.load(Ordering::SeqCst) > 0
+    }
+}
+
+impl Drop for SecureInput {
+    fn drop(&mut self) {
+        #[cfg(target_os = "macos")]
+        unsafe {
+            ffi::DisableSecureEventInput();
+        }
+
+        ACTIVE.fetch_sub(1, Ordering::SeqCst);
+        info!("Disabled secure keyboard entry");
+    }
+}
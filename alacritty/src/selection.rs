@@ -106,6 +106,67 @@ impl Selection {
         }
     }
 
+    /// Drop the selected lines above `max_line`
+    ///
+    /// Returns `false` when none of the selected lines are left.
+    pub fn truncate(&mut self, max_line: isize) -> bool {
+        if self.line_range().start > max_line {
+            return false;
+        }
+
+        let clamp = |point: &mut Point<isize>| {
+            if point.line > max_line {
+                point.line = max_line;
+                point.col = Column(0);
+            }
+        };
+
+        match *self {
+            Selection::Simple { ref mut region } => {
+                if region.start.point.line > max_line {
+                    clamp(&mut region.start.point);
+                    region.start.side = Side::Left;
+                }
+                if region.end.point.line > max_line {
+                    clamp(&mut region.end.point);
+                    region.end.side = Side::Left;
+                }
+            },
+            Selection::Semantic { ref mut region } => {
+                clamp(&mut region.start);
+                clamp(&mut region.end);
+            },
+            Selection::Lines { ref mut region, ref mut initial_line } => {
+                clamp(&mut region.start);
+                clamp(&mut region.end);
+                *initial_line = min(*initial_line, max_line);
+            },
+        }
+
+        true
+    }
+
+    /// Whether any of the buffer lines in `lines` are part of the selection
+    pub fn intersects_lines(&self, lines: Range<isize>) -> bool {
+        let selected = self.line_range();
+        selected.start < lines.end && lines.start < selected.end
+    }
+
+    /// Buffer lines touched by the selection
+    fn line_range(&self) -> Range<isize> {
+        let (start, end) = match *self {
+            Selection::Simple { ref region } => (region.start.point.line, region.end.point.line),
+            Selection::Semantic { ref region } => (region.start.line, region.end.line),
+            Selection::Lines { ref region, initial_line } => {
+                let start = min(initial_line, min(region.start.line, region.end.line));
+                let end = max(initial_line, max(region.start.line, region.end.line));
+                (start, end)
+            },
+        };
+
+        min(start, end)..max(start, end) + 1
+    }
+
     pub fn semantic(point: Point<usize>) -> Selection {
         Selection::Semantic {
             region: Range {
@@ -371,7 +432,7 @@ pub struct Span {
     ty: SpanType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Locations {
     /// Start point from bottom of buffer
     pub start: Point<usize>,
@@ -0,0 +1,141 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! State kept between sessions
+//!
+//! With `window.remember_size`, the size of the window in pixels is written to
+//! `$XDG_STATE_HOME/alacritty/window_size` on exit and used as the initial size
+//! on the next start. macOS keeps it in `~/Library/Application Support` and
+//! Windows in `%LOCALAPPDATA%` instead. Missing or broken state is only logged,
+//! Alacritty falls back to the configured dimensions then.
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const WINDOW_SIZE_FILE: &str = "window_size";
+
+/// Window size in pixels from the last session
+pub fn load_window_size() -> Option<(u32, u32)> {
+    let path = state_dir()?.join(WINDOW_SIZE_FILE);
+    match read_window_size(&path) {
+        Ok(size) => size,
+        Err(err) => {
+            warn!("Unable to read window size from {}: {}", path.display(), err);
+            None
+        },
+    }
+}
+
+/// Keep the window size in pixels for the next session
+pub fn save_window_size(width: u32, height: u32) {
+    let dir = match state_dir() {
+        Some(dir) => dir,
+        None => {
+            warn!("Unable to save window size, no state directory found");
+            return;
+        },
+    };
+
+    let path = dir.join(WINDOW_SIZE_FILE);
+    if let Err(err) = write_window_size(&path, width, height) {
+        warn!("Unable to write window size to {}: {}", path.display(), err);
+    }
+}
+
+/// Size stored in the file at `path`, `None` without a file
+fn read_window_size(path: &Path) -> io::Result<Option<(u32, u32)>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    match parse_size(contents.trim()) {
+        Some(size) => Ok(Some(size)),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid window size")),
+    }
+}
+
+fn write_window_size(path: &Path, width: u32, height: u32) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{}x{}\n", width, height))
+}
+
+/// Parse `WIDTHxHEIGHT`, neither of which may be zero
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let mut parts = size.splitn(2, 'x');
+    let width = parts.next()?.parse().ok()?;
+    let height = parts.next()?.parse().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn state_dir() -> Option<PathBuf> {
+    // Relative paths in XDG variables are invalid and must be ignored
+    let state_home = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))?;
+    Some(state_home.join("alacritty"))
+}
+
+#[cfg(target_os = "macos")]
+fn state_dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME")?;
+    Some(Path::new(&home).join("Library/Application Support/alacritty"))
+}
+
+#[cfg(windows)]
+fn state_dir() -> Option<PathBuf> {
+    let local_app_data = env::var_os("LOCALAPPDATA")?;
+    Some(Path::new(&local_app_data).join("alacritty"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{read_window_size, write_window_size, parse_size};
+
+    #[test]
+    fn window_size_is_restored() {
+        let dir = ::std::env::temp_dir().join(format!("alacritty-state-{}", ::std::process::id()));
+        let path = dir.join("window_size");
+
+        assert_eq!(read_window_size(&path).unwrap(), None);
+
+        write_window_size(&path, 1280, 720).unwrap();
+        assert_eq!(read_window_size(&path).unwrap(), Some((1280, 720)));
+
+        fs::write(&path, "broken").unwrap();
+        assert!(read_window_size(&path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sizes_are_parsed() {
+        assert_eq!(parse_size("800x600"), Some((800, 600)));
+        assert_eq!(parse_size("0x600"), None);
+        assert_eq!(parse_size("800"), None);
+        assert_eq!(parse_size("800x600x1"), None);
+        assert_eq!(parse_size("-800x600"), None);
+    }
+}
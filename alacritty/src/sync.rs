@@ -15,6 +15,8 @@
 //! Synchronization types
 //!
 //! Most importantly, a fair mutex is included
+use std::time::{Duration, Instant};
+
 use parking_lot::{Mutex, MutexGuard};
 
 /// A fair mutex
@@ -44,4 +46,111 @@ impl<T> FairMutex<T> {
         let _next = self.next.lock();
         self.data.lock()
     }
+
+    /// Lock the mutex, unless it isn't available within `timeout`
+    ///
+    /// The turn is given up after timing out, so a busy thread using `lock`
+    /// could keep this from ever succeeding. Callers fall back to `lock`
+    /// after a few failed attempts.
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<MutexGuard<T>> {
+        let start = Instant::now();
+        let _next = self.next.try_lock_for(timeout)?;
+        let remaining = timeout.checked_sub(start.elapsed()).unwrap_or_default();
+        self.data.try_lock_for(remaining)
+    }
+}
+
+/// Time the main thread waits for the terminal before it skips its work
+const SKIPPING_LOCK_TIMEOUT_MS: u64 = 4;
+
+/// Attempts skipped in a row before the main thread waits for the terminal
+///
+/// The fair lock makes the I/O thread hand over the terminal once it's done
+/// with the current chunk of output.
+const MAX_SKIPPED_LOCKS: usize = 4;
+
+/// Locking of the terminal by the main thread, which gives up while it's busy
+///
+/// After a few attempts timed out in a row, the next one waits for the lock.
+#[derive(Debug, Default)]
+pub struct SkippingLock {
+    skipped: usize,
+}
+
+impl SkippingLock {
+    /// Lock the mutex, `None` if the attempt was skipped
+    pub fn lock<'a, T>(&mut self, mutex: &'a FairMutex<T>) -> Option<MutexGuard<'a, T>> {
+        let guard = if self.skipped < MAX_SKIPPED_LOCKS {
+            mutex.try_lock_for(Duration::from_millis(SKIPPING_LOCK_TIMEOUT_MS))
+        } else {
+            Some(mutex.lock())
+        };
+
+        match guard {
+            Some(_) => self.skipped = 0,
+            None => self.skipped += 1,
+        }
+        guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{FairMutex, SkippingLock, MAX_SKIPPED_LOCKS};
+
+    #[test]
+    fn try_lock_for_times_out() {
+        let mutex = Arc::new(FairMutex::new(0));
+        let guard = mutex.lock();
+
+        let other = Arc::clone(&mutex);
+        let locked = thread::spawn(move || {
+            other.try_lock_for(Duration::from_millis(10)).is_some()
+        }).join().unwrap();
+        assert!(!locked);
+
+        drop(guard);
+        assert!(mutex.try_lock_for(Duration::from_millis(10)).is_some());
+    }
+
+    /// Drawing waits for a writer which keeps the mutex busy after a few skipped frames
+    ///
+    /// The writer takes the place of the I/O thread, it only releases the mutex
+    /// well after the timeout of a skipped attempt.
+    #[test]
+    fn skipping_lock_waits_after_skipped_attempts() {
+        let mutex = Arc::new(FairMutex::new(0));
+        let (locked_tx, locked_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+
+        let writer = {
+            let mutex = Arc::clone(&mutex);
+            thread::spawn(move || {
+                let mut guard = mutex.lock();
+                locked_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+                thread::sleep(Duration::from_millis(50));
+                *guard = 1;
+            })
+        };
+        locked_rx.recv().unwrap();
+
+        let mut draw_lock = SkippingLock::default();
+        for _ in 0..MAX_SKIPPED_LOCKS {
+            assert!(draw_lock.lock(&mutex).is_none());
+        }
+
+        // The next attempt doesn't give up, it sees the output of the writer
+        release_tx.send(()).unwrap();
+        assert_eq!(draw_lock.lock(&mutex).map(|guard| *guard), Some(1));
+        writer.join().unwrap();
+
+        // Skipping starts over once the mutex was locked
+        let _guard = mutex.lock();
+        assert!(draw_lock.lock(&mutex).is_none());
+    }
 }
@@ -18,16 +18,18 @@ use index::Column;
 bitflags! {
     #[derive(Serialize, Deserialize)]
     pub struct Flags: u32 {
-        const INVERSE           = 0b0_0000_0001;
-        const BOLD              = 0b0_0000_0010;
-        const ITALIC            = 0b0_0000_0100;
-        const UNDERLINE         = 0b0_0000_1000;
-        const WRAPLINE          = 0b0_0001_0000;
-        const WIDE_CHAR         = 0b0_0010_0000;
-        const WIDE_CHAR_SPACER  = 0b0_0100_0000;
-        const DIM               = 0b0_1000_0000;
-        const DIM_BOLD          = 0b0_1000_0010;
-        const HIDDEN            = 0b1_0000_0000;
+        const INVERSE           = 0b00_0000_0001;
+        const BOLD              = 0b00_0000_0010;
+        const ITALIC            = 0b00_0000_0100;
+        const UNDERLINE         = 0b00_0000_1000;
+        const WRAPLINE          = 0b00_0001_0000;
+        const WIDE_CHAR         = 0b00_0010_0000;
+        const WIDE_CHAR_SPACER  = 0b00_0100_0000;
+        const DIM               = 0b00_1000_0000;
+        const DIM_BOLD          = 0b00_1000_0010;
+        const HIDDEN            = 0b01_0000_0000;
+        /// Kept by selective erases, set with DECSCA
+        const PROTECTED         = 0b10_0000_0000;
     }
 }
 
@@ -64,7 +66,7 @@ impl LineLength for grid::Row<Cell> {
             return Column(self.len());
         }
 
-        for (index, cell) in self[..].iter().rev().enumerate() {
+        for (index, cell) in self.iter().rev().enumerate() {
             if cell.c != ' ' {
                 length = Column(self.len() - index);
                 break;
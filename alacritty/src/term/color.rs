@@ -1,4 +1,4 @@
-use std::ops::{Index, IndexMut};
+use std::ops::{Index, IndexMut, Range};
 use std::fmt;
 
 use {Rgb, ansi};
@@ -61,8 +61,8 @@ impl List {
         self[ansi::NamedColor::Background] = colors.primary.background;
 
         // Foreground and background for custom cursor colors
-        self[ansi::NamedColor::CursorText] = colors.cursor.text.unwrap_or_else(Rgb::default);
-        self[ansi::NamedColor::Cursor]     = colors.cursor.cursor.unwrap_or_else(Rgb::default);
+        self[ansi::NamedColor::CursorText] = colors.cursor.text().rgb().unwrap_or_else(Rgb::default);
+        self[ansi::NamedColor::Cursor]     = colors.cursor.cursor().rgb().unwrap_or_else(Rgb::default);
 
         // Dims
         self[ansi::NamedColor::DimForeground] = colors
@@ -101,45 +101,25 @@ impl List {
         for r in 0..6 {
             for g in 0..6 {
                 for b in 0..6 {
-                    // Override colors 16..232 with the config (if present)
-                    if let Some(indexed_color) = colors
-                        .indexed_colors
-                        .iter()
-                        .find(|ic| ic.index == index as u8)
-                    {
-                        self[index] = indexed_color.color;
-                    } else {
-                        self[index] = Rgb { r: if r == 0 { 0 } else { r * 40 + 55 },
-                            b: if b == 0 { 0 } else { b * 40 + 55 },
-                            g: if g == 0 { 0 } else { g * 40 + 55 },
-                        };
-                    }
+                    self[index] = Rgb { r: if r == 0 { 0 } else { r * 40 + 55 },
+                        b: if b == 0 { 0 } else { b * 40 + 55 },
+                        g: if g == 0 { 0 } else { g * 40 + 55 },
+                    };
                     index += 1;
                 }
             }
         }
 
         debug_assert!(index == 232);
+
+        // Override colors 16..232 with the config (if present)
+        self.fill_indexed(colors, 16..232);
     }
 
     pub fn fill_gray_ramp(&mut self, colors: &Colors) {
         let mut index: usize = 232;
 
         for i in 0..24 {
-            // Index of the color is number of named colors + number of cube colors + i
-            let color_index = 16 + 216 + i;
-
-            // Override colors 232..256 with the config (if present)
-            if let Some(indexed_color) = colors
-                .indexed_colors
-                .iter()
-                .find(|ic| ic.index == color_index)
-            {
-                self[index] = indexed_color.color;
-                index += 1;
-                continue;
-            }
-
             let value = i * 10 + 8;
             self[index] = Rgb {
                 r: value,
@@ -150,6 +130,21 @@ impl List {
         }
 
         debug_assert!(index == 256);
+
+        // Override colors 232..256 with the config (if present)
+        self.fill_indexed(colors, 232..256);
+    }
+
+    /// Apply the `indexed_colors` of the config within `range`
+    ///
+    /// Later entries take precedence over earlier ones with the same index.
+    fn fill_indexed(&mut self, colors: &Colors, range: Range<usize>) {
+        for indexed_color in &colors.indexed_colors {
+            let index = indexed_color.index as usize;
+            if index >= range.start && index < range.end {
+                self[index] = indexed_color.color;
+            }
+        }
     }
 }
 
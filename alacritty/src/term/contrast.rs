@@ -0,0 +1,165 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Minimum contrast between the text and background of cells
+//!
+//! Text which is too close to its background is moved toward white or black,
+//! just far enough to reach the configured contrast ratio.
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
+use fnv::FnvHasher;
+
+use term::color::{self, List};
+use Rgb;
+
+const WHITE: Rgb = Rgb { r: 0xff, g: 0xff, b: 0xff };
+const BLACK: Rgb = Rgb { r: 0x00, g: 0x00, b: 0x00 };
+
+/// Steps of the search for the closest color with enough contrast
+///
+/// The result is within 1/1024 of the distance to white or black, which is
+/// below the resolution of the 8-bit channels.
+const STEPS: usize = 10;
+
+/// Ratio of two relative luminances, from 1 to 21
+#[inline]
+fn ratio(a: f64, b: f64) -> f64 {
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Color `t` of the way from `from` to `to`
+fn mix(from: Rgb, to: Rgb, t: f64) -> Rgb {
+    let channel = |from: u8, to: u8| {
+        (f64::from(from) + (f64::from(to) - f64::from(from)) * t).round() as u8
+    };
+
+    Rgb { r: channel(from.r, to.r), g: channel(from.g, to.g), b: channel(from.b, to.b) }
+}
+
+/// Enforces the minimum contrast while the cells of a frame are resolved
+pub struct MinimumContrast {
+    ratio: f64,
+
+    /// Luminance of the colors seen so far, starting with the palette
+    luminance: HashMap<Rgb, f64, BuildHasherDefault<FnvHasher>>,
+}
+
+impl MinimumContrast {
+    /// Nothing needs to be done for a ratio of 1, every color has that
+    pub fn new(ratio: f64, colors: &List) -> Option<MinimumContrast> {
+        if ratio <= 1.0 {
+            return None;
+        }
+
+        let mut luminance = HashMap::default();
+        for index in 0..color::COUNT {
+            let rgb = colors[index];
+            luminance.entry(rgb).or_insert_with(|| rgb.luminance());
+        }
+
+        Some(MinimumContrast { ratio, luminance })
+    }
+
+    fn luminance(&mut self, rgb: Rgb) -> f64 {
+        *self.luminance.entry(rgb).or_insert_with(|| rgb.luminance())
+    }
+
+    /// Text color with at least the minimum contrast to `bg`
+    ///
+    /// The text moves away from the background, toward white when it's
+    /// lighter and black when it's darker. When that side can't reach the
+    /// ratio, the other one is used if it gets closer to it.
+    pub fn apply(&mut self, fg: Rgb, bg: Rgb) -> Rgb {
+        let fg_luminance = self.luminance(fg);
+        let bg_luminance = self.luminance(bg);
+        if ratio(fg_luminance, bg_luminance) >= self.ratio {
+            return fg;
+        }
+
+        let white = (WHITE, ratio(1.0, bg_luminance));
+        let black = (BLACK, ratio(0.0, bg_luminance));
+        let (away, other) = if fg_luminance >= bg_luminance {
+            (white, black)
+        } else {
+            (black, white)
+        };
+        let target = if away.1 >= self.ratio || away.1 >= other.1 { away.0 } else { other.0 };
+
+        // The contrast only grows on the way to the target once the text
+        // passed the background, so the colors which are far enough are one
+        // range at the end
+        let (mut near, mut far) = (0.0, 1.0);
+        for _ in 0..STEPS {
+            let t = (near + far) / 2.;
+            if ratio(mix(fg, target, t).luminance(), bg_luminance) >= self.ratio {
+                far = t;
+            } else {
+                near = t;
+            }
+        }
+
+        mix(fg, target, far)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use term::color::List;
+    use config::Colors;
+    use Rgb;
+
+    use super::MinimumContrast;
+
+    fn gray(value: u8) -> Rgb {
+        Rgb { r: value, g: value, b: value }
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let colors = List::from(&Colors::default());
+        assert!(MinimumContrast::new(1.0, &colors).is_none());
+    }
+
+    #[test]
+    fn gray_on_gray() {
+        let colors = List::from(&Colors::default());
+        let mut contrast = MinimumContrast::new(4.5, &colors).unwrap();
+
+        // Colors with enough contrast are kept
+        assert_eq!(contrast.apply(gray(0xff), gray(0x00)), gray(0xff));
+
+        for &bg in &[0x00, 0x20, 0x60, 0x76, 0x77, 0x80, 0xa0, 0xff] {
+            for &fg in &[0x00, 0x20, 0x60, 0x77, 0x80, 0xa0, 0xff] {
+                let text = contrast.apply(gray(fg), gray(bg));
+                let bg = gray(bg);
+
+                // Around the middle only one of white or black reaches the ratio
+                let best = bg.contrast(gray(0xff)).max(bg.contrast(gray(0x00)));
+                assert!(text.contrast(bg) >= 4.5 || text.contrast(bg) == best);
+
+                // Just enough, one step less of the channels wouldn't do
+                let target = if text.r > bg.r { 0xff } else { 0x00 };
+                if text != gray(fg) && text != gray(target) {
+                    let closer = if target == 0xff { gray(text.r - 1) } else { gray(text.r + 1) };
+                    assert!(closer.contrast(bg) < 4.5);
+                }
+            }
+        }
+
+        // Equal colors move toward the side with more room
+        assert!(contrast.apply(gray(0x30), gray(0x30)).r > 0x30);
+        assert!(contrast.apply(gray(0xd0), gray(0xd0)).r < 0xd0);
+    }
+}
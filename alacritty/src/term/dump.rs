@@ -0,0 +1,120 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Snapshot of the terminal state for bug reports
+//!
+//! The `DumpState` action writes it as JSON, the format is described in
+//! `docs/state_dump.md`. Only the state which influences how the next output
+//! is interpreted is included, the cells of the grids are left out unless
+//! they're requested.
+use ansi::CursorStyle;
+use grid::Grid;
+use index::Point;
+use term::cell::Cell;
+use term::mode::TermMode;
+
+/// Names of the modes, as they appear in the dump
+const MODES: &[(&str, TermMode)] = &[
+    ("SHOW_CURSOR", TermMode::SHOW_CURSOR),
+    ("APP_CURSOR", TermMode::APP_CURSOR),
+    ("APP_KEYPAD", TermMode::APP_KEYPAD),
+    ("MOUSE_REPORT_CLICK", TermMode::MOUSE_REPORT_CLICK),
+    ("BRACKETED_PASTE", TermMode::BRACKETED_PASTE),
+    ("SGR_MOUSE", TermMode::SGR_MOUSE),
+    ("MOUSE_MOTION", TermMode::MOUSE_MOTION),
+    ("LINE_WRAP", TermMode::LINE_WRAP),
+    ("LINE_FEED_NEW_LINE", TermMode::LINE_FEED_NEW_LINE),
+    ("ORIGIN", TermMode::ORIGIN),
+    ("INSERT", TermMode::INSERT),
+    ("FOCUS_IN_OUT", TermMode::FOCUS_IN_OUT),
+    ("ALT_SCREEN", TermMode::ALT_SCREEN),
+    ("MOUSE_DRAG", TermMode::MOUSE_DRAG),
+    ("LEFT_RIGHT_MARGIN", TermMode::LEFT_RIGHT_MARGIN),
+    ("UTF8_MOUSE", TermMode::UTF8_MOUSE),
+    ("URXVT_MOUSE", TermMode::URXVT_MOUSE),
+];
+
+/// Names of the set modes
+pub fn mode_names(mode: TermMode) -> Vec<String> {
+    MODES.iter()
+        .filter(|&&(_, flag)| mode.contains(flag))
+        .map(|&(name, _)| name.to_owned())
+        .collect()
+}
+
+/// Terminal state written by the `DumpState` action
+///
+/// Dumps are read back by tests and tools which replay a bug report.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateDump {
+    pub lines: usize,
+    pub columns: usize,
+    /// Lines in the scrollback history
+    pub history: usize,
+    pub display_offset: usize,
+    pub alt_screen: bool,
+    pub modes: Vec<String>,
+    /// The next printed character wraps to the next line first
+    pub input_needs_wrap: bool,
+    pub eight_bit_controls: bool,
+    pub cursor: CursorDump,
+    pub saved_cursor: CursorDump,
+    pub saved_cursor_alt: CursorDump,
+    /// Style requested with `DECSCUSR`, the configured one is used without it
+    pub cursor_style: Option<CursorStyle>,
+    /// Top line and the line below the bottom of the scroll region
+    pub scroll_region: (usize, usize),
+    /// Left column and the column right of the right margin
+    pub left_right_margins: (usize, usize),
+    pub tab_stops: Vec<usize>,
+    /// Colors changed by the application
+    pub colors: Vec<ColorDump>,
+    pub grid: Option<Grid<Cell>>,
+    pub alt_grid: Option<Grid<Cell>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CursorDump {
+    pub point: Point,
+    /// Template of the printed cells, with the colors and attributes of SGR
+    pub pen: Cell,
+    /// Character sets designated as G0 to G3
+    pub charsets: Vec<String>,
+    /// Index of the character set which is invoked
+    pub active_charset: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColorDump {
+    pub index: usize,
+    /// Color as `#rrggbb`
+    pub color: String,
+    pub default: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use term::mode::TermMode;
+
+    use super::{mode_names, MODES};
+
+    #[test]
+    fn every_mode_has_a_name() {
+        let all = MODES.iter().fold(TermMode::NONE, |all, &(_, flag)| all | flag);
+        assert_eq!(all, TermMode::ANY);
+
+        let mode = TermMode::SHOW_CURSOR | TermMode::ORIGIN;
+        assert_eq!(mode_names(mode), vec!["SHOW_CURSOR", "ORIGIN"]);
+    }
+}
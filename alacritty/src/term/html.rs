@@ -0,0 +1,176 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! HTML export of terminal text
+//!
+//! The text is wrapped in a single `<pre>` element using the default colors,
+//! cells which differ from the default are wrapped in `<span>` elements with
+//! inline styles. Neighbouring cells with the same style share a span.
+use std::fmt::Write;
+
+use Rgb;
+
+/// Visual attributes of a cell
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Style {
+    pub fg: Rgb,
+    /// Background color, `None` for the default background
+    pub bg: Option<Rgb>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+/// Builds a `<pre>` element from styled characters
+pub struct Html {
+    text: String,
+    foreground: Rgb,
+    /// Style of the open span
+    span: Option<Style>,
+}
+
+impl Html {
+    pub fn new(foreground: Rgb, background: Rgb) -> Html {
+        let mut text = String::new();
+        let _ = write!(
+            text,
+            "<pre style=\"color:{};background-color:{}\">",
+            css_color(foreground),
+            css_color(background),
+        );
+
+        Html {
+            text,
+            foreground,
+            span: None,
+        }
+    }
+
+    /// Append a character
+    pub fn push(&mut self, c: char, style: Style) {
+        if self.span != Some(style) {
+            self.close_span();
+
+            if !self.is_default(&style) {
+                self.open_span(style);
+            }
+        }
+
+        match c {
+            '&' => self.text.push_str("&amp;"),
+            '<' => self.text.push_str("&lt;"),
+            '>' => self.text.push_str("&gt;"),
+            '"' => self.text.push_str("&quot;"),
+            c => self.text.push(c),
+        }
+    }
+
+    /// Start a new line
+    ///
+    /// Spans are closed at the end of the line, so backgrounds don't extend
+    /// past the last cell.
+    pub fn newline(&mut self) {
+        self.close_span();
+        self.text.push('\n');
+    }
+
+    pub fn finish(mut self) -> String {
+        self.close_span();
+        self.text.push_str("</pre>");
+        self.text
+    }
+
+    fn is_default(&self, style: &Style) -> bool {
+        style.fg == self.foreground
+            && style.bg.is_none()
+            && !style.bold
+            && !style.italic
+            && !style.underline
+    }
+
+    fn open_span(&mut self, style: Style) {
+        self.text.push_str("<span style=\"");
+
+        if style.fg != self.foreground {
+            let _ = write!(self.text, "color:{};", css_color(style.fg));
+        }
+        if let Some(bg) = style.bg {
+            let _ = write!(self.text, "background-color:{};", css_color(bg));
+        }
+        if style.bold {
+            self.text.push_str("font-weight:bold;");
+        }
+        if style.italic {
+            self.text.push_str("font-style:italic;");
+        }
+        if style.underline {
+            self.text.push_str("text-decoration:underline;");
+        }
+
+        self.text.push_str("\">");
+        self.span = Some(style);
+    }
+
+    fn close_span(&mut self) {
+        if self.span.take().is_some() {
+            self.text.push_str("</span>");
+        }
+    }
+}
+
+fn css_color(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Html, Style};
+    use Rgb;
+
+    const FG: Rgb = Rgb { r: 0xff, g: 0xff, b: 0xff };
+    const BG: Rgb = Rgb { r: 0, g: 0, b: 0 };
+    const RED: Rgb = Rgb { r: 0xff, g: 0, b: 0 };
+
+    fn style(fg: Rgb, bg: Option<Rgb>) -> Style {
+        Style { fg, bg, bold: false, italic: false, underline: false }
+    }
+
+    #[test]
+    fn merges_runs() {
+        let mut html = Html::new(FG, BG);
+        for c in "ab".chars() {
+            html.push(c, style(RED, None));
+        }
+        html.push('c', style(FG, None));
+        html.newline();
+        html.push('d', style(FG, Some(RED)));
+
+        assert_eq!(
+            html.finish(),
+            "<pre style=\"color:#ffffff;background-color:#000000\">\
+             <span style=\"color:#ff0000;\">ab</span>c\n\
+             <span style=\"background-color:#ff0000;\">d</span></pre>"
+        );
+    }
+
+    #[test]
+    fn escapes_text() {
+        let mut html = Html::new(FG, BG);
+        for c in "<a&\">".chars() {
+            html.push(c, style(FG, None));
+        }
+
+        assert!(html.finish().contains("&lt;a&amp;&quot;&gt;</pre>"));
+    }
+}
@@ -13,7 +13,9 @@
 // limitations under the License.
 //
 //! Exports the `Term` type which is a high-level API for the Grid
+use std::collections::VecDeque;
 use std::ops::{Range, Index, IndexMut};
+use std::path::{Path, PathBuf};
 use std::{ptr, io, mem};
 use std::cmp::{min, max};
 use std::time::{Duration, Instant};
@@ -22,23 +24,42 @@ use arraydeque::ArrayDeque;
 use unicode_width::UnicodeWidthChar;
 use url::Url;
 
-use font::{self, Size};
-use ansi::{self, Color, NamedColor, Attr, Handler, CharsetIndex, StandardCharset, CursorStyle};
+use font::Size;
+use ansi::{self, Color, NamedColor, Attr, Handler, CharsetIndex, StandardCharset, CursorStyle, WindowSize};
 use grid::{BidirectionalIterator, Grid, Indexed, IndexRegion, DisplayIter, Scroll, ViewportPosition};
 use index::{self, Point, Column, Line, IndexRange, Contains, RangeInclusive, Linear};
 use selection::{self, Selection, Locations};
-use config::{Config, VisualBellAnimation};
+use config::{CellColor, CommandWrapper, Config, Osc52, VisualBellAnimation};
+use daemon;
 use {MouseCursor, Rgb};
 use copypasta::{Clipboard, Load, Store};
-use input::FONT_SIZE_STEP;
+use util::thread;
+use version;
 
 pub mod cell;
 pub mod color;
+mod contrast;
+mod dump;
+mod html;
 pub use self::cell::Cell;
 use self::cell::LineLength;
+use self::contrast::MinimumContrast;
+use self::dump::{StateDump, CursorDump, ColorDump};
+use self::html::Html;
+
+/// Step of the font size bindings, and the smallest size they go down to
+pub const FONT_SIZE_STEP: f32 = 0.5;
 
 const URL_SEPARATOR_CHARS: [char; 3] = [' ', '"', '\''];
 
+/// Lowest contrast ratio of the text of the cursor and selected cells
+///
+/// Configured colors with less contrast fall back to inverting the cell.
+const MIN_CONTRAST: f64 = 1.5;
+
+/// Shortest time between two bells which are animated or played
+const BELL_INTERVAL_MS: u64 = 100;
+
 /// A type that can expand a given point to a region
 ///
 /// Usually this is implemented for some 2-D array type since
@@ -97,50 +118,8 @@ impl Search for Term {
         point
     }
 
-    fn url_search(&self, mut point: Point<usize>) -> Option<String> {
-        point.line = self.grid.num_lines().0 - point.line - 1;
-
-        // Limit the starting point to the last line in the history
-        point.line = min(point.line, self.grid.len() - 1);
-
-        // Create forwards and backwards iterators
-        let iterf = self.grid.iter_from(point);
-        point.col += 1;
-        let mut iterb = self.grid.iter_from(point);
-
-        // Put all characters until separators into a string
-        let mut buf = String::new();
-        while let Some(cell) = iterb.prev() {
-            if URL_SEPARATOR_CHARS.contains(&cell.c) {
-                break;
-            }
-            buf.insert(0, cell.c);
-        }
-        for cell in iterf {
-            if URL_SEPARATOR_CHARS.contains(&cell.c) {
-                break;
-            }
-            buf.push(cell.c);
-        }
-
-        // Heuristic to remove all leading '('
-        while buf.starts_with('(') {
-            buf.remove(0);
-        }
-
-        // Heuristic to remove all ')' from end of URLs without matching '('
-        let str_count = |text: &str, c: char| {
-            text.chars().filter(|tc| *tc == c).count()
-        };
-        while buf.ends_with(')') && str_count(&buf, '(') < str_count(&buf, ')') {
-            buf.pop();
-        }
-
-        // Check if string is valid url
-        match Url::parse(&buf) {
-            Ok(_) => Some(buf),
-            Err(_) => None,
-        }
+    fn url_search(&self, point: Point<usize>) -> Option<String> {
+        self.url_at(point).map(|(url, _)| url)
     }
 }
 
@@ -159,8 +138,9 @@ impl selection::Dimensions for Term {
 /// background cell). Additionally, this manages some state of the grid only
 /// relevant for rendering like temporarily changing the cell with the cursor.
 ///
-/// This manages the cursor during a render. The cursor location is inverted to
-/// draw it, and reverted after drawing to maintain state.
+/// This manages the cursor during a render. The cell below a block cursor is
+/// drawn in the cursor colors, the other shapes are drawn over the cells by the
+/// renderer, as described by `RenderableCellsIter::cursor`.
 pub struct RenderableCellsIter<'a> {
     inner: DisplayIter<'a, Cell>,
     grid: &'a Grid<Cell>,
@@ -170,7 +150,25 @@ pub struct RenderableCellsIter<'a> {
     config: &'a Config,
     colors: &'a color::List,
     selection: Option<RangeInclusive<index::Linear>>,
+    /// The hovered URL, which is underlined
+    url_highlight: Option<RangeInclusive<index::Linear>>,
     cursor_cells: ArrayDeque<[Indexed<Cell>; 3]>,
+
+    /// The cell below the cursor is selected, the cursor is drawn on top
+    cursor_selected: bool,
+    /// The cursor covers both cells of a wide char
+    wide_cursor: bool,
+    /// Cursor drawn over the cells
+    renderable_cursor: Option<RenderableCursor>,
+
+    /// Cells of the preedit string, drawn after the grid
+    preedit_cells: Vec<Indexed<Cell>>,
+    /// Display line of the cursor, once it has been reached
+    preedit_line: Option<Line>,
+    /// Display lines which need to be updated, all lines are updated without it
+    damage: Option<Vec<bool>>,
+    /// Text colors are adjusted to the configured minimum contrast
+    minimum_contrast: Option<MinimumContrast>,
 }
 
 impl<'a> RenderableCellsIter<'a> {
@@ -185,70 +183,165 @@ impl<'a> RenderableCellsIter<'a> {
         mode: TermMode,
         config: &'b Config,
         selection: Option<Locations>,
+        url_highlight: Option<Locations>,
         cursor_style: CursorStyle,
+        preedit: Option<&Preedit>,
     ) -> RenderableCellsIter<'b> {
         let cursor_offset = grid.line_to_offset(cursor.line);
         let inner = grid.display_iter();
 
-        let mut selection_range = None;
-        if let Some(loc) = selection {
-            // Get on-screen lines of the selection's locations
-            let start_line = grid.buffer_line_to_visible(loc.start.line);
-            let end_line = grid.buffer_line_to_visible(loc.end.line);
-
-            // Get start/end locations based on what part of selection is on screen
-            let locations = match (start_line, end_line) {
-                (ViewportPosition::Visible(start_line), ViewportPosition::Visible(end_line)) => {
-                    Some((start_line, loc.start.col, end_line, loc.end.col))
-                },
-                (ViewportPosition::Visible(start_line), ViewportPosition::Above) => {
-                    Some((start_line, loc.start.col, Line(0), Column(0)))
-                },
-                (ViewportPosition::Below, ViewportPosition::Visible(end_line)) => {
-                    Some((grid.num_lines(), Column(0), end_line, loc.end.col))
-                },
-                (ViewportPosition::Below, ViewportPosition::Above) =>  {
-                    Some((grid.num_lines(), Column(0), Line(0), Column(0)))
-                },
-                _ => None,
-            };
-
-            if let Some((start_line, start_col, end_line, end_col)) = locations {
-                // start and end *lines* are swapped as we switch from buffer to
-                // Line coordinates.
-                let mut end = Point {
-                    line: start_line,
-                    col: start_col,
-                };
-                let mut start = Point {
-                    line: end_line,
-                    col: end_col,
-                };
-
-                if start > end {
-                    ::std::mem::swap(&mut start, &mut end);
-                }
-
-                let cols = grid.num_cols();
-                let start = Linear(start.line.0 * cols.0 + start.col.0);
-                let end = Linear(end.line.0 * cols.0 + end.col.0);
-
-                // Update the selection
-                selection_range = Some(RangeInclusive::new(start, end));
-            }
-        }
-
         RenderableCellsIter {
             cursor,
             cursor_offset,
             grid,
             inner,
             mode,
-            selection: selection_range,
+            selection: selection.and_then(|loc| Self::visible_range(grid, loc)),
+            url_highlight: url_highlight.and_then(|loc| Self::visible_range(grid, loc)),
             config,
             colors,
             cursor_cells: ArrayDeque::new(),
-        }.initialize(cursor_style)
+            cursor_selected: false,
+            wide_cursor: false,
+            renderable_cursor: None,
+            preedit_cells: Vec::new(),
+            preedit_line: None,
+            damage: None,
+            minimum_contrast: MinimumContrast::new(config.colors().minimum_contrast, colors),
+        }.initialize(cursor_style, preedit)
+    }
+
+    /// Cells of the viewport covered by buffer locations
+    fn visible_range(grid: &Grid<Cell>, loc: Locations) -> Option<RangeInclusive<Linear>> {
+        // Get on-screen lines of the locations
+        let start_line = grid.buffer_line_to_visible(loc.start.line);
+        let end_line = grid.buffer_line_to_visible(loc.end.line);
+
+        // Get start/end locations based on what part of the range is on screen
+        let (start_line, start_col, end_line, end_col) = match (start_line, end_line) {
+            (ViewportPosition::Visible(start_line), ViewportPosition::Visible(end_line)) => {
+                (start_line, loc.start.col, end_line, loc.end.col)
+            },
+            (ViewportPosition::Visible(start_line), ViewportPosition::Above) => {
+                (start_line, loc.start.col, Line(0), Column(0))
+            },
+            (ViewportPosition::Below, ViewportPosition::Visible(end_line)) => {
+                (grid.num_lines(), Column(0), end_line, loc.end.col)
+            },
+            (ViewportPosition::Below, ViewportPosition::Above) =>  {
+                (grid.num_lines(), Column(0), Line(0), Column(0))
+            },
+            _ => return None,
+        };
+
+        // start and end *lines* are swapped as we switch from buffer to
+        // Line coordinates.
+        let mut end = Point {
+            line: start_line,
+            col: start_col,
+        };
+        let mut start = Point {
+            line: end_line,
+            col: end_col,
+        };
+
+        if start > end {
+            ::std::mem::swap(&mut start, &mut end);
+        }
+
+        let cols = grid.num_cols();
+        let start = Linear(start.line.0 * cols.0 + start.col.0);
+        let end = Linear(end.line.0 * cols.0 + end.col.0);
+
+        Some(RangeInclusive::new(start, end))
+    }
+
+    /// The cursor, unless it's hidden or outside of the viewport
+    #[inline]
+    pub fn cursor(&self) -> Option<RenderableCursor> {
+        self.renderable_cursor
+    }
+
+    /// Check if the cursor, the selection or the URL underline is on a display line
+    ///
+    /// These are drawn over default cells, so the line can't be skipped when
+    /// it's blank.
+    fn is_highlighted(&self, line: Line) -> bool {
+        let cols = self.grid.num_cols().0;
+        let start = Linear(line.0 * cols);
+        let end = Linear(line.0 * cols + cols - 1);
+        let overlaps = |range: &Option<RangeInclusive<Linear>>| match *range {
+            Some(RangeInclusive::NonEmpty { start: ref first, end: ref last }) => {
+                *first <= end && *last >= start
+            },
+            _ => false,
+        };
+
+        self.inner.offset() == self.cursor_offset
+            || overlaps(&self.selection)
+            || overlaps(&self.url_highlight)
+    }
+
+    /// Only yield the cells of the damaged display lines
+    fn with_damage(mut self, damage: Vec<bool>) -> Self {
+        self.damage = Some(damage);
+        self
+    }
+
+    #[inline]
+    fn is_damaged(&self, line: Line) -> bool {
+        self.damage.as_ref().map_or(true, |damage| damage[line.0])
+    }
+
+    /// Lay out the preedit string starting at the cursor
+    ///
+    /// The text is underlined and the cell at the input method's cursor is
+    /// inverted. Text which doesn't fit in the line is cut off.
+    fn populate_preedit(&mut self, preedit: &Preedit) {
+        let cols = self.grid.num_cols().0;
+        let template = self.grid[*self.cursor];
+        let cursor_width = preedit.cursor_width();
+        let cursor = self.cursor.col.0 + cursor_width;
+
+        // Add a trailing cell when the input method's cursor is past the text
+        let caret = if cursor_width == preedit.width() { Some((' ', 1)) } else { None };
+
+        let mut col = self.cursor.col.0;
+        let chars = preedit.text.chars()
+            .map(|c| (c, c.width().unwrap_or(0)))
+            .chain(caret);
+        for (c, width) in chars {
+            if width == 0 {
+                continue;
+            }
+            if col + width > cols {
+                break;
+            }
+
+            let mut cell = Cell::new(c, template.fg, template.bg);
+            cell.flags = cell::Flags::UNDERLINE;
+            if col == cursor {
+                cell.flags.insert(cell::Flags::INVERSE);
+            }
+
+            if width == 2 {
+                let mut spacer = cell;
+                spacer.c = ' ';
+                spacer.flags.insert(cell::Flags::WIDE_CHAR_SPACER);
+                self.preedit_cells.push(Indexed { line: Line(0), column: Column(col + 1), inner: spacer });
+                cell.flags.insert(cell::Flags::WIDE_CHAR);
+            }
+
+            self.preedit_cells.push(Indexed { line: Line(0), column: Column(col), inner: cell });
+            col += width;
+        }
+    }
+
+    /// Check if a grid cell is hidden by the preedit string
+    #[inline]
+    fn is_below_preedit(&self, cell: &Indexed<Cell>) -> bool {
+        let end = self.cursor.col.0 + self.preedit_cells.len();
+        Some(cell.line) == self.preedit_line && cell.column >= self.cursor.col && cell.column.0 < end
     }
 
     fn push_cursor_cells(&mut self, original: Cell, cursor: Cell, wide: Cell) {
@@ -269,6 +362,7 @@ impl<'a> RenderableCellsIter<'a> {
         // If cursor is over a wide (2 cell size) character,
         // print the second cursor cell
         if self.is_wide_cursor(&cursor) {
+            self.wide_cursor = true;
             self.cursor_cells.push_back(Indexed {
                 line: self.cursor.line,
                 column: self.cursor.col + 1,
@@ -278,46 +372,85 @@ impl<'a> RenderableCellsIter<'a> {
     }
 
     fn populate_block_cursor(&mut self) {
-        let cell = &self.grid[self.cursor];
-        let text_color = self.config.cursor_text_color().unwrap_or(cell.bg);
-        let cursor_color = self.config.cursor_cursor_color().unwrap_or(cell.fg);
+        let cell = self.grid[self.cursor];
+
+        // The cell colors of the cursor are the ones the cell is drawn with
+        let (cell_fg, cell_bg) = if cell.inverse() { (cell.bg, cell.fg) } else { (cell.fg, cell.bg) };
+        let mut text_color = self.config.cursor_text_color(cell_fg, cell_bg);
+        let mut cursor_color = self.config.cursor_cursor_color(cell_fg, cell_bg);
+
+        // Invert the cell instead when the text would be invisible on the cursor
+        let text_rgb = self.compute_fg_rgb(text_color, &cell);
+        if text_rgb.contrast(self.compute_bg_rgb(cursor_color)) < MIN_CONTRAST {
+            text_color = cell_bg;
+            cursor_color = cell_fg;
+        }
+
+        // Draw the cell as if it wasn't selected when the cursor would blend
+        // into the selection
+        if self.cursor_selected {
+            let (_, selection_bg) = self.resolve_rgb(&cell, true);
+            if self.compute_bg_rgb(cursor_color).contrast(selection_bg) < MIN_CONTRAST {
+                let (fg, bg) = self.resolve_rgb(&cell, false);
+                text_color = Color::Spec(fg);
+                cursor_color = Color::Spec(bg);
+            }
+        }
 
         let original_cell = self.grid[self.cursor];
 
+        // The cursor colors are final, inverting them again would swap them
         let mut cursor_cell = self.grid[self.cursor];
         cursor_cell.fg = text_color;
         cursor_cell.bg = cursor_color;
+        cursor_cell.flags.remove(cell::Flags::INVERSE);
 
         let mut wide_cell = cursor_cell;
         wide_cell.c = ' ';
 
+        let (_, color) = self.resolve_rgb(&cursor_cell, false);
+        self.set_renderable_cursor(CursorStyle::Block, color, &cursor_cell);
         self.push_cursor_cells(original_cell, cursor_cell, wide_cell);
     }
 
-    fn populate_char_cursor(&mut self, cursor_cell_char: char, wide_cell_char: char) {
-        let original_cell = self.grid[self.cursor];
-
+    /// Draw the cell normally, with the cursor shape on top of it
+    fn populate_shape_cursor(&mut self, shape: CursorStyle) {
         let mut cursor_cell = self.grid[self.cursor];
-        let cursor_color = self.config.cursor_cursor_color().unwrap_or(cursor_cell.fg);
-        cursor_cell.c = cursor_cell_char;
-        cursor_cell.fg = cursor_color;
-
-        let mut wide_cell = cursor_cell;
-        wide_cell.c = wide_cell_char;
+        let mut cursor_color = self.config.cursor_cursor_color(cursor_cell.fg, cursor_cell.bg);
+
+        // The cursor is drawn on top of the selection colors of the cell
+        if self.cursor_selected {
+            let (text, background) = self.resolve_rgb(&cursor_cell, true);
+            cursor_cell.fg = Color::Spec(text);
+            cursor_cell.bg = Color::Spec(background);
+            cursor_cell.flags.remove(cell::Flags::INVERSE);
+        }
 
-        self.push_cursor_cells(original_cell, cursor_cell, wide_cell);
-    }
+        // Use the text color when the cursor would be invisible on the cell
+        let cursor_rgb = self.compute_fg_rgb(cursor_color, &cursor_cell);
+        if cursor_rgb.contrast(self.compute_bg_rgb(cursor_cell.bg)) < MIN_CONTRAST {
+            cursor_color = cursor_cell.fg;
+        }
 
-    fn populate_underline_cursor(&mut self) {
-        self.populate_char_cursor(font::UNDERLINE_CURSOR_CHAR, font::UNDERLINE_CURSOR_CHAR);
+        let color = self.compute_fg_rgb(cursor_color, &cursor_cell);
+        self.set_renderable_cursor(shape, color, &cursor_cell);
+        self.populate_no_cursor();
     }
 
-    fn populate_beam_cursor(&mut self) {
-        self.populate_char_cursor(font::BEAM_CURSOR_CHAR, ' ');
-    }
+    fn set_renderable_cursor(&mut self, shape: CursorStyle, color: Rgb, cell: &Cell) {
+        // Cursors below the viewport aren't drawn
+        let line = self.cursor.line + self.grid.display_offset();
+        if line >= self.grid.num_lines() {
+            return;
+        }
 
-    fn populate_box_cursor(&mut self) {
-        self.populate_char_cursor(font::BOX_CURSOR_CHAR, ' ');
+        self.renderable_cursor = Some(RenderableCursor {
+            line,
+            column: self.cursor.col,
+            width: if self.is_wide_cursor(cell) { 2 } else { 1 },
+            shape,
+            color,
+        });
     }
 
     #[inline]
@@ -334,21 +467,26 @@ impl<'a> RenderableCellsIter<'a> {
         }).expect("won't exceed capacity");
     }
 
-    fn initialize(mut self, cursor_style: CursorStyle) -> Self {
-        if self.cursor_is_visible() {
+    fn initialize(mut self, cursor_style: CursorStyle, preedit: Option<&Preedit>) -> Self {
+        // Display line of the cursor, it's below the viewport when scrolled far enough
+        let line = self.cursor.line + self.grid.display_offset();
+        if line < self.grid.num_lines() {
+            let cell = self.grid[*self.cursor];
+            self.cursor_selected = self.is_selected(line, self.cursor.col, &cell);
+        }
+
+        if let Some(preedit) = preedit {
+            // The preedit string replaces the cursor while composing
+            self.populate_preedit(preedit);
+            self.populate_no_cursor();
+        } else if self.cursor_is_visible() {
             match cursor_style {
-                CursorStyle::HollowBlock => {
-                    self.populate_box_cursor();
-                },
                 CursorStyle::Block => {
                     self.populate_block_cursor();
                 },
-                CursorStyle::Beam => {
-                    self.populate_beam_cursor();
+                CursorStyle::Beam | CursorStyle::Underline | CursorStyle::HollowBlock => {
+                    self.populate_shape_cursor(cursor_style);
                 },
-                CursorStyle::Underline => {
-                    self.populate_underline_cursor();
-                }
             }
         } else {
             self.populate_no_cursor();
@@ -362,63 +500,166 @@ impl<'a> RenderableCellsIter<'a> {
         self.mode.contains(mode::TermMode::SHOW_CURSOR) && self.grid.contains(self.cursor)
     }
 
+    /// Check if a cell at a display position is selected
+    ///
+    /// A wide char and its spacer are a unit, the selection of one of them
+    /// selects both.
+    fn is_selected(&self, line: Line, column: Column, cell: &Cell) -> bool {
+        let range = match self.selection {
+            Some(ref range) => range,
+            None => return false,
+        };
+
+        let index = line.0 * self.grid.num_cols().0 + column.0;
+        let partner = if cell.flags.contains(cell::Flags::WIDE_CHAR) {
+            Some(index + 1)
+        } else if cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) && index > 0 {
+            Some(index - 1)
+        } else {
+            None
+        };
+
+        range.contains_(Linear(index))
+            || partner.map_or(false, |partner| range.contains_(Linear(partner)))
+    }
+
+    fn is_url(&self, line: Line, column: Column) -> bool {
+        let index = line.0 * self.grid.num_cols().0 + column.0;
+        self.url_highlight.as_ref().map_or(false, |range| range.contains_(Linear(index)))
+    }
+
+    /// Resolve the text and background color of a cell
+    fn resolve_rgb(&self, cell: &Cell, selected: bool) -> (Rgb, Rgb) {
+        let mut fg_rgb = self.compute_fg_rgb(cell.fg, cell);
+        let mut bg_rgb = self.compute_bg_rgb(cell.bg);
+
+        if cell.inverse() {
+            mem::swap(&mut fg_rgb, &mut bg_rgb);
+        }
+
+        if !selected {
+            return (fg_rgb, bg_rgb);
+        }
+
+        let colors = self.config.colors().selection;
+        let text = cell_rgb(colors.text(), fg_rgb, bg_rgb);
+        let background = cell_rgb(colors.background(), fg_rgb, bg_rgb);
+
+        // Invert the cell instead when the selected text would be invisible
+        if text.contrast(background) < MIN_CONTRAST {
+            (bg_rgb, fg_rgb)
+        } else {
+            (text, background)
+        }
+    }
+
+    #[inline]
     fn compute_fg_rgb(&self, fg: Color, cell: &Cell) -> Rgb {
-        use self::cell::Flags;
-        match fg {
-            Color::Spec(rgb) => rgb,
-            Color::Named(ansi) => {
-                match (self.config.draw_bold_text_with_bright_colors(), cell.flags & Flags::DIM_BOLD) {
-                    // If no bright foreground is set, treat it like the BOLD flag doesn't exist
-                    (_, self::cell::Flags::DIM_BOLD)
-                        if ansi == NamedColor::Foreground
-                            && self.config.colors().primary.bright_foreground.is_none() =>
-                    {
-                        self.colors[NamedColor::DimForeground]
-                    }
-                    // Draw bold text in bright colors *and* contains bold flag.
-                    (true,  self::cell::Flags::BOLD)     => self.colors[ansi.to_bright()],
-                    // Cell is marked as dim and not bold
-                    (_,     self::cell::Flags::DIM) |
-                    (false, self::cell::Flags::DIM_BOLD) => self.colors[ansi.to_dim()],
-                    // None of the above, keep original color.
-                    _ => self.colors[ansi]
-                }
-            },
-            Color::Indexed(idx) => {
-                let idx = match (
-                    self.config.draw_bold_text_with_bright_colors(),
-                    cell.flags & Flags::DIM_BOLD,
-                    idx
-                ) {
-                    (true,  self::cell::Flags::BOLD, 0...7)  => idx as usize + 8,
-                    (false, self::cell::Flags::DIM,  8...15) => idx as usize - 8,
-                    (false, self::cell::Flags::DIM,  0...7)  => idx as usize + 260,
-                    _ => idx as usize,
-                };
+        compute_fg_rgb(
+            self.colors,
+            self.config.draw_bold_text_with_bright_colors(),
+            self.config.colors().primary.bright_foreground.is_some(),
+            fg,
+            cell,
+        )
+    }
 
-                self.colors[idx]
-            }
+    #[inline]
+    fn compute_bg_alpha(&self, bg: Rgb) -> f32 {
+        if is_default_bg(self.colors, bg) {
+            0.0
+        } else {
+            self.explicit_bg_alpha()
         }
     }
 
+    /// Opacity of backgrounds which differ from the default background
     #[inline]
-    fn compute_bg_alpha(&self, bg: Color) -> f32 {
-        match bg {
-            Color::Named(NamedColor::Background) => 0.0,
-            _ => 1.0
+    fn explicit_bg_alpha(&self) -> f32 {
+        if self.config.colors().transparent_background_colors {
+            self.config.background_opacity().get()
+        } else {
+            1.0
         }
     }
 
+    #[inline]
     fn compute_bg_rgb(&self, bg: Color) -> Rgb {
-        match bg {
-            Color::Spec(rgb) => rgb,
-            Color::Named(ansi) => self.colors[ansi],
-            Color::Indexed(idx) => self.colors[idx],
-        }
+        compute_bg_rgb(self.colors, bg)
+    }
+}
+
+/// Resolve the foreground color of a cell
+///
+/// Truecolor and indexed colors are always drawn as they are. Only named
+/// colors are affected by the BOLD and DIM flags:
+///
+///  * BOLD uses the bright variant if `bold_is_bright` is set
+///  * DIM uses the dim variant
+///  * BOLD and DIM together use the normal color if `bold_is_bright` is set,
+///    otherwise the dim variant
+///
+/// The default foreground is dimmed if both flags are set without a
+/// configured bright foreground.
+fn compute_fg_rgb(
+    colors: &color::List,
+    bold_is_bright: bool,
+    has_bright_foreground: bool,
+    fg: Color,
+    cell: &Cell,
+) -> Rgb {
+    use self::cell::Flags;
+    match fg {
+        Color::Spec(rgb) => rgb,
+        Color::Indexed(idx) => colors[idx as usize],
+        Color::Named(ansi) => {
+            match (bold_is_bright, cell.flags & Flags::DIM_BOLD) {
+                // If no bright foreground is set, treat it like the BOLD flag doesn't exist
+                (_, self::cell::Flags::DIM_BOLD)
+                    if ansi == NamedColor::Foreground && !has_bright_foreground =>
+                {
+                    colors[NamedColor::DimForeground]
+                }
+                // Draw bold text in bright colors *and* contains bold flag.
+                (true,  self::cell::Flags::BOLD)     => colors[ansi.to_bright()],
+                // Cell is marked as dim and not bold
+                (_,     self::cell::Flags::DIM) |
+                (false, self::cell::Flags::DIM_BOLD) => colors[ansi.to_dim()],
+                // None of the above, keep original color.
+                _ => colors[ansi]
+            }
+        },
+    }
+}
+
+/// Resolve a configured color for a cell drawn in `fg` on `bg`
+#[inline]
+fn cell_rgb(color: CellColor, fg: Rgb, bg: Rgb) -> Rgb {
+    match color {
+        CellColor::CellForeground => fg,
+        CellColor::CellBackground => bg,
+        CellColor::Rgb(rgb) => rgb,
+    }
+}
+
+/// Check if a background is drawn by clearing the window
+///
+/// This compares the resolved color, so cells keep matching the default
+/// background when it's changed at runtime.
+#[inline]
+fn is_default_bg(colors: &color::List, bg: Rgb) -> bool {
+    bg == colors[NamedColor::Background]
+}
+
+fn compute_bg_rgb(colors: &color::List, bg: Color) -> Rgb {
+    match bg {
+        Color::Spec(rgb) => rgb,
+        Color::Named(ansi) => colors[ansi],
+        Color::Indexed(idx) => colors[idx],
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RenderableCell {
     /// A _Display_ line (not necessarily an _Active_ line)
     pub line: Line,
@@ -430,6 +671,55 @@ pub struct RenderableCell {
     pub flags: cell::Flags,
 }
 
+/// Cursor drawn by the renderer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderableCursor {
+    /// A _Display_ line (not necessarily an _Active_ line)
+    pub line: Line,
+    pub column: Column,
+    /// Number of cells covered by the cursor, two for a wide char
+    pub width: usize,
+    pub shape: CursorStyle,
+    pub color: Rgb,
+}
+
+/// Renderable cells of the last frame
+///
+/// Resolving the colors of every cell is expensive, so the cells of lines which
+/// didn't change since the last frame are reused. Lines are only reused while
+/// the viewport is at the bottom of the scrollback.
+#[derive(Default)]
+pub struct RenderCache {
+    lines: Vec<Vec<RenderableCell>>,
+    cursor: Option<RenderableCursor>,
+    cols: Column,
+    cursor_line: Line,
+    selection: Option<Locations>,
+    url_highlight: Option<Locations>,
+    valid: bool,
+}
+
+impl RenderCache {
+    /// Update all lines in the next frame
+    ///
+    /// Required when something changes which isn't tracked by the grid, like
+    /// the config.
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Iterate over the cells of all lines
+    pub fn cells<'a>(&'a self) -> Box<Iterator<Item = &'a RenderableCell> + 'a> {
+        Box::new(self.lines.iter().flat_map(|line| line.iter()))
+    }
+
+    /// The cursor drawn over the cells
+    #[inline]
+    pub fn cursor(&self) -> Option<RenderableCursor> {
+        self.cursor
+    }
+}
+
 impl<'a> Iterator for RenderableCellsIter<'a> {
     type Item = RenderableCell;
 
@@ -440,47 +730,81 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         loop {
+            // Skip lines which didn't change since the last frame, and lines
+            // of default cells which are drawn by clearing the window
+            if self.inner.column() == Column(0) {
+                let line = self.inner.line();
+                if !self.is_damaged(line) || (self.inner.is_blank_line() && !self.is_highlighted(line)) {
+                    self.inner.skip_line();
+                    continue;
+                }
+            }
+
             // Handle cursor
-            let (cell, selected) = if self.cursor_offset == self.inner.offset() &&
+            let (cell, selected, cursor) = if self.cursor_offset == self.inner.offset() &&
                 self.inner.column() == self.cursor.col
             {
-                // Cursor cell
+                // Cursor cell, only the cell below the cursor uses the selection colors
                 let mut cell = self.cursor_cells.pop_front().unwrap();
                 cell.line = self.inner.line();
+                let selected = mem::replace(&mut self.cursor_selected, false);
 
                 // Since there may be multiple cursor cells (for a wide
                 // char), only update iteration position after all cursor
-                // cells have been drawn.
+                // cells have been drawn. The spacer of a wide char is
+                // covered by the cursor as well.
                 if self.cursor_cells.is_empty() {
                     self.inner.next();
+                    if self.wide_cursor {
+                        self.inner.next();
+                    }
+                }
+
+                // The preedit string is placed on the cursor's line
+                if !self.preedit_cells.is_empty() {
+                    self.preedit_line = Some(cell.line);
+                    continue;
+                }
+
+                (cell, selected, self.renderable_cursor.is_some())
+            } else if let Some(mut cell) = self.inner.next() {
+                if self.is_below_preedit(&cell) {
+                    continue;
                 }
-                (cell, false)
-            } else {
-                let cell = self.inner.next()?;
 
-                let index = Linear(cell.line.0 * self.grid.num_cols().0 + cell.column.0);
+                let selected = self.is_selected(cell.line, cell.column, &cell);
 
-                let selected = self.selection.as_ref()
-                    .map(|range| range.contains_(index))
-                    .unwrap_or(false);
+                if self.is_url(cell.line, cell.column) {
+                    cell.flags.insert(cell::Flags::UNDERLINE);
+                }
 
                 // Skip empty cells
                 if cell.is_empty() && !selected {
                     continue;
                 }
 
-                (cell, selected)
+                (cell, selected, false)
+            } else {
+                // Draw the preedit string once the rest of the grid is done
+                let line = self.preedit_line?;
+                let mut cell = self.preedit_cells.pop()?;
+                cell.line = line;
+                (cell, false, false)
             };
 
-            // Apply inversion and lookup RGB values
-            let mut fg_rgb = self.compute_fg_rgb(cell.fg, &cell);
-            let mut bg_rgb = self.compute_bg_rgb(cell.bg);
+            // Apply inversion and selection, and lookup RGB values
+            let (mut fg_rgb, bg_rgb) = self.resolve_rgb(&cell, selected);
 
-            let bg_alpha = if selected ^ cell.inverse() {
-                mem::swap(&mut fg_rgb, &mut bg_rgb);
-                self.compute_bg_alpha(cell.fg)
+            // The cursor and selection colors already avoid unreadable text
+            if !selected && !cursor {
+                if let Some(ref mut minimum_contrast) = self.minimum_contrast {
+                    fg_rgb = minimum_contrast.apply(fg_rgb, bg_rgb);
+                }
+            }
+            let bg_alpha = if selected {
+                self.explicit_bg_alpha()
             } else {
-                self.compute_bg_alpha(cell.bg)
+                self.compute_bg_alpha(bg_rgb)
             };
 
             return Some(RenderableCell {
@@ -499,22 +823,25 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
 
 pub mod mode {
     bitflags! {
-        pub struct TermMode: u16 {
-            const SHOW_CURSOR         = 0b00_0000_0000_0001;
-            const APP_CURSOR          = 0b00_0000_0000_0010;
-            const APP_KEYPAD          = 0b00_0000_0000_0100;
-            const MOUSE_REPORT_CLICK  = 0b00_0000_0000_1000;
-            const BRACKETED_PASTE     = 0b00_0000_0001_0000;
-            const SGR_MOUSE           = 0b00_0000_0010_0000;
-            const MOUSE_MOTION        = 0b00_0000_0100_0000;
-            const LINE_WRAP           = 0b00_0000_1000_0000;
-            const LINE_FEED_NEW_LINE  = 0b00_0001_0000_0000;
-            const ORIGIN              = 0b00_0010_0000_0000;
-            const INSERT              = 0b00_0100_0000_0000;
-            const FOCUS_IN_OUT        = 0b00_1000_0000_0000;
-            const ALT_SCREEN          = 0b01_0000_0000_0000;
-            const MOUSE_DRAG          = 0b10_0000_0000_0000;
-            const ANY                 = 0b11_1111_1111_1111;
+        pub struct TermMode: u32 {
+            const SHOW_CURSOR         = 0b0_0000_0000_0000_0001;
+            const APP_CURSOR          = 0b0_0000_0000_0000_0010;
+            const APP_KEYPAD          = 0b0_0000_0000_0000_0100;
+            const MOUSE_REPORT_CLICK  = 0b0_0000_0000_0000_1000;
+            const BRACKETED_PASTE     = 0b0_0000_0000_0001_0000;
+            const SGR_MOUSE           = 0b0_0000_0000_0010_0000;
+            const MOUSE_MOTION        = 0b0_0000_0000_0100_0000;
+            const LINE_WRAP           = 0b0_0000_0000_1000_0000;
+            const LINE_FEED_NEW_LINE  = 0b0_0000_0001_0000_0000;
+            const ORIGIN              = 0b0_0000_0010_0000_0000;
+            const INSERT              = 0b0_0000_0100_0000_0000;
+            const FOCUS_IN_OUT        = 0b0_0000_1000_0000_0000;
+            const ALT_SCREEN          = 0b0_0001_0000_0000_0000;
+            const MOUSE_DRAG          = 0b0_0010_0000_0000_0000;
+            const LEFT_RIGHT_MARGIN   = 0b0_0100_0000_0000_0000;
+            const UTF8_MOUSE          = 0b0_1000_0000_0000_0000;
+            const URXVT_MOUSE         = 0b1_0000_0000_0000_0000;
+            const ANY                 = 0b1_1111_1111_1111_1111;
             const NONE                = 0;
         }
     }
@@ -606,6 +933,28 @@ pub struct Cursor {
 
     /// Currently configured graphic character sets
     charsets: Charsets,
+
+    /// The graphic character set, out of `charsets`, which ASCII is currently
+    /// being mapped to
+    active_charset: CharsetIndex,
+
+    /// The line was about to wrap when the cursor was saved
+    ///
+    /// Only used by the saved cursors, the active one keeps this state in
+    /// `Term::input_needs_wrap`.
+    wrap_pending: bool,
+}
+
+impl Cursor {
+    /// Template of the blanks written by erase operations
+    ///
+    /// All blanks take the current background color, which full screen
+    /// programs rely on to paint large areas. Other attributes, like underline
+    /// or reverse video, aren't applied to them.
+    #[inline]
+    fn erase_template(&self) -> Cell {
+        Cell::new(' ', Color::Named(NamedColor::Foreground), self.template.bg)
+    }
 }
 
 pub struct VisualBell {
@@ -638,6 +987,10 @@ impl VisualBell {
 
     /// Ring the visual bell, and return its intensity.
     pub fn ring(&mut self) -> f64 {
+        if self.animation == VisualBellAnimation::None {
+            return 0.0;
+        }
+
         let now = Instant::now();
         self.start_time = Some(now);
         self.intensity_at_instant(now)
@@ -711,6 +1064,7 @@ impl VisualBell {
                     VisualBellAnimation::EaseOutExpo => cubic_bezier(0.19, 1.0, 0.22, 1.0, time),
                     VisualBellAnimation::EaseOutCirc => cubic_bezier(0.075, 0.82, 0.165, 1.0, time),
                     VisualBellAnimation::Linear => time,
+                    VisualBellAnimation::None => 1.0,
                 };
 
                 // Since we want the `intensity` of the VisualBell to decay over
@@ -743,9 +1097,15 @@ pub struct Term {
     /// Would be nice to avoid the allocation...
     next_title: Option<String>,
 
+    /// Working directory last reported by the shell
+    current_dir: Option<PathBuf>,
+
     /// Got a request to set the mouse cursor; it's buffered here until the next draw
     next_mouse_cursor: Option<MouseCursor>,
 
+    /// URL below the mouse, which is underlined
+    url_highlight: Option<Locations>,
+
     /// Alternate grid
     alt_grid: Grid<Cell>,
 
@@ -755,10 +1115,6 @@ pub struct Term {
     /// The cursor
     cursor: Cursor,
 
-    /// The graphic character set, out of `charsets`, which ASCII is currently
-    /// being mapped to
-    active_charset: CharsetIndex,
-
     /// Tabstops
     tabs: Vec<bool>,
 
@@ -768,6 +1124,9 @@ pub struct Term {
     /// Scroll region
     scroll_region: Range<Line>,
 
+    /// Left and right margins, only used in left/right margin mode
+    left_right_margins: Range<Column>,
+
     /// Font size
     pub font_size: Size,
     original_font_size: Size,
@@ -777,9 +1136,33 @@ pub struct Term {
 
     pub dirty: bool,
 
+    /// The window can't be seen, frames aren't drawn until it can
+    ///
+    /// Output isn't held back for the frame while it's occluded, and every
+    /// chunk of output wakes up the main loop to handle bells and titles.
+    pub occluded: bool,
+
     pub visual_bell: VisualBell,
     pub next_is_urgent: Option<bool>,
 
+    /// The alert sound of the system should be played
+    pub next_bell_is_audible: bool,
+
+    /// Size the window was asked to change to by the program
+    pub next_window_size: Option<WindowSize>,
+
+    /// Programs may resize the window
+    allow_resize_requests: bool,
+
+    /// Message drawn over the last line, like the confirmation to close
+    pub message: Option<String>,
+
+    /// Play the alert sound when the bell rings
+    audible_bell: bool,
+
+    /// Last time the bell rang, to limit the rate of the animation and sound
+    bell_rung_at: Option<Instant>,
+
     /// Saved cursor from main grid
     cursor_save: Cursor,
 
@@ -810,6 +1193,73 @@ pub struct Term {
 
     /// Automatically scroll to bottom when new lines are added
     auto_scroll: bool,
+
+    /// Push the lines cleared by `CSI 2 J` into the history
+    clear_copies_to_history: bool,
+
+    /// Draw bold text in the bright variant of its color
+    bold_is_bright: bool,
+
+    /// A bright foreground color is configured
+    has_bright_foreground: bool,
+
+    /// The pty shut down and the terminal should be closed
+    should_exit: bool,
+
+    /// Text currently being composed by the input method
+    preedit: Option<Preedit>,
+
+    /// Command receiving the output for the printer
+    printer_command: Option<CommandWrapper>,
+
+    /// Raw C1 bytes are controls and replies use 8-bit controls
+    eight_bit_controls: bool,
+
+    /// Initial state of the 8-bit controls, restored on reset
+    default_eight_bit_controls: bool,
+
+    /// Reply to ENQ
+    answerback: Vec<u8>,
+
+    /// Access of programs to the clipboard
+    osc52: Osc52,
+
+    /// Queries of programs are ignored
+    disable_reports: bool,
+}
+
+/// Text being composed by an input method
+///
+/// The preedit string is drawn over the grid at the cursor position, without
+/// modifying the grid itself.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Preedit {
+    /// Text which has not been committed yet
+    pub text: String,
+
+    /// Position of the input method's cursor, in chars
+    pub cursor: usize,
+}
+
+impl Preedit {
+    pub fn new(text: String, cursor: usize) -> Preedit {
+        Preedit { text, cursor }
+    }
+
+    /// Number of cells occupied by the preedit text
+    pub fn width(&self) -> usize {
+        self.text.chars().filter_map(|c| c.width()).sum()
+    }
+
+    /// Number of cells in front of the input method's cursor
+    pub fn cursor_width(&self) -> usize {
+        self.text.chars().take(self.cursor).filter_map(|c| c.width()).sum()
+    }
+}
+
+/// Types that are interested in when the display is resized
+pub trait OnResize {
+    fn on_resize(&mut self, size: &SizeInfo);
 }
 
 /// Terminal size info
@@ -845,12 +1295,62 @@ impl SizeInfo {
         Column(((self.width - 2. * self.padding_x) / self.cell_width) as usize)
     }
 
-    pub fn contains_point(&self, x: usize, y:usize) -> bool {
-        x <= (self.width - self.padding_x) as usize &&
-            x >= self.padding_x as usize &&
-            y <= (self.height - self.padding_y) as usize &&
-            y >= self.padding_y as usize
-    }
+    /// Grow the size until it fits at least `columns` by `lines` cells
+    ///
+    /// Cells are at least one pixel large, so the dimensions of the grid are
+    /// always well defined.
+    pub fn clamp_to_grid(&mut self, columns: u32, lines: u32) {
+        self.cell_width = self.cell_width.max(1.);
+        self.cell_height = self.cell_height.max(1.);
+
+        let min_width = columns.max(1) as f32 * self.cell_width + 2. * self.padding_x;
+        let min_height = lines.max(1) as f32 * self.cell_height + 2. * self.padding_y;
+        self.width = self.width.max(min_width);
+        self.height = self.height.max(min_height);
+    }
+
+    /// Size of the window in pixels for a resize request of the program
+    ///
+    /// Missing dimensions keep the current size, zero fills the screen in that
+    /// dimension. The result never exceeds the `screen_width` by
+    /// `screen_height` pixels of the screen.
+    pub fn requested_window_size(
+        &self,
+        size: WindowSize,
+        screen_width: f32,
+        screen_height: f32,
+    ) -> (f32, f32) {
+        let (width, height) = match size {
+            WindowSize::Pixels { width, height } => (
+                width.map(|width| width as f32),
+                height.map(|height| height as f32),
+            ),
+            WindowSize::Cells { columns, lines } => (
+                columns.map(|columns| columns as f32 * self.cell_width + 2. * self.padding_x),
+                lines.map(|lines| lines as f32 * self.cell_height + 2. * self.padding_y),
+            ),
+        };
+
+        let dimension = |requested: Option<f32>, current: f32, screen: f32| {
+            match requested {
+                Some(size) if size > 0. => size.min(screen),
+                Some(_) => screen,
+                None => current,
+            }
+        };
+
+        (
+            dimension(width, self.width, screen_width),
+            dimension(height, self.height, screen_height),
+        )
+    }
+
+    pub fn contains_point(&self, x: usize, y:usize) -> bool {
+        x <= (self.width - self.padding_x) as usize &&
+            x >= self.padding_x as usize &&
+            y <= (self.height - self.padding_y) as usize &&
+            y >= self.padding_y as usize
+    }
 
     pub fn pixels_to_coords(&self, x: usize, y: usize) -> Point {
         let col = Column(x.saturating_sub(self.padding_x as usize) / (self.cell_width as usize));
@@ -878,8 +1378,15 @@ impl Term {
         self.next_title.take()
     }
 
+    /// Working directory reported by the shell with OSC 7
+    #[inline]
+    pub fn current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_ref().map(|dir| dir.as_path())
+    }
+
     pub fn scroll_display(&mut self, scroll: Scroll) {
         self.grid.scroll_display(scroll);
+        self.highlight_url(None);
         self.dirty = true;
     }
 
@@ -888,6 +1395,97 @@ impl Term {
         self.next_mouse_cursor.take()
     }
 
+    /// URL below a point of the viewport, with its location in the buffer
+    fn url_at(&self, mut point: Point<usize>) -> Option<(String, Locations)> {
+        point.line = self.grid.num_lines().0 - point.line - 1 + self.grid.display_offset();
+
+        // Limit the starting point to the last line in the history
+        point.line = min(point.line, self.grid.len() - 1);
+
+        // Create forwards and backwards iterators
+        let mut iterf = self.grid.iter_from(point);
+        point.col += 1;
+        let mut iterb = self.grid.iter_from(point);
+
+        // Collect all characters until separators, with their location
+        let mut chars = VecDeque::new();
+        while let Some(cell) = iterb.prev() {
+            if URL_SEPARATOR_CHARS.contains(&cell.c) {
+                break;
+            }
+            chars.push_front((cell.c, iterb.cur));
+        }
+        while let Some(cell) = iterf.next() {
+            if URL_SEPARATOR_CHARS.contains(&cell.c) {
+                break;
+            }
+            chars.push_back((cell.c, iterf.cur));
+        }
+
+        // Heuristic to remove all leading '('
+        while chars.front().map_or(false, |&(c, _)| c == '(') {
+            chars.pop_front();
+        }
+
+        // Heuristic to remove all ')' from end of URLs without matching '('
+        let count = |chars: &VecDeque<(char, Point<usize>)>, c: char| {
+            chars.iter().filter(|&&(tc, _)| tc == c).count()
+        };
+        while chars.back().map_or(false, |&(c, _)| c == ')')
+            && count(&chars, '(') < count(&chars, ')')
+        {
+            chars.pop_back();
+        }
+
+        // Check if string is valid url
+        let url: String = chars.iter().map(|&(c, _)| c).collect();
+        Url::parse(&url).ok()?;
+
+        // The first character is the furthest from the bottom of the buffer
+        let locations = Locations { start: chars.back()?.1, end: chars.front()?.1 };
+        Some((url, locations))
+    }
+
+    /// Underline the URL below a point of the viewport, or remove the underline
+    ///
+    /// The underline isn't part of the grid, only the lines of the old and new
+    /// URL are drawn again. Returns whether a URL is underlined.
+    pub fn highlight_url(&mut self, point: Option<Point<usize>>) -> bool {
+        let highlight = point.and_then(|point| self.url_at(point)).map(|(_, locations)| locations);
+        if highlight != self.url_highlight {
+            let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+            let cursor = if highlight.is_some() {
+                MouseCursor::Hand
+            } else if self.mode.intersects(mouse_modes) {
+                MouseCursor::Arrow
+            } else {
+                MouseCursor::Text
+            };
+            self.next_mouse_cursor = Some(cursor);
+            self.url_highlight = highlight;
+            self.dirty = true;
+        }
+
+        self.url_highlight.is_some()
+    }
+
+    /// Switch the encoding of mouse reports
+    ///
+    /// Only the encoding set last is used, resetting it falls back to the
+    /// bytes of X10 instead of an encoding set earlier.
+    fn set_mouse_encoding(&mut self, encoding: TermMode) {
+        self.mode.remove(TermMode::UTF8_MOUSE | TermMode::SGR_MOUSE | TermMode::URXVT_MOUSE);
+        self.mode.insert(encoding);
+    }
+
+    /// Keep the arrow cursor while any of the mouse modes is still set
+    fn update_mouse_cursor(&mut self) {
+        let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        if !self.mode.intersects(mouse_modes) {
+            self.next_mouse_cursor = Some(MouseCursor::Text);
+        }
+    }
+
     pub fn new(config: &Config, size: SizeInfo) -> Term {
         let num_cols = size.cols();
         let num_lines = size.lines();
@@ -902,26 +1500,36 @@ impl Term {
             .collect::<Vec<bool>>();
 
         let scroll_region = Line(0)..grid.num_lines();
+        let left_right_margins = Column(0)..grid.num_cols();
 
         Term {
             next_title: None,
+            current_dir: None,
             next_mouse_cursor: None,
+            url_highlight: None,
             dirty: false,
+            occluded: false,
             visual_bell: VisualBell::new(config),
             next_is_urgent: None,
+            next_bell_is_audible: false,
+            message: None,
+            audible_bell: config.bell().audible(),
+            next_window_size: None,
+            allow_resize_requests: config.window().allow_resize_requests(),
+            bell_rung_at: None,
             input_needs_wrap: false,
             grid,
             alt_grid: alt,
             alt: false,
             font_size: config.font().size(),
             original_font_size: config.font().size(),
-            active_charset: Default::default(),
             cursor: Default::default(),
             cursor_save: Default::default(),
             cursor_save_alt: Default::default(),
             tabs,
             mode: Default::default(),
             scroll_region,
+            left_right_margins,
             size_info: size,
             colors: color::List::from(config.colors()),
             color_modified: [false; color::COUNT],
@@ -932,6 +1540,17 @@ impl Term {
             dynamic_title: config.dynamic_title(),
             tabspaces,
             auto_scroll: config.scrolling().auto_scroll,
+            clear_copies_to_history: config.scrolling().clear_copies_to_history,
+            bold_is_bright: config.draw_bold_text_with_bright_colors(),
+            has_bright_foreground: config.colors().primary.bright_foreground.is_some(),
+            should_exit: false,
+            preedit: None,
+            printer_command: config.printer().command().cloned(),
+            eight_bit_controls: config.terminal().eight_bit_controls(),
+            default_eight_bit_controls: config.terminal().eight_bit_controls(),
+            answerback: config.terminal().answerback(),
+            osc52: config.terminal().osc52(),
+            disable_reports: config.terminal().disable_reports(),
         }
     }
 
@@ -958,11 +1577,34 @@ impl Term {
             }
         }
         self.visual_bell.update_config(config);
+        self.audible_bell = config.bell().audible();
+        self.allow_resize_requests = config.window().allow_resize_requests();
         self.default_cursor_style = config.cursor_style();
         self.dynamic_title = config.dynamic_title();
         self.auto_scroll = config.scrolling().auto_scroll;
-        self.grid
-            .update_history(config.scrolling().history as usize, &self.cursor.template);
+        self.clear_copies_to_history = config.scrolling().clear_copies_to_history;
+        self.bold_is_bright = config.draw_bold_text_with_bright_colors();
+        self.has_bright_foreground = config.colors().primary.bright_foreground.is_some();
+        self.printer_command = config.printer().command().cloned();
+        self.default_eight_bit_controls = config.terminal().eight_bit_controls();
+        self.answerback = config.terminal().answerback();
+        self.osc52 = config.terminal().osc52();
+        self.disable_reports = config.terminal().disable_reports();
+
+        // A font size changed with the bindings is kept until it's reset
+        if self.font_size == self.original_font_size {
+            self.font_size = config.font().size();
+        }
+        self.original_font_size = config.font().size();
+
+        // The history belongs to the primary screen, even while the alternate
+        // screen is active
+        let history_size = config.scrolling().history as usize;
+        if self.alt {
+            self.alt_grid.update_history(history_size);
+        } else {
+            self.grid.update_history(history_size);
+        }
     }
 
     #[inline]
@@ -970,61 +1612,214 @@ impl Term {
         self.dirty
     }
 
-    pub fn selection_to_string(&self) -> Option<String> {
-        /// Need a generic push() for the Append trait
-        trait PushChar {
-            fn push_char(&mut self, c: char);
-            fn maybe_newline(&mut self, grid: &Grid<Cell>, line: usize, ending: Column) {
-                if ending != Column(0) && !grid[line][ending - 1].flags.contains(cell::Flags::WRAPLINE) {
-                    self.push_char('\n');
-                }
+    /// Request that the terminal be closed
+    #[inline]
+    pub fn exit(&mut self) {
+        self.should_exit = true;
+    }
+
+    #[inline]
+    pub fn should_exit(&self) -> bool {
+        self.should_exit
+    }
+
+    /// Update the text being composed by the input method
+    ///
+    /// An empty preedit string clears the composition.
+    pub fn set_preedit(&mut self, preedit: Option<Preedit>) {
+        let preedit = preedit.and_then(|preedit| {
+            if preedit.text.is_empty() {
+                None
+            } else {
+                Some(preedit)
             }
+        });
+
+        if self.preedit != preedit {
+            self.preedit = preedit;
+            self.dirty = true;
         }
+    }
+
+    #[inline]
+    pub fn preedit(&self) -> Option<&Preedit> {
+        self.preedit.as_ref()
+    }
 
-        impl PushChar for String {
-            #[inline]
-            fn push_char(&mut self, c: char) {
-                self.push(c);
+    /// Text of the screen without its history, like it's sent to the printer
+    pub fn screen_to_string(&self) -> String {
+        let mut text = String::new();
+        for line in IndexRange::from(Line(0)..self.grid.num_lines()) {
+            for cell in &self.grid[line] {
+                if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                    text.push(cell.c);
+                }
+            }
+
+            while text.ends_with(' ') {
+                text.pop();
             }
+            text.push('\n');
         }
+        text
+    }
+
+    /// Send output to the printer command in the background
+    fn print(&self, data: Vec<u8>) {
+        let command = match self.printer_command {
+            Some(ref command) => command.clone(),
+            None => {
+                debug!("Discarding {} bytes for the printer", data.len());
+                return;
+            },
+        };
+
+        thread::spawn_named("printer", move || {
+            if let Err(err) = daemon::spawn_with_input(command.program(), command.args(), &data) {
+                warn!("Unable to run printer command {:?}: {}", command.program(), err);
+            }
+        });
+    }
+
+    pub fn selection_to_string(&self) -> Option<String> {
+        let mut res = String::new();
+        self.visit_selection(|cell| res.push(cell.map_or('\n', |cell| cell.c)))?;
+        Some(res)
+    }
 
-        use std::ops::Range;
+    /// Text of the screen, or of the whole scrollback history
+    ///
+    /// Lines are converted like selected lines, so wrapped lines are joined.
+    /// Trailing whitespace is removed from every line and from the end.
+    pub fn contents_to_string(&self, scrollback: bool) -> String {
+        let top = if scrollback {
+            self.grid.len() - 1
+        } else {
+            self.visible_to_buffer(Point::new(Line(0), Column(0))).line
+        };
+        let bottom = if scrollback { 0 } else { top + 1 - self.grid.num_lines().0 };
 
-        trait Append : PushChar {
-            fn append(&mut self, grid: &Grid<Cell>, line: usize, cols: Range<Column>);
+        let mut text = String::new();
+        let max_col = Column(usize::max_value() - 1);
+        for line in (bottom..=top).rev() {
+            self.visit_selected_line(line, Column(0)..max_col, &mut |cell: Option<&Cell>| {
+                text.push(cell.map_or('\n', |cell| cell.c))
+            });
         }
 
-        impl Append for String {
-            fn append(&mut self, grid: &Grid<Cell>, mut line: usize, cols: Range<Column>) {
-                // Select until last line still within the buffer
-                line = min(line, grid.len() - 1);
+        let mut contents = text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n");
+        let len = contents.trim_end().len();
+        contents.truncate(len);
+        contents.push('\n');
+        contents
+    }
 
-                let grid_line = &grid[line];
-                let line_length = grid_line.line_length();
-                let line_end = min(line_length, cols.end + 1);
+    /// Convert the selection to HTML, keeping the colors and text attributes
+    pub fn selection_to_html(&self) -> Option<String> {
+        let mut html = Html::new(
+            self.colors[NamedColor::Foreground],
+            self.colors[NamedColor::Background],
+        );
 
-                if line_end.0 == 0 && cols.end >= grid.num_cols() - 1 {
-                    self.push('\n');
-                } else if cols.start < line_end {
-                    for cell in &grid_line[cols.start..line_end] {
-                        if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
-                            self.push(cell.c);
-                        }
-                    }
+        self.visit_selection(|cell| match cell {
+            Some(cell) => {
+                let c = if cell.flags.contains(cell::Flags::HIDDEN) { ' ' } else { cell.c };
+                html.push(c, self.html_style(cell));
+            },
+            None => html.newline(),
+        })?;
 
-                    if cols.end >= grid.num_cols() - 1 {
-                        self.maybe_newline(grid, line, line_end);
-                    }
-                }
+        Some(html.finish())
+    }
+
+    /// Colors and attributes of a cell, resolved like they're rendered
+    fn html_style(&self, cell: &Cell) -> html::Style {
+        let mut fg = compute_fg_rgb(
+            &self.colors,
+            self.bold_is_bright,
+            self.has_bright_foreground,
+            cell.fg,
+            cell,
+        );
+        let mut bg = compute_bg_rgb(&self.colors, cell.bg);
+
+        if cell.inverse() {
+            mem::swap(&mut fg, &mut bg);
+        }
+
+        html::Style {
+            fg,
+            bg: if is_default_bg(&self.colors, bg) { None } else { Some(bg) },
+            bold: cell.bold(),
+            italic: cell.flags.contains(cell::Flags::ITALIC),
+            underline: cell.flags.contains(cell::Flags::UNDERLINE),
+        }
+    }
+
+    /// Snapshot of the state for bug reports
+    ///
+    /// The cells of the grids are only included with `include_grid`, they're
+    /// the bulk of the dump and might contain private output.
+    pub fn state_dump(&self, include_grid: bool) -> StateDump {
+        let cursor = |cursor: &Cursor| CursorDump {
+            point: cursor.point,
+            pen: cursor.template,
+            charsets: cursor.charsets.0.iter().map(|charset| format!("{:?}", charset)).collect(),
+            active_charset: cursor.active_charset as usize,
+        };
+        let hex = |rgb: Rgb| format!("#{:02x}{:02x}{:02x}", rgb.r, rgb.g, rgb.b);
+
+        let colors = (0..color::COUNT)
+            .filter(|&index| self.color_modified[index])
+            .map(|index| ColorDump {
+                index,
+                color: hex(self.colors[index]),
+                default: hex(self.original_colors[index]),
+            })
+            .collect();
+
+        let grid = |grid: &Grid<Cell>| {
+            if include_grid {
+                let mut grid = grid.clone();
+                grid.truncate();
+                Some(grid)
+            } else {
+                None
             }
+        };
+
+        StateDump {
+            lines: self.grid.num_lines().0,
+            columns: self.grid.num_cols().0,
+            history: self.grid.scroll_limit(),
+            display_offset: self.grid.display_offset(),
+            alt_screen: self.alt,
+            modes: dump::mode_names(self.mode),
+            input_needs_wrap: self.input_needs_wrap,
+            eight_bit_controls: self.eight_bit_controls,
+            cursor: cursor(&self.cursor),
+            saved_cursor: cursor(&self.cursor_save),
+            saved_cursor_alt: cursor(&self.cursor_save_alt),
+            cursor_style: self.cursor_style,
+            scroll_region: (self.scroll_region.start.0, self.scroll_region.end.0),
+            left_right_margins: (self.left_right_margins.start.0, self.left_right_margins.end.0),
+            tab_stops: (0..self.tabs.len()).filter(|&i| self.tabs[i]).collect(),
+            colors,
+            grid: grid(&self.grid),
+            alt_grid: grid(&self.alt_grid),
         }
+    }
 
+    /// Visit the selected cells in reading order
+    ///
+    /// Line breaks are visited as `None`, joined lines are not broken.
+    fn visit_selection<F>(&self, mut visit: F) -> Option<()>
+        where F: FnMut(Option<&Cell>)
+    {
         let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
         let selection = self.grid.selection.clone()?;
         let span = selection.to_span(self, alt_screen)?;
 
-        let mut res = String::new();
-
         let Locations { mut start, mut end } = span.to_locations();
 
         if start > end {
@@ -1037,36 +1832,66 @@ impl Term {
         match line_count {
             // Selection within single line
             0 => {
-                res.append(&self.grid, start.line, start.col..end.col);
+                self.visit_selected_line(start.line, start.col..end.col, &mut visit);
             },
 
             // Selection ends on line following start
             1 => {
                 // Ending line
-                res.append(&self.grid, end.line, end.col..max_col);
+                self.visit_selected_line(end.line, end.col..max_col, &mut visit);
 
                 // Starting line
-                res.append(&self.grid, start.line, Column(0)..start.col);
+                self.visit_selected_line(start.line, Column(0)..start.col, &mut visit);
 
             },
 
             // Multi line selection
             _ => {
                 // Ending line
-                res.append(&self.grid, end.line, end.col..max_col);
+                self.visit_selected_line(end.line, end.col..max_col, &mut visit);
 
                 let middle_range = (start.line + 1)..(end.line);
                 for line in middle_range.rev() {
-                    res.append(&self.grid, line, Column(0)..max_col);
+                    self.visit_selected_line(line, Column(0)..max_col, &mut visit);
                 }
 
                 // Starting line
-                res.append(&self.grid, start.line, Column(0)..start.col);
+                self.visit_selected_line(start.line, Column(0)..start.col, &mut visit);
 
             }
         }
 
-        Some(res)
+        Some(())
+    }
+
+    fn visit_selected_line<F>(&self, mut line: usize, cols: Range<Column>, visit: &mut F)
+        where F: FnMut(Option<&Cell>)
+    {
+        let grid = &self.grid;
+
+        // Select until last line still within the buffer
+        line = min(line, grid.len() - 1);
+
+        let grid_line = &grid[line];
+        let line_length = grid_line.line_length();
+        let line_end = min(line_length, cols.end + 1);
+
+        if line_end.0 == 0 && cols.end >= grid.num_cols() - 1 {
+            visit(None);
+        } else if cols.start < line_end {
+            for cell in &grid_line[cols.start..line_end] {
+                if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                    visit(Some(cell));
+                }
+            }
+
+            if cols.end >= grid.num_cols() - 1
+                && line_end != Column(0)
+                && !grid_line[line_end - 1].flags.contains(cell::Flags::WRAPLINE)
+            {
+                visit(None);
+            }
+        }
     }
 
     pub(crate) fn visible_to_buffer(&self, point: Point) -> Point<usize> {
@@ -1105,12 +1930,7 @@ impl Term {
         config: &'b Config,
         window_focused: bool,
     ) -> RenderableCellsIter {
-        let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
-        let selection = self.grid.selection.as_ref()
-            .and_then(|s| s.to_span(self, alt_screen))
-            .map(|span| {
-                span.to_locations()
-            });
+        let selection = self.selection_locations();
 
         let cursor = if window_focused || !config.unfocused_hollow_cursor() {
             self.cursor_style.unwrap_or(self.default_cursor_style)
@@ -1125,10 +1945,88 @@ impl Term {
             self.mode,
             config,
             selection,
+            self.url_highlight,
             cursor,
+            self.preedit.as_ref(),
         )
     }
 
+    /// Update the renderable cells of the lines which changed since the last frame
+    ///
+    /// Besides the lines modified in the grid, the lines of the old and new
+    /// cursor position and of the old and new URL underline are updated. A change of the selection or the viewport
+    /// updates all lines.
+    pub fn update_render_cache(
+        &mut self,
+        config: &Config,
+        window_focused: bool,
+        cache: &mut RenderCache,
+    ) {
+        let num_lines = *self.grid.num_lines();
+        let cols = self.grid.num_cols();
+        let selection = self.selection_locations();
+        let cursor_line = self.cursor.point.line;
+
+        let reuse = cache.valid
+            && cache.lines.len() == num_lines
+            && cache.cols == cols
+            && self.grid.display_offset() == 0
+            && cache.selection == selection;
+
+        // Lines which gained or lost the URL underline
+        let mut url_lines = Vec::new();
+        if cache.url_highlight != self.url_highlight {
+            for loc in cache.url_highlight.iter().chain(self.url_highlight.iter()) {
+                for line in loc.start.line..=loc.end.line {
+                    if let ViewportPosition::Visible(line) = self.grid.buffer_line_to_visible(line) {
+                        url_lines.push(line);
+                    }
+                }
+            }
+        }
+
+        let damage: Vec<bool> = (0..num_lines)
+            .map(Line)
+            .map(|line| {
+                !reuse
+                    || self.grid.is_damaged(line)
+                    || line == cursor_line
+                    || line == cache.cursor_line
+                    || url_lines.contains(&line)
+            })
+            .collect();
+
+        cache.lines.resize(num_lines, Vec::new());
+        for (cells, _) in cache.lines.iter_mut().zip(&damage).filter(|&(_, damaged)| *damaged) {
+            cells.clear();
+        }
+
+        let cells = self.renderable_cells(config, window_focused).with_damage(damage);
+        cache.cursor = cells.cursor();
+        for cell in cells {
+            cache.lines[cell.line.0].push(cell);
+        }
+
+        // Reuse is only possible at the bottom, where the cursor is visible
+        cache.valid = self.grid.display_offset() == 0;
+        cache.cols = cols;
+        cache.cursor_line = cursor_line;
+        cache.selection = selection;
+        cache.url_highlight = self.url_highlight;
+
+        self.grid.reset_damage();
+    }
+
+    /// Active selection in buffer locations
+    fn selection_locations(&self) -> Option<Locations> {
+        let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
+        self.grid.selection.as_ref()
+            .and_then(|s| s.to_span(self, alt_screen))
+            .map(|span| {
+                span.to_locations()
+            })
+    }
+
     /// Resize terminal to new dimensions
     pub fn resize(&mut self, size : &SizeInfo) {
         debug!("Term::resize");
@@ -1152,8 +2050,14 @@ impl Term {
             return;
         }
 
+        // The cursor isn't in the last column anymore, or it's in a new one
+        if old_cols != num_cols {
+            self.input_needs_wrap = false;
+        }
+
         self.grid.selection = None;
         self.alt_grid.selection = None;
+        self.url_highlight = None;
 
         // Should not allow less than 1 col, causes all sorts of checks to be required.
         if num_cols <= Column(1) {
@@ -1168,13 +2072,13 @@ impl Term {
         // Scroll up to keep cursor in terminal
         if self.cursor.point.line >= num_lines {
             let lines = self.cursor.point.line - num_lines + 1;
-            self.grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor.template);
+            self.grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor.erase_template());
         }
 
         // Scroll up alt grid as well
         if self.cursor_save_alt.point.line >= num_lines {
             let lines = self.cursor_save_alt.point.line - num_lines + 1;
-            self.alt_grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor_save_alt.template);
+            self.alt_grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor_save_alt.erase_template());
         }
 
         // Move prompt down when growing if scrollback lines are available
@@ -1194,8 +2098,9 @@ impl Term {
         self.grid.resize(num_lines, num_cols, &Cell::default());
         self.alt_grid.resize(num_lines, num_cols, &Cell::default());
 
-        // Reset scrolling region to new size
+        // Reset scrolling region and margins to new size
         self.scroll_region = Line(0)..self.grid.num_lines();
+        self.left_right_margins = Column(0)..self.grid.num_cols();
 
         // Ensure cursors are in-bounds.
         self.cursor.point.col = min(self.cursor.point.col, num_cols - 1);
@@ -1226,14 +2131,59 @@ impl Term {
         &self.cursor
     }
 
+    /// Switch between the primary and the alternate screen
+    ///
+    /// The alternate screen is cleared when it's entered, with the background
+    /// of the pen at that time. Clearing it on exit would keep the background a
+    /// program left behind for the next one.
     pub fn swap_alt(&mut self) {
-        if self.alt {
-            let template = &self.cursor.template;
-            self.grid.region_mut(..).each(|c| c.reset(template));
-        }
+        // The selected text isn't visible on the other screen
+        self.grid.selection = None;
 
         self.alt = !self.alt;
         ::std::mem::swap(&mut self.grid, &mut self.alt_grid);
+
+        if self.alt {
+            let template = self.cursor.erase_template();
+            self.grid.region_mut(..).each(|c| c.reset(&template));
+        }
+        self.grid.damage_all();
+    }
+
+    /// Clear the selection if any of the cells from `start` to `end` are selected
+    ///
+    /// The region is in the active area and includes both ends. Like text, it
+    /// covers the whole lines between the line of `start` and the one of `end`.
+    fn clear_selection_in(&mut self, start: Point, end: Point) {
+        // Buffer lines count up from the bottom, so text starts at the highest line
+        let num_lines = self.grid.num_lines().0;
+        let to_buffer = |point: Point| Point { line: num_lines - 1 - point.line.0, col: point.col };
+        let (start, end) = (to_buffer(start), to_buffer(end));
+        let before = |a: Point<usize>, b: Point<usize>| {
+            a.line > b.line || (a.line == b.line && a.col < b.col)
+        };
+
+        let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
+        let intersects = self.grid.selection.as_ref()
+            .and_then(|selection| selection.to_span(self, alt_screen))
+            .map_or(false, |span| {
+                let Locations { start: last, end: first } = span.to_locations();
+                !before(end, first) && !before(last, start)
+            });
+
+        if intersects {
+            self.grid.selection = None;
+        }
+    }
+
+    /// Clear the selection if it touches any of the lines in the history
+    fn clear_selection_in_history(&mut self) {
+        let history = self.grid.num_lines().0 as isize..isize::max_value();
+        let intersects = self.grid.selection.as_ref()
+            .map_or(false, |selection| selection.intersects_lines(history));
+        if intersects {
+            self.grid.selection = None;
+        }
     }
 
     /// Scroll screen down
@@ -1246,8 +2196,20 @@ impl Term {
         lines = min(lines, self.scroll_region.end - self.scroll_region.start);
         lines = min(lines, self.scroll_region.end - origin);
 
+        // Only the cells between the margins move, lines can't be rotated
+        if self.has_narrow_margins() {
+            let region = origin..self.scroll_region.end;
+            for line in IndexRange(region.start..region.end - lines).rev() {
+                self.copy_margin_cells(line, line + lines);
+            }
+            for line in IndexRange(region.start..region.start + lines) {
+                self.clear_margin_cells(line);
+            }
+            return;
+        }
+
         // Scroll between origin and bottom
-        self.grid.scroll_down(&(origin..self.scroll_region.end), lines, &self.cursor.template);
+        self.grid.scroll_down(&(origin..self.scroll_region.end), lines, &self.cursor.erase_template());
     }
 
     /// Scroll screen up
@@ -1259,113 +2221,438 @@ impl Term {
         trace!("scroll_up_relative: origin={}, lines={}", origin, lines);
         let lines = min(lines, self.scroll_region.end - self.scroll_region.start);
 
-        // Scroll from origin to bottom less number of lines
-        self.grid.scroll_up(&(origin..self.scroll_region.end), lines, &self.cursor.template);
-    }
+        self.snap_to_bottom();
 
-    fn deccolm(&mut self) {
-        // Setting 132 column font makes no sense, but run the other side effects
-        // Clear scrolling region
-        let scroll_region = Line(0)..self.grid.num_lines();
-        self.set_scrolling_region(scroll_region);
+        // Only the cells between the margins move, nothing enters the history
+        if self.has_narrow_margins() {
+            let lines = min(lines, self.scroll_region.end - origin);
+            let region = origin..self.scroll_region.end;
+            for line in IndexRange(region.start..region.end - lines) {
+                self.copy_margin_cells(line + lines, line);
+            }
+            for line in IndexRange(region.end - lines..region.end) {
+                self.clear_margin_cells(line);
+            }
+            return;
+        }
 
-        // Clear grid
-        let template = self.cursor.template;
-        self.grid.region_mut(..).each(|c| c.reset(&template));
+        // Scroll from origin to bottom less number of lines
+        self.grid.scroll_up(&(origin..self.scroll_region.end), lines, &self.cursor.erase_template());
     }
 
+    /// Columns between the left and right margins
+    ///
+    /// Without left/right margin mode, these are all columns.
     #[inline]
-    pub fn background_color(&self) -> Rgb {
-        self.colors[NamedColor::Background]
+    fn horizontal_margins(&self) -> Range<Column> {
+        if self.mode.contains(mode::TermMode::LEFT_RIGHT_MARGIN) {
+            self.left_right_margins.clone()
+        } else {
+            Column(0)..self.grid.num_cols()
+        }
     }
-}
 
-impl ansi::TermInfo for Term {
+    /// The margins don't span all columns, so lines can't be scrolled as a whole
     #[inline]
-    fn lines(&self) -> Line {
-        self.grid.num_lines()
+    fn has_narrow_margins(&self) -> bool {
+        self.horizontal_margins() != (Column(0)..self.grid.num_cols())
     }
 
+    /// The cursor is between the left and right margins
     #[inline]
-    fn cols(&self) -> Column {
-        self.grid.num_cols()
+    fn cursor_in_margins(&self) -> bool {
+        self.horizontal_margins().contains_(self.cursor.point.col)
     }
-}
 
-impl ansi::Handler for Term {
-    /// Set the window title
+    /// Right edge for the cursor, the right margin unless the cursor is past it
     #[inline]
-    fn set_title(&mut self, title: &str) {
-        if self.dynamic_title {
-            self.next_title = Some(title.to_owned());
+    fn right_edge(&self) -> Column {
+        let right = self.horizontal_margins().end;
+        if self.cursor.point.col < right {
+            right
+        } else {
+            self.grid.num_cols()
         }
     }
 
-    /// Set the mouse cursor
-    #[inline]
-    fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
-        self.next_mouse_cursor = Some(cursor);
-    }
-
-    /// A character to be displayed
-    #[inline]
-    fn input(&mut self, c: char) {
-        // If enabled, scroll to bottom when character is received
-        if self.auto_scroll {
-            self.scroll_display(Scroll::Bottom);
+    /// Copy the cells between the margins from line `src` to line `dst`
+    fn copy_margin_cells(&mut self, src: Line, dst: Line) {
+        let margins = self.horizontal_margins();
+        for col in IndexRange(margins) {
+            let cell = self.grid[src][col];
+            self.grid[dst][col] = cell;
         }
+        self.clear_split_wide_chars(dst);
+    }
 
-        if self.input_needs_wrap {
-            if !self.mode.contains(mode::TermMode::LINE_WRAP) {
-                return;
+    /// Erase the cells between the margins of a line
+    fn clear_margin_cells(&mut self, line: Line) {
+        let margins = self.horizontal_margins();
+        {
+            let template = self.cursor.erase_template();
+            let row = &mut self.grid[line];
+            for c in &mut row[margins] {
+                c.reset(&template);
             }
+        }
+        self.clear_split_wide_chars(line);
+    }
 
-            trace!("wrapping");
-
-            {
-                let location = Point {
-                    line: self.cursor.point.line,
-                    col: self.cursor.point.col
-                };
+    /// Shift the cells of a line from `start` to the right, inserting `count` blanks
+    ///
+    /// Cells shifted over the right margin before `right` are lost. The blanks
+    /// have the background of the cursor and the cursor doesn't move.
+    fn insert_cells(&mut self, line: Line, start: Column, right: Column, count: Column) {
+        let count = min(count, right - start);
+        let destination = start + count;
 
-                let cell = &mut self.grid[&location];
-                cell.flags.insert(cell::Flags::WRAPLINE);
+        {
+            let cells = self.grid[line].cells_mut();
+            let num_cells = (right - destination).0;
+            unsafe {
+                let src = cells[start.0..].as_ptr();
+                let dst = cells[destination.0..].as_mut_ptr();
+                ptr::copy(src, dst, num_cells);
             }
 
-            if (self.cursor.point.line + 1) >= self.scroll_region.end {
-                self.linefeed();
-            } else {
-                self.cursor.point.line += 1;
+            let template = self.cursor.erase_template();
+            for c in &mut cells[start.0..destination.0] {
+                c.reset(&template);
             }
+        }
 
-            self.cursor.point.col = Column(0);
-            self.input_needs_wrap = false;
+        self.clear_split_wide_chars(line);
+    }
+
+    /// Shift the cells of a line after `start` to the left, deleting `count` cells
+    ///
+    /// Blanks with the background of the cursor are inserted at the right
+    /// margin before `right` and the cursor doesn't move.
+    fn delete_cells(&mut self, line: Line, start: Column, right: Column, count: Column) {
+        let count = min(count, right - start);
+        let end = start + count;
+
+        {
+            let cells = self.grid[line].cells_mut();
+            let num_cells = (right - end).0;
+            unsafe {
+                let src = cells[end.0..].as_ptr();
+                let dst = cells[start.0..].as_mut_ptr();
+                ptr::copy(src, dst, num_cells);
+            }
+
+            let template = self.cursor.erase_template();
+            for c in &mut cells[(right - count).0..right.0] {
+                c.reset(&template);
+            }
+        }
+
+        self.clear_split_wide_chars(line);
+    }
+
+    /// Clear the halves of wide chars which were split by editing a line
+    ///
+    /// A wide char without its spacer, or a spacer without its wide char,
+    /// can't be drawn, so they're replaced by blanks.
+    fn clear_split_wide_chars(&mut self, line: Line) {
+        let template = self.cursor.erase_template();
+        let row = &mut self.grid[line];
+        let cols = row.len();
+        for col in 0..cols {
+            let flags = row[Column(col)].flags;
+            let split = if flags.contains(cell::Flags::WIDE_CHAR) {
+                col + 1 == cols || !row[Column(col + 1)].flags.contains(cell::Flags::WIDE_CHAR_SPACER)
+            } else if flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                col == 0 || !row[Column(col - 1)].flags.contains(cell::Flags::WIDE_CHAR)
+            } else {
+                false
+            };
+
+            if split {
+                row[Column(col)].reset(&template);
+            }
+        }
+    }
+
+    /// Show the bottom of the scrollback for new output, if enabled
+    ///
+    /// Otherwise the viewport stays on the lines in the history which are
+    /// shown while new lines enter it.
+    #[inline]
+    fn snap_to_bottom(&mut self) {
+        if self.auto_scroll && self.grid.display_offset() != 0 {
+            self.scroll_display(Scroll::Bottom);
+        }
+    }
+
+    /// Send a reply to the program running in the terminal
+    ///
+    /// Replies are written with 7-bit controls, which are replaced by their
+    /// single byte forms with 8-bit controls.
+    fn reply<W: io::Write>(&self, writer: &mut W, reply: &[u8]) {
+        if self.eight_bit_controls {
+            self.send_report(writer, &eight_bit_controls(reply));
+        } else {
+            self.send_report(writer, reply);
+        }
+    }
+
+    /// Write the reply to a query of a program
+    ///
+    /// Replies end up in the input of the shell once the program which sent
+    /// the query is gone, so all of them are written here, where they're
+    /// dropped with `terminal.disable_reports`.
+    fn send_report<W: io::Write>(&self, writer: &mut W, report: &[u8]) {
+        if self.disable_reports {
+            debug!("Dropping report: {:?}", String::from_utf8_lossy(report));
+            return;
+        }
+
+        let _ = writer.write_all(report);
+    }
+
+    fn deccolm(&mut self) {
+        // Setting 132 column font makes no sense, but run the other side effects
+        // Clear scrolling region
+        let scroll_region = Line(0)..self.grid.num_lines();
+        self.set_scrolling_region(scroll_region);
+
+        // Clear left/right margin mode and the margins
+        self.mode.remove(mode::TermMode::LEFT_RIGHT_MARGIN);
+        self.left_right_margins = Column(0)..self.grid.num_cols();
+
+        // Clear grid
+        let template = self.cursor.erase_template();
+        self.grid.region_mut(..).each(|c| c.reset(&template));
+    }
+
+    /// Move the cursor to the start of the next line, before printing
+    fn wrapline(&mut self) {
+        trace!("wrapping");
+
+        // Lines only continue on the next one when they span all columns
+        if !self.has_narrow_margins() {
+            let location = Point {
+                line: self.cursor.point.line,
+                col: self.cursor.point.col
+            };
+
+            let cell = &mut self.grid[&location];
+            cell.flags.insert(cell::Flags::WRAPLINE);
+        }
+
+        if (self.cursor.point.line + 1) >= self.scroll_region.end {
+            self.linefeed();
+        } else {
+            self.cursor.point.line += 1;
+        }
+
+        // Wrapping goes to the left margin, unless the cursor is past the right one
+        let margins = self.horizontal_margins();
+        self.cursor.point.col = if self.cursor.point.col < margins.end {
+            margins.start
+        } else {
+            Column(0)
+        };
+    }
+
+    /// Clear part of the current line, selective erases keep protected cells
+    ///
+    /// Like every erase, this cancels a pending wrap.
+    fn erase_line(&mut self, mode: ansi::LineClearMode, selective: bool) {
+        self.input_needs_wrap = false;
+        let template = self.cursor.erase_template();
+
+        let col = self.cursor.point.col;
+        let line = self.cursor.point.line;
+        let last_col = self.grid.num_cols() - 1;
+        let cleared = match mode {
+            ansi::LineClearMode::Right => (col, last_col),
+            ansi::LineClearMode::Left => (Column(0), col),
+            ansi::LineClearMode::All => (Column(0), last_col),
+        };
+        self.clear_selection_in(Point::new(line, cleared.0), Point::new(line, cleared.1));
+
+        let row = &mut self.grid[line];
+        for cell in &mut row[cleared.0..cleared.1 + 1] {
+            erase(cell, &template, selective);
+        }
+    }
+
+    /// Clear part of the screen, selective erases keep protected cells
+    fn erase_screen(&mut self, mode: ansi::ClearMode, selective: bool) {
+        // Clearing the history alone doesn't touch the cursor
+        match mode {
+            ansi::ClearMode::Saved => (),
+            _ => self.input_needs_wrap = false,
+        }
+        let template = self.cursor.erase_template();
+
+        let cursor = self.cursor.point;
+        let first = Point::new(Line(0), Column(0));
+        let last = Point::new(self.grid.num_lines() - 1, self.grid.num_cols() - 1);
+        match mode {
+            ansi::ClearMode::Below => self.clear_selection_in(cursor, last),
+            ansi::ClearMode::Above => self.clear_selection_in(first, cursor),
+            ansi::ClearMode::All => self.clear_selection_in(first, last),
+            ansi::ClearMode::Saved => self.clear_selection_in_history(),
+        }
+
+        match mode {
+            ansi::ClearMode::Below => {
+                for cell in &mut self.grid[self.cursor.point.line][self.cursor.point.col..] {
+                    erase(cell, &template, selective);
+                }
+                if self.cursor.point.line < self.grid.num_lines() - 1 {
+                    self.grid.region_mut((self.cursor.point.line + 1)..)
+                        .each(|cell| erase(cell, &template, selective));
+                }
+            },
+            ansi::ClearMode::All => {
+                // Like xterm, the cleared lines can be kept in the history,
+                // unless protected cells have to stay where they are
+                if self.clear_copies_to_history && !self.alt && !selective {
+                    let num_lines = self.grid.num_lines();
+                    let occupied = (0..num_lines.0)
+                        .rev()
+                        .find(|&line| self.grid[Line(line)].iter().any(|cell| !cell.is_empty()))
+                        .map_or(0, |line| line + 1);
+                    if occupied > 0 {
+                        self.grid.scroll_up(&(Line(0)..num_lines), Line(occupied), &template);
+                    }
+                }
+
+                self.grid.region_mut(..).each(|cell| erase(cell, &template, selective));
+            },
+            ansi::ClearMode::Above => {
+                // If clearing more than one line
+                if self.cursor.point.line > Line(0) {
+                    // Fully clear all lines before the current line
+                    self.grid.region_mut(..self.cursor.point.line)
+                        .each(|cell| erase(cell, &template, selective));
+                }
+                // Clear up to the current column in the current line
+                let end = min(self.cursor.point.col + 1, self.grid.num_cols());
+                for cell in &mut self.grid[self.cursor.point.line][..end] {
+                    erase(cell, &template, selective);
+                }
+            },
+            // Only the history is cleared, the visible lines are left alone
+            ansi::ClearMode::Saved => {
+                self.grid.clear_history();
+            }
+        }
+    }
+
+    #[inline]
+    pub fn background_color(&self) -> Rgb {
+        self.colors[NamedColor::Background]
+    }
+}
+
+impl ansi::TermInfo for Term {
+    #[inline]
+    fn lines(&self) -> Line {
+        self.grid.num_lines()
+    }
+
+    #[inline]
+    fn cols(&self) -> Column {
+        self.grid.num_cols()
+    }
+
+    #[inline]
+    fn eight_bit_controls(&self) -> bool {
+        self.eight_bit_controls
+    }
+}
+
+impl ansi::Handler for Term {
+    /// Set the window title
+    #[inline]
+    fn set_title(&mut self, title: &str) {
+        if self.dynamic_title {
+            self.next_title = Some(title.to_owned());
+        }
+    }
+
+    #[inline]
+    fn printer_output(&mut self, data: &[u8]) {
+        trace!("printer_output: {} bytes", data.len());
+        self.print(data.to_vec());
+    }
+
+    #[inline]
+    fn print_screen(&mut self) {
+        trace!("print_screen");
+        let text = self.screen_to_string();
+        self.print(text.into_bytes());
+    }
+
+    /// Set the working directory of the shell
+    #[inline]
+    fn set_current_dir(&mut self, dir: PathBuf) {
+        trace!("set_current_dir: {:?}", dir);
+        self.current_dir = Some(dir);
+    }
+
+    /// Set the mouse cursor
+    #[inline]
+    fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
+        self.next_mouse_cursor = Some(cursor);
+    }
+
+    /// A character to be displayed
+    #[inline]
+    fn input(&mut self, c: char) {
+        self.snap_to_bottom();
+
+        // Without auto wrap, the last column is overwritten instead. A wide
+        // char which doesn't fit into the last column wraps right away.
+        if self.mode.contains(mode::TermMode::LINE_WRAP) {
+            let too_wide = c.width() == Some(2) && self.cursor.point.col + 2 > self.right_edge();
+            if self.input_needs_wrap || too_wide {
+                self.wrapline();
+            }
         }
+        self.input_needs_wrap = false;
+
+        // Column after the last one the char may be written to
+        let right = self.right_edge();
 
         {
             // Number of cells the char will occupy
             if let Some(width) = c.width() {
-                // Sigh, borrowck making us check the width twice. Hopefully the
-                // optimizer can fix it.
-                let num_cols = self.grid.num_cols();
+                // A wide char which doesn't fit is moved back from the margin
+                // when it can't be wrapped
+                if !self.mode.contains(mode::TermMode::LINE_WRAP) && self.cursor.point.col + width > right {
+                    self.cursor.point.col = right - min(width, right.0);
+                }
+
                 {
                     // If in insert mode, first shift cells to the right.
-                    if self.mode.contains(mode::TermMode::INSERT) && self.cursor.point.col + width < num_cols {
+                    if self.mode.contains(mode::TermMode::INSERT) && self.cursor.point.col + width < right {
                         let line = self.cursor.point.line; // borrowck
                         let col = self.cursor.point.col;
-                        let line = &mut self.grid[line];
+                        let cells = self.grid[line].cells_mut();
 
-                        let src = line[col..].as_ptr();
-                        let dst = line[(col + width)..].as_mut_ptr();
+                        let src = cells[col.0..].as_ptr();
+                        let dst = cells[(col + width).0..].as_mut_ptr();
                         unsafe {
                             // memmove
-                            ptr::copy(src, dst, (num_cols - col - width).0);
+                            ptr::copy(src, dst, (right - col - width).0);
+                        }
+
+                        // Only half of a wide char pushed over the margin is left
+                        let last = &mut cells[right.0 - 1];
+                        if last.flags.contains(cell::Flags::WIDE_CHAR) {
+                            *last = self.cursor.erase_template();
                         }
                     }
 
                     let cell = &mut self.grid[&self.cursor.point];
                     *cell = self.cursor.template;
-                    cell.c = self.cursor.charsets[self.active_charset].map(c);
+                    cell.c = self.cursor.charsets[self.cursor.active_charset].map(c);
 
                     // Handle wide chars
                     if width == 2 {
@@ -1374,7 +2661,7 @@ impl ansi::Handler for Term {
                 }
 
                 // Set spacer cell for wide chars.
-                if width == 2 && self.cursor.point.col + 1 < num_cols {
+                if width == 2 && self.cursor.point.col + 1 < right {
                     self.cursor.point.col += 1;
                     let spacer = &mut self.grid[&self.cursor.point];
                     *spacer = self.cursor.template;
@@ -1383,12 +2670,11 @@ impl ansi::Handler for Term {
             }
         }
 
-        if (self.cursor.point.col + 1) < self.grid.num_cols() {
+        if (self.cursor.point.col + 1) < right {
             self.cursor.point.col += 1;
-        } else {
+        } else if self.mode.contains(mode::TermMode::LINE_WRAP) {
             self.input_needs_wrap = true;
         }
-
     }
 
     #[inline]
@@ -1404,14 +2690,15 @@ impl ansi::Handler for Term {
     #[inline]
     fn goto(&mut self, line: Line, col: Column) {
         trace!("goto: line={}, col={}", line, col);
-        let (y_offset, max_y) = if self.mode.contains(mode::TermMode::ORIGIN) {
-            (self.scroll_region.start, self.scroll_region.end - 1)
+        let (y_offset, max_y, x_offset, max_x) = if self.mode.contains(mode::TermMode::ORIGIN) {
+            let margins = self.horizontal_margins();
+            (self.scroll_region.start, self.scroll_region.end - 1, margins.start, margins.end - 1)
         } else {
-            (Line(0), self.grid.num_lines() - 1)
+            (Line(0), self.grid.num_lines() - 1, Column(0), self.grid.num_cols() - 1)
         };
 
         self.cursor.point.line = min(line + y_offset, max_y);
-        self.cursor.point.col = min(col, self.grid.num_cols() - 1);
+        self.cursor.point.col = min(col + x_offset, max_x);
         self.input_needs_wrap = false;
     }
 
@@ -1431,29 +2718,13 @@ impl ansi::Handler for Term {
 
     #[inline]
     fn insert_blank(&mut self, count: Column) {
-        // Ensure inserting within terminal bounds
-
-        let count = min(count, self.size_info.cols() - self.cursor.point.col);
-
-        let source = self.cursor.point.col;
-        let destination = self.cursor.point.col + count;
-        let num_cells = (self.size_info.cols() - destination).0;
-
-        let line = self.cursor.point.line; // borrowck
-        let line = &mut self.grid[line];
-
-        unsafe {
-            let src = line[source..].as_ptr();
-            let dst = line[destination..].as_mut_ptr();
-
-            ptr::copy(src, dst, num_cells);
-        }
-
-        // Cells were just moved out towards the end of the line; fill in
-        // between source and dest with blanks.
-        let template = self.cursor.template;
-        for c in &mut line[source..destination] {
-            c.reset(&template);
+        trace!("insert_blank: {}", count);
+        self.input_needs_wrap = false;
+        // Cells outside of the margins are left alone
+        if self.cursor_in_margins() {
+            let point = self.cursor.point;
+            let right = self.horizontal_margins().end;
+            self.insert_cells(point.line, point.col, right, count);
         }
     }
 
@@ -1476,37 +2747,105 @@ impl ansi::Handler for Term {
     #[inline]
     fn move_forward(&mut self, cols: Column) {
         trace!("move_forward: {}", cols);
-        self.cursor.point.col = min(self.cursor.point.col + cols, self.grid.num_cols() - 1);
+        let right = self.right_edge();
+        self.cursor.point.col = min(self.cursor.point.col + cols, right - 1);
         self.input_needs_wrap = false;
     }
 
     #[inline]
     fn move_backward(&mut self, cols: Column) {
         trace!("move_backward: {}", cols);
-        self.cursor.point.col -= min(self.cursor.point.col, cols);
+        // The left margin stops the cursor, unless it's already left of it
+        let left = self.horizontal_margins().start;
+        let left = if self.cursor.point.col >= left { left } else { Column(0) };
+        self.cursor.point.col = max(self.cursor.point.col - min(self.cursor.point.col, cols), left);
         self.input_needs_wrap = false;
     }
 
     #[inline]
-    fn identify_terminal<W: io::Write>(&mut self, writer: &mut W) {
-        let _ = writer.write_all(b"\x1b[?6c");
+    fn identify_terminal<W: io::Write>(&mut self, writer: &mut W, intermediate: Option<char>) {
+        match intermediate {
+            None => self.reply(writer, b"\x1b[?6c"),
+            Some('>') => {
+                let reply = format!("\x1b[>0;{};1c", version::number());
+                self.reply(writer, reply.as_bytes());
+            },
+            _ => debug!("Unsupported device attributes intermediate"),
+        }
+    }
+
+    #[inline]
+    fn answerback<W: io::Write>(&mut self, writer: &mut W) {
+        // The answerback is sent as configured, without 8-bit controls
+        if !self.answerback.is_empty() {
+            self.send_report(writer, &self.answerback);
+        }
     }
 
     #[inline]
     fn device_status<W: io::Write>(&mut self, writer: &mut W, arg: usize) {
         trace!("device status: {}", arg);
         match arg {
-            5 => {
-                let _ = writer.write_all(b"\x1b[0n");
-            },
+            5 => self.reply(writer, b"\x1b[0n"),
             6 => {
                 let pos = self.cursor.point;
-                let _ = write!(writer, "\x1b[{};{}R", pos.line + 1, pos.col + 1);
+                let reply = format!("\x1b[{};{}R", pos.line + 1, pos.col + 1);
+                self.reply(writer, reply.as_bytes());
             },
             _ => debug!("unknown device status query: {}", arg),
         };
     }
 
+    #[inline]
+    fn report_cell_size<W: io::Write>(&mut self, writer: &mut W) {
+        let size = &self.size_info;
+        let reply = format!("\x1b[6;{};{}t", size.cell_height as usize, size.cell_width as usize);
+        self.reply(writer, reply.as_bytes());
+    }
+
+    /// The window is resized by the main loop, requests are ignored unless
+    /// `window.allow_resize_requests` is set
+    #[inline]
+    fn resize_window(&mut self, size: WindowSize) {
+        trace!("resize_window: {:?}", size);
+        if self.allow_resize_requests {
+            self.next_window_size = Some(size);
+            self.dirty = true;
+        }
+    }
+
+    #[inline]
+    fn request_status_string<W: io::Write>(&mut self, writer: &mut W, selector: &[u8]) {
+        trace!("request status string: {:?}", selector);
+        let value = match selector {
+            b"m" => Some(format!("{}m", sgr_parameters(&self.cursor.template))),
+            b" q" => {
+                // Blinking cursors aren't supported, so the steady styles are reported
+                let style = match self.cursor_style.unwrap_or(self.default_cursor_style) {
+                    CursorStyle::Block | CursorStyle::HollowBlock => 2,
+                    CursorStyle::Underline => 4,
+                    CursorStyle::Beam => 6,
+                };
+                Some(format!("{} q", style))
+            },
+            b"r" => Some(format!("{};{}r", self.scroll_region.start + 1, self.scroll_region.end)),
+            b"s" => {
+                let margins = self.horizontal_margins();
+                Some(format!("{};{}s", margins.start + 1, margins.end))
+            },
+            b"\"q" => {
+                let protected = self.cursor.template.flags.contains(cell::Flags::PROTECTED);
+                Some(format!("{}\"q", if protected { 1 } else { 0 }))
+            },
+            _ => None,
+        };
+
+        match value {
+            Some(value) => self.reply(writer, format!("\x1bP1$r{}\x1b\\", value).as_bytes()),
+            None => self.reply(writer, b"\x1bP0$r\x1b\\"),
+        }
+    }
+
     #[inline]
     fn move_down_and_cr(&mut self, lines: Line) {
         trace!("move_down_and_cr: {}", lines);
@@ -1551,15 +2890,16 @@ impl ansi::Handler for Term {
         trace!("backspace");
         if self.cursor.point.col > Column(0) {
             self.cursor.point.col -= 1;
-            self.input_needs_wrap = false;
         }
+        self.input_needs_wrap = false;
     }
 
     /// Carriage return
     #[inline]
     fn carriage_return(&mut self) {
         trace!("carriage_return");
-        self.cursor.point.col = Column(0);
+        let left = self.horizontal_margins().start;
+        self.cursor.point.col = if self.cursor.point.col >= left { left } else { Column(0) };
         self.input_needs_wrap = false;
     }
 
@@ -1567,9 +2907,13 @@ impl ansi::Handler for Term {
     #[inline]
     fn linefeed(&mut self) {
         trace!("linefeed");
+        self.input_needs_wrap = false;
         let next = self.cursor.point.line + 1;
         if next == self.scroll_region.end {
-            self.scroll_up(Line(1));
+            // Outside of the margins, the cursor stays at the bottom
+            if self.cursor_in_margins() {
+                self.scroll_up(Line(1));
+            }
         } else if next < self.grid.num_lines() {
             self.cursor.point.line += 1;
         }
@@ -1579,8 +2923,20 @@ impl ansi::Handler for Term {
     #[inline]
     fn bell(&mut self) {
         trace!("bell");
-        self.visual_bell.ring();
         self.next_is_urgent = Some(true);
+
+        // A flood of bells would keep the audio of the system busy
+        let now = Instant::now();
+        let interval = Duration::from_millis(BELL_INTERVAL_MS);
+        if self.bell_rung_at.map_or(false, |at| now.duration_since(at) < interval) {
+            return;
+        }
+        self.bell_rung_at = Some(now);
+
+        self.visual_bell.ring();
+        if self.audible_bell {
+            self.next_bell_is_audible = true;
+        }
     }
 
     #[inline]
@@ -1641,59 +2997,75 @@ impl ansi::Handler for Term {
     #[inline]
     fn insert_blank_lines(&mut self, lines: Line) {
         trace!("insert_blank_lines: {}", lines);
-        if self.scroll_region.contains_(self.cursor.point.line) {
+        if self.scroll_region.contains_(self.cursor.point.line) && self.cursor_in_margins() {
             let origin = self.cursor.point.line;
             self.scroll_down_relative(origin, lines);
+
+            // Like xterm, the cursor moves to the left margin
+            self.cursor.point.col = self.horizontal_margins().start;
+            self.input_needs_wrap = false;
         }
     }
 
     #[inline]
     fn delete_lines(&mut self, lines: Line) {
         trace!("delete_lines: {}", lines);
-        if self.scroll_region.contains_(self.cursor.point.line) {
+        if self.scroll_region.contains_(self.cursor.point.line) && self.cursor_in_margins() {
             let origin = self.cursor.point.line;
             self.scroll_up_relative(origin, lines);
+
+            // Like xterm, the cursor moves to the left margin
+            self.cursor.point.col = self.horizontal_margins().start;
+            self.input_needs_wrap = false;
         }
     }
 
     #[inline]
     fn erase_chars(&mut self, count: Column) {
         trace!("erase_chars: {}, {}", count, self.cursor.point.col);
+        self.input_needs_wrap = false;
+        let line = self.cursor.point.line;
         let start = self.cursor.point.col;
-        let end = min(start + count, self.grid.num_cols() - 1);
+        let end = min(start + count, self.grid.num_cols());
 
-        let row = &mut self.grid[self.cursor.point.line];
-        let template = self.cursor.template; // Cleared cells have current background color set
-        for c in &mut row[start..end] {
-            c.reset(&template);
+        {
+            let row = &mut self.grid[line];
+            let template = self.cursor.erase_template();
+            for c in &mut row[start..end] {
+                c.reset(&template);
+            }
         }
+
+        self.clear_split_wide_chars(line);
     }
 
     #[inline]
     fn delete_chars(&mut self, count: Column) {
-        // Ensure deleting within terminal bounds
-        let count = min(count, self.size_info.cols());
-
-        let start = self.cursor.point.col;
-        let end = min(start + count, self.grid.num_cols() - 1);
-        let n = (self.size_info.cols() - end).0;
-
-        let line = self.cursor.point.line; // borrowck
-        let line = &mut self.grid[line];
-
-        unsafe {
-            let src = line[end..].as_ptr();
-            let dst = line[start..].as_mut_ptr();
+        trace!("delete_chars: {}", count);
+        self.input_needs_wrap = false;
+        // Cells outside of the margins are left alone
+        if self.cursor_in_margins() {
+            let point = self.cursor.point;
+            let right = self.horizontal_margins().end;
+            self.delete_cells(point.line, point.col, right, count);
+        }
+    }
 
-            ptr::copy(src, dst, n);
+    #[inline]
+    fn scroll_left(&mut self, count: Column) {
+        trace!("scroll_left: {}", count);
+        let margins = self.horizontal_margins();
+        for line in IndexRange(self.scroll_region.clone()) {
+            self.delete_cells(line, margins.start, margins.end, count);
         }
+    }
 
-        // Clear last `count` cells in line. If deleting 1 char, need to delete
-        // 1 cell.
-        let template = self.cursor.template;
-        let end = self.size_info.cols() - count;
-        for c in &mut line[end..] {
-            c.reset(&template);
+    #[inline]
+    fn scroll_right(&mut self, count: Column) {
+        trace!("scroll_right: {}", count);
+        let margins = self.horizontal_margins();
+        for line in IndexRange(self.scroll_region.clone()) {
+            self.insert_cells(line, margins.start, margins.end, count);
         }
     }
 
@@ -1711,11 +3083,13 @@ impl ansi::Handler for Term {
             }
             self.cursor.point.col = col;
         }
+        self.input_needs_wrap = false;
     }
 
     #[inline]
     fn move_forward_tabs(&mut self, count: i64) {
-        trace!("[unimplemented] move_forward_tabs: {}", count);
+        trace!("move_forward_tabs: {}", count);
+        self.put_tab(count);
     }
 
     #[inline]
@@ -1728,6 +3102,7 @@ impl ansi::Handler for Term {
         };
 
         *cursor = self.cursor;
+        cursor.wrap_pending = self.input_needs_wrap;
     }
 
     #[inline]
@@ -1742,36 +3117,23 @@ impl ansi::Handler for Term {
         self.cursor = *source;
         self.cursor.point.line = min(self.cursor.point.line, self.grid.num_lines() - 1);
         self.cursor.point.col = min(self.cursor.point.col, self.grid.num_cols() - 1);
+
+        // Like xterm, the wrap continues if the cursor is back in the last column,
+        // which it isn't anymore after the terminal was widened
+        let last_column = self.right_edge() - 1;
+        self.input_needs_wrap = self.cursor.wrap_pending && self.cursor.point.col == last_column;
     }
 
     #[inline]
     fn clear_line(&mut self, mode: ansi::LineClearMode) {
         trace!("clear_line: {:?}", mode);
-        let mut template = self.cursor.template;
-        template.flags ^= template.flags;
-
-        let col =  self.cursor.point.col;
+        self.erase_line(mode, false);
+    }
 
-        match mode {
-            ansi::LineClearMode::Right => {
-                let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[col..] {
-                    cell.reset(&template);
-                }
-            },
-            ansi::LineClearMode::Left => {
-                let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[..=col] {
-                    cell.reset(&template);
-                }
-            },
-            ansi::LineClearMode::All => {
-                let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[..] {
-                    cell.reset(&template);
-                }
-            },
-        }
+    #[inline]
+    fn selective_clear_line(&mut self, mode: ansi::LineClearMode) {
+        trace!("selective_clear_line: {:?}", mode);
+        self.erase_line(mode, true);
     }
 
     /// Set the indexed color value
@@ -1780,6 +3142,7 @@ impl ansi::Handler for Term {
         trace!("set_color[{}] = {:?}", index, color);
         self.colors[index] = color;
         self.color_modified[index] = true;
+        self.grid.damage_all();
     }
 
     /// Reset the indexed color to original value
@@ -1788,12 +3151,18 @@ impl ansi::Handler for Term {
         trace!("reset_color[{}]", index);
         self.colors[index] = self.original_colors[index];
         self.color_modified[index] = false;
+        self.grid.damage_all();
     }
 
     /// Set the clipboard
     #[inline]
     fn set_clipboard(&mut self, string: &str)
     {
+        if self.osc52 == Osc52::Disabled {
+            debug!("Ignoring OSC 52 clipboard request");
+            return;
+        }
+
         Clipboard::new()
             .and_then(|mut clipboard| clipboard.store_primary(string))
             .unwrap_or_else(|err| {
@@ -1801,43 +3170,46 @@ impl ansi::Handler for Term {
             });
     }
 
+    #[inline]
+    fn report_clipboard<W: io::Write>(&mut self, writer: &mut W, selection: &[u8]) {
+        if self.osc52 != Osc52::Full {
+            debug!("Ignoring OSC 52 clipboard query");
+            return;
+        }
+
+        let contents = match Clipboard::new().and_then(|clipboard| clipboard.load_primary()) {
+            Ok(contents) => contents,
+            Err(err) => {
+                warn!("Error loading the clipboard. {}", err);
+                return;
+            },
+        };
+
+        // Only the selections xterm knows are echoed, nothing else of the query
+        let selection: String = selection.iter()
+            .filter(|byte| b"cpqs01234567".contains(byte))
+            .map(|&byte| byte as char)
+            .collect();
+        let reply = format!("\x1b]52;{};{}\x1b\\", selection, ::base64::encode(&contents));
+        self.reply(writer, reply.as_bytes());
+    }
+
     #[inline]
     fn clear_screen(&mut self, mode: ansi::ClearMode) {
         trace!("clear_screen: {:?}", mode);
-        let mut template = self.cursor.template;
-        template.flags ^= template.flags;
+        self.erase_screen(mode, false);
+    }
 
-        match mode {
-            ansi::ClearMode::Below => {
-                for cell in &mut self.grid[self.cursor.point.line][self.cursor.point.col..] {
-                    cell.reset(&template);
-                }
-                if self.cursor.point.line < self.grid.num_lines() - 1 {
-                    self.grid.region_mut((self.cursor.point.line + 1)..)
-                        .each(|cell| cell.reset(&template));
-                }
-            },
-            ansi::ClearMode::All => {
-                self.grid.region_mut(..).each(|c| c.reset(&template));
-            },
-            ansi::ClearMode::Above => {
-                // If clearing more than one line
-                if self.cursor.point.line > Line(1) {
-                    // Fully clear all lines before the current line
-                    self.grid.region_mut(..self.cursor.point.line)
-                        .each(|cell| cell.reset(&template));
-                }
-                // Clear up to the current column in the current line
-                let end = min(self.cursor.point.col + 1, self.grid.num_cols());
-                for cell in &mut self.grid[self.cursor.point.line][..end] {
-                    cell.reset(&template);
-                }
-            },
-            // If scrollback is implemented, this should clear it
-            ansi::ClearMode::Saved => {
-                self.grid.clear_history();
-            }
-        }
+    #[inline]
+    fn selective_clear_screen(&mut self, mode: ansi::ClearMode) {
+        trace!("selective_clear_screen: {:?}", mode);
+        self.erase_screen(mode, true);
+    }
+
+    #[inline]
+    fn set_character_protection(&mut self, protected: bool) {
+        trace!("set_character_protection: {}", protected);
+        self.cursor.template.flags.set(cell::Flags::PROTECTED, protected);
     }
 
     #[inline]
@@ -1859,33 +3231,74 @@ impl ansi::Handler for Term {
         }
     }
 
+    /// Reset the modes, attributes and margins, but keep the screen contents
+    #[inline]
+    fn soft_reset(&mut self) {
+        trace!("soft_reset");
+
+        // Like with a full reset, the selection is gone
+        self.grid.selection = None;
+
+        self.input_needs_wrap = false;
+        self.mode.remove(TermMode::INSERT | TermMode::ORIGIN | TermMode::APP_CURSOR | TermMode::APP_KEYPAD);
+        self.mode.insert(TermMode::SHOW_CURSOR | TermMode::LINE_WRAP);
+        self.scroll_region = Line(0)..self.grid.num_lines();
+        self.left_right_margins = Column(0)..self.grid.num_cols();
+        self.cursor.template = Cell::default();
+        self.cursor.charsets = Default::default();
+        self.cursor.active_charset = Default::default();
+        self.cursor_save = Default::default();
+        self.cursor_style = None;
+    }
+
     // Reset all important fields in the term struct
     #[inline]
     fn reset_state(&mut self) {
+        if self.alt {
+            self.swap_alt();
+        }
+
+        self.grid.selection = None;
+        self.alt_grid.selection = None;
         self.input_needs_wrap = false;
         self.next_title = None;
         self.next_mouse_cursor = None;
+        self.url_highlight = None;
         self.alt = false;
         self.cursor = Default::default();
-        self.active_charset = Default::default();
         self.mode = Default::default();
+        self.scroll_region = Line(0)..self.grid.num_lines();
+        self.left_right_margins = Column(0)..self.grid.num_cols();
         self.font_size = self.original_font_size;
         self.next_is_urgent = None;
+        self.next_bell_is_audible = false;
+        self.next_window_size = None;
         self.cursor_save = Default::default();
         self.cursor_save_alt = Default::default();
         self.colors = self.original_colors;
         self.color_modified = [false; color::COUNT];
         self.cursor_style = None;
+        self.eight_bit_controls = self.default_eight_bit_controls;
         self.grid.clear_history();
         self.grid.region_mut(..).each(|c| c.reset(&Cell::default()));
     }
 
+    #[inline]
+    fn set_eight_bit_controls(&mut self, enabled: bool) {
+        trace!("set_eight_bit_controls: {}", enabled);
+        self.eight_bit_controls = enabled;
+    }
+
     #[inline]
     fn reverse_index(&mut self) {
         trace!("reverse_index");
+        self.input_needs_wrap = false;
         // if cursor is at the top
         if self.cursor.point.line == self.scroll_region.start {
-            self.scroll_down(Line(1));
+            // Outside of the margins, the cursor stays at the top
+            if self.cursor_in_margins() {
+                self.scroll_down(Line(1));
+            }
         } else {
             self.cursor.point.line -= min(self.cursor.point.line, Line(1));
         }
@@ -1901,7 +3314,8 @@ impl ansi::Handler for Term {
             Attr::Reset => {
                 self.cursor.template.fg = Color::Named(NamedColor::Foreground);
                 self.cursor.template.bg = Color::Named(NamedColor::Background);
-                self.cursor.template.flags = cell::Flags::empty();
+                // The protection isn't a rendition, only DECSCA changes it
+                self.cursor.template.flags &= cell::Flags::PROTECTED;
             },
             Attr::Reverse => self.cursor.template.flags.insert(cell::Flags::INVERSE),
             Attr::CancelReverse => self.cursor.template.flags.remove(cell::Flags::INVERSE),
@@ -1926,6 +3340,7 @@ impl ansi::Handler for Term {
         trace!("set_mode: {:?}", mode);
         match mode {
             ansi::Mode::SwapScreenAndSetRestoreCursor => {
+                // The cursor is saved along with the pen and the character sets
                 self.mode.insert(mode::TermMode::ALT_SCREEN);
                 self.save_cursor_position();
                 if !self.alt {
@@ -1949,10 +3364,13 @@ impl ansi::Handler for Term {
             },
             ansi::Mode::ReportFocusInOut => self.mode.insert(mode::TermMode::FOCUS_IN_OUT),
             ansi::Mode::BracketedPaste => self.mode.insert(mode::TermMode::BRACKETED_PASTE),
-            ansi::Mode::SgrMouse => self.mode.insert(mode::TermMode::SGR_MOUSE),
+            ansi::Mode::Utf8Mouse => self.set_mouse_encoding(mode::TermMode::UTF8_MOUSE),
+            ansi::Mode::SgrMouse => self.set_mouse_encoding(mode::TermMode::SGR_MOUSE),
+            ansi::Mode::UrxvtMouse => self.set_mouse_encoding(mode::TermMode::URXVT_MOUSE),
             ansi::Mode::LineWrap => self.mode.insert(mode::TermMode::LINE_WRAP),
             ansi::Mode::LineFeedNewLine => self.mode.insert(mode::TermMode::LINE_FEED_NEW_LINE),
             ansi::Mode::Origin => self.mode.insert(mode::TermMode::ORIGIN),
+            ansi::Mode::LeftRightMargin => self.mode.insert(mode::TermMode::LEFT_RIGHT_MARGIN),
             ansi::Mode::DECCOLM => self.deccolm(),
             ansi::Mode::Insert => self.mode.insert(mode::TermMode::INSERT), // heh
             _ => {
@@ -1967,7 +3385,6 @@ impl ansi::Handler for Term {
         match mode {
             ansi::Mode::SwapScreenAndSetRestoreCursor => {
                 self.mode.remove(mode::TermMode::ALT_SCREEN);
-                self.restore_cursor_position();
                 if self.alt {
                     self.swap_alt();
                 }
@@ -1977,22 +3394,28 @@ impl ansi::Handler for Term {
             ansi::Mode::CursorKeys => self.mode.remove(mode::TermMode::APP_CURSOR),
             ansi::Mode::ReportMouseClicks => {
                 self.mode.remove(mode::TermMode::MOUSE_REPORT_CLICK);
-                self.set_mouse_cursor(MouseCursor::Text);
+                self.update_mouse_cursor();
             },
             ansi::Mode::ReportCellMouseMotion => {
                 self.mode.remove(mode::TermMode::MOUSE_DRAG);
-                self.set_mouse_cursor(MouseCursor::Text);
+                self.update_mouse_cursor();
             },
             ansi::Mode::ReportAllMouseMotion => {
                 self.mode.remove(mode::TermMode::MOUSE_MOTION);
-                self.set_mouse_cursor(MouseCursor::Text);
+                self.update_mouse_cursor();
             },
             ansi::Mode::ReportFocusInOut => self.mode.remove(mode::TermMode::FOCUS_IN_OUT),
             ansi::Mode::BracketedPaste => self.mode.remove(mode::TermMode::BRACKETED_PASTE),
+            ansi::Mode::Utf8Mouse => self.mode.remove(mode::TermMode::UTF8_MOUSE),
             ansi::Mode::SgrMouse => self.mode.remove(mode::TermMode::SGR_MOUSE),
+            ansi::Mode::UrxvtMouse => self.mode.remove(mode::TermMode::URXVT_MOUSE),
             ansi::Mode::LineWrap => self.mode.remove(mode::TermMode::LINE_WRAP),
             ansi::Mode::LineFeedNewLine => self.mode.remove(mode::TermMode::LINE_FEED_NEW_LINE),
             ansi::Mode::Origin => self.mode.remove(mode::TermMode::ORIGIN),
+            ansi::Mode::LeftRightMargin => {
+                self.mode.remove(mode::TermMode::LEFT_RIGHT_MARGIN);
+                self.left_right_margins = Column(0)..self.grid.num_cols();
+            },
             ansi::Mode::DECCOLM => self.deccolm(),
             ansi::Mode::Insert => self.mode.remove(mode::TermMode::INSERT),
             _ => {
@@ -2001,6 +3424,61 @@ impl ansi::Handler for Term {
         }
     }
 
+    #[inline]
+    fn set_horizontal_margins(&mut self, left: Column, right: Option<Column>) {
+        trace!("set_horizontal_margins: {}..{:?}", left, right);
+        if !self.mode.contains(mode::TermMode::LEFT_RIGHT_MARGIN) {
+            self.save_cursor_position();
+            return;
+        }
+
+        // The margins need to span at least two columns
+        let cols = self.grid.num_cols();
+        let right = min(right.unwrap_or(cols), cols);
+        if left + 1 < right {
+            self.left_right_margins = left..right;
+            self.goto(Line(0), Column(0));
+        }
+    }
+
+    #[inline]
+    fn report_mode<W: io::Write>(&mut self, writer: &mut W, private: bool, mode: i64) {
+        trace!("report_mode: private={}, mode={}", private, mode);
+        let known = ansi::Mode::from_primitive(private, mode);
+        let flag = match known {
+            Some(ansi::Mode::CursorKeys) => Some(mode::TermMode::APP_CURSOR),
+            Some(ansi::Mode::Insert) => Some(mode::TermMode::INSERT),
+            Some(ansi::Mode::Origin) => Some(mode::TermMode::ORIGIN),
+            Some(ansi::Mode::LineWrap) => Some(mode::TermMode::LINE_WRAP),
+            Some(ansi::Mode::LineFeedNewLine) => Some(mode::TermMode::LINE_FEED_NEW_LINE),
+            Some(ansi::Mode::ShowCursor) => Some(mode::TermMode::SHOW_CURSOR),
+            Some(ansi::Mode::LeftRightMargin) => Some(mode::TermMode::LEFT_RIGHT_MARGIN),
+            Some(ansi::Mode::ReportMouseClicks) => Some(mode::TermMode::MOUSE_REPORT_CLICK),
+            Some(ansi::Mode::ReportCellMouseMotion) => Some(mode::TermMode::MOUSE_DRAG),
+            Some(ansi::Mode::ReportAllMouseMotion) => Some(mode::TermMode::MOUSE_MOTION),
+            Some(ansi::Mode::ReportFocusInOut) => Some(mode::TermMode::FOCUS_IN_OUT),
+            Some(ansi::Mode::Utf8Mouse) => Some(mode::TermMode::UTF8_MOUSE),
+            Some(ansi::Mode::SgrMouse) => Some(mode::TermMode::SGR_MOUSE),
+            Some(ansi::Mode::UrxvtMouse) => Some(mode::TermMode::URXVT_MOUSE),
+            Some(ansi::Mode::SwapScreenAndSetRestoreCursor) => Some(mode::TermMode::ALT_SCREEN),
+            Some(ansi::Mode::BracketedPaste) => Some(mode::TermMode::BRACKETED_PASTE),
+            // 132 columns and blinking cursors aren't supported
+            Some(ansi::Mode::DECCOLM) | Some(ansi::Mode::BlinkingCursor) | None => None,
+        };
+
+        // Set, reset, or permanently reset for known modes, zero for unknown ones
+        let state = match flag {
+            Some(flag) if self.mode.contains(flag) => 1,
+            Some(_) => 2,
+            None if known.is_some() => 4,
+            None => 0,
+        };
+
+        let prefix = if private { "?" } else { "" };
+        let reply = format!("\x1b[{}{};{}$y", prefix, mode, state);
+        self.reply(writer, reply.as_bytes());
+    }
+
     #[inline]
     fn set_scrolling_region(&mut self, region: Range<Line>) {
         trace!("set scroll region: {:?}", region);
@@ -2030,7 +3508,7 @@ impl ansi::Handler for Term {
     #[inline]
     fn set_active_charset(&mut self, index: CharsetIndex) {
         trace!("Activate {:?} character set", index);
-        self.active_charset = index;
+        self.cursor.active_charset = index;
     }
 
     #[inline]
@@ -2040,21 +3518,88 @@ impl ansi::Handler for Term {
     }
 }
 
+/// Reset a cell to the erase template, unless a selective erase has to keep it
+#[inline]
+fn erase(cell: &mut Cell, template: &Cell, selective: bool) {
+    if !selective || !cell.flags.contains(cell::Flags::PROTECTED) {
+        cell.reset(template);
+    }
+}
+
+/// Replace the 7-bit controls (ESC Fe) in a reply with 8-bit C1 controls
+fn eight_bit_controls(reply: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(reply.len());
+    let mut bytes = reply.iter().cloned().peekable();
+    while let Some(byte) = bytes.next() {
+        match bytes.peek() {
+            Some(&next) if byte == 0x1b && (0x40..=0x5f).contains(&next) => {
+                converted.push(next + 0x40);
+                bytes.next();
+            },
+            _ => converted.push(byte),
+        }
+    }
+    converted
+}
+
+/// SGR parameters which recreate the attributes of a cell
+fn sgr_parameters(template: &Cell) -> String {
+    let mut parameters = String::from("0");
+
+    let flags = [
+        (cell::Flags::BOLD, 1),
+        (cell::Flags::DIM, 2),
+        (cell::Flags::ITALIC, 3),
+        (cell::Flags::UNDERLINE, 4),
+        (cell::Flags::INVERSE, 7),
+        (cell::Flags::HIDDEN, 8),
+    ];
+    for &(flag, parameter) in &flags {
+        if template.flags.contains(flag) {
+            parameters.push_str(&format!(";{}", parameter));
+        }
+    }
+
+    let colors = [(template.fg, 30, 90, 38), (template.bg, 40, 100, 48)];
+    for &(color, normal, bright, extended) in &colors {
+        match color {
+            Color::Named(color) if (color as usize) < 8 => {
+                parameters.push_str(&format!(";{}", normal + color as usize));
+            },
+            Color::Named(color) if (color as usize) < 16 => {
+                parameters.push_str(&format!(";{}", bright + color as usize - 8));
+            },
+            Color::Indexed(index) => {
+                parameters.push_str(&format!(";{};5;{}", extended, index));
+            },
+            Color::Spec(rgb) => {
+                parameters.push_str(&format!(";{};2;{};{};{}", extended, rgb.r, rgb.g, rgb.b));
+            },
+            // The default colors
+            Color::Named(_) => (),
+        }
+    }
+
+    parameters
+}
+
 #[cfg(test)]
 mod tests {
     extern crate serde_json;
 
-    use super::{Cell, Term, SizeInfo};
+    use super::{Cell, Term, TermMode, Preedit, SizeInfo, RenderCache, StateDump};
     use term::{cell, Search};
 
     use grid::{Grid, Scroll};
     use index::{Point, Line, Column, Side};
-    use ansi::{self, Handler, CharsetIndex, StandardCharset};
+    use ansi::{self, Handler, CharsetIndex, CursorStyle, StandardCharset, WindowSize};
     use selection::Selection;
-    use std::mem;
-    use input::FONT_SIZE_STEP;
+    use std::{io, mem};
+    use super::FONT_SIZE_STEP;
     use font::Size;
     use config::Config;
+    use version;
+    use {MouseCursor, Rgb};
 
     #[test]
     fn semantic_selection_works() {
@@ -2152,24 +3697,8 @@ mod tests {
         assert_eq!(term.selection_to_string(), Some("aaa\n\naaa\n".into()));
     }
 
-    /// Check that the grid can be serialized back and forth losslessly
-    ///
-    /// This test is in the term module as opposed to the grid since we want to
-    /// test this property with a T=Cell.
-    #[test]
-    fn grid_serde() {
-        let template = Cell::default();
-
-        let grid: Grid<Cell> = Grid::new(Line(24), Column(80), 0, template);
-        let serialized = serde_json::to_string(&grid).expect("ser");
-        let deserialized = serde_json::from_str::<Grid<Cell>>(&serialized)
-                                      .expect("de");
-
-        assert_eq!(deserialized, grid);
-    }
-
     #[test]
-    fn input_line_drawing_character() {
+    fn selection_to_html_keeps_colors() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2179,43 +3708,47 @@ mod tests {
             padding_y: 0.0,
         };
         let mut term = Term::new(&Default::default(), size);
-        let cursor = Point::new(Line(0), Column(0));
-        term.configure_charset(CharsetIndex::G0,
-                               StandardCharset::SpecialCharacterAndLineDrawing);
-        term.input('a');
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(5), 0, Cell::default());
+        let red = Rgb { r: 0xff, g: 0, b: 0 };
+        for (i, c) in "a<b".chars().enumerate() {
+            grid[Line(0)][Column(i)].c = c;
+        }
+        grid[Line(0)][Column(1)].fg = ansi::Color::Spec(red);
+        grid[Line(0)][Column(2)].fg = ansi::Color::Spec(red);
+        grid[Line(0)][Column(3)].c = '好';
+        grid[Line(0)][Column(3)].flags.insert(cell::Flags::WIDE_CHAR);
+        grid[Line(0)][Column(4)].flags.insert(cell::Flags::WIDE_CHAR_SPACER);
 
-        assert_eq!(term.grid()[&cursor].c, '▒');
+        mem::swap(&mut term.grid, &mut grid);
+
+        *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(0) }));
+        let html = term.selection_to_html().unwrap();
+        assert!(html.starts_with("<pre style="));
+        assert!(html.ends_with(">a<span style=\"color:#ff0000;\">&lt;b</span>好\n</pre>"));
+
+        // Plain text is unaffected
+        assert_eq!(term.selection_to_string(), Some("a<b好\n".into()));
     }
 
-    fn change_font_size_works(font_size: f32) {
+    #[test]
+    fn contents_join_wrapped_lines() {
         let size = SizeInfo {
-            width: 21.0,
-            height: 51.0,
-            cell_width: 3.0,
-            cell_height: 3.0,
+            width: 5.0,
+            height: 3.0,
+            cell_width: 1.0,
+            cell_height: 1.0,
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size);
-        term.change_font_size(font_size);
-
-        let expected_font_size: Size = config.font().size() + Size::new(font_size);
-        assert_eq!(term.font_size, expected_font_size);
-    }
-
-    #[test]
-    fn increase_font_size_works() {
-        change_font_size_works(10.0);
-    }
+        let mut term = Term::new(&Default::default(), size);
+        advance(&mut term, b"abcdefg \r\nx  \r\n\r\nlast");
 
-    #[test]
-    fn decrease_font_size_works() {
-        change_font_size_works(-10.0);
+        assert_eq!(term.contents_to_string(false), "x\n\nlast\n");
+        assert_eq!(term.contents_to_string(true), "abcdefg\nx\n\nlast\n");
     }
 
     #[test]
-    fn prevent_font_below_threshold_works() {
+    fn selection_follows_scrolled_output() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2224,17 +3757,29 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size);
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(3), 10, Cell::default());
+        for c in 0..3 {
+            grid[Line(0)][Column(c)].c = 'a';
+        }
 
-        term.change_font_size(-100.0);
+        mem::swap(&mut term.grid, &mut grid);
 
-        let expected_font_size: Size = Size::new(FONT_SIZE_STEP);
-        assert_eq!(term.font_size, expected_font_size);
+        *term.selection_mut() = Some(Selection::lines(Point { line: 2, col: Column(0) }));
+        assert_eq!(term.selection_to_string(), Some("aaa\n".into()));
+
+        // The selection moves into the history together with the text
+        let template = Cell::default();
+        term.grid.scroll_up(&(Line(0)..Line(3)), Line(5), &template);
+        assert_eq!(term.selection_to_string(), Some("aaa\n".into()));
+
+        // Until the text is dropped from the history
+        term.grid.scroll_up(&(Line(0)..Line(3)), Line(6), &template);
+        assert_eq!(term.selection(), &None);
     }
 
     #[test]
-    fn reset_font_size_works() {
+    fn clearing_selected_lines_clears_selection() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2243,18 +3788,30 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size);
+        let mut term = Term::new(&Default::default(), size);
 
-        term.change_font_size(10.0);
-        term.reset_font_size();
+        // Erasing lines below the selection keeps it
+        *term.selection_mut() = Some(Selection::lines(Point { line: 10, col: Column(0) }));
+        term.goto(Line(10), Column(0));
+        term.clear_screen(ansi::ClearMode::Below);
+        assert!(term.selection().is_some());
 
-        let expected_font_size: Size = config.font().size();
-        assert_eq!(term.font_size, expected_font_size);
+        term.goto(Line(5), Column(0));
+        term.clear_line(ansi::LineClearMode::All);
+        assert!(term.selection().is_some());
+
+        term.clear_screen(ansi::ClearMode::Below);
+        assert_eq!(term.selection(), &None);
+
+        // Switching to the alternate screen hides the selected text
+        *term.selection_mut() = Some(Selection::lines(Point { line: 10, col: Column(0) }));
+        term.swap_alt();
+        term.swap_alt();
+        assert_eq!(term.selection(), &None);
     }
 
     #[test]
-    fn clear_saved_lines() {
+    fn clearing_part_of_a_line_clears_selection_in_it() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2263,24 +3820,113 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size);
+        let mut term = Term::new(&Default::default(), size);
 
-        // Add one line of scrollback
-        term.grid.scroll_up(&(Line(0)..Line(1)), Line(1), &Cell::default());
+        // Select the first three cells of the line 2, which is buffer line 14
+        let select = |term: &mut Term| {
+            let mut selection = Selection::simple(Point { line: 14, col: Column(0) }, Side::Left);
+            selection.update(Point { line: 14, col: Column(2) }, Side::Right);
+            *term.selection_mut() = Some(selection);
+        };
 
-        // Clear the history
+        select(&mut term);
+        term.goto(Line(2), Column(4));
+        term.clear_line(ansi::LineClearMode::Right);
+        assert!(term.selection().is_some());
+
+        term.clear_line(ansi::LineClearMode::Left);
+        assert_eq!(term.selection(), &None);
+
+        // Erasing from the end of the selection clears it
+        select(&mut term);
+        term.goto(Line(2), Column(3));
+        term.clear_screen(ansi::ClearMode::Below);
+        assert!(term.selection().is_some());
+
+        term.goto(Line(2), Column(2));
+        term.clear_screen(ansi::ClearMode::Below);
+        assert_eq!(term.selection(), &None);
+
+        select(&mut term);
+        term.goto(Line(1), Column(6));
+        term.clear_screen(ansi::ClearMode::Above);
+        assert!(term.selection().is_some());
+
+        term.goto(Line(2), Column(0));
+        term.clear_screen(ansi::ClearMode::Above);
+        assert_eq!(term.selection(), &None);
+    }
+
+    #[test]
+    fn clearing_history_clears_selection_in_it() {
+        let mut term = term_with_history();
+
+        *term.selection_mut() = Some(Selection::lines(Point { line: 10, col: Column(0) }));
         term.clear_screen(ansi::ClearMode::Saved);
+        assert!(term.selection().is_some());
 
-        // Make sure that scrolling does not change the grid
-        let mut scrolled_grid = term.grid.clone();
-        scrolled_grid.scroll_display(Scroll::Top);
-        assert_eq!(term.grid, scrolled_grid);
+        *term.selection_mut() = Some(Selection::lines(Point { line: 18, col: Column(0) }));
+        term.clear_screen(ansi::ClearMode::Saved);
+        assert_eq!(term.selection(), &None);
     }
 
-    // `((ftp://a.de))` -> `Some("ftp://a.de")`
     #[test]
-    fn url_trim_unmatched_parens() {
+    fn resets_clear_selection() {
+        let mut term = term_with_history();
+
+        *term.selection_mut() = Some(Selection::lines(Point { line: 10, col: Column(0) }));
+        term.soft_reset();
+        assert_eq!(term.selection(), &None);
+
+        // A full reset also leaves the alternate screen
+        term.swap_alt();
+        *term.selection_mut() = Some(Selection::lines(Point { line: 10, col: Column(0) }));
+        term.reset_state();
+        assert_eq!(term.selection(), &None);
+        assert!(!term.alt);
+    }
+
+    #[test]
+    fn resizing_clears_selection() {
+        let mut term = term_with_history();
+        let mut size = *term.size_info();
+
+        // Nothing moves when the size stays the same
+        *term.selection_mut() = Some(Selection::lines(Point { line: 10, col: Column(0) }));
+        term.resize(&size);
+        assert!(term.selection().is_some());
+
+        size.width += size.cell_width;
+        term.resize(&size);
+        assert_eq!(term.selection(), &None);
+    }
+
+    #[test]
+    fn bells_are_rate_limited() {
+        let mut term = term_with_history();
+        term.audible_bell = true;
+
+        term.bell();
+        assert!(mem::replace(&mut term.next_bell_is_audible, false));
+        assert_eq!(term.next_is_urgent.take(), Some(true));
+
+        // The urgency hint is requested again, the sound isn't played
+        term.bell();
+        assert!(!term.next_bell_is_audible);
+        assert_eq!(term.next_is_urgent, Some(true));
+    }
+
+    /// Columns and backgrounds of the first line, in the order they're drawn
+    fn first_line_backgrounds(term: &Term, config: &Config) -> Vec<(Column, Rgb)> {
+        term.renderable_cells(config, true)
+            .filter(|cell| cell.line == Line(0))
+            .map(|cell| (cell.column, cell.bg))
+            .collect()
+    }
+
+    #[test]
+    fn blank_lines_are_only_drawn_when_highlighted() {
+        let config: Config = Default::default();
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2289,33 +3935,156 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let mut term = Term::new(&Default::default(), size);
-        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
-        grid[Line(0)][Column(0)].c = '(';
-        grid[Line(0)][Column(1)].c = '(';
-        grid[Line(0)][Column(2)].c = 'f';
-        grid[Line(0)][Column(3)].c = 't';
-        grid[Line(0)][Column(4)].c = 'p';
-        grid[Line(0)][Column(5)].c = ':';
-        grid[Line(0)][Column(6)].c = '/';
-        grid[Line(0)][Column(7)].c = '/';
-        grid[Line(0)][Column(8)].c = 'a';
-        grid[Line(0)][Column(9)].c = '.';
-        grid[Line(0)][Column(10)].c = 'd';
-        grid[Line(0)][Column(11)].c = 'e';
-        grid[Line(0)][Column(12)].c = ')';
-        grid[Line(0)][Column(13)].c = ')';
-        mem::swap(&mut term.grid, &mut grid);
+        let mut term = Term::new(&config, size);
+        term.goto(Line(2), Column(0));
+        term.input('a');
+        term.goto(Line(2), Column(0));
+        term.input(' ');
+        term.goto(Line(0), Column(0));
 
-        // Search for URL in grid
-        let url = term.url_search(Point::new(0, Column(4)));
+        let mut selection = Selection::simple(Point { line: 11, col: Column(0) }, Side::Left);
+        selection.update(Point { line: 11, col: Column(6) }, Side::Right);
+        *term.selection_mut() = Some(selection);
 
-        assert_eq!(url, Some("ftp://a.de".into()));
+        // The cursor line, the written line and the selected line
+        let mut lines: Vec<_> = term.renderable_cells(&config, true)
+            .map(|cell| cell.line)
+            .collect();
+        lines.dedup();
+        assert_eq!(lines, vec![Line(0), Line(5)]);
+        assert!(!term.grid()[Line(2)].is_blank());
+        assert!(term.grid()[Line(3)].is_blank());
     }
 
-    // `ftp://a.de/()` -> `Some("ftp://a.de/()")`
     #[test]
-    fn url_allow_matching_parens() {
+    fn wide_chars_are_selected_as_a_unit() {
+        let config: Config = Default::default();
+        let mut term = term_with_history();
+        term.goto(Line(0), Column(0));
+        advance(&mut term, "日本x".as_bytes());
+        term.goto(Line(5), Column(0));
+
+        // From the spacer of the first to the glyph of the second char
+        let mut selection = Selection::simple(Point { line: 16, col: Column(1) }, Side::Left);
+        selection.update(Point { line: 16, col: Column(2) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+
+        let background = term.colors[ansi::NamedColor::Background];
+        let selected: Vec<_> = first_line_backgrounds(&term, &config)
+            .into_iter()
+            .map(|(column, bg)| (column, bg != background))
+            .collect();
+        assert_eq!(selected, vec![
+            (Column(0), true),
+            (Column(1), true),
+            (Column(2), true),
+            (Column(3), true),
+            (Column(4), false),
+        ]);
+    }
+
+    #[test]
+    fn cursor_is_drawn_over_the_selection() {
+        let config: Config = Default::default();
+        let mut term = term_with_history();
+        term.goto(Line(0), Column(0));
+        advance(&mut term, "a日b".as_bytes());
+        term.goto(Line(0), Column(1));
+        *term.selection_mut() = Some(Selection::lines(Point { line: 16, col: Column(0) }));
+
+        let cells = first_line_backgrounds(&term, &config);
+        let selection_bg = cells[0].1;
+
+        // The block cursor covers both halves of the wide char, its spacer
+        // isn't drawn on top of it
+        let cursor: Vec<_> = cells.iter().filter(|&&(column, _)| column == Column(1)).collect();
+        let spacer: Vec<_> = cells.iter().filter(|&&(column, _)| column == Column(2)).collect();
+        assert_eq!(cursor.len(), 2);
+        assert_eq!(cursor[0].1, selection_bg);
+        assert_ne!(cursor[1].1, selection_bg);
+        assert_eq!(spacer, vec![&(Column(2), cursor[1].1)]);
+        assert!(cells.contains(&(Column(3), selection_bg)));
+
+        // Other cursors keep the selection around them
+        term.cursor_style = Some(CursorStyle::Beam);
+        let cells = first_line_backgrounds(&term, &config);
+        assert!(cells.iter().all(|&(_, bg)| bg == selection_bg));
+    }
+
+    #[test]
+    fn cursor_shapes_are_drawn_over_the_text() {
+        let config: Config = Default::default();
+        let mut term = term_with_history();
+        term.goto(Line(0), Column(0));
+        advance(&mut term, "a日b".as_bytes());
+        term.goto(Line(0), Column(1));
+        term.cursor_style = Some(CursorStyle::Underline);
+
+        // The cell below the cursor is drawn once, with its own text
+        let cells: Vec<_> = term.renderable_cells(&config, true)
+            .filter(|cell| cell.line == Line(0) && cell.column == Column(1))
+            .collect();
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0].c, '日');
+
+        let cursor = term.renderable_cells(&config, true).cursor().unwrap();
+        assert_eq!((cursor.line, cursor.column), (Line(0), Column(1)));
+        assert_eq!(cursor.width, 2);
+        assert_eq!(cursor.shape, CursorStyle::Underline);
+
+        // The cursor moves with the scrolled viewport, until it's below it
+        term.scroll_display(Scroll::Lines(4));
+        let cursor = term.renderable_cells(&config, true).cursor().unwrap();
+        assert_eq!(cursor.line, Line(4));
+        term.goto(Line(16), Column(0));
+        assert_eq!(term.renderable_cells(&config, true).cursor(), None);
+
+        term.scroll_display(Scroll::Bottom);
+        assert!(term.renderable_cells(&config, true).cursor().is_some());
+        term.mode.remove(TermMode::SHOW_CURSOR);
+        assert_eq!(term.renderable_cells(&config, true).cursor(), None);
+    }
+
+    #[test]
+    fn cell_size_is_reported_after_resizes() {
+        let mut term = term_with_history();
+        let mut size = *term.size_info();
+        size.cell_width = 7.0;
+        size.cell_height = 15.0;
+        term.resize(&size);
+
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
+        for &byte in b"\x1b[16t" {
+            parser.advance(&mut term, byte, &mut reply);
+        }
+        assert_eq!(reply, b"\x1b[6;15;7t".to_vec());
+    }
+
+    #[test]
+    fn replies_honor_eight_bit_controls() {
+        let mut term = term_with_history();
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
+        for &byte in b"\x1b G\x9b6n\x1bP$q\"q\x1b\\\x1bc\x9b5n\x1b[5n" {
+            parser.advance(&mut term, byte, &mut reply);
+        }
+
+        // After the reset, a raw CSI is text again
+        assert_eq!(reply, b"\x9b1;1R\x901$r0\"q\x9c\x1b[0n".to_vec());
+    }
+
+    fn assert_render_cache(term: &mut Term, cache: &mut RenderCache, config: &Config) {
+        term.update_render_cache(config, true, cache);
+        let mut expected: Vec<_> = term.renderable_cells(config, true).collect();
+        let mut cached: Vec<_> = cache.cells().cloned().collect();
+        expected.sort_by_key(|cell| (cell.line, cell.column));
+        cached.sort_by_key(|cell| (cell.line, cell.column));
+        assert_eq!(cached, expected);
+    }
+
+    #[test]
+    fn render_cache_reuses_unchanged_lines() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2324,14 +4093,910 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let mut term = Term::new(&Default::default(), size);
-        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
-        grid[Line(0)][Column(0)].c = 'f';
-        grid[Line(0)][Column(1)].c = 't';
-        grid[Line(0)][Column(2)].c = 'p';
-        grid[Line(0)][Column(3)].c = ':';
-        grid[Line(0)][Column(4)].c = '/';
-        grid[Line(0)][Column(5)].c = '/';
+        let config: Config = Default::default();
+        let mut term = Term::new(&config, size);
+        let mut cache = RenderCache::default();
+        assert_render_cache(&mut term, &mut cache, &config);
+
+        term.input('a');
+        term.input('b');
+        assert_render_cache(&mut term, &mut cache, &config);
+
+        // Both the old and the new cursor line are updated
+        term.goto(Line(5), Column(3));
+        term.input('c');
+        assert_render_cache(&mut term, &mut cache, &config);
+        assert_eq!(cache.lines[0].iter().filter(|cell| cell.c == 'a').count(), 1);
+
+        *term.selection_mut() = Some(Selection::lines(Point { line: 11, col: Column(0) }));
+        assert_render_cache(&mut term, &mut cache, &config);
+
+        for _ in 0..20 {
+            term.linefeed();
+        }
+        assert_render_cache(&mut term, &mut cache, &config);
+    }
+
+    /// Check that the grid can be serialized back and forth losslessly
+    ///
+    /// This test is in the term module as opposed to the grid since we want to
+    /// test this property with a T=Cell.
+    #[test]
+    fn grid_serde() {
+        let template = Cell::default();
+
+        let grid: Grid<Cell> = Grid::new(Line(24), Column(80), 0, template);
+        let serialized = serde_json::to_string(&grid).expect("ser");
+        let deserialized = serde_json::from_str::<Grid<Cell>>(&serialized)
+                                      .expect("de");
+
+        assert_eq!(deserialized, grid);
+    }
+
+    #[test]
+    fn input_line_drawing_character() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let cursor = Point::new(Line(0), Column(0));
+        term.configure_charset(CharsetIndex::G0,
+                               StandardCharset::SpecialCharacterAndLineDrawing);
+        term.input('a');
+
+        assert_eq!(term.grid()[&cursor].c, '▒');
+    }
+
+    #[test]
+    fn preedit_drawn_at_cursor() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term = Term::new(&config, size);
+        term.input('a');
+        term.input('b');
+        term.goto_col(Column(1));
+        term.set_preedit(Some(Preedit::new("日x".into(), 1)));
+
+        let mut cells = term.renderable_cells(&config, true)
+            .filter(|cell| cell.line == Line(0))
+            .map(|cell| (cell.column, cell.c, cell.flags))
+            .collect::<Vec<_>>();
+        cells.sort_by_key(|&(column, _, _)| column);
+
+        let underline = cell::Flags::UNDERLINE;
+        assert_eq!(cells, vec![
+            (Column(0), 'a', cell::Flags::empty()),
+            (Column(1), '日', underline | cell::Flags::WIDE_CHAR),
+            (Column(2), ' ', underline | cell::Flags::WIDE_CHAR_SPACER),
+            (Column(3), 'x', underline | cell::Flags::INVERSE),
+        ]);
+
+        // The grid itself is left untouched
+        assert_eq!(term.grid()[Line(0)][Column(1)].c, 'b');
+
+        // Clearing the preedit shows the grid again
+        term.set_preedit(Some(Preedit::default()));
+        assert!(term.preedit().is_none());
+    }
+
+    fn change_font_size_works(font_size: f32) {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        term.change_font_size(font_size);
+
+        let expected_font_size: Size = config.font().size() + Size::new(font_size);
+        assert_eq!(term.font_size, expected_font_size);
+    }
+
+    #[test]
+    fn increase_font_size_works() {
+        change_font_size_works(10.0);
+    }
+
+    #[test]
+    fn decrease_font_size_works() {
+        change_font_size_works(-10.0);
+    }
+
+    #[test]
+    fn prevent_font_below_threshold_works() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        term.change_font_size(-100.0);
+
+        let expected_font_size: Size = Size::new(FONT_SIZE_STEP);
+        assert_eq!(term.font_size, expected_font_size);
+    }
+
+    #[test]
+    fn reset_font_size_works() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        term.change_font_size(10.0);
+        term.reset_font_size();
+
+        let expected_font_size: Size = config.font().size();
+        assert_eq!(term.font_size, expected_font_size);
+    }
+
+    #[test]
+    fn clear_saved_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        // Add one line of scrollback
+        term.grid.scroll_up(&(Line(0)..Line(1)), Line(1), &Cell::default());
+
+        // Clear the history
+        term.clear_screen(ansi::ClearMode::Saved);
+
+        // Make sure that scrolling does not change the grid
+        let mut scrolled_grid = term.grid.clone();
+        scrolled_grid.scroll_display(Scroll::Top);
+        assert_eq!(term.grid, scrolled_grid);
+    }
+
+    #[test]
+    fn resize_to_tiny_sizes() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        // Cheap deterministic pseudo random numbers
+        let mut seed = 0x2545_f491u32;
+        let mut random = |max: u32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed % (max * 4)) as f32 / 4.
+        };
+
+        for _ in 0..1000 {
+            let mut size = SizeInfo {
+                width: random(40),
+                height: random(40),
+                cell_width: random(6) - 1.,
+                cell_height: random(6) - 1.,
+                padding_x: random(3),
+                padding_y: random(3),
+            };
+            size.clamp_to_grid(2, 1);
+
+            assert!(size.cols() >= Column(2), "{:?}", size);
+            assert!(size.lines() >= Line(1), "{:?}", size);
+
+            term.resize(&size);
+            size.pixels_to_coords(size.width as usize, size.height as usize);
+            term.input('a');
+        }
+    }
+
+    #[test]
+    fn window_resize_requests_are_opt_in() {
+        let size = SizeInfo {
+            width: 100.0,
+            height: 50.0,
+            cell_width: 10.0,
+            cell_height: 20.0,
+            padding_x: 2.0,
+            padding_y: 3.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let request = WindowSize::Cells { lines: Some(2), columns: None };
+
+        term.resize_window(request);
+        assert_eq!(term.next_window_size, None);
+
+        let config: Config = ::serde_yaml::from_str(
+            "window:\n  allow_resize_requests: true\n",
+        ).unwrap();
+        term.update_config(&config);
+        term.resize_window(request);
+        assert_eq!(term.next_window_size, Some(request));
+
+        assert_eq!(size.requested_window_size(request, 1920., 1080.), (100., 46.));
+        let cells = WindowSize::Cells { lines: Some(100), columns: Some(0) };
+        assert_eq!(size.requested_window_size(cells, 1920., 1080.), (1920., 1080.));
+        let pixels = WindowSize::Pixels { height: None, width: Some(640) };
+        assert_eq!(size.requested_window_size(pixels, 1920., 1080.), (640., 50.));
+    }
+
+    /// Terminal with 4 lines of history, all lines except the last contain `a`
+    fn term_with_history() -> Term {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let output: Vec<u8> = (0..20).flat_map(|_| b"a\r\n".iter().cloned()).collect();
+        advance(&mut term, &output);
+        assert_eq!(term.grid.scroll_limit(), 4);
+
+        term
+    }
+
+    fn advance(term: &mut Term, bytes: &[u8]) {
+        let mut parser = ansi::Processor::new();
+        for byte in bytes {
+            parser.advance(term, *byte, &mut io::sink());
+        }
+    }
+
+    /// Terminal with 5 columns and 3 lines
+    fn small_term() -> Term {
+        let size = SizeInfo {
+            width: 5.0,
+            height: 3.0,
+            cell_width: 1.0,
+            cell_height: 1.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        Term::new(&Default::default(), size)
+    }
+
+    fn line_text(term: &Term, line: usize) -> String {
+        term.grid()[Line(line)].iter().map(|cell| cell.c).collect()
+    }
+
+    /// Cases of xterm's wrap tests, which decide where the next char goes
+    #[test]
+    fn pending_wrap() {
+        // Output, the first two lines, the cursor and whether the next char wraps first
+        let cases: &[(&str, [&str; 2], (usize, usize), bool)] = &[
+            ("abcde", ["abcde", "     "], (0, 4), true),
+            ("abcdef", ["abcde", "f    "], (1, 1), false),
+            ("abcde\rX", ["Xbcde", "     "], (0, 1), false),
+            ("abcde\nX", ["abcde", "    X"], (1, 4), true),
+            ("abcde\x1bMX", ["    X", "abcde"], (0, 4), true),
+            // Moving the cursor to the last column doesn't make it wrap again
+            ("abcde\x1b[CX", ["abcdX", "     "], (0, 4), true),
+            ("abcde\x1b[DX", ["abcXe", "     "], (0, 4), false),
+            ("abcde\x1b[1;5HX", ["abcdX", "     "], (0, 4), true),
+            ("ab\x1b[IX", ["ab  X", "     "], (0, 4), true),
+            ("abcde\tX", ["abcdX", "     "], (0, 4), true),
+            // Erases cancel the wrap
+            ("abcde\x1b[KX", ["abcdX", "     "], (0, 4), true),
+            ("abcde\x1b[JX", ["abcdX", "     "], (0, 4), true),
+            ("abcde\x1b[XX", ["abcdX", "     "], (0, 4), true),
+            ("abcde\x1b[PX", ["abcdX", "     "], (0, 4), true),
+            ("abcde\x1b[@X", ["abcdX", "     "], (0, 4), true),
+            // Inserting and deleting lines moves to the left margin
+            ("abcde\x1b[LX", ["X    ", "abcde"], (0, 1), false),
+            ("abcde\x1b[MX", ["X    ", "     "], (0, 1), false),
+            // Backspace stays on the line, even right after a wrap
+            ("abcde\x08X", ["abcXe", "     "], (0, 4), false),
+            ("abcdef\x08X", ["abcde", "X    "], (1, 1), false),
+            // Without DECAWM the last column is overwritten
+            ("\x1b[?7labcdef", ["abcdf", "     "], (0, 4), false),
+            ("abcde\x1b[?7lf", ["abcdf", "     "], (0, 4), false),
+            // Wide chars fill the last column or wrap before it
+            ("abc\u{4e2d}", ["abc\u{4e2d} ", "     "], (0, 4), true),
+            ("abcd\u{4e2d}", ["abcd ", "\u{4e2d}    "], (1, 2), false),
+            ("\x1b[?7labcd\u{4e2d}", ["abc\u{4e2d} ", "     "], (0, 4), false),
+            // The saved cursor keeps the wrap, also around the alternate screen
+            ("abcde\x1b7\r\x1b8X", ["abcde", "X    "], (1, 1), false),
+            ("abcde\x1b[?1049h\x1b[?1049lX", ["abcde", "X    "], (1, 1), false),
+        ];
+
+        for &(output, lines, (line, col), wrap) in cases {
+            let mut term = small_term();
+            advance(&mut term, output.as_bytes());
+
+            let text = (line_text(&term, 0), line_text(&term, 1));
+            assert_eq!(text, (lines[0].to_owned(), lines[1].to_owned()), "output {:?}", output);
+            assert_eq!(term.cursor.point, Point::new(Line(line), Column(col)), "output {:?}", output);
+            assert_eq!(term.input_needs_wrap, wrap, "output {:?}", output);
+        }
+    }
+
+    #[test]
+    fn widening_drops_the_pending_wrap() {
+        let mut term = small_term();
+        advance(&mut term, b"abcde\x1b7");
+        assert!(term.input_needs_wrap);
+
+        let size = SizeInfo { width: 7.0, ..*term.size_info() };
+        term.resize(&size);
+        assert!(!term.input_needs_wrap);
+
+        // The restored cursor isn't in the last column anymore
+        advance(&mut term, b"\x1b8X");
+        assert_eq!(line_text(&term, 0), "abcdX  ");
+        assert_eq!(term.cursor().point, Point::new(Line(0), Column(5)));
+    }
+
+    #[test]
+    fn selective_erase_keeps_protected_cells() {
+        let mut term = small_term();
+
+        // SGR 0 doesn't end the protection, only DECSCA does
+        advance(&mut term, b"ab\x1b[1\"qcd\x1b[0\"qe\r\n\x1b[1\"qX\x1b[mY\x1b[0\"qZ");
+        assert_eq!(line_text(&term, 0), "abcde");
+        assert_eq!(line_text(&term, 1), "XYZ  ");
+
+        advance(&mut term, b"\x1b[?2J");
+        assert_eq!(line_text(&term, 0), "  cd ");
+        assert_eq!(line_text(&term, 1), "XY   ");
+
+        advance(&mut term, b"\x1b[H\x1b[?2K");
+        assert_eq!(line_text(&term, 0), "  cd ");
+
+        // The regular erases ignore the protection
+        advance(&mut term, b"\x1b[2J");
+        assert_eq!(line_text(&term, 0), "     ");
+        assert_eq!(line_text(&term, 1), "     ");
+
+        // A reset ends the protection
+        advance(&mut term, b"\x1b[1\"q\x1bcz\x1b[?2J");
+        assert_eq!(line_text(&term, 0), "     ");
+    }
+
+    #[test]
+    fn linefeeds_return_the_carriage_in_new_line_mode() {
+        let mut term = small_term();
+        advance(&mut term, b"ab\ncd");
+        assert_eq!(line_text(&term, 1), "  cd ");
+
+        advance(&mut term, b"\x1b[20h\nef\x0bg\x1b[20l\nh");
+        assert_eq!(line_text(&term, 0), "ef   ");
+        assert_eq!(line_text(&term, 1), "g    ");
+        assert_eq!(line_text(&term, 2), " h   ");
+        assert_eq!(term.cursor().point, Point { line: Line(2), col: Column(2) });
+
+        // NEL returns the carriage without new line mode as well
+        advance(&mut term, b"\x1bE");
+        assert_eq!(term.cursor().point, Point { line: Line(2), col: Column(0) });
+    }
+
+    #[test]
+    fn insert_mode_shifts_cells_to_the_right() {
+        let mut term = small_term();
+        advance(&mut term, b"abcd\r\x1b[4hxy\x1b[4lz");
+        assert_eq!(line_text(&term, 0), "xyzbc");
+
+        // The half of the wide char which is left at the margin is cleared
+        advance(&mut term, "\r\na日本".as_bytes());
+        advance(&mut term, b"\r\x1b[4hx");
+        assert_eq!(line_text(&term, 1), "xa日  ");
+        assert!(!term.grid()[Line(1)][Column(4)].flags.contains(cell::Flags::WIDE_CHAR));
+    }
+
+    #[test]
+    fn chars_are_inserted_with_the_background_of_the_cursor() {
+        let mut term = small_term();
+        advance(&mut term, b"abcde\r\x1b[2C\x1b[41m\x1b[2@");
+        assert_eq!(line_text(&term, 0), "ab  c");
+        assert_eq!(term.grid()[Line(0)][Column(3)].bg, ansi::Color::Named(ansi::NamedColor::Red));
+        assert_eq!(term.grid()[Line(0)][Column(4)].bg, ansi::Color::Named(ansi::NamedColor::Background));
+        assert_eq!(term.cursor().point.col, Column(2));
+
+        advance(&mut term, b"\x1b[9@");
+        assert_eq!(line_text(&term, 0), "ab   ");
+        assert_eq!(term.grid()[Line(0)][Column(4)].bg, ansi::Color::Named(ansi::NamedColor::Red));
+    }
+
+    #[test]
+    fn chars_are_deleted_up_to_the_margin() {
+        let mut term = small_term();
+        advance(&mut term, b"abcde\r\x1b[1C\x1b[41m\x1b[2P");
+        assert_eq!(line_text(&term, 0), "ade  ");
+        assert_eq!(term.grid()[Line(0)][Column(2)].bg, ansi::Color::Named(ansi::NamedColor::Background));
+        assert_eq!(term.grid()[Line(0)][Column(3)].bg, ansi::Color::Named(ansi::NamedColor::Red));
+        assert_eq!(term.cursor().point.col, Column(1));
+
+        // Only the cells after the cursor are deleted
+        advance(&mut term, b"\r\nabcde\r\x1b[3C\x1b[9P");
+        assert_eq!(line_text(&term, 1), "abc  ");
+    }
+
+    #[test]
+    fn chars_are_erased_up_to_the_margin() {
+        let mut term = small_term();
+        advance(&mut term, b"abcde\r\x1b[3C\x1b[5X");
+        assert_eq!(line_text(&term, 0), "abc  ");
+        assert_eq!(term.cursor().point.col, Column(3));
+
+        advance(&mut term, b"\r\nabcde\r\x1b[X");
+        assert_eq!(line_text(&term, 1), " bcde");
+    }
+
+    #[test]
+    fn erased_cells_only_take_the_background() {
+        let mut term = small_term();
+        advance(&mut term, b"abc\x1b[44;4;7m\x1b[2J\x1b[2;3H\x1b[K\x1b[1K\x1b[2X\x1b[P\x1b[@");
+        advance(&mut term, b"\x1b[3;1H\n\x1b[L\x1b[M\x1b[S\x1b[T\x1b[2 @\x1b[ A");
+
+        let blue = ansi::Color::Named(ansi::NamedColor::Blue);
+        for line in 0..3 {
+            for cell in term.grid()[Line(line)].iter() {
+                assert_eq!((cell.c, cell.bg, cell.flags), (' ', blue, cell::Flags::empty()));
+            }
+        }
+    }
+
+    #[test]
+    fn split_wide_chars_are_cleared() {
+        let mut term = small_term();
+
+        // Deleting the spacer of a wide char
+        advance(&mut term, "a日本\r\x1b[2C\x1b[P".as_bytes());
+        assert_eq!(line_text(&term, 0), "a 本  ");
+
+        // Inserting in the middle of a wide char and pushing one over the margin
+        advance(&mut term, "\r\na日本\r\x1b[2C\x1b[@".as_bytes());
+        assert_eq!(line_text(&term, 1), "a    ");
+
+        // Erasing the first half of a wide char
+        advance(&mut term, "\r\na日本\r\x1b[C\x1b[X".as_bytes());
+        assert_eq!(line_text(&term, 2), "a  本 ");
+        assert!(term.grid()[Line(2)][Column(3)].flags.contains(cell::Flags::WIDE_CHAR));
+    }
+
+    #[test]
+    fn scroll_region_is_shifted_horizontally() {
+        let mut term = small_term();
+        advance(&mut term, b"abcde\r\nfghij\r\nklmno");
+        let cursor = term.cursor().point;
+
+        advance(&mut term, b"\x1b[2 @");
+        assert_eq!(line_text(&term, 0), "cde  ");
+        assert_eq!(line_text(&term, 1), "hij  ");
+        assert_eq!(line_text(&term, 2), "mno  ");
+        assert_eq!(term.cursor().point, cursor);
+
+        advance(&mut term, b"\x1b[ A");
+        assert_eq!(line_text(&term, 0), " cde ");
+        assert_eq!(line_text(&term, 2), " mno ");
+
+        // Only the lines of the scrolling region are shifted
+        advance(&mut term, b"\x1b[1;2r\x1b[9 @");
+        assert_eq!(line_text(&term, 0), "     ");
+        assert_eq!(line_text(&term, 1), "     ");
+        assert_eq!(line_text(&term, 2), " mno ");
+    }
+
+    #[test]
+    fn lines_are_scrolled_between_the_margins() {
+        let mut term = small_term();
+        advance(&mut term, b"abcde\r\nfghij\r\nklmno\x1b[?69h\x1b[2;4s");
+        assert_eq!(term.cursor().point, Point { line: Line(0), col: Column(0) });
+
+        advance(&mut term, b"\x1b[1;2H\x1b[M");
+        assert_eq!(line_text(&term, 0), "aghie");
+        assert_eq!(line_text(&term, 1), "flmnj");
+        assert_eq!(line_text(&term, 2), "k   o");
+
+        advance(&mut term, b"\x1b[L");
+        assert_eq!(line_text(&term, 0), "a   e");
+        assert_eq!(line_text(&term, 1), "fghij");
+        assert_eq!(line_text(&term, 2), "klmno");
+
+        // Nothing enters the history, and the cursor outside of the margins only stays
+        advance(&mut term, b"\x1b[3;2H\n\x1b[3;1H\n");
+        assert_eq!(line_text(&term, 0), "aghie");
+        assert_eq!(line_text(&term, 2), "k   o");
+        assert_eq!(term.grid.scroll_limit(), 0);
+        assert_eq!(term.cursor().point, Point { line: Line(2), col: Column(0) });
+
+        // Cells are only inserted and deleted up to the right margin
+        advance(&mut term, b"\x1b[2;2H\x1b[P");
+        assert_eq!(line_text(&term, 1), "fmn j");
+        advance(&mut term, b"\x1b[2@");
+        assert_eq!(line_text(&term, 1), "f  mj");
+    }
+
+    #[test]
+    fn cursor_is_kept_between_the_margins() {
+        let mut term = small_term();
+        advance(&mut term, b"\x1b[?69h\x1b[2;4s\x1b[1;2Hxyzw");
+        assert_eq!(line_text(&term, 0), " xyz ");
+        assert_eq!(line_text(&term, 1), " w   ");
+        assert_eq!(term.cursor().point, Point { line: Line(1), col: Column(2) });
+
+        advance(&mut term, b"\x1b[9C");
+        assert_eq!(term.cursor().point.col, Column(3));
+        advance(&mut term, b"\r");
+        assert_eq!(term.cursor().point.col, Column(1));
+        advance(&mut term, b"\x1b[9D");
+        assert_eq!(term.cursor().point.col, Column(1));
+
+        // Origin mode addresses the cells between the margins
+        advance(&mut term, b"\x1b[?6h\x1b[1;1H");
+        assert_eq!(term.cursor().point, Point { line: Line(0), col: Column(1) });
+        advance(&mut term, b"\x1b[1;9H");
+        assert_eq!(term.cursor().point.col, Column(3));
+
+        // A soft reset restores the margins
+        advance(&mut term, b"\x1b[!p");
+        assert_eq!(term.horizontal_margins(), Column(0)..Column(5));
+    }
+
+    #[test]
+    fn cursor_is_saved_without_left_right_margin_mode() {
+        let mut term = small_term();
+        advance(&mut term, b"\x1b[2;3H\x1b[s\x1b[H\x1b[u");
+        assert_eq!(term.cursor().point, Point { line: Line(1), col: Column(2) });
+        assert_eq!(term.horizontal_margins(), Column(0)..Column(5));
+
+        // Disabling the mode resets the margins
+        advance(&mut term, b"\x1b[?69h\x1b[2;3s\x1b[?69l\x1b[?69h");
+        assert_eq!(term.horizontal_margins(), Column(0)..Column(5));
+    }
+
+    #[test]
+    fn modes_are_reported() {
+        let mut term = small_term();
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
+        for &byte in b"\x1b[?69h\x1b[?69$p\x1b[?7l\x1b[?7$p\x1b[4$p\x1b[?12$p\x1b[?9999$p" {
+            parser.advance(&mut term, byte, &mut reply);
+        }
+
+        let expected = "\x1b[?69;1$y\x1b[?7;2$y\x1b[4;2$y\x1b[?12;4$y\x1b[?9999;0$y";
+        assert_eq!(String::from_utf8(reply).unwrap(), expected);
+        assert_eq!(request_status(&mut term, "s"), "\x1bP1$r1;5s\x1b\\");
+    }
+
+    #[test]
+    fn enq_sends_the_answerback() {
+        let mut term = small_term();
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
+        parser.advance(&mut term, 0x05, &mut reply);
+        assert!(reply.is_empty());
+
+        let config: Config = ::serde_yaml::from_str(
+            "terminal:\n  answerback: '\\eok\\r'\n",
+        ).unwrap();
+        term.update_config(&config);
+        parser.advance(&mut term, 0x05, &mut reply);
+        assert_eq!(reply, b"\x1bok\r".to_vec());
+
+        // ENQ prints nothing
+        assert_eq!(line_text(&term, 0), "     ");
+    }
+
+    #[test]
+    fn reports_can_be_disabled() {
+        let queries: &[u8] = b"\x1b[c\x1b[>c\x1bZ\x1b[5n\x1b[6n\x1b[?69$p\x1b[16t\x1bP$qm\x1b\\\x05";
+
+        let mut term = small_term();
+        let config: Config = ::serde_yaml::from_str("terminal:\n  answerback: ok\n").unwrap();
+        term.update_config(&config);
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
+        for &byte in queries {
+            parser.advance(&mut term, byte, &mut reply);
+        }
+        assert!(reply.ends_with(b"\x1bP1$r0m\x1b\\ok"));
+
+        // The queries are still consumed
+        let config: Config = ::serde_yaml::from_str(
+            "terminal:\n  answerback: ok\n  osc52: full\n  disable_reports: true\n",
+        ).unwrap();
+        term.update_config(&config);
+        let mut reply = Vec::new();
+        for &byte in queries.iter().chain(b"\x1b]52;c;?\x07x") {
+            parser.advance(&mut term, byte, &mut reply);
+        }
+        assert!(reply.is_empty());
+        assert_eq!(line_text(&term, 0), "x    ");
+
+        // Without full access, the clipboard isn't reported either
+        let mut term = small_term();
+        for &byte in b"\x1b]52;c;?\x07" {
+            parser.advance(&mut term, byte, &mut reply);
+        }
+        assert!(reply.is_empty());
+    }
+
+    #[test]
+    fn alternate_screen_restores_the_saved_cursor() {
+        let mut term = small_term();
+
+        // Pen, character sets and cursor are saved, the rest is global
+        advance(&mut term, b"ab\x1b[1;31m\x1b)0\x0e\x1b[2;3H\x1b]2;title\x07\x1b[?1h\x07");
+        advance(&mut term, b"\x1b[?1049h\x1b[0;44m\x1b(B\x0f\x1b[3;5Hx\x1b[?1l\x1b[?1049l");
+
+        assert_eq!(line_text(&term, 0), "ab   ");
+        assert_eq!(line_text(&term, 2), "     ");
+        assert_eq!(term.cursor().point, Point::new(Line(1), Column(2)));
+        assert!(term.cursor().template.flags.contains(cell::Flags::BOLD));
+        assert_eq!(term.cursor().template.fg, ansi::Color::Named(ansi::NamedColor::Red));
+        assert!(!term.mode().contains(TermMode::APP_CURSOR));
+        assert_eq!(term.get_next_title(), Some("title".to_owned()));
+        assert_eq!(term.next_is_urgent, Some(true));
+        assert!(term.bell_rung_at.is_some());
+
+        advance(&mut term, b"q");
+        assert_eq!(line_text(&term, 1), "  \u{2500}  ");
+
+        // Entering the alternate screen clears it with the current background
+        advance(&mut term, b"\x1b[44m\x1b[?1049h");
+        assert_eq!(line_text(&term, 2), "     ");
+        let background = ansi::Color::Named(ansi::NamedColor::Blue);
+        assert_eq!(term.grid()[Line(2)][Column(4)].bg, background);
+    }
+
+    #[test]
+    fn disabled_auto_wrap_overwrites_the_last_column() {
+        let mut term = small_term();
+        advance(&mut term, b"\x1b[?7labcdefg");
+        assert_eq!(line_text(&term, 0), "abcdg");
+        assert_eq!(line_text(&term, 1), "     ");
+        assert_eq!(term.cursor().point.col, Column(4));
+
+        // Nothing is pending once auto wrap is enabled again
+        advance(&mut term, b"\x1b[?7hhi");
+        assert_eq!(line_text(&term, 0), "abcdh");
+        assert_eq!(line_text(&term, 1), "i    ");
+
+        // A pending wrap is dropped when auto wrap is disabled
+        advance(&mut term, b"\r\x1b[4Cj\x1b[?7lk");
+        assert_eq!(line_text(&term, 1), "i   k");
+
+        // Wide chars are moved back to fit
+        advance(&mut term, "日".as_bytes());
+        assert_eq!(line_text(&term, 1), "i  日 ");
+        assert_eq!(term.cursor().point.col, Column(4));
+    }
+
+    /// Stream random output through a terminal, checking its memory after every chunk
+    ///
+    /// Neither the parser nor the terminal may keep more than their limits
+    /// allow, no matter what's received.
+    fn stream_random_output(chunks: usize, max_string_len: usize) {
+        let size = SizeInfo {
+            width: 800.0,
+            height: 240.0,
+            cell_width: 10.0,
+            cell_height: 10.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let mut parser = ansi::Processor::new();
+        parser.set_max_string_len(max_string_len);
+
+        let max_lines = config.scrolling().history as usize + term.grid.num_lines().0;
+
+        // Xorshift, to get the same bytes on every run
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut chunk = vec![0u8; 0x1_0000];
+        for _ in 0..chunks {
+            for byte in chunk.iter_mut() {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                *byte = state as u8;
+            }
+
+            for byte in &chunk {
+                parser.advance(&mut term, *byte, &mut io::sink());
+            }
+
+            // The grids are swapped on the alternate screen
+            assert!(term.grid.len() <= max_lines);
+            assert!(term.alt_grid.len() <= max_lines);
+            assert!(parser.buffered_len() <= ansi::Processor::max_buffered_len());
+
+            // Invalid UTF-8 in titles is replaced by U+FFFD, which takes up to
+            // three bytes for every byte of the string
+            let title_len = term.next_title.as_ref().map_or(0, |title| title.len());
+            assert!(title_len <= 3 * max_string_len);
+            let dir_len = term.current_dir.as_ref().map_or(0, |dir| dir.as_os_str().len());
+            assert!(dir_len <= max_string_len);
+        }
+    }
+
+    /// Random output must neither panic nor grow the memory used by the terminal
+    ///
+    /// The short string limit lets random output reach the handling of long
+    /// strings. The fuzz target in `fuzz/` covers a lot more input.
+    #[test]
+    fn random_output_is_bounded() {
+        stream_random_output(16, 256);
+    }
+
+    /// Like `random_output_is_bounded`, for 1 GiB of output with the default limit
+    ///
+    /// Run it in release mode with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn gigabyte_of_random_output_is_bounded() {
+        stream_random_output((1 << 30) / 0x1_0000, ansi::DEFAULT_MAX_STRING_LEN);
+    }
+
+    #[test]
+    fn clear_below_keeps_history() {
+        let mut term = term_with_history();
+        advance(&mut term, b"\x1b[6;1H\x1b[0J");
+
+        assert_eq!(term.grid.scroll_limit(), 4);
+        assert_eq!(term.grid[Line(4)][Column(0)].c, 'a');
+        assert_eq!(term.grid[Line(5)][Column(0)].c, ' ');
+        assert_eq!(term.grid[Line(6)][Column(0)].c, ' ');
+    }
+
+    #[test]
+    fn clear_above_keeps_history() {
+        let mut term = term_with_history();
+        advance(&mut term, b"\x1b[2;1H\x1b[1J");
+
+        assert_eq!(term.grid.scroll_limit(), 4);
+        assert_eq!(term.grid[Line(0)][Column(0)].c, ' ');
+        assert_eq!(term.grid[Line(1)][Column(0)].c, ' ');
+        assert_eq!(term.grid[Line(2)][Column(0)].c, 'a');
+    }
+
+    #[test]
+    fn clear_all_keeps_history() {
+        let mut term = term_with_history();
+        advance(&mut term, b"\x1b[2J");
+
+        assert_eq!(term.grid.scroll_limit(), 4);
+        assert_eq!(term.grid[Line(0)][Column(0)].c, ' ');
+        assert_eq!(term.grid[Line(15)][Column(0)].c, ' ');
+    }
+
+    #[test]
+    fn clear_all_copies_to_history() {
+        let mut term = term_with_history();
+        term.clear_copies_to_history = true;
+        advance(&mut term, b"\x1b[2J");
+
+        // Only the 16 lines with text are pushed into the history
+        assert_eq!(term.grid.scroll_limit(), 20);
+        assert_eq!(term.grid[Line(0)][Column(0)].c, ' ');
+        term.grid.scroll_display(Scroll::Lines(1));
+        assert_eq!(term.grid.display_iter().next().map(|cell| cell.inner.c), Some('a'));
+    }
+
+    #[test]
+    fn clear_saved_resets_display_offset() {
+        let mut term = term_with_history();
+        term.grid.scroll_display(Scroll::Lines(2));
+        advance(&mut term, b"\x1b[3J");
+
+        assert_eq!(term.grid.scroll_limit(), 0);
+        assert_eq!(term.grid.display_offset(), 0);
+        assert_eq!(term.grid[Line(0)][Column(0)].c, 'a');
+    }
+
+    #[test]
+    fn output_while_scrolled_up() {
+        let mut term = term_with_history();
+        term.grid.scroll_display(Scroll::Lines(2));
+
+        // The viewport stays on the same lines while they enter the history
+        advance(&mut term, b"b\r\nb\r\n");
+        assert_eq!(term.grid.display_offset(), 4);
+
+        // Until the oldest ones are dropped from the history
+        term.grid.update_history(6);
+        let output: Vec<u8> = (0..30).flat_map(|_| b"b\r\n".iter().cloned()).collect();
+        advance(&mut term, &output);
+        assert_eq!(term.grid.scroll_limit(), 6);
+        assert_eq!(term.grid.display_offset(), 6);
+
+        let config: Config = ::serde_yaml::from_str("scrolling:\n  auto_scroll: true\n").unwrap();
+        term.update_config(&config);
+        advance(&mut term, b"b\r\n");
+        assert_eq!(term.grid.display_offset(), 0);
+    }
+
+    // `((ftp://a.de))` -> `Some("ftp://a.de")`
+    #[test]
+    fn url_trim_unmatched_parens() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
+        grid[Line(0)][Column(0)].c = '(';
+        grid[Line(0)][Column(1)].c = '(';
+        grid[Line(0)][Column(2)].c = 'f';
+        grid[Line(0)][Column(3)].c = 't';
+        grid[Line(0)][Column(4)].c = 'p';
+        grid[Line(0)][Column(5)].c = ':';
+        grid[Line(0)][Column(6)].c = '/';
+        grid[Line(0)][Column(7)].c = '/';
+        grid[Line(0)][Column(8)].c = 'a';
+        grid[Line(0)][Column(9)].c = '.';
+        grid[Line(0)][Column(10)].c = 'd';
+        grid[Line(0)][Column(11)].c = 'e';
+        grid[Line(0)][Column(12)].c = ')';
+        grid[Line(0)][Column(13)].c = ')';
+        mem::swap(&mut term.grid, &mut grid);
+
+        // Search for URL in grid
+        let url = term.url_search(Point::new(0, Column(4)));
+
+        assert_eq!(url, Some("ftp://a.de".into()));
+    }
+
+    // `ftp://a.de/()` -> `Some("ftp://a.de/()")`
+    #[test]
+    fn url_allow_matching_parens() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
+        grid[Line(0)][Column(0)].c = 'f';
+        grid[Line(0)][Column(1)].c = 't';
+        grid[Line(0)][Column(2)].c = 'p';
+        grid[Line(0)][Column(3)].c = ':';
+        grid[Line(0)][Column(4)].c = '/';
+        grid[Line(0)][Column(5)].c = '/';
         grid[Line(0)][Column(6)].c = 'a';
         grid[Line(0)][Column(7)].c = '.';
         grid[Line(0)][Column(8)].c = 'd';
@@ -2370,6 +5035,355 @@ mod tests {
 
         assert_eq!(url, None);
     }
+
+    #[test]
+    fn hovered_urls_are_underlined() {
+        let size = SizeInfo {
+            width: 60.0,
+            height: 9.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term = Term::new(&config, size);
+        term.goto(Line(1), Column(0));
+        for c in "at http://a.de now".chars() {
+            term.input(c);
+        }
+
+        let mut cache = RenderCache::default();
+        assert_render_cache(&mut term, &mut cache, &config);
+
+        let underlined = |cache: &RenderCache| -> Vec<Column> {
+            cache.cells()
+                .filter(|cell| cell.flags.contains(cell::Flags::UNDERLINE))
+                .map(|cell| cell.column)
+                .collect()
+        };
+
+        assert!(!term.highlight_url(Some(Point::new(1, Column(1)))));
+        assert!(term.highlight_url(Some(Point::new(1, Column(5)))));
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursor::Hand));
+        assert_render_cache(&mut term, &mut cache, &config);
+        assert_eq!(underlined(&cache), (3..14).map(Column).collect::<Vec<_>>());
+
+        // The underline isn't part of the grid
+        assert!(!term.grid()[Line(1)][Column(3)].flags.contains(cell::Flags::UNDERLINE));
+
+        assert!(!term.highlight_url(None));
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursor::Text));
+        assert_render_cache(&mut term, &mut cache, &config);
+        assert!(underlined(&cache).is_empty());
+    }
+
+    #[test]
+    fn secondary_device_attributes() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut parser = ansi::Processor::new();
+        let mut response = Vec::new();
+
+        for byte in b"\x1b[c\x1b[>c" {
+            parser.advance(&mut term, *byte, &mut response);
+        }
+
+        let expected = format!("\x1b[?6c\x1b[>0;{};1c", version::number());
+        assert_eq!(String::from_utf8(response).unwrap(), expected);
+    }
+
+    /// Response of the terminal to a DECRQSS request
+    fn request_status(term: &mut Term, selector: &str) -> String {
+        let mut parser = ansi::Processor::new();
+        let mut response = Vec::new();
+
+        for byte in format!("\x1bP$q{}\x1b\\", selector).bytes() {
+            parser.advance(term, byte, &mut response);
+        }
+
+        String::from_utf8(response).unwrap()
+    }
+
+    #[test]
+    fn request_status_round_trips_sgr() {
+        let mut term = term_with_history();
+        let sgrs: [&[u8]; 4] = [
+            b"\x1b[0m",
+            b"\x1b[1;3;4;7;31;102m",
+            b"\x1b[2;8;38;5;123;48;2;1;2;3m",
+            b"\x1b[38;2;255;128;0;45m",
+        ];
+
+        for sgr in &sgrs {
+            advance(&mut term, sgr);
+            let pen = term.cursor.template;
+
+            let response = request_status(&mut term, "m");
+            assert!(response.starts_with("\x1bP1$r0"));
+            assert!(response.ends_with("m\x1b\\"));
+
+            // Applying the reported attributes recreates the pen
+            advance(&mut term, b"\x1b[0;32;41m");
+            let reported = &response[5..response.len() - 2];
+            advance(&mut term, format!("\x1b[{}", reported).as_bytes());
+            assert_eq!(term.cursor.template, pen);
+        }
+    }
+
+    #[test]
+    fn request_status_reports_settings() {
+        let mut term = term_with_history();
+
+        advance(&mut term, b"\x1b[4 q\x1b[2;10r");
+        assert_eq!(request_status(&mut term, " q"), "\x1bP1$r4 q\x1b\\");
+        assert_eq!(request_status(&mut term, "r"), "\x1bP1$r2;10r\x1b\\");
+        assert_eq!(request_status(&mut term, "\"q"), "\x1bP1$r0\"q\x1b\\");
+        advance(&mut term, b"\x1b[1\"q");
+        assert_eq!(request_status(&mut term, "\"q"), "\x1bP1$r1\"q\x1b\\");
+
+        // Unknown selectors are rejected
+        assert_eq!(request_status(&mut term, "x"), "\x1bP0$r\x1b\\");
+
+        // Other DCS sequences with the same intermediate aren't requests
+        let mut parser = ansi::Processor::new();
+        let mut response = Vec::new();
+        for &byte in b"\x1bP$pm\x1b\\\x1bP1$qm\x1b\\" {
+            parser.advance(&mut term, byte, &mut response);
+        }
+        assert!(response.is_empty());
+    }
+
+    /// Parse a config with a `colors` section
+    ///
+    /// The section is only used with the normal and bright colors, the default
+    /// ones are added to it.
+    fn colors_config(yaml: &str) -> Config {
+        let palette = "colors:\n\
+            \x20 normal: { black: '0x000000', red: '0xd54e53', green: '0xb9ca4a', yellow: '0xe6c547', \
+                          blue: '0x7aa6da', magenta: '0xc397d8', cyan: '0x70c0ba', white: '0xffffff' }\n\
+            \x20 bright: { black: '0x666666', red: '0xff3334', green: '0x9ec400', yellow: '0xe7c547', \
+                          blue: '0x7aa6da', magenta: '0xb77ee0', cyan: '0x54ced6', white: '0xffffff' }\n";
+        ::serde_yaml::from_str(&yaml.replace("colors:\n", palette)).unwrap()
+    }
+
+    #[test]
+    fn foreground_color_precedence() {
+        use super::compute_fg_rgb;
+        use ansi::{Color, NamedColor};
+        use term::cell::Flags;
+        use term::color;
+
+        let config = colors_config("colors:\n  primary:\n    bright_foreground: '0xffffff'\n");
+        let colors = color::List::from(config.colors());
+        assert_ne!(colors[NamedColor::BrightForeground], colors[NamedColor::Foreground]);
+        let spec = Rgb { r: 1, g: 2, b: 3 };
+
+        let flags = [Flags::empty(), Flags::BOLD, Flags::DIM, Flags::DIM_BOLD];
+        for &bold_is_bright in &[true, false] {
+            for &flag in &flags {
+                let mut cell = Cell::default();
+                cell.flags = flag;
+                let fg = |color| compute_fg_rgb(&colors, bold_is_bright, true, color, &cell);
+
+                // Truecolor and indexed colors are never adjusted
+                assert_eq!(fg(Color::Spec(spec)), spec);
+                assert_eq!(fg(Color::Indexed(1)), colors[NamedColor::Red]);
+                assert_eq!(fg(Color::Indexed(9)), colors[NamedColor::BrightRed]);
+                assert_eq!(fg(Color::Indexed(42)), colors[42]);
+
+                let expected = match (bold_is_bright, flag) {
+                    (true, Flags::BOLD) => NamedColor::BrightRed,
+                    (false, Flags::DIM_BOLD) | (_, Flags::DIM) => NamedColor::DimRed,
+                    _ => NamedColor::Red,
+                };
+                assert_eq!(fg(Color::Named(NamedColor::Red)), colors[expected]);
+
+                let expected = match (bold_is_bright, flag) {
+                    (true, Flags::BOLD) => NamedColor::BrightForeground,
+                    (false, Flags::DIM_BOLD) | (_, Flags::DIM) => NamedColor::DimForeground,
+                    _ => NamedColor::Foreground,
+                };
+                assert_eq!(fg(Color::Named(NamedColor::Foreground)), colors[expected]);
+            }
+        }
+
+        // Without a bright foreground, bold has no effect on dim text
+        let mut cell = Cell::default();
+        cell.flags = Flags::DIM_BOLD;
+        let dim = compute_fg_rgb(&colors, true, false, Color::Named(NamedColor::Foreground), &cell);
+        assert_eq!(dim, colors[NamedColor::DimForeground]);
+    }
+
+    #[test]
+    fn background_alpha_follows_default_background() {
+        fn alphas(term: &Term, config: &Config) -> Vec<(Column, f32)> {
+            let mut cells = term.renderable_cells(config, true)
+                .filter(|cell| cell.line == Line(0) && cell.column < Column(2))
+                .map(|cell| (cell.column, cell.bg_alpha))
+                .collect::<Vec<_>>();
+            cells.sort_by_key(|&(column, _)| column);
+            cells
+        }
+
+        let mut term = term_with_history();
+        advance(&mut term, b"\x1b[H\x1b[41ma\x1b[48;2;0;0;0mb\x1b[m");
+        let config = Default::default();
+        assert_eq!(alphas(&term, &config), vec![(Column(0), 1.0), (Column(1), 0.0)]);
+
+        let config = colors_config(
+            "background_opacity: 0.5\ncolors:\n  transparent_background_colors: true\n",
+        );
+        assert_eq!(alphas(&term, &config), vec![(Column(0), 0.5), (Column(1), 0.0)]);
+
+        // Changing the default background at runtime is respected
+        advance(&mut term, b"\x1b]11;rgb:d5/4e/53\x07");
+        assert_eq!(alphas(&term, &config), vec![(Column(0), 0.0), (Column(1), 0.5)]);
+    }
+
+    #[test]
+    fn minimum_contrast_adjusts_text() {
+        fn colors(term: &Term, config: &Config) -> Vec<(char, Rgb, Rgb)> {
+            let mut cells = term.renderable_cells(config, true)
+                .filter(|cell| cell.line == Line(0))
+                .map(|cell| (cell.column, cell.c, cell.fg, cell.bg))
+                .collect::<Vec<_>>();
+            cells.sort_by_key(|&(column, ..)| column);
+            cells.into_iter().map(|(_, c, fg, bg)| (c, fg, bg)).collect()
+        }
+
+        let mut term = small_term();
+        advance(&mut term, b"\x1b[90ma\x1b[38;2;128;128;128;48;2;120;120;120mb\x1b[m\r\n");
+        let gray = Rgb { r: 0x66, g: 0x66, b: 0x66 };
+        let black = Rgb { r: 0, g: 0, b: 0 };
+        assert_eq!(colors(&term, &Default::default())[0], ('a', gray, black));
+
+        let config = colors_config("colors:\n  minimum_contrast: 4.5\n");
+        for (c, fg, bg) in colors(&term, &config) {
+            assert!(fg.contrast(bg) >= 4.5, "{} has a contrast of {}", c, fg.contrast(bg));
+        }
+
+        // Selected cells keep the selection colors
+        *term.selection_mut() = Some(Selection::lines(Point { line: 2, col: Column(0) }));
+        assert_eq!(colors(&term, &config)[0], ('a', black, gray));
+
+        // Out of range ratios are rejected
+        let config = colors_config("colors:\n  minimum_contrast: 30\n");
+        assert_eq!(config.colors().minimum_contrast, 1.0);
+    }
+
+    /// Text and background color of the topmost cell drawn at a column of the first line
+    fn drawn_colors(term: &Term, config: &Config, column: usize) -> (Rgb, Rgb) {
+        term.renderable_cells(config, true)
+            .filter(|cell| cell.line == Line(0) && cell.column == Column(column))
+            .map(|cell| (cell.fg, cell.bg))
+            .last()
+            .unwrap()
+    }
+
+    #[test]
+    fn unreadable_cursor_and_selection_colors_invert_the_cell() {
+        use ansi::NamedColor;
+        use term::color;
+
+        let config = colors_config(
+            "colors:\n  cursor: { text: '0x101010', cursor: '0x101010' }\n  \
+             selection: { text: '0x202020', background: '0x202020' }\n",
+        );
+        let colors = color::List::from(config.colors());
+        let (fg, bg) = (colors[NamedColor::Foreground], colors[NamedColor::Background]);
+
+        let mut term = small_term();
+        term.update_config(&config);
+        advance(&mut term, b"ab\x1b[7mcd\x1b[m\x1b[H");
+
+        // The cursor inverts the colors the cell is drawn with
+        assert_eq!(drawn_colors(&term, &config, 0), (bg, fg));
+        assert_eq!(term.renderable_cells(&config, true).cursor().unwrap().color, fg);
+        term.goto(Line(0), Column(2));
+        assert_eq!(drawn_colors(&term, &config, 2), (fg, bg));
+        assert_eq!(term.renderable_cells(&config, true).cursor().unwrap().color, bg);
+
+        // So do selections
+        term.mode.remove(TermMode::SHOW_CURSOR);
+        *term.selection_mut() = Some(Selection::lines(Point { line: 2, col: Column(0) }));
+        assert_eq!(drawn_colors(&term, &config, 1), (bg, fg));
+        assert_eq!(drawn_colors(&term, &config, 3), (fg, bg));
+    }
+
+    #[test]
+    fn readable_cursor_colors_ignore_inverse() {
+        let config = colors_config("colors:\n  cursor: { text: '0x000000', cursor: '0xffffff' }\n");
+        let black = Rgb { r: 0, g: 0, b: 0 };
+        let white = Rgb { r: 0xff, g: 0xff, b: 0xff };
+
+        let mut term = small_term();
+        term.update_config(&config);
+        advance(&mut term, b"a\x1b[7mb\x1b[m\x1b[H");
+
+        assert_eq!(drawn_colors(&term, &config, 0), (black, white));
+        term.goto(Line(0), Column(1));
+        assert_eq!(drawn_colors(&term, &config, 1), (black, white));
+        assert_eq!(term.renderable_cells(&config, true).cursor().unwrap().color, white);
+    }
+
+    #[test]
+    fn indexed_color_overrides_survive_resets() {
+        let config = colors_config("colors:\n  indexed_colors:\n    - { index: 42, color: '0x123456' }\n");
+        let override_color = Rgb { r: 0x12, g: 0x34, b: 0x56 };
+        let mut term = term_with_history();
+        term.update_config(&config);
+        assert_eq!(term.colors[42], override_color);
+
+        // OSC 104 restores the configured override, not the cube color
+        advance(&mut term, b"\x1b]4;42;rgb:ff/ff/ff\x07");
+        assert_eq!(term.colors[42], Rgb { r: 0xff, g: 0xff, b: 0xff });
+        advance(&mut term, b"\x1b]104;42\x07");
+        assert_eq!(term.colors[42], override_color);
+
+        // Reloading keeps colors which were changed at runtime
+        advance(&mut term, b"\x1b]4;42;rgb:ff/ff/ff\x07");
+        term.update_config(&Default::default());
+        assert_eq!(term.colors[42], Rgb { r: 0xff, g: 0xff, b: 0xff });
+        advance(&mut term, b"\x1b]104\x07");
+        assert_eq!(term.colors[42], Rgb { r: 0x00, g: 0xd7, b: 0x87 });
+    }
+
+    #[test]
+    fn state_dump() {
+        let mut term = small_term();
+        advance(&mut term, b"\x1b[?1h\x1b[2;3r\x1b)0\x0e\x1b[1;31m\x1b]4;1;rgb:12/34/56\x07abcde");
+
+        let json = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+        let dump = serde_json::to_value(term.state_dump(false)).unwrap();
+        assert_eq!(dump["lines"], 3);
+        assert_eq!(dump["columns"], 5);
+        assert!(dump["modes"].as_array().unwrap().contains(&"APP_CURSOR".into()));
+        assert_eq!(dump["scroll_region"], json("[1, 3]"));
+        assert_eq!(dump["input_needs_wrap"], true);
+        assert_eq!(dump["cursor"]["point"], json(r#"{ "line": 0, "col": 4 }"#));
+        assert_eq!(dump["cursor"]["pen"]["fg"], json(r#"{ "Named": "Red" }"#));
+        assert_eq!(dump["cursor"]["charsets"][1], "SpecialCharacterAndLineDrawing");
+        assert_eq!(dump["cursor"]["active_charset"], 1);
+        assert_eq!(dump["tab_stops"], json("[0]"));
+        let colors = r##"[{ "index": 1, "color": "#123456", "default": "#d54e53" }]"##;
+        assert_eq!(dump["colors"], json(colors));
+        assert!(dump["grid"].is_null());
+
+        let dump = serde_json::to_value(term.state_dump(true)).unwrap();
+        assert_eq!(dump["grid"]["lines"], 3);
+
+        // Dumps are read back without losing anything
+        let read: StateDump = serde_json::from_value(dump.clone()).unwrap();
+        assert_eq!(serde_json::to_value(read).unwrap(), dump);
+    }
 }
 
 #[cfg(all(test, feature = "bench"))]
@@ -2377,11 +5391,12 @@ mod benches {
     extern crate test;
     extern crate serde_json as json;
 
-    use std::io::Read;
+    use std::io::{self, Read};
     use std::fs::File;
     use std::mem;
     use std::path::Path;
 
+    use ansi;
     use grid::Grid;
     use config::Config;
 
@@ -2432,4 +5447,93 @@ mod benches {
             }
         })
     }
+
+    /// Benchmark for the renderable cells of an idle prompt
+    ///
+    /// A 4K window with only a prompt on the first line, all other lines are
+    /// blank and skipped without looking at their cells.
+    #[bench]
+    fn render_iter_idle_prompt(b: &mut test::Bencher) {
+        let size = SizeInfo {
+            width: 3840.0,
+            height: 2160.0,
+            cell_width: 10.0,
+            cell_height: 20.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+
+        let config = Config::default();
+        let mut terminal = Term::new(&config, size);
+        let mut parser = ansi::Processor::new();
+        for byte in b"user@host:~$ ".iter() {
+            parser.advance(&mut terminal, *byte, &mut io::sink());
+        }
+
+        b.iter(|| {
+            let iter = terminal.renderable_cells(&config, false);
+            for cell in iter {
+                test::black_box(cell);
+            }
+        })
+    }
+
+    /// Benchmark for the parser throughput
+    ///
+    /// Feeds the output of `yes` through the parser, which is the workload the
+    /// flow control of the event loop has to deal with.
+    #[bench]
+    fn parse_yes(b: &mut test::Bencher) {
+        let size = SizeInfo {
+            width: 1000.0,
+            height: 1000.0,
+            cell_width: 10.0,
+            cell_height: 20.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+
+        let config = Config::default();
+        let mut terminal = Term::new(&config, size);
+        let mut parser = ansi::Processor::new();
+
+        let input = "y\n".repeat(0x8_0000);
+        b.bytes = input.len() as u64;
+
+        b.iter(|| {
+            for byte in input.as_bytes() {
+                parser.advance(&mut terminal, *byte, &mut io::sink());
+            }
+        })
+    }
+
+    /// Benchmark for scrolling the screen into a full history
+    ///
+    /// Feeds the output of `seq`, every line scrolls the whole screen. The
+    /// lines of the grid are rotated instead of being moved, so this shouldn't
+    /// depend on the size of the screen or the history.
+    #[bench]
+    fn scroll_seq(b: &mut test::Bencher) {
+        let size = SizeInfo {
+            width: 1000.0,
+            height: 1000.0,
+            cell_width: 10.0,
+            cell_height: 20.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+
+        let config = Config::default();
+        let mut terminal = Term::new(&config, size);
+        let mut parser = ansi::Processor::new();
+
+        let input: String = (1..100_000).map(|i| format!("{}\r\n", i)).collect();
+        b.bytes = input.len() as u64;
+
+        b.iter(|| {
+            for byte in input.as_bytes() {
+                parser.advance(&mut terminal, *byte, &mut io::sink());
+            }
+        })
+    }
 }
@@ -0,0 +1,205 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Window title built from the `window.title_format` template
+use std::env;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use config::Config;
+use term::Term;
+use tty::{Foreground, Process};
+use cli::DEFAULT_TITLE;
+
+/// Minimum time between two lookups of the foreground process
+///
+/// The lookup reads from `/proc`, which is too slow to do on every frame.
+const REFRESH_INTERVAL_MS: u64 = 250;
+
+/// Longest title passed on to the window, in characters
+const MAX_TITLE_LEN: usize = 256;
+
+pub struct Title {
+    /// Template from the config, the title is passed on as-is without one
+    format: Option<String>,
+
+    /// Titles set by the running program are shown
+    dynamic: bool,
+
+    /// Title set by the running program
+    title: String,
+
+    foreground: Foreground,
+
+    /// Foreground process at the time of the last lookup
+    process: Option<Process>,
+    refreshed_at: Option<Instant>,
+
+    /// Title which was shown last
+    shown: Option<String>,
+}
+
+impl Title {
+    pub fn new(config: &Config, foreground: Foreground) -> Title {
+        Title {
+            format: config.window().title_format().map(str::to_owned),
+            dynamic: config.dynamic_title(),
+            title: DEFAULT_TITLE.to_owned(),
+            foreground,
+            process: None,
+            refreshed_at: None,
+            shown: None,
+        }
+    }
+
+    pub fn update_config(&mut self, config: &Config) {
+        self.format = config.window().title_format().map(str::to_owned);
+        self.dynamic = config.dynamic_title();
+        self.shown = None;
+    }
+
+    /// Title to show in the window, if it changed since the last call
+    ///
+    /// The foreground process is only looked up again once the last lookup is
+    /// older than `REFRESH_INTERVAL_MS`.
+    pub fn next_title(&mut self, terminal: &mut Term) -> Option<String> {
+        if let Some(title) = terminal.get_next_title() {
+            self.title = title;
+        }
+
+        if !self.dynamic {
+            return None;
+        }
+
+        let title = match self.format {
+            Some(ref format) => {
+                let interval = Duration::from_millis(REFRESH_INTERVAL_MS);
+                if self.refreshed_at.map(|at| at.elapsed() >= interval).unwrap_or(true) {
+                    self.process = self.foreground.process();
+                    self.refreshed_at = Some(Instant::now());
+                }
+
+                let process = self.process.as_ref();
+                let cwd = terminal.current_dir().or_else(|| {
+                    process.and_then(|process| process.cwd.as_ref()).map(|cwd| cwd.as_path())
+                });
+                let program = process.map(|process| process.name.as_str());
+                format_title(format, &self.title, cwd, program)
+            },
+            None => self.title.clone(),
+        };
+        let title = sanitize(&title);
+
+        if self.shown.as_ref() == Some(&title) {
+            return None;
+        }
+
+        self.shown = Some(title.clone());
+        Some(title)
+    }
+
+    /// Time until the foreground process should be looked up again
+    ///
+    /// The program and its directory change without any output, so titles
+    /// which show them are refreshed on a timer.
+    pub fn next_refresh(&self) -> Option<Duration> {
+        let format = self.format.as_ref()?;
+        if !self.dynamic || !(format.contains("{program}") || format.contains("{cwd}")) {
+            return None;
+        }
+
+        let interval = Duration::from_millis(REFRESH_INTERVAL_MS);
+        let elapsed = self.refreshed_at.map_or(interval, |at| at.elapsed());
+        Some(interval.checked_sub(elapsed).unwrap_or(Duration::from_millis(0)))
+    }
+}
+
+/// Replace the placeholders of a title template
+///
+/// Unknown placeholders are kept as they are, unknown values are left empty.
+fn format_title(format: &str, title: &str, cwd: Option<&Path>, program: Option<&str>) -> String {
+    let mut formatted = String::with_capacity(format.len());
+
+    let mut rest = format;
+    while let (Some(start), Some(end)) = (rest.find('{'), rest.find('}')) {
+        if end < start {
+            formatted.push_str(&rest[..=end]);
+            rest = &rest[end + 1..];
+            continue;
+        }
+
+        formatted.push_str(&rest[..start]);
+        match &rest[start..=end] {
+            "{title}" => formatted.push_str(title),
+            "{cwd}" => formatted.push_str(&cwd.map(display_path).unwrap_or_default()),
+            "{program}" => formatted.push_str(program.unwrap_or("")),
+            placeholder => formatted.push_str(placeholder),
+        }
+        rest = &rest[end + 1..];
+    }
+    formatted.push_str(rest);
+
+    formatted
+}
+
+/// Title without control characters and invalid UTF-8, cut to `MAX_TITLE_LEN`
+///
+/// Invalid UTF-8 was already replaced by U+FFFD when the title was parsed.
+fn sanitize(title: &str) -> String {
+    title.chars()
+        .filter(|&c| !c.is_control() && c != '\u{fffd}')
+        .take(MAX_TITLE_LEN)
+        .collect()
+}
+
+/// Path with the home directory abbreviated to `~`
+fn display_path(path: &Path) -> String {
+    let home = env::var_os("HOME");
+    match home.as_ref().and_then(|home| path.strip_prefix(home).ok()) {
+        Some(relative) if relative == Path::new("") => String::from("~"),
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{format_title, sanitize, MAX_TITLE_LEN};
+
+    #[test]
+    fn format_title_placeholders() {
+        let title = format_title(
+            "{program} — {cwd} ({title})",
+            "zsh",
+            Some(Path::new("/tmp/project")),
+            Some("vim"),
+        );
+        assert_eq!(title, "vim — /tmp/project (zsh)");
+
+        assert_eq!(format_title("{program}{cwd}{other}", "zsh", None, None), "{other}");
+
+        // Placeholders in the title itself aren't replaced
+        assert_eq!(format_title("} {title}", "{cwd}", None, None), "} {cwd}");
+    }
+
+    #[test]
+    fn titles_are_sanitized() {
+        assert_eq!(sanitize("vim\x1b[2J\u{fffd}\u{9b}\n\x07 ~"), "vim[2J ~");
+
+        let long: String = ::std::iter::repeat('ä').take(2 * MAX_TITLE_LEN).collect();
+        assert_eq!(sanitize(&long).chars().count(), MAX_TITLE_LEN);
+    }
+}
@@ -16,6 +16,7 @@
 
 use mio;
 use std::io;
+use std::path::PathBuf;
 
 #[cfg(not(windows))]
 mod unix;
@@ -27,6 +28,9 @@ mod windows;
 #[cfg(windows)]
 pub use self::windows::*;
 
+mod replay;
+pub use self::replay::Replay;
+
 /// This trait defines the behaviour needed to read and/or write to a stream.
 /// It defines an abstraction over mio's interface in order to allow either one
 /// read/write object or a seperate read and write object.
@@ -42,4 +46,30 @@ pub trait EventedReadWrite {
     fn read_token(&self) -> mio::Token;
     fn writer(&mut self) -> &mut Self::Writer;
     fn write_token(&self) -> mio::Token;
+
+    /// Token used for waking up the event loop when the child changes state
+    ///
+    /// Implementations that have no way to register the child with mio return
+    /// `None`, in which case `next_child_event` is polled on every iteration.
+    fn child_event_token(&self) -> Option<mio::Token>;
+
+    /// Fetch the next pending state change of the child process
+    fn next_child_event(&mut self) -> Option<ChildEvent>;
+}
+
+/// Process in the foreground of the pty
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Process {
+    /// Name of the program
+    pub name: String,
+
+    /// Working directory, if it could be determined
+    pub cwd: Option<PathBuf>,
+}
+
+/// Events concerning the process running inside the pty
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChildEvent {
+    /// The child process exited with the given status code
+    Exited(i32),
 }
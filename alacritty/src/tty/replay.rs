@@ -0,0 +1,144 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Replay of recorded pty output
+//!
+//! Used in place of a pty to feed a recording, like the ones created by
+//! `--ref-test`, through the event loop as fast as possible.
+
+use std::fs::File;
+use std::io::{self, Read, Sink};
+use std::path::Path;
+
+use mio;
+
+use term::{OnResize, SizeInfo};
+use tty::{ChildEvent, EventedReadWrite};
+
+/// Reader which remembers when the end of the recording was reached
+pub struct ReplayReader {
+    file: File,
+    finished: bool,
+}
+
+impl Read for ReplayReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            self.finished = true;
+        }
+        Ok(read)
+    }
+}
+
+pub struct Replay {
+    reader: ReplayReader,
+    writer: Sink,
+    len: u64,
+    registration: mio::Registration,
+    readiness: mio::SetReadiness,
+    token: mio::Token,
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(len_without_is_empty))]
+impl Replay {
+    /// Open a recording for replay
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        // Files can't be polled, but they are always ready to be read from
+        let (registration, readiness) = mio::Registration::new2();
+        readiness.set_readiness(mio::Ready::readable())?;
+
+        Ok(Replay {
+            reader: ReplayReader { file, finished: false },
+            writer: io::sink(),
+            len,
+            registration,
+            readiness,
+            token: mio::Token::from(0),
+        })
+    }
+
+    /// Size of the recording in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+}
+
+impl EventedReadWrite for Replay {
+    type Reader = ReplayReader;
+    type Writer = Sink;
+
+    #[inline]
+    fn register(
+        &mut self,
+        poll: &mio::Poll,
+        token: &mut Iterator<Item = &usize>,
+        interest: mio::Ready,
+        poll_opts: mio::PollOpt,
+    ) -> io::Result<()> {
+        self.token = (*token.next().unwrap()).into();
+        poll.register(&self.registration, self.token, interest, poll_opts)
+    }
+
+    #[inline]
+    fn reregister(&mut self, poll: &mio::Poll, interest: mio::Ready, poll_opts: mio::PollOpt) -> io::Result<()> {
+        poll.reregister(&self.registration, self.token, interest, poll_opts)
+    }
+
+    #[inline]
+    fn deregister(&mut self, poll: &mio::Poll) -> io::Result<()> {
+        self.readiness.set_readiness(mio::Ready::empty())?;
+        poll.deregister(&self.registration)
+    }
+
+    #[inline]
+    fn reader(&mut self) -> &mut ReplayReader {
+        &mut self.reader
+    }
+
+    #[inline]
+    fn read_token(&self) -> mio::Token {
+        self.token
+    }
+
+    #[inline]
+    fn writer(&mut self) -> &mut Sink {
+        &mut self.writer
+    }
+
+    #[inline]
+    fn write_token(&self) -> mio::Token {
+        self.token
+    }
+
+    #[inline]
+    fn child_event_token(&self) -> Option<mio::Token> {
+        None
+    }
+
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        if self.reader.finished {
+            Some(ChildEvent::Exited(0))
+        } else {
+            None
+        }
+    }
+}
+
+impl OnResize for Replay {
+    fn on_resize(&mut self, _size: &SizeInfo) {}
+}
@@ -15,57 +15,154 @@
 //! tty related functionality
 //!
 
-use tty::EventedReadWrite;
-use term::SizeInfo;
-use display::OnResize;
+use tty::{ChildEvent, EventedReadWrite, Process};
+use term::{OnResize, SizeInfo};
 use config::{Config, Shell};
 use cli::Options;
 use mio;
 
-use libc::{self, c_int, pid_t, winsize, SIGCHLD, TIOCSCTTY, WNOHANG};
+use libc::{self, c_int, winsize, SIGCHLD, TIOCSCTTY};
 use terminfo::Database;
 
-use std::os::unix::io::{FromRawFd, RawFd};
-use std::fs::File;
-use std::os::unix::process::CommandExt;
-use std::process::{Command, Stdio};
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+use std::fs::{self, File};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, Stdio};
 use std::ffi::CStr;
 use std::ptr;
+use std::sync::{Arc, Mutex, Once, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
 use mio::unix::EventedFd;
-use std::io;
+use std::io::{self, Read};
 use std::os::unix::io::AsRawFd;
 
 
-/// Process ID of child process
+/// Write end of the pipe which is notified whenever a SIGCHLD is received
 ///
-/// Necessary to put this in static storage for `sigchld` to have access
-static mut PID: pid_t = 0;
+/// Signal handlers can't carry any state, so the pipe is kept in static storage
+/// for `sigchld` to have access. It's set once, before the handler is installed.
+static mut SIGCHLD_PIPE: c_int = -1;
 
-/// Exit flag
-///
-/// Calling exit() in the SIGCHLD handler sometimes causes opengl to deadlock,
-/// and the process hangs. Instead, this flag is set, and its status can be
-/// checked via `process_should_exit`.
-static mut SHOULD_EXIT: bool = false;
+/// Write ends of the `ChildSignals` of every `Pty`, set together with `SIGCHLD_PIPE`
+static mut CHILD_SIGNALS: *const Mutex<Vec<RawFd>> = 0 as *const _;
+
+static SIGCHLD_INIT: Once = Once::new();
 
 extern "C" fn sigchld(_a: c_int) {
-    let mut status: c_int = 0;
+    // Only async-signal-safe functions may be used here, so the actual
+    // handling is deferred to the event loop.
     unsafe {
-        let p = libc::waitpid(PID, &mut status, WNOHANG);
-        if p < 0 {
-            die!("Waiting for pid {} failed: {}\n", PID, errno());
+        let saved_errno = errno();
+        libc::write(SIGCHLD_PIPE, b"\0".as_ptr() as *const _, 1);
+        ::errno::set_errno(::errno::Errno(saved_errno));
+    }
+}
+
+/// Install the SIGCHLD handler, on the first call
+///
+/// Returns the pipes which are notified of every SIGCHLD. The handler only
+/// wakes up a thread, which then notifies each of them.
+fn child_signal_pipes() -> &'static Mutex<Vec<RawFd>> {
+    SIGCHLD_INIT.call_once(|| {
+        let (reader, writer) = pipe()
+            .unwrap_or_else(|err| die!("Creating SIGCHLD pipe failed: {}", err));
+
+        let pipes: &'static Mutex<Vec<RawFd>> = Box::leak(Box::new(Mutex::new(Vec::new())));
+        unsafe {
+            // The handler must never block, the thread waits for it instead
+            set_nonblocking(writer.as_raw_fd());
+            SIGCHLD_PIPE = writer.into_raw_fd();
+            CHILD_SIGNALS = pipes;
+
+            libc::signal(SIGCHLD, sigchld as _);
         }
 
-        if PID == p {
-            SHOULD_EXIT = true;
+        ::util::thread::spawn_named("SIGCHLD forwarder", move || {
+            forward_child_signals(reader, pipes)
+        });
+    });
+
+    unsafe { &*CHILD_SIGNALS }
+}
+
+/// Notify every pipe in `pipes` for each SIGCHLD
+fn forward_child_signals(mut reader: File, pipes: &Mutex<Vec<RawFd>>) {
+    let mut buf = [0u8; 64];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return,
+            Ok(_) => (),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                error!("Reading SIGCHLD pipe failed: {}", err);
+                return;
+            },
+        }
+
+        // A full pipe already has a notification pending, so errors are ignored
+        for &fd in pipes.lock().unwrap().iter() {
+            unsafe {
+                libc::write(fd, b"\0".as_ptr() as *const _, 1);
+            }
         }
     }
 }
 
-pub fn process_should_exit() -> bool {
-    unsafe { SHOULD_EXIT }
+/// Pipe of a single `Pty`, which is notified whenever a SIGCHLD is received
+///
+/// The notifications of one terminal are drained without taking them away
+/// from the others, each one checks on its own child process.
+struct ChildSignals {
+    reader: File,
+    writer: File,
 }
 
+impl ChildSignals {
+    fn new() -> io::Result<ChildSignals> {
+        let pipes = child_signal_pipes();
+
+        let (reader, writer) = pipe()?;
+        unsafe {
+            set_nonblocking(reader.as_raw_fd());
+            set_nonblocking(writer.as_raw_fd());
+        }
+        pipes.lock().unwrap().push(writer.as_raw_fd());
+
+        Ok(ChildSignals { reader, writer })
+    }
+
+    /// Drop the pending notifications
+    ///
+    /// A single check is enough for any number of signals. The reader is
+    /// non-blocking, so this stops once the pipe is empty.
+    fn drain(&mut self) {
+        let mut buf = [0u8; 64];
+        loop {
+            match self.reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl Drop for ChildSignals {
+    /// Stop the notifications before the writer is closed and its fd reused
+    fn drop(&mut self) {
+        let fd = self.writer.as_raw_fd();
+        child_signal_pipes().lock().unwrap().retain(|&pipe| pipe != fd);
+    }
+}
+
+/// Time the processes of the terminal get to exit after the hangup
+const HANGUP_TIMEOUT_MS: u64 = 100;
+
+/// Time the processes get to exit after `SIGTERM`, before they're killed
+const TERMINATE_TIMEOUT_MS: u64 = 1000;
+
 /// Get the current value of errno
 fn errno() -> c_int {
     ::errno::errno().0
@@ -189,6 +286,14 @@ pub struct Pty {
     pub fd: File,
     pub raw_fd: RawFd,
     token: mio::Token,
+    child: Child,
+    signals: ChildSignals,
+    signals_token: mio::Token,
+    kill_on_close: bool,
+
+    /// Duplicate of the master which is used for foreground lookups, it's
+    /// closed together with the pty
+    control: Arc<File>,
 }
 
 impl Pty {
@@ -207,13 +312,134 @@ impl Pty {
             die!("ioctl TIOCSWINSZ failed: {}", errno());
         }
     }
+
+    /// Handle for looking up the foreground process from another thread
+    pub fn foreground(&self) -> Foreground {
+        Foreground {
+            control: Some(Arc::downgrade(&self.control)),
+            shell: Some(self.child.id() as libc::pid_t),
+        }
+    }
+
+    /// Wait until the process groups are gone, at most for `timeout_ms`
+    fn wait_for_groups(&mut self, groups: &mut Vec<libc::pid_t>, timeout_ms: u64) {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            // The group of the shell is only empty once the shell was reaped
+            let _ = self.child.try_wait();
+            groups.retain(|&pgrp| unsafe { libc::kill(-pgrp, 0) } == 0);
+
+            if groups.is_empty() || Instant::now() >= deadline {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for Pty {
+    /// Hang up the processes of the terminal before the pty is closed
+    ///
+    /// The shell was made the leader of its own process group, which is sent
+    /// a `SIGHUP` together with the job in the foreground. Processes which
+    /// ignore it would otherwise keep running without a terminal.
+    fn drop(&mut self) {
+        let mut groups = vec![self.child.id() as libc::pid_t];
+        let foreground = unsafe { libc::tcgetpgrp(self.raw_fd) };
+        if foreground > 0 && foreground != groups[0] {
+            groups.push(foreground);
+        }
+
+        let mut signals = vec![(libc::SIGHUP, HANGUP_TIMEOUT_MS)];
+        if self.kill_on_close {
+            signals.push((libc::SIGTERM, TERMINATE_TIMEOUT_MS));
+            signals.push((libc::SIGKILL, 0));
+        }
+
+        for (signal, timeout_ms) in signals {
+            debug!("Sending signal {} to process groups {:?}", signal, groups);
+            groups.retain(|&pgrp| unsafe { libc::kill(-pgrp, signal) } == 0);
+
+            // Stopped jobs have to be continued to handle the hangup
+            if signal == libc::SIGHUP {
+                for pgrp in &groups {
+                    unsafe { libc::kill(-pgrp, libc::SIGCONT); }
+                }
+            }
+
+            self.wait_for_groups(&mut groups, timeout_ms);
+            if groups.is_empty() {
+                return;
+            }
+        }
+
+        if !self.kill_on_close {
+            info!("Processes are still running after the hangup: {:?}", groups);
+        }
+    }
+}
+
+/// Lookup of the process in the foreground of a pty
+///
+/// The pty is owned by the I/O thread, so this only keeps a weak reference to
+/// a duplicate of its file descriptor. Once the pty is closed no process is
+/// found anymore, a reused descriptor is never queried.
+#[derive(Debug, Default, Clone)]
+pub struct Foreground {
+    control: Option<Weak<File>>,
+
+    /// The shell leads a process group of its own
+    shell: Option<libc::pid_t>,
+}
+
+impl Foreground {
+    /// Process group leader in the foreground of the pty
+    pub fn process(&self) -> Option<Process> {
+        let pgrp = self.pgrp()?;
+        process_info(pgrp)
+    }
+
+    /// Whether a job other than the shell is in the foreground of the pty
+    pub fn has_job(&self) -> bool {
+        match (self.pgrp(), self.shell) {
+            (Some(pgrp), Some(shell)) => pgrp != shell,
+            _ => false,
+        }
+    }
+
+    /// Foreground process group of the pty, while it's still open
+    fn pgrp(&self) -> Option<libc::pid_t> {
+        // The pty can't be closed while the descriptor is borrowed
+        let control = self.control.as_ref()?.upgrade()?;
+        let pgrp = unsafe { libc::tcgetpgrp(control.as_raw_fd()) };
+        if pgrp > 0 {
+            Some(pgrp)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_info(pid: libc::pid_t) -> Option<Process> {
+    let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+
+    Some(Process {
+        name: comm.trim().to_owned(),
+        cwd: fs::read_link(format!("/proc/{}/cwd", pid)).ok(),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_info(_pid: libc::pid_t) -> Option<Process> {
+    None
 }
 
 /// Create a new tty and return a handle to interact with it.
-pub fn new<T: ToWinsize>(
+pub fn new(
     config: &Config,
     options: &Options,
-    size: &T,
+    size: &SizeInfo,
     window_id: Option<usize>,
 ) -> Pty {
     let win = size.to_winsize();
@@ -222,6 +448,13 @@ pub fn new<T: ToWinsize>(
 
     let (master, slave) = openpty(win.ws_row as _, win.ws_col as _);
 
+    // Nothing but the shell may inherit the pty, programs started by Alacritty
+    // itself would keep it open after the shell is gone
+    unsafe {
+        libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+        libc::fcntl(slave, libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+
     let default_shell = &Shell::new(pw.shell);
     let shell = config.shell()
         .unwrap_or(default_shell);
@@ -261,6 +494,11 @@ pub fn new<T: ToWinsize>(
     if let Some(window_id) = window_id {
         builder.env("WINDOWID", format!("{}", window_id));
     }
+
+    // Size of a cell in pixels when the shell was started, programs drawing
+    // images should ask for the current size with `CSI 16 t`
+    builder.env("ALACRITTY_CELL_WIDTH", format!("{}", size.cell_width as usize));
+    builder.env("ALACRITTY_CELL_HEIGHT", format!("{}", size.cell_height as usize));
     for (key, value) in config.env().iter() {
         builder.env(key, value);
     }
@@ -298,15 +536,12 @@ pub fn new<T: ToWinsize>(
         builder.current_dir(dir.as_path());
     }
 
+    // Handle SIGCHLD before spawning, so an early exit of the child isn't missed
+    let signals = ChildSignals::new()
+        .unwrap_or_else(|err| die!("Creating SIGCHLD pipe failed: {}", err));
+
     match builder.spawn() {
         Ok(child) => {
-            unsafe {
-                // Set PID for SIGCHLD handler
-                PID = child.id() as _;
-
-                // Handle SIGCHLD
-                libc::signal(SIGCHLD, sigchld as _);
-            }
             unsafe {
                 // Maybe this should be done outside of this function so nonblocking
                 // isn't forced upon consumers. Although maybe it should be?
@@ -316,7 +551,12 @@ pub fn new<T: ToWinsize>(
             let pty = Pty {
                 fd: unsafe {File::from_raw_fd(master) },
                 raw_fd: master,
-                token: mio::Token::from(0)
+                token: mio::Token::from(0),
+                child,
+                signals,
+                signals_token: mio::Token::from(0),
+                kill_on_close: config.terminal().kill_on_close(),
+                control: Arc::new(dup(master).unwrap_or_else(|err| die!("dup() failed: {}", err))),
             };
             pty.resize(size);
             pty
@@ -345,6 +585,16 @@ impl EventedReadWrite for Pty {
             self.token,
             interest,
             poll_opts
+        )?;
+
+        // The signal pipe is drained on every wakeup, so it doesn't need to
+        // be reregistered like the pty itself.
+        self.signals_token = (*token.next().unwrap()).into();
+        poll.register(
+            &EventedFd(&self.signals.reader.as_raw_fd()),
+            self.signals_token,
+            mio::Ready::readable(),
+            mio::PollOpt::level()
         )
     }
 
@@ -360,7 +610,8 @@ impl EventedReadWrite for Pty {
 
     #[inline]
     fn deregister(&mut self, poll: &mio::Poll) -> io::Result<()> {
-        poll.deregister(&EventedFd(&self.raw_fd))
+        poll.deregister(&EventedFd(&self.raw_fd))?;
+        poll.deregister(&EventedFd(&self.signals.reader.as_raw_fd()))
     }
 
     #[inline]
@@ -382,6 +633,30 @@ impl EventedReadWrite for Pty {
     fn write_token(&self) -> mio::Token {
         self.token
     }
+
+    #[inline]
+    fn child_event_token(&self) -> Option<mio::Token> {
+        Some(self.signals_token)
+    }
+
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        self.signals.drain();
+
+        match self.child.try_wait() {
+            Ok(Some(status)) => {
+                // Follow the shell convention for processes killed by a signal
+                let code = status.code()
+                    .or_else(|| status.signal().map(|signal| 128 + signal))
+                    .unwrap_or(1);
+                Some(ChildEvent::Exited(code))
+            },
+            Ok(None) => None,
+            Err(err) => {
+                error!("Error checking child process termination: {}", err);
+                None
+            },
+        }
+    }
 }
 
 /// Types that can produce a `libc::winsize`
@@ -401,17 +676,9 @@ impl<'a> ToWinsize for &'a SizeInfo {
     }
 }
 
-impl OnResize for i32 {
+impl OnResize for Pty {
     fn on_resize(&mut self, size: &SizeInfo) {
-        let win = size.to_winsize();
-
-        let res = unsafe {
-            libc::ioctl(*self, libc::TIOCSWINSZ, &win as *const _)
-        };
-
-        if res < 0 {
-            die!("ioctl TIOCSWINSZ failed: {}", errno());
-        }
+        self.resize(&size);
     }
 }
 
@@ -422,8 +689,51 @@ unsafe fn set_nonblocking(fd: c_int) {
     assert_eq!(res, 0);
 }
 
+/// Create a pipe, both ends are closed on exec
+fn pipe() -> io::Result<(File, File)> {
+    let mut fds: [c_int; 2] = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    for fd in &fds {
+        unsafe {
+            libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+    }
+
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+/// Duplicate a file descriptor, the duplicate is closed with the returned file
+fn dup(fd: c_int) -> io::Result<File> {
+    match unsafe { libc::dup(fd) } {
+        -1 => Err(io::Error::last_os_error()),
+        fd => Ok(unsafe { File::from_raw_fd(fd) }),
+    }
+}
+
 #[test]
 fn test_get_pw_entry() {
     let mut buf: [i8; 1024] = [0; 1024];
     let _pw = get_pw_entry(&mut buf);
 }
+
+#[test]
+fn sigchld_notifies_every_pty() {
+    fn notified(signals: &ChildSignals) -> bool {
+        let fd = signals.reader.as_raw_fd();
+        let mut fds = [libc::pollfd { fd, events: libc::POLLIN, revents: 0 }];
+        unsafe { libc::poll(fds.as_mut_ptr(), 1, 5000) == 1 }
+    }
+
+    let mut first = ChildSignals::new().unwrap();
+    let second = ChildSignals::new().unwrap();
+
+    Command::new("true").status().unwrap();
+
+    // Draining the notifications of one pty keeps those of the other
+    assert!(notified(&first));
+    first.drain();
+    assert!(notified(&second));
+}
@@ -14,62 +14,87 @@
 
 use std::io;
 use std::fs::OpenOptions;
-use std::os::raw::c_void;
 use std::os::windows::io::{FromRawHandle, IntoRawHandle};
 use std::os::windows::fs::OpenOptionsExt;
 use std::env;
 use std::cell::UnsafeCell;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use dunce::canonicalize;
 use mio;
 use mio::Evented;
 use mio_named_pipes::NamedPipe;
+use winapi::shared::minwindef::FALSE;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::processthreadsapi::{OpenProcess, TerminateProcess};
 use winapi::um::synchapi::WaitForSingleObject;
 use winapi::um::winbase::{WAIT_OBJECT_0, FILE_FLAG_OVERLAPPED};
+use winapi::um::winnt::{PROCESS_TERMINATE, SYNCHRONIZE};
 use winapi::shared::winerror::WAIT_TIMEOUT;
 use winpty::{ConfigFlags, MouseMode, SpawnConfig, SpawnFlags, Winpty};
 use winpty::Config as WinptyConfig;
 
 use config::{Config, Shell};
-use display::OnResize;
 use cli::Options;
-use tty::EventedReadWrite;
-use term::SizeInfo;
-
-/// Handle to the winpty agent process. Required so we know when it closes.
-static mut HANDLE: *mut c_void = 0usize as *mut c_void;
+use tty::{ChildEvent, EventedReadWrite, Process};
+use term::{OnResize, SizeInfo};
 
 /// How long the winpty agent should wait for any RPC request
 /// This is a placeholder value until we see how often long responses happen
 const AGENT_TIMEOUT: u32 = 10000;
 
-pub fn process_should_exit() -> bool {
-    unsafe {
-        match WaitForSingleObject(HANDLE, 0) {
-            // Process has exited
-            WAIT_OBJECT_0 => {
-                info!("wait_object_0");
-                true
-            }
-            // Reached timeout of 0, process has not exited
-            WAIT_TIMEOUT => false,
-            // Error checking process, winpty gave us a bad agent handle?
-            _ => {
-                info!("Bad exit: {}", ::std::io::Error::last_os_error());
-                true
-            }
-        }
-    }
-}
+/// Time the processes of the console get to exit after it was closed
+const CLOSE_TIMEOUT_MS: u64 = 1000;
+
+/// Most processes of the console which are terminated on close
+const MAX_CONSOLE_PROCESSES: usize = 64;
 
 pub struct Pty<'a, R: io::Read + Evented + Send, W: io::Write + Evented + Send> {
-    // TODO: Provide methods for accessing this safely
-    pub winpty: UnsafeCell<Winpty<'a>>,
+    winpty: UnsafeCell<Winpty<'a>>,
 
     conout: R,
     conin: W,
     read_token: mio::Token,
     write_token: mio::Token,
+
+    /// Dropped after `winpty`, once the console is closed
+    stragglers: Stragglers,
+}
+
+/// Processes of the console which are terminated when they outlive it
+///
+/// Freeing winpty closes the console, which asks the processes attached to it
+/// to exit. The ones which are still running after `CLOSE_TIMEOUT_MS` are
+/// terminated, if `terminal.kill_on_close` is set.
+struct Stragglers {
+    kill_on_close: bool,
+    pids: Vec<u32>,
+}
+
+impl Drop for Stragglers {
+    fn drop(&mut self) {
+        let deadline = Instant::now() + Duration::from_millis(CLOSE_TIMEOUT_MS);
+        for &pid in &self.pids {
+            let now = Instant::now();
+            let timeout = if now < deadline { deadline - now } else { Duration::from_secs(0) };
+            let timeout_ms = timeout.as_secs() as u32 * 1000 + timeout.subsec_millis();
+
+            unsafe {
+                // Processes which already exited can't be opened anymore
+                let handle = OpenProcess(PROCESS_TERMINATE | SYNCHRONIZE, FALSE, pid);
+                if handle.is_null() {
+                    continue;
+                }
+
+                if WaitForSingleObject(handle, timeout_ms) == WAIT_TIMEOUT {
+                    debug!("Terminating process {} of the console", pid);
+                    TerminateProcess(handle, 1);
+                }
+                CloseHandle(handle);
+            }
+        }
+    }
 }
 
 pub fn new<'a>(
@@ -90,14 +115,24 @@ pub fn new<'a>(
     let (conin, conout) = (winpty.conin_name(), winpty.conout_name());
 
     // Get process commandline
-    let default_shell = &Shell::new(env::var("COMSPEC").unwrap_or_else(|_| "cmd".into()));
-    let shell = config.shell().unwrap_or(default_shell);
+    let default_shell = default_shell();
+    let shell = config.shell().unwrap_or(&default_shell);
     let initial_command = options.command().unwrap_or(shell);
     let mut cmdline = initial_command.args().to_vec();
     cmdline.insert(0, initial_command.program().into());
 
+    // Keep the window open with an explanation when the shell doesn't exist,
+    // instead of closing it right away
+    if find_executable(initial_command.program()).is_none() {
+        let message = format!("Unable to find shell: {}", initial_command.program());
+        error!("{}", message);
+        cmdline = vec!["cmd.exe".into(), "/C".into(), format!("\"echo {} & pause\"", message)];
+    }
+
     // Warning, here be borrow hell
-    let cwd = options.working_dir.as_ref().map(|dir| canonicalize(dir).unwrap());
+    let cwd = options.working_dir.as_ref()
+        .map(|dir| canonicalize(dir).unwrap())
+        .or_else(|| env::var_os("USERPROFILE").map(PathBuf::from));
     let cwd = cwd.as_ref().map(|dir| dir.to_str().unwrap());
 
     // Spawn process
@@ -150,10 +185,6 @@ pub fn new<'a>(
 
     winpty.spawn(&spawnconfig).unwrap();
 
-    unsafe {
-        HANDLE = winpty.raw_handle();
-    }
-
     Pty {
         winpty: UnsafeCell::new(winpty),
         conout: conout_pipe,
@@ -161,6 +192,83 @@ pub fn new<'a>(
         // Placeholder tokens that are overwritten
         read_token: 0.into(),
         write_token: 0.into(),
+        stragglers: Stragglers {
+            kill_on_close: config.terminal().kill_on_close(),
+            pids: Vec::new(),
+        },
+    }
+}
+
+/// Pick the most capable shell which is installed
+///
+/// PowerShell Core is preferred over Windows PowerShell, which is preferred
+/// over cmd.
+fn default_shell() -> Shell<'static> {
+    let program = ["pwsh.exe", "powershell.exe"]
+        .iter()
+        .find(|program| find_executable(program).is_some())
+        .map(|program| String::from(*program))
+        .unwrap_or_else(|| env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".into()));
+
+    info!("Using {} as the default shell", program);
+
+    Shell::new(program)
+}
+
+/// Find a program like `CreateProcess` would
+///
+/// Programs without directory are searched in `%PATH%`, and the `.exe`
+/// extension is optional.
+fn find_executable(program: &str) -> Option<PathBuf> {
+    let candidates = |path: PathBuf| {
+        let exe = path.with_extension("exe");
+        if path.extension().is_none() { vec![path, exe] } else { vec![path] }
+    };
+
+    let path = Path::new(program);
+    if path.parent().map(|parent| parent != Path::new("")).unwrap_or(false) {
+        return candidates(path.to_owned()).into_iter().find(|path| path.is_file());
+    }
+
+    env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .flat_map(|dir| candidates(dir.join(program)))
+            .find(|path| path.is_file())
+    })
+}
+
+impl<'a, R: io::Read + Evented + Send, W: io::Write + Evented + Send> Pty<'a, R, W> {
+    /// Handle for looking up the foreground process from another thread
+    pub fn foreground(&self) -> Foreground {
+        Foreground
+    }
+}
+
+impl<'a, R: io::Read + Evented + Send, W: io::Write + Evented + Send> Drop for Pty<'a, R, W> {
+    fn drop(&mut self) {
+        // The console has to be asked for its processes before it's closed
+        if self.stragglers.kill_on_close {
+            match self.winpty.get_mut().console_process_list(MAX_CONSOLE_PROCESSES) {
+                Ok(pids) => self.stragglers.pids = pids.into_iter().map(|pid| pid as u32).collect(),
+                Err(err) => info!("Unable to list the processes of the console: {}", err),
+            }
+        }
+    }
+}
+
+/// Lookup of the process in the foreground of a pty
+///
+/// The console gives no access to its foreground process, so none is found.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Foreground;
+
+impl Foreground {
+    pub fn process(&self) -> Option<Process> {
+        None
+    }
+
+    pub fn has_job(&self) -> bool {
+        false
     }
 }
 
@@ -272,6 +380,35 @@ impl<'a> EventedReadWrite for Pty<'a, NamedPipe, NamedPipe> {
     fn write_token(&self) -> mio::Token {
         self.write_token
     }
+
+    #[inline]
+    fn child_event_token(&self) -> Option<mio::Token> {
+        // The agent process handle can't be registered with mio
+        None
+    }
+
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        // The winpty agent shuts down together with the child process, but it
+        // doesn't expose the exit code of the child itself.
+        let handle = self.winpty.get_mut().raw_handle();
+        match unsafe { WaitForSingleObject(handle, 0) } {
+            // Process has exited
+            WAIT_OBJECT_0 => Some(ChildEvent::Exited(0)),
+            // Reached timeout of 0, process has not exited
+            WAIT_TIMEOUT => None,
+            // Error checking process, winpty gave us a bad agent handle?
+            _ => {
+                info!("Bad exit: {}", io::Error::last_os_error());
+                Some(ChildEvent::Exited(1))
+            }
+        }
+    }
+}
+
+impl<'a> OnResize for Pty<'a, NamedPipe, NamedPipe> {
+    fn on_resize(&mut self, sizeinfo: &SizeInfo) {
+        self.winpty.get_mut().on_resize(sizeinfo);
+    }
 }
 
 impl<'a> OnResize for Winpty<'a> {
@@ -11,7 +11,12 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::borrow::Cow;
 use std::cmp;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[cfg(not(feature = "nightly"))]
 #[inline(always)]
@@ -44,6 +49,25 @@ pub fn limit<T: Ord>(value: T, min: T, max: T) -> T {
     cmp::min(cmp::max(value, min), max)
 }
 
+/// Quote `text` for the shell, so it's read as a single word
+///
+/// Text consisting only of characters without special meaning is left alone.
+pub fn shell_escape(text: &str) -> Cow<str> {
+    let is_plain = |c: char| {
+        c.is_alphanumeric() || "%+,-./:=@_".contains(c) || (cfg!(windows) && c == '\\')
+    };
+    if !text.is_empty() && text.chars().all(is_plain) {
+        return Cow::Borrowed(text);
+    }
+
+    if cfg!(windows) {
+        // Both cmd and PowerShell read doubled quotes as a literal quote
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Owned(format!("'{}'", text.replace('\'', "'\\''")))
+    }
+}
+
 /// Utilities for writing to the
 pub mod fmt {
     use std::fmt;
@@ -81,9 +105,63 @@ pub mod fmt {
     }
 }
 
+/// UTC date and time like `2018-10-14-143005`, for use in file names
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date of the days since the epoch, from Howard Hinnant's date
+    // algorithms, with eras of 400 years starting in March
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Create a new file named after the current time, like `alacritty-2018-10-14-143005.txt`
+///
+/// Files created within the same second get a counter appended instead of
+/// replacing each other.
+pub fn create_timestamped_file(directory: &Path, extension: &str) -> io::Result<(File, PathBuf)> {
+    let timestamp = utc_timestamp(SystemTime::now());
+
+    let mut attempt = 0;
+    loop {
+        let name = match attempt {
+            0 => format!("alacritty-{}.{}", timestamp, extension),
+            _ => format!("alacritty-{}-{}.{}", timestamp, attempt, extension),
+        };
+        let path = directory.join(name);
+
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::limit;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{limit, shell_escape, utc_timestamp};
 
     #[test]
     fn limit_works() {
@@ -91,4 +169,24 @@ mod tests {
         assert_eq!(10, limit(5, 10, 100));
         assert_eq!(100, limit(1000, 10, 100));
     }
+
+    #[test]
+    fn utc_timestamps() {
+        let at = |secs| utc_timestamp(UNIX_EPOCH + Duration::from_secs(secs));
+
+        assert_eq!(at(0), "1970-01-01-000000");
+        assert_eq!(at(951_782_399), "2000-02-28-235959");
+        assert_eq!(at(951_868_800), "2000-03-01-000000");
+        assert_eq!(at(1_539_527_405), "2018-10-14-143005");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn shell_escape_works() {
+        assert_eq!(shell_escape("/tmp/file.txt"), "/tmp/file.txt");
+        assert_eq!(shell_escape("/tmp/a file"), "'/tmp/a file'");
+        assert_eq!(shell_escape("it's"), "'it'\\''s'");
+        assert_eq!(shell_escape("$HOME"), "'$HOME'");
+        assert_eq!(shell_escape(""), "''");
+    }
 }
@@ -0,0 +1,68 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Information about the build, captured by the build script
+use std::fmt;
+
+/// Version of the crate
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Output of `git describe`, "unknown" when built without git repository
+pub const GIT_DESCRIBE: &str = env!("ALACRITTY_GIT_DESCRIBE");
+
+/// Hash of the commit Alacritty was built from
+pub const GIT_COMMIT: &str = env!("ALACRITTY_GIT_COMMIT");
+
+/// Date of the build in the `YYYY-MM-DD` format
+pub const BUILD_DATE: &str = env!("ALACRITTY_BUILD_DATE");
+
+/// Cargo profile used for the build
+pub const BUILD_PROFILE: &str = env!("ALACRITTY_BUILD_PROFILE");
+
+/// Comma separated list of enabled cargo features
+pub const FEATURES: &str = env!("ALACRITTY_FEATURES");
+
+/// Version encoded as a number, like `major * 10000 + minor * 100 + patch`
+///
+/// Reported in the secondary device attributes.
+pub fn number() -> u32 {
+    VERSION.split(|c| c == '.' || c == '-')
+        .take(3)
+        .map(|part| part.parse::<u32>().unwrap_or(0))
+        .fold(0, |number, part| number * 100 + part)
+}
+
+/// Detailed description of the build, as printed by `--version -v`
+pub struct Long;
+
+impl fmt::Display for Long {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "alacritty {} ({})", VERSION, GIT_DESCRIBE)?;
+        writeln!(f, "commit: {}", GIT_COMMIT)?;
+        writeln!(f, "build date: {}", BUILD_DATE)?;
+        writeln!(f, "profile: {}", BUILD_PROFILE)?;
+        write!(f, "features: {}", if FEATURES.is_empty() { "none" } else { FEATURES })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn version_number() {
+        let expected = env!("CARGO_PKG_VERSION_MAJOR").parse::<u32>().unwrap() * 10_000
+            + env!("CARGO_PKG_VERSION_MINOR").parse::<u32>().unwrap() * 100
+            + env!("CARGO_PKG_VERSION_PATCH").parse::<u32>().unwrap();
+        assert_eq!(super::number(), expected);
+    }
+}
@@ -12,47 +12,54 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::convert::From;
+use std::env;
 use std::fmt::{self, Display};
 use std::ops::Deref;
+#[cfg(
+    any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd")
+)]
+use std::time::{Duration, Instant};
 
-use gl;
 use glutin::GlContext;
 #[cfg(windows)]
 use winit::Icon;
-#[cfg(windows)]
-use image::ImageFormat;
+#[cfg(not(target_os = "macos"))]
+use image;
 use glutin::{
-    self, ContextBuilder, ControlFlow, CursorState, Event, EventsLoop,
-    MouseCursor as GlutinMouseCursor, WindowBuilder,
+    self, Api, ContextBuilder, ControlFlow, CursorState, Event, EventsLoop, GlProfile, GlRequest,
+    MouseCursor as GlutinMouseCursor, Robustness, WindowBuilder,
 };
 
 use MouseCursor;
 
-use cli::Options;
-use config::{Decorations, WindowConfig};
-use display::OnResize;
-use term::SizeInfo;
+use cli::{Options, DEFAULT_CLASS, DEFAULT_TITLE};
+use config::{Decorations, RendererPreference, WindowConfig};
+use renderer;
+use term::{OnResize, SizeInfo};
 use event_loop::WindowNotifier;
+use secure_input::SecureInput;
 
-#[cfg(windows)]
-static WINDOW_ICON: &'static [u8] = include_bytes!("../assets/windows/alacritty.ico");
+#[cfg(
+    any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd")
+)]
+use x11_dl::xlib;
 
-/// Default text for the window's title bar, if not overriden.
+/// Time the visibility of the window is cached for
 ///
-/// In X11, this the default value for the `WM_NAME` property.
-pub const DEFAULT_TITLE: &str = "Alacritty";
+/// Each query is a round trip to the X server. Exposing the window refreshes
+/// it right away.
+#[cfg(
+    any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd")
+)]
+const VISIBILITY_CACHE_MS: u64 = 500;
 
-/// Default text for general window class, X11 specific.
-///
-/// In X11, this is the default value for the `WM_CLASS` property. The
-/// second value of `WM_CLASS` is **never** changed to anything but
-/// the default value.
-///
-/// ```ignore
-/// $ xprop | grep WM_CLASS
-/// WM_CLASS(STRING) = "Alacritty", "Alacritty"
-/// ```
-pub const DEFAULT_CLASS: &str = "Alacritty";
+/// Logo scaled to the sizes of the window icon by the build script, as RGBA
+#[cfg(not(target_os = "macos"))]
+static WINDOW_ICONS: [(u32, &'static [u8]); 3] = [
+    (16, include_bytes!(concat!(env!("OUT_DIR"), "/window_icon_16.rgba"))),
+    (32, include_bytes!(concat!(env!("OUT_DIR"), "/window_icon_32.rgba"))),
+    (64, include_bytes!(concat!(env!("OUT_DIR"), "/window_icon_64.rgba"))),
+];
 
 /// Window errors
 #[derive(Debug)]
@@ -75,8 +82,50 @@ pub struct Window {
     window: glutin::GlWindow,
     mouse_visible: bool,
 
+    /// Title requested by the terminal, without the secure input indicator
+    title: String,
+
+    /// Class of the window, for creating it again with a new context
+    class: String,
+
+    /// Keeps secure keyboard entry enabled while set
+    secure_input: Option<SecureInput>,
+
+    /// Snap the window size to the cell grid
+    resize_increments: bool,
+
+    /// Cell and padding size of the last resize increments
+    increments: Option<(u32, u32, u32, u32)>,
+
+    /// Whether the window covers the screen
+    #[cfg(not(target_os = "macos"))]
+    fullscreen: bool,
+
+    /// Windowed state to restore when leaving fullscreen
+    #[cfg(target_os = "macos")]
+    fullscreen: macos::Fullscreen,
+
     /// Whether or not the window is the focused window.
     pub is_focused: bool,
+
+    /// Opacity applied by the compositor
+    opacity: f32,
+
+    /// The platform's lack of window opacity was logged
+    #[cfg(not(target_os = "macos"))]
+    opacity_unsupported_logged: bool,
+
+    /// Xlib for querying the window's state, unless running on Wayland
+    #[cfg(
+        any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd")
+    )]
+    xlib: Option<xlib::Xlib>,
+
+    /// Result of the last visibility query and the time it was made at
+    #[cfg(
+        any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd")
+    )]
+    visibility: Option<(bool, Instant)>,
 }
 
 /// Threadsafe APIs for the window
@@ -171,6 +220,16 @@ impl<T: Display> Display for Points<T> {
     }
 }
 
+impl Error {
+    /// The GL context lost its objects, like after a reset of the GPU
+    pub fn is_context_lost(&self) -> bool {
+        match *self {
+            Error::Context(glutin::ContextError::ContextLost) => true,
+            _ => false,
+        }
+    }
+}
+
 impl ::std::error::Error for Error {
     fn cause(&self) -> Option<&::std::error::Error> {
         match *self {
@@ -208,29 +267,183 @@ impl From<glutin::ContextError> for Error {
     }
 }
 
+/// OpenGL versions which are requested for the context, in order
+fn gl_requests(preference: RendererPreference) -> &'static [(Api, (u8, u8))] {
+    match preference {
+        RendererPreference::Auto => {
+            &[(Api::OpenGl, (3, 3)), (Api::OpenGlEs, (3, 0)), (Api::OpenGlEs, (2, 0))]
+        },
+        RendererPreference::OpenGl => &[(Api::OpenGl, (3, 3))],
+        RendererPreference::OpenGlEs | RendererPreference::OpenGlEsNonInstanced => {
+            &[(Api::OpenGlEs, (3, 0)), (Api::OpenGlEs, (2, 0))]
+        },
+    }
+}
+
 fn create_gl_window(
     window: WindowBuilder,
     event_loop: &EventsLoop,
-    srgb: bool,
+    preference: RendererPreference,
 ) -> ::std::result::Result<glutin::GlWindow, glutin::CreationError> {
-    let context = ContextBuilder::new().with_srgb(srgb).with_vsync(true);
-    ::glutin::GlWindow::new(window, context, event_loop)
+    let mut error = None;
+
+    for &(api, version) in gl_requests(preference) {
+        for &srgb in &[false, true] {
+            // Robust contexts report GPU resets, so the renderer can be rebuilt
+            let mut context = ContextBuilder::new()
+                .with_gl(GlRequest::Specific(api, version))
+                .with_gl_robustness(Robustness::TryRobustLoseContextOnReset)
+                .with_srgb(srgb)
+                .with_vsync(true);
+            if api == Api::OpenGl {
+                context = context.with_gl_profile(GlProfile::Core);
+            }
+
+            match ::glutin::GlWindow::new(window.clone(), context, event_loop) {
+                Ok(window) => return Ok(window),
+                Err(err) => {
+                    let (major, minor) = version;
+                    debug!("Creating {:?} {}.{} context failed: {}", api, major, minor, err);
+                    error = Some(err);
+                },
+            }
+        }
+    }
+
+    Err(error.expect("no OpenGL version requested"))
+}
+
+/// Image of the window icon, as RGBA pixels
+#[cfg(not(target_os = "macos"))]
+struct IconImage {
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// Images of the window icon, from the smallest to the largest
+///
+/// The image of `window.icon` replaces the embedded logo. macOS uses the icon
+/// of the application bundle instead.
+#[cfg(not(target_os = "macos"))]
+fn window_icon(window_config: &WindowConfig) -> Vec<IconImage> {
+    if let Some(path) = window_config.icon() {
+        match image::open(path) {
+            Ok(icon) => {
+                let icon = icon.to_rgba();
+                let (width, height) = icon.dimensions();
+                return vec![IconImage { rgba: icon.into_raw(), width, height }];
+            },
+            Err(err) => warn!("Unable to load window icon {}: {}", path.display(), err),
+        }
+    }
+
+    WINDOW_ICONS
+        .iter()
+        .map(|&(size, rgba)| IconImage { rgba: rgba.to_vec(), width: size, height: size })
+        .collect()
+}
+
+/// Value of the X11 `_NET_WM_ICON` property
+///
+/// Every image is its width and height followed by its pixels in ARGB, each of
+/// them a `CARDINAL`, which Xlib takes as `long`.
+#[cfg(
+    any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd")
+)]
+fn net_wm_icon(icons: &[IconImage]) -> Vec<::std::os::raw::c_ulong> {
+    let mut data = Vec::new();
+    for icon in icons {
+        data.push(icon.width.into());
+        data.push(icon.height.into());
+        data.extend(icon.rgba.chunks(4).map(|pixel| {
+            let argb = (u32::from(pixel[3]) << 24)
+                | (u32::from(pixel[0]) << 16)
+                | (u32::from(pixel[1]) << 8)
+                | u32::from(pixel[2]);
+            ::std::os::raw::c_ulong::from(argb)
+        }));
+    }
+    data
+}
+
+/// Variable with the ID of the startup notification on X11
+const STARTUP_ID_ENV: &str = "DESKTOP_STARTUP_ID";
+
+/// Variable with the token of the xdg-activation protocol on Wayland
+const ACTIVATION_TOKEN_ENV: &str = "XDG_ACTIVATION_TOKEN";
+
+/// Take the startup notification ID of the launcher from the environment
+///
+/// The variables are removed, so the shell and the programs started from it
+/// don't complete the notification of Alacritty instead of their own.
+fn take_startup_id() -> Option<String> {
+    let id = env::var(STARTUP_ID_ENV).ok().filter(|id| !id.is_empty());
+    env::remove_var(STARTUP_ID_ENV);
+
+    // Winit doesn't give access to the xdg-activation protocol
+    if env::var_os(ACTIVATION_TOKEN_ENV).is_some() {
+        debug!("Ignoring the xdg-activation token, Wayland activation isn't supported");
+        env::remove_var(ACTIVATION_TOKEN_ENV);
+    }
+
+    id
+}
+
+/// Message of the startup notification protocol reporting the launch as done
+///
+/// Values with spaces are quoted, quotes and backslashes are escaped.
+fn startup_remove_message(id: &str) -> String {
+    let mut value = String::with_capacity(id.len());
+    for c in id.chars() {
+        if c == '"' || c == '\\' {
+            value.push('\\');
+        }
+        value.push(c);
+    }
+
+    if value.contains(' ') {
+        format!("remove: ID=\"{}\"", value)
+    } else {
+        format!("remove: ID={}", value)
+    }
+}
+
+/// Split a message into the data of successive X11 client messages
+///
+/// The message is terminated by a null byte, the padding of the last chunk is
+/// zeroed as well.
+fn startup_message_chunks(message: &str) -> Vec<[u8; 20]> {
+    let mut bytes = message.as_bytes().to_vec();
+    bytes.push(0);
+
+    bytes
+        .chunks(20)
+        .map(|chunk| {
+            let mut data = [0; 20];
+            data[..chunk.len()].copy_from_slice(chunk);
+            data
+        })
+        .collect()
 }
 
 impl Window {
     /// Create a new window
     ///
     /// This creates a window and fully initializes a window.
-    pub fn new(options: &Options, window_config: &WindowConfig) -> Result<Window> {
+    pub fn new(
+        options: &Options,
+        window_config: &WindowConfig,
+        renderer_preference: RendererPreference,
+    ) -> Result<Window> {
         let event_loop = EventsLoop::new();
+        let startup_id = take_startup_id();
 
         let title = options.title.as_ref().map_or(DEFAULT_TITLE, |t| t);
-        let class = options.class.as_ref().map_or(DEFAULT_TITLE, |c| c);
+        let class = options.class.as_ref().map_or(DEFAULT_CLASS, |c| c);
         let window_builder = Window::get_platform_window(title, window_config);
         let window_builder = Window::platform_builder_ext(window_builder, &class);
-        let window = create_gl_window(window_builder.clone(), &event_loop, false)
-            .or_else(|_| create_gl_window(window_builder, &event_loop, true))?;
-        window.show();
+        let window = create_gl_window(window_builder, &event_loop, renderer_preference)?;
 
         // Text cursor
         window.set_cursor(GlutinMouseCursor::Text);
@@ -241,20 +454,140 @@ impl Window {
         }
 
         // Set OpenGL symbol loader. This call MUST be after window.make_current on windows.
-        gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+        renderer::load_gl(|symbol| window.get_proc_address(symbol) as *const _);
 
         let window = Window {
             event_loop,
             window,
             mouse_visible: true,
+            title: title.to_owned(),
+            class: class.to_owned(),
+            secure_input: None,
+            resize_increments: window_config.resize_increments(),
+            increments: None,
+            fullscreen: Default::default(),
             is_focused: false,
+            opacity: 1.0,
+            #[cfg(not(target_os = "macos"))]
+            opacity_unsupported_logged: false,
+            #[cfg(
+                any(
+                    target_os = "linux",
+                    target_os = "freebsd",
+                    target_os = "dragonfly",
+                    target_os = "openbsd"
+                )
+            )]
+            xlib: Window::open_xlib(&window),
+            #[cfg(
+                any(
+                    target_os = "linux",
+                    target_os = "freebsd",
+                    target_os = "dragonfly",
+                    target_os = "openbsd"
+                )
+            )]
+            visibility: None,
         };
 
         window.run_os_extensions();
 
+        #[cfg(
+            any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd"
+            )
+        )]
+        window.set_x11_icon(&window_icon(window_config));
+
+        // The launcher matches the window by its startup ID when it's mapped,
+        // and stops its feedback once the window is shown
+        if let Some(ref startup_id) = startup_id {
+            window.set_startup_id(startup_id);
+        }
+
+        window.window.show();
+
+        if let Some(startup_id) = startup_id {
+            window.complete_startup_notification(&startup_id);
+        }
+
         Ok(window)
     }
 
+    /// Replace the window and its OpenGL context after the context was lost
+    ///
+    /// A context which was reset can't be used anymore, and it's only
+    /// destroyed along with its window. A new window is created in place of
+    /// the old one, with the same size, title and fullscreen state. Objects of
+    /// the old context must not be deleted afterwards, their names might be
+    /// taken by the new context.
+    pub fn recreate_context(
+        &mut self,
+        window_config: &WindowConfig,
+        renderer_preference: RendererPreference,
+    ) -> Result<()> {
+        let window_builder = Window::get_platform_window(&self.title, window_config);
+        let mut window_builder = Window::platform_builder_ext(window_builder, &self.class);
+        if let Some((width, height)) = self.window.get_inner_size() {
+            window_builder = window_builder.with_dimensions(width, height);
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            if self.fullscreen {
+                window_builder =
+                    window_builder.with_fullscreen(Some(self.window.get_current_monitor()));
+            }
+        }
+
+        let window = create_gl_window(window_builder, &self.event_loop, renderer_preference)?;
+        window.set_cursor(GlutinMouseCursor::Text);
+        unsafe {
+            window.make_current()?;
+        }
+        renderer::load_gl(|symbol| window.get_proc_address(symbol) as *const _);
+
+        // The old window and its context are destroyed here
+        self.window = window;
+        self.increments = None;
+        self.opacity = 1.0;
+        #[cfg(
+            any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd"
+            )
+        )]
+        {
+            self.xlib = Window::open_xlib(&self.window);
+            self.visibility = None;
+        }
+
+        self.run_os_extensions();
+
+        #[cfg(
+            any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd"
+            )
+        )]
+        self.set_x11_icon(&window_icon(window_config));
+
+        self.update_title();
+        if !self.mouse_visible {
+            self.mouse_visible = true;
+            self.set_mouse_visible(false);
+        }
+        self.window.show();
+
+        Ok(())
+    }
+
     /// Get some properties about the device
     ///
     /// Some window properties are provided since subsystems like font
@@ -265,6 +598,15 @@ impl Window {
         }
     }
 
+    /// Size of the monitor the window is on
+    pub fn screen_size(&self) -> Size<Pixels<u32>> {
+        let (width, height) = self.window.get_current_monitor().get_dimensions();
+        Size {
+            width: Pixels(width),
+            height: Pixels(height),
+        }
+    }
+
     pub fn inner_size_pixels(&self) -> Option<Size<Pixels<u32>>> {
         self.window.get_inner_size().map(|(w, h)| Size {
             width: Pixels(w),
@@ -309,11 +651,33 @@ impl Window {
 
     /// Set the window title
     #[inline]
-    pub fn set_title(&self, _title: &str) {
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_owned();
+        self.update_title();
+    }
+
+    /// Toggle secure keyboard entry
+    ///
+    /// A lock is appended to the title while secure keyboard entry is enabled.
+    pub fn toggle_secure_input(&mut self) {
+        self.secure_input = match self.secure_input.take() {
+            Some(_) => None,
+            None => SecureInput::enable(),
+        };
+        self.update_title();
+    }
+
+    fn update_title(&self) {
         // Because winpty doesn't know anything about OSC escapes this gets set to an empty
         // string on windows
         #[cfg(not(windows))]
-        self.window.set_title(_title);
+        {
+            if self.secure_input.is_some() {
+                self.window.set_title(&format!("{} \u{1f512}", self.title));
+            } else {
+                self.window.set_title(&self.title);
+            }
+        }
     }
 
     #[inline]
@@ -321,6 +685,7 @@ impl Window {
         self.window.set_cursor(match cursor {
             MouseCursor::Arrow => GlutinMouseCursor::Arrow,
             MouseCursor::Text => GlutinMouseCursor::Text,
+            MouseCursor::Hand => GlutinMouseCursor::Hand,
         });
     }
 
@@ -381,7 +746,16 @@ impl Window {
 
     #[cfg(windows)]
     pub fn get_platform_window(title: &str, window_config: &WindowConfig) -> WindowBuilder {
-        let icon = Icon::from_bytes_with_format(WINDOW_ICON, ImageFormat::ICO).unwrap();
+        // Windows scales the largest image for the taskbar
+        let icon = window_icon(window_config).pop().and_then(|icon| {
+            match Icon::from_rgba(icon.rgba, icon.width, icon.height) {
+                Ok(icon) => Some(icon),
+                Err(err) => {
+                    warn!("Invalid window icon: {:?}", err);
+                    None
+                },
+            }
+        });
 
         let decorations = match window_config.decorations() {
             Decorations::None => false,
@@ -393,7 +767,7 @@ impl Window {
             .with_visibility(cfg!(windows))
             .with_decorations(decorations)
             .with_transparency(true)
-            .with_window_icon(Some(icon))
+            .with_window_icon(icon)
     }
 
     #[cfg(target_os = "macos")]
@@ -434,6 +808,456 @@ impl Window {
         self.window.set_urgent(is_urgent);
     }
 
+    /// Flash the taskbar button until the window is focused
+    #[cfg(windows)]
+    pub fn set_urgent(&self, is_urgent: bool) {
+        use std::mem;
+
+        use glutin::os::windows::WindowExt;
+        use winapi::um::winuser::{FlashWindowEx, FLASHWINFO, FLASHW_STOP, FLASHW_TIMERNOFG, FLASHW_TRAY};
+
+        let mut info = FLASHWINFO {
+            cbSize: mem::size_of::<FLASHWINFO>() as u32,
+            hwnd: self.window.get_hwnd() as _,
+            dwFlags: if is_urgent { FLASHW_TRAY | FLASHW_TIMERNOFG } else { FLASHW_STOP },
+            uCount: 0,
+            dwTimeout: 0,
+        };
+
+        unsafe {
+            FlashWindowEx(&mut info);
+        }
+    }
+
+    /// Bounce the dock icon once
+    ///
+    /// macOS cancels the request by itself once the application is activated.
+    #[cfg(target_os = "macos")]
+    pub fn set_urgent(&self, is_urgent: bool) {
+        use objc::runtime::{Class, Object};
+
+        // `NSInformationalRequest`, `NSCriticalRequest` would keep bouncing
+        const INFORMATIONAL_REQUEST: isize = 10;
+
+        if !is_urgent {
+            return;
+        }
+
+        unsafe {
+            let class = Class::get("NSApplication").unwrap();
+            let app: *mut Object = msg_send![class, sharedApplication];
+            let _: isize = msg_send![app, requestUserAttention: INFORMATIONAL_REQUEST];
+        }
+    }
+
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    fn open_xlib(window: &glutin::GlWindow) -> Option<xlib::Xlib> {
+        use glutin::os::unix::WindowExt;
+
+        window.get_xlib_display()?;
+        match xlib::Xlib::open() {
+            Ok(xlib) => Some(xlib),
+            Err(err) => {
+                warn!("Unable to query the window state: {}", err);
+                None
+            },
+        }
+    }
+
+    /// Whether the window is mapped
+    ///
+    /// Windows covered by other windows count as visible, winit doesn't ask
+    /// for visibility notifications. Wayland gives no way to tell without frame
+    /// callbacks, which aren't exposed by winit either.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    pub fn is_visible(&mut self) -> bool {
+        let cache = Duration::from_millis(VISIBILITY_CACHE_MS);
+        match self.visibility {
+            Some((visible, queried_at)) if queried_at.elapsed() < cache => visible,
+            _ => {
+                let visible = self.query_visibility();
+                self.visibility = Some((visible, Instant::now()));
+                visible
+            },
+        }
+    }
+
+    /// Query the visibility again with the next check
+    ///
+    /// Called once the window was exposed or changed its state.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    pub fn refresh_visibility(&mut self) {
+        self.visibility = None;
+    }
+
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    fn query_visibility(&self) -> bool {
+        use std::mem;
+
+        use glutin::os::unix::WindowExt;
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+        let (xlib, xlib_window, xlib_display) = match (&self.xlib, xlib_window, xlib_display) {
+            (&Some(ref xlib), Some(xlib_window), Some(xlib_display)) => {
+                (xlib, xlib_window, xlib_display)
+            },
+            _ => return true,
+        };
+
+        unsafe {
+            let mut attributes: xlib::XWindowAttributes = mem::zeroed();
+            if (xlib.XGetWindowAttributes)(xlib_display as _, xlib_window as _, &mut attributes) == 0 {
+                return true;
+            }
+            attributes.map_state == xlib::IsViewable
+        }
+    }
+
+    /// Whether any part of the window is on screen
+    ///
+    /// Minimized windows and windows on other spaces are occluded as well.
+    #[cfg(target_os = "macos")]
+    pub fn is_visible(&self) -> bool {
+        use glutin::os::macos::WindowExt;
+        use objc::runtime::Object;
+
+        // `NSWindowOcclusionStateVisible`
+        const OCCLUSION_STATE_VISIBLE: usize = 1 << 1;
+
+        let nswindow = self.window.get_nswindow() as *mut Object;
+        unsafe {
+            let state: usize = msg_send![nswindow, occlusionState];
+            state & OCCLUSION_STATE_VISIBLE != 0
+        }
+    }
+
+    /// Whether the window isn't minimized
+    #[cfg(windows)]
+    pub fn is_visible(&self) -> bool {
+        use glutin::os::windows::WindowExt;
+        use winapi::um::winuser::IsIconic;
+
+        unsafe { IsIconic(self.window.get_hwnd() as _) == 0 }
+    }
+
+    /// The visibility is queried on every check, there's nothing to refresh
+    #[cfg(any(target_os = "macos", windows))]
+    pub fn refresh_visibility(&mut self) {
+    }
+
+    /// Play the bell of the X server
+    ///
+    /// Wayland has no bell, nothing is played there.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    pub fn ring_bell(&self) {
+        use glutin::os::unix::WindowExt;
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+        if let (&Some(ref xlib), Some(xlib_window), Some(xlib_display)) =
+            (&self.xlib, xlib_window, xlib_display)
+        {
+            unsafe {
+                // The volume is relative to the one configured for the keyboard
+                (xlib.XkbBell)(xlib_display as _, xlib_window as _, 0, 0);
+                (xlib.XFlush)(xlib_display as _);
+            }
+        }
+    }
+
+    /// Play the alert sound of the system
+    #[cfg(target_os = "macos")]
+    pub fn ring_bell(&self) {
+        #[link(name = "AppKit", kind = "framework")]
+        extern "C" {
+            fn NSBeep();
+        }
+
+        unsafe { NSBeep() }
+    }
+
+    /// Play the default sound of the system
+    #[cfg(windows)]
+    pub fn ring_bell(&self) {
+        use winapi::um::winuser::{MessageBeep, MB_OK};
+
+        unsafe {
+            MessageBeep(MB_OK);
+        }
+    }
+
+    /// Make the whole window translucent, text included
+    ///
+    /// The compositor blends the window with what's below it, the renderer
+    /// isn't involved.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        if (opacity - self.opacity).abs() < ::std::f32::EPSILON {
+            return;
+        }
+        self.opacity = opacity;
+
+        self.set_platform_opacity(opacity);
+    }
+
+    /// Set the `_NET_WM_WINDOW_OPACITY` of the window
+    ///
+    /// Wayland has no protocol for it, the opacity is ignored there.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    fn set_platform_opacity(&mut self, opacity: f32) {
+        use std::os::raw::c_ulong;
+
+        use glutin::os::unix::WindowExt;
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+        let (xlib, xlib_window, xlib_display) = match (&self.xlib, xlib_window, xlib_display) {
+            (&Some(ref xlib), Some(xlib_window), Some(xlib_display)) => {
+                (xlib, xlib_window as xlib::Window, xlib_display as *mut xlib::Display)
+            },
+            _ => {
+                if !self.opacity_unsupported_logged {
+                    self.opacity_unsupported_logged = true;
+                    warn!("Window opacity is only supported on X11, ignoring it");
+                }
+                return;
+            },
+        };
+
+        unsafe {
+            let atom = (xlib.XInternAtom)(
+                xlib_display,
+                b"_NET_WM_WINDOW_OPACITY\0".as_ptr() as *const _,
+                0,
+            );
+
+            // Opaque windows go without the property, like most clients
+            if opacity >= 1.0 {
+                (xlib.XDeleteProperty)(xlib_display, xlib_window, atom);
+            } else {
+                // Format 32 properties are passed as longs
+                let value = (f64::from(opacity) * f64::from(u32::max_value())) as c_ulong;
+                (xlib.XChangeProperty)(
+                    xlib_display,
+                    xlib_window,
+                    atom,
+                    xlib::XA_CARDINAL,
+                    32,
+                    xlib::PropModeReplace,
+                    &value as *const c_ulong as *const u8,
+                    1,
+                );
+            }
+            (xlib.XFlush)(xlib_display);
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_platform_opacity(&mut self, opacity: f32) {
+        use glutin::os::macos::WindowExt;
+        use objc::runtime::Object;
+
+        let nswindow = self.window.get_nswindow() as *mut Object;
+        unsafe {
+            let _: () = msg_send![nswindow, setAlphaValue: f64::from(opacity)];
+        }
+    }
+
+    /// Layered windows don't work with the OpenGL context, nothing is done
+    #[cfg(windows)]
+    fn set_platform_opacity(&mut self, _opacity: f32) {
+        if !self.opacity_unsupported_logged {
+            self.opacity_unsupported_logged = true;
+            warn!("Window opacity isn't supported on Windows, ignoring it");
+        }
+    }
+
+    /// Set the `_NET_WM_ICON` of the window
+    ///
+    /// Winit only sets a single image, this includes all of them. Wayland has
+    /// no window icons, the compositor picks the icon of the desktop file.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    fn set_x11_icon(&self, icons: &[IconImage]) {
+        use glutin::os::unix::WindowExt;
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+        let (xlib, xlib_window, xlib_display) = match (&self.xlib, xlib_window, xlib_display) {
+            (&Some(ref xlib), Some(xlib_window), Some(xlib_display)) => {
+                (xlib, xlib_window as xlib::Window, xlib_display as *mut xlib::Display)
+            },
+            _ => return,
+        };
+
+        let data = net_wm_icon(icons);
+        unsafe {
+            let atom = (xlib.XInternAtom)(xlib_display, b"_NET_WM_ICON\0".as_ptr() as *const _, 0);
+            (xlib.XChangeProperty)(
+                xlib_display,
+                xlib_window,
+                atom,
+                xlib::XA_CARDINAL,
+                32,
+                xlib::PropModeReplace,
+                data.as_ptr() as *const u8,
+                data.len() as i32,
+            );
+            (xlib.XFlush)(xlib_display);
+        }
+    }
+
+    /// Attach the startup notification ID taken from the launcher to the window
+    ///
+    /// The ID is set as `_NET_STARTUP_ID`, which has to happen before the
+    /// window is mapped. There's no startup notification outside of X11.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    fn set_startup_id(&self, id: &str) {
+        use glutin::os::unix::WindowExt;
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+        let (xlib, xlib_window, xlib_display) = match (&self.xlib, xlib_window, xlib_display) {
+            (&Some(ref xlib), Some(xlib_window), Some(xlib_display)) => {
+                (xlib, xlib_window as xlib::Window, xlib_display as *mut xlib::Display)
+            },
+            _ => return,
+        };
+
+        unsafe {
+            let atom = |name: &[u8]| (xlib.XInternAtom)(xlib_display, name.as_ptr() as *const _, 0);
+
+            (xlib.XChangeProperty)(
+                xlib_display,
+                xlib_window,
+                atom(b"_NET_STARTUP_ID\0"),
+                atom(b"UTF8_STRING\0"),
+                8,
+                xlib::PropModeReplace,
+                id.as_ptr(),
+                id.len() as i32,
+            );
+            (xlib.XFlush)(xlib_display);
+        }
+    }
+
+    #[cfg(
+        not(
+            any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd"
+            )
+        )
+    )]
+    fn set_startup_id(&self, _id: &str) {}
+
+    /// Complete the startup notification once the window is shown
+    ///
+    /// The `remove` message is sent to the root window.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    fn complete_startup_notification(&self, id: &str) {
+        use std::mem;
+
+        use glutin::os::unix::WindowExt;
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+        let (xlib, xlib_window, xlib_display) = match (&self.xlib, xlib_window, xlib_display) {
+            (&Some(ref xlib), Some(xlib_window), Some(xlib_display)) => {
+                (xlib, xlib_window as xlib::Window, xlib_display as *mut xlib::Display)
+            },
+            _ => return,
+        };
+
+        unsafe {
+            let atom = |name: &[u8]| (xlib.XInternAtom)(xlib_display, name.as_ptr() as *const _, 0);
+
+            let root = (xlib.XDefaultRootWindow)(xlib_display);
+            let begin = atom(b"_NET_STARTUP_INFO_BEGIN\0");
+            let info = atom(b"_NET_STARTUP_INFO\0");
+            for (i, chunk) in startup_message_chunks(&startup_remove_message(id)).iter().enumerate() {
+                let mut message: xlib::XClientMessageEvent = mem::zeroed();
+                message.type_ = xlib::ClientMessage;
+                message.display = xlib_display;
+                message.window = xlib_window;
+                message.message_type = if i == 0 { begin } else { info };
+                message.format = 8;
+                for (index, &byte) in chunk.iter().enumerate() {
+                    message.data.set_byte(index, byte as _);
+                }
+
+                let mut event = xlib::XEvent::from(message);
+                (xlib.XSendEvent)(xlib_display, root, 0, xlib::PropertyChangeMask, &mut event);
+            }
+
+            (xlib.XFlush)(xlib_display);
+        }
+    }
+
     #[cfg(
         not(
             any(
@@ -444,7 +1268,7 @@ impl Window {
             )
         )
     )]
-    pub fn set_urgent(&self, _is_urgent: bool) {}
+    fn complete_startup_notification(&self, _id: &str) {}
 
     pub fn set_ime_spot(&self, _x: i32, _y: i32) {
         // This is not implemented on windows as of winit 0.15.1
@@ -467,6 +1291,184 @@ impl Window {
         None
     }
 
+    /// Make the window manager resize the window in whole cells
+    ///
+    /// Does nothing unless enabled with `window.resize_increments`.
+    pub fn set_resize_increments(&mut self, size: &SizeInfo) {
+        let increments = (
+            size.cell_width as u32,
+            size.cell_height as u32,
+            (2. * size.padding_x) as u32,
+            (2. * size.padding_y) as u32,
+        );
+        if !self.resize_increments || self.increments == Some(increments) {
+            return;
+        }
+        self.increments = Some(increments);
+
+        self.set_platform_resize_increments(increments);
+    }
+
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    fn set_platform_resize_increments(&self, increments: (u32, u32, u32, u32)) {
+        use glutin::os::unix::WindowExt;
+        use x11_dl::xlib::{self, PBaseSize, PResizeInc};
+
+        // Wayland has no size hints
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+        let (xlib_window, xlib_display) = match (xlib_window, xlib_display) {
+            (Some(xlib_window), Some(xlib_display)) => (xlib_window, xlib_display),
+            _ => return,
+        };
+
+        let xlib = match xlib::Xlib::open() {
+            Ok(xlib) => xlib,
+            Err(err) => {
+                warn!("Unable to set resize increments: {}", err);
+                return;
+            },
+        };
+
+        let (width_inc, height_inc, base_width, base_height) = increments;
+        unsafe {
+            let hints = (xlib.XAllocSizeHints)();
+            if hints.is_null() {
+                return;
+            }
+
+            // Keep the minimum and maximum size hints set by winit
+            let mut supplied = 0;
+            (xlib.XGetWMNormalHints)(xlib_display as _, xlib_window as _, hints, &mut supplied);
+
+            (*hints).flags |= PResizeInc | PBaseSize;
+            (*hints).width_inc = width_inc as _;
+            (*hints).height_inc = height_inc as _;
+            (*hints).base_width = base_width as _;
+            (*hints).base_height = base_height as _;
+
+            (xlib.XSetWMNormalHints)(xlib_display as _, xlib_window as _, hints);
+            (xlib.XFree)(hints as *mut _);
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_platform_resize_increments(&self, increments: (u32, u32, u32, u32)) {
+        use glutin::os::macos::WindowExt;
+        use objc::runtime::Object;
+
+        #[repr(C)]
+        struct NSSize {
+            width: f64,
+            height: f64,
+        }
+
+        // Increments are in points, macOS has no base size for them
+        let scale = f64::from(self.hidpi_factor());
+        let size = NSSize {
+            width: f64::from(increments.0) / scale,
+            height: f64::from(increments.1) / scale,
+        };
+
+        let nswindow = self.window.get_nswindow() as *mut Object;
+        unsafe {
+            let _: () = msg_send![nswindow, setContentResizeIncrements: size];
+        }
+    }
+
+    #[cfg(windows)]
+    fn set_platform_resize_increments(&self, _increments: (u32, u32, u32, u32)) {}
+
+    /// Toggle fullscreen
+    ///
+    /// On macOS this is the native fullscreen, which moves the window to a
+    /// space of its own with an animation.
+    #[cfg(not(target_os = "macos"))]
+    pub fn toggle_fullscreen(&mut self) {
+        self.fullscreen = !self.fullscreen;
+
+        let monitor = if self.fullscreen {
+            Some(self.window.get_current_monitor())
+        } else {
+            None
+        };
+        self.window.set_fullscreen(monitor);
+    }
+
+    /// Toggle fullscreen
+    ///
+    /// On macOS this is the native fullscreen, which moves the window to a
+    /// space of its own with an animation.
+    #[cfg(target_os = "macos")]
+    pub fn toggle_fullscreen(&mut self) {
+        use glutin::os::macos::WindowExt;
+
+        let nswindow = self.window.get_nswindow() as *mut _;
+        unsafe { self.fullscreen.toggle_native(nswindow) };
+    }
+
+    /// Toggle fullscreen within the current space on macOS
+    ///
+    /// Other platforms have no spaces, so this is the same as
+    /// `toggle_fullscreen` there.
+    #[cfg(not(target_os = "macos"))]
+    pub fn toggle_simple_fullscreen(&mut self) {
+        self.toggle_fullscreen();
+    }
+
+    /// Toggle fullscreen within the current space on macOS
+    ///
+    /// Other platforms have no spaces, so this is the same as
+    /// `toggle_fullscreen` there.
+    #[cfg(target_os = "macos")]
+    pub fn toggle_simple_fullscreen(&mut self) {
+        use glutin::os::macos::WindowExt;
+
+        let nswindow = self.window.get_nswindow() as *mut _;
+        unsafe { self.fullscreen.toggle_simple(nswindow) };
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn is_fullscreen(&self) -> bool {
+        use glutin::os::macos::WindowExt;
+
+        let nswindow = self.window.get_nswindow() as *mut _;
+        unsafe { self.fullscreen.is_active(nswindow) }
+    }
+
+    /// Whether the window is still animating into or out of native fullscreen
+    ///
+    /// macOS resizes the window several times during the animation, resizing
+    /// the grid should wait until the window has its final size.
+    #[cfg(not(target_os = "macos"))]
+    pub fn in_fullscreen_transition(&mut self) -> bool {
+        false
+    }
+
+    /// Whether the window is still animating into or out of native fullscreen
+    ///
+    /// macOS resizes the window several times during the animation, resizing
+    /// the grid should wait until the window has its final size.
+    #[cfg(target_os = "macos")]
+    pub fn in_fullscreen_transition(&mut self) -> bool {
+        use glutin::os::macos::WindowExt;
+
+        let nswindow = self.window.get_nswindow() as *mut _;
+        unsafe { self.fullscreen.in_transition(nswindow) }
+    }
+
     /// Hide the window
     pub fn hide(&self) {
         self.window.hide();
@@ -477,6 +1479,162 @@ impl Window {
     }
 }
 
+/// Fullscreen handling of macOS
+///
+/// The native fullscreen is tracked by the window itself, since it can also be
+/// toggled with the green title bar button. Simple fullscreen hides the dock
+/// and the menu bar and makes a borderless window cover the screen instead.
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ptr;
+    use std::time::{Duration, Instant};
+
+    use objc::runtime::{Class, Object, YES};
+
+    /// `NSWindowStyleMaskFullScreen`
+    const FULLSCREEN_MASK: usize = 1 << 14;
+
+    /// `NSWindowStyleMaskTitled` and `NSWindowStyleMaskResizable`
+    const DECORATED_MASK: usize = 1 | (1 << 3);
+
+    /// `NSApplicationPresentationHideDock` and `NSApplicationPresentationHideMenuBar`
+    const HIDE_DOCK_AND_MENU_BAR: usize = (1 << 1) | (1 << 3);
+
+    /// Longest time to wait for the native fullscreen animation
+    const TRANSITION_TIMEOUT_MS: u64 = 2000;
+
+    #[repr(C)]
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct NSRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+
+    /// State of a window before it entered simple fullscreen
+    #[derive(Debug, Copy, Clone)]
+    struct Windowed {
+        frame: NSRect,
+        style_mask: usize,
+        presentation_options: usize,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct Fullscreen {
+        /// Windowed state while in simple fullscreen
+        simple: Option<Windowed>,
+
+        /// Frame of the window before it entered native fullscreen
+        ///
+        /// macOS snaps the frame it restores to the resize increments, which
+        /// can change in fullscreen together with the font size.
+        native: Option<NSRect>,
+
+        /// Start of the native fullscreen animation, and whether it enters
+        transition: Option<(Instant, bool)>,
+    }
+
+    impl Fullscreen {
+        pub unsafe fn toggle_native(&mut self, nswindow: *mut Object) {
+            if let Some(windowed) = self.simple.take() {
+                restore(nswindow, windowed);
+                return;
+            }
+
+            let entering = !is_native(nswindow);
+            if entering {
+                self.native = Some(msg_send![nswindow, frame]);
+            }
+            self.transition = Some((Instant::now(), entering));
+
+            let _: () = msg_send![nswindow, toggleFullScreen: ptr::null_mut::<Object>()];
+        }
+
+        pub unsafe fn toggle_simple(&mut self, nswindow: *mut Object) {
+            if is_native(nswindow) {
+                self.toggle_native(nswindow);
+                return;
+            }
+
+            if let Some(windowed) = self.simple.take() {
+                restore(nswindow, windowed);
+                return;
+            }
+
+            let app = application();
+            let windowed = Windowed {
+                frame: msg_send![nswindow, frame],
+                style_mask: msg_send![nswindow, styleMask],
+                presentation_options: msg_send![app, presentationOptions],
+            };
+
+            let screen: *mut Object = msg_send![nswindow, screen];
+            let screen_frame: NSRect = msg_send![screen, frame];
+
+            let _: () = msg_send![app, setPresentationOptions: HIDE_DOCK_AND_MENU_BAR];
+            let _: () = msg_send![nswindow, setStyleMask: windowed.style_mask & !DECORATED_MASK];
+            let _: () = msg_send![nswindow, setFrame: screen_frame display: YES];
+
+            self.simple = Some(windowed);
+        }
+
+        /// Check whether the window is in native or simple fullscreen
+        pub unsafe fn is_active(&self, nswindow: *mut Object) -> bool {
+            self.simple.is_some() || is_native(nswindow)
+        }
+
+        /// Check whether the native fullscreen animation is still running
+        ///
+        /// Once the window left native fullscreen, its old frame is restored.
+        pub unsafe fn in_transition(&mut self, nswindow: *mut Object) -> bool {
+            let (start, entering) = match self.transition {
+                Some(transition) => transition,
+                None => return false,
+            };
+
+            let timeout = Duration::from_millis(TRANSITION_TIMEOUT_MS);
+            if start.elapsed() < timeout {
+                if is_native(nswindow) != entering {
+                    return true;
+                }
+
+                let screen: *mut Object = msg_send![nswindow, screen];
+                let screen_frame: NSRect = msg_send![screen, frame];
+                let frame: NSRect = msg_send![nswindow, frame];
+                if entering && frame != screen_frame {
+                    return true;
+                }
+            }
+
+            if !entering && !is_native(nswindow) {
+                if let Some(frame) = self.native.take() {
+                    let _: () = msg_send![nswindow, setFrame: frame display: YES];
+                }
+            }
+            self.transition = None;
+
+            false
+        }
+    }
+
+    unsafe fn application() -> *mut Object {
+        let class = Class::get("NSApplication").unwrap();
+        msg_send![class, sharedApplication]
+    }
+
+    unsafe fn is_native(nswindow: *mut Object) -> bool {
+        let style_mask: usize = msg_send![nswindow, styleMask];
+        style_mask & FULLSCREEN_MASK != 0
+    }
+
+    unsafe fn restore(nswindow: *mut Object, windowed: Windowed) {
+        let _: () = msg_send![application(), setPresentationOptions: windowed.presentation_options];
+        let _: () = msg_send![nswindow, setStyleMask: windowed.style_mask];
+        let _: () = msg_send![nswindow, setFrame: windowed.frame display: YES];
+    }
+}
+
 pub trait OsExtensions {
     fn run_os_extensions(&self) {}
 }
@@ -551,6 +1709,9 @@ impl OnResize for Window {
     #[inline]
     fn on_resize(&mut self, size: &SizeInfo) {
         self.window.resize(size.width as u32, size.height as u32);
+
+        // Cell sizes change with the font size and DPI
+        self.set_resize_increments(size);
     }
 }
 
@@ -584,3 +1745,45 @@ impl SetInnerSize<Pixels<u32>> for Window {
             .set_inner_size(*size.width as _, *size.height as _);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{startup_message_chunks, startup_remove_message};
+
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    #[test]
+    fn icons_are_packed_as_argb() {
+        use super::{net_wm_icon, IconImage};
+
+        let icons = [
+            IconImage { rgba: vec![0x11, 0x22, 0x33, 0x44], width: 1, height: 1 },
+            IconImage { rgba: vec![0; 8], width: 2, height: 1 },
+        ];
+        assert_eq!(net_wm_icon(&icons), vec![1, 1, 0x4411_2233, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn startup_ids_are_quoted() {
+        assert_eq!(startup_remove_message("gnome-1_TIME42"), "remove: ID=gnome-1_TIME42");
+        assert_eq!(startup_remove_message(r#"a "b"\c"#), r#"remove: ID="a \"b\"\\c""#);
+    }
+
+    #[test]
+    fn startup_messages_are_null_terminated() {
+        let chunks = startup_message_chunks("remove: ID=0123456789");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[0], b"remove: ID=012345678");
+        assert_eq!(&chunks[1][..2], b"9\0");
+        assert!(chunks[1][2..].iter().all(|&byte| byte == 0));
+
+        // The terminator gets a chunk of its own
+        assert_eq!(startup_message_chunks("remove: ID=012345678").len(), 2);
+    }
+}
@@ -7,10 +7,10 @@ use std::fs::File;
 use std::io::{self, Read};
 use std::path::Path;
 
-use alacritty::Grid;
-use alacritty::Term;
-use alacritty::ansi;
-use alacritty::index::Column;
+use alacritty::{Grid, Rgb};
+use alacritty::headless::Emulator;
+use alacritty::index::{Column, Line, Point, Side};
+use alacritty::selection::Selection;
 use alacritty::term::Cell;
 use alacritty::term::SizeInfo;
 use alacritty::util::fmt::{Red, Green};
@@ -51,6 +51,13 @@ ref_tests! {
     zsh_tab_completion
     history
     grid_reset
+    selection_wide_chars
+    selection_over_cursor
+    colors_bold_dim
+    csi_clear_below
+    csi_clear_above
+    csi_clear_all
+    csi_clear_saved
 }
 
 fn read_u8<P>(path: P) -> Vec<u8>
@@ -77,6 +84,29 @@ struct RefConfig {
     history_size: u32,
 }
 
+/// Selection from the left of `start` to the right of `end`, in display lines
+#[derive(Deserialize)]
+struct RefSelection {
+    start: RefPoint,
+    end: RefPoint,
+}
+
+#[derive(Deserialize)]
+struct RefPoint {
+    line: usize,
+    col: usize,
+}
+
+/// Colors of a cell as it's drawn
+#[derive(Deserialize, Debug, PartialEq)]
+struct RefCell {
+    line: usize,
+    column: usize,
+    c: char,
+    fg: Rgb,
+    bg: Rgb,
+}
+
 fn ref_test(dir: &Path) {
     let recording = read_u8(dir.join("alacritty.recording"));
     let serialized_size = read_string(dir.join("size.json")).unwrap();
@@ -90,21 +120,17 @@ fn ref_test(dir: &Path) {
     let mut config: Config = Default::default();
     config.set_history(ref_config.history_size);
 
-    let mut terminal = Term::new(&config, size);
-    let mut parser = ansi::Processor::new();
-
-    for byte in recording {
-        parser.advance(&mut terminal, byte, &mut io::sink());
-    }
+    let mut emulator = Emulator::with_config(&config, size, io::sink());
+    emulator.advance(&recording);
 
     // Truncate invisible lines from the grid
-    let mut term_grid = terminal.grid().clone();
+    let mut term_grid = emulator.term().grid().clone();
     term_grid.truncate();
 
     if grid != term_grid {
         for i in 0..grid.len() {
             for j in 0..grid.num_cols().0 {
-                let cell = terminal.grid()[i][Column(j)];
+                let cell = emulator.term().grid()[i][Column(j)];
                 let original_cell = grid[i][Column(j)];
                 if original_cell != cell {
                     println!("[{i}][{j}] {original:?} => {now:?}",
@@ -117,4 +143,40 @@ fn ref_test(dir: &Path) {
     }
 
     assert_eq!(grid, term_grid);
+
+    // The renderable cells are only compared by the tests which list them,
+    // the selection and the cells are written by hand
+    let serialized_cells = match read_string(dir.join("cells.json")) {
+        Ok(serialized_cells) => serialized_cells,
+        Err(_) => return,
+    };
+    let cells: Vec<RefCell> = json::from_str(&serialized_cells).unwrap();
+
+    if let Ok(serialized_selection) = read_string(dir.join("selection.json")) {
+        let selection: RefSelection = json::from_str(&serialized_selection).unwrap();
+        let selection = {
+            let grid = emulator.term().grid();
+            let point = |point: RefPoint| {
+                grid.visible_to_buffer(Point { line: Line(point.line), col: Column(point.col) })
+            };
+
+            let mut range = Selection::simple(point(selection.start), Side::Left);
+            range.update(point(selection.end), Side::Right);
+            range
+        };
+        *emulator.term_mut().selection_mut() = Some(selection);
+    }
+
+    let term_cells: Vec<RefCell> = emulator.term()
+        .renderable_cells(&config, true)
+        .map(|cell| RefCell {
+            line: cell.line.0,
+            column: cell.column.0,
+            c: cell.c,
+            fg: cell.fg,
+            bg: cell.bg,
+        })
+        .collect();
+
+    assert_eq!(cells, term_cells, "Ref test failed; renderable cells don't match");
 }
@@ -184,7 +184,12 @@ fn build_ui(app: &gtk::Application) {
 }
 
 fn main() {
-    let _ = alacritty::logging::initialize(&alacritty::cli::Options::default());
+    // The log file is removed again when the guard is dropped after the
+    // application quits
+    let _log_file = alacritty::logging::initialize(
+        &alacritty::cli::Options::default(),
+        &alacritty::config::Config::default(),
+    );
 
     let application = gtk::Application::new(
         "technology.unrelenting.galacritty",
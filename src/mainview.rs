@@ -3,7 +3,6 @@ use std::rc::Rc;
 use std::sync::Arc;
 use std::cell::RefCell;
 use std::thread::JoinHandle;
-use std::os::unix::io::{RawFd, AsRawFd};
 
 use epoxy;
 use shared_library::dynamic_library::DynamicLibrary;
@@ -17,9 +16,9 @@ use gtk::prelude::*;
 use alacritty::{cli, gl};
 use alacritty::display::{Display, DisplayCommand, InitialSize};
 use alacritty::event_loop::{self, EventLoop, WindowNotifier};
-use alacritty::tty::{self, Pty, process_should_exit};
+use alacritty::tty::{self, Pty};
 use alacritty::sync::FairMutex;
-use alacritty::term::{Term, SizeInfo};
+use alacritty::term::{Term, Preedit, SizeInfo};
 use alacritty::config::Config;
 
 // TODO vec for multiple widgets
@@ -34,6 +33,7 @@ pub enum Event {
     CharInput(char, IsControlHeld),
     StringInput(String),
     StrInput(&'static str),
+    PreeditChanged(Option<Preedit>),
     WindowResized(u32, u32),
     HiDPIFactorChanged(f32),
     ChangeFontSize(i8),
@@ -61,7 +61,6 @@ pub struct State {
     pub config: Config,
     pub display: Display,
     terminal: Arc<FairMutex<Term>>,
-    pty_fd: RawFd,
     loop_notifier: event_loop::Notifier,
     io_thread: JoinHandle<(EventLoop<Pty>, event_loop::State)>,
     pub event_queue: Vec<Event>,
@@ -74,7 +73,6 @@ pub fn create_view(window: gtk::ApplicationWindow, header_bar: gtk::HeaderBar) -
     let glarea = gtk::GLArea::new();
 
     let im = gtk::IMMulticontext::new();
-    im.set_use_preedit(false);
 
     let state: Rc<RefCell<Option<State>>> = Rc::new(RefCell::new(None));
 
@@ -107,20 +105,20 @@ pub fn create_view(window: gtk::ApplicationWindow, header_bar: gtk::HeaderBar) -
         let terminal = Arc::new(FairMutex::new(terminal));
 
         let pty = tty::new(&config, &options, &display.size(), None);
-        let pty_fd = pty.fd.as_raw_fd();
 
         let event_loop = EventLoop::new(
             Arc::clone(&terminal),
             Box::new(Notifier),
             pty,
+            &config,
             options.ref_test,
         );
 
-        let loop_notifier = event_loop::Notifier(event_loop.channel());
+        let loop_notifier = event_loop::Notifier(event_loop.channel(), event_loop.queued_bytes());
         let io_thread = event_loop.spawn(None);
 
         *state = Some(State {
-            config, display, terminal, pty_fd,
+            config, display, terminal,
             loop_notifier, io_thread,
             event_queue: Vec::new()
         });
@@ -131,7 +129,7 @@ pub fn create_view(window: gtk::ApplicationWindow, header_bar: gtk::HeaderBar) -
         *state = None;
     }));
 
-    glarea.connect_render(clone!(state, im => move |_glarea, _glctx| {
+    glarea.connect_render(clone!(state, im => move |glarea, _glctx| {
         let state_cell = &state;
         let mut state = state_cell.borrow_mut();
         let mut quit = false;
@@ -165,6 +163,9 @@ pub fn create_view(window: gtk::ApplicationWindow, header_bar: gtk::HeaderBar) -
                         use alacritty::event::Notify;
                         state.loop_notifier.notify(s.as_bytes().to_vec());
                     },
+                    Event::PreeditChanged(preedit) => {
+                        terminal.set_preedit(preedit);
+                    },
                     Event::WindowResized(w, h) => {
                         state.display.command_channel().send(DisplayCommand::NewSize(w, h)).expect("send new size");
                         terminal.dirty = true;
@@ -183,6 +184,7 @@ pub fn create_view(window: gtk::ApplicationWindow, header_bar: gtk::HeaderBar) -
                     }
                 }
             }
+            quit = terminal.should_exit();
             if let Some(title) = terminal.get_next_title() {
                 header_bar.set_title(&*title);
             }
@@ -192,12 +194,12 @@ pub fn create_view(window: gtk::ApplicationWindow, header_bar: gtk::HeaderBar) -
                 im.set_cursor_location(&gtk::Rectangle {
                     x: x.into(), y: y.into(), width: cell_width as i32, height: cell_height as i32
                 });
-                state.display.handle_resize(&mut terminal, &state.config, &mut [&mut state.pty_fd]);
+                state.display.handle_resize(&mut terminal, &state.config, &mut [&mut state.loop_notifier]);
                 drop(terminal); // mutex unlock
-                state.display.draw(&state.terminal, &state.config, true);
-            }
-            if process_should_exit() {
-                quit = true;
+                // A frame skipped while the I/O thread holds the terminal is drawn with the next one
+                if !state.display.draw(&state.terminal, &state.config, true) {
+                    glarea.queue_render();
+                }
             }
         }
         drop(state);
@@ -224,6 +226,20 @@ pub fn create_view(window: gtk::ApplicationWindow, header_bar: gtk::HeaderBar) -
             return Inhibit(true);
         }
         let kv = event.get_keyval();
+        // Escape cancels the composition, if the input method didn't handle it already
+        if kv == gdk::enums::key::Escape {
+            let composing = state.borrow().as_ref()
+                .map(|state| state.terminal.lock().preedit().is_some())
+                .unwrap_or(false);
+            if composing {
+                im.reset();
+                if let Some(ref mut state) = *state.borrow_mut() {
+                    state.event_queue.push(Event::PreeditChanged(None));
+                }
+                glarea.queue_draw();
+                return Inhibit(true);
+            }
+        }
         trace!("non-IM input: keyval {:?} unicode {:?}", kv, gdk::keyval_to_unicode(kv));
         let mut state = state.borrow_mut();
         if let Some(ref mut state) = *state {
@@ -337,6 +353,18 @@ pub fn create_view(window: gtk::ApplicationWindow, header_bar: gtk::HeaderBar) -
         let mut state = state.borrow_mut();
         if let Some(ref mut state) = *state {
             state.event_queue.push(Event::StringInput(s.to_owned()));
+            state.event_queue.push(Event::PreeditChanged(None));
+        }
+        glarea.queue_draw();
+    }));
+
+    im.connect_preedit_changed(clone!(glarea, state => move |im| {
+        let (text, _attrs, cursor) = im.get_preedit_string();
+        trace!("IM preedit: str {:?} cursor {}", text, cursor);
+        let mut state = state.borrow_mut();
+        if let Some(ref mut state) = *state {
+            let preedit = Preedit::new(text.to_string(), cursor.max(0) as usize);
+            state.event_queue.push(Event::PreeditChanged(Some(preedit)));
         }
         glarea.queue_draw();
     }));